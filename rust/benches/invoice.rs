@@ -0,0 +1,153 @@
+// LNP/BP universal invoice library implementing LNPBP-38 standard
+// Written in 2021 by
+//     Dr. Maxim Orlovsky <orlovsky@pandoracore.com>
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the MIT License
+// along with this software.
+// If not, see <https://opensource.org/licenses/MIT>.
+
+
+//! Encoding and decoding of a small (address-only) invoice and a large one
+//! (PSBT beneficiary, 10 alternative beneficiaries and 8 consignment
+//! endpoints), and sorting of 10k invoices. Run with `cargo bench` from this
+//! directory.
+
+use std::str::FromStr;
+
+use bitcoin::secp256k1::{SecretKey, SECP256K1};
+use bitcoin::{Address, Network, PublicKey};
+use criterion::{
+    black_box, criterion_group, criterion_main, BatchSize, Criterion,
+};
+use invoice::{
+    Beneficiary, CanonicalInvoice, ConsignmentEndpoint, Invoice, InvoiceBuilder,
+};
+use lnpbp::bech32::ToBech32String;
+use strict_encoding::{StrictDecode, StrictEncode};
+
+// BIP-174 test vector for a PSBT with one P2PKH input and two outputs
+const PSBT: &str = "cHNidP8BAFUCAAAAASeaIyOl37UfxF8iD6WLD8E+HjNCeSqF1+Ns1jM7XLw5AAAAAAD/////AaBa6gsAAAAAGXapFP/pwAYQl8w7Y28ssEYPpPxCfStFiKwAAAAAAAEBIJVe6gsAAAAAF6kUY0UgD2jRieGtwN8cTRbqjxTA2+uHIgIDsTQcy6doO2r08SOM1ul+cWfVafrEfx5I1HVBhENVvUZGMEMCIAQktY7/qqaU4VWepck7v9SokGQiQFXN8HC2dxRpRC0HAh9cjrD+plFtYLisszrWTt5g6Hhb+zqpS5m9+GFR25qaAQEEIgAgdx/RitRZZm3Unz1WTj28QvTIR3TjYK2haBao7UiNVoEBBUdSIQOxNBzLp2g7avTxI4zW6X5xZ9Vp+sR/HkjUdUGEQ1W9RiED3lXR4drIBeP4pYwfv5uUwC89uq/hJ/78pJlfJvggg71SriIGA7E0HMunaDtq9PEjjNbpfnFn1Wn6xH8eSNR1QYRDVb1GELSmumcAAACAAAAAgAQAAIAiBgPeVdHh2sgF4/iljB+/m5TALz26r+En/vykmV8m+CCDvRC0prpnAAAAgAAAAIAFAACAAAA=";
+
+fn address(no: u8) -> Address {
+    let sk = SecretKey::from_slice(&[no; 32]).unwrap();
+    let pk = PublicKey::new(sk.public_key(SECP256K1));
+    Address::p2wpkh(&pk, Network::Bitcoin).unwrap()
+}
+
+fn small() -> Invoice {
+    Invoice::with_address(address(1), Some(1000))
+}
+
+fn large() -> Invoice {
+    let mut builder =
+        InvoiceBuilder::new(Beneficiary::from_str(PSBT).unwrap())
+            .amount(5000.into());
+    for no in 0..8 {
+        let endpoint = ConsignmentEndpoint::from_str(&format!(
+            "rgbhttpjsonrpc:https://proxy{}.example.com/json-rpc",
+            no
+        ))
+        .unwrap();
+        builder = builder.consignment_endpoint(endpoint);
+    }
+    let mut invoice = builder.finish().unwrap();
+    for no in 2..12 {
+        invoice.add_alt_beneficiary(Beneficiary::Address(address(no)));
+    }
+    invoice
+}
+
+fn encoding(c: &mut Criterion) {
+    for (name, invoice) in [("small", small()), ("large", large())] {
+        let data = invoice.strict_serialize().unwrap();
+        let s = invoice.to_bech32_string();
+
+        let mut group = c.benchmark_group(name);
+        group.bench_function("strict_encode", |b| {
+            b.iter(|| black_box(&invoice).strict_serialize().unwrap())
+        });
+        group.bench_function("strict_decode", |b| {
+            b.iter(|| Invoice::strict_deserialize(black_box(&data)).unwrap())
+        });
+        group.bench_function("to_bech32_string", |b| {
+            b.iter(|| black_box(&invoice).to_bech32_string())
+        });
+        group.bench_function("from_str", |b| {
+            b.iter(|| Invoice::from_str(black_box(&s)).unwrap())
+        });
+        group.bench_function("signature_hash", |b| {
+            b.iter(|| black_box(&invoice).signature_hash())
+        });
+        group.finish();
+    }
+}
+
+fn sorting(c: &mut Criterion) {
+    let invoices = (0..10_000u64)
+        .map(|no| {
+            let mut invoice = Invoice::with_address(
+                address((no % 250) as u8 + 1),
+                Some(no * 7919 % 10_000),
+            );
+            invoice.set_purpose(format!("order #{}", no));
+            invoice
+        })
+        .collect::<Vec<_>>();
+
+    let mut group = c.benchmark_group("sort 10k");
+    group.sample_size(10);
+    // Ordering used before invoices were compared field by field
+    group.bench_function("by_bech32_string", |b| {
+        b.iter_batched(
+            || invoices.clone(),
+            |mut invoices| {
+                invoices.sort_by_key(Invoice::to_bech32_string);
+                invoices
+            },
+            BatchSize::LargeInput,
+        )
+    });
+    group.bench_function("by_canonical_bytes", |b| {
+        b.iter_batched(
+            || invoices.clone(),
+            |mut invoices| {
+                invoices.sort_by_cached_key(Invoice::canonical_bytes);
+                invoices
+            },
+            BatchSize::LargeInput,
+        )
+    });
+    group.bench_function("by_fields", |b| {
+        b.iter_batched(
+            || invoices.clone(),
+            |mut invoices| {
+                invoices.sort();
+                invoices
+            },
+            BatchSize::LargeInput,
+        )
+    });
+    group.bench_function("canonical_invoice", |b| {
+        b.iter_batched(
+            || invoices.clone(),
+            |invoices| {
+                let mut invoices = invoices
+                    .into_iter()
+                    .map(CanonicalInvoice::new)
+                    .collect::<Vec<_>>();
+                invoices.sort();
+                invoices
+            },
+            BatchSize::LargeInput,
+        )
+    });
+    group.finish();
+}
+
+criterion_group!(benches, encoding, sorting);
+criterion_main!(benches);
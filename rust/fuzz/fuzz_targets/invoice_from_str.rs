@@ -0,0 +1,28 @@
+// LNP/BP universal invoice library implementing LNPBP-38 standard
+// Written in 2021 by
+//     Dr. Maxim Orlovsky <orlovsky@pandoracore.com>
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the MIT License
+// along with this software.
+// If not, see <https://opensource.org/licenses/MIT>.
+
+
+#![no_main]
+
+use std::str::FromStr;
+
+use invoice::Invoice;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    if let Ok(s) = std::str::from_utf8(data) {
+        if let Ok(invoice) = Invoice::from_str(s) {
+            assert_eq!(Invoice::from_str(&invoice.to_string()), Ok(invoice));
+        }
+    }
+});
@@ -0,0 +1,26 @@
+// LNP/BP universal invoice library implementing LNPBP-38 standard
+// Written in 2021 by
+//     Dr. Maxim Orlovsky <orlovsky@pandoracore.com>
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the MIT License
+// along with this software.
+// If not, see <https://opensource.org/licenses/MIT>.
+
+
+#![no_main]
+
+use invoice::Invoice;
+use libfuzzer_sys::fuzz_target;
+use strict_encoding::{StrictDecode, StrictEncode};
+
+fuzz_target!(|data: &[u8]| {
+    if let Ok(invoice) = Invoice::strict_deserialize(data) {
+        let encoded = invoice.strict_serialize().expect("encodable invoice");
+        assert_eq!(Invoice::strict_deserialize(&encoded).ok(), Some(invoice));
+    }
+});
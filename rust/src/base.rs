@@ -12,33 +12,51 @@
 // If not, see <https://opensource.org/licenses/MIT>.
 
 use amplify::Slice32;
-use chrono::NaiveDateTime;
+use chrono::{DateTime, Datelike, Months, NaiveDateTime, Timelike, Utc};
 #[cfg(feature = "serde")]
 use serde_with::{As, DisplayFromStr};
 use std::cmp::Ordering;
-use std::fmt::{self, Display, Formatter, Write};
+use std::collections::BTreeMap;
+use std::convert::TryFrom;
+use std::fmt::{self, Display, Formatter};
 use std::io;
+use std::iter::FusedIterator;
+use std::ops::{BitAnd, BitOr, BitOrAssign};
 use std::str::FromStr;
 
-#[cfg(feature = "rgb")]
+use amplify::flags::FlagVec;
 use amplify::Wrapper;
+use bitcoin::hashes::hex::{FromHex, ToHex};
 #[cfg(feature = "rgb")]
 use bitcoin::hashes::sha256t;
 use bitcoin::hashes::{sha256d, Hash};
+use bitcoin::psbt::PartiallySignedTransaction as PsbtV0;
+use bitcoin::psbt::{self, TapTree};
 use bitcoin::secp256k1::{self, schnorr};
-use bitcoin::Address;
-use bitcoin_scripts::hlc::HashLock;
+use bitcoin::{Address, BlockHash};
+use bitcoin_scripts::hlc::{HashLock, HashPreimage};
+use bitcoin_scripts::PubkeyScript;
 use bp::seals::txout::blind::ConcealedSeal;
-use commit_verify::merkle::MerkleNode;
+use commit_verify::merkle::{merklize, MerkleNode};
 use internet2::addr::{NodeAddr, NodeId};
 use internet2::tlv;
-use lnp::p2p::bolt::{InitFeatures, ShortChannelId};
+use lnp::p2p::bifrost::ChannelId;
+use lnp::p2p::bolt::{Feature, InitFeatures, ShortChannelId};
 use lnpbp::bech32::{self, Blob, FromBech32Str, ToBech32String};
 use lnpbp::chain::{AssetId, Chain};
-use miniscript::{descriptor::DescriptorPublicKey, Descriptor};
+use miniscript::{descriptor::DescriptorPublicKey, Descriptor, ForEachKey};
 use strict_encoding::{StrictDecode, StrictEncode};
 use wallet::psbt::Psbt;
 
+use crate::bip21::{Bip21Error, BIP21_SCHEME};
+use crate::zip;
+use crate::{
+    AddressProof, Iso4217, Iso4217Error, PaymentCode, SilentPaymentCode,
+    TrackingBeneficiary, WitnessVoutSeal,
+};
+#[cfg(feature = "elements")]
+use crate::{LiquidAddress, LiquidNetwork};
+
 /// Error when an RGB-only operation is attempted on a non-RGB invoice.
 #[derive(
     Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Hash, Debug, Display, Error,
@@ -46,8 +64,309 @@ use wallet::psbt::Psbt;
 #[display("the operation is supported only for RGB invoices")]
 pub struct NotRgbInvoice;
 
+/// Error when an RGB invoice is constructed without consignment endpoints,
+/// which makes it unpayable.
+#[derive(
+    Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Hash, Debug, Display, Error,
+)]
+#[display("RGB invoice requires at least one consignment endpoint")]
+pub struct NoConsignmentEndpoint;
+
+/// Inconsistencies between invoice fields detected by [`Invoice::validate`].
+#[derive(Clone, Eq, PartialEq, Debug, Display, Error, From)]
+#[display(doc_comments)]
+pub enum InvoiceInconsistency {
+    /// beneficiary address {0} belongs to a different network than the one
+    /// specified by the invoice
+    AddressNetwork(Address),
+
+    /// invoice asset is a native asset of a different network than the one
+    /// specified by the invoice
+    NativeAssetNetwork,
+
+    /// beneficiary descriptor uses extended keys of a different network than
+    /// the one specified by the invoice
+    DescriptorNetwork,
+
+    /// invoice expires at {expiry}, before it was issued at {issued_at}
+    ExpiryBeforeIssue {
+        expiry: DateTime<Utc>,
+        issued_at: DateTime<Utc>,
+    },
+
+    /// {0}
+    #[from]
+    Expiry(ExpiryError),
+
+    /// {0}
+    #[from]
+    Quantity(QuantityError),
+
+    /// invoice specifies quantity of items but accepts any amount
+    QuantityWithoutAmount,
+
+    /// RGB invoice has no consignment endpoints, so the payer can't deliver
+    /// the transfer consignment
+    NoConsignmentEndpoint,
+
+    /// invoice is issued for a network unknown to this software version
+    UnknownNetwork(u8),
+
+    /// invoice specifies RGB contract interface but does not request payment
+    /// in RGB assets
+    IfaceWithoutRgbAsset,
+
+    /// {0}
+    #[from]
+    Reference(ReferenceError),
+
+    /// {0}
+    #[from]
+    OnchainPolicy(OnchainPolicyError),
+
+    /// {0}
+    #[from]
+    ConsignmentEndpoints(ConsignmentEndpointsError),
+
+    /// beneficiary Liquid address {0} belongs to a different network than
+    /// the one specified by the invoice
+    #[cfg(feature = "elements")]
+    LiquidAddressNetwork(LiquidAddress),
+
+    /// invoice mixes confidential and explicit Liquid beneficiary addresses,
+    /// so paying to an explicit one reveals amount and asset of the payment
+    #[cfg(feature = "elements")]
+    MixedLiquidConfidentiality,
+}
+
+/// Errors happening during invoice signature verification.
+#[derive(
+    Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Hash, Debug, Display, Error,
+)]
+#[display(doc_comments)]
+pub enum SignatureError {
+    /// invoice is not signed
+    NoSignature,
+
+    /// invoice signature is not valid for the invoice data and public key
+    InvalidSignature,
+
+    /// invoice data are inconsistent for strict serialization
+    InconsistentSerialization,
+
+    /// invoice is signed with a key different from the merchant identity key
+    MerchantKeyMismatch,
+}
+
+/// Errors happening during invoice signing.
+#[derive(
+    Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Hash, Debug, Display, Error,
+)]
+#[display(doc_comments)]
+pub enum SigningError {
+    /// invoice data are inconsistent for strict serialization
+    InconsistentSerialization,
+
+    /// invoice issuer must sign with `Invoice::sign` instead of co-signing
+    IssuerCosignature,
+}
+
+/// TLV type of the invoice co-signatures record
+pub const COSIGNATURES_TLV_TYPE: u64 = 0x17;
+
+/// TLV type of the issuer signature scheme record
+pub const SIGNATURE_SCHEME_TLV_TYPE: u64 = 0x25;
+
+/// TLV types of the invoice fields known to this library
+pub(crate) const KNOWN_TLV_TYPES: [u64; 27] = [
+    0x00, 0x01, 0x02, 0x03, 0x04, 0x05, 0x06, 0x07, 0x08, 0x09, 0x0a, 0x0b,
+    0x0c, 0x0d, 0x0e, 0x0f, 0x11, 0x12, 0x13, 0x15, 0x17, 0x19, 0x1b, 0x1d,
+    0x1f, 0x21, 0x25,
+];
+
+/// Role of the party signing the invoice.
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    serde(crate = "serde_crate", rename_all = "camelCase")
+)]
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Hash, Debug, Display)]
+pub enum SignerRole {
+    /// Merchant issuing the invoice; its signature is kept in the legacy
+    /// signature record
+    #[display("issuer")]
+    Issuer,
+
+    /// Marketplace or payment platform acting on behalf of the merchant
+    #[display("platform")]
+    Platform,
+
+    /// Payer acknowledging the invoice terms
+    #[display("payer")]
+    Payer,
+
+    #[display("other:{0}")]
+    Other(u8),
+}
+
+impl StrictEncode for SignerRole {
+    fn strict_encode<E: io::Write>(
+        &self,
+        mut e: E,
+    ) -> Result<usize, strict_encoding::Error> {
+        Ok(match self {
+            SignerRole::Issuer => 0u8.strict_encode(&mut e)?,
+            SignerRole::Platform => 1u8.strict_encode(&mut e)?,
+            SignerRole::Payer => 2u8.strict_encode(&mut e)?,
+            SignerRole::Other(role) => strict_encode_list!(e; 0xFFu8, role),
+        })
+    }
+}
+
+impl StrictDecode for SignerRole {
+    fn strict_decode<D: io::Read>(
+        mut d: D,
+    ) -> Result<Self, strict_encoding::Error> {
+        Ok(match u8::strict_decode(&mut d)? {
+            0 => SignerRole::Issuer,
+            1 => SignerRole::Platform,
+            2 => SignerRole::Payer,
+            0xFF => SignerRole::Other(u8::strict_decode(&mut d)?),
+            tag => {
+                return Err(strict_encoding::Error::EnumValueNotKnown(
+                    "SignerRole",
+                    tag as usize,
+                ))
+            }
+        })
+    }
+}
+
+/// Signature of a party other than the invoice issuer.
+///
+/// Each co-signer commits to everything signed before it with a merkle root
+/// (tagged with [`INVOICE_MERKLE_PREFIX`]) over the following leaves:
+/// - [`Invoice::signature_leaves`], with the issuer signature and signature
+///   scheme TLV record leaves (if present) included in the order of TLV
+///   types;
+/// - the preceding co-signatures, each leaf being a single SHA256 hash of
+///   the co-signature strict encoding;
+/// - the co-signer role, as a single SHA256 hash of its strict encoding.
+///
+/// Co-signatures are kept in [`COSIGNATURES_TLV_TYPE`] record, which is not
+/// committed to by the issuer signature.
+#[cfg_attr(
+    feature = "serde",
+    serde_as,
+    derive(Serialize, Deserialize),
+    serde(crate = "serde_crate", rename_all = "camelCase")
+)]
+#[derive(
+    Copy,
+    Clone,
+    Ord,
+    PartialOrd,
+    Eq,
+    PartialEq,
+    Hash,
+    Debug,
+    StrictEncode,
+    StrictDecode,
+)]
+pub struct Cosignature {
+    pub role: SignerRole,
+    #[cfg_attr(feature = "serde", serde(with = "As::<DisplayFromStr>"))]
+    pub pubkey: secp256k1::PublicKey,
+    #[cfg_attr(feature = "serde", serde(with = "As::<DisplayFromStr>"))]
+    pub signature: schnorr::Signature,
+}
+
+/// Tag prefix used in merklization of the invoice data for the signature
+/// (see [`SignatureScheme::Merkle`])
+pub const INVOICE_MERKLE_PREFIX: &str = "LNPBP38:invoice";
+
+/// Schemes for computing the hash committed to by the invoice signature.
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    serde(crate = "serde_crate", rename_all = "camelCase")
+)]
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Hash, Debug, Display)]
+pub enum SignatureScheme {
+    /// Legacy scheme: single SHA256 hash of the strict serialization of the
+    /// invoice with signature TLV records omitted.
+    #[display("flat")]
+    Flat,
+
+    /// LNPBP-81 merkle tree root (tagged with [`INVOICE_MERKLE_PREFIX`]) over
+    /// invoice fields and TLV records (see [`Invoice::signature_leaves`]),
+    /// allowing selective disclosure of the invoice fields.
+    #[display("merkle")]
+    Merkle,
+}
+
+impl StrictEncode for SignatureScheme {
+    fn strict_encode<E: io::Write>(
+        &self,
+        e: E,
+    ) -> Result<usize, strict_encoding::Error> {
+        match self {
+            SignatureScheme::Flat => 0u8.strict_encode(e),
+            SignatureScheme::Merkle => 1u8.strict_encode(e),
+        }
+    }
+}
+
+impl StrictDecode for SignatureScheme {
+    fn strict_decode<D: io::Read>(
+        d: D,
+    ) -> Result<Self, strict_encoding::Error> {
+        Ok(match u8::strict_decode(d)? {
+            0 => SignatureScheme::Flat,
+            1 => SignatureScheme::Merkle,
+            tag => {
+                return Err(strict_encoding::Error::EnumValueNotKnown(
+                    "SignatureScheme",
+                    tag as usize,
+                ))
+            }
+        })
+    }
+}
+
+/// Issuer signature of the invoice together with the scheme of the signed
+/// hash, which is the only scheme used for the signature verification.
+///
+/// The public key and the signature are kept in the signature TLV record
+/// (`0x00`), which has the same encoding as before the introduction of
+/// merkle signatures. The scheme is kept in a separate odd TLV record
+/// ([`SIGNATURE_SCHEME_TLV_TYPE`]); signatures without it use
+/// [`SignatureScheme::Flat`].
+#[cfg_attr(
+    feature = "serde",
+    serde_as,
+    derive(Serialize, Deserialize),
+    serde(crate = "serde_crate", rename_all = "camelCase")
+)]
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Hash, Debug)]
+pub struct InvoiceSignature {
+    pub scheme: SignatureScheme,
+    #[cfg_attr(feature = "serde", serde(with = "As::<DisplayFromStr>"))]
+    pub pubkey: secp256k1::PublicKey,
+    #[cfg_attr(feature = "serde", serde(with = "As::<DisplayFromStr>"))]
+    pub signature: schnorr::Signature,
+}
+
 /// NB: Invoice fields are non-public since each time we update them we must
 /// clear signature
+///
+/// Invoice TLV records follow "it's OK to be odd" rule: decoders ignore
+/// unknown odd records and reject invoices with unknown even records. New
+/// fields must use odd TLV types, unless software not aware of the field
+/// would process the invoice incorrectly.
+///
+/// Invoice is displayed as a bech32 string; the alternate format (`{:#}`)
+/// produces a short multi-line summary of the invoice for humans.
 #[cfg_attr(
     feature = "serde",
     serde_as,
@@ -55,12 +374,12 @@ pub struct NotRgbInvoice;
     serde(crate = "serde_crate", rename_all = "camelCase")
 )]
 #[derive(
-    Getters, Clone, Eq, PartialEq, Debug, Display, NetworkEncode, NetworkDecode,
+    Getters, Clone, Eq, PartialEq, Debug, NetworkEncode, NetworkDecode,
 )]
 #[network_encoding(use_tlv)]
-#[display(Invoice::to_bech32_string)]
 pub struct Invoice {
-    /// Version byte, always 0 for the initial version
+    /// Version byte, always [`Invoice::VERSION`] for the invoices supported
+    /// by this library
     version: u8,
 
     /// Amount in the specified asset - a price per single item, if `quantity`
@@ -76,7 +395,7 @@ pub struct Invoice {
     /// List of beneficiary ordered in most desirable-first order, which follow
     /// `beneficiary` value
     #[network_encoding(tlv = 0x01)]
-    alt_beneficiaries: Vec<Beneficiary>,
+    pub(crate) alt_beneficiaries: Vec<Beneficiary>,
 
     /// AssetId can also be used to define blockchain. If it's empty it implies
     /// bitcoin mainnet
@@ -85,70 +404,459 @@ pub struct Invoice {
         feature = "serde",
         serde(with = "As::<Option<DisplayFromStr>>")
     )]
-    asset: Option<AssetId>,
+    pub(crate) asset: Option<AssetId>,
 
+    /// Expiry time, encoded as UNIX timestamp (in seconds) and represented
+    /// in RFC 3339 format by serde
     #[network_encoding(tlv = 0x03)]
-    #[cfg_attr(
-        feature = "serde",
-        serde(with = "As::<Option<DisplayFromStr>>")
-    )]
-    expiry: Option<NaiveDateTime>, // Must be mapped to i64
+    expiry: Option<DateTime<Utc>>,
 
     /// Interval between recurrent payments
     #[network_encoding(tlv = 0x04)]
     recurrent: Recurrent,
 
+    /// Maximal number of recurrent payments
+    #[network_encoding(tlv = 0x1f)]
+    recurrence_limit: Option<u32>,
+
     #[network_encoding(tlv = 0x06)]
     quantity: Option<Quantity>,
 
-    /// If the price of the asset provided by fiat provider URL goes below this
-    /// limit the merchant will not accept the payment and it will become
-    /// expired
+    /// Sole currency requirement, kept in the record used before multiple
+    /// requirements were supported; see `currency_requirements`
     #[network_encoding(tlv = 0x08)]
-    currency_requirement: Option<CurrencyData>,
+    #[getter(skip)]
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Option::is_none")
+    )]
+    currency_requirement: Option<LegacyCurrencyData>,
+
+    /// If the price of the asset provided by fiat provider URL goes below
+    /// any of these limits the merchant will not accept the payment and it
+    /// will become expired. Used only for two or more requirements, while a
+    /// single one is kept in `currency_requirement`
+    #[network_encoding(tlv = 0x0e)]
+    #[getter(skip)]
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "CurrencyRequirements::is_empty")
+    )]
+    currency_requirements: CurrencyRequirements,
 
+    /// Legacy plain merchant name; see `merchant_info` for the structured
+    /// merchant identity
     #[network_encoding(tlv = 0x05)]
     merchant: Option<String>,
 
+    #[network_encoding(tlv = 0x21)]
+    merchant_info: Option<MerchantInfo>,
+
     #[network_encoding(tlv = 0x07)]
     purpose: Option<String>,
 
     #[network_encoding(tlv = 0x09)]
     details: Option<Details>,
 
+    /// Document embedded into the invoice
+    #[network_encoding(tlv = 0x0f)]
+    inline_details: Option<InlineDetails>,
+
     #[network_encoding(tlv = 0x00)]
+    #[getter(skip)]
     #[cfg_attr(
         feature = "serde",
         serde(with = "As::<Option<(DisplayFromStr, DisplayFromStr)>>")
     )]
     signature: Option<(secp256k1::PublicKey, schnorr::Signature)>,
 
+    /// Scheme of the issuer signature; absent for the flat signatures
+    /// produced before the introduction of merkle signatures
+    #[network_encoding(tlv = 0x25)]
+    #[getter(skip)]
+    signature_scheme: Option<SignatureScheme>,
+
     /// List of nodes which are able to accept RGB consignment
     #[network_encoding(tlv = 0x0a)]
-    consignment_endpoints: Vec<ConsignmentEndpoint>,
+    #[getter(skip)]
+    pub(crate) consignment_endpoints: ConsignmentEndpoints,
+
+    /// Network the invoice is issued for
+    #[network_encoding(tlv = 0x0b)]
+    network: Option<Network>,
+
+    /// Time of the invoice issuance
+    #[network_encoding(tlv = 0x0d)]
+    issued_at: Option<DateTime<Utc>>,
+
+    /// Expiry as a number of seconds since `issued_at`; mutually exclusive
+    /// with `expiry`. Uses even TLV type, so software not supporting relative
+    /// expiry fails to decode such invoices instead of treating them as
+    /// never expiring
+    #[network_encoding(tlv = 0x0c)]
+    relative_expiry: Option<u32>,
+
+    /// Shares of the payment received by each of the alternative
+    /// beneficiaries; if present, alternative beneficiaries are parts of a
+    /// split payment and not alternatives to the main beneficiary. Uses even
+    /// TLV type, so software not supporting split payments fails to decode
+    /// such invoices instead of treating them as alternatives
+    #[network_encoding(tlv = 0x12)]
+    split_shares: Vec<SplitShare>,
+
+    /// Display metadata of the invoiced asset asserted by the issuer; see
+    /// [`AssetMetadata`]
+    #[network_encoding(tlv = 0x11)]
+    asset_metadata: Option<AssetMetadata>,
+
+    /// RGB contract interface expected by the invoice
+    #[network_encoding(tlv = 0x13)]
+    #[getter(skip)]
+    iface: Option<Iface>,
 
+    /// Beneficiary supplied by the payer for receiving refunds
+    #[network_encoding(tlv = 0x15)]
+    refund: Option<Beneficiary>,
+
+    /// Signatures of the parties other than the issuer, in the signing order
+    #[network_encoding(tlv = 0x17)]
+    #[getter(skip)]
+    cosignatures: Vec<Cosignature>,
+
+    /// BIP-322 proof of control over the main beneficiary address; see
+    /// [`AddressProof`]
+    #[network_encoding(tlv = 0x19)]
+    beneficiary_proof: Option<AddressProof>,
+
+    /// Requirements to on-chain payment transactions and the number of
+    /// confirmations the merchant waits for
+    #[network_encoding(tlv = 0x1b)]
+    onchain_policy: Option<OnchainPolicy>,
+
+    /// Opaque order or payment identifier used by the merchant for payment
+    /// reconciliation, up to [`MAX_REFERENCE_LEN`] bytes
+    #[network_encoding(tlv = 0x1d)]
+    reference: Option<Blob>,
+
+    /// Unknown TLV records, including custom extensions
     #[network_encoding(unknown_tlvs)]
-    #[cfg_attr(feature = "serde", serde(skip))]
-    unknown: tlv::Stream,
+    #[cfg_attr(
+        feature = "serde",
+        serde(
+            rename = "extensions",
+            default,
+            skip_serializing_if = "tlv::Stream::is_empty",
+            with = "As::<crate::extension::TlvExtensions>"
+        )
+    )]
+    pub(crate) unknown: tlv::Stream,
+}
+
+/// Owned field values of an invoice, produced by [`Invoice::into_parts`].
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct InvoiceParts {
+    pub amount: AmountExt,
+    pub beneficiary: Beneficiary,
+    pub alt_beneficiaries: Vec<Beneficiary>,
+    pub split_shares: Vec<SplitShare>,
+    pub asset: Option<AssetId>,
+    pub asset_metadata: Option<AssetMetadata>,
+    pub iface: Option<Iface>,
+    pub refund: Option<Beneficiary>,
+    pub onchain_policy: Option<OnchainPolicy>,
+    pub expiry: Option<DateTime<Utc>>,
+    pub issued_at: Option<DateTime<Utc>>,
+    pub relative_expiry: Option<u32>,
+    pub recurrent: Recurrent,
+    pub recurrence_limit: Option<u32>,
+    pub quantity: Option<Quantity>,
+    pub currency_requirements: Vec<CurrencyData>,
+    pub merchant: Option<String>,
+    pub merchant_info: Option<MerchantInfo>,
+    pub purpose: Option<String>,
+    pub reference: Option<Blob>,
+    pub details: Option<Details>,
+    pub inline_details: Option<InlineDetails>,
+    pub consignment_endpoints: Vec<ConsignmentEndpoint>,
+    pub network: Option<Network>,
+}
+
+impl ToBech32String for Invoice {
+    fn to_bech32_string(&self) -> String {
+        zip::to_bech32_zip_string(<Self as FromBech32Str>::HRP, |data| {
+            self.strict_encode(data)
+                .expect("in-memory strict encoding failure");
+        })
+    }
 }
 
-impl bech32::Strategy for Invoice {
+impl FromBech32Str for Invoice {
     const HRP: &'static str = "i";
 
-    type Strategy = bech32::strategies::CompressedStrictEncoding;
+    fn from_bech32_str(s: &str) -> Result<Self, bech32::Error> {
+        let data = zip::bech32_zip_str_to_payload(
+            Self::HRP,
+            s,
+            DecodePolicy::default().max_payload_len,
+        )?;
+        Ok(Invoice::strict_deserialize(data)?)
+    }
+}
+
+/// Invoice decoded with [`DecodePolicy::accept_unknown_required`] option,
+/// keeping unknown even TLV records (including not yet assigned types from
+/// the core range) in the `unknown` stream instead of failing.
+struct LenientInvoice(Invoice);
+
+impl StrictDecode for LenientInvoice {
+    fn strict_decode<D: io::Read>(
+        mut d: D,
+    ) -> Result<Self, strict_encoding::Error> {
+        let mut data = vec![];
+        d.read_to_end(&mut data)?;
+
+        let mut cursor = io::Cursor::new(&data);
+        u8::strict_decode(&mut cursor)?;
+        AmountExt::strict_decode(&mut cursor)?;
+        Beneficiary::strict_decode(&mut cursor)?;
+        let fields_len = cursor.position() as usize;
+        let tlvs = tlv::Stream::strict_decode(&mut cursor)?;
+        if cursor.position() as usize != data.len() {
+            return Err(strict_encoding::Error::DataNotEntirelyConsumed);
+        }
+
+        let (required, known): (BTreeMap<_, _>, BTreeMap<_, _>) =
+            tlvs.into_iter().partition(|(type_no, _)| {
+                type_no.is_multiple_of(2)
+                    && !KNOWN_TLV_TYPES.contains(&**type_no)
+            });
+        let mut payload = data[..fields_len].to_vec();
+        tlv::Stream::from(known).strict_encode(&mut payload)?;
+
+        let mut invoice = Invoice::strict_deserialize(payload)?;
+        for (type_no, value) in required {
+            invoice.unknown.insert(type_no, value);
+        }
+        Ok(LenientInvoice(invoice))
+    }
+}
+
+/// Invoice data decoded without knowing their version in advance.
+#[derive(Clone, Eq, PartialEq, Debug)]
+#[allow(clippy::large_enum_variant)]
+pub enum VersionedInvoice {
+    /// Invoice of [`Invoice::VERSION`], supported by this library
+    Supported(Invoice),
+
+    /// Invoice of a version unknown to this library
+    Unsupported {
+        version: u8,
+        /// Invoice data following the version byte
+        payload: Vec<u8>,
+    },
+}
+
+impl VersionedInvoice {
+    /// Returns version of the invoice.
+    pub fn version(&self) -> u8 {
+        match self {
+            VersionedInvoice::Supported(invoice) => invoice.version,
+            VersionedInvoice::Unsupported { version, .. } => *version,
+        }
+    }
+}
+
+/// URI schemes which may prefix bech32 invoice string. `lightning:` is used
+/// by wallets for QR codes of lightning payment requests.
+pub const INVOICE_URI_SCHEMES: [&str; 3] = ["lnpbp:", "i:", "lightning:"];
+
+/// Errors parsing invoice from a bech32 string.
+#[derive(Clone, PartialEq, Eq, Debug, Display, Error, From)]
+#[display(doc_comments)]
+pub enum InvoiceParseError {
+    /// the string is not an invoice: it has `{found}` prefix instead of `i`
+    WrongHrp { found: String },
+
+    /// invoice string is corrupted or mistyped: checksum does not match
+    Checksum,
+
+    /// invoice string is malformed: {0}
+    Bech32(::bech32::Error),
+
+    /// invoice data are corrupted and can't be decompressed
+    Decompression,
+
+    /// invoice data are invalid: {0}
+    #[from]
+    Encoding(strict_encoding::Error),
+
+    /// invoice has version {0}, which is not supported by this software
+    UnsupportedVersion(u8),
+
+    /// invalid BIP-21 URI: {0}
+    Bip21(Bip21Error),
+}
+
+impl InvoiceParseError {
+    /// Classifies error of decoding bech32 string `s`.
+    fn with(err: bech32::Error, s: &str) -> InvoiceParseError {
+        match err {
+            bech32::Error::WrongPrefix => InvoiceParseError::WrongHrp {
+                found: s
+                    .rsplit_once('1')
+                    .map(|(hrp, _)| hrp)
+                    .unwrap_or_default()
+                    .to_owned(),
+            },
+            bech32::Error::Bech32Error(::bech32::Error::InvalidChecksum)
+            | bech32::Error::WrongVariant => InvoiceParseError::Checksum,
+            bech32::Error::Bech32Error(err) => InvoiceParseError::Bech32(err),
+            bech32::Error::NotStrictEncoded(err) => {
+                InvoiceParseError::Encoding(err)
+            }
+            bech32::Error::NotBitcoinHash(err) => InvoiceParseError::Encoding(
+                strict_encoding::Error::DataIntegrityError(err.to_string()),
+            ),
+            bech32::Error::NoEncodingPrefix
+            | bech32::Error::UnknownRawDataEncoding(_)
+            | bech32::Error::DeflateEncoding
+            | bech32::Error::InflateError(_) => {
+                InvoiceParseError::Decompression
+            }
+        }
+    }
 }
 
 impl FromStr for Invoice {
-    type Err = bech32::Error;
+    type Err = InvoiceParseError;
 
+    /// Parses bech32 invoice string, which may be prefixed with one of
+    /// [`INVOICE_URI_SCHEMES`] and may be either all-lowercase or
+    /// all-uppercase (as used in QR codes). Mixed case is rejected per
+    /// BIP-173. BIP-21 `bitcoin:` URIs are parsed with
+    /// [`Invoice::from_bip21`].
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        Invoice::from_bech32_str(s)
+        if s.split_once(':').is_some_and(|(scheme, _)| {
+            scheme.eq_ignore_ascii_case(BIP21_SCHEME)
+        }) {
+            return Invoice::from_bip21(s).map_err(InvoiceParseError::Bip21);
+        }
+        let s = INVOICE_URI_SCHEMES
+            .iter()
+            .find_map(|scheme| {
+                s.get(..scheme.len())
+                    .filter(|prefix| prefix.eq_ignore_ascii_case(scheme))
+                    .map(|_| &s[scheme.len()..])
+            })
+            .unwrap_or(s);
+        let has_lower = s.bytes().any(|c| c.is_ascii_lowercase());
+        let has_upper = s.bytes().any(|c| c.is_ascii_uppercase());
+        if has_lower && has_upper {
+            return Err(InvoiceParseError::Bech32(::bech32::Error::MixedCase));
+        }
+        Invoice::parse_with_policy(
+            &s.to_ascii_lowercase(),
+            &DecodePolicy::default(),
+        )
+    }
+}
+
+/// Error reported when a decoded invoice exceeds one of the
+/// [`DecodePolicy`] limits.
+#[derive(
+    Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Hash, Debug, Display, Error,
+)]
+#[display("invoice {field} has size {count}, exceeding the limit of {limit}")]
+pub struct DecodeLimit {
+    /// Name of the field exceeding the limit
+    pub field: &'static str,
+    /// Number of items (or bytes for strings) in the field
+    pub count: usize,
+    pub limit: usize,
+}
+
+/// Limits on the size of decoded invoice data, protecting wallets from
+/// resource exhaustion by maliciously crafted invoices.
+///
+/// The default limits are expected to be sufficient for any legitimate
+/// invoice; other limits may be used with [`Invoice::parse_with_policy`].
+/// The payload limit is enforced while decompressing the invoice string,
+/// before any of the invoice data are decoded.
+/// Invoices with more than [`MAX_CONSIGNMENT_ENDPOINTS`] endpoints are
+/// always rejected on decoding.
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug)]
+pub struct DecodePolicy {
+    /// Maximal size in bytes of the decompressed invoice data
+    pub max_payload_len: usize,
+    pub max_alt_beneficiaries: usize,
+    pub max_consignment_endpoints: usize,
+    /// Maximal number of route hint hops in each lightning beneficiary
+    pub max_path_hints: usize,
+    /// Maximal length in bytes of merchant name, purpose and details source
+    pub max_string_len: usize,
+    /// Accept invoices with unknown even TLV records, which signal fields
+    /// required to process the invoice correctly. Such invoices must not be
+    /// paid; see [`Invoice::has_unknown_required_fields`]
+    pub accept_unknown_required: bool,
+}
+
+impl Default for DecodePolicy {
+    fn default() -> Self {
+        DecodePolicy {
+            max_payload_len: 0x20000,
+            max_alt_beneficiaries: 32,
+            max_consignment_endpoints: MAX_CONSIGNMENT_ENDPOINTS,
+            max_path_hints: 20,
+            max_string_len: 1024,
+            accept_unknown_required: false,
+        }
     }
 }
 
+/// Invoices are ordered field by field, in the order of their canonical TLV
+/// encoding, consistently with the invoice equality. The ordering differs
+/// both from the ordering of bech32 strings and of canonical bytes; use
+/// [`CanonicalInvoice`] for ordering by the canonical bytes.
 impl Ord for Invoice {
     fn cmp(&self, other: &Self) -> Ordering {
-        self.to_string().cmp(&other.to_string())
+        self.version
+            .cmp(&other.version)
+            .then_with(|| self.amount.cmp(&other.amount))
+            .then_with(|| self.beneficiary.cmp(&other.beneficiary))
+            .then_with(|| self.signature.cmp(&other.signature))
+            .then_with(|| self.alt_beneficiaries.cmp(&other.alt_beneficiaries))
+            .then_with(|| self.asset.cmp(&other.asset))
+            .then_with(|| self.expiry.cmp(&other.expiry))
+            .then_with(|| self.recurrent.cmp(&other.recurrent))
+            .then_with(|| self.merchant.cmp(&other.merchant))
+            .then_with(|| self.quantity.cmp(&other.quantity))
+            .then_with(|| self.purpose.cmp(&other.purpose))
+            .then_with(|| {
+                self.currency_requirement.cmp(&other.currency_requirement)
+            })
+            .then_with(|| self.details.cmp(&other.details))
+            .then_with(|| {
+                self.consignment_endpoints.cmp(&other.consignment_endpoints)
+            })
+            .then_with(|| self.network.cmp(&other.network))
+            .then_with(|| self.relative_expiry.cmp(&other.relative_expiry))
+            .then_with(|| self.issued_at.cmp(&other.issued_at))
+            .then_with(|| {
+                self.currency_requirements.cmp(&other.currency_requirements)
+            })
+            .then_with(|| self.inline_details.cmp(&other.inline_details))
+            .then_with(|| self.asset_metadata.cmp(&other.asset_metadata))
+            .then_with(|| self.split_shares.cmp(&other.split_shares))
+            .then_with(|| self.iface.cmp(&other.iface))
+            .then_with(|| self.refund.cmp(&other.refund))
+            .then_with(|| self.cosignatures.cmp(&other.cosignatures))
+            .then_with(|| self.beneficiary_proof.cmp(&other.beneficiary_proof))
+            .then_with(|| self.onchain_policy.cmp(&other.onchain_policy))
+            .then_with(|| self.reference.cmp(&other.reference))
+            .then_with(|| self.recurrence_limit.cmp(&other.recurrence_limit))
+            .then_with(|| self.merchant_info.cmp(&other.merchant_info))
+            .then_with(|| self.signature_scheme.cmp(&other.signature_scheme))
+            .then_with(|| self.unknown.cmp(&other.unknown))
     }
 }
 
@@ -158,83 +866,297 @@ impl PartialOrd for Invoice {
     }
 }
 
-impl Invoice {
-    pub fn new(
-        beneficiary: Beneficiary,
-        amount: Option<u64>,
-        asset: Option<AssetId>,
-    ) -> Invoice {
-        Invoice {
-            version: 0,
-            amount: amount
-                .map(|value| AmountExt::Normal(value))
-                .unwrap_or(AmountExt::Any),
-            beneficiary,
-            alt_beneficiaries: vec![],
-            asset,
-            recurrent: Default::default(),
-            expiry: None,
-            quantity: None,
-            currency_requirement: None,
-            merchant: None,
-            purpose: None,
-            details: None,
-            signature: None,
-            consignment_endpoints: empty!(),
-            unknown: Default::default(),
-        }
+/// Invoice together with its cached canonical serialization, which makes
+/// comparison and hashing cheap for sorting large sets of invoices or using
+/// them as map keys.
+///
+/// Unlike [`Invoice`], the wrapper is compared by the canonical bytes: thus
+/// invoices listing equivalent URLs in different forms (like with and without
+/// a trailing slash) are distinct, and the ordering differs from the ordering
+/// of invoices.
+#[derive(Clone, Debug)]
+pub struct CanonicalInvoice {
+    invoice: Invoice,
+    bytes: Vec<u8>,
+}
+
+impl CanonicalInvoice {
+    pub fn new(invoice: Invoice) -> CanonicalInvoice {
+        let bytes = invoice.canonical_bytes();
+        CanonicalInvoice { invoice, bytes }
     }
 
-    pub fn with_descriptor(
-        descr: Descriptor<DescriptorPublicKey>,
-        amount: Option<u64>,
-        chain: &Chain,
-    ) -> Invoice {
-        Invoice::new(
-            Beneficiary::Descriptor(descr),
-            amount,
-            if chain == &Chain::Mainnet {
-                None
-            } else {
-                Some(chain.native_asset())
+    pub fn as_invoice(&self) -> &Invoice {
+        &self.invoice
+    }
+
+    pub fn into_invoice(self) -> Invoice {
+        self.invoice
+    }
+
+    /// Returns cached canonical serialization of the invoice.
+    pub fn canonical_bytes(&self) -> &[u8] {
+        &self.bytes
+    }
+}
+
+impl From<Invoice> for CanonicalInvoice {
+    fn from(invoice: Invoice) -> Self {
+        CanonicalInvoice::new(invoice)
+    }
+}
+
+impl AsRef<Invoice> for CanonicalInvoice {
+    fn as_ref(&self) -> &Invoice {
+        &self.invoice
+    }
+}
+
+impl PartialEq for CanonicalInvoice {
+    fn eq(&self, other: &Self) -> bool {
+        self.bytes == other.bytes
+    }
+}
+
+impl Eq for CanonicalInvoice {}
+
+impl Ord for CanonicalInvoice {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.bytes.cmp(&other.bytes)
+    }
+}
+
+impl PartialOrd for CanonicalInvoice {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl std::hash::Hash for CanonicalInvoice {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        self.bytes.hash(state)
+    }
+}
+
+impl Invoice {
+    /// Version of the invoices supported by this library. Invoices of other
+    /// versions may interpret their fields differently, so they are rejected
+    /// on decoding; see [`Invoice::decode_any_version`].
+    pub const VERSION: u8 = 0;
+
+    /// Initial capacity of the buffers receiving invoice serialization,
+    /// which fits most of the invoices without reallocations.
+    const SERIALIZATION_CAPACITY: usize = 256;
+
+    pub fn new(
+        beneficiary: Beneficiary,
+        amount: Option<u64>,
+        asset: Option<AssetId>,
+    ) -> Invoice {
+        Invoice {
+            version: Invoice::VERSION,
+            amount: amount.map(AmountExt::Normal).unwrap_or(AmountExt::Any),
+            beneficiary,
+            alt_beneficiaries: vec![],
+            asset,
+            recurrent: Default::default(),
+            recurrence_limit: None,
+            expiry: None,
+            quantity: None,
+            currency_requirement: None,
+            currency_requirements: empty!(),
+            merchant: None,
+            merchant_info: None,
+            purpose: None,
+            reference: None,
+            details: None,
+            inline_details: None,
+            signature: None,
+            signature_scheme: None,
+            consignment_endpoints: Default::default(),
+            network: None,
+            issued_at: None,
+            relative_expiry: None,
+            split_shares: empty!(),
+            asset_metadata: None,
+            iface: None,
+            refund: None,
+            onchain_policy: None,
+            cosignatures: empty!(),
+            beneficiary_proof: None,
+            unknown: Default::default(),
+        }
+    }
+
+    /// Constructs invoice specifying the network it is issued for, which
+    /// allows payers to detect incompatible invoices early.
+    pub fn new_on_network(
+        beneficiary: Beneficiary,
+        amount: Option<u64>,
+        asset: Option<AssetId>,
+        network: Network,
+    ) -> Invoice {
+        let mut invoice = Invoice::new(beneficiary, amount, asset);
+        invoice.network = Some(network);
+        invoice
+    }
+
+    /// Constructs invoice paying to a descriptor on a given chain. The
+    /// invoice network is set from the chain, unless the chain can't be
+    /// represented by [`Network`].
+    pub fn with_descriptor(
+        descr: Descriptor<DescriptorPublicKey>,
+        amount: Option<u64>,
+        chain: &Chain,
+    ) -> Invoice {
+        let mut invoice = Invoice::new(
+            Beneficiary::Descriptor(descr),
+            amount,
+            if chain == &Chain::Mainnet {
+                None
+            } else {
+                Some(chain.native_asset())
             },
-        )
+        );
+        invoice.network = Network::try_from(chain.clone()).ok();
+        invoice
+    }
+
+    /// Constructs RGB invoice paying a given contract assets to a blinded
+    /// UTXO. Fails if no consignment endpoints are provided.
+    #[cfg(feature = "rgb")]
+    pub fn new_rgb(
+        contract_id: rgb::ContractId,
+        seal: ConcealedSeal,
+        amount: Option<u64>,
+        endpoints: Vec<ConsignmentEndpoint>,
+    ) -> Result<Invoice, NoConsignmentEndpoint> {
+        if endpoints.is_empty() {
+            return Err(NoConsignmentEndpoint);
+        }
+        let mut invoice = Invoice::new(
+            Beneficiary::BlindUtxo(seal),
+            amount,
+            Some(contract_id_to_asset_id(contract_id)),
+        );
+        for endpoint in endpoints {
+            invoice.consignment_endpoints.push(endpoint);
+        }
+        Ok(invoice)
     }
 
+    /// Constructs invoice paying to a bitcoin address, with the invoice
+    /// network set from the address network.
     pub fn with_address(address: Address, amount: Option<u64>) -> Invoice {
         let asset = if address.network != bitcoin::Network::Bitcoin {
             Some(AssetId::native(&address.network.into()))
         } else {
             None
         };
-        Invoice::new(Beneficiary::Address(address), amount, asset)
+        let network = Network::from(address.network);
+        Invoice::new_on_network(
+            Beneficiary::Address(address),
+            amount,
+            asset,
+            network,
+        )
+    }
+
+    /// Returns canonical serialization of the invoice, which is used for the
+    /// bech32 encoding and [`CanonicalInvoice`] ordering.
+    pub fn canonical_bytes(&self) -> Vec<u8> {
+        let mut data = Vec::with_capacity(Invoice::SERIALIZATION_CAPACITY);
+        self.strict_encode(&mut data)
+            .expect("in-memory strict encoding failure");
+        data
+    }
+
+    /// Returns uppercase bech32 representation of the invoice, which allows
+    /// QR codes to use more compact alphanumeric mode
+    pub fn to_bech32_string_upper(&self) -> String {
+        self.to_bech32_string().to_ascii_uppercase()
     }
 
+    /// Detects whether the invoice requests payment in RGB assets, i.e. its
+    /// asset is neither absent nor a native asset of a known chain.
+    ///
+    /// NB: Before v0.9.2 this method returned inverted result.
     #[cfg(feature = "rgb")]
     pub fn is_rgb(&self) -> bool {
-        self.rgb_asset().is_none()
+        self.rgb_asset().is_some()
+    }
+
+    /// Returns blinded UTXO receiving the assets of an RGB invoice, or `None`
+    /// if the invoice is not an RGB one or if its main beneficiary is not a
+    /// blinded UTXO (like [`Beneficiary::WitnessVoutSeal`]).
+    #[cfg(feature = "rgb")]
+    pub fn rgb_seal(&self) -> Option<ConcealedSeal> {
+        self.rgb_asset()?;
+        match self.beneficiary {
+            Beneficiary::BlindUtxo(seal) => Some(seal),
+            _ => None,
+        }
     }
 
+    /// Returns RGB contract of the invoice asset, or `None` if the invoice
+    /// asset is native or (with `elements` feature) is a Liquid asset.
     #[cfg(feature = "rgb")]
     pub fn rgb_asset(&self) -> Option<rgb::ContractId> {
-        self.asset.and_then(|asset_id| {
-            if *&[
-                Chain::Mainnet,
-                Chain::Signet,
-                Chain::LiquidV1,
-                Chain::Testnet3,
-            ]
-            .iter()
-            .map(Chain::native_asset)
-            .all(|id| id != asset_id)
+        let chain = self
+            .network
+            .and_then(|network| Chain::try_from(network).ok());
+        #[cfg(feature = "elements")]
+        if chain == Some(Chain::LiquidV1) {
+            return None;
+        }
+        self.asset
+            .filter(|asset_id| !is_native_asset(asset_id, chain.as_ref()))
+            .map(asset_id_to_contract_id)
+    }
+
+    /// Classifies invoice asset using the chain inferred from the invoice
+    /// itself: from the `network` field or, if it is absent, from the network
+    /// of the main beneficiary address, defaulting to bitcoin mainnet.
+    /// Native assets are reported as [`AssetClass::InvalidNativeChain`] if
+    /// the `network` field contradicts the beneficiary address network.
+    pub fn classify_asset_auto(&self) -> AssetClass {
+        #[cfg(feature = "elements")]
+        if let Beneficiary::LiquidAddress(address) = &self.beneficiary {
+            let chain = match self.network {
+                Some(network) if !address.network.matches_network(&network) => {
+                    let chain = Chain::try_from(network).ok();
+                    return match self.classify_asset(chain) {
+                        AssetClass::Native => AssetClass::InvalidNativeChain,
+                        class => class,
+                    };
+                }
+                Some(network) => Chain::try_from(network).ok(),
+                None if address.network == LiquidNetwork::Liquid => {
+                    Some(Chain::LiquidV1)
+                }
+                None => None,
+            };
+            return self.classify_asset(chain);
+        }
+        let address_network = match &self.beneficiary {
+            Beneficiary::Address(address) => Some(address.network),
+            _ => None,
+        };
+        let chain = match (self.network, address_network) {
+            (Some(network), Some(address_network))
+                if !network.matches_address_network(address_network) =>
             {
-                Some(rgb::ContractId::from_inner(sha256t::Hash::from_inner(
-                    asset_id.into_inner(),
-                )))
-            } else {
-                None
+                let chain = Chain::try_from(network).ok();
+                return match self.classify_asset(chain) {
+                    AssetClass::Native => AssetClass::InvalidNativeChain,
+                    class => class,
+                };
             }
-        })
+            (Some(network), _) => Chain::try_from(network).ok(),
+            (None, Some(address_network)) => Some(Chain::from(address_network)),
+            (None, None) => Some(Chain::Mainnet),
+        };
+        self.classify_asset(chain)
     }
 
     pub fn classify_asset(&self, chain: Option<Chain>) -> AssetClass {
@@ -242,248 +1164,4408 @@ impl Invoice {
             (None, Some(Chain::Mainnet)) => AssetClass::Native,
             (None, _) => AssetClass::InvalidNativeChain,
             (Some(asset_id), Some(chain))
-                if asset_id == chain.native_asset() =>
+                if is_native_asset_of(&asset_id, &chain) =>
             {
                 AssetClass::Native
             }
-            (Some(asset_id), _)
-                if *&[
-                    Chain::Mainnet,
-                    Chain::Signet,
-                    Chain::LiquidV1,
-                    Chain::Testnet3,
-                ]
-                .iter()
-                .map(Chain::native_asset)
-                .find(|id| id == &asset_id)
-                .is_some() =>
-            {
+            (Some(asset_id), _) if is_native_asset(&asset_id, None) => {
                 AssetClass::InvalidNativeChain
             }
+            #[cfg(feature = "elements")]
+            (Some(asset_id), Some(Chain::LiquidV1)) => {
+                AssetClass::Liquid(asset_id)
+            }
             #[cfg(feature = "rgb")]
             (Some(asset_id), _) => {
-                AssetClass::Rgb(rgb::ContractId::from_inner(
-                    sha256t::Hash::from_inner(asset_id.into_inner()),
-                ))
+                AssetClass::Rgb(asset_id_to_contract_id(asset_id))
             }
             #[cfg(not(feature = "rgb"))]
             (Some(asset_id), _) => AssetClass::Other(asset_id),
         }
     }
 
-    pub fn beneficiaries(&self) -> BeneficiariesIter {
+    /// Performs cross-field consistency checks of the invoice data, returning
+    /// all detected inconsistencies.
+    pub fn validate(&self) -> Result<(), Vec<InvoiceInconsistency>> {
+        let mut errors = vec![];
+
+        if let Some(Network::Unknown(tag, _)) = self.network {
+            errors.push(InvoiceInconsistency::UnknownNetwork(tag));
+        } else if let Some(network) = self.network {
+            let key_matches = |key: &DescriptorPublicKey| match key {
+                DescriptorPublicKey::XPub(xpub) => {
+                    (xpub.xkey.network == bitcoin::Network::Bitcoin)
+                        == network.is_mainnet()
+                }
+                DescriptorPublicKey::Single(_) => true,
+            };
+            for beneficiary in self.beneficiaries() {
+                match beneficiary {
+                    Beneficiary::Address(address)
+                        if !network
+                            .matches_address_network(address.network) =>
+                    {
+                        errors.push(InvoiceInconsistency::AddressNetwork(
+                            address.clone(),
+                        ))
+                    }
+                    Beneficiary::Descriptor(descriptor)
+                    | Beneficiary::TrackingDescriptor(TrackingBeneficiary {
+                        descriptor,
+                        ..
+                    }) if !descriptor.for_each_key(key_matches) => {
+                        errors.push(InvoiceInconsistency::DescriptorNetwork)
+                    }
+                    #[cfg(feature = "elements")]
+                    Beneficiary::LiquidAddress(address)
+                        if !address.network.matches_network(&network) =>
+                    {
+                        errors.push(InvoiceInconsistency::LiquidAddressNetwork(
+                            address.clone(),
+                        ))
+                    }
+                    _ => {}
+                }
+            }
+
+            match &self.refund {
+                Some(Beneficiary::Address(address))
+                    if !network.matches_address_network(address.network) =>
+                {
+                    errors.push(InvoiceInconsistency::AddressNetwork(
+                        address.clone(),
+                    ))
+                }
+                #[cfg(feature = "elements")]
+                Some(Beneficiary::LiquidAddress(address))
+                    if !address.network.matches_network(&network) =>
+                {
+                    errors.push(InvoiceInconsistency::LiquidAddressNetwork(
+                        address.clone(),
+                    ))
+                }
+                _ => {}
+            }
+
+            let chain = Chain::try_from(network).ok();
+            if let Some(asset_id) = self.asset {
+                if is_native_asset(&asset_id, None)
+                    && Some(asset_id) != chain.map(|c| c.native_asset())
+                {
+                    errors.push(InvoiceInconsistency::NativeAssetNetwork);
+                }
+            }
+        }
+
+        #[cfg(feature = "elements")]
+        {
+            let mut blinding = self.beneficiaries().filter_map(|b| match b {
+                Beneficiary::LiquidAddress(address) => {
+                    Some(address.is_blinded())
+                }
+                _ => None,
+            });
+            if let Some(first) = blinding.next() {
+                if blinding.any(|blinded| blinded != first) {
+                    errors
+                        .push(InvoiceInconsistency::MixedLiquidConfidentiality);
+                }
+            }
+        }
+
+        match self.effective_expiry() {
+            Ok(Some(expiry)) => match self.issued_at {
+                Some(issued_at) if expiry < issued_at => {
+                    errors.push(InvoiceInconsistency::ExpiryBeforeIssue {
+                        expiry,
+                        issued_at,
+                    })
+                }
+                _ => {}
+            },
+            Ok(None) => {}
+            Err(err) => errors.push(err.into()),
+        }
+
+        if let Some(quantity) = self.quantity {
+            if self.amount == AmountExt::Any {
+                errors.push(InvoiceInconsistency::QuantityWithoutAmount);
+            }
+            if let Err(err) = quantity.validate() {
+                errors.push(err.into());
+            }
+        }
+
+        if let Some(Err(err)) = self.onchain_policy.map(|p| p.validate()) {
+            errors.push(err.into());
+        }
+
+        if let Some(reference) = &self.reference {
+            if let Err(err) = check_reference(reference) {
+                errors.push(err.into());
+            }
+        }
+
+        if let Err(err) = self.consignment_endpoints.check() {
+            errors.push(err.into());
+        }
+
+        #[cfg(feature = "rgb")]
+        if self.is_rgb() && self.consignment_endpoints.0.is_empty() {
+            errors.push(InvoiceInconsistency::NoConsignmentEndpoint);
+        }
+
+        #[cfg(feature = "rgb")]
+        if self.iface.is_some() && !self.is_rgb() {
+            errors.push(InvoiceInconsistency::IfaceWithoutRgbAsset);
+        }
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
+
+    pub fn beneficiaries(&self) -> BeneficiariesIter<'_> {
         BeneficiariesIter {
             invoice: self,
-            index: 0,
+            front: 0,
+            back: 1 + self.alt_beneficiaries.len(),
         }
     }
 
-    pub fn set_amount(&mut self, amount: AmountExt) -> bool {
-        if self.amount == amount {
-            return false;
-        }
-        self.amount = amount;
-        self.signature = None;
-        return true;
+    /// Detects recurrent invoices paying to a bare bitcoin address, which
+    /// makes the payer reuse the address for each of the payments. Wallets
+    /// should warn users about such invoices.
+    pub fn requires_fresh_address_per_payment(&self) -> bool {
+        self.recurrent != Recurrent::NonRecurrent
+            && matches!(self.beneficiary, Beneficiary::Address(_))
     }
 
-    pub fn set_recurrent(&mut self, recurrent: Recurrent) -> bool {
-        if self.recurrent == recurrent {
-            return false;
-        }
-        self.recurrent = recurrent;
-        self.signature = None;
-        return true;
+    /// Returns beneficiaries supported by the payer, in the order of their
+    /// priority.
+    pub fn selectable_beneficiaries(
+        &self,
+        caps: PayerCapabilities,
+    ) -> impl DoubleEndedIterator<Item = &Beneficiary> + Clone {
+        self.beneficiaries()
+            .filter(move |beneficiary| caps.supports(beneficiary))
     }
 
-    pub fn set_expiry(&mut self, expiry: NaiveDateTime) -> bool {
-        if self.expiry == Some(expiry) {
-            return false;
+    /// Selects the most preferred beneficiary supported by the payer.
+    ///
+    /// Split payment invoices require all of their beneficiaries to be paid,
+    /// so for them the main beneficiary is returned only if the payer
+    /// supports every beneficiary, and `None` otherwise.
+    pub fn select_beneficiary(
+        &self,
+        caps: PayerCapabilities,
+    ) -> Option<&Beneficiary> {
+        if self.is_split_payment() {
+            return if self.beneficiaries().all(|b| caps.supports(b)) {
+                Some(&self.beneficiary)
+            } else {
+                None
+            };
         }
-        self.expiry = Some(expiry);
-        self.signature = None;
-        return true;
+        self.selectable_beneficiaries(caps).next()
     }
 
-    pub fn set_no_expiry(&mut self) -> bool {
-        if self.expiry == None {
-            return false;
-        }
-        self.expiry = None;
-        self.signature = None;
-        return true;
+    /// Returns beneficiaries which can be paid with an on-chain bitcoin
+    /// transaction: addresses, descriptors, PSBTs, silent payment and BIP-47
+    /// payment codes.
+    pub fn onchain_beneficiaries(
+        &self,
+    ) -> impl DoubleEndedIterator<Item = &Beneficiary> + Clone {
+        self.beneficiaries().filter(|beneficiary| {
+            matches!(
+                beneficiary,
+                Beneficiary::Address(_)
+                    | Beneficiary::Descriptor(_)
+                    | Beneficiary::TrackingDescriptor(_)
+                    | Beneficiary::Psbt(_)
+                    | Beneficiary::SilentPayment(_)
+                    | Beneficiary::PaymentCode(_)
+            )
+        })
     }
 
-    pub fn set_quantity(&mut self, quantity: Quantity) -> bool {
-        if self.quantity == Some(quantity) {
-            return false;
-        }
-        self.quantity = Some(quantity);
-        self.signature = None;
-        return true;
+    /// Returns lightning nodes receiving the payment.
+    pub fn lightning_beneficiaries(
+        &self,
+    ) -> impl DoubleEndedIterator<Item = &LnAddress> + Clone {
+        self.beneficiaries()
+            .filter_map(|beneficiary| match beneficiary {
+                Beneficiary::Bolt(address) => Some(address),
+                _ => None,
+            })
     }
 
-    pub fn remove_quantity(&mut self) -> bool {
-        if self.quantity == None {
-            return false;
-        }
-        self.quantity = None;
-        self.signature = None;
-        return true;
+    /// Returns beneficiaries receiving RGB assets: blinded UTXOs and witness
+    /// transaction output seals.
+    pub fn rgb_beneficiaries(
+        &self,
+    ) -> impl DoubleEndedIterator<Item = &Beneficiary> + Clone {
+        self.beneficiaries().filter(|beneficiary| {
+            matches!(
+                beneficiary,
+                Beneficiary::BlindUtxo(_) | Beneficiary::WitnessVoutSeal(_)
+            )
+        })
     }
 
-    pub fn set_currency_requirement(
-        &mut self,
-        currency_data: CurrencyData,
-    ) -> bool {
-        let currency_data = Some(currency_data);
-        if self.currency_requirement == currency_data {
+    pub fn set_beneficiary(&mut self, beneficiary: Beneficiary) -> bool {
+        if self.beneficiary == beneficiary {
             return false;
         }
-        self.currency_requirement = currency_data;
-        self.signature = None;
-        return true;
+        self.beneficiary = beneficiary;
+        self.invalidate_signatures();
+        true
     }
 
-    pub fn remove_currency_requirement(&mut self) -> bool {
-        if self.currency_requirement == None {
+    /// Adds alternative beneficiary, unless it is already present among the
+    /// invoice beneficiaries. For split payment invoices the new beneficiary
+    /// receives zero share, which can be changed with
+    /// [`Invoice::set_payment_split`].
+    pub fn add_alt_beneficiary(&mut self, beneficiary: Beneficiary) -> bool {
+        if self.beneficiary == beneficiary
+            || self.alt_beneficiaries.contains(&beneficiary)
+        {
             return false;
         }
-        self.currency_requirement = None;
-        self.signature = None;
-        return true;
+        self.alt_beneficiaries.push(beneficiary);
+        if self.is_split_payment() {
+            self.split_shares.push(SplitShare::Amount(0));
+        }
+        self.invalidate_signatures();
+        true
     }
 
-    pub fn set_merchant(&mut self, merchant: String) -> bool {
-        let merchant = if merchant.is_empty() {
-            None
-        } else {
-            Some(merchant)
+    /// Removes alternative beneficiary together with its split payment
+    /// share, if any.
+    pub fn remove_alt_beneficiary(
+        &mut self,
+        beneficiary: &Beneficiary,
+    ) -> bool {
+        let index = match self
+            .alt_beneficiaries
+            .iter()
+            .position(|alt| alt == beneficiary)
+        {
+            Some(index) => index,
+            None => return false,
         };
-        if self.merchant == merchant {
-            return false;
+        self.alt_beneficiaries.remove(index);
+        if index < self.split_shares.len() {
+            self.split_shares.remove(index);
         }
-        self.merchant = merchant;
-        self.signature = None;
-        return true;
+        self.invalidate_signatures();
+        true
     }
 
-    pub fn remove_merchant(&mut self) -> bool {
-        if self.merchant == None {
+    pub fn set_asset(&mut self, asset: AssetId) -> bool {
+        if self.asset == Some(asset) {
             return false;
         }
-        self.merchant = None;
-        self.signature = None;
-        return true;
+        self.asset = Some(asset);
+        self.invalidate_signatures();
+        true
     }
 
-    pub fn set_purpose(&mut self, purpose: String) -> bool {
-        let purpose = if purpose.is_empty() {
-            None
-        } else {
-            Some(purpose)
-        };
-        if self.purpose == purpose {
+    pub fn remove_asset(&mut self) -> bool {
+        if self.asset.is_none() {
             return false;
         }
-        self.purpose = purpose;
-        self.signature = None;
-        return true;
+        self.asset = None;
+        self.invalidate_signatures();
+        true
     }
 
-    pub fn remove_purpose(&mut self) -> bool {
-        if self.purpose == None {
+    /// Sets display metadata of the invoiced asset. The metadata are covered
+    /// by the invoice signature, but remain issuer assertions which wallets
+    /// must reconcile with the asset contract from the consignment.
+    pub fn set_asset_metadata(&mut self, metadata: AssetMetadata) -> bool {
+        let metadata = Some(metadata);
+        if self.asset_metadata == metadata {
             return false;
         }
-        self.purpose = None;
-        self.signature = None;
-        return true;
+        self.asset_metadata = metadata;
+        self.invalidate_signatures();
+        true
     }
 
-    pub fn set_details(&mut self, details: Details) -> bool {
-        let details = Some(details);
-        if self.details == details {
+    pub fn remove_asset_metadata(&mut self) -> bool {
+        if self.asset_metadata.is_none() {
             return false;
         }
-        self.details = details;
-        self.signature = None;
-        return true;
+        self.asset_metadata = None;
+        self.invalidate_signatures();
+        true
     }
 
-    pub fn remove_details(&mut self) -> bool {
-        if self.details == None {
+    /// Returns RGB contract interface expected by the invoice.
+    #[cfg(feature = "rgb")]
+    pub fn iface(&self) -> Option<&Iface> {
+        self.iface.as_ref()
+    }
+
+    /// Sets RGB contract interface which the invoiced asset must support.
+    #[cfg(feature = "rgb")]
+    pub fn set_iface(&mut self, iface: Iface) -> bool {
+        let iface = Some(iface);
+        if self.iface == iface {
             return false;
         }
-        self.details = None;
-        self.signature = None;
-        return true;
+        self.iface = iface;
+        self.invalidate_signatures();
+        true
     }
 
     #[cfg(feature = "rgb")]
-    pub fn add_consignment_endpoint(
-        &mut self,
-        node: ConsignmentEndpoint,
-    ) -> bool {
-        if self.consignment_endpoints.contains(&node) {
+    pub fn remove_iface(&mut self) -> bool {
+        if self.iface.is_none() {
             return false;
         }
-        self.consignment_endpoints.push(node);
+        self.iface = None;
+        self.invalidate_signatures();
         true
     }
 
-    pub fn signature_hash(&self) -> MerkleNode {
-        // TODO: Change signature encoding algorithm to a merkle-tree based
-        MerkleNode::hash(
-            &self.strict_serialize().expect(
-                "invoice data are inconsistent for strict serialization",
-            ),
-        )
+    /// Renders invoice amount using the asset metadata, like `1.5 USDT`.
+    /// Returns `None` if the invoice has no asset metadata or its amount is
+    /// not a specific number of atomic units.
+    pub fn format_amount(&self) -> Option<String> {
+        let atomic = self.amount.atomic_value()?;
+        self.asset_metadata
+            .as_ref()
+            .map(|metadata| metadata.format_amount(atomic))
     }
 
-    pub fn set_signature(
+    /// Decomposes invoice into its field values. Neither the signatures and
+    /// the beneficiary address proof, which become invalid once the invoice
+    /// is rebuilt with [`InvoiceBuilder`](crate::InvoiceBuilder), nor unknown
+    /// TLV records are included.
+    pub fn into_parts(self) -> InvoiceParts {
+        let currency_requirements = self.currency_requirements().to_vec();
+        InvoiceParts {
+            amount: self.amount,
+            beneficiary: self.beneficiary,
+            alt_beneficiaries: self.alt_beneficiaries,
+            split_shares: self.split_shares,
+            asset: self.asset,
+            asset_metadata: self.asset_metadata,
+            iface: self.iface,
+            refund: self.refund,
+            onchain_policy: self.onchain_policy,
+            expiry: self.expiry,
+            issued_at: self.issued_at,
+            relative_expiry: self.relative_expiry,
+            recurrent: self.recurrent,
+            recurrence_limit: self.recurrence_limit,
+            quantity: self.quantity,
+            currency_requirements,
+            merchant: self.merchant,
+            merchant_info: self.merchant_info,
+            purpose: self.purpose,
+            reference: self.reference,
+            details: self.details,
+            inline_details: self.inline_details,
+            consignment_endpoints: self.consignment_endpoints.0,
+            network: self.network,
+        }
+    }
+
+    /// Sets the invoice amount, rejecting [`AmountExt::Milli`] amounts with
+    /// the fraction exceeding 999 milli-units, which can't be encoded.
+    pub fn set_amount(
         &mut self,
-        pubkey: secp256k1::PublicKey,
-        signature: schnorr::Signature,
-    ) {
-        self.signature = Some((pubkey, signature))
+        amount: AmountExt,
+    ) -> Result<bool, AmountParseError> {
+        if let AmountExt::Milli(_, frac) = amount {
+            if frac > 999 {
+                return Err(AmountParseError);
+            }
+        }
+        Ok(self.set_amount_unchecked(amount))
     }
 
-    pub fn remove_signature(&mut self) {
-        self.signature = None
+    /// Used by [`crate::InvoiceBuilder`], which validates amount on finish,
+    /// and by conversions producing amounts with valid fractions
+    pub(crate) fn set_amount_unchecked(&mut self, amount: AmountExt) -> bool {
+        if self.amount == amount {
+            return false;
+        }
+        self.amount = amount;
+        self.invalidate_signatures();
+        true
     }
-}
 
-#[derive(Clone, Ord, PartialOrd, Eq, PartialEq, Hash, Debug)]
-#[non_exhaustive]
-pub enum AssetClass {
+    pub fn set_recurrent(&mut self, recurrent: Recurrent) -> bool {
+        if self.recurrent == recurrent {
+            return false;
+        }
+        self.recurrent = recurrent;
+        self.invalidate_signatures();
+        true
+    }
+
+    /// Limits the number of recurrent payments
+    pub fn set_recurrence_limit(&mut self, limit: u32) -> bool {
+        if self.recurrence_limit == Some(limit) {
+            return false;
+        }
+        self.recurrence_limit = Some(limit);
+        self.invalidate_signatures();
+        true
+    }
+
+    pub fn remove_recurrence_limit(&mut self) -> bool {
+        if self.recurrence_limit.is_none() {
+            return false;
+        }
+        self.recurrence_limit = None;
+        self.invalidate_signatures();
+        true
+    }
+
+    /// Iterates over payment due times for a subscription starting at
+    /// `start`, taking into account the recurrence limit and the invoice
+    /// expiry (after which no new periods may start).
+    pub fn payment_schedule(
+        &self,
+        start: DateTime<Utc>,
+    ) -> impl Iterator<Item = DateTime<Utc>> {
+        let expiry = self.effective_expiry();
+        let recurrent = self.recurrent.clone();
+        (0..self.recurrence_limit.unwrap_or(u32::MAX))
+            .map_while(move |n| recurrent.nth_occurrence(start, n))
+            .take_while(move |due| match expiry {
+                Ok(Some(expiry)) => *due <= expiry,
+                Ok(None) => true,
+                // Inconsistent expiry prevents all payments
+                Err(_) => false,
+            })
+    }
+
+    pub fn set_expiry(&mut self, expiry: DateTime<Utc>) -> bool {
+        self.set_expiry_term(ExpiryTerm::Absolute(expiry))
+    }
+
+    /// Sets either absolute or relative expiry, removing the other one.
+    /// Relative expiry requires [`Invoice::issued_at`] to be set. Invoices
+    /// store time with a precision of seconds, so fractions of a second are
+    /// dropped.
+    pub fn set_expiry_term(&mut self, term: ExpiryTerm) -> bool {
+        let (expiry, relative_expiry) = match term {
+            ExpiryTerm::Absolute(expiry) => (Some(whole_seconds(expiry)), None),
+            ExpiryTerm::RelativeSeconds(secs) => (None, Some(secs)),
+        };
+        if self.expiry == expiry && self.relative_expiry == relative_expiry {
+            return false;
+        }
+        self.expiry = expiry;
+        self.relative_expiry = relative_expiry;
+        self.invalidate_signatures();
+        true
+    }
+
+    /// Returns expiry term, as it is specified in the invoice
+    pub fn expiry_term(&self) -> Option<ExpiryTerm> {
+        match (self.expiry, self.relative_expiry) {
+            (Some(expiry), _) => Some(ExpiryTerm::Absolute(expiry)),
+            (None, Some(secs)) => Some(ExpiryTerm::RelativeSeconds(secs)),
+            (None, None) => None,
+        }
+    }
+
+    /// Resolves expiry term into the absolute expiry time, or `None` if the
+    /// invoice has no expiry.
+    pub fn effective_expiry(
+        &self,
+    ) -> Result<Option<DateTime<Utc>>, ExpiryError> {
+        match (self.expiry, self.relative_expiry, self.issued_at) {
+            (None, None, _) => Ok(None),
+            (Some(expiry), None, _) => Ok(Some(expiry)),
+            (Some(_), Some(_), _) => Err(ExpiryError::Ambiguous),
+            (None, Some(_), None) => Err(ExpiryError::NoIssuanceTime),
+            (None, Some(secs), Some(issued_at)) => issued_at
+                .checked_add_signed(chrono::Duration::seconds(secs as i64))
+                .map(Some)
+                .ok_or(ExpiryError::OutOfRange),
+        }
+    }
+
+    /// Sets issuance time, dropping fractions of a second
+    pub fn set_issued_at(&mut self, issued_at: DateTime<Utc>) -> bool {
+        let issued_at = whole_seconds(issued_at);
+        if self.issued_at == Some(issued_at) {
+            return false;
+        }
+        self.issued_at = Some(issued_at);
+        self.invalidate_signatures();
+        true
+    }
+
+    /// Sets expiry from the date and time interpreted as UTC
+    #[deprecated(
+        since = "0.9.2",
+        note = "use `set_expiry` with `DateTime<Utc>`"
+    )]
+    pub fn set_expiry_naive(&mut self, expiry: NaiveDateTime) -> bool {
+        self.set_expiry(DateTime::from_utc(expiry, Utc))
+    }
+
+    /// Returns the moment after which the invoice can't be paid anymore, or
+    /// `None` if the invoice never expires.
+    ///
+    /// For non-recurrent invoices this is just the
+    /// [`Invoice::effective_expiry`] value. For recurrent invoices expiry
+    /// bounds the whole subscription: it is the latest moment in which a new
+    /// recurrence period may start, and the payment for that last period is
+    /// accepted until the period ends.
+    ///
+    /// Invoices with inconsistent expiry data are treated as never payable,
+    /// returning the earliest representable time.
+    pub fn payable_until(&self) -> Option<DateTime<Utc>> {
+        let expiry = match self.effective_expiry() {
+            Ok(expiry) => expiry?,
+            Err(_) => return Some(DateTime::<Utc>::MIN_UTC),
+        };
+        match &self.recurrent {
+            Recurrent::NonRecurrent => Some(expiry),
+            recurrent => recurrent.period_end(expiry),
+        }
+    }
+
+    /// Detects whether the invoice has expired at the moment `now`. Invoice
+    /// with no expiry never expires; otherwise the invoice is expired starting
+    /// exactly at the moment returned by [`Invoice::payable_until`].
+    pub fn is_expired(&self, now: DateTime<Utc>) -> bool {
+        self.payable_until()
+            .map(|until| now >= until)
+            .unwrap_or(false)
+    }
+
+    /// Computes total amount due for the `quantity` of items, or for the
+    /// default quantity specified by the invoice if `quantity` is `None`.
+    ///
+    /// Invoices without `quantity` option are paid for a single item, and
+    /// invoices accepting any amount are returned as [`AmountExt::Any`]
+    /// unless a specific quantity is requested.
+    pub fn total_amount(
+        &self,
+        quantity: Option<u32>,
+    ) -> Result<AmountExt, AmountError> {
+        let allowed = self.quantity.unwrap_or(Quantity {
+            min: 1,
+            max: Some(1),
+            default: 1,
+        });
+        if self.amount == AmountExt::Any {
+            return match quantity {
+                None => Ok(AmountExt::Any),
+                Some(_) => Err(AmountError::NotComputable),
+            };
+        }
+        let requested = quantity.unwrap_or(allowed.default);
+        if !allowed.contains(requested) {
+            return Err(AmountError::QuantityOutOfRange {
+                requested,
+                min: allowed.min,
+                max: allowed.max,
+            });
+        }
+        self.amount
+            .checked_mul(requested)
+            .ok_or(AmountError::Overflow)
+    }
+
+    /// Parses lowercase bech32 invoice string, checking decoded data against
+    /// the limits of a given policy.
+    pub fn parse_with_policy(
+        s: &str,
+        policy: &DecodePolicy,
+    ) -> Result<Invoice, InvoiceParseError> {
+        let data = zip::bech32_zip_str_to_payload(
+            <Invoice as FromBech32Str>::HRP,
+            s,
+            policy.max_payload_len,
+        )
+        .map_err(|err| InvoiceParseError::with(err, s))?;
+        match data.first() {
+            Some(&Invoice::VERSION) | None => {}
+            Some(version) => {
+                return Err(InvoiceParseError::UnsupportedVersion(*version))
+            }
+        }
+        let invoice = if policy.accept_unknown_required {
+            LenientInvoice::strict_deserialize(data)?.0
+        } else {
+            Invoice::strict_deserialize(data)?
+        };
+        invoice.validate_limits(policy).map_err(|err| {
+            strict_encoding::Error::DataIntegrityError(err.to_string())
+        })?;
+        invoice.check_consistency()?;
+        Ok(invoice)
+    }
+
+    /// Decodes strict-encoded invoice data, returning version and raw payload
+    /// of the invoices not supported by this library instead of failing.
+    pub fn decode_any_version(
+        data: impl AsRef<[u8]>,
+    ) -> Result<VersionedInvoice, strict_encoding::Error> {
+        let data = data.as_ref();
+        match data.first() {
+            Some(&Invoice::VERSION) => {
+                let invoice = Invoice::strict_deserialize(data)?;
+                invoice.check_consistency()?;
+                Ok(VersionedInvoice::Supported(invoice))
+            }
+            Some(version) => Ok(VersionedInvoice::Unsupported {
+                version: *version,
+                payload: data[1..].to_vec(),
+            }),
+            None => Err(strict_encoding::Error::Io(
+                io::ErrorKind::UnexpectedEof.into(),
+            )),
+        }
+    }
+
+    /// Checks that the invoice data do not exceed the limits of a given
+    /// decode policy.
+    pub fn validate_limits(
+        &self,
+        policy: &DecodePolicy,
+    ) -> Result<(), DecodeLimit> {
+        let check = |field, count, limit| {
+            if count > limit {
+                Err(DecodeLimit {
+                    field,
+                    count,
+                    limit,
+                })
+            } else {
+                Ok(())
+            }
+        };
+        check(
+            "alternative beneficiaries",
+            self.alt_beneficiaries.len(),
+            policy.max_alt_beneficiaries,
+        )?;
+        check(
+            "consignment endpoints",
+            self.consignment_endpoints.0.len(),
+            policy.max_consignment_endpoints,
+        )?;
+        for beneficiary in self.beneficiaries() {
+            if let Beneficiary::Bolt(address) = beneficiary {
+                check(
+                    "lightning path hints",
+                    address.path_hint_count(),
+                    policy.max_path_hints,
+                )?;
+            }
+        }
+        let strings = [
+            ("merchant", self.merchant.as_deref()),
+            ("purpose", self.purpose.as_deref()),
+            (
+                "details source",
+                self.details.as_ref().map(|details| details.source.as_str()),
+            ),
+        ];
+        for (field, value) in strings {
+            if let Some(value) = value {
+                check(field, value.len(), policy.max_string_len)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Checks consistency of decoded invoice data which can't be verified by
+    /// the strict decoding of individual fields.
+    pub(crate) fn check_consistency(
+        &self,
+    ) -> Result<(), strict_encoding::Error> {
+        let integrity_err = |err: &dyn Display| {
+            strict_encoding::Error::DataIntegrityError(err.to_string())
+        };
+        if self.version != Invoice::VERSION {
+            return Err(integrity_err(&InvoiceParseError::UnsupportedVersion(
+                self.version,
+            )));
+        }
+        self.effective_expiry().map_err(|err| integrity_err(&err))?;
+        self.check_payment_split()
+            .map_err(|err| integrity_err(&err))?;
+        if let Some(reference) = &self.reference {
+            check_reference(reference).map_err(|err| integrity_err(&err))?;
+        }
+        if self.currency_requirement.is_some()
+            && !self.currency_requirements.0.is_empty()
+        {
+            return Err(integrity_err(&CurrencyError::AmbiguousRequirements));
+        }
+        Ok(())
+    }
+
+    /// Detects whether the alternative beneficiaries receive parts of a split
+    /// payment instead of being alternatives to the main beneficiary.
+    pub fn is_split_payment(&self) -> bool {
+        !self.split_shares.is_empty()
+    }
+
+    /// Turns the invoice into a split payment invoice, where each alternative
+    /// beneficiary receives the corresponding share of the payment and the
+    /// main beneficiary receives the remainder.
+    pub fn set_payment_split(
+        &mut self,
+        shares: Vec<SplitShare>,
+    ) -> Result<bool, SplitError> {
+        if self.split_shares == shares {
+            return Ok(false);
+        }
+        let prev = ::std::mem::replace(&mut self.split_shares, shares);
+        if let Err(err) = self.check_payment_split() {
+            self.split_shares = prev;
+            return Err(err);
+        }
+        self.invalidate_signatures();
+        Ok(true)
+    }
+
+    pub(crate) fn set_payment_split_unchecked(
+        &mut self,
+        shares: Vec<SplitShare>,
+    ) {
+        self.split_shares = shares;
+        self.invalidate_signatures();
+    }
+
+    /// Turns split payment invoice back into the invoice where alternative
+    /// beneficiaries are alternatives to the main beneficiary.
+    pub fn remove_payment_split(&mut self) -> bool {
+        if self.split_shares.is_empty() {
+            return false;
+        }
+        self.split_shares = empty!();
+        self.invalidate_signatures();
+        true
+    }
+
+    /// Checks that the split shares match alternative beneficiaries and do
+    /// not exceed the invoice amount.
+    pub fn check_payment_split(&self) -> Result<(), SplitError> {
+        if !self.is_split_payment() {
+            return Ok(());
+        }
+        if self.split_shares.len() != self.alt_beneficiaries.len() {
+            return Err(SplitError::LengthMismatch {
+                shares: self.split_shares.len(),
+                beneficiaries: self.alt_beneficiaries.len(),
+            });
+        }
+        let basis_points = self
+            .split_shares
+            .iter()
+            .map(|share| match share {
+                SplitShare::BasisPoints(bp) => *bp as u32,
+                SplitShare::Amount(_) => 0,
+            })
+            .sum::<u32>();
+        if basis_points > SplitShare::TOTAL_BASIS_POINTS as u32 {
+            return Err(SplitError::Exceeds);
+        }
+        match self.amount {
+            AmountExt::Any => Ok(()),
+            AmountExt::Normal(amount) | AmountExt::Milli(amount, _) => {
+                self.payment_split(amount).map(|_| ())
+            }
+        }
+    }
+
+    /// Resolves amounts paid to each of the beneficiaries out of the `total`
+    /// payment amount. Invoices which are not split payment invoices pay the
+    /// whole amount to the main beneficiary.
+    ///
+    /// Basis point shares are rounded down, leaving the remainder to the main
+    /// beneficiary.
+    pub fn payment_split(
+        &self,
+        total: u64,
+    ) -> Result<Vec<(&Beneficiary, u64)>, SplitError> {
+        let mut split = Vec::with_capacity(self.split_shares.len() + 1);
+        split.push((&self.beneficiary, total));
+        let mut remainder = total;
+        for (beneficiary, share) in
+            self.alt_beneficiaries.iter().zip(&self.split_shares)
+        {
+            let amount = share.resolve(total);
+            remainder =
+                remainder.checked_sub(amount).ok_or(SplitError::Exceeds)?;
+            split.push((beneficiary, amount));
+        }
+        split[0].1 = remainder;
+        Ok(split)
+    }
+
+    /// Returns time remaining before the invoice expires, which is zero for
+    /// already expired invoices and `None` for the invoices which never
+    /// expire.
+    pub fn time_left(&self, now: DateTime<Utc>) -> Option<chrono::Duration> {
+        self.payable_until().map(|until| {
+            if now >= until {
+                chrono::Duration::zero()
+            } else {
+                until - now
+            }
+        })
+    }
+
+    pub fn set_no_expiry(&mut self) -> bool {
+        if self.expiry.is_none() && self.relative_expiry.is_none() {
+            return false;
+        }
+        self.expiry = None;
+        self.relative_expiry = None;
+        self.invalidate_signatures();
+        true
+    }
+
+    /// Sets quantity option after checking its consistency with
+    /// [`Quantity::validate`].
+    pub fn set_quantity(
+        &mut self,
+        quantity: Quantity,
+    ) -> Result<bool, QuantityError> {
+        quantity.validate()?;
+        Ok(self.set_quantity_unchecked(quantity))
+    }
+
+    /// Used by [`crate::InvoiceBuilder`], which validates quantity on finish
+    pub(crate) fn set_quantity_unchecked(
+        &mut self,
+        quantity: Quantity,
+    ) -> bool {
+        if self.quantity == Some(quantity) {
+            return false;
+        }
+        self.quantity = Some(quantity);
+        self.invalidate_signatures();
+        true
+    }
+
+    pub fn remove_quantity(&mut self) -> bool {
+        if self.quantity.is_none() {
+            return false;
+        }
+        self.quantity = None;
+        self.invalidate_signatures();
+        true
+    }
+
+    /// Returns currency requirements in the order of merchant preference.
+    pub fn currency_requirements(&self) -> &[CurrencyData] {
+        match &self.currency_requirement {
+            Some(LegacyCurrencyData(currency_data)) => {
+                std::slice::from_ref(currency_data)
+            }
+            None => &self.currency_requirements.0,
+        }
+    }
+
+    /// Puts currency requirements into the invoice records: a single
+    /// requirement representable in the legacy format goes to the legacy
+    /// record readable by older software, while lists use their own record.
+    fn store_currency_requirements(&mut self, requirements: Vec<CurrencyData>) {
+        if requirements == self.currency_requirements() {
+            return;
+        }
+        match <[CurrencyData; 1]>::try_from(requirements) {
+            Ok([currency_data]) if currency_data.is_legacy_representable() => {
+                self.currency_requirement =
+                    Some(LegacyCurrencyData(currency_data));
+                self.currency_requirements = empty!();
+            }
+            Ok([currency_data]) => {
+                self.currency_requirement = None;
+                self.currency_requirements =
+                    CurrencyRequirements(vec![currency_data]);
+            }
+            Err(requirements) => {
+                self.currency_requirement = None;
+                self.currency_requirements = CurrencyRequirements(requirements);
+            }
+        }
+        self.invalidate_signatures();
+    }
+
+    /// Adds currency requirement, replacing the existing requirement for the
+    /// same ISO 4217 currency code, if any.
+    pub fn add_currency_requirement(
+        &mut self,
+        currency_data: CurrencyData,
+    ) -> Result<bool, CurrencyError> {
+        currency_data.validate()?;
+        let requirements = self.currency_requirements();
+        if requirements.len() >= MAX_CURRENCY_REQUIREMENTS
+            && requirements
+                .iter()
+                .all(|data| data.iso4217 != currency_data.iso4217)
+        {
+            return Err(CurrencyError::TooManyRequirements);
+        }
+        Ok(self.add_currency_requirement_unchecked(currency_data))
+    }
+
+    pub(crate) fn add_currency_requirement_unchecked(
+        &mut self,
+        currency_data: CurrencyData,
+    ) -> bool {
+        let mut requirements = self.currency_requirements().to_vec();
+        match requirements
+            .iter_mut()
+            .find(|data| data.iso4217 == currency_data.iso4217)
+        {
+            Some(data) if *data == currency_data => return false,
+            Some(data) => *data = currency_data,
+            None => requirements.push(currency_data),
+        }
+        self.store_currency_requirements(requirements);
+        true
+    }
+
+    /// Removes currency requirement for the given ISO 4217 currency code.
+    pub fn remove_currency_requirement(&mut self, iso4217: Iso4217) -> bool {
+        let mut requirements = self.currency_requirements().to_vec();
+        let len = requirements.len();
+        requirements.retain(|data| data.iso4217 != iso4217);
+        if requirements.len() == len {
+            return false;
+        }
+        self.store_currency_requirements(requirements);
+        true
+    }
+
+    #[deprecated(
+        since = "0.9.2",
+        note = "use `add_currency_requirement` supporting multiple currencies"
+    )]
+    pub fn set_currency_requirement(
+        &mut self,
+        currency_data: CurrencyData,
+    ) -> bool {
+        if self.currency_requirements() == [currency_data.clone()] {
+            return false;
+        }
+        self.store_currency_requirements(vec![currency_data]);
+        true
+    }
+
+    pub fn set_merchant(&mut self, merchant: String) -> bool {
+        let merchant = if merchant.is_empty() {
+            None
+        } else {
+            Some(merchant)
+        };
+        if self.merchant == merchant {
+            return false;
+        }
+        self.merchant = merchant;
+        self.invalidate_signatures();
+        true
+    }
+
+    pub fn remove_merchant(&mut self) -> bool {
+        if self.merchant.is_none() {
+            return false;
+        }
+        self.merchant = None;
+        self.invalidate_signatures();
+        true
+    }
+
+    /// Returns merchant name from the structured merchant identity, falling
+    /// back to the legacy plain merchant name.
+    pub fn merchant_name(&self) -> Option<&str> {
+        self.merchant_info
+            .as_ref()
+            .map(|info| info.name.as_str())
+            .or(self.merchant.as_deref())
+    }
+
+    pub fn set_merchant_info(&mut self, merchant_info: MerchantInfo) -> bool {
+        let merchant_info = Some(merchant_info);
+        if self.merchant_info == merchant_info {
+            return false;
+        }
+        self.merchant_info = merchant_info;
+        self.invalidate_signatures();
+        true
+    }
+
+    pub fn remove_merchant_info(&mut self) -> bool {
+        if self.merchant_info.is_none() {
+            return false;
+        }
+        self.merchant_info = None;
+        self.invalidate_signatures();
+        true
+    }
+
+    pub fn set_purpose(&mut self, purpose: String) -> bool {
+        let purpose = if purpose.is_empty() {
+            None
+        } else {
+            Some(purpose)
+        };
+        if self.purpose == purpose {
+            return false;
+        }
+        self.purpose = purpose;
+        self.invalidate_signatures();
+        true
+    }
+
+    pub fn remove_purpose(&mut self) -> bool {
+        if self.purpose.is_none() {
+            return false;
+        }
+        self.purpose = None;
+        self.invalidate_signatures();
+        true
+    }
+
+    /// Sets opaque order or payment identifier, which must not exceed
+    /// [`MAX_REFERENCE_LEN`] bytes. Unlike the purpose, the reference is not
+    /// intended to be read by humans.
+    pub fn set_reference(
+        &mut self,
+        reference: Blob,
+    ) -> Result<bool, ReferenceError> {
+        check_reference(&reference)?;
+        Ok(self.set_reference_unchecked(reference))
+    }
+
+    /// Used by [`crate::InvoiceBuilder`], which validates reference on finish
+    pub(crate) fn set_reference_unchecked(&mut self, reference: Blob) -> bool {
+        let reference = Some(reference);
+        if self.reference == reference {
+            return false;
+        }
+        self.reference = reference;
+        self.invalidate_signatures();
+        true
+    }
+
+    pub fn remove_reference(&mut self) -> bool {
+        if self.reference.is_none() {
+            return false;
+        }
+        self.reference = None;
+        self.invalidate_signatures();
+        true
+    }
+
+    pub fn set_details(&mut self, details: Details) -> bool {
+        let details = Some(details);
+        if self.details == details {
+            return false;
+        }
+        self.details = details;
+        self.invalidate_signatures();
+        true
+    }
+
+    pub fn remove_details(&mut self) -> bool {
+        if self.details.is_none() {
+            return false;
+        }
+        self.details = None;
+        self.invalidate_signatures();
+        true
+    }
+
+    pub fn set_inline_details(
+        &mut self,
+        inline_details: InlineDetails,
+    ) -> bool {
+        let inline_details = Some(inline_details);
+        if self.inline_details == inline_details {
+            return false;
+        }
+        self.inline_details = inline_details;
+        self.invalidate_signatures();
+        true
+    }
+
+    pub fn remove_inline_details(&mut self) -> bool {
+        if self.inline_details.is_none() {
+            return false;
+        }
+        self.inline_details = None;
+        self.invalidate_signatures();
+        true
+    }
+
+    /// Returns flags of the main beneficiary, if it is a lightning node.
+    pub fn ln_flags(&self) -> Option<LnFlags> {
+        match &self.beneficiary {
+            Beneficiary::Bolt(address) => Some(address.ln_flags),
+            _ => None,
+        }
+    }
+
+    /// Sets flags of the main beneficiary, if it is a lightning node.
+    pub fn set_ln_flags(&mut self, flags: LnFlags) -> bool {
+        match &mut self.beneficiary {
+            Beneficiary::Bolt(address) if address.ln_flags != flags => {
+                address.ln_flags = flags;
+                self.invalidate_signatures();
+                true
+            }
+            _ => false,
+        }
+    }
+
+    pub fn set_network(&mut self, network: Network) -> bool {
+        if self.network == Some(network) {
+            return false;
+        }
+        self.network = Some(network);
+        self.invalidate_signatures();
+        true
+    }
+
+    pub fn remove_network(&mut self) -> bool {
+        if self.network.is_none() {
+            return false;
+        }
+        self.network = None;
+        self.invalidate_signatures();
+        true
+    }
+
+    /// Sets beneficiary to which the merchant should send refunds.
+    ///
+    /// Refund is usually added by the payer to an invoice already signed by
+    /// the merchant, which invalidates and removes the merchant signature.
+    /// Payers needing to present the merchant signature should pass the
+    /// refund beneficiary to the merchant separately from the signed invoice.
+    pub fn set_refund(&mut self, refund: Beneficiary) -> bool {
+        let refund = Some(refund);
+        if self.refund == refund {
+            return false;
+        }
+        self.refund = refund;
+        self.invalidate_signatures();
+        true
+    }
+
+    pub fn remove_refund(&mut self) -> bool {
+        if self.refund.is_none() {
+            return false;
+        }
+        self.refund = None;
+        self.invalidate_signatures();
+        true
+    }
+
+    /// Attaches beneficiary address proof made over the invoice id (see
+    /// [`AddressProof`]). The proof is not verified.
+    ///
+    /// Since the invoice signature commits to the proof, the existing
+    /// signatures are removed, so the proof must be attached before the
+    /// invoice is signed.
+    pub fn attach_address_proof(&mut self, proof: AddressProof) -> bool {
+        let proof = Some(proof);
+        if self.beneficiary_proof == proof {
+            return false;
+        }
+        self.remove_signature();
+        self.beneficiary_proof = proof;
+        true
+    }
+
+    /// Removes beneficiary address proof together with the invoice
+    /// signatures committing to it.
+    pub fn remove_address_proof(&mut self) -> bool {
+        if self.beneficiary_proof.is_none() {
+            return false;
+        }
+        self.remove_signature();
+        self.beneficiary_proof = None;
+        true
+    }
+
+    /// Sets requirements to on-chain payment transactions; the policy is not
+    /// validated.
+    pub fn set_onchain_policy(&mut self, policy: OnchainPolicy) -> bool {
+        let policy = Some(policy);
+        if self.onchain_policy == policy {
+            return false;
+        }
+        self.onchain_policy = policy;
+        self.invalidate_signatures();
+        true
+    }
+
+    pub fn remove_onchain_policy(&mut self) -> bool {
+        if self.onchain_policy.is_none() {
+            return false;
+        }
+        self.onchain_policy = None;
+        self.invalidate_signatures();
+        true
+    }
+
+    /// Returns consignment endpoints in the order of the issuer preference.
+    pub fn consignment_endpoints(&self) -> &[ConsignmentEndpoint] {
+        &self.consignment_endpoints.0
+    }
+
+    /// Iterates over URLs of RGB HTTP JSON-RPC consignment endpoints.
+    pub fn consignment_endpoints_http(&self) -> impl Iterator<Item = &Url> {
+        self.consignment_endpoints.iter().filter_map(
+            |endpoint| match endpoint {
+                ConsignmentEndpoint::RgbHttpJsonRpc(url) => Some(url),
+                _ => None,
+            },
+        )
+    }
+
+    /// Iterates over node addresses of Storm consignment endpoints.
+    pub fn consignment_endpoints_storm(
+        &self,
+    ) -> impl Iterator<Item = &NodeAddr> {
+        self.consignment_endpoints.iter().filter_map(
+            |endpoint| match endpoint {
+                ConsignmentEndpoint::Storm(addr) => Some(addr),
+                _ => None,
+            },
+        )
+    }
+
+    /// Returns the first consignment endpoint using a given protocol.
+    pub fn first_endpoint(
+        &self,
+        protocol: EndpointProtocol,
+    ) -> Option<&ConsignmentEndpoint> {
+        self.consignment_endpoints
+            .iter()
+            .find(|endpoint| endpoint.protocol() == protocol)
+    }
+
+    /// Adds consignment endpoint, unless it is already present or the
+    /// invoice already has [`MAX_CONSIGNMENT_ENDPOINTS`] endpoints.
+    #[cfg(feature = "rgb")]
+    pub fn add_consignment_endpoint(
+        &mut self,
+        node: ConsignmentEndpoint,
+    ) -> bool {
+        if !self.consignment_endpoints.push(node) {
+            return false;
+        }
+        self.invalidate_signatures();
+        true
+    }
+
+    #[cfg(feature = "rgb")]
+    pub fn remove_consignment_endpoint(
+        &mut self,
+        node: &ConsignmentEndpoint,
+    ) -> bool {
+        let len = self.consignment_endpoints.0.len();
+        self.consignment_endpoints
+            .0
+            .retain(|endpoint| endpoint != node);
+        if self.consignment_endpoints.0.len() == len {
+            return false;
+        }
+        self.invalidate_signatures();
+        true
+    }
+
+    /// Replaces all consignment endpoints with the provided list, removing
+    /// duplicates (the first occurrence of each endpoint is kept). Fails,
+    /// keeping the invoice unchanged, if more than
+    /// [`MAX_CONSIGNMENT_ENDPOINTS`] distinct endpoints are provided.
+    #[cfg(feature = "rgb")]
+    pub fn set_consignment_endpoints(
+        &mut self,
+        nodes: impl IntoIterator<Item = ConsignmentEndpoint>,
+    ) -> Result<bool, ConsignmentEndpointsError> {
+        let mut endpoints = ConsignmentEndpoints::default();
+        for node in nodes {
+            endpoints.push_unchecked(node);
+        }
+        endpoints.check()?;
+        if self.consignment_endpoints == endpoints {
+            return Ok(false);
+        }
+        self.consignment_endpoints = endpoints;
+        self.invalidate_signatures();
+        Ok(true)
+    }
+
+    /// Removes consignment endpoints which are equal to some preceding
+    /// endpoint after URL normalization. Decoding keeps such duplicates, so
+    /// signatures of the invoices listing the same endpoint twice remain
+    /// valid.
+    pub fn dedup_consignment_endpoints(&mut self) -> bool {
+        let endpoints = self.consignment_endpoints.0.clone();
+        let mut deduped = ConsignmentEndpoints::default();
+        for endpoint in endpoints {
+            deduped.push(endpoint);
+        }
+        if self.consignment_endpoints == deduped {
+            return false;
+        }
+        self.consignment_endpoints = deduped;
+        self.invalidate_signatures();
+        true
+    }
+
+    /// Computes hash which is signed by the invoice signature using the
+    /// current ([`SignatureScheme::Merkle`]) signature scheme.
+    ///
+    /// # Panics
+    ///
+    /// If the invoice data are inconsistent for strict serialization.
+    pub fn signature_hash(&self) -> MerkleNode {
+        self.signature_hash_with(SignatureScheme::Merkle)
+    }
+
+    /// Computes hash which is signed by the invoice signature using a specific
+    /// signature scheme. In all schemes the signature itself is not committed
+    /// to, so the hash is the same for signed and unsigned invoice.
+    ///
+    /// # Panics
+    ///
+    /// If the invoice data are inconsistent for strict serialization.
+    pub fn signature_hash_with(&self, scheme: SignatureScheme) -> MerkleNode {
+        self.try_signature_hash(scheme)
+            .expect("invoice data are inconsistent for strict serialization")
+    }
+
+    /// Returns merkle tree leaves committing to the invoice data which are
+    /// used by [`SignatureScheme::Merkle`]. The leaves are ordered as
+    /// follows:
+    /// - `version`, `amount` and `beneficiary` fields, each of them being a
+    ///   single SHA256 hash of the field strict encoding;
+    /// - each of the TLV records present in the invoice (apart from the
+    ///   issuer signature, signature scheme and co-signatures records), in
+    ///   the order of TLV types.
+    ///   Each record leaf is a single SHA256 hash of the concatenated strict
+    ///   encoding of the TLV type (`u64`) and TLV value (`u16`-length-prefixed
+    ///   byte string).
+    ///
+    /// # Panics
+    ///
+    /// If the invoice data are inconsistent for strict serialization.
+    pub fn signature_leaves(&self) -> Vec<MerkleNode> {
+        self.try_signature_leaves()
+            .expect("invoice data are inconsistent for strict serialization")
+    }
+
+    fn try_signature_leaves(
+        &self,
+    ) -> Result<Vec<MerkleNode>, strict_encoding::Error> {
+        self.try_leaves(false)
+    }
+
+    /// Computes merkle leaves over the invoice fields and TLV records, which
+    /// include the issuer signature and signature scheme records if
+    /// `with_signature` is set.
+    /// Co-signatures are never included.
+    fn try_leaves(
+        &self,
+        with_signature: bool,
+    ) -> Result<Vec<MerkleNode>, strict_encoding::Error> {
+        let mut data = Vec::with_capacity(Invoice::SERIALIZATION_CAPACITY);
+        self.strict_encode(&mut data)?;
+
+        let fields = [
+            self.version.strict_serialize()?,
+            self.amount.strict_serialize()?,
+            self.beneficiary.strict_serialize()?,
+        ];
+        let offset = fields.iter().map(Vec::len).sum::<usize>();
+        let mut leaves = fields
+            .iter()
+            .map(|field| MerkleNode::hash(field))
+            .collect::<Vec<_>>();
+        let mut record = vec![];
+        for (ty, value) in &tlv::Stream::strict_deserialize(&data[offset..])? {
+            // The signature records can't commit to themselves
+            if ((**ty == 0x00 || **ty == SIGNATURE_SCHEME_TLV_TYPE)
+                && !with_signature)
+                || **ty == COSIGNATURES_TLV_TYPE
+            {
+                continue;
+            }
+            record.clear();
+            ty.strict_encode(&mut record)?;
+            value.strict_encode(&mut record)?;
+            leaves.push(MerkleNode::hash(&record));
+        }
+        Ok(leaves)
+    }
+
+    fn try_signature_hash(
+        &self,
+        scheme: SignatureScheme,
+    ) -> Result<MerkleNode, strict_encoding::Error> {
+        Ok(match scheme {
+            SignatureScheme::Flat => {
+                let mut unsigned = self.clone();
+                unsigned.remove_signature();
+                MerkleNode::hash(&unsigned.strict_serialize()?)
+            }
+            SignatureScheme::Merkle => {
+                merklize(INVOICE_MERKLE_PREFIX, self.try_signature_leaves()?).0
+            }
+        })
+    }
+
+    /// Sets the issuer signature produced externally over
+    /// [`Invoice::signature_hash`] ([`SignatureScheme::Merkle`]), removing
+    /// co-signatures which committed to the previous one.
+    pub fn set_signature(
+        &mut self,
+        pubkey: secp256k1::PublicKey,
+        signature: schnorr::Signature,
+    ) {
+        self.remove_signature();
+        self.signature = Some((pubkey, signature));
+        self.signature_scheme = Some(SignatureScheme::Merkle);
+    }
+
+    /// Returns the issuer signature together with its scheme.
+    pub fn signature(&self) -> Option<InvoiceSignature> {
+        self.signature.map(|(pubkey, signature)| InvoiceSignature {
+            scheme: self.signature_scheme.unwrap_or(SignatureScheme::Flat),
+            pubkey,
+            signature,
+        })
+    }
+
+    /// Removes the issuer signature together with all co-signatures.
+    pub fn remove_signature(&mut self) {
+        self.signature = None;
+        self.signature_scheme = None;
+        self.cosignatures = empty!();
+    }
+
+    /// Removes all signatures and the beneficiary address proof, which
+    /// become invalid once the invoice data change.
+    pub(crate) fn invalidate_signatures(&mut self) {
+        self.remove_signature();
+        self.beneficiary_proof = None;
+    }
+
+    /// Returns co-signatures in the signing order.
+    pub fn cosignatures(&self) -> &[Cosignature] {
+        &self.cosignatures
+    }
+
+    /// Returns all invoice signatures, starting with the issuer one.
+    pub fn signatures(
+        &self,
+    ) -> Vec<(secp256k1::PublicKey, schnorr::Signature, SignerRole)> {
+        self.signature
+            .map(|(pubkey, signature)| (pubkey, signature, SignerRole::Issuer))
+            .into_iter()
+            .chain(
+                self.cosignatures
+                    .iter()
+                    .map(|cosig| (cosig.pubkey, cosig.signature, cosig.role)),
+            )
+            .collect()
+    }
+
+    /// Computes hash which is signed by the next co-signer with a given role
+    /// (see [`Cosignature`] for the details).
+    ///
+    /// # Panics
+    ///
+    /// If the invoice data are inconsistent for strict serialization.
+    pub fn cosignature_hash(&self, role: SignerRole) -> MerkleNode {
+        self.try_cosignature_hash(self.cosignatures.len(), role)
+            .expect("invoice data are inconsistent for strict serialization")
+    }
+
+    fn try_cosignature_hash(
+        &self,
+        index: usize,
+        role: SignerRole,
+    ) -> Result<MerkleNode, strict_encoding::Error> {
+        let mut leaves = self.try_leaves(true)?;
+        for cosig in &self.cosignatures[..index] {
+            leaves.push(MerkleNode::hash(&cosig.strict_serialize()?));
+        }
+        leaves.push(MerkleNode::hash(&role.strict_serialize()?));
+        Ok(merklize(INVOICE_MERKLE_PREFIX, leaves).0)
+    }
+
+    /// Adds co-signature produced externally over
+    /// [`Invoice::cosignature_hash`]. The signature is not verified.
+    pub fn add_cosignature(&mut self, cosignature: Cosignature) {
+        self.cosignatures.push(cosignature)
+    }
+
+    /// Co-signs the invoice with BIP-340 Schnorr signature, committing to
+    /// the invoice data and all of the existing signatures.
+    pub fn cosign(
+        &mut self,
+        keypair: &secp256k1::KeyPair,
+        role: SignerRole,
+    ) -> Result<(), SigningError> {
+        if role == SignerRole::Issuer {
+            return Err(SigningError::IssuerCosignature);
+        }
+        let hash = self
+            .try_cosignature_hash(self.cosignatures.len(), role)
+            .map_err(|_| SigningError::InconsistentSerialization)?;
+        let msg = secp256k1::Message::from_slice(&hash)
+            .expect("merkle node is a 32-byte hash");
+        let signature =
+            secp256k1::Secp256k1::signing_only().sign_schnorr(&msg, keypair);
+        self.cosignatures.push(Cosignature {
+            role,
+            pubkey: keypair.public_key(),
+            signature,
+        });
+        Ok(())
+    }
+
+    /// Verifies all invoice signatures, returning the result for each of
+    /// the signers in the order of [`Invoice::signatures`].
+    pub fn verify_signatures(
+        &self,
+    ) -> Vec<(SignerRole, secp256k1::PublicKey, Result<(), SignatureError>)>
+    {
+        let secp = secp256k1::Secp256k1::verification_only();
+        let issuer = self.signature().map(|sig| {
+            (
+                SignerRole::Issuer,
+                sig.pubkey,
+                self.verify_signature_with_key(sig.pubkey),
+            )
+        });
+        let cosigners =
+            self.cosignatures.iter().enumerate().map(|(index, cosig)| {
+                let result = self
+                    .try_cosignature_hash(index, cosig.role)
+                    .map_err(|_| SignatureError::InconsistentSerialization)
+                    .and_then(|hash| {
+                        let msg = secp256k1::Message::from_slice(&hash)
+                            .expect("merkle node is a 32-byte hash");
+                        let pubkey = cosig.pubkey.x_only_public_key().0;
+                        secp.verify_schnorr(&cosig.signature, &msg, &pubkey)
+                            .map_err(|_| SignatureError::InvalidSignature)
+                    });
+                (cosig.role, cosig.pubkey, result)
+            });
+        issuer.into_iter().chain(cosigners).collect()
+    }
+
+    /// Verifies invoice signature against the public key embedded into the
+    /// invoice.
+    pub fn verify_signature(&self) -> Result<(), SignatureError> {
+        let sig = self.signature().ok_or(SignatureError::NoSignature)?;
+        self.verify_signature_with_key(sig.pubkey)
+    }
+
+    /// Verifies invoice signature with [`Invoice::verify_signature`],
+    /// additionally requiring the signing key to be the merchant identity
+    /// key, if the invoice specifies one.
+    pub fn verify_signature_strict(&self) -> Result<(), SignatureError> {
+        let sig = self.signature().ok_or(SignatureError::NoSignature)?;
+        if let Some(identity_key) = self
+            .merchant_info
+            .as_ref()
+            .and_then(|info| info.identity_key)
+        {
+            if identity_key.x_only_public_key()
+                != sig.pubkey.x_only_public_key()
+            {
+                return Err(SignatureError::MerchantKeyMismatch);
+            }
+        }
+        self.verify_signature_with_key(sig.pubkey)
+    }
+
+    /// Verifies invoice signature against an externally provided (for
+    /// instance, a known merchant) public key, ignoring the key embedded into
+    /// the invoice. The signature is verified only against the hash of the
+    /// scheme specified by the signature record.
+    pub fn verify_signature_with_key(
+        &self,
+        pubkey: secp256k1::PublicKey,
+    ) -> Result<(), SignatureError> {
+        let sig = self.signature().ok_or(SignatureError::NoSignature)?;
+        let msg = self
+            .signing_message(sig.scheme)
+            .map_err(|_| SignatureError::InconsistentSerialization)?;
+        secp256k1::Secp256k1::verification_only()
+            .verify_schnorr(&sig.signature, &msg, &pubkey.x_only_public_key().0)
+            .map_err(|_| SignatureError::InvalidSignature)
+    }
+
+    /// Signs the invoice with BIP-340 Schnorr signature using the provided
+    /// key pair, replacing any existing signatures.
+    pub fn sign(
+        &mut self,
+        keypair: &secp256k1::KeyPair,
+    ) -> Result<(), SigningError> {
+        let msg = self
+            .signing_message(SignatureScheme::Merkle)
+            .map_err(|_| SigningError::InconsistentSerialization)?;
+        let signature =
+            secp256k1::Secp256k1::signing_only().sign_schnorr(&msg, keypair);
+        self.set_signature(keypair.public_key(), signature);
+        Ok(())
+    }
+
+    pub(crate) fn signing_message(
+        &self,
+        scheme: SignatureScheme,
+    ) -> Result<secp256k1::Message, strict_encoding::Error> {
+        Ok(
+            secp256k1::Message::from_slice(&self.try_signature_hash(scheme)?)
+                .expect("merkle node is a 32-byte hash"),
+        )
+    }
+}
+
+/// Checks whether the asset is the native asset of one of the standard
+/// chains (including regtest with the default genesis block) or of the
+/// `chain` provided as a context. The context is required to recognize
+/// native assets of custom signet and regtest networks, which genesis
+/// blocks are not known in advance.
+pub fn is_native_asset(asset_id: &AssetId, chain: Option<&Chain>) -> bool {
+    Chain::all_standard()
+        .iter()
+        .chain(&[Chain::from(bitcoin::Network::Regtest)])
+        .chain(chain)
+        .any(|chain| chain.native_asset() == *asset_id)
+}
+
+/// Checks whether the asset is the native asset of a given chain.
+pub fn is_native_asset_of(asset_id: &AssetId, chain: &Chain) -> bool {
+    chain.native_asset() == *asset_id
+}
+
+/// Converts RGB contract id into the invoice asset id, keeping the byte
+/// order. Note that the hex representation of the asset id is not
+/// reversed, unlike the one of the contract id inner hash. Thus for the
+/// bytes from `0x00` to `0x1f` the asset id is displayed as
+/// `000102…1e1f`, the contract id as
+/// `rgb1qqqsyqcyq5rqwzqfpg9scrgwpugpzysnzs23v9ccrydpk8qarc0s5a5nga` and its
+/// inner hash as `1f1e1d…0100`.
+#[cfg(feature = "rgb")]
+pub fn contract_id_to_asset_id(contract_id: rgb::ContractId) -> AssetId {
+    AssetId::from_inner(contract_id.into_inner().into_inner())
+}
+
+/// Converts invoice asset id into RGB contract id, keeping the byte order;
+/// reverse of [`contract_id_to_asset_id`].
+#[cfg(feature = "rgb")]
+pub fn asset_id_to_contract_id(asset_id: AssetId) -> rgb::ContractId {
+    rgb::ContractId::from_inner(sha256t::Hash::from_inner(
+        asset_id.into_inner(),
+    ))
+}
+
+#[derive(Clone, Ord, PartialOrd, Eq, PartialEq, Hash, Debug, Display)]
+#[non_exhaustive]
+pub enum AssetClass {
+    #[display("native chain asset")]
     Native,
     #[cfg(feature = "rgb")]
+    #[display("RGB asset {0}")]
     Rgb(rgb::ContractId),
     #[cfg(not(feature = "rgb"))]
+    #[display("asset {0}")]
     Other(AssetId),
+    #[cfg(feature = "elements")]
+    #[display("Liquid asset {0}")]
+    Liquid(AssetId),
+    #[display("native asset of a different chain")]
     InvalidNativeChain,
 }
 
-#[derive(Clone, PartialEq, Eq, Debug)]
-pub struct BeneficiariesIter<'a> {
-    invoice: &'a Invoice,
-    index: usize,
-}
+/// Blockchain network the invoice is issued for.
+///
+/// Used to detect incompatibility between the payer wallet and the invoice
+/// early, before the payment is attempted.
+///
+/// Strict encoding uses a single-byte tag, followed by the genesis block
+/// hash for custom networks. The tags are stable, and tags unknown to this
+/// version of the library are decoded as [`Network::Unknown`]:
+/// - `0x00`: [`Network::Mainnet`];
+/// - `0x01`: [`Network::Testnet3`];
+/// - `0x02`: [`Network::Signet`];
+/// - `0x03`: [`Network::Regtest`];
+/// - `0x04`: [`Network::LiquidV1`];
+/// - `0x05`: [`Network::Testnet4`];
+/// - `0x06`: [`Network::SignetCustom`];
+/// - `0x07`: [`Network::LiquidTestnet`];
+/// - `0x08`: [`Network::RegtestCustom`].
+///
+/// Networks with tags unknown to this version of the library keep the rest
+/// of the TLV record value in [`NetworkParams`], so such invoices re-encode
+/// to the same data and keep their signatures valid.
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Hash, Debug)]
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    serde(crate = "serde_crate", rename_all = "lowercase")
+)]
+#[non_exhaustive]
+pub enum Network {
+    Mainnet,
+    Testnet3,
+    /// Default bitcoin signet
+    Signet,
+    /// Bitcoin regtest with the default genesis block
+    Regtest,
+    LiquidV1,
+    Testnet4,
+    /// Private bitcoin signet with a given genesis block hash
+    SignetCustom(BlockHash),
+    LiquidTestnet,
+    /// Bitcoin regtest with a non-default genesis block hash
+    RegtestCustom(BlockHash),
+    /// Network unknown to this version of the library, identified by its
+    /// strict encoding tag and followed by the raw network parameters. Tags
+    /// of the known networks (up to [`Network::MAX_KNOWN_TAG`]) are not
+    /// allowed and fail encoding.
+    Unknown(
+        u8,
+        #[cfg_attr(
+            feature = "serde",
+            serde(with = "As::<serde_with::hex::Hex>")
+        )]
+        NetworkParams,
+    ),
+}
+
+impl Network {
+    /// The largest strict encoding tag of the networks known to this
+    /// version of the library
+    pub const MAX_KNOWN_TAG: u8 = 8;
+}
+
+/// Raw parameters of a network unknown to this version of the library.
+///
+/// Parameters are stored inline, keeping [`Network`] `Copy`, and are limited
+/// to [`NetworkParams::MAX_LEN`] bytes: enough for a genesis block hash,
+/// which is the only parameter of the known custom networks.
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Default)]
+pub struct NetworkParams {
+    len: u8,
+    data: [u8; NetworkParams::MAX_LEN],
+}
+
+impl NetworkParams {
+    /// The maximal length of the unknown network parameters
+    pub const MAX_LEN: usize = 32;
+
+    pub fn as_slice(&self) -> &[u8] {
+        &self.data[..self.len as usize]
+    }
+
+    pub fn len(&self) -> usize {
+        self.len as usize
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+}
+
+impl AsRef<[u8]> for NetworkParams {
+    fn as_ref(&self) -> &[u8] {
+        self.as_slice()
+    }
+}
+
+impl TryFrom<&[u8]> for NetworkParams {
+    type Error = NetworkParamsTooLong;
+
+    fn try_from(slice: &[u8]) -> Result<Self, Self::Error> {
+        if slice.len() > NetworkParams::MAX_LEN {
+            return Err(NetworkParamsTooLong(slice.len()));
+        }
+        let mut data = [0u8; NetworkParams::MAX_LEN];
+        data[..slice.len()].copy_from_slice(slice);
+        Ok(NetworkParams {
+            len: slice.len() as u8,
+            data,
+        })
+    }
+}
+
+impl TryFrom<Vec<u8>> for NetworkParams {
+    type Error = NetworkParamsTooLong;
+
+    fn try_from(vec: Vec<u8>) -> Result<Self, Self::Error> {
+        NetworkParams::try_from(vec.as_slice())
+    }
+}
+
+impl Ord for NetworkParams {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.as_slice().cmp(other.as_slice())
+    }
+}
+
+impl PartialOrd for NetworkParams {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl fmt::Debug for NetworkParams {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "NetworkParams({})", self.as_slice().to_hex())
+    }
+}
+
+/// Error constructing parameters of an unknown network longer than
+/// [`NetworkParams::MAX_LEN`] bytes.
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug, Display, Error)]
+#[display("unknown network parameters of {0} bytes exceed 32 bytes limit")]
+pub struct NetworkParamsTooLong(pub usize);
+
+impl StrictEncode for Network {
+    fn strict_encode<E: io::Write>(
+        &self,
+        mut e: E,
+    ) -> Result<usize, strict_encoding::Error> {
+        Ok(match self {
+            Network::Mainnet => 0u8.strict_encode(&mut e)?,
+            Network::Testnet3 => 1u8.strict_encode(&mut e)?,
+            Network::Signet => 2u8.strict_encode(&mut e)?,
+            Network::Regtest => 3u8.strict_encode(&mut e)?,
+            Network::LiquidV1 => 4u8.strict_encode(&mut e)?,
+            Network::Testnet4 => 5u8.strict_encode(&mut e)?,
+            Network::SignetCustom(hash) => strict_encode_list!(e; 6u8, hash),
+            Network::LiquidTestnet => 7u8.strict_encode(&mut e)?,
+            Network::RegtestCustom(hash) => strict_encode_list!(e; 8u8, hash),
+            Network::Unknown(tag, _) if *tag <= Network::MAX_KNOWN_TAG => {
+                return Err(strict_encoding::Error::DataIntegrityError(
+                    format!(
+                        "network tag {} is reserved for known networks",
+                        tag
+                    ),
+                ))
+            }
+            Network::Unknown(tag, params) => {
+                e.write_all(&[*tag])?;
+                e.write_all(params.as_slice())?;
+                1 + params.len()
+            }
+        })
+    }
+}
+
+impl StrictDecode for Network {
+    fn strict_decode<D: io::Read>(
+        mut d: D,
+    ) -> Result<Self, strict_encoding::Error> {
+        Ok(match u8::strict_decode(&mut d)? {
+            0 => Network::Mainnet,
+            1 => Network::Testnet3,
+            2 => Network::Signet,
+            3 => Network::Regtest,
+            4 => Network::LiquidV1,
+            5 => Network::Testnet4,
+            6 => Network::SignetCustom(BlockHash::strict_decode(&mut d)?),
+            7 => Network::LiquidTestnet,
+            8 => Network::RegtestCustom(BlockHash::strict_decode(&mut d)?),
+            tag => {
+                let mut params = vec![];
+                d.read_to_end(&mut params)?;
+                let params =
+                    NetworkParams::try_from(params).map_err(|err| {
+                        strict_encoding::Error::DataIntegrityError(
+                            err.to_string(),
+                        )
+                    })?;
+                Network::Unknown(tag, params)
+            }
+        })
+    }
+}
+
+/// Error converting chain into invoice network.
+#[derive(Clone, Eq, PartialEq, Debug, Display, Error)]
+#[display("chain {0} is not supported by the invoice network field")]
+pub struct UnsupportedChain(pub Chain);
+
+/// Error converting invoice network into chain.
+#[derive(
+    Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Hash, Debug, Display, Error,
+)]
+#[display("network {0} has no corresponding chain definition")]
+pub struct UnsupportedNetwork(pub Network);
+
+/// Error parsing invoice network name.
+#[derive(Clone, Eq, PartialEq, Hash, Debug, Display, Error)]
+#[display("unknown network `{0}`")]
+pub struct UnknownNetwork(pub String);
+
+const GENESIS_HASH_TESTNET4: &str =
+    "00000000da84f2bafbbc53dee25a72ae507ff4914b867c565be350b0da8bf043";
+const GENESIS_HASH_LIQUID_TESTNET: &str =
+    "a771da8e52ee6ad581ed1e9a99825e5b3b7992225534eaa2ae23244fe26ab1c1";
+
+impl TryFrom<Chain> for Network {
+    type Error = UnsupportedChain;
+
+    fn try_from(chain: Chain) -> Result<Self, Self::Error> {
+        let regtest = Chain::from(bitcoin::Network::Regtest);
+        Ok(match chain {
+            Chain::Mainnet => Network::Mainnet,
+            Chain::Testnet3 => Network::Testnet3,
+            Chain::Signet => Network::Signet,
+            Chain::Regtest(_) if chain == regtest => Network::Regtest,
+            Chain::Regtest(hash) => Network::RegtestCustom(hash),
+            Chain::SignetCustom(_) if chain == Chain::Signet => Network::Signet,
+            Chain::SignetCustom(hash) => Network::SignetCustom(hash),
+            Chain::LiquidV1 => Network::LiquidV1,
+            Chain::Other(ref params)
+                if params.genesis_hash.to_hex() == GENESIS_HASH_TESTNET4 =>
+            {
+                Network::Testnet4
+            }
+            Chain::Other(ref params)
+                if params.genesis_hash.to_hex()
+                    == GENESIS_HASH_LIQUID_TESTNET =>
+            {
+                Network::LiquidTestnet
+            }
+            _ => return Err(UnsupportedChain(chain)),
+        })
+    }
+}
+
+impl Network {
+    /// Detects networks using mainnet encoding of addresses and extended
+    /// keys.
+    pub fn is_mainnet(self) -> bool {
+        matches!(self, Network::Mainnet | Network::LiquidV1)
+    }
+
+    /// Checks whether bitcoin addresses of a given network can belong to
+    /// this network. Signet and testnet4 addresses use the same encoding as
+    /// testnet3 ones, so testnet addresses are accepted for them.
+    pub fn matches_address_network(self, network: bitcoin::Network) -> bool {
+        match (self, network) {
+            (
+                Network::Signet | Network::SignetCustom(_) | Network::Testnet4,
+                bitcoin::Network::Testnet,
+            ) => true,
+            (Network::SignetCustom(_), bitcoin::Network::Signet) => true,
+            (Network::RegtestCustom(_), bitcoin::Network::Regtest) => true,
+            (this, network) => this == Network::from(network),
+        }
+    }
+}
+
+impl Display for Network {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Network::Mainnet => f.write_str("mainnet"),
+            Network::Testnet3 => f.write_str("testnet3"),
+            Network::Signet => f.write_str("signet"),
+            Network::Regtest => f.write_str("regtest"),
+            Network::LiquidV1 => f.write_str("liquidv1"),
+            Network::Testnet4 => f.write_str("testnet4"),
+            Network::SignetCustom(hash) => write!(f, "signetcustom:{}", hash),
+            Network::LiquidTestnet => f.write_str("liquidtestnet"),
+            Network::RegtestCustom(hash) => {
+                write!(f, "regtestcustom:{}", hash)
+            }
+            Network::Unknown(tag, params) if params.is_empty() => {
+                write!(f, "unknown:{}", tag)
+            }
+            Network::Unknown(tag, params) => {
+                write!(f, "unknown:{}:{}", tag, params.as_slice().to_hex())
+            }
+        }
+    }
+}
+
+impl FromStr for Network {
+    type Err = UnknownNetwork;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let unknown = || UnknownNetwork(s.to_owned());
+        Ok(match s.split_once(':') {
+            None => match s {
+                "mainnet" => Network::Mainnet,
+                "testnet3" => Network::Testnet3,
+                "signet" => Network::Signet,
+                "regtest" => Network::Regtest,
+                "liquidv1" => Network::LiquidV1,
+                "testnet4" => Network::Testnet4,
+                "liquidtestnet" => Network::LiquidTestnet,
+                _ => return Err(unknown()),
+            },
+            Some(("signetcustom", hash)) => Network::SignetCustom(
+                BlockHash::from_hex(hash).map_err(|_| unknown())?,
+            ),
+            Some(("regtestcustom", hash)) => Network::RegtestCustom(
+                BlockHash::from_hex(hash).map_err(|_| unknown())?,
+            ),
+            Some(("unknown", data)) => {
+                let (tag, params) = match data.split_once(':') {
+                    Some((tag, params)) => (
+                        tag,
+                        Vec::<u8>::from_hex(params)
+                            .ok()
+                            .and_then(|params| {
+                                NetworkParams::try_from(params).ok()
+                            })
+                            .ok_or_else(unknown)?,
+                    ),
+                    None => (data, NetworkParams::default()),
+                };
+                match tag.parse().map_err(|_| unknown())? {
+                    tag if tag <= Network::MAX_KNOWN_TAG => {
+                        return Err(unknown())
+                    }
+                    tag => Network::Unknown(tag, params),
+                }
+            }
+            Some(_) => return Err(unknown()),
+        })
+    }
+}
+
+impl From<bitcoin::Network> for Network {
+    fn from(network: bitcoin::Network) -> Self {
+        match network {
+            bitcoin::Network::Bitcoin => Network::Mainnet,
+            bitcoin::Network::Testnet => Network::Testnet3,
+            bitcoin::Network::Signet => Network::Signet,
+            bitcoin::Network::Regtest => Network::Regtest,
+        }
+    }
+}
+
+/// Converts the network into the chain. Testnet4 and Liquid testnet have no
+/// dedicated [`Chain`] variants and are not convertible.
+impl TryFrom<Network> for Chain {
+    type Error = UnsupportedNetwork;
+
+    fn try_from(network: Network) -> Result<Self, Self::Error> {
+        Ok(match network {
+            Network::Mainnet => Chain::Mainnet,
+            Network::Testnet3 => Chain::Testnet3,
+            Network::Signet => Chain::Signet,
+            Network::Regtest => Chain::from(bitcoin::Network::Regtest),
+            Network::LiquidV1 => Chain::LiquidV1,
+            Network::SignetCustom(hash) => Chain::SignetCustom(hash),
+            Network::RegtestCustom(hash) => Chain::Regtest(hash),
+            Network::Testnet4
+            | Network::LiquidTestnet
+            | Network::Unknown(..) => return Err(UnsupportedNetwork(network)),
+        })
+    }
+}
+
+/// Set of beneficiary kinds which payer wallet is able to pay to.
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Hash, Debug, Default)]
+pub struct PayerCapabilities(u16);
+
+impl PayerCapabilities {
+    /// Paying to on-chain addresses
+    pub const ON_CHAIN_ADDRESS: Self = PayerCapabilities(0x01);
+    /// Paying to output descriptors
+    pub const DESCRIPTOR: Self = PayerCapabilities(0x02);
+    /// Completing PSBT transaction templates
+    pub const PSBT: Self = PayerCapabilities(0x04);
+    /// Paying to lightning nodes
+    pub const LIGHTNING_BOLT: Self = PayerCapabilities(0x08);
+    /// Paying RGB assets to blinded UTXOs
+    pub const RGB_BLIND_UTXO: Self = PayerCapabilities(0x10);
+    /// Accepting beneficiaries of unknown kinds, which payer is trusted to
+    /// interpret by itself
+    pub const UNKNOWN_OK: Self = PayerCapabilities(0x20);
+    /// Deriving BIP-352 silent payment outputs
+    pub const SILENT_PAYMENT: Self = PayerCapabilities(0x40);
+    /// Paying to BIP-47 payment codes
+    pub const PAYMENT_CODE: Self = PayerCapabilities(0x80);
+    /// Paying to Bifrost nodes
+    pub const BIFROST: Self = PayerCapabilities(0x100);
+    /// Paying RGB assets to outputs of the paying transaction
+    pub const RGB_WITNESS_VOUT: Self = PayerCapabilities(0x200);
+    /// Paying to Liquid addresses, including blinding of confidential
+    /// payments
+    pub const LIQUID_ADDRESS: Self = PayerCapabilities(0x400);
+
+    /// Capabilities of a wallet which can't pay anything.
+    pub const fn empty() -> Self {
+        PayerCapabilities(0)
+    }
+
+    /// Capabilities of a wallet which can pay to any beneficiary.
+    pub const fn all() -> Self {
+        PayerCapabilities(0x7FF)
+    }
+
+    pub const fn contains(self, other: Self) -> bool {
+        self.0 & other.0 == other.0
+    }
+
+    /// Checks whether the payer is able to pay to the given beneficiary.
+    pub fn supports(self, beneficiary: &Beneficiary) -> bool {
+        let required = match beneficiary {
+            Beneficiary::Address(_) => Self::ON_CHAIN_ADDRESS,
+            Beneficiary::Descriptor(_) | Beneficiary::TrackingDescriptor(_) => {
+                Self::DESCRIPTOR
+            }
+            Beneficiary::Psbt(_) => Self::PSBT,
+            Beneficiary::Bolt(_) => Self::LIGHTNING_BOLT,
+            Beneficiary::BlindUtxo(_) => Self::RGB_BLIND_UTXO,
+            Beneficiary::Unknown(_) => Self::UNKNOWN_OK,
+            Beneficiary::SilentPayment(_) => Self::SILENT_PAYMENT,
+            Beneficiary::PaymentCode(_) => Self::PAYMENT_CODE,
+            Beneficiary::Bifrost(_) => Self::BIFROST,
+            Beneficiary::WitnessVoutSeal(_) => Self::RGB_WITNESS_VOUT,
+            #[cfg(feature = "elements")]
+            Beneficiary::LiquidAddress(_) => Self::LIQUID_ADDRESS,
+        };
+        self.contains(required)
+    }
+}
+
+impl BitOr for PayerCapabilities {
+    type Output = Self;
+
+    fn bitor(self, rhs: Self) -> Self::Output {
+        PayerCapabilities(self.0 | rhs.0)
+    }
+}
+
+impl BitOrAssign for PayerCapabilities {
+    fn bitor_assign(&mut self, rhs: Self) {
+        self.0 |= rhs.0
+    }
+}
+
+impl BitAnd for PayerCapabilities {
+    type Output = Self;
+
+    fn bitand(self, rhs: Self) -> Self::Output {
+        PayerCapabilities(self.0 & rhs.0)
+    }
+}
+
+/// Iterator over invoice beneficiaries, starting with the main beneficiary
+/// and followed by the alternative ones.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct BeneficiariesIter<'a> {
+    invoice: &'a Invoice,
+    front: usize,
+    back: usize,
+}
+
+impl<'a> BeneficiariesIter<'a> {
+    fn get(&self, index: usize) -> Option<&'a Beneficiary> {
+        match index {
+            0 => Some(&self.invoice.beneficiary),
+            index => self.invoice.alt_beneficiaries.get(index - 1),
+        }
+    }
+}
+
+impl<'a> Iterator for BeneficiariesIter<'a> {
+    type Item = &'a Beneficiary;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.front >= self.back {
+            return None;
+        }
+        self.front += 1;
+        self.get(self.front - 1)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let len = self.back - self.front;
+        (len, Some(len))
+    }
+}
+
+impl<'a> DoubleEndedIterator for BeneficiariesIter<'a> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        if self.front >= self.back {
+            return None;
+        }
+        self.back -= 1;
+        self.get(self.back)
+    }
+}
+
+impl<'a> ExactSizeIterator for BeneficiariesIter<'a> {}
+
+impl<'a> FusedIterator for BeneficiariesIter<'a> {}
+
+impl<'a> IntoIterator for &'a Invoice {
+    type Item = &'a Beneficiary;
+    type IntoIter = BeneficiariesIter<'a>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.beneficiaries()
+    }
+}
+
+/// Errors parsing [`Url`].
+#[derive(Clone, Eq, PartialEq, Hash, Debug, Display, Error)]
+#[display(doc_comments)]
+pub enum UrlError {
+    /// invalid URL `{url}`: {reason}
+    Invalid { url: String, reason: String },
+
+    /// URL `{0}` has no host
+    NoHost(String),
+
+    /// URL scheme `{0}` is not supported
+    UnsupportedScheme(String),
+
+    /// URL must not contain embedded credentials
+    Credentials,
+}
+
+/// HTTP(S) URL referencing an external resource, like price provider or
+/// consignment endpoint.
+///
+/// Only `http` and `https` URLs without embedded credentials are allowed;
+/// hosts may be Tor onion addresses. Parsing normalizes the URL and strips
+/// trailing slashes from its path. Strict decoding keeps the encoded string
+/// verbatim, so signatures of already issued invoices remain valid, while
+/// comparison always uses the normalized URL.
+#[derive(Clone, Debug)]
+pub struct Url {
+    string: String,
+    normalized: url::Url,
+}
+
+impl Url {
+    const HTTP_SCHEMES: &'static [&'static str] = &["http", "https"];
+
+    fn normalize(s: &str, schemes: &[&str]) -> Result<url::Url, UrlError> {
+        let mut url = url::Url::parse(s).map_err(|err| UrlError::Invalid {
+            url: s.to_owned(),
+            reason: err.to_string(),
+        })?;
+        if !schemes.contains(&url.scheme()) {
+            return Err(UrlError::UnsupportedScheme(url.scheme().to_owned()));
+        }
+        if !url.username().is_empty() || url.password().is_some() {
+            return Err(UrlError::Credentials);
+        }
+        if url.host().is_none() {
+            return Err(UrlError::NoHost(s.to_owned()));
+        }
+        let path = url.path().trim_end_matches('/').to_owned();
+        url.set_path(&path);
+        Ok(url)
+    }
+
+    /// Constructs URL from its strict-encoded string, keeping the string
+    /// verbatim.
+    fn with_encoded(
+        string: String,
+        schemes: &[&str],
+    ) -> Result<Url, strict_encoding::Error> {
+        let normalized = Url::normalize(&string, schemes).map_err(|err| {
+            strict_encoding::Error::DataIntegrityError(err.to_string())
+        })?;
+        Ok(Url { string, normalized })
+    }
+
+    /// Returns URL string, as it was parsed or decoded.
+    pub fn as_str(&self) -> &str {
+        &self.string
+    }
+
+    /// Returns normalized URL.
+    pub fn to_url(&self) -> url::Url {
+        self.normalized.clone()
+    }
+
+    /// Detects whether URL host is a Tor onion address.
+    pub fn is_onion(&self) -> bool {
+        self.normalized
+            .host_str()
+            .map(|host| host.ends_with(".onion"))
+            .unwrap_or_default()
+    }
+}
+
+impl PartialEq for Url {
+    fn eq(&self, other: &Self) -> bool {
+        self.normalized == other.normalized
+    }
+}
+
+impl Eq for Url {}
+
+impl Ord for Url {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.normalized.cmp(&other.normalized)
+    }
+}
+
+impl PartialOrd for Url {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl std::hash::Hash for Url {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        self.normalized.hash(state)
+    }
+}
+
+impl Display for Url {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.string)
+    }
+}
+
+impl FromStr for Url {
+    type Err = UrlError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let normalized = Url::normalize(s, Url::HTTP_SCHEMES)?;
+        Ok(Url {
+            string: normalized.to_string(),
+            normalized,
+        })
+    }
+}
+
+impl TryFrom<url::Url> for Url {
+    type Error = UrlError;
+
+    fn try_from(url: url::Url) -> Result<Self, Self::Error> {
+        Url::from_str(url.as_str())
+    }
+}
+
+impl StrictEncode for Url {
+    fn strict_encode<E: io::Write>(
+        &self,
+        e: E,
+    ) -> Result<usize, strict_encoding::Error> {
+        self.string.strict_encode(e)
+    }
+}
+
+impl StrictDecode for Url {
+    fn strict_decode<D: io::Read>(
+        d: D,
+    ) -> Result<Self, strict_encoding::Error> {
+        Url::with_encoded(String::strict_decode(d)?, Url::HTTP_SCHEMES)
+    }
+}
+
+/// WebSocket URL of a consignment endpoint. Follows the same rules as
+/// [`Url`], except that only `ws` and `wss` schemes are allowed.
+#[derive(Clone, Ord, PartialOrd, Eq, PartialEq, Hash, Debug, Display)]
+#[display(inner)]
+pub struct WsUrl(Url);
+
+impl WsUrl {
+    const SCHEMES: &'static [&'static str] = &["ws", "wss"];
+
+    /// Returns URL string, as it was parsed or decoded.
+    pub fn as_str(&self) -> &str {
+        self.0.as_str()
+    }
+
+    /// Returns normalized URL.
+    pub fn to_url(&self) -> url::Url {
+        self.0.to_url()
+    }
+
+    /// Detects whether URL host is a Tor onion address.
+    pub fn is_onion(&self) -> bool {
+        self.0.is_onion()
+    }
+}
+
+impl FromStr for WsUrl {
+    type Err = UrlError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let normalized = Url::normalize(s, WsUrl::SCHEMES)?;
+        Ok(WsUrl(Url {
+            string: normalized.to_string(),
+            normalized,
+        }))
+    }
+}
+
+impl StrictEncode for WsUrl {
+    fn strict_encode<E: io::Write>(
+        &self,
+        e: E,
+    ) -> Result<usize, strict_encoding::Error> {
+        self.0.strict_encode(e)
+    }
+}
+
+impl StrictDecode for WsUrl {
+    fn strict_decode<D: io::Read>(
+        d: D,
+    ) -> Result<Self, strict_encoding::Error> {
+        Url::with_encoded(String::strict_decode(d)?, WsUrl::SCHEMES).map(WsUrl)
+    }
+}
+
+/// An endpoint to a consignment exchange medium.
+///
+/// Strict encoding starts with a single-byte protocol tag:
+/// - `0x00`: [`ConsignmentEndpoint::Storm`];
+/// - `0x01`: [`ConsignmentEndpoint::RgbHttpJsonRpc`];
+/// - `0x02`: [`ConsignmentEndpoint::RgbWebSocket`];
+/// - `0x03`: [`ConsignmentEndpoint::Bifrost`].
+///
+/// Endpoints with tags starting from `0x02` wrap their data into a byte
+/// string with 16-bit length prefix, so endpoints of protocols added in the
+/// future are decoded as [`ConsignmentEndpoint::Unknown`].
+#[derive(Clone, Ord, PartialOrd, Eq, PartialEq, Hash, Debug, Display)]
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    serde(crate = "serde_crate")
+)]
+#[display(inner)]
+#[non_exhaustive]
+pub enum ConsignmentEndpoint {
+    /// Storm protocol
+    #[display("storm:{0}")]
+    Storm(NodeAddr),
+
+    /// RGB HTTP JSON-RPC protocol
+    #[display("rgbhttpjsonrpc:{0}")]
+    RgbHttpJsonRpc(
+        #[cfg_attr(feature = "serde", serde(with = "As::<DisplayFromStr>"))]
+        Url,
+    ),
+
+    /// RGB proxy WebSocket protocol, pushing consignments to the receiver
+    #[display("rgbws:{0}")]
+    RgbWebSocket(
+        #[cfg_attr(feature = "serde", serde(with = "As::<DisplayFromStr>"))]
+        WsUrl,
+    ),
+
+    /// Bifrost node relaying consignments over LN transport
+    #[display("bifrost:{0}")]
+    Bifrost(NodeAddr),
+
+    /// Endpoint of a protocol unknown to this version of the library. The
+    /// protocol tag must differ from the tags of the known protocols.
+    #[display("unknown:{protocol}:{data}")]
+    Unknown { protocol: u8, data: Blob },
+}
+
+/// Protocol of a consignment endpoint, displayed as the endpoint prefix.
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Hash, Debug, Display)]
+#[non_exhaustive]
+pub enum EndpointProtocol {
+    #[display("storm")]
+    Storm,
+    #[display("rgbhttpjsonrpc")]
+    RgbHttpJsonRpc,
+    #[display("rgbws")]
+    RgbWebSocket,
+    #[display("bifrost")]
+    Bifrost,
+    #[display("unknown")]
+    Unknown(u8),
+}
+
+impl ConsignmentEndpoint {
+    pub fn protocol(&self) -> EndpointProtocol {
+        match self {
+            ConsignmentEndpoint::Storm(_) => EndpointProtocol::Storm,
+            ConsignmentEndpoint::RgbHttpJsonRpc(_) => {
+                EndpointProtocol::RgbHttpJsonRpc
+            }
+            ConsignmentEndpoint::RgbWebSocket(_) => {
+                EndpointProtocol::RgbWebSocket
+            }
+            ConsignmentEndpoint::Bifrost(_) => EndpointProtocol::Bifrost,
+            ConsignmentEndpoint::Unknown { protocol, .. } => {
+                EndpointProtocol::Unknown(*protocol)
+            }
+        }
+    }
+}
+
+impl StrictEncode for ConsignmentEndpoint {
+    fn strict_encode<E: io::Write>(
+        &self,
+        mut e: E,
+    ) -> Result<usize, strict_encoding::Error> {
+        Ok(match self {
+            ConsignmentEndpoint::Storm(addr) => {
+                strict_encode_list!(e; 0u8, addr)
+            }
+            ConsignmentEndpoint::RgbHttpJsonRpc(url) => {
+                strict_encode_list!(e; 1u8, url)
+            }
+            ConsignmentEndpoint::RgbWebSocket(url) => {
+                let data = Blob::from(url.strict_serialize()?);
+                strict_encode_list!(e; 2u8, data)
+            }
+            ConsignmentEndpoint::Bifrost(addr) => {
+                let data = Blob::from(addr.strict_serialize()?);
+                strict_encode_list!(e; 3u8, data)
+            }
+            ConsignmentEndpoint::Unknown { protocol, data } => {
+                strict_encode_list!(e; protocol, data)
+            }
+        })
+    }
+}
+
+impl StrictDecode for ConsignmentEndpoint {
+    fn strict_decode<D: io::Read>(
+        mut d: D,
+    ) -> Result<Self, strict_encoding::Error> {
+        Ok(match u8::strict_decode(&mut d)? {
+            0 => ConsignmentEndpoint::Storm(NodeAddr::strict_decode(&mut d)?),
+            1 => {
+                ConsignmentEndpoint::RgbHttpJsonRpc(Url::strict_decode(&mut d)?)
+            }
+            protocol => {
+                let data = Blob::strict_decode(&mut d)?;
+                match protocol {
+                    2 => ConsignmentEndpoint::RgbWebSocket(
+                        WsUrl::strict_deserialize(&data)?,
+                    ),
+                    3 => ConsignmentEndpoint::Bifrost(
+                        NodeAddr::strict_deserialize(&data)?,
+                    ),
+                    _ => ConsignmentEndpoint::Unknown { protocol, data },
+                }
+            }
+        })
+    }
+}
+
+/// Incorrect consignment endpoint format
+#[derive(Clone, Eq, PartialEq, Hash, Debug, Display, Error)]
+#[display(doc_comments)]
+pub enum ConsignmentEndpointParseError {
+    /// consignment endpoint `{0}` must start with the protocol name
+    /// followed by `:`
+    NoProtocol(String),
+
+    /// consignment endpoint protocol `{0}` is unknown
+    UnknownProtocol(String),
+
+    /// invalid Storm consignment endpoint node address `{0}`
+    Storm(String),
+
+    /// invalid RGB HTTP JSON-RPC consignment endpoint: {0}
+    RgbHttpJsonRpc(UrlError),
+
+    /// invalid RGB WebSocket consignment endpoint: {0}
+    RgbWebSocket(UrlError),
+
+    /// invalid Bifrost consignment endpoint node address `{0}`
+    Bifrost(String),
+
+    /// invalid consignment endpoint `{0}` of unknown protocol; it must be in
+    /// form of `unknown:<protocol tag>:<bech32 data>`
+    Unknown(String),
+}
+
+impl FromStr for ConsignmentEndpoint {
+    type Err = ConsignmentEndpointParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.split_once(':') {
+            Some((protocol, endpoint)) => match protocol {
+                "storm" => Ok(ConsignmentEndpoint::Storm(
+                    NodeAddr::from_str(endpoint).map_err(|_| {
+                        ConsignmentEndpointParseError::Storm(
+                            endpoint.to_owned(),
+                        )
+                    })?,
+                )),
+                "rgbhttpjsonrpc" => Ok(ConsignmentEndpoint::RgbHttpJsonRpc(
+                    Url::from_str(endpoint).map_err(
+                        ConsignmentEndpointParseError::RgbHttpJsonRpc,
+                    )?,
+                )),
+                "rgbws" => Ok(ConsignmentEndpoint::RgbWebSocket(
+                    WsUrl::from_str(endpoint)
+                        .map_err(ConsignmentEndpointParseError::RgbWebSocket)?,
+                )),
+                "bifrost" => Ok(ConsignmentEndpoint::Bifrost(
+                    NodeAddr::from_str(endpoint).map_err(|_| {
+                        ConsignmentEndpointParseError::Bifrost(
+                            endpoint.to_owned(),
+                        )
+                    })?,
+                )),
+                "unknown" => {
+                    let invalid =
+                        || ConsignmentEndpointParseError::Unknown(s.to_owned());
+                    let (protocol, data) =
+                        endpoint.split_once(':').ok_or_else(invalid)?;
+                    let protocol = protocol.parse().map_err(|_| invalid())?;
+                    if protocol <= 3 {
+                        return Err(invalid());
+                    }
+                    Ok(ConsignmentEndpoint::Unknown {
+                        protocol,
+                        data: Blob::from_str(data).map_err(|_| invalid())?,
+                    })
+                }
+                _ => Err(ConsignmentEndpointParseError::UnknownProtocol(
+                    protocol.to_owned(),
+                )),
+            },
+            _ => Err(ConsignmentEndpointParseError::NoProtocol(s.to_owned())),
+        }
+    }
+}
+
+#[derive(
+    Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Debug, Default, Display, From,
+)]
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    serde(crate = "serde_crate", rename_all = "camelCase")
+)]
+#[non_exhaustive]
+pub enum Recurrent {
+    #[default]
+    #[display("non-recurrent")]
+    NonRecurrent,
+
+    #[display("each {0} seconds")]
+    Seconds(u64),
+
+    #[display("each {0} months")]
+    Months(u8),
+
+    #[display("each {0} years")]
+    Years(u8),
+
+    #[display("each {0} days")]
+    Days(u16),
+
+    #[display("each {0} weeks")]
+    Weeks(u16),
+
+    /// Recurrence type introduced by a newer version of the library. Keeps
+    /// the strict encoding tag together with the undecoded schedule
+    /// parameters, so the invoice signature stays verifiable; payment
+    /// schedules can't be computed for it. Tags up to
+    /// [`Recurrent::MAX_KNOWN_TAG`] fail encoding.
+    #[display("unknown recurrence type {0}")]
+    Unknown(
+        u8,
+        #[cfg_attr(
+            feature = "serde",
+            serde(with = "As::<serde_with::hex::Hex>")
+        )]
+        Vec<u8>,
+    ),
+}
+
+impl Recurrent {
+    /// The largest strict encoding tag of the recurrence types known to this
+    /// version of the library
+    pub const MAX_KNOWN_TAG: u8 = 5;
+
+    /// Iterates over the value unless the payment is non-recurrent. Used by
+    /// the TLV encoding derivation, which omits the record for empty values.
+    #[doc(hidden)]
+    #[inline]
+    pub fn iter(&self) -> std::option::IntoIter<Recurrent> {
+        match self {
+            Recurrent::NonRecurrent => None,
+            _ => Some(self.clone()),
+        }
+        .into_iter()
+    }
+
+    /// Computes the end of a recurrence period starting at `start`. Month-
+    /// and year-based periods are clamped to the end of the month (i.e. a
+    /// monthly period starting at January 31 ends at February 28 or 29).
+    ///
+    /// Returns `None` for non-recurrent payments and if the end of the period
+    /// can't be represented.
+    pub fn period_end(&self, start: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        match self {
+            Recurrent::NonRecurrent => None,
+            _ => self.nth_occurrence(start, 1),
+        }
+    }
+
+    /// Computes `n`-th payment due time for a schedule starting at `start`,
+    /// where the zeroth payment is due at `start` itself.
+    ///
+    /// Each occurrence is computed from `start` and not from the previous
+    /// occurrence, so month-end clamping does not accumulate: a monthly
+    /// schedule starting at January 31 continues with February 28 (or 29),
+    /// March 31, April 30 etc. Yearly schedule starting at February 29
+    /// continues with February 28 in non-leap years.
+    ///
+    /// Returns `None` if the occurrence can't be represented, for `n > 0` of
+    /// non-recurrent payments and for zero-length periods.
+    pub fn nth_occurrence(
+        &self,
+        start: DateTime<Utc>,
+        n: u32,
+    ) -> Option<DateTime<Utc>> {
+        if n == 0 {
+            return Some(start);
+        }
+        let months = |months: u32| {
+            months.checked_mul(n).and_then(|months| {
+                start.checked_add_months(Months::new(months))
+            })
+        };
+        let period_secs = match *self {
+            Recurrent::Seconds(secs) => Some(secs),
+            Recurrent::Days(days) => Some(days as u64 * 86400),
+            Recurrent::Weeks(weeks) => Some(weeks as u64 * 7 * 86400),
+            _ => None,
+        };
+        match *self {
+            Recurrent::NonRecurrent
+            | Recurrent::Unknown(..)
+            | Recurrent::Seconds(0)
+            | Recurrent::Days(0)
+            | Recurrent::Weeks(0)
+            | Recurrent::Months(0)
+            | Recurrent::Years(0) => None,
+            Recurrent::Seconds(_)
+            | Recurrent::Days(_)
+            | Recurrent::Weeks(_) => period_secs?
+                .checked_mul(n as u64)
+                .and_then(|secs| i64::try_from(secs).ok())
+                .and_then(|secs| {
+                    start.checked_add_signed(chrono::Duration::seconds(secs))
+                }),
+            Recurrent::Months(m) => months(m as u32),
+            Recurrent::Years(y) => months(y as u32 * 12),
+        }
+    }
+
+    /// Returns time when the next payment is due for a schedule starting at
+    /// `start`: the schedule start if no payments were made yet, or the first
+    /// occurrence after the `last_payment` otherwise.
+    ///
+    /// Returns `None` if no further payments are expected (for non-recurrent
+    /// payments which were already paid) or the next occurrence can't be
+    /// represented.
+    pub fn next_after(
+        &self,
+        start: DateTime<Utc>,
+        last_payment: Option<DateTime<Utc>>,
+    ) -> Option<DateTime<Utc>> {
+        let last_payment = match last_payment {
+            None => return Some(start),
+            Some(last_payment) if last_payment < start => return Some(start),
+            Some(last_payment) => last_payment,
+        };
+        // Estimate of the number of periods passed, which never exceeds
+        // the actual one
+        let passed = match *self {
+            Recurrent::NonRecurrent | Recurrent::Unknown(..) => return None,
+            Recurrent::Seconds(_)
+            | Recurrent::Days(_)
+            | Recurrent::Weeks(_) => {
+                let period = self
+                    .nth_occurrence(start, 1)
+                    .map(|next| (next - start).num_seconds() as u64)
+                    .unwrap_or(0);
+                let elapsed = (last_payment - start).num_seconds() as u64;
+                elapsed.checked_div(period).unwrap_or(0)
+            }
+            Recurrent::Months(period) | Recurrent::Years(period) => {
+                let period = match self {
+                    Recurrent::Years(_) => period as i64 * 12,
+                    _ => period as i64,
+                };
+                let elapsed =
+                    (last_payment.year() as i64 - start.year() as i64) * 12
+                        + last_payment.month() as i64
+                        - start.month() as i64;
+                (elapsed - 1).max(0).checked_div(period).unwrap_or(0) as u64
+            }
+        };
+        let mut n = u32::try_from(passed).ok()?;
+        loop {
+            let occurrence = self.nth_occurrence(start, n)?;
+            if occurrence > last_payment {
+                return Some(occurrence);
+            }
+            n = n.checked_add(1)?;
+        }
+    }
+
+    /// Iterates over payment due times for a schedule starting at `start`
+    /// which fall before `end`.
+    pub fn occurrences_between(
+        &self,
+        start: DateTime<Utc>,
+        end: DateTime<Utc>,
+    ) -> impl Iterator<Item = DateTime<Utc>> {
+        let recurrent = self.clone();
+        (0..=u32::MAX)
+            .map_while(move |n| recurrent.nth_occurrence(start, n))
+            .take_while(move |occurrence| *occurrence < end)
+    }
+}
+
+impl StrictEncode for Recurrent {
+    fn strict_encode<E: io::Write>(
+        &self,
+        mut e: E,
+    ) -> Result<usize, strict_encoding::Error> {
+        Ok(match self {
+            Recurrent::NonRecurrent => 0u8.strict_encode(&mut e)?,
+            Recurrent::Seconds(secs) => strict_encode_list!(e; 1u8, secs),
+            Recurrent::Months(months) => strict_encode_list!(e; 2u8, months),
+            Recurrent::Years(years) => strict_encode_list!(e; 3u8, years),
+            Recurrent::Days(days) => strict_encode_list!(e; 4u8, days),
+            Recurrent::Weeks(weeks) => strict_encode_list!(e; 5u8, weeks),
+            Recurrent::Unknown(tag, _) if *tag <= Recurrent::MAX_KNOWN_TAG => {
+                return Err(strict_encoding::Error::DataIntegrityError(
+                    format!(
+                        "recurrence tag {} is reserved for known recurrence \
+                         types",
+                        tag
+                    ),
+                ))
+            }
+            Recurrent::Unknown(tag, params) => {
+                e.write_all(&[*tag])?;
+                e.write_all(params)?;
+                1 + params.len()
+            }
+        })
+    }
+}
+
+impl StrictDecode for Recurrent {
+    fn strict_decode<D: io::Read>(
+        mut d: D,
+    ) -> Result<Self, strict_encoding::Error> {
+        Ok(match u8::strict_decode(&mut d)? {
+            0 => Recurrent::NonRecurrent,
+            1 => Recurrent::Seconds(u64::strict_decode(&mut d)?),
+            2 => Recurrent::Months(u8::strict_decode(&mut d)?),
+            3 => Recurrent::Years(u8::strict_decode(&mut d)?),
+            4 => Recurrent::Days(u16::strict_decode(&mut d)?),
+            5 => Recurrent::Weeks(u16::strict_decode(&mut d)?),
+            tag => {
+                let mut params = vec![];
+                d.read_to_end(&mut params)?;
+                Recurrent::Unknown(tag, params)
+            }
+        })
+    }
+}
+
+// TODO: Derive `Eq` & `Hash` once Psbt will support them
+#[cfg_attr(
+    feature = "serde",
+    serde_as,
+    derive(Serialize, Deserialize),
+    serde(crate = "serde_crate", rename = "lowercase", untagged)
+)]
+#[derive(Clone, Eq, PartialEq, Debug, From, StrictEncode)]
+#[non_exhaustive]
+pub enum Beneficiary {
+    /// Addresses are useful when you do not like to leak public key
+    /// information
+    #[from]
+    Address(
+        #[cfg_attr(feature = "serde", serde(with = "As::<DisplayFromStr>"))]
+        Address,
+    ),
+
+    /// Used by protocols that work with existing UTXOs and can assign some
+    /// client-validated data to them (like in RGB). We always hide the real
+    /// UTXO behind the hashed version (using some salt)
+    #[from]
+    BlindUtxo(ConcealedSeal),
+
+    /// Miniscript-based descriptors allowing custom derivation & key
+    /// generation. Use [`Beneficiary::TrackingDescriptor`] to restrict the
+    /// derivation indexes which may be used by the payer.
+    #[from]
+    Descriptor(
+        #[cfg_attr(feature = "serde", serde(with = "As::<DisplayFromStr>"))]
+        Descriptor<DescriptorPublicKey>,
+    ),
+
+    /// Full transaction template in PSBT format
+    #[from]
+    Psbt(#[cfg_attr(feature = "serde", serde(with = "As::<PsbtBase64>"))] Psbt),
+
+    /// Lightning node receiving the payment. Not the same as lightning invoice
+    /// since many of the invoice data now will be part of [`Invoice`] here.
+    #[from]
+    Bolt(LnAddress),
+
+    /// Fallback option for all future variants
+    Unknown(
+        #[cfg_attr(feature = "serde", serde(with = "As::<DisplayFromStr>"))]
+        Blob,
+    ),
+
+    /// Reusable BIP-352 silent payment code. Payer must derive the actual
+    /// output from the code and the inputs of the paying transaction.
+    #[from]
+    SilentPayment(
+        #[cfg_attr(feature = "serde", serde(with = "As::<DisplayFromStr>"))]
+        SilentPaymentCode,
+    ),
+
+    /// Reusable BIP-47 payment code, allowing payer to derive a fresh
+    /// address for each payment.
+    #[from]
+    PaymentCode(
+        #[cfg_attr(feature = "serde", serde(with = "As::<DisplayFromStr>"))]
+        PaymentCode,
+    ),
+
+    /// Bifrost node receiving the payment
+    #[from]
+    Bifrost(BifrostAddress),
+
+    /// RGB seal defined by an output of the transaction paying the invoice,
+    /// which doesn't require receiver to have an existing UTXO
+    #[from]
+    WitnessVoutSeal(WitnessVoutSeal),
+
+    /// Descriptor with the derivation metadata of the receiver tracking
+    /// account, allowing recurrent payments to use fresh derivation indexes
+    #[from]
+    TrackingDescriptor(TrackingBeneficiary),
+
+    /// Liquid address; confidential addresses require payer to blind the
+    /// payment amount and asset with the address blinding key
+    #[cfg(feature = "elements")]
+    #[from]
+    LiquidAddress(
+        #[cfg_attr(feature = "serde", serde(with = "As::<DisplayFromStr>"))]
+        LiquidAddress,
+    ),
+}
+
+/// Beneficiaries are ordered by their type, in the order of the strict
+/// encoding tags, and then by their data. PSBTs, which don't define any
+/// ordering, are compared by their strict encoding.
+impl Ord for Beneficiary {
+    fn cmp(&self, other: &Self) -> Ordering {
+        match (self, other) {
+            (Beneficiary::Address(a), Beneficiary::Address(b)) => a.cmp(b),
+            (Beneficiary::BlindUtxo(a), Beneficiary::BlindUtxo(b)) => a.cmp(b),
+            (Beneficiary::Descriptor(a), Beneficiary::Descriptor(b)) => {
+                a.cmp(b)
+            }
+            (Beneficiary::Psbt(a), Beneficiary::Psbt(b)) => {
+                a.strict_serialize().ok().cmp(&b.strict_serialize().ok())
+            }
+            (Beneficiary::Bolt(a), Beneficiary::Bolt(b)) => a.cmp(b),
+            (Beneficiary::Unknown(a), Beneficiary::Unknown(b)) => a.cmp(b),
+            (Beneficiary::SilentPayment(a), Beneficiary::SilentPayment(b)) => {
+                a.cmp(b)
+            }
+            (Beneficiary::PaymentCode(a), Beneficiary::PaymentCode(b)) => {
+                a.cmp(b)
+            }
+            (Beneficiary::Bifrost(a), Beneficiary::Bifrost(b)) => a.cmp(b),
+            (
+                Beneficiary::WitnessVoutSeal(a),
+                Beneficiary::WitnessVoutSeal(b),
+            ) => a.cmp(b),
+            (
+                Beneficiary::TrackingDescriptor(a),
+                Beneficiary::TrackingDescriptor(b),
+            ) => a.cmp(b),
+            #[cfg(feature = "elements")]
+            (Beneficiary::LiquidAddress(a), Beneficiary::LiquidAddress(b)) => {
+                a.cmp(b)
+            }
+            _ => self.tag().cmp(&other.tag()),
+        }
+    }
+}
+
+impl PartialOrd for Beneficiary {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+/// Displays PSBT beneficiary as a standard BIP-174 base64 string; the rest of
+/// beneficiaries use their own string representation.
+impl Display for Beneficiary {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Beneficiary::Address(address) => Display::fmt(address, f),
+            Beneficiary::BlindUtxo(seal) => Display::fmt(seal, f),
+            Beneficiary::Descriptor(descriptor) => Display::fmt(descriptor, f),
+            Beneficiary::Psbt(psbt) => {
+                Display::fmt(&PsbtV0::from(psbt.clone()), f)
+            }
+            Beneficiary::Bolt(ln_address) => Display::fmt(ln_address, f),
+            Beneficiary::Unknown(blob) => Display::fmt(blob, f),
+            Beneficiary::SilentPayment(code) => Display::fmt(code, f),
+            Beneficiary::PaymentCode(code) => Display::fmt(code, f),
+            Beneficiary::Bifrost(address) => Display::fmt(address, f),
+            Beneficiary::WitnessVoutSeal(seal) => Display::fmt(seal, f),
+            Beneficiary::TrackingDescriptor(tracking) => {
+                Display::fmt(tracking, f)
+            }
+            #[cfg(feature = "elements")]
+            Beneficiary::LiquidAddress(address) => Display::fmt(address, f),
+        }
+    }
+}
+
+/// Serde adapter representing PSBT as a BIP-174 base64 string in
+/// human-readable formats and using PSBT own serialization otherwise.
+#[cfg(feature = "serde")]
+struct PsbtBase64;
+
+#[cfg(feature = "serde")]
+impl serde_with::SerializeAs<Psbt> for PsbtBase64 {
+    fn serialize_as<S>(source: &Psbt, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        if serializer.is_human_readable() {
+            serializer.collect_str(&PsbtV0::from(source.clone()))
+        } else {
+            serde::Serialize::serialize(source, serializer)
+        }
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde_with::DeserializeAs<'de, Psbt> for PsbtBase64 {
+    fn deserialize_as<D>(deserializer: D) -> Result<Psbt, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        if deserializer.is_human_readable() {
+            let s = <String as serde::Deserialize>::deserialize(deserializer)?;
+            PsbtV0::from_str(&s)
+                .map(Psbt::from)
+                .map_err(serde::de::Error::custom)
+        } else {
+            <Psbt as serde::Deserialize>::deserialize(deserializer)
+        }
+    }
+}
+
+// Decoding is implemented manually since the strict decoding of PSBT taproot
+// trees provided by `strict_encoding` panics on incomplete trees
+impl StrictDecode for Beneficiary {
+    fn strict_decode<D: io::Read>(
+        mut d: D,
+    ) -> Result<Self, strict_encoding::Error> {
+        Ok(match u8::strict_decode(&mut d)? {
+            0 => Beneficiary::Address(StrictDecode::strict_decode(&mut d)?),
+            1 => Beneficiary::BlindUtxo(StrictDecode::strict_decode(&mut d)?),
+            2 => Beneficiary::Descriptor(StrictDecode::strict_decode(&mut d)?),
+            3 => Beneficiary::Psbt(decode_psbt(&mut d)?),
+            4 => Beneficiary::Bolt(StrictDecode::strict_decode(&mut d)?),
+            5 => Beneficiary::Unknown(StrictDecode::strict_decode(&mut d)?),
+            6 => {
+                Beneficiary::SilentPayment(StrictDecode::strict_decode(&mut d)?)
+            }
+            7 => Beneficiary::PaymentCode(StrictDecode::strict_decode(&mut d)?),
+            8 => Beneficiary::Bifrost(StrictDecode::strict_decode(&mut d)?),
+            9 => Beneficiary::WitnessVoutSeal(StrictDecode::strict_decode(
+                &mut d,
+            )?),
+            10 => Beneficiary::TrackingDescriptor(StrictDecode::strict_decode(
+                &mut d,
+            )?),
+            #[cfg(feature = "elements")]
+            11 => {
+                Beneficiary::LiquidAddress(StrictDecode::strict_decode(&mut d)?)
+            }
+            tag => {
+                return Err(strict_encoding::Error::EnumValueNotKnown(
+                    "Beneficiary",
+                    tag as usize,
+                ))
+            }
+        })
+    }
+}
+
+/// Decodes PSBT in the same way as its derived strict decoding does, but
+/// returns an error instead of panicking on invalid output taproot trees.
+fn decode_psbt(mut d: impl io::Read) -> Result<Psbt, strict_encoding::Error> {
+    let psbt_version = StrictDecode::strict_decode(&mut d)?;
+    let tx_version = StrictDecode::strict_decode(&mut d)?;
+    let fallback_locktime = StrictDecode::strict_decode(&mut d)?;
+    let inputs = StrictDecode::strict_decode(&mut d)?;
+    let len = usize::strict_decode(&mut d)?;
+    let mut outputs = Vec::with_capacity(len);
+    for _ in 0..len {
+        let index = usize::strict_decode(&mut d)?;
+        let value = u64::strict_decode(&mut d)?;
+        let script = PubkeyScript::strict_decode(&mut d)?;
+        let mut output = wallet::psbt::Output::new(
+            index,
+            bitcoin::TxOut {
+                value,
+                script_pubkey: script.into(),
+            },
+        );
+        output.redeem_script = StrictDecode::strict_decode(&mut d)?;
+        output.witness_script = StrictDecode::strict_decode(&mut d)?;
+        output.bip32_derivation = StrictDecode::strict_decode(&mut d)?;
+        output.tap_internal_key = StrictDecode::strict_decode(&mut d)?;
+        output.tap_tree = Option::<Vec<u8>>::strict_decode(&mut d)?
+            .map(|tree| {
+                <TapTree as psbt::serialize::Deserialize>::deserialize(&tree)
+                    .map_err(|err| {
+                        strict_encoding::Error::DataIntegrityError(
+                            err.to_string(),
+                        )
+                    })
+            })
+            .transpose()?;
+        output.tap_key_origins = StrictDecode::strict_decode(&mut d)?;
+        output.proprietary = StrictDecode::strict_decode(&mut d)?;
+        output.unknown = StrictDecode::strict_decode(&mut d)?;
+        outputs.push(output);
+    }
+    Ok(Psbt {
+        psbt_version,
+        tx_version,
+        fallback_locktime,
+        inputs,
+        outputs,
+        xpub: StrictDecode::strict_decode(&mut d)?,
+        proprietary: StrictDecode::strict_decode(&mut d)?,
+        unknown: StrictDecode::strict_decode(&mut d)?,
+    })
+}
+
+/// Incorrect beneficiary format; keeps the list of the beneficiary
+/// interpretations which were attempted while parsing the string.
+#[derive(Clone, Ord, PartialOrd, Eq, PartialEq, Hash, Debug, Error)]
+pub struct BeneficiaryParseError {
+    attempts: Vec<&'static str>,
+}
+
+impl BeneficiaryParseError {
+    /// Names of the beneficiary kinds which were tried during the parsing.
+    pub fn attempts(&self) -> &[&'static str] {
+        &self.attempts
+    }
+}
+
+impl Display for BeneficiaryParseError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "incorrect beneficiary format (attempted to parse as {})",
+            self.attempts.join(", ")
+        )
+    }
+}
+
+impl Beneficiary {
+    /// Magic bytes of BIP-174 PSBT serialization encoded as base64.
+    const PSBT_BASE64_MAGIC: &'static str = "cHNidP8";
+    /// Magic bytes of BIP-174 PSBT serialization encoded as hex.
+    const PSBT_HEX_MAGIC: &'static str = "70736274ff";
+
+    /// Strict encoding tag of the beneficiary type.
+    fn tag(&self) -> u8 {
+        match self {
+            Beneficiary::Address(_) => 0,
+            Beneficiary::BlindUtxo(_) => 1,
+            Beneficiary::Descriptor(_) => 2,
+            Beneficiary::Psbt(_) => 3,
+            Beneficiary::Bolt(_) => 4,
+            Beneficiary::Unknown(_) => 5,
+            Beneficiary::SilentPayment(_) => 6,
+            Beneficiary::PaymentCode(_) => 7,
+            Beneficiary::Bifrost(_) => 8,
+            Beneficiary::WitnessVoutSeal(_) => 9,
+            Beneficiary::TrackingDescriptor(_) => 10,
+            #[cfg(feature = "elements")]
+            Beneficiary::LiquidAddress(_) => 11,
+        }
+    }
+
+    fn parse_address(s: &str) -> Option<Self> {
+        Address::from_str(s).ok().map(Beneficiary::Address)
+    }
+
+    #[cfg(feature = "elements")]
+    fn parse_liquid_address(s: &str) -> Option<Self> {
+        LiquidAddress::from_str(s)
+            .ok()
+            .map(Beneficiary::LiquidAddress)
+    }
+
+    fn parse_blind_utxo(s: &str) -> Option<Self> {
+        ConcealedSeal::from_str(s).ok().map(Beneficiary::BlindUtxo)
+    }
+
+    fn parse_silent_payment(s: &str) -> Option<Self> {
+        SilentPaymentCode::from_str(s)
+            .ok()
+            .map(Beneficiary::SilentPayment)
+    }
+
+    fn parse_payment_code(s: &str) -> Option<Self> {
+        PaymentCode::from_str(s).ok().map(Beneficiary::PaymentCode)
+    }
+
+    fn parse_bifrost(s: &str) -> Option<Self> {
+        BifrostAddress::from_str(s).ok().map(Beneficiary::Bifrost)
+    }
+
+    fn parse_witness_vout(s: &str) -> Option<Self> {
+        WitnessVoutSeal::from_str(s)
+            .ok()
+            .map(Beneficiary::WitnessVoutSeal)
+    }
+
+    /// Parses tracking beneficiary data following `track:` prefix.
+    fn parse_tracking(s: &str) -> Option<Self> {
+        TrackingBeneficiary::from_str(&format!(
+            "{}{}",
+            TrackingBeneficiary::PREFIX,
+            s
+        ))
+        .ok()
+        .map(Beneficiary::TrackingDescriptor)
+    }
+
+    fn parse_descriptor(s: &str) -> Option<Self> {
+        Descriptor::<DescriptorPublicKey>::from_str(s)
+            .ok()
+            .map(Beneficiary::Descriptor)
+    }
+
+    /// Parses PSBT either from base64 or from hex-encoded BIP-174 data.
+    fn parse_psbt(s: &str) -> Option<Self> {
+        if let Ok(psbt) = PsbtV0::from_str(s) {
+            return Some(Beneficiary::Psbt(Psbt::from(psbt)));
+        }
+        Psbt::from_str(s).ok().map(Beneficiary::Psbt)
+    }
+
+    /// Parses PSBT only if the string starts with PSBT magic bytes.
+    fn detect_psbt(s: &str) -> Option<Self> {
+        if s.starts_with(Self::PSBT_BASE64_MAGIC)
+            || s.to_lowercase().starts_with(Self::PSBT_HEX_MAGIC)
+        {
+            Self::parse_psbt(s)
+        } else {
+            None
+        }
+    }
+
+    /// Parses `<node_id>[+<features>]@<lock>` into a minimal
+    /// [`LnAddress`] without payment secret and path hints. Features are
+    /// provided as a BOLT-9 feature bit string (like `0101`).
+    fn parse_bolt(s: &str) -> Option<Self> {
+        let (node, lock) = s.split_once('@')?;
+        let (node_id, features) = match node.split_once('+') {
+            Some((node_id, features)) => {
+                let flags = amplify::flags::FlagVec::from_str(features).ok()?;
+                (node_id, InitFeatures::try_from(flags).ok()?)
+            }
+            None => (node, InitFeatures::default()),
+        };
+        Some(Beneficiary::Bolt(LnAddress {
+            node_id: NodeId::from_str(node_id).ok()?,
+            features,
+            lock: Lock::from_str(lock).ok()?,
+            secret: None,
+            network: Chain::Mainnet,
+            min_final_cltv_expiry: None,
+            path_hints: vec![],
+            ln_flags: LnFlags::empty(),
+        }))
+    }
+
+    /// Parses either `0x`-prefixed hex data or Bech32m-encoded `data1...`
+    /// blob.
+    fn parse_unknown(s: &str) -> Option<Self> {
+        if let Some(hex) = s.strip_prefix("0x") {
+            Vec::<u8>::from_hex(hex)
+                .ok()
+                .map(Blob::from)
+                .map(Beneficiary::Unknown)
+        } else {
+            Blob::from_str(s).ok().map(Beneficiary::Unknown)
+        }
+    }
+}
+
+// TODO: Since we can't present full beneficiary data in a string form (because
+//       of the lightning part) we have to remove this implementation once
+//       serde_with will be working
+/// Parses beneficiary string. The string may start with `addr:`, `utxob:`,
+/// `psbt:`, `bolt:`, `sp:` or `track:` prefix forcing a specific
+/// interpretation (`track:` is always required for tracking descriptors);
+/// otherwise bitcoin address, Liquid address (with `elements` feature), blind
+/// UTXO, witness output seal
+/// (`wvout:<method>:<blinding>`), silent payment code (`sp1...`),
+/// BIP-47 payment code (`PM8T...`), descriptor, PSBT (recognized by its
+/// magic bytes), lightning node (`<node_id>[+<features>]@<payment_hash>`),
+/// Bifrost node (`bifrost:<node_id>`) and unknown data blob (`0x...` or
+/// `data1...`) are tried in this order.
+impl FromStr for Beneficiary {
+    type Err = BeneficiaryParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        type Parser = fn(&str) -> Option<Beneficiary>;
+
+        let forced: [(&str, &'static str, Parser); 6] = [
+            ("addr:", "bitcoin address", Beneficiary::parse_address),
+            ("utxob:", "blind UTXO", Beneficiary::parse_blind_utxo),
+            ("psbt:", "PSBT", Beneficiary::parse_psbt),
+            ("bolt:", "lightning node", Beneficiary::parse_bolt),
+            (
+                "sp:",
+                "silent payment code",
+                Beneficiary::parse_silent_payment,
+            ),
+            (
+                TrackingBeneficiary::PREFIX,
+                "tracking descriptor",
+                Beneficiary::parse_tracking,
+            ),
+        ];
+        for (prefix, kind, parser) in forced.iter() {
+            if let Some(data) = s.strip_prefix(prefix) {
+                return parser(data).ok_or_else(|| BeneficiaryParseError {
+                    attempts: vec![kind],
+                });
+            }
+        }
+
+        let heuristics: &[(&'static str, Parser)] = &[
+            ("bitcoin address", Beneficiary::parse_address),
+            #[cfg(feature = "elements")]
+            ("Liquid address", Beneficiary::parse_liquid_address),
+            ("blind UTXO", Beneficiary::parse_blind_utxo),
+            ("witness output seal", Beneficiary::parse_witness_vout),
+            ("silent payment code", Beneficiary::parse_silent_payment),
+            ("payment code", Beneficiary::parse_payment_code),
+            ("descriptor", Beneficiary::parse_descriptor),
+            ("PSBT", Beneficiary::detect_psbt),
+            ("lightning node", Beneficiary::parse_bolt),
+            ("bifrost node", Beneficiary::parse_bifrost),
+            ("data blob", Beneficiary::parse_unknown),
+        ];
+        heuristics
+            .iter()
+            .find_map(|(_, parser)| parser(s))
+            .ok_or_else(|| BeneficiaryParseError {
+                attempts: heuristics.iter().map(|(kind, _)| *kind).collect(),
+            })
+    }
+}
+
+/// Error parsing [`Lock`] string.
+#[derive(
+    Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Hash, Debug, Display, Error,
+)]
+#[display(
+    "payment lock must be hex-encoded 32-byte hash or 33-byte compressed point"
+)]
+pub struct LockParseError;
+
+/// Lock of a lightning payment: hash of the payment preimage for HTLCs or
+/// elliptic curve point for PTLCs.
+///
+/// Displayed as a hexadecimal string; hash locks are 32 bytes long and point
+/// locks are 33-byte compressed public keys.
+#[derive(
+    Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Hash, Debug, Display, From,
+)]
+pub enum Lock {
+    #[from]
+    #[display(inner)]
+    Hash(HashLock),
+
+    #[from]
+    #[display(inner)]
+    Point(secp256k1::PublicKey),
+}
+
+impl Lock {
+    /// Checks that SHA256 hash of the preimage matches the hash lock; always
+    /// fails for point locks.
+    pub fn verify_preimage(&self, preimage: &[u8; 32]) -> bool {
+        match self {
+            Lock::Hash(lock) => {
+                let preimage =
+                    HashPreimage::from_inner(Slice32::from_inner(*preimage));
+                HashLock::from(preimage) == *lock
+            }
+            Lock::Point(_) => false,
+        }
+    }
+
+    /// Checks that the scalar revealed by a PTLC payment, multiplied by the
+    /// curve generator, gives the point lock; always fails for hash locks.
+    pub fn verify_scalar(&self, scalar: &secp256k1::SecretKey) -> bool {
+        match self {
+            Lock::Hash(_) => false,
+            Lock::Point(point) => {
+                let secp = secp256k1::Secp256k1::signing_only();
+                secp256k1::PublicKey::from_secret_key(&secp, scalar) == *point
+            }
+        }
+    }
+}
+
+impl FromStr for Lock {
+    type Err = LockParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.len() {
+            64 => HashLock::from_str(s)
+                .map(Lock::Hash)
+                .map_err(|_| LockParseError),
+            66 => secp256k1::PublicKey::from_str(s)
+                .map(Lock::Point)
+                .map_err(|_| LockParseError),
+            _ => Err(LockParseError),
+        }
+    }
+}
+
+/// Capabilities and requirements of a lightning payment receiver, which are
+/// not covered by the node features.
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    serde(crate = "serde_crate", transparent)
+)]
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Hash, Debug, Default)]
+pub struct LnFlags(u8);
+
+impl LnFlags {
+    /// Hold invoice: the receiver settles the payment only after the order
+    /// is fulfilled, so the payment stays pending until then and may be
+    /// cancelled with the funds returned to the payer
+    pub const HODL: Self = LnFlags(0x01);
+    /// The receiver node accepts spontaneous keysend payments, which reveal
+    /// the preimage to the receiver and don't use the invoice payment hash
+    pub const KEYSEND_OK: Self = LnFlags(0x02);
+    /// The receiver accepts atomic multi-path payments, where each part uses
+    /// its own payment hash derived from a secret shared by the payer
+    pub const AMP_OK: Self = LnFlags(0x04);
+    /// The receiver requires the payment to be split with basic multi-path
+    /// payments; BOLT-11 invoices signal it with required `basic_mpp`
+    /// feature
+    pub const MPP_REQUIRED: Self = LnFlags(0x08);
+    /// The beneficiary is a BOLT-12 offer node: the payer requests a BOLT-12
+    /// invoice from it to obtain the payment hash, so the beneficiary lock
+    /// is not used
+    pub const OFFER: Self = LnFlags(0x10);
+
+    /// Flags which can't be ignored by the payer, encoded with required
+    /// feature bits
+    pub const REQUIRED: Self = LnFlags(0x18);
+
+    /// Number of defined flags
+    const COUNT: u16 = 5;
+
+    pub const fn empty() -> Self {
+        LnFlags(0)
+    }
+
+    pub const fn all() -> Self {
+        LnFlags(0x1F)
+    }
+
+    pub const fn is_empty(&self) -> bool {
+        self.0 == 0
+    }
+
+    pub const fn contains(self, other: Self) -> bool {
+        self.0 & other.0 == other.0
+    }
+}
+
+impl BitOr for LnFlags {
+    type Output = Self;
+
+    fn bitor(self, rhs: Self) -> Self::Output {
+        LnFlags(self.0 | rhs.0)
+    }
+}
+
+impl BitOrAssign for LnFlags {
+    fn bitor_assign(&mut self, rhs: Self) {
+        self.0 |= rhs.0
+    }
+}
+
+impl BitAnd for LnFlags {
+    type Output = Self;
+
+    fn bitand(self, rhs: Self) -> Self::Output {
+        LnFlags(self.0 & rhs.0)
+    }
+}
+
+#[cfg_attr(
+    feature = "serde",
+    serde_as,
+    derive(Serialize, Deserialize),
+    serde(crate = "serde_crate")
+)]
+#[derive(Clone, Ord, PartialOrd, Eq, PartialEq, Hash, Debug, Display)]
+#[display("{node_id}")]
+pub struct LnAddress {
+    pub node_id: NodeId,
+    /// Node features; [`LnAddress::PTLC_FEATURE_BIT`],
+    /// [`LnAddress::MULTI_HOP_FEATURE_BIT`] and the
+    /// [`LnAddress::LN_FLAGS_FEATURE_BIT`] range are reserved for the wire
+    /// encoding and are removed when decoding
+    pub features: InitFeatures,
+    #[cfg_attr(feature = "serde", serde(with = "As::<DisplayFromStr>"))]
+    pub lock: Lock,
+    /// Payment secret (BOLT-11 `s` field); if absent, conversion into BOLT-11
+    /// invoice requires it to be provided or derives it from the node key
+    pub secret: Option<Slice32>,
+    /// Legacy per-beneficiary chain information, which is kept for the wire
+    /// format compatibility. The invoice-level `network` and `asset` fields
+    /// take precedence over it.
+    pub network: Chain,
+    pub min_final_cltv_expiry: Option<u16>,
+    /// Private routes to the node, each consisting of one or more hops
+    pub path_hints: Vec<LnRouteHint>,
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "LnFlags::is_empty")
+    )]
+    pub ln_flags: LnFlags,
+}
+
+impl LnAddress {
+    /// Required experimental feature bit indicating that the lock is a PTLC
+    /// point and not a payment hash. Decoders not aware of PTLCs reject the
+    /// unknown required feature instead of reading the point as a hash. Hash
+    /// locks are encoded without it, so the encoding of HTLC addresses is
+    /// unchanged.
+    pub const PTLC_FEATURE_BIT: u16 = 100;
+
+    /// Required experimental feature bit indicating that route hints have
+    /// multiple hops. Decoders not aware of multi-hop routes reject the
+    /// unknown required feature instead of reading the routes as a flat list
+    /// of hops. Addresses with single-hop routes only are encoded without it,
+    /// like before multi-hop support.
+    pub const MULTI_HOP_FEATURE_BIT: u16 = 102;
+
+    /// Experimental feature bit pair of the first [`LnFlags`] flag; the
+    /// following flags use next bit pairs. [`LnFlags::REQUIRED`] flags are
+    /// encoded with the required (even) bit of the pair and rejected by the
+    /// decoders not aware of them; other flags use the optional (odd) bit and
+    /// are kept by such decoders as unknown optional features.
+    pub const LN_FLAGS_FEATURE_BIT: u16 = 108;
+
+    /// Feature bit encoding [`LnFlags`] flag number `no`
+    fn ln_flag_bit(no: u16) -> u16 {
+        let optional = !LnFlags::REQUIRED.contains(LnFlags(1 << no));
+        Self::LN_FLAGS_FEATURE_BIT + no * 2 + optional as u16
+    }
+
+    /// Total number of hops in all route hints.
+    pub fn path_hint_count(&self) -> usize {
+        self.path_hints.iter().map(|route| route.0.len()).sum()
+    }
+}
+
+impl StrictEncode for LnAddress {
+    fn strict_encode<E: io::Write>(
+        &self,
+        mut e: E,
+    ) -> Result<usize, strict_encoding::Error> {
+        let mut features = self.features.clone();
+        let point = matches!(self.lock, Lock::Point(_));
+        let multi_hop = self.path_hints.iter().any(|route| route.0.len() != 1);
+        let flags = (0..LnFlags::COUNT).map(|no| {
+            (
+                Self::ln_flag_bit(no),
+                self.ln_flags.contains(LnFlags(1 << no)),
+            )
+        });
+        for (bit, set) in [
+            (Self::PTLC_FEATURE_BIT, point),
+            (Self::MULTI_HOP_FEATURE_BIT, multi_hop),
+        ]
+        .iter()
+        .copied()
+        .chain(flags)
+        {
+            if set {
+                features.unknown.set(bit);
+            } else {
+                features.unknown.unset(bit);
+            }
+        }
+        features.unknown = trim_flags(&features.unknown);
+        let mut len = strict_encode_list!(e; self.node_id, features);
+        len += match self.lock {
+            Lock::Hash(lock) => lock.strict_encode(&mut e)?,
+            Lock::Point(point) => point.strict_encode(&mut e)?,
+        };
+        len += strict_encode_list!(e;
+            self.secret,
+            self.network,
+            self.min_final_cltv_expiry
+        );
+        len += if multi_hop {
+            self.path_hints.strict_encode(&mut e)?
+        } else {
+            self.path_hints
+                .iter()
+                .flat_map(|route| &route.0)
+                .copied()
+                .collect::<Vec<_>>()
+                .strict_encode(&mut e)?
+        };
+        Ok(len)
+    }
+}
+
+/// Removes trailing zero bytes from the flag vector. `FlagVec::shrink` drops
+/// the byte holding the top flag when its number is a multiple of 8.
+fn trim_flags(flags: &FlagVec) -> FlagVec {
+    let mut bytes = flags.as_inner().to_vec();
+    while bytes.last() == Some(&0) {
+        bytes.pop();
+    }
+    FlagVec::from_inner(bytes)
+}
+
+impl StrictDecode for LnAddress {
+    fn strict_decode<D: io::Read>(
+        mut d: D,
+    ) -> Result<Self, strict_encoding::Error> {
+        let node_id = NodeId::strict_decode(&mut d)?;
+        let mut flags = FlagVec::strict_decode(&mut d)?;
+        // The wire encoding bits are taken before the conversion into
+        // `InitFeatures`, which rejects unknown required (even) features
+        let mut take_flag = |bit| {
+            let set = flags.is_set(bit);
+            if set {
+                flags.unset(bit);
+            }
+            set
+        };
+        let point = take_flag(Self::PTLC_FEATURE_BIT);
+        let multi_hop = take_flag(Self::MULTI_HOP_FEATURE_BIT);
+        let ln_flags = (0..LnFlags::COUNT)
+            .filter(|&no| take_flag(Self::ln_flag_bit(no)))
+            .fold(LnFlags::empty(), |flags, no| flags | LnFlags(1 << no));
+        let flags = trim_flags(&flags);
+        // `InitFeatures` conversion panics on the required features placed
+        // at the byte boundaries (lnp2p sets them into an empty `FlagVec`,
+        // which does not grow for such flags), so they are rejected here
+        if let Some(feature) = Feature::all().iter().find(|feature| {
+            feature
+                .bit(true)
+                .map(|bit| bit % 8 == 0 && flags.is_set(bit))
+                .unwrap_or_default()
+        }) {
+            return Err(strict_encoding::Error::DataIntegrityError(format!(
+                "unsupported required feature {}",
+                feature
+            )));
+        }
+        let features = InitFeatures::try_from(flags).map_err(|err| {
+            strict_encoding::Error::DataIntegrityError(err.to_string())
+        })?;
+        let lock = if point {
+            Lock::Point(StrictDecode::strict_decode(&mut d)?)
+        } else {
+            Lock::Hash(StrictDecode::strict_decode(&mut d)?)
+        };
+        let secret = StrictDecode::strict_decode(&mut d)?;
+        let network = StrictDecode::strict_decode(&mut d)?;
+        let min_final_cltv_expiry = StrictDecode::strict_decode(&mut d)?;
+        let path_hints = if multi_hop {
+            StrictDecode::strict_decode(&mut d)?
+        } else {
+            Vec::<LnPathHint>::strict_decode(&mut d)?
+                .into_iter()
+                .map(|hop| LnRouteHint(vec![hop]))
+                .collect()
+        };
+        Ok(LnAddress {
+            node_id,
+            features,
+            lock,
+            secret,
+            network,
+            min_final_cltv_expiry,
+            path_hints,
+            ln_flags,
+        })
+    }
+}
+
+/// Path hints for a lightning network payment, equal to the value of the `r`
+/// key of the lightning BOLT-11 invoice
+/// <https://github.com/lightningnetwork/lightning-rfc/blob/master/11-payment-encoding.md#tagged-fields>
+#[cfg_attr(
+    feature = "serde",
+    serde_as,
+    derive(Serialize, Deserialize),
+    serde(crate = "serde_crate")
+)]
+#[derive(
+    Copy,
+    Clone,
+    Ord,
+    PartialOrd,
+    Eq,
+    PartialEq,
+    Hash,
+    Debug,
+    Display,
+    StrictEncode,
+    StrictDecode,
+)]
+#[display("{short_channel_id}@{node_id}")]
+pub struct LnPathHint {
+    pub node_id: NodeId,
+    #[cfg_attr(feature = "serde", serde(with = "As::<DisplayFromStr>"))]
+    pub short_channel_id: ShortChannelId,
+    pub fee_base_msat: u32,
+    pub fee_proportional_millionths: u32,
+    pub cltv_expiry_delta: u16,
+}
+
+/// Private route to a lightning node: one of the routes of the BOLT-11 `r`
+/// field, listing hops from the public network towards the node.
+///
+/// Serialized as a list of hops; a single hop is deserialized as one-hop
+/// route, as used by the invoices before multi-hop route hints were added.
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize),
+    serde(crate = "serde_crate", transparent)
+)]
+#[derive(
+    Clone,
+    Ord,
+    PartialOrd,
+    Eq,
+    PartialEq,
+    Hash,
+    Debug,
+    Default,
+    From,
+    StrictEncode,
+    StrictDecode,
+)]
+pub struct LnRouteHint(#[from] pub Vec<LnPathHint>);
+
+impl From<LnPathHint> for LnRouteHint {
+    fn from(hop: LnPathHint) -> Self {
+        LnRouteHint(vec![hop])
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for LnRouteHint {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        #[derive(Deserialize)]
+        #[serde(crate = "serde_crate", untagged)]
+        enum Repr {
+            Route(Vec<LnPathHint>),
+            Hop(LnPathHint),
+        }
+
+        Ok(match Repr::deserialize(deserializer)? {
+            Repr::Route(hops) => LnRouteHint(hops),
+            Repr::Hop(hop) => LnRouteHint(vec![hop]),
+        })
+    }
+}
+
+/// Errors parsing [`BifrostAddress`] string representation.
+#[derive(Clone, Eq, PartialEq, Debug, Display, Error)]
+#[display(doc_comments)]
+pub enum BifrostAddressError {
+    /// Bifrost address must start with `bifrost:` prefix
+    NoPrefix,
+
+    /// Bifrost address contains invalid node id
+    NodeId,
+
+    /// invalid Bifrost protocol name `{0}`; protocol names may contain only
+    /// ASCII alphanumeric characters and dashes
+    Protocol(String),
+
+    /// Bifrost address contains invalid channel id
+    Channel,
+
+    /// unknown Bifrost address parameter `{0}`
+    UnknownParam(String),
+}
+
+/// Bifrost node receiving the payment.
+///
+/// String representation is `bifrost:<node_id>`, optionally followed by
+/// `?protocols=<name>[,<name>...]` and `&channel=<channel_id>` parameters
+/// when these data are present.
+///
+/// Strict encoding uses beneficiary tag `0x08`, followed by 33-byte node
+/// public key, the list of protocol names (little-endian `u16` count and
+/// then each name as `u16` length followed by its UTF-8 bytes) and optional
+/// 32-byte channel id (`0x00` if absent, `0x01` followed by the id
+/// otherwise). For instance, the address of a node with id
+/// `0279be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798`
+/// without protocols and channel hint is encoded as
+/// ```text
+/// 080279be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798000000
+/// ```
+#[cfg_attr(
+    feature = "serde",
+    serde_as,
+    derive(Serialize, Deserialize),
+    serde(crate = "serde_crate")
+)]
+#[derive(
+    Clone,
+    Ord,
+    PartialOrd,
+    Eq,
+    PartialEq,
+    Hash,
+    Debug,
+    StrictEncode,
+    StrictDecode,
+)]
+pub struct BifrostAddress {
+    pub node_id: NodeId,
+
+    /// Names of Bifrost protocols supported by the node for receiving the
+    /// payment
+    pub protocols: Vec<String>,
+
+    /// Channel which should be used for the payment, if any
+    #[cfg_attr(
+        feature = "serde",
+        serde(with = "As::<Option<DisplayFromStr>>")
+    )]
+    pub channel_hint: Option<ChannelId>,
+}
+
+impl BifrostAddress {
+    pub fn new(node_id: NodeId) -> BifrostAddress {
+        BifrostAddress {
+            node_id,
+            protocols: none!(),
+            channel_hint: None,
+        }
+    }
+}
+
+impl Display for BifrostAddress {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "bifrost:{}", self.node_id)?;
+        let mut sep = '?';
+        if !self.protocols.is_empty() {
+            write!(f, "{}protocols={}", sep, self.protocols.join(","))?;
+            sep = '&';
+        }
+        if let Some(channel) = self.channel_hint {
+            write!(f, "{}channel={}", sep, channel)?;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for BifrostAddress {
+    type Err = BifrostAddressError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s
+            .strip_prefix("bifrost:")
+            .ok_or(BifrostAddressError::NoPrefix)?;
+        let (node_id, params) = s.split_once('?').unwrap_or((s, ""));
+        let mut address = BifrostAddress::new(
+            NodeId::from_str(node_id)
+                .map_err(|_| BifrostAddressError::NodeId)?,
+        );
+        for param in params.split('&').filter(|param| !param.is_empty()) {
+            match param.split_once('=') {
+                Some(("protocols", list)) => {
+                    address.protocols = list
+                        .split(',')
+                        .map(|name| {
+                            if !name.is_empty()
+                                && name.chars().all(|c| {
+                                    c.is_ascii_alphanumeric() || c == '-'
+                                })
+                            {
+                                Ok(name.to_owned())
+                            } else {
+                                Err(BifrostAddressError::Protocol(
+                                    name.to_owned(),
+                                ))
+                            }
+                        })
+                        .collect::<Result<_, _>>()?;
+                }
+                Some(("channel", channel)) => {
+                    address.channel_hint = Some(
+                        ChannelId::from_str(channel)
+                            .map_err(|_| BifrostAddressError::Channel)?,
+                    );
+                }
+                _ => {
+                    return Err(BifrostAddressError::UnknownParam(
+                        param.to_owned(),
+                    ))
+                }
+            }
+        }
+        Ok(address)
+    }
+}
+
+/// Invoice amount.
+///
+/// Amounts are ordered by their value in milli-units, with [`AmountExt::Any`]
+/// going before all specific amounts. Amounts of the same value are
+/// additionally ordered by their representation, placing
+/// [`AmountExt::Normal`] before [`AmountExt::Milli`], so the ordering stays
+/// consistent with the equality.
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug, Default, Display, From)]
+pub enum AmountExt {
+    /// Payments for any amount is accepted: useful for charity/donations, etc
+    #[default]
+    #[display("any")]
+    Any,
+
+    #[from]
+    #[display(inner)]
+    Normal(u64),
+
+    /// Amount with a fractional part in milli-units (like millisatoshis),
+    /// which must not exceed 999. Use [`AmountExt::with_milli`] to construct
+    /// the amount with the fraction checked; amounts with a larger fraction
+    /// fail strict encoding and are rejected by [`Invoice::set_amount`].
+    #[display("{0}.{1:03}")]
+    Milli(u64, u16),
+}
+
+impl AmountExt {
+    /// Constructs amount from the integer and milli-unit parts, normalizing
+    /// zero fractional part to [`AmountExt::Normal`].
+    pub fn with_milli(val: u64, frac: u16) -> Result<Self, AmountParseError> {
+        match frac {
+            0 => Ok(AmountExt::Normal(val)),
+            1..=999 => Ok(AmountExt::Milli(val, frac)),
+            _ => Err(AmountParseError),
+        }
+    }
+
+    pub fn atomic_value(&self) -> Option<u64> {
+        match self {
+            AmountExt::Any => None,
+            AmountExt::Normal(val) => Some(*val),
+            AmountExt::Milli(_, _) => None,
+        }
+    }
+
+    /// Returns amount in milli-units (like millisatoshis), or `None` for
+    /// [`AmountExt::Any`] and amounts not fitting into `u64`.
+    pub fn to_msat(&self) -> Option<u64> {
+        self.msat_value().and_then(|msat| u64::try_from(msat).ok())
+    }
+
+    /// Adds two amounts, returning `None` if any of them is
+    /// [`AmountExt::Any`] or on overflow.
+    pub fn checked_add(self, other: AmountExt) -> Option<AmountExt> {
+        let msat = self.msat_value()?.checked_add(other.msat_value()?)?;
+        AmountExt::with_msat_value(msat)
+    }
+
+    /// Multiplies amount by a given factor, returning `None` for
+    /// [`AmountExt::Any`] or on overflow.
+    pub fn checked_mul(self, factor: u32) -> Option<AmountExt> {
+        let msat = self.msat_value()?.checked_mul(factor as u128)?;
+        AmountExt::with_msat_value(msat)
+    }
+
+    fn msat_value(&self) -> Option<u128> {
+        match self {
+            AmountExt::Any => None,
+            AmountExt::Normal(val) => Some(*val as u128 * 1000),
+            AmountExt::Milli(val, frac) => {
+                Some(*val as u128 * 1000 + *frac as u128)
+            }
+        }
+    }
+
+    fn with_msat_value(msat: u128) -> Option<AmountExt> {
+        let val = u64::try_from(msat / 1000).ok()?;
+        Some(match (msat % 1000) as u16 {
+            0 => AmountExt::Normal(val),
+            frac => AmountExt::Milli(val, frac),
+        })
+    }
+}
+
+impl Ord for AmountExt {
+    fn cmp(&self, other: &Self) -> Ordering {
+        let key = |amount: &AmountExt| match *amount {
+            AmountExt::Any => (None, 0, 0, 0),
+            AmountExt::Normal(val) => (amount.msat_value(), 0, val, 0),
+            AmountExt::Milli(val, frac) => (amount.msat_value(), 1, val, frac),
+        };
+        key(self).cmp(&key(other))
+    }
+}
+
+impl PartialOrd for AmountExt {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+#[derive(
+    Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Display, Error, From,
+)]
+#[display(doc_comments)]
+#[from(std::num::ParseIntError)]
+/// invalid amount: must be `any`, an integer number of units or a number
+/// with up to three fractional digits of milli-units
+pub struct AmountParseError;
+
+/// Drops fractions of a second, which are not preserved by the encoding
+fn whole_seconds(time: DateTime<Utc>) -> DateTime<Utc> {
+    time.with_nanosecond(0).unwrap_or(time)
+}
+
+/// Invoice expiry, either absolute or relative to the invoice issuance time
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug, Display)]
+pub enum ExpiryTerm {
+    #[display("{0}")]
+    Absolute(DateTime<Utc>),
+
+    #[display("{0} seconds after issuance")]
+    RelativeSeconds(u32),
+}
+
+/// Errors resolving invoice expiry.
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug, Display, Error)]
+#[display(doc_comments)]
+pub enum ExpiryError {
+    /// invoice has relative expiry but no issuance time
+    NoIssuanceTime,
+
+    /// invoice has both absolute and relative expiry
+    Ambiguous,
+
+    /// invoice expiry is out of the representable time range
+    OutOfRange,
+}
+
+/// Share of the split payment received by an alternative beneficiary.
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    serde(crate = "serde_crate", rename_all = "camelCase")
+)]
+#[derive(
+    Copy,
+    Clone,
+    Ord,
+    PartialOrd,
+    Eq,
+    PartialEq,
+    Hash,
+    Debug,
+    StrictEncode,
+    StrictDecode,
+)]
+pub enum SplitShare {
+    /// Fixed amount, in the invoice amount units
+    Amount(u64),
+
+    /// Share of the total payment, in basis points (1/100 of a percent)
+    BasisPoints(u16),
+}
+
+impl SplitShare {
+    /// Number of basis points making up the whole payment.
+    pub const TOTAL_BASIS_POINTS: u16 = 10_000;
+
+    /// Resolves amount of the share out of the `total` payment amount,
+    /// rounding basis point shares down.
+    pub fn resolve(self, total: u64) -> u64 {
+        match self {
+            SplitShare::Amount(amount) => amount,
+            SplitShare::BasisPoints(bp) => {
+                (total as u128 * bp as u128
+                    / SplitShare::TOTAL_BASIS_POINTS as u128)
+                    as u64
+            }
+        }
+    }
+}
+
+impl Display for SplitShare {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            SplitShare::Amount(amount) => write!(f, "{}", amount),
+            SplitShare::BasisPoints(bp) => {
+                write!(f, "{}.{:02}%", bp / 100, bp % 100)
+            }
+        }
+    }
+}
+
+/// Errors in split payment invoice data.
+#[derive(
+    Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Hash, Debug, Display, Error,
+)]
+#[display(doc_comments)]
+pub enum SplitError {
+    /// payment split has {shares} shares for {beneficiaries} alternative
+    /// beneficiaries
+    LengthMismatch { shares: usize, beneficiaries: usize },
+
+    /// payment split parts exceed the payment amount
+    Exceeds,
+}
+
+/// Errors computing total invoice amount.
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug, Display, Error)]
+#[display(doc_comments)]
+pub enum AmountError {
+    /// requested quantity {requested} lies outside of the range allowed by
+    /// the invoice
+    QuantityOutOfRange {
+        requested: u32,
+        min: u32,
+        max: Option<u32>,
+    },
+
+    /// total amount overflows
+    Overflow,
+
+    /// total amount can't be computed for invoices accepting any amount
+    NotComputable,
+}
+
+impl FromStr for AmountExt {
+    type Err = AmountParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.trim().to_lowercase() == "any" {
+            return Ok(AmountExt::Any);
+        }
+        let is_number =
+            |s: &str| !s.is_empty() && s.chars().all(|c| c.is_ascii_digit());
+        match s.split_once('.') {
+            None if is_number(s) => Ok(AmountExt::Normal(s.parse()?)),
+            // Fractional part is positional: `1.05` means 1 unit and 50
+            // milli-units
+            Some((int, frac))
+                if is_number(int) && is_number(frac) && frac.len() <= 3 =>
+            {
+                let frac = format!("{:0<3}", frac);
+                AmountExt::with_milli(int.parse()?, frac.parse()?)
+            }
+            _ => Err(AmountParseError),
+        }
+    }
+}
+
+impl StrictEncode for AmountExt {
+    fn strict_encode<E: io::Write>(
+        &self,
+        mut e: E,
+    ) -> Result<usize, strict_encoding::Error> {
+        Ok(match self {
+            AmountExt::Any => 0u8.strict_encode(&mut e)?,
+            AmountExt::Normal(val) => strict_encode_list!(e; 1u8, val),
+            AmountExt::Milli(_, frac) if *frac > 999 => {
+                return Err(strict_encoding::Error::ValueOutOfRange(
+                    "milli-unit fraction",
+                    0..1000,
+                    *frac as u128,
+                ))
+            }
+            AmountExt::Milli(val, frac) => {
+                strict_encode_list!(e; 2u8, val, frac)
+            }
+        })
+    }
+}
+
+impl StrictDecode for AmountExt {
+    fn strict_decode<D: io::Read>(
+        mut d: D,
+    ) -> Result<Self, strict_encoding::Error> {
+        Ok(match u8::strict_decode(&mut d)? {
+            0 => AmountExt::Any,
+            1 => AmountExt::Normal(u64::strict_decode(&mut d)?),
+            2 => {
+                let val = u64::strict_decode(&mut d)?;
+                let frac = u16::strict_decode(&mut d)?;
+                if frac > 999 {
+                    return Err(strict_encoding::Error::ValueOutOfRange(
+                        "milli-unit fraction",
+                        0..1000,
+                        frac as u128,
+                    ));
+                }
+                AmountExt::Milli(val, frac)
+            }
+            tag => {
+                return Err(strict_encoding::Error::EnumValueNotKnown(
+                    "AmountExt",
+                    tag as usize,
+                ))
+            }
+        })
+    }
+}
+
+#[derive(
+    Clone,
+    Ord,
+    PartialOrd,
+    Eq,
+    PartialEq,
+    Hash,
+    Debug,
+    Display,
+    StrictEncode,
+    StrictDecode,
+)]
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    serde(crate = "serde_crate")
+)]
+#[display("{source}#{commitment}")]
+pub struct Details {
+    #[cfg_attr(feature = "serde", serde(with = "As::<DisplayFromStr>"))]
+    pub commitment: sha256d::Hash,
+    #[cfg_attr(feature = "serde", serde(with = "As::<DisplayFromStr>"))]
+    pub source: Url,
+}
+
+impl Details {
+    /// Constructs details referencing document with the given content. The
+    /// commitment is a double SHA256 hash of the raw document bytes, without
+    /// any canonicalization of the document data. Like other bitcoin hashes,
+    /// the commitment is displayed in the reversed byte order.
+    pub fn commit_to(bytes: &[u8], source: Url) -> Details {
+        Details {
+            commitment: sha256d::Hash::hash(bytes),
+            source,
+        }
+    }
+
+    /// Verifies that the document fetched from the details source matches
+    /// its commitment.
+    pub fn verify(&self, bytes: &[u8]) -> bool {
+        sha256d::Hash::hash(bytes) == self.commitment
+    }
+}
+
+/// Structured identity of the merchant issuing the invoice.
+#[cfg_attr(
+    feature = "serde",
+    serde_as,
+    derive(Serialize, Deserialize),
+    serde(crate = "serde_crate", rename_all = "camelCase")
+)]
+#[derive(
+    Clone,
+    Ord,
+    PartialOrd,
+    Eq,
+    PartialEq,
+    Hash,
+    Debug,
+    Display,
+    StrictEncode,
+    StrictDecode,
+)]
+#[display("{name}")]
+pub struct MerchantInfo {
+    pub name: String,
+
+    /// Internet domain of the merchant, like `shop.example`
+    pub domain: Option<String>,
+
+    /// Public key which the merchant uses for signing its invoices
+    #[cfg_attr(
+        feature = "serde",
+        serde(with = "As::<Option<DisplayFromStr>>")
+    )]
+    pub identity_key: Option<secp256k1::PublicKey>,
 
-impl<'a> Iterator for BeneficiariesIter<'a> {
-    type Item = &'a Beneficiary;
+    /// Commitment to the merchant logo image
+    pub logo: Option<Details>,
+}
 
-    fn next(&mut self) -> Option<Self::Item> {
-        self.index += 1;
-        if self.index == 1 {
-            Some(&self.invoice.beneficiary)
-        } else {
-            self.invoice.alt_beneficiaries.get(self.index - 2)
+impl MerchantInfo {
+    pub fn new(name: impl ToString) -> MerchantInfo {
+        MerchantInfo {
+            name: name.to_string(),
+            domain: None,
+            identity_key: None,
+            logo: None,
         }
     }
 }
 
-/// An endpoint to a consignment exchange medium.
+/// Small document, like terms of sale, embedded directly into the invoice.
 #[derive(
     Clone,
     Ord,
@@ -492,331 +5574,709 @@ impl<'a> Iterator for BeneficiariesIter<'a> {
     PartialEq,
     Hash,
     Debug,
-    Display,
     StrictEncode,
     StrictDecode,
 )]
 #[cfg_attr(
     feature = "serde",
+    serde_as,
     derive(Serialize, Deserialize),
     serde(crate = "serde_crate")
 )]
-#[display(inner)]
-#[non_exhaustive]
-pub enum ConsignmentEndpoint {
-    /// Storm protocol
-    #[display("storm:{0}")]
-    Storm(NodeAddr),
+pub struct InlineDetails {
+    /// MIME type of the document, like `text/plain`
+    pub mime: String,
+    /// Raw document data
+    #[cfg_attr(feature = "serde", serde(with = "As::<serde_with::hex::Hex>"))]
+    pub data: Vec<u8>,
+}
 
-    /// RGB HTTP JSON-RPC protocol
-    #[display("rgbhttpjsonrpc:{0}")]
-    RgbHttpJsonRpc(String), // Url,
+impl InlineDetails {
+    pub fn new(mime: impl ToString, data: Vec<u8>) -> InlineDetails {
+        InlineDetails {
+            mime: mime.to_string(),
+            data,
+        }
+    }
+
+    /// Returns commitment to the document, matching the one produced by
+    /// [`Details::commit_to`] for the same data.
+    pub fn commitment(&self) -> sha256d::Hash {
+        sha256d::Hash::hash(&self.data)
+    }
 }
 
-#[derive(
-    Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Display, Error,
-)]
+impl Display for InlineDetails {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{}, {} bytes", self.mime, self.data.len())
+    }
+}
+
+/// Errors in [`AssetMetadata`] fields.
+#[derive(Clone, Eq, PartialEq, Hash, Debug, Display, Error)]
 #[display(doc_comments)]
-/// Incorrect consignment endpoint format
-pub struct ConsignmentEndpointParseError;
+pub enum AssetMetadataError {
+    /// asset ticker `{0}` must consist of 1 to 8 uppercase ASCII letters
+    Ticker(String),
 
-impl FromStr for ConsignmentEndpoint {
-    type Err = ConsignmentEndpointParseError;
+    /// asset precision {0} exceeds maximal precision of 18 decimal digits
+    Precision(u8),
+}
 
-    fn from_str(s: &str) -> Result<Self, Self::Err> {
-        match s.split_once(":") {
-            Some((protocol, endpoint)) => match protocol {
-                "storm" => Ok(ConsignmentEndpoint::Storm(
-                    NodeAddr::from_str(endpoint)
-                        .or(Err(ConsignmentEndpointParseError))?,
-                )),
-                "rgbhttpjsonrpc" => Ok(ConsignmentEndpoint::RgbHttpJsonRpc(
-                    endpoint.to_string(),
-                )),
-                _ => Err(ConsignmentEndpointParseError),
-            },
-            _ => Err(ConsignmentEndpointParseError),
+/// Display metadata of an RGB fungible asset, allowing wallets not having
+/// the asset contract yet to present the invoice amount to the user.
+///
+/// NB: The metadata are asserted by the invoice issuer and are not
+/// validated against the asset contract. Wallets must reconcile them with
+/// the contract data from the consignment before relying on them.
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    serde(crate = "serde_crate")
+)]
+#[derive(Clone, Ord, PartialOrd, Eq, PartialEq, Hash, Debug)]
+pub struct AssetMetadata {
+    pub ticker: String,
+    pub name: Option<String>,
+    /// Number of decimal digits in the asset amount fractional part
+    pub precision: u8,
+}
+
+impl AssetMetadata {
+    /// Maximal number of decimal digits in the asset amount fractional part
+    pub const MAX_PRECISION: u8 = 18;
+
+    /// Maximal length of the asset ticker
+    pub const MAX_TICKER_LEN: usize = 8;
+
+    /// Constructs asset metadata, checking that the ticker has 1 to 8
+    /// uppercase ASCII letters and that the precision does not exceed
+    /// [`AssetMetadata::MAX_PRECISION`].
+    pub fn new(
+        ticker: impl ToString,
+        name: Option<String>,
+        precision: u8,
+    ) -> Result<AssetMetadata, AssetMetadataError> {
+        let metadata = AssetMetadata {
+            ticker: ticker.to_string(),
+            name,
+            precision,
+        };
+        metadata.validate()?;
+        Ok(metadata)
+    }
+
+    /// Checks consistency of the metadata fields
+    pub fn validate(&self) -> Result<(), AssetMetadataError> {
+        let ticker = &self.ticker;
+        if ticker.is_empty()
+            || ticker.len() > Self::MAX_TICKER_LEN
+            || !ticker.bytes().all(|c| c.is_ascii_uppercase())
+        {
+            return Err(AssetMetadataError::Ticker(ticker.clone()));
+        }
+        if self.precision > Self::MAX_PRECISION {
+            return Err(AssetMetadataError::Precision(self.precision));
+        }
+        Ok(())
+    }
+
+    /// Renders amount given in atomic units as a decimal number followed by
+    /// the ticker, like `1.5 USDT`; trailing zeros of the fractional part
+    /// are omitted.
+    pub fn format_amount(&self, atomic: u64) -> String {
+        let divisor = 10u64.pow(self.precision as u32);
+        let (int, frac) = (atomic / divisor, atomic % divisor);
+        if frac == 0 {
+            return format!("{} {}", int, self.ticker);
+        }
+        let frac = format!("{:01$}", frac, self.precision as usize);
+        format!("{}.{} {}", int, frac.trim_end_matches('0'), self.ticker)
+    }
+}
+
+impl Display for AssetMetadata {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.ticker)?;
+        if let Some(name) = &self.name {
+            write!(f, " ({})", name)?;
         }
+        Ok(())
     }
 }
 
+/// RGB contract interface (or schema) expected by the invoice, allowing
+/// payers to reject unsupported contracts before fetching the consignment.
+///
+/// Strict-encoded as the 32-byte interface id followed by the name with
+/// 16-bit little-endian length prefix. Thus `RGB20` interface with id
+/// consisting of `0x01` bytes is put into the invoice TLV stream as type
+/// `1300000000000000`, length `2700` and value `0101…01` (32 bytes) `0500`
+/// `5247423230`.
+///
+/// Displayed as `<name>#<hex id>`.
+#[cfg_attr(
+    feature = "serde",
+    serde_as,
+    derive(Serialize, Deserialize),
+    serde(crate = "serde_crate")
+)]
 #[derive(
     Clone,
-    Copy,
-    PartialEq,
-    Eq,
-    PartialOrd,
     Ord,
+    PartialOrd,
+    Eq,
+    PartialEq,
     Hash,
     Debug,
     Display,
-    From,
     StrictEncode,
     StrictDecode,
 )]
-#[cfg_attr(
-    feature = "serde",
-    derive(Serialize, Deserialize),
-    serde(crate = "serde_crate", rename_all = "camelCase")
-)]
-#[non_exhaustive]
-pub enum Recurrent {
-    #[display("non-recurrent")]
-    NonRecurrent,
+#[display("{name}#{id}")]
+pub struct Iface {
+    #[cfg_attr(feature = "serde", serde(with = "As::<DisplayFromStr>"))]
+    pub id: Slice32,
+    /// Human-readable interface name, like `RGB20` or `RGB21`
+    pub name: String,
+}
 
-    #[display("each {0} seconds")]
-    Seconds(u64),
+impl Iface {
+    pub fn new(id: Slice32, name: impl ToString) -> Iface {
+        Iface {
+            id,
+            name: name.to_string(),
+        }
+    }
+}
 
-    #[display("each {0} months")]
-    Months(u8),
+impl FromStr for Iface {
+    type Err = IfaceParseError;
 
-    #[display("each {0} years")]
-    Years(u8),
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (name, id) = s.rsplit_once('#').ok_or(IfaceParseError)?;
+        if name.is_empty() {
+            return Err(IfaceParseError);
+        }
+        let id = Slice32::from_str(id).map_err(|_| IfaceParseError)?;
+        Ok(Iface::new(id, name))
+    }
 }
 
-impl Default for Recurrent {
-    fn default() -> Self {
-        Recurrent::NonRecurrent
+/// Error parsing [`Iface`] string.
+#[derive(
+    Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Hash, Debug, Display, Error,
+)]
+#[display("interface must be given as `<name>#<32-byte hex id>`")]
+pub struct IfaceParseError;
+
+impl StrictEncode for AssetMetadata {
+    fn strict_encode<E: io::Write>(
+        &self,
+        mut e: E,
+    ) -> Result<usize, strict_encoding::Error> {
+        Ok(strict_encode_list!(e; self.ticker, self.name, self.precision))
     }
 }
 
-impl Recurrent {
-    #[inline]
-    pub fn iter(&self) -> Recurrent {
-        *self
+impl StrictDecode for AssetMetadata {
+    fn strict_decode<D: io::Read>(
+        mut d: D,
+    ) -> Result<Self, strict_encoding::Error> {
+        AssetMetadata::new(
+            String::strict_decode(&mut d)?,
+            StrictDecode::strict_decode(&mut d)?,
+            StrictDecode::strict_decode(&mut d)?,
+        )
+        .map_err(|err| {
+            strict_encoding::Error::DataIntegrityError(err.to_string())
+        })
+    }
+}
+
+#[cfg_attr(
+    feature = "serde",
+    serde_as,
+    derive(Serialize, Deserialize),
+    serde(crate = "serde_crate")
+)]
+#[derive(Clone, Ord, PartialOrd, Eq, PartialEq, Hash, Debug)]
+pub struct CurrencyData {
+    #[cfg_attr(feature = "serde", serde(with = "As::<DisplayFromStr>"))]
+    pub iso4217: Iso4217,
+    pub coins: u32,
+    /// Fractional part of the amount, in units of `10^-decimals`
+    pub fractions: u16,
+    /// Number of decimal digits in the fractional part of the amount, which
+    /// is usually the currency exponent (see [`Iso4217::exponent`])
+    pub decimals: u8,
+    /// Price provider, strict-encoded as an empty string when absent
+    #[cfg_attr(
+        feature = "serde",
+        serde(with = "As::<Option<DisplayFromStr>>")
+    )]
+    pub price_provider: Option<Url>,
+}
+
+impl StrictEncode for CurrencyData {
+    fn strict_encode<E: io::Write>(
+        &self,
+        mut e: E,
+    ) -> Result<usize, strict_encoding::Error> {
+        let price_provider = self
+            .price_provider
+            .as_ref()
+            .map(Url::as_str)
+            .unwrap_or_default();
+        Ok(strict_encode_list!(e;
+            self.iso4217,
+            self.coins,
+            self.fractions,
+            self.decimals,
+            price_provider
+        ))
     }
 }
 
-impl Iterator for Recurrent {
-    type Item = Recurrent;
+impl StrictDecode for CurrencyData {
+    fn strict_decode<D: io::Read>(
+        mut d: D,
+    ) -> Result<Self, strict_encoding::Error> {
+        let data = CurrencyData {
+            iso4217: Iso4217::strict_decode(&mut d)?,
+            coins: u32::strict_decode(&mut d)?,
+            fractions: u16::strict_decode(&mut d)?,
+            decimals: u8::strict_decode(&mut d)?,
+            price_provider: decode_price_provider(&mut d)?,
+        };
+        data.validate().map_err(|err| {
+            strict_encoding::Error::DataIntegrityError(err.to_string())
+        })?;
+        Ok(data)
+    }
+}
 
-    #[inline]
-    fn next(&mut self) -> Option<Self::Item> {
-        match self {
-            Recurrent::NonRecurrent => None,
-            _ => Some(*self),
-        }
+fn decode_price_provider(
+    d: impl io::Read,
+) -> Result<Option<Url>, strict_encoding::Error> {
+    let price_provider = String::strict_decode(d)?;
+    if price_provider.is_empty() {
+        return Ok(None);
     }
+    Ok(Some(Url::with_encoded(price_provider, Url::HTTP_SCHEMES)?))
 }
 
-// TODO: Derive `Eq` & `Hash` once Psbt will support them
+/// Single currency requirement in the format used before the decimals were
+/// made explicit: fractions are kept in a single byte and the decimals are
+/// implied by the currency exponent.
 #[cfg_attr(
     feature = "serde",
-    serde_as,
     derive(Serialize, Deserialize),
-    serde(crate = "serde_crate", rename = "lowercase", untagged)
-)]
-#[derive(
-    Clone, Eq, PartialEq, Debug, Display, From, StrictEncode, StrictDecode,
+    serde(crate = "serde_crate", transparent)
 )]
-#[display(inner)]
-#[non_exhaustive]
-pub enum Beneficiary {
-    /// Addresses are useful when you do not like to leak public key
-    /// information
-    #[from]
-    Address(
-        #[cfg_attr(feature = "serde", serde(with = "As::<DisplayFromStr>"))]
-        Address,
-    ),
+#[derive(Clone, Ord, PartialOrd, Eq, PartialEq, Hash, Debug)]
+pub(crate) struct LegacyCurrencyData(CurrencyData);
 
-    /// Used by protocols that work with existing UTXOs and can assign some
-    /// client-validated data to them (like in RGB). We always hide the real
-    /// UTXO behind the hashed version (using some salt)
-    #[from]
-    BlindUtxo(ConcealedSeal),
+impl StrictEncode for LegacyCurrencyData {
+    fn strict_encode<E: io::Write>(
+        &self,
+        mut e: E,
+    ) -> Result<usize, strict_encoding::Error> {
+        let data = &self.0;
+        let fractions = u8::try_from(data.fractions)
+            .ok()
+            .filter(|_| data.is_legacy_representable())
+            .ok_or_else(|| {
+                strict_encoding::Error::DataIntegrityError(format!(
+                    "currency amount {} can't be represented in the legacy \
+                     encoding",
+                    data
+                ))
+            })?;
+        let price_provider = data
+            .price_provider
+            .as_ref()
+            .map(Url::as_str)
+            .unwrap_or_default();
+        Ok(strict_encode_list!(e;
+            data.iso4217,
+            data.coins,
+            fractions,
+            price_provider
+        ))
+    }
+}
 
-    /// Miniscript-based descriptors allowing custom derivation & key
-    /// generation
-    // TODO: Use Tracking account here
-    #[from]
-    Descriptor(
-        #[cfg_attr(feature = "serde", serde(with = "As::<DisplayFromStr>"))]
-        Descriptor<DescriptorPublicKey>,
-    ),
+impl StrictDecode for LegacyCurrencyData {
+    fn strict_decode<D: io::Read>(
+        mut d: D,
+    ) -> Result<Self, strict_encoding::Error> {
+        let iso4217 = Iso4217::strict_decode(&mut d)?;
+        Ok(LegacyCurrencyData(CurrencyData {
+            iso4217,
+            coins: u32::strict_decode(&mut d)?,
+            fractions: u8::strict_decode(&mut d)?.into(),
+            decimals: CurrencyData::implied_decimals(iso4217),
+            price_provider: decode_price_provider(&mut d)?,
+        }))
+    }
+}
 
-    /// Full transaction template in PSBT format
+/// Errors in [`CurrencyData`] amount.
+#[derive(Clone, Eq, PartialEq, Hash, Debug, Display, Error, From)]
+#[display(doc_comments)]
+pub enum CurrencyError {
+    /// fractions {fractions} do not fit {decimals} decimal digits
+    FractionsOutOfRange { fractions: u16, decimals: u8 },
+
+    /// currency amount can't have {0} decimal digits, the maximum is 4
+    DecimalsOutOfRange(u8),
+
+    /// {0}
     #[from]
-    // TODO: Fix display once PSBT implement `Display`
-    #[display("PSBT!")]
-    Psbt(Psbt),
+    Code(Iso4217Error),
 
-    /// Lightning node receiving the payment. Not the same as lightning invoice
-    /// since many of the invoice data now will be part of [`Invoice`] here.
+    /// invalid price provider: {0}
     #[from]
-    Bolt(LnAddress),
+    PriceProvider(UrlError),
 
-    // TODO: Add Bifrost invoices
-    /// Fallback option for all future variants
-    Unknown(
-        #[cfg_attr(feature = "serde", serde(with = "As::<DisplayFromStr>"))]
-        Blob,
-    ),
+    /// invoice can't have more than 16 currency requirements
+    TooManyRequirements,
+
+    /// invoice has both a single currency requirement and a list of them
+    AmbiguousRequirements,
+
+    /// invalid currency amount `{0}`: must be in form of
+    /// `<amount> <ISO4217 code> [<price provider>]`
+    InvalidFormat(String),
+}
+
+impl CurrencyData {
+    /// Exponent assumed for currencies not having a known minor unit.
+    pub const DEFAULT_EXPONENT: u8 = 2;
+
+    /// Maximal number of decimal digits in the fractional part of the
+    /// amount.
+    pub const MAX_DECIMALS: u8 = 4;
+
+    /// Constructs currency data, checking that the fractions fit the given
+    /// number of decimal digits.
+    pub fn new(
+        iso4217: Iso4217,
+        coins: u32,
+        fractions: u16,
+        decimals: u8,
+        price_provider: Option<Url>,
+    ) -> Result<Self, CurrencyError> {
+        let data = CurrencyData {
+            iso4217,
+            coins,
+            fractions,
+            decimals,
+            price_provider,
+        };
+        data.validate()?;
+        Ok(data)
+    }
+
+    /// Constructs currency data without price provider from the amount in
+    /// major and minor currency units (e.g. dollars and cents), using the
+    /// currency exponent as the number of decimal digits.
+    pub fn from_major_minor(
+        iso4217: Iso4217,
+        major: u32,
+        minor: u16,
+    ) -> Result<Self, CurrencyError> {
+        let decimals = Self::implied_decimals(iso4217);
+        CurrencyData::new(iso4217, major, minor, decimals, None)
+    }
+
+    /// Returns number of decimal digits implied for the currency by older
+    /// invoices: the currency exponent or [`CurrencyData::DEFAULT_EXPONENT`]
+    /// for unknown currencies and the ones without minor units.
+    pub fn implied_decimals(iso4217: Iso4217) -> u8 {
+        iso4217.exponent().unwrap_or(Self::DEFAULT_EXPONENT)
+    }
+
+    /// Returns number of decimal digits in the fractional part of the amount.
+    pub fn exponent(&self) -> u8 {
+        self.decimals
+    }
+
+    /// Returns the whole amount in units of `10^-decimals`.
+    pub fn to_minor_units(&self) -> u64 {
+        u64::from(self.coins) * 10u64.pow(self.decimals.into())
+            + u64::from(self.fractions)
+    }
+
+    /// Detects whether the amount can be put into the invoice record used
+    /// for a single currency requirement before the decimals were made
+    /// explicit.
+    pub fn is_legacy_representable(&self) -> bool {
+        self.decimals == Self::implied_decimals(self.iso4217)
+            && self.fractions <= u8::MAX as u16
+    }
+
+    /// Checks that the fractions fit the number of decimal digits.
+    pub fn validate(&self) -> Result<(), CurrencyError> {
+        if self.decimals > Self::MAX_DECIMALS {
+            return Err(CurrencyError::DecimalsOutOfRange(self.decimals));
+        }
+        if u32::from(self.fractions) >= 10u32.pow(self.decimals.into()) {
+            return Err(CurrencyError::FractionsOutOfRange {
+                fractions: self.fractions,
+                decimals: self.decimals,
+            });
+        }
+        Ok(())
+    }
+}
+
+/// Currency data is represented as `<amount> <ISO4217 code>`, followed by the
+/// price provider, if any. The amount has exactly as many fractional digits as
+/// the amount decimals, e.g. `10.05 USD`, `100 JPY` or `1.500 BHD`. When
+/// parsed, the amount gets the currency exponent as decimals, unless it has
+/// more fractional digits (up to [`CurrencyData::MAX_DECIMALS`]), so `1.5 USD`
+/// means 1 dollar and 50 cents, while `0.125 USD` keeps 3 decimals.
+impl Display for CurrencyData {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self.decimals {
+            0 => write!(f, "{} {}", self.coins, self.iso4217)?,
+            decimals => write!(
+                f,
+                "{}.{:0width$} {}",
+                self.coins,
+                self.fractions,
+                self.iso4217,
+                width = decimals as usize
+            )?,
+        }
+        if let Some(price_provider) = &self.price_provider {
+            write!(f, " {}", price_provider)?;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for CurrencyData {
+    type Err = CurrencyError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || CurrencyError::InvalidFormat(s.to_owned());
+        let is_number =
+            |s: &str| !s.is_empty() && s.chars().all(|c| c.is_ascii_digit());
+
+        let mut parts = s.split_whitespace();
+        let (amount, iso4217) = match (parts.next(), parts.next()) {
+            (Some(amount), Some(iso4217)) => {
+                (amount, Iso4217::from_str(iso4217)?)
+            }
+            _ => return Err(invalid()),
+        };
+        let price_provider = parts.next().map(Url::from_str).transpose()?;
+        if parts.next().is_some() {
+            return Err(invalid());
+        }
+
+        let exponent = Self::implied_decimals(iso4217);
+        let (major, minor) = match amount.split_once('.') {
+            None if is_number(amount) => (amount, ""),
+            Some((major, minor)) if is_number(major) && is_number(minor) => {
+                (major, minor)
+            }
+            _ => return Err(invalid()),
+        };
+        let decimals = u8::try_from(minor.len())
+            .map_err(|_| invalid())?
+            .max(exponent);
+        if decimals > Self::MAX_DECIMALS {
+            return Err(CurrencyError::DecimalsOutOfRange(decimals));
+        }
+        // Fractional part is positional: `1.5 USD` means 1 dollar and 50
+        // cents
+        let fractions =
+            format!("{:0<width$}", minor, width = decimals as usize);
+        let fractions = fractions.parse().unwrap_or_default();
+        let major = major.parse().map_err(|_| invalid())?;
+
+        CurrencyData::new(iso4217, major, fractions, decimals, price_provider)
+    }
+}
+
+/// Maximal number of consignment endpoints an invoice may have.
+pub const MAX_CONSIGNMENT_ENDPOINTS: usize = 16;
+
+/// Errors in the list of invoice consignment endpoints
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug, Display, Error)]
+#[display(doc_comments)]
+pub enum ConsignmentEndpointsError {
+    /// invoice has {0} consignment endpoints, while at most 16 are allowed
+    TooMany(usize),
 }
 
-#[derive(
-    Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Display, Error,
-)]
-#[display(doc_comments)]
-/// Incorrect beneficiary format
-pub struct BeneficiaryParseError;
+/// Maximal length of the invoice payment reference, in bytes.
+pub const MAX_REFERENCE_LEN: usize = 32;
 
-// TODO: Since we can't present full beneficiary data in a string form (because
-//       of the lightning part) we have to remove this implementation once
-//       serde_with will be working
-impl FromStr for Beneficiary {
-    type Err = BeneficiaryParseError;
+/// Errors in invoice payment reference
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug, Display, Error)]
+#[display(doc_comments)]
+pub enum ReferenceError {
+    /// payment reference has {0} bytes, exceeding the limit of 32 bytes
+    TooLong(usize),
+}
 
-    fn from_str(s: &str) -> Result<Self, Self::Err> {
-        if let Ok(address) = Address::from_str(s) {
-            Ok(Beneficiary::Address(address))
-        } else if let Ok(outpoint) = ConcealedSeal::from_str(s) {
-            Ok(Beneficiary::BlindUtxo(outpoint))
-        } else if let Ok(descriptor) =
-            Descriptor::<DescriptorPublicKey>::from_str(s)
-        {
-            Ok(Beneficiary::Descriptor(descriptor))
-        } else {
-            Err(BeneficiaryParseError)
-        }
+fn check_reference(reference: &Blob) -> Result<(), ReferenceError> {
+    if reference.as_inner().len() > MAX_REFERENCE_LEN {
+        return Err(ReferenceError::TooLong(reference.as_inner().len()));
     }
+    Ok(())
 }
 
+/// List of invoice consignment endpoints, which length is limited with
+/// [`MAX_CONSIGNMENT_ENDPOINTS`] on decoding.
 #[cfg_attr(
     feature = "serde",
-    serde_as,
     derive(Serialize, Deserialize),
-    serde(crate = "serde_crate")
+    serde(crate = "serde_crate", transparent)
 )]
-#[derive(
-    Clone,
-    Ord,
-    PartialOrd,
-    Eq,
-    PartialEq,
-    Hash,
-    Debug,
-    Display,
-    StrictEncode,
-    StrictDecode,
-)]
-#[display("{node_id}")]
-pub struct LnAddress {
-    pub node_id: NodeId,
-    pub features: InitFeatures,
-    #[cfg_attr(feature = "serde", serde(with = "As::<DisplayFromStr>"))]
-    pub lock: HashLock, /* When PTLC will be available the same field will
-                         * be re-used + the use of
-                         * PTCL will be indicated with
-                         * a feature flag */
-    pub secret: Option<Slice32>,
-    pub network: Chain,
-    pub min_final_cltv_expiry: Option<u16>,
-    pub path_hints: Vec<LnPathHint>,
-}
+#[derive(Clone, Ord, PartialOrd, Eq, PartialEq, Hash, Debug, Default)]
+pub(crate) struct ConsignmentEndpoints(Vec<ConsignmentEndpoint>);
 
-/// Path hints for a lightning network payment, equal to the value of the `r`
-/// key of the lightning BOLT-11 invoice
-/// <https://github.com/lightningnetwork/lightning-rfc/blob/master/11-payment-encoding.md#tagged-fields>
-#[cfg_attr(
-    feature = "serde",
-    serde_as,
-    derive(Serialize, Deserialize),
-    serde(crate = "serde_crate")
-)]
-#[derive(
-    Copy,
-    Clone,
-    Ord,
-    PartialOrd,
-    Eq,
-    PartialEq,
-    Hash,
-    Debug,
-    Display,
-    StrictEncode,
-    StrictDecode,
-)]
-#[display("{short_channel_id}@{node_id}")]
-pub struct LnPathHint {
-    pub node_id: NodeId,
-    #[cfg_attr(feature = "serde", serde(with = "As::<DisplayFromStr>"))]
-    pub short_channel_id: ShortChannelId,
-    pub fee_base_msat: u32,
-    pub fee_proportional_millionths: u32,
-    pub cltv_expiry_delta: u16,
-}
+impl ConsignmentEndpoints {
+    /// Used by the derived TLV encoding to detect empty lists
+    pub(crate) fn iter(&self) -> std::slice::Iter<'_, ConsignmentEndpoint> {
+        self.0.iter()
+    }
 
-#[derive(
-    Copy,
-    Clone,
-    Ord,
-    PartialOrd,
-    Eq,
-    PartialEq,
-    Hash,
-    Debug,
-    Display,
-    From,
-    StrictEncode,
-    StrictDecode,
-)]
-pub enum AmountExt {
-    /// Payments for any amount is accepted: useful for charity/donations, etc
-    #[display("any")]
-    Any,
+    /// Adds endpoint unless it is already present or the list is full.
+    pub(crate) fn push(&mut self, endpoint: ConsignmentEndpoint) -> bool {
+        if self.0.len() >= MAX_CONSIGNMENT_ENDPOINTS
+            || self.0.contains(&endpoint)
+        {
+            return false;
+        }
+        self.0.push(endpoint);
+        true
+    }
 
-    #[from]
-    #[display(inner)]
-    Normal(u64),
+    /// Adds endpoint unless it is already present, ignoring the limit on the
+    /// number of endpoints, which is checked with [`Self::check`].
+    pub(crate) fn push_unchecked(
+        &mut self,
+        endpoint: ConsignmentEndpoint,
+    ) -> bool {
+        if self.0.contains(&endpoint) {
+            return false;
+        }
+        self.0.push(endpoint);
+        true
+    }
 
-    #[display("{0}.{1}")]
-    Milli(u64, u16),
+    pub(crate) fn check(&self) -> Result<(), ConsignmentEndpointsError> {
+        if self.0.len() > MAX_CONSIGNMENT_ENDPOINTS {
+            return Err(ConsignmentEndpointsError::TooMany(self.0.len()));
+        }
+        Ok(())
+    }
 }
 
-impl Default for AmountExt {
-    fn default() -> Self {
-        AmountExt::Any
+impl StrictEncode for ConsignmentEndpoints {
+    fn strict_encode<E: io::Write>(
+        &self,
+        e: E,
+    ) -> Result<usize, strict_encoding::Error> {
+        self.0.strict_encode(e)
     }
 }
 
-impl AmountExt {
-    pub fn atomic_value(&self) -> Option<u64> {
-        match self {
-            AmountExt::Any => None,
-            AmountExt::Normal(val) => Some(*val),
-            AmountExt::Milli(_, _) => None,
-        }
+impl StrictDecode for ConsignmentEndpoints {
+    fn strict_decode<D: io::Read>(
+        d: D,
+    ) -> Result<Self, strict_encoding::Error> {
+        let endpoints =
+            ConsignmentEndpoints(Vec::<ConsignmentEndpoint>::strict_decode(d)?);
+        endpoints.check().map_err(|err| {
+            strict_encoding::Error::DataIntegrityError(err.to_string())
+        })?;
+        Ok(endpoints)
     }
 }
 
-#[derive(
-    Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Display, Error, From,
+/// Maximal number of currency requirements an invoice may have.
+pub const MAX_CURRENCY_REQUIREMENTS: usize = 16;
+
+/// Ordered list of invoice currency requirements, containing at most one
+/// entry per ISO 4217 currency code, which length is limited with
+/// [`MAX_CURRENCY_REQUIREMENTS`] on decoding.
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    serde(crate = "serde_crate", transparent)
 )]
-#[display(doc_comments)]
-#[from(std::num::ParseIntError)]
-/// Incorrect beneficiary format
-pub struct AmountParseError;
+#[derive(Clone, Ord, PartialOrd, Eq, PartialEq, Hash, Debug, Default)]
+pub(crate) struct CurrencyRequirements(Vec<CurrencyData>);
 
-impl FromStr for AmountExt {
-    type Err = AmountParseError;
+impl CurrencyRequirements {
+    /// Used by the derived TLV encoding to detect empty lists
+    pub(crate) fn iter(&self) -> std::slice::Iter<'_, CurrencyData> {
+        self.0.iter()
+    }
 
-    fn from_str(s: &str) -> Result<Self, Self::Err> {
-        if s.trim().to_lowercase() == "any" {
-            return Ok(AmountExt::Any);
+    #[cfg(feature = "serde")]
+    fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+impl StrictEncode for CurrencyRequirements {
+    fn strict_encode<E: io::Write>(
+        &self,
+        e: E,
+    ) -> Result<usize, strict_encoding::Error> {
+        self.0.strict_encode(e)
+    }
+}
+
+impl StrictDecode for CurrencyRequirements {
+    fn strict_decode<D: io::Read>(
+        d: D,
+    ) -> Result<Self, strict_encoding::Error> {
+        let requirements = Vec::<CurrencyData>::strict_decode(d)?;
+        if requirements.len() > MAX_CURRENCY_REQUIREMENTS {
+            return Err(strict_encoding::Error::DataIntegrityError(
+                CurrencyError::TooManyRequirements.to_string(),
+            ));
         }
-        let mut split = s.split(".");
-        Ok(match (split.next(), split.next()) {
-            (Some(amt), None) => AmountExt::Normal(amt.parse()?),
-            (Some(int), Some(frac)) => {
-                AmountExt::Milli(int.parse()?, frac.parse()?)
+        for (no, currency_data) in requirements.iter().enumerate() {
+            if requirements[..no]
+                .iter()
+                .any(|data| data.iso4217 == currency_data.iso4217)
+            {
+                return Err(strict_encoding::Error::DataIntegrityError(
+                    format!(
+                        "duplicated currency requirement for {}",
+                        currency_data.iso4217
+                    ),
+                ));
             }
-            _ => Err(AmountParseError)?,
-        })
+        }
+        Ok(CurrencyRequirements(requirements))
     }
 }
 
+/// Quantity of items which may be paid with a single invoice, where the
+/// invoice amount is a price per item.
+///
+/// String representation is one of the following:
+/// - `N` - exactly `N` items;
+/// - `min..max` - from `min` to `max` items;
+/// - `min..` - `min` items or more.
+///
+/// Ranges may be followed by `=default` specifying the quantity proposed to
+/// the payer by default; if omitted, the default is `min` or a single item,
+/// whichever is greater, but not more than `max`.
 #[derive(
+    Copy,
     Clone,
     Ord,
     PartialOrd,
@@ -824,7 +6284,7 @@ impl FromStr for AmountExt {
     PartialEq,
     Hash,
     Debug,
-    Display,
+    From,
     StrictEncode,
     StrictDecode,
 )]
@@ -833,95 +6293,145 @@ impl FromStr for AmountExt {
     derive(Serialize, Deserialize),
     serde(crate = "serde_crate")
 )]
-#[display("{source}#commitment")]
-pub struct Details {
-    #[cfg_attr(feature = "serde", serde(with = "As::<DisplayFromStr>"))]
-    pub commitment: sha256d::Hash,
-    pub source: String, // Url
+pub struct Quantity {
+    pub min: u32, // We will default to zero
+    pub max: Option<u32>,
+    #[from]
+    pub default: u32,
 }
 
-#[derive(Clone, Ord, PartialOrd, Eq, PartialEq, Hash, Debug, From)]
-// TODO: Move to amplify library
-pub struct Iso4217([u8; 3]);
-
-impl Display for Iso4217 {
-    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
-        f.write_char(self.0[0].into())?;
-        f.write_char(self.0[1].into())?;
-        f.write_char(self.0[2].into())
+impl Default for Quantity {
+    fn default() -> Self {
+        Self {
+            min: 0,
+            max: None,
+            default: 1,
+        }
     }
 }
 
-#[derive(
-    Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Hash, Debug, Display, Error,
-)]
+/// Errors in quantity data
+#[derive(Clone, Eq, PartialEq, Hash, Debug, Display, Error)]
 #[display(doc_comments)]
-pub enum Iso4217Error {
-    /// Wrong string length to parse ISO4217 data
-    WrongLen,
+pub enum QuantityError {
+    /// minimal quantity {min} exceeds maximal quantity {max}
+    Range { min: u32, max: u32 },
+
+    /// default quantity {default} lies outside of the allowed quantity range
+    Default { default: u32 },
+
+    /// invalid quantity `{0}`: must be in form of `N`, `min..[max]` or
+    /// `min..[max]=default`
+    InvalidFormat(String),
 }
 
-impl FromStr for Iso4217 {
-    type Err = Iso4217Error;
+impl Quantity {
+    /// Constructs quantity, checking that `default` lies within `min..=max`
+    /// range.
+    pub fn new(
+        min: u32,
+        max: Option<u32>,
+        default: u32,
+    ) -> Result<Quantity, QuantityError> {
+        let quantity = Quantity { min, max, default };
+        quantity.validate()?;
+        Ok(quantity)
+    }
 
-    fn from_str(s: &str) -> Result<Self, Self::Err> {
-        if s.bytes().len() != 3 {
-            return Err(Iso4217Error::WrongLen);
+    /// Constructs quantity with the default of a single item, or of the
+    /// closest number of items allowed by the range.
+    pub fn with_range(
+        min: u32,
+        max: Option<u32>,
+    ) -> Result<Quantity, QuantityError> {
+        Quantity::new(min, max, Quantity::implied_default(min, max))
+    }
+
+    /// Checks consistency of the quantity fields
+    pub fn validate(&self) -> Result<(), QuantityError> {
+        if let Some(max) = self.max {
+            if self.min > max {
+                return Err(QuantityError::Range { min: self.min, max });
+            }
         }
+        if !self.contains(self.default) {
+            return Err(QuantityError::Default {
+                default: self.default,
+            });
+        }
+        Ok(())
+    }
 
-        let mut inner = [0u8; 3];
-        inner.copy_from_slice(&s.bytes().collect::<Vec<u8>>()[0..3]);
-        Ok(Iso4217(inner))
+    /// Detects whether `n` items are allowed by the quantity range
+    pub fn contains(&self, n: u32) -> bool {
+        n >= self.min && self.max.map(|max| n <= max).unwrap_or(true)
+    }
+
+    fn implied_default(min: u32, max: Option<u32>) -> u32 {
+        let default = min.max(1);
+        max.map(|max| default.min(max)).unwrap_or(default)
     }
 }
 
-impl StrictEncode for Iso4217 {
-    fn strict_encode<E: io::Write>(
-        &self,
-        mut e: E,
-    ) -> Result<usize, strict_encoding::Error> {
-        e.write(&self.0)?;
-        Ok(3)
+impl Display for Quantity {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        if self.max == Some(self.min) && self.default == self.min {
+            return write!(f, "{}", self.min);
+        }
+        write!(f, "{}..", self.min)?;
+        if let Some(max) = self.max {
+            write!(f, "{}", max)?;
+        }
+        if self.default != Quantity::implied_default(self.min, self.max) {
+            write!(f, "={}", self.default)?;
+        }
+        Ok(())
     }
 }
 
-impl StrictDecode for Iso4217 {
-    fn strict_decode<D: io::Read>(
-        mut d: D,
-    ) -> Result<Self, strict_encoding::Error> {
-        let mut me = Self([0u8; 3]);
-        d.read_exact(&mut me.0)?;
-        Ok(me)
+impl FromStr for Quantity {
+    type Err = QuantityError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || QuantityError::InvalidFormat(s.to_owned());
+        let number = |s: &str| {
+            if s.is_empty() || !s.chars().all(|c| c.is_ascii_digit()) {
+                return Err(invalid());
+            }
+            s.parse::<u32>().map_err(|_| invalid())
+        };
+
+        let (range, default) = match s.split_once('=') {
+            Some((range, default)) => (range, Some(number(default)?)),
+            None => (s, None),
+        };
+        let (min, max) = match range.split_once("..") {
+            Some((min, "")) => (number(min)?, None),
+            Some((min, max)) => (number(min)?, Some(number(max)?)),
+            None if default.is_none() => {
+                let n = number(range)?;
+                (n, Some(n))
+            }
+            None => return Err(invalid()),
+        };
+        let default =
+            default.unwrap_or_else(|| Quantity::implied_default(min, max));
+        Quantity::new(min, max, default)
     }
 }
 
-#[cfg_attr(
-    feature = "serde",
-    serde_as,
-    derive(Serialize, Deserialize),
-    serde(crate = "serde_crate")
-)]
-#[derive(
-    Clone,
-    Ord,
-    PartialOrd,
-    Eq,
-    PartialEq,
-    Hash,
-    Debug,
-    Display,
-    StrictEncode,
-    StrictDecode,
-)]
-#[display("{coins}.{fractions} {iso4217}")]
-pub struct CurrencyData {
-    #[cfg_attr(feature = "serde", serde(with = "As::<DisplayFromStr>"))]
-    pub iso4217: Iso4217,
-    pub coins: u32,
-    pub fractions: u8,
-    pub price_provider: String, // Url,
+/// Errors in on-chain payment policy data
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug, Display, Error)]
+#[display(doc_comments)]
+pub enum OnchainPolicyError {
+    /// minimal feerate {0} sat/vB lies outside of the allowed range from 1 to
+    /// 10000 sat/vB
+    Feerate(u32),
 }
 
+/// Requirements of the merchant to on-chain payment transactions, which
+/// payer wallets should follow, together with the number of confirmations
+/// the merchant waits for before considering the invoice paid.
 #[derive(
     Copy,
     Clone,
@@ -931,40 +6441,144 @@ pub struct CurrencyData {
     PartialEq,
     Hash,
     Debug,
-    From,
+    Default,
     StrictEncode,
     StrictDecode,
 )]
 #[cfg_attr(
     feature = "serde",
     derive(Serialize, Deserialize),
-    serde(crate = "serde_crate")
+    serde(crate = "serde_crate", rename_all = "camelCase")
 )]
-pub struct Quantity {
-    pub min: u32, // We will default to zero
-    pub max: Option<u32>,
-    #[from]
-    pub default: u32,
+pub struct OnchainPolicy {
+    /// Minimal feerate of the payment transaction, in sat/vB
+    pub min_feerate_sat_vb: Option<u32>,
+    /// Whether the payment transaction must signal replaceability (BIP-125);
+    /// `false` forbids signalling, which is used by merchants accepting
+    /// unconfirmed payments
+    pub rbf_required: Option<bool>,
+    /// Number of confirmations required by the merchant
+    pub min_confirmations: Option<u8>,
 }
 
-impl Default for Quantity {
-    fn default() -> Self {
-        Self {
-            min: 0,
-            max: None,
-            default: 1,
+impl OnchainPolicy {
+    /// The largest feerate which can be required by invoices, in sat/vB
+    pub const MAX_FEERATE: u32 = 10_000;
+
+    /// Checks that the minimal feerate lies within
+    /// `1..=`[`OnchainPolicy::MAX_FEERATE`] range.
+    pub fn validate(&self) -> Result<(), OnchainPolicyError> {
+        match self.min_feerate_sat_vb {
+            Some(feerate) if feerate == 0 || feerate > Self::MAX_FEERATE => {
+                Err(OnchainPolicyError::Feerate(feerate))
+            }
+            _ => Ok(()),
         }
     }
 }
 
-impl Display for Quantity {
+impl Display for OnchainPolicy {
     fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
-        write!(f, "{} items", self.default)?;
-        match (self.min, self.max) {
-            (0, Some(max)) => write!(f, " (or any amount up to {})", max),
-            (0, None) => Ok(()),
-            (_, Some(max)) => write!(f, " (or from {} to {})", self.min, max),
-            (_, None) => write!(f, " (or any amount above {})", self.min),
+        let mut parts = vec![];
+        if let Some(feerate) = self.min_feerate_sat_vb {
+            parts.push(format!("min feerate {} sat/vB", feerate));
+        }
+        match self.rbf_required {
+            Some(true) => parts.push("RBF required".to_owned()),
+            Some(false) => parts.push("RBF not allowed".to_owned()),
+            None => {}
+        }
+        if let Some(confirmations) = self.min_confirmations {
+            parts.push(format!("{} confirmations", confirmations));
+        }
+        if parts.is_empty() {
+            return f.write_str("none");
+        }
+        f.write_str(&parts.join(", "))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn quantity_round_trip() {
+        for (s, quantity) in [
+            ("0", Quantity::new(0, Some(0), 0)),
+            ("5", Quantity::new(5, Some(5), 5)),
+            ("0..", Quantity::new(0, None, 1)),
+            ("1..", Quantity::new(1, None, 1)),
+            ("3..", Quantity::new(3, None, 3)),
+            ("0..10", Quantity::new(0, Some(10), 1)),
+            ("2..10", Quantity::new(2, Some(10), 2)),
+            ("0..0", Quantity::new(0, Some(0), 0)),
+            ("5..5=5", Quantity::new(5, Some(5), 5)),
+            ("1..10=4", Quantity::new(1, Some(10), 4)),
+            ("0..=0", Quantity::new(0, None, 0)),
+            (
+                "0..4294967295=4294967295",
+                Quantity::new(0, Some(u32::MAX), u32::MAX),
+            ),
+        ] {
+            let quantity = quantity.unwrap();
+            let parsed = Quantity::from_str(s).unwrap();
+            assert_eq!(parsed, quantity, "{}", s);
+            assert_eq!(
+                Quantity::from_str(&parsed.to_string()).unwrap(),
+                parsed,
+                "{}",
+                s
+            );
+        }
+
+        // Canonical forms are displayed as they are
+        for s in ["0", "5", "0..", "3..", "2..10", "1..10=4", "0..=0"] {
+            assert_eq!(Quantity::from_str(s).unwrap().to_string(), s);
+        }
+        assert_eq!(Quantity::from_str("5..5=5").unwrap().to_string(), "5");
+        assert_eq!(Quantity::default().to_string(), "0..");
+    }
+
+    #[test]
+    fn quantity_invalid_input() {
+        for s in [
+            "",
+            "..",
+            "=1",
+            "a",
+            "-1",
+            "+1",
+            " 1",
+            "1 ",
+            "1..a",
+            "a..1",
+            "1..2..3",
+            "1=1",
+            "1..2=",
+            "1..2=a",
+            "0x10",
+            "4294967296",
+            "1..4294967296",
+        ] {
+            assert_eq!(
+                Quantity::from_str(s),
+                Err(QuantityError::InvalidFormat(s.to_owned())),
+                "{}",
+                s
+            );
         }
+        assert_eq!(
+            Quantity::from_str("10..2"),
+            Err(QuantityError::Range { min: 10, max: 2 })
+        );
+        assert_eq!(
+            Quantity::from_str("1..10=11"),
+            Err(QuantityError::Default { default: 11 })
+        );
+        assert_eq!(
+            Quantity::from_str("2..=1"),
+            Err(QuantityError::Default { default: 1 })
+        );
     }
 }
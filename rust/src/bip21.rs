@@ -0,0 +1,262 @@
+// LNP/BP universal invoice library implementing LNPBP-38 standard
+// Written in 2021 by
+//     Dr. Maxim Orlovsky <orlovsky@pandoracore.com>
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the MIT License
+// along with this software.
+// If not, see <https://opensource.org/licenses/MIT>.
+
+//! Interoperability with BIP-21 `bitcoin:` URIs.
+//!
+//! The address beneficiary is put into the URI path, the invoice amount is
+//! converted into BTC, merchant and purpose become `label` and `message`
+//! parameters. The full invoice is embedded as `lnpbp` parameter, allowing
+//! LNPBP-38-aware wallets to recover all of the invoice data.
+
+use bitcoin::Address;
+use percent_encoding::{
+    percent_decode_str, utf8_percent_encode, AsciiSet, NON_ALPHANUMERIC,
+};
+use std::str::FromStr;
+
+use lnpbp::chain::Chain;
+
+use crate::{is_native_asset_of, AmountExt, Beneficiary, Invoice};
+
+/// URI scheme used by BIP-21
+pub const BIP21_SCHEME: &str = "bitcoin";
+
+/// Name of BIP-21 URI parameter containing the full LNPBP-38 invoice
+pub const BIP21_LNPBP_PARAM: &str = "lnpbp";
+
+/// Characters which are not percent-encoded in BIP-21 parameter values
+const BIP21_VALUE: &AsciiSet = &NON_ALPHANUMERIC
+    .remove(b'-')
+    .remove(b'.')
+    .remove(b'_')
+    .remove(b'~');
+
+const SATS_IN_BTC: u64 = 100_000_000;
+
+/// Errors converting invoices to and from BIP-21 URIs.
+#[derive(Clone, Eq, PartialEq, Hash, Debug, Display, Error)]
+#[display(doc_comments)]
+pub enum Bip21Error {
+    /// URI does not use `bitcoin:` scheme
+    WrongScheme,
+
+    /// invalid bitcoin address `{0}` in the URI
+    InvalidAddress(String),
+
+    /// invalid BTC amount `{0}` in the URI
+    InvalidAmount(String),
+
+    /// amount `{0}` has more than 8 decimal places
+    AmountPrecision(String),
+
+    /// BIP-21 URI parameter `{0}` is not supported and is required
+    UnknownRequiredParam(String),
+
+    /// BIP-21 URI parameter `{0}` is repeated
+    RepeatedParam(String),
+
+    /// parameter value is not a valid percent-encoded UTF-8 string
+    InvalidEncoding,
+
+    /// invalid LNPBP-38 invoice embedded into the URI
+    InvalidInvoice,
+
+    /// LNPBP-38 invoice embedded into the URI does not pay to the URI address
+    InconsistentInvoice,
+
+    /// BIP-21 URI parameter `{0}` contradicts the embedded LNPBP-38 invoice
+    InconsistentParam(String),
+
+    /// invoice has no on-chain address beneficiary
+    NoAddress,
+
+    /// invoice amount can't be represented in BTC with satoshi precision
+    UnrepresentableAmount,
+}
+
+impl Invoice {
+    /// Constructs BIP-21 URI paying to the first address beneficiary of the
+    /// invoice (in the order of beneficiary priority). The amount is put
+    /// into the URI only for invoices paying in bitcoins; amounts of other
+    /// assets are available from the embedded invoice only.
+    pub fn to_bip21(&self) -> Result<String, Bip21Error> {
+        let address = self
+            .beneficiaries()
+            .find_map(|beneficiary| match beneficiary {
+                Beneficiary::Address(address) => Some(address),
+                _ => None,
+            })
+            .ok_or(Bip21Error::NoAddress)?;
+
+        let mut params = vec![];
+        match self.amount() {
+            _ if !self.pays_btc(address) => {}
+            AmountExt::Any => {}
+            AmountExt::Normal(sats) => {
+                params.push(format!("amount={}", format_btc(*sats)))
+            }
+            AmountExt::Milli(..) => {
+                return Err(Bip21Error::UnrepresentableAmount)
+            }
+        }
+        if let Some(merchant) = self.merchant_name() {
+            params.push(format!("label={}", encode_value(merchant)));
+        }
+        if let Some(purpose) = self.purpose() {
+            params.push(format!("message={}", encode_value(purpose)));
+        }
+        params.push(format!("{}={}", BIP21_LNPBP_PARAM, self));
+
+        Ok(format!("{}:{}?{}", BIP21_SCHEME, address, params.join("&")))
+    }
+
+    /// Parses BIP-21 URI. If the URI contains embedded LNPBP-38 invoice, it
+    /// is returned after checking that it pays to the URI address and that
+    /// the URI amount, label and message match the invoice; otherwise a new
+    /// invoice is constructed from the URI data.
+    pub fn from_bip21(uri: &str) -> Result<Invoice, Bip21Error> {
+        let (scheme, rest) =
+            uri.split_once(':').ok_or(Bip21Error::WrongScheme)?;
+        if !scheme.eq_ignore_ascii_case(BIP21_SCHEME) {
+            return Err(Bip21Error::WrongScheme);
+        }
+        let (address, query) = rest.split_once('?').unwrap_or((rest, ""));
+        let address = Address::from_str(address)
+            .map_err(|_| Bip21Error::InvalidAddress(address.to_owned()))?;
+
+        let mut amount = None;
+        let mut label = None;
+        let mut message = None;
+        let mut embedded = None;
+        for param in query.split('&').filter(|param| !param.is_empty()) {
+            let (key, value) = param.split_once('=').unwrap_or((param, ""));
+            let value = percent_decode_str(value)
+                .decode_utf8()
+                .map_err(|_| Bip21Error::InvalidEncoding)?
+                .into_owned();
+            let slot = match key {
+                "amount" => {
+                    if amount.is_some() {
+                        return Err(Bip21Error::RepeatedParam(key.to_owned()));
+                    }
+                    amount = Some(parse_btc(&value)?);
+                    continue;
+                }
+                "label" => &mut label,
+                "message" => &mut message,
+                BIP21_LNPBP_PARAM => &mut embedded,
+                key if key.starts_with("req-") => {
+                    return Err(Bip21Error::UnknownRequiredParam(
+                        key.to_owned(),
+                    ))
+                }
+                _ => continue,
+            };
+            if slot.is_some() {
+                return Err(Bip21Error::RepeatedParam(key.to_owned()));
+            }
+            *slot = Some(value);
+        }
+
+        if let Some(embedded) = embedded {
+            let invoice = Invoice::from_str(&embedded)
+                .map_err(|_| Bip21Error::InvalidInvoice)?;
+            if !invoice.beneficiaries().any(|beneficiary| {
+                beneficiary == &Beneficiary::from(address.clone())
+            }) {
+                return Err(Bip21Error::InconsistentInvoice);
+            }
+            let inconsistent =
+                |param: &str| Bip21Error::InconsistentParam(param.to_owned());
+            if let Some(amount) = amount {
+                if !invoice.pays_btc(&address)
+                    || *invoice.amount() != AmountExt::Normal(amount)
+                {
+                    return Err(inconsistent("amount"));
+                }
+            }
+            if let Some(label) = label.filter(|label| !label.is_empty()) {
+                if invoice.merchant_name() != Some(&label) {
+                    return Err(inconsistent("label"));
+                }
+            }
+            if let Some(message) = message.filter(|message| !message.is_empty())
+            {
+                if invoice.purpose().as_deref() != Some(&message) {
+                    return Err(inconsistent("message"));
+                }
+            }
+            return Ok(invoice);
+        }
+
+        let mut invoice = Invoice::with_address(address, amount);
+        if let Some(label) = label {
+            invoice.set_merchant(label);
+        }
+        if let Some(message) = message {
+            invoice.set_purpose(message);
+        }
+        Ok(invoice)
+    }
+
+    /// Detects whether the invoice is paid in bitcoins of the address network
+    fn pays_btc(&self, address: &Address) -> bool {
+        let chain = Chain::from(address.network);
+        self.asset()
+            .is_none_or(|asset| is_native_asset_of(&asset, &chain))
+    }
+}
+
+fn encode_value(value: &str) -> String {
+    utf8_percent_encode(value, BIP21_VALUE).to_string()
+}
+
+/// Formats satoshi amount as a decimal BTC value without trailing zeros
+fn format_btc(sats: u64) -> String {
+    let btc = sats / SATS_IN_BTC;
+    let fract = sats % SATS_IN_BTC;
+    if fract == 0 {
+        return btc.to_string();
+    }
+    let fract = format!("{:08}", fract);
+    format!("{}.{}", btc, fract.trim_end_matches('0'))
+}
+
+/// Parses decimal BTC value into satoshis
+fn parse_btc(s: &str) -> Result<u64, Bip21Error> {
+    let invalid = || Bip21Error::InvalidAmount(s.to_owned());
+    let (btc, fract) = s.split_once('.').unwrap_or((s, ""));
+    if btc.is_empty() && fract.is_empty()
+        || !btc.bytes().chain(fract.bytes()).all(|c| c.is_ascii_digit())
+    {
+        return Err(invalid());
+    }
+    if fract.len() > 8 {
+        return Err(Bip21Error::AmountPrecision(s.to_owned()));
+    }
+    let btc = if btc.is_empty() {
+        0
+    } else {
+        btc.parse::<u64>().map_err(|_| invalid())?
+    };
+    let fract = if fract.is_empty() {
+        0
+    } else {
+        format!("{:0<8}", fract)
+            .parse::<u64>()
+            .map_err(|_| invalid())?
+    };
+    btc.checked_mul(SATS_IN_BTC)
+        .and_then(|sats| sats.checked_add(fract))
+        .ok_or_else(invalid)
+}
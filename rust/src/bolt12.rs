@@ -0,0 +1,1065 @@
+// LNP/BP universal invoice library implementing LNPBP-38 standard
+// Written in 2021 by
+//     Dr. Maxim Orlovsky <orlovsky@pandoracore.com>
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the MIT License
+// along with this software.
+// If not, see <https://opensource.org/licenses/MIT>.
+
+//! BOLT-12 offers and their conversion from and into invoices.
+//!
+//! Offers are parsed and serialized by this module itself, since the version
+//! of the `lightning` library used for BOLT-11 conversion keeps its offers
+//! implementation private. The module supports the offer TLV records of
+//! BOLT-12, checked against the BOLT-12 test vectors, together with the
+//! `offer_recurrence*` records of the BOLT-12 recurrence draft; offers are
+//! displayed as bech32 strings with the `lno` prefix and without a checksum.
+//! Offers without a node id, reachable only through blinded paths, are not
+//! supported.
+//!
+//! Invoices and offers share only a part of their data. Conversions in both
+//! directions drop the data which can't be represented in the target format
+//! and list them in [`LossyConversion`], while data changing the payment
+//! semantics, like non-bitcoin assets or fiat-denominated amounts, fail the
+//! conversion.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::convert::TryFrom;
+use std::fmt::{self, Display, Formatter};
+use std::str::FromStr;
+
+use amplify::Slice32;
+use bitcoin::bech32::{self, FromBase32, ToBase32};
+use bitcoin::hashes::Hash;
+use bitcoin::secp256k1::PublicKey;
+use bitcoin::BlockHash;
+use bitcoin_scripts::hlc::HashLock;
+use chrono::{TimeZone, Utc};
+use internet2::addr::NodeId;
+use lnp::p2p::bolt::InitFeatures;
+use lnpbp::chain::Chain;
+
+use crate::{
+    AmountExt, Beneficiary, Invoice, Iso4217, LnAddress, LnFlags, Lock,
+    MerchantInfo, Network, Quantity, Recurrent,
+};
+
+/// Bech32 prefix of BOLT-12 offers
+pub const OFFER_HRP: &str = "lno";
+
+/// Maximal amount in millisatoshis accepted by BOLT-12 offers, matching the
+/// total bitcoin supply
+pub const MAX_OFFER_AMOUNT_MSAT: u64 = 21_000_000 * 100_000_000 * 1000;
+
+const TLV_CHAINS: u64 = 2;
+const TLV_METADATA: u64 = 4;
+const TLV_CURRENCY: u64 = 6;
+const TLV_AMOUNT: u64 = 8;
+const TLV_DESCRIPTION: u64 = 10;
+const TLV_FEATURES: u64 = 12;
+const TLV_ABSOLUTE_EXPIRY: u64 = 14;
+const TLV_PATHS: u64 = 16;
+const TLV_ISSUER: u64 = 18;
+const TLV_QUANTITY_MAX: u64 = 20;
+const TLV_NODE_ID: u64 = 22;
+const TLV_RECURRENCE: u64 = 26;
+const TLV_RECURRENCE_BASE: u64 = 28;
+const TLV_RECURRENCE_PAYWINDOW: u64 = 64;
+const TLV_RECURRENCE_LIMIT: u64 = 66;
+
+/// Errors parsing BOLT-12 offers and converting them from or into invoices.
+#[derive(
+    Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Hash, Debug, Display, Error,
+)]
+#[display(doc_comments)]
+pub enum OfferError {
+    /// string is not a BOLT-12 offer, which must start with `lno1`
+    InvalidPrefix,
+
+    /// offer string has invalid bech32 data or invalid `+` continuations
+    InvalidEncoding,
+
+    /// offer TLV stream is truncated, has non-minimal encoding or unordered
+    /// records
+    InvalidTlvStream,
+
+    /// offer TLV record {0} has invalid value
+    InvalidRecord(u64),
+
+    /// offer TLV record {0} is required but unknown to this software version
+    UnknownRequiredRecord(u64),
+
+    /// TLV record type {0} is outside of the range allowed for offers
+    OutOfRangeRecord(u64),
+
+    /// offer has an amount but no description
+    MissingDescription,
+
+    /// offer has no node id
+    MissingNodeId,
+
+    /// offer specifies currency without an amount
+    MissingAmount,
+
+    /// offer amount exceeds the total bitcoin supply
+    InvalidAmount,
+
+    /// offer quantity can't be represented by an invoice
+    InvalidQuantity,
+
+    /// invoice has no lightning beneficiary
+    NoLightningBeneficiary,
+
+    /// invoice requests split payment, which can't be represented by an
+    /// offer
+    SplitPayment,
+
+    /// invoice chain is not supported by BOLT-12 offers
+    UnknownChain,
+
+    /// invoice requests payment in an asset other than the chain native one
+    NonNativeAsset,
+
+    /// invoice amount can't be represented in millisatoshis
+    AmountOverflow,
+
+    /// offer amount is denominated in {0} and not in bitcoin
+    FiatAmount(Iso4217),
+
+    /// expiry time is before the UNIX epoch or is inconsistent
+    InvalidExpiry,
+
+    /// recurrence period can't be represented in the target format
+    UnsupportedRecurrence,
+
+    /// offer quantity exceeds the maximal invoice quantity
+    QuantityOverflow,
+}
+
+/// Data of an invoice or an offer which have no counterpart in the other
+/// format and are dropped by the conversion.
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Hash, Debug, Display)]
+#[display(doc_comments)]
+pub enum LostField {
+    /// beneficiaries other than the lightning one
+    AltBeneficiaries,
+
+    /// payment hash or point of the lightning beneficiary
+    PaymentLock,
+
+    /// payment secret of the lightning beneficiary
+    PaymentSecret,
+
+    /// lightning route hints
+    RouteHints,
+
+    /// final CLTV expiry delta
+    MinFinalCltvExpiry,
+
+    /// lightning node features
+    NodeFeatures,
+
+    /// lightning beneficiary flags
+    LnFlags,
+
+    /// minimal and default quantity of items
+    QuantityBounds,
+
+    /// currency requirements
+    CurrencyRequirements,
+
+    /// structured merchant information
+    MerchantInfo,
+
+    /// payment details
+    Details,
+
+    /// merchant reference
+    Reference,
+
+    /// invoice issue time
+    IssuedAt,
+
+    /// asset metadata
+    AssetMetadata,
+
+    /// consignment endpoints
+    ConsignmentEndpoints,
+
+    /// refund beneficiary
+    Refund,
+
+    /// on-chain payment policy
+    OnchainPolicy,
+
+    /// signatures and beneficiary proof
+    Signatures,
+
+    /// unknown records and extensions
+    Extensions,
+
+    /// offer chains other than the first one
+    Chains,
+
+    /// offer metadata
+    Metadata,
+
+    /// offer features
+    OfferFeatures,
+
+    /// blinded paths to the offer node
+    BlindedPaths,
+
+    /// recurrence base time
+    RecurrenceBase,
+
+    /// recurrence payment window
+    RecurrencePaywindow,
+}
+
+/// Summary of the data dropped by a conversion between an invoice and an
+/// offer.
+#[derive(Clone, Eq, PartialEq, Hash, Debug, Default)]
+pub struct LossyConversion {
+    lost: BTreeSet<LostField>,
+}
+
+impl LossyConversion {
+    /// Detects whether the conversion has kept all the data.
+    pub fn is_lossless(&self) -> bool {
+        self.lost.is_empty()
+    }
+
+    /// Checks whether the given data were dropped by the conversion.
+    pub fn is_lost(&self, field: LostField) -> bool {
+        self.lost.contains(&field)
+    }
+
+    /// Iterates over the dropped data.
+    pub fn lost_fields(&self) -> impl Iterator<Item = LostField> + '_ {
+        self.lost.iter().copied()
+    }
+
+    fn lose_if(&mut self, field: LostField, lost: bool) {
+        if lost {
+            self.lost.insert(field);
+        }
+    }
+}
+
+impl Display for LossyConversion {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        if self.is_lossless() {
+            return f.write_str("no data lost");
+        }
+        f.write_str("lost ")?;
+        for (no, field) in self.lost.iter().enumerate() {
+            if no > 0 {
+                f.write_str(", ")?;
+            }
+            Display::fmt(field, f)?;
+        }
+        Ok(())
+    }
+}
+
+/// Period of recurrent payments requested by an offer.
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Hash, Debug, Display)]
+pub enum OfferRecurrence {
+    #[display("each {0} seconds")]
+    Seconds(u32),
+
+    #[display("each {0} days")]
+    Days(u32),
+
+    #[display("each {0} months")]
+    Months(u32),
+
+    #[display("each {0} years")]
+    Years(u32),
+}
+
+/// Time from which the offer recurrence periods are counted.
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Hash, Debug)]
+pub struct RecurrenceBase {
+    /// Whether the payer may start paying from any period and not only from
+    /// the current one
+    pub start_any_period: bool,
+
+    /// UNIX timestamp of the first period start
+    pub basetime: u64,
+}
+
+/// Time window around the start of each recurrence period in which the
+/// payment is accepted.
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Hash, Debug)]
+pub struct RecurrencePaywindow {
+    pub seconds_before: u32,
+
+    /// Whether the amount of a payment made after the period start is
+    /// reduced proportionally to the time passed
+    pub proportional_amount: bool,
+
+    pub seconds_after: u32,
+}
+
+/// BOLT-12 offer.
+///
+/// Offers are displayed and parsed as bech32 strings with `lno` prefix;
+/// parsing accepts strings split with `+` followed by optional whitespace
+/// and uppercase strings. Unknown odd TLV records are kept, while offers with
+/// unknown even records fail to parse.
+#[derive(Clone, Eq, PartialEq, Hash, Debug)]
+pub struct Offer {
+    /// Genesis block hashes of the chains the offer is valid for; empty list
+    /// implies bitcoin mainnet
+    pub chains: Vec<BlockHash>,
+
+    /// Opaque data used by the issuer to identify the offer
+    pub metadata: Option<Vec<u8>>,
+
+    /// Currency of the amount; if absent, the amount is in millisatoshis
+    pub currency: Option<Iso4217>,
+
+    /// Price of a single item in millisatoshis or in the currency minor
+    /// units; if absent, any amount is accepted
+    pub amount: Option<u64>,
+
+    /// Description of the offer purpose, which must be present if the offer
+    /// has an amount
+    pub description: Option<String>,
+
+    /// Feature bits, serialized as in BOLT-9
+    pub features: Vec<u8>,
+
+    /// Expiry time as UNIX timestamp
+    pub absolute_expiry: Option<u64>,
+
+    /// Serialized blinded paths to the offer node, kept undecoded
+    pub paths: Option<Vec<u8>>,
+
+    pub issuer: Option<String>,
+
+    /// Maximal number of items in a single payment, with `0` meaning any
+    /// number of items; if absent, a single item is paid
+    pub quantity_max: Option<u64>,
+
+    /// Key of the node signing invoices for the offer
+    pub node_id: PublicKey,
+
+    pub recurrence: Option<OfferRecurrence>,
+
+    pub recurrence_base: Option<RecurrenceBase>,
+
+    pub recurrence_paywindow: Option<RecurrencePaywindow>,
+
+    /// Maximal number of the recurrence period
+    pub recurrence_limit: Option<u32>,
+
+    /// Unknown odd TLV records
+    unknown: BTreeMap<u64, Vec<u8>>,
+}
+
+impl Offer {
+    /// Constructs offer accepting any amount for a single item on bitcoin
+    /// mainnet.
+    pub fn new(node_id: PublicKey, description: impl ToString) -> Offer {
+        Offer {
+            chains: vec![],
+            metadata: None,
+            currency: None,
+            amount: None,
+            description: Some(description.to_string()),
+            features: vec![],
+            absolute_expiry: None,
+            paths: None,
+            issuer: None,
+            quantity_max: None,
+            node_id,
+            recurrence: None,
+            recurrence_base: None,
+            recurrence_paywindow: None,
+            recurrence_limit: None,
+            unknown: empty!(),
+        }
+    }
+
+    /// Iterates over unknown TLV records, ordered by their type.
+    pub fn unknown_records(&self) -> impl Iterator<Item = (u64, &[u8])> {
+        self.unknown
+            .iter()
+            .map(|(type_no, value)| (*type_no, value.as_slice()))
+    }
+
+    /// Sets value of an unknown TLV record, which must have an odd type from
+    /// the ranges allowed for offers.
+    pub fn set_unknown_record(
+        &mut self,
+        type_no: u64,
+        value: Vec<u8>,
+    ) -> Result<(), OfferError> {
+        if !is_offer_type(type_no) {
+            return Err(OfferError::OutOfRangeRecord(type_no));
+        }
+        if type_no.is_multiple_of(2) {
+            return Err(OfferError::UnknownRequiredRecord(type_no));
+        }
+        self.unknown.insert(type_no, value);
+        Ok(())
+    }
+
+    /// Serializes offer into TLV stream.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut records = self.unknown.clone();
+        let mut insert = |type_no, value: Option<Vec<u8>>| {
+            if let Some(value) = value {
+                records.insert(type_no, value);
+            }
+        };
+        insert(
+            TLV_CHAINS,
+            Some(self.chains.iter().flat_map(|hash| hash.into_inner()))
+                .filter(|_| !self.chains.is_empty())
+                .map(Iterator::collect),
+        );
+        insert(TLV_METADATA, self.metadata.clone());
+        insert(
+            TLV_CURRENCY,
+            self.currency.map(|code| code.as_str().as_bytes().to_vec()),
+        );
+        insert(TLV_AMOUNT, self.amount.map(tu64));
+        insert(
+            TLV_DESCRIPTION,
+            self.description.as_ref().map(|d| d.as_bytes().to_vec()),
+        );
+        insert(
+            TLV_FEATURES,
+            Some(self.features.clone()).filter(|f| !f.is_empty()),
+        );
+        insert(TLV_ABSOLUTE_EXPIRY, self.absolute_expiry.map(tu64));
+        insert(TLV_PATHS, self.paths.clone());
+        insert(
+            TLV_ISSUER,
+            self.issuer
+                .as_ref()
+                .map(|issuer| issuer.as_bytes().to_vec()),
+        );
+        insert(TLV_QUANTITY_MAX, self.quantity_max.map(tu64));
+        insert(TLV_NODE_ID, Some(self.node_id.serialize().to_vec()));
+        insert(
+            TLV_RECURRENCE,
+            self.recurrence.map(|recurrence| {
+                let (unit, period) = match recurrence {
+                    OfferRecurrence::Seconds(period) => (0u8, period),
+                    OfferRecurrence::Days(period) => (1, period),
+                    OfferRecurrence::Months(period) => (2, period),
+                    OfferRecurrence::Years(period) => (3, period),
+                };
+                let mut value = vec![unit];
+                value.extend(tu64(period.into()));
+                value
+            }),
+        );
+        insert(
+            TLV_RECURRENCE_BASE,
+            self.recurrence_base.map(|base| {
+                let mut value = vec![base.start_any_period as u8];
+                value.extend(tu64(base.basetime));
+                value
+            }),
+        );
+        insert(
+            TLV_RECURRENCE_PAYWINDOW,
+            self.recurrence_paywindow.map(|window| {
+                let mut value = window.seconds_before.to_be_bytes().to_vec();
+                value.push(window.proportional_amount as u8);
+                value.extend(tu64(window.seconds_after.into()));
+                value
+            }),
+        );
+        insert(
+            TLV_RECURRENCE_LIMIT,
+            self.recurrence_limit.map(|limit| tu64(limit.into())),
+        );
+
+        let mut data = vec![];
+        for (type_no, value) in records {
+            write_bigsize(&mut data, type_no);
+            write_bigsize(&mut data, value.len() as u64);
+            data.extend(value);
+        }
+        data
+    }
+
+    /// Parses offer from TLV stream, checking the offer semantics required
+    /// by BOLT-12.
+    pub fn from_bytes(mut data: &[u8]) -> Result<Offer, OfferError> {
+        let mut records = BTreeMap::<u64, &[u8]>::new();
+        while !data.is_empty() {
+            let type_no = read_bigsize(&mut data)?;
+            let len = read_bigsize(&mut data)?;
+            if records.keys().next_back() >= Some(&type_no) {
+                return Err(OfferError::InvalidTlvStream);
+            }
+            if !is_offer_type(type_no) {
+                return Err(OfferError::OutOfRangeRecord(type_no));
+            }
+            let len = usize::try_from(len)
+                .ok()
+                .filter(|len| *len <= data.len())
+                .ok_or(OfferError::InvalidTlvStream)?;
+            let (value, rest) = data.split_at(len);
+            records.insert(type_no, value);
+            data = rest;
+        }
+
+        let mut take = |type_no| records.remove(&type_no);
+        let invalid = OfferError::InvalidRecord;
+        let string = |type_no, value: &[u8]| {
+            String::from_utf8(value.to_vec()).map_err(|_| invalid(type_no))
+        };
+
+        let node_id = take(TLV_NODE_ID).ok_or(OfferError::MissingNodeId)?;
+        let node_id =
+            PublicKey::from_slice(node_id).map_err(|_| invalid(TLV_NODE_ID))?;
+        let mut offer = Offer::new(node_id, "");
+        offer.description = take(TLV_DESCRIPTION)
+            .map(|value| string(TLV_DESCRIPTION, value))
+            .transpose()?;
+
+        if let Some(value) = take(TLV_CHAINS) {
+            if value.is_empty() || !value.len().is_multiple_of(32) {
+                return Err(invalid(TLV_CHAINS));
+            }
+            offer.chains = value
+                .chunks(32)
+                .map(|hash| {
+                    BlockHash::from_slice(hash).expect("chunk of 32 bytes")
+                })
+                .collect();
+        }
+        offer.metadata = take(TLV_METADATA).map(<[u8]>::to_vec);
+        offer.currency = take(TLV_CURRENCY)
+            .map(|value| {
+                <[u8; 3]>::try_from(value)
+                    .ok()
+                    .and_then(|code| Iso4217::new(code).ok())
+                    .ok_or(invalid(TLV_CURRENCY))
+            })
+            .transpose()?;
+        offer.amount = take(TLV_AMOUNT)
+            .map(|value| read_tu64(value, invalid(TLV_AMOUNT)))
+            .transpose()?;
+        offer.features = take(TLV_FEATURES).unwrap_or_default().to_vec();
+        offer.absolute_expiry = take(TLV_ABSOLUTE_EXPIRY)
+            .map(|value| read_tu64(value, invalid(TLV_ABSOLUTE_EXPIRY)))
+            .transpose()?;
+        offer.paths = take(TLV_PATHS).map(<[u8]>::to_vec);
+        offer.issuer = take(TLV_ISSUER)
+            .map(|value| string(TLV_ISSUER, value))
+            .transpose()?;
+        offer.quantity_max = take(TLV_QUANTITY_MAX)
+            .map(|value| read_tu64(value, invalid(TLV_QUANTITY_MAX)))
+            .transpose()?;
+        offer.recurrence = take(TLV_RECURRENCE)
+            .map(|value| {
+                let err = invalid(TLV_RECURRENCE);
+                let (unit, period) = value.split_first().ok_or(err)?;
+                let period = read_tu32(period, err)?;
+                Ok(match unit {
+                    0 => OfferRecurrence::Seconds(period),
+                    1 => OfferRecurrence::Days(period),
+                    2 => OfferRecurrence::Months(period),
+                    3 => OfferRecurrence::Years(period),
+                    _ => return Err(err),
+                })
+            })
+            .transpose()?;
+        offer.recurrence_base = take(TLV_RECURRENCE_BASE)
+            .map(|value| {
+                let err = invalid(TLV_RECURRENCE_BASE);
+                let (start_any_period, basetime) =
+                    value.split_first().ok_or(err)?;
+                Ok(RecurrenceBase {
+                    start_any_period: read_bool(*start_any_period, err)?,
+                    basetime: read_tu64(basetime, err)?,
+                })
+            })
+            .transpose()?;
+        offer.recurrence_paywindow = take(TLV_RECURRENCE_PAYWINDOW)
+            .map(|value| {
+                let err = invalid(TLV_RECURRENCE_PAYWINDOW);
+                if value.len() < 5 {
+                    return Err(err);
+                }
+                let (before, rest) = value.split_at(4);
+                Ok(RecurrencePaywindow {
+                    seconds_before: u32::from_be_bytes(
+                        <[u8; 4]>::try_from(before).expect("slice of 4 bytes"),
+                    ),
+                    proportional_amount: read_bool(rest[0], err)?,
+                    seconds_after: read_tu32(&rest[1..], err)?,
+                })
+            })
+            .transpose()?;
+        offer.recurrence_limit = take(TLV_RECURRENCE_LIMIT)
+            .map(|value| read_tu32(value, invalid(TLV_RECURRENCE_LIMIT)))
+            .transpose()?;
+
+        for (type_no, value) in records {
+            if type_no.is_multiple_of(2) {
+                return Err(OfferError::UnknownRequiredRecord(type_no));
+            }
+            offer.unknown.insert(type_no, value.to_vec());
+        }
+
+        match (offer.currency, offer.amount) {
+            (Some(_), None) => return Err(OfferError::MissingAmount),
+            (None, Some(amount)) if amount > MAX_OFFER_AMOUNT_MSAT => {
+                return Err(OfferError::InvalidAmount)
+            }
+            _ => {}
+        }
+        if offer.amount.is_some() && offer.description.is_none() {
+            return Err(OfferError::MissingDescription);
+        }
+        Ok(offer)
+    }
+
+    /// Converts the offer into an invoice paid to the offer node, reporting
+    /// offer data which invoices can't represent.
+    ///
+    /// The invoice beneficiary is a lightning node with
+    /// [`LnFlags::OFFER`] flag and an all-zero payment hash: the payer
+    /// obtains the payment hash in the BOLT-12 invoice requested from the
+    /// node. Offers with fiat-denominated amounts fail the conversion.
+    pub fn to_invoice(&self) -> Result<(Invoice, LossyConversion), OfferError> {
+        let mut lost = LossyConversion::default();
+
+        let chain = match self.chains.first() {
+            None => Chain::Mainnet,
+            Some(hash) => Chain::from_genesis_hash(hash)
+                .ok_or(OfferError::UnknownChain)?,
+        };
+        lost.lose_if(LostField::Chains, self.chains.len() > 1);
+        if let Some(currency) = self.currency {
+            return Err(OfferError::FiatAmount(currency));
+        }
+
+        let ln_address = LnAddress {
+            node_id: NodeId::from(self.node_id),
+            features: InitFeatures::default(),
+            lock: Lock::Hash(HashLock::from(Slice32::default())),
+            secret: None,
+            network: chain.clone(),
+            min_final_cltv_expiry: None,
+            path_hints: vec![],
+            ln_flags: LnFlags::OFFER,
+        };
+        let asset = if chain == Chain::Mainnet {
+            None
+        } else {
+            Some(chain.native_asset())
+        };
+        let mut invoice =
+            Invoice::new(Beneficiary::Bolt(ln_address), None, asset);
+
+        if let Some(msat) = self.amount {
+            invoice.set_amount_unchecked(match msat % 1000 {
+                0 => AmountExt::Normal(msat / 1000),
+                frac => AmountExt::Milli(msat / 1000, frac as u16),
+            });
+        }
+        if let Some(description) =
+            self.description.as_ref().filter(|d| !d.is_empty())
+        {
+            invoice.set_purpose(description.clone());
+        }
+        if let Some(issuer) = &self.issuer {
+            invoice.set_merchant(issuer.clone());
+        }
+        if let Some(expiry) = self.absolute_expiry {
+            let expiry = i64::try_from(expiry)
+                .ok()
+                .and_then(|secs| Utc.timestamp_opt(secs, 0).single())
+                .ok_or(OfferError::InvalidExpiry)?;
+            invoice.set_expiry(expiry);
+        }
+        if let Some(recurrence) = self.recurrence {
+            invoice.set_recurrent(invoice_recurrence(recurrence)?);
+        }
+        if let Some(limit) = self.recurrence_limit {
+            invoice.set_recurrence_limit(limit);
+        }
+        if let Some(max) = self.quantity_max {
+            let max = match max {
+                0 => None,
+                max => Some(
+                    u32::try_from(max)
+                        .map_err(|_| OfferError::QuantityOverflow)?,
+                ),
+            };
+            invoice
+                .set_quantity(Quantity {
+                    min: 1,
+                    max,
+                    default: 1,
+                })
+                .map_err(|_| OfferError::InvalidQuantity)?;
+        }
+        if let Ok(network) = Network::try_from(chain) {
+            invoice.set_network(network);
+        }
+
+        lost.lose_if(LostField::Metadata, self.metadata.is_some());
+        lost.lose_if(LostField::OfferFeatures, !self.features.is_empty());
+        lost.lose_if(LostField::BlindedPaths, self.paths.is_some());
+        lost.lose_if(LostField::RecurrenceBase, self.recurrence_base.is_some());
+        lost.lose_if(
+            LostField::RecurrencePaywindow,
+            self.recurrence_paywindow.is_some(),
+        );
+        lost.lose_if(LostField::Extensions, !self.unknown.is_empty());
+
+        Ok((invoice, lost))
+    }
+}
+
+impl Display for Offer {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        bech32::encode_without_checksum_to_fmt(
+            f,
+            OFFER_HRP,
+            self.to_bytes().to_base32(),
+        )
+        .expect("offer HRP is valid")
+    }
+}
+
+impl FromStr for Offer {
+    type Err = OfferError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        // Offer string may be split by `+` followed by optional whitespace
+        let joined;
+        let s = if s.contains('+') {
+            for chunk in s.split('+') {
+                let chunk = chunk.trim_start();
+                if chunk.is_empty() || chunk.contains(char::is_whitespace) {
+                    return Err(OfferError::InvalidEncoding);
+                }
+            }
+            joined = s
+                .chars()
+                .filter(|c| *c != '+' && !c.is_whitespace())
+                .collect::<String>();
+            joined.as_str()
+        } else {
+            s
+        };
+
+        let (hrp, data) = bech32::decode_without_checksum(s)
+            .map_err(|_| OfferError::InvalidEncoding)?;
+        if hrp != OFFER_HRP {
+            return Err(OfferError::InvalidPrefix);
+        }
+        let data = Vec::<u8>::from_base32(&data)
+            .map_err(|_| OfferError::InvalidEncoding)?;
+        Offer::from_bytes(&data)
+    }
+}
+
+impl Invoice {
+    /// Converts the invoice into an offer paid to its first lightning
+    /// beneficiary, reporting invoice data which offers can't represent.
+    ///
+    /// Invoices requesting payment in non-native assets or split payments
+    /// fail the conversion.
+    pub fn to_bolt12_offer(
+        &self,
+    ) -> Result<(Offer, LossyConversion), OfferError> {
+        let mut lost = LossyConversion::default();
+
+        let ln_address = self
+            .beneficiaries()
+            .find_map(|beneficiary| match beneficiary {
+                Beneficiary::Bolt(params) => Some(params),
+                _ => None,
+            })
+            .ok_or(OfferError::NoLightningBeneficiary)?;
+        if !self.split_shares().is_empty() {
+            return Err(OfferError::SplitPayment);
+        }
+        lost.lose_if(
+            LostField::AltBeneficiaries,
+            self.beneficiaries().count() > 1,
+        );
+
+        let chain = invoice_chain(self, ln_address)?;
+        if let Some(asset) = self.asset() {
+            if *asset != chain.native_asset() {
+                return Err(OfferError::NonNativeAsset);
+            }
+        }
+
+        let mut offer = Offer::new(
+            ln_address.node_id.public_key(),
+            self.purpose().clone().unwrap_or_default(),
+        );
+        if chain != Chain::Mainnet {
+            offer.chains.push(*chain.as_genesis_hash());
+        }
+        offer.amount = offer_amount(self.amount())?;
+        offer.issuer = self.merchant().clone().or_else(|| {
+            self.merchant_info().as_ref().map(|info| info.name.clone())
+        });
+        offer.absolute_expiry = self
+            .effective_expiry()
+            .map_err(|_| OfferError::InvalidExpiry)?
+            .map(|expiry| u64::try_from(expiry.timestamp()))
+            .transpose()
+            .map_err(|_| OfferError::InvalidExpiry)?;
+        offer.recurrence = offer_recurrence(self.recurrent())?;
+        offer.recurrence_limit = *self.recurrence_limit();
+        if let Some(quantity) = self.quantity() {
+            lost.lose_if(
+                LostField::QuantityBounds,
+                quantity.min > 1 || quantity.default != 1,
+            );
+            offer.quantity_max = match quantity.max {
+                None => Some(0),
+                Some(max) if max > 1 => Some(max.into()),
+                Some(_) => None,
+            };
+        }
+
+        lost.lose_if(
+            LostField::PaymentLock,
+            !ln_address.ln_flags.contains(LnFlags::OFFER),
+        );
+        lost.lose_if(LostField::PaymentSecret, ln_address.secret.is_some());
+        lost.lose_if(LostField::RouteHints, !ln_address.path_hints.is_empty());
+        lost.lose_if(
+            LostField::MinFinalCltvExpiry,
+            ln_address.min_final_cltv_expiry.is_some(),
+        );
+        lost.lose_if(
+            LostField::NodeFeatures,
+            ln_address.features != InitFeatures::default(),
+        );
+        lost.lose_if(
+            LostField::LnFlags,
+            (ln_address.ln_flags | LnFlags::OFFER) != LnFlags::OFFER,
+        );
+        lost.lose_if(
+            LostField::CurrencyRequirements,
+            !self.currency_requirements().is_empty(),
+        );
+        lost.lose_if(
+            LostField::MerchantInfo,
+            match (self.merchant(), self.merchant_info()) {
+                (_, None) => false,
+                (None, Some(info)) => *info != MerchantInfo::new(&info.name),
+                (Some(_), Some(_)) => true,
+            },
+        );
+        lost.lose_if(
+            LostField::Details,
+            self.details().is_some() || self.inline_details().is_some(),
+        );
+        lost.lose_if(LostField::Reference, self.reference().is_some());
+        lost.lose_if(LostField::IssuedAt, self.issued_at().is_some());
+        lost.lose_if(LostField::AssetMetadata, self.asset_metadata().is_some());
+        lost.lose_if(
+            LostField::ConsignmentEndpoints,
+            !self.consignment_endpoints().is_empty(),
+        );
+        lost.lose_if(LostField::Refund, self.refund().is_some());
+        lost.lose_if(LostField::OnchainPolicy, self.onchain_policy().is_some());
+        lost.lose_if(
+            LostField::Signatures,
+            self.signature().is_some()
+                || !self.cosignatures().is_empty()
+                || self.beneficiary_proof().is_some(),
+        );
+        lost.lose_if(LostField::Extensions, self.extensions().next().is_some());
+
+        Ok((offer, lost))
+    }
+}
+
+impl TryFrom<Invoice> for Offer {
+    type Error = OfferError;
+
+    fn try_from(invoice: Invoice) -> Result<Self, Self::Error> {
+        invoice.to_bolt12_offer().map(|(offer, _)| offer)
+    }
+}
+
+impl TryFrom<Offer> for Invoice {
+    type Error = OfferError;
+
+    fn try_from(offer: Offer) -> Result<Self, Self::Error> {
+        offer.to_invoice().map(|(invoice, _)| invoice)
+    }
+}
+
+/// Detects the invoice chain from the invoice-level `network` field, then
+/// from the invoice native asset, falling back to the legacy lightning
+/// beneficiary chain information.
+fn invoice_chain(
+    invoice: &Invoice,
+    ln_address: &LnAddress,
+) -> Result<Chain, OfferError> {
+    if let Some(network) = invoice.network() {
+        return Chain::try_from(*network).map_err(|_| OfferError::UnknownChain);
+    }
+    match invoice.asset() {
+        Some(asset) => Chain::all_standard()
+            .iter()
+            .find(|chain| chain.native_asset() == *asset)
+            .cloned()
+            .ok_or(OfferError::NonNativeAsset),
+        None => Ok(ln_address.network.clone()),
+    }
+}
+
+/// Converts invoice amount, measured in satoshis, into the offer amount in
+/// millisatoshis. Returns `None` for invoices accepting any amount.
+fn offer_amount(amount: &AmountExt) -> Result<Option<u64>, OfferError> {
+    let (sat, msat) = match *amount {
+        AmountExt::Any => return Ok(None),
+        AmountExt::Normal(sat) => (sat, 0),
+        AmountExt::Milli(sat, msat) => (sat, msat as u64),
+    };
+    sat.checked_mul(1000)
+        .and_then(|value| value.checked_add(msat))
+        .filter(|value| *value <= MAX_OFFER_AMOUNT_MSAT)
+        .map(Some)
+        .ok_or(OfferError::AmountOverflow)
+}
+
+fn offer_recurrence(
+    recurrent: &Recurrent,
+) -> Result<Option<OfferRecurrence>, OfferError> {
+    Ok(Some(match *recurrent {
+        Recurrent::NonRecurrent => return Ok(None),
+        Recurrent::Seconds(secs) => match u32::try_from(secs) {
+            Ok(secs) => OfferRecurrence::Seconds(secs),
+            Err(_) if secs.is_multiple_of(86400) => OfferRecurrence::Days(
+                u32::try_from(secs / 86400)
+                    .map_err(|_| OfferError::UnsupportedRecurrence)?,
+            ),
+            Err(_) => return Err(OfferError::UnsupportedRecurrence),
+        },
+        Recurrent::Days(days) => OfferRecurrence::Days(days.into()),
+        Recurrent::Weeks(weeks) => OfferRecurrence::Days(u32::from(weeks) * 7),
+        Recurrent::Months(months) => OfferRecurrence::Months(months.into()),
+        Recurrent::Years(years) => OfferRecurrence::Years(years.into()),
+        _ => return Err(OfferError::UnsupportedRecurrence),
+    }))
+}
+
+fn invoice_recurrence(
+    recurrence: OfferRecurrence,
+) -> Result<Recurrent, OfferError> {
+    Ok(match recurrence {
+        OfferRecurrence::Seconds(secs) => Recurrent::Seconds(secs.into()),
+        OfferRecurrence::Days(days) => match u16::try_from(days) {
+            Ok(days) => Recurrent::Days(days),
+            Err(_) => Recurrent::Seconds(u64::from(days) * 86400),
+        },
+        OfferRecurrence::Months(months) => match u8::try_from(months) {
+            Ok(months) => Recurrent::Months(months),
+            Err(_) if months.is_multiple_of(12) => Recurrent::Years(
+                u8::try_from(months / 12)
+                    .map_err(|_| OfferError::UnsupportedRecurrence)?,
+            ),
+            Err(_) => return Err(OfferError::UnsupportedRecurrence),
+        },
+        OfferRecurrence::Years(years) => Recurrent::Years(
+            u8::try_from(years)
+                .map_err(|_| OfferError::UnsupportedRecurrence)?,
+        ),
+    })
+}
+
+/// Detects TLV types allowed in offers: the offer range and the experimental
+/// offer range of BOLT-12.
+fn is_offer_type(type_no: u64) -> bool {
+    (1..80).contains(&type_no)
+        || (1_000_000_000..2_000_000_000).contains(&type_no)
+}
+
+fn write_bigsize(data: &mut Vec<u8>, value: u64) {
+    match value {
+        0..=0xfc => data.push(value as u8),
+        0xfd..=0xffff => {
+            data.push(0xfd);
+            data.extend((value as u16).to_be_bytes());
+        }
+        0x10000..=0xffff_ffff => {
+            data.push(0xfe);
+            data.extend((value as u32).to_be_bytes());
+        }
+        _ => {
+            data.push(0xff);
+            data.extend(value.to_be_bytes());
+        }
+    }
+}
+
+fn read_bigsize(data: &mut &[u8]) -> Result<u64, OfferError> {
+    let (prefix, rest) =
+        data.split_first().ok_or(OfferError::InvalidTlvStream)?;
+    let (len, min) = match prefix {
+        0xfd => (2, 0xfd),
+        0xfe => (4, 0x10000),
+        0xff => (8, 0x1_0000_0000),
+        value => {
+            *data = rest;
+            return Ok(*value as u64);
+        }
+    };
+    if rest.len() < len {
+        return Err(OfferError::InvalidTlvStream);
+    }
+    let (bytes, rest) = rest.split_at(len);
+    let value = bytes.iter().fold(0u64, |acc, b| acc << 8 | *b as u64);
+    if value < min {
+        return Err(OfferError::InvalidTlvStream);
+    }
+    *data = rest;
+    Ok(value)
+}
+
+/// Serializes integer with the leading zero bytes removed.
+fn tu64(value: u64) -> Vec<u8> {
+    let skip = value.leading_zeros() as usize / 8;
+    value.to_be_bytes()[skip..].to_vec()
+}
+
+fn read_tu64(value: &[u8], err: OfferError) -> Result<u64, OfferError> {
+    if value.len() > 8 || value.first() == Some(&0) {
+        return Err(err);
+    }
+    Ok(value.iter().fold(0u64, |acc, b| acc << 8 | *b as u64))
+}
+
+fn read_tu32(value: &[u8], err: OfferError) -> Result<u32, OfferError> {
+    read_tu64(value, err)
+        .and_then(|value| u32::try_from(value).map_err(|_| err))
+}
+
+fn read_bool(value: u8, err: OfferError) -> Result<bool, OfferError> {
+    match value {
+        0 => Ok(false),
+        1 => Ok(true),
+        _ => Err(err),
+    }
+}
@@ -0,0 +1,287 @@
+// LNP/BP universal invoice library implementing LNPBP-38 standard
+// Written in 2021 by
+//     Dr. Maxim Orlovsky <orlovsky@pandoracore.com>
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the MIT License
+// along with this software.
+// If not, see <https://opensource.org/licenses/MIT>.
+
+use chrono::{DateTime, Utc};
+use lnpbp::bech32::Blob;
+use lnpbp::chain::AssetId;
+
+#[cfg(feature = "rgb")]
+use crate::Iface;
+use crate::{
+    AmountExt, AssetMetadata, Beneficiary, ConsignmentEndpoint, CurrencyData,
+    CurrencyError, Details, ExpiryError, ExpiryTerm, InlineDetails, Invoice,
+    InvoiceInconsistency, MerchantInfo, Network, OnchainPolicy, Quantity,
+    QuantityError, Recurrent, SplitError, SplitShare,
+    MAX_CURRENCY_REQUIREMENTS,
+};
+
+/// Errors detected by [`InvoiceBuilder::finish`] during cross-field
+/// validation of the invoice data.
+#[derive(Clone, Eq, PartialEq, Debug, Display, Error, From)]
+#[display(doc_comments)]
+pub enum BuilderError {
+    /// invoice expiry time {0} is in the past
+    ExpiryInPast(DateTime<Utc>),
+
+    /// {0}
+    #[from]
+    Expiry(ExpiryError),
+
+    /// {0}
+    #[from]
+    Quantity(QuantityError),
+
+    /// {0}
+    #[from]
+    Currency(CurrencyError),
+
+    /// {0}
+    #[from]
+    Split(SplitError),
+
+    /// beneficiary address {0} does not belong to the invoice network
+    NetworkMismatch(bitcoin::Address),
+
+    /// quantity can't be used with invoices accepting any amount
+    QuantityWithoutAmount,
+
+    /// amount fraction {0} exceeds 999 milli-units
+    AmountFraction(u16),
+
+    /// {0}
+    #[from]
+    Inconsistency(InvoiceInconsistency),
+}
+
+/// Issues detected by [`InvoiceBuilder::finish_with_warnings`] which don't
+/// prevent the invoice from being constructed.
+#[derive(Clone, Eq, PartialEq, Hash, Debug, Display)]
+#[display(doc_comments)]
+pub enum BuilderWarning {
+    /// invoice string has {len} characters, exceeding the limit of {limit}
+    /// characters
+    SizeLimit { len: usize, limit: usize },
+}
+
+/// Builder constructing [`Invoice`] with all of its optional fields. This is
+/// the recommended way of creating new invoices, since it validates
+/// consistency of the invoice data before producing the invoice.
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct InvoiceBuilder {
+    invoice: Invoice,
+    size_limit: Option<usize>,
+}
+
+impl InvoiceBuilder {
+    /// Starts constructing invoice paying to a given main beneficiary.
+    pub fn new(beneficiary: Beneficiary) -> Self {
+        InvoiceBuilder {
+            invoice: Invoice::new(beneficiary, None, None),
+            size_limit: None,
+        }
+    }
+
+    pub fn amount(mut self, amount: AmountExt) -> Self {
+        self.invoice.set_amount_unchecked(amount);
+        self
+    }
+
+    pub fn asset(mut self, asset: AssetId) -> Self {
+        self.invoice.set_asset(asset);
+        self
+    }
+
+    pub fn asset_metadata(mut self, metadata: AssetMetadata) -> Self {
+        self.invoice.set_asset_metadata(metadata);
+        self
+    }
+
+    #[cfg(feature = "rgb")]
+    pub fn iface(mut self, iface: Iface) -> Self {
+        self.invoice.set_iface(iface);
+        self
+    }
+
+    pub fn alt_beneficiary(mut self, beneficiary: Beneficiary) -> Self {
+        self.invoice.add_alt_beneficiary(beneficiary);
+        self
+    }
+
+    pub fn expiry(mut self, expiry: DateTime<Utc>) -> Self {
+        self.invoice.set_expiry(expiry);
+        self
+    }
+
+    pub fn expiry_term(mut self, term: ExpiryTerm) -> Self {
+        self.invoice.set_expiry_term(term);
+        self
+    }
+
+    pub fn issued_at(mut self, issued_at: DateTime<Utc>) -> Self {
+        self.invoice.set_issued_at(issued_at);
+        self
+    }
+
+    pub fn recurrent(mut self, recurrent: Recurrent) -> Self {
+        self.invoice.set_recurrent(recurrent);
+        self
+    }
+
+    pub fn recurrence_limit(mut self, limit: u32) -> Self {
+        self.invoice.set_recurrence_limit(limit);
+        self
+    }
+
+    /// Makes alternative beneficiaries parts of a split payment, each of them
+    /// receiving the share with the same index.
+    pub fn payment_split(mut self, shares: Vec<SplitShare>) -> Self {
+        self.invoice.set_payment_split_unchecked(shares);
+        self
+    }
+
+    pub fn quantity(mut self, quantity: Quantity) -> Self {
+        self.invoice.set_quantity_unchecked(quantity);
+        self
+    }
+
+    pub fn currency_requirement(mut self, currency: CurrencyData) -> Self {
+        self.invoice.add_currency_requirement_unchecked(currency);
+        self
+    }
+
+    pub fn merchant(mut self, merchant: &str) -> Self {
+        self.invoice.set_merchant(merchant.to_owned());
+        self
+    }
+
+    pub fn merchant_info(mut self, merchant_info: MerchantInfo) -> Self {
+        self.invoice.set_merchant_info(merchant_info);
+        self
+    }
+
+    pub fn purpose(mut self, purpose: &str) -> Self {
+        self.invoice.set_purpose(purpose.to_owned());
+        self
+    }
+
+    pub fn reference(mut self, reference: Blob) -> Self {
+        self.invoice.set_reference_unchecked(reference);
+        self
+    }
+
+    pub fn details(mut self, details: Details) -> Self {
+        self.invoice.set_details(details);
+        self
+    }
+
+    pub fn inline_details(mut self, inline_details: InlineDetails) -> Self {
+        self.invoice.set_inline_details(inline_details);
+        self
+    }
+
+    /// Adds consignment endpoint unless it is already present. Adding more
+    /// than [`crate::MAX_CONSIGNMENT_ENDPOINTS`] endpoints makes
+    /// [`InvoiceBuilder::finish`] fail.
+    pub fn consignment_endpoint(
+        mut self,
+        endpoint: ConsignmentEndpoint,
+    ) -> Self {
+        self.invoice.consignment_endpoints.push_unchecked(endpoint);
+        self
+    }
+
+    pub fn network(mut self, network: Network) -> Self {
+        self.invoice.set_network(network);
+        self
+    }
+
+    pub fn onchain_policy(mut self, policy: OnchainPolicy) -> Self {
+        self.invoice.set_onchain_policy(policy);
+        self
+    }
+
+    /// Sets the maximal length of the invoice string, exceeding which is
+    /// reported by [`InvoiceBuilder::finish_with_warnings`]. The limit may be
+    /// taken from [`crate::QrCapacity::capacity`].
+    pub fn size_limit(mut self, limit: usize) -> Self {
+        self.size_limit = Some(limit);
+        self
+    }
+
+    /// Validates the invoice data and produces the invoice.
+    pub fn finish(self) -> Result<Invoice, BuilderError> {
+        self.finish_with_warnings().map(|(invoice, _)| invoice)
+    }
+
+    /// Validates the invoice data and produces the invoice together with
+    /// the list of non-fatal issues.
+    pub fn finish_with_warnings(
+        self,
+    ) -> Result<(Invoice, Vec<BuilderWarning>), BuilderError> {
+        let invoice = self.invoice;
+
+        if let Some(expiry) = invoice.effective_expiry()? {
+            if expiry < Utc::now() {
+                return Err(BuilderError::ExpiryInPast(expiry));
+            }
+        }
+
+        if let AmountExt::Milli(_, frac) = invoice.amount() {
+            if *frac > 999 {
+                return Err(BuilderError::AmountFraction(*frac));
+            }
+        }
+
+        if let Some(quantity) = invoice.quantity() {
+            if invoice.amount() == &AmountExt::Any {
+                return Err(BuilderError::QuantityWithoutAmount);
+            }
+            quantity.validate()?;
+        }
+
+        if invoice.currency_requirements().len() > MAX_CURRENCY_REQUIREMENTS {
+            return Err(CurrencyError::TooManyRequirements.into());
+        }
+        for currency in invoice.currency_requirements() {
+            currency.validate()?;
+        }
+
+        invoice.check_payment_split()?;
+
+        if let Some(network) = invoice.network() {
+            for beneficiary in invoice.beneficiaries() {
+                if let Beneficiary::Address(address) = beneficiary {
+                    if !network.matches_address_network(address.network) {
+                        return Err(BuilderError::NetworkMismatch(
+                            address.clone(),
+                        ));
+                    }
+                }
+            }
+        }
+
+        if let Err(mut errors) = invoice.validate() {
+            return Err(errors.remove(0).into());
+        }
+
+        let mut warnings = vec![];
+        if let Some(limit) = self.size_limit {
+            let len = invoice.bech32_len();
+            if len > limit {
+                warnings.push(BuilderWarning::SizeLimit { len, limit });
+            }
+        }
+
+        Ok((invoice, warnings))
+    }
+}
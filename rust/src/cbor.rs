@@ -0,0 +1,228 @@
+// LNP/BP universal invoice library implementing LNPBP-38 standard
+// Written in 2021 by
+//     Dr. Maxim Orlovsky <orlovsky@pandoracore.com>
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the MIT License
+// along with this software.
+// If not, see <https://opensource.org/licenses/MIT>.
+
+//! CBOR representation of invoices, used by hardware wallets and air-gapped
+//! signers.
+//!
+//! Invoice is encoded as a CBOR array of four items:
+//! - `version` as an unsigned integer;
+//! - strict-encoded `amount` as a byte string;
+//! - strict-encoded `beneficiary` as a byte string;
+//! - map from TLV record types (unsigned integers) to the raw TLV record
+//!   values (byte strings), including unknown TLV records.
+//!
+//! Only deterministic (canonical) CBOR per RFC 8949 section 4.2 is produced
+//! and accepted: integers and lengths use the shortest form, there are no
+//! indefinite-length items and map keys go in ascending order, so the same
+//! invoice always yields identical bytes.
+
+use amplify::Wrapper;
+use internet2::tlv;
+use std::convert::TryFrom;
+use strict_encoding::{StrictDecode, StrictEncode};
+
+use crate::Invoice;
+
+const MAJOR_UINT: u8 = 0;
+const MAJOR_BYTES: u8 = 2;
+const MAJOR_ARRAY: u8 = 4;
+const MAJOR_MAP: u8 = 5;
+
+/// Errors decoding invoice from CBOR data.
+#[derive(Clone, PartialEq, Debug, Display, Error)]
+#[display(doc_comments)]
+pub enum CborError {
+    /// unexpected end of CBOR data
+    UnexpectedEnd,
+
+    /// unexpected CBOR data item; {0} was expected
+    UnexpectedItem(&'static str),
+
+    /// CBOR data are not in the canonical form
+    NonCanonical,
+
+    /// CBOR data contain extra bytes after the invoice
+    TrailingData,
+
+    /// invoice version {0} is out of range
+    VersionOutOfRange(u64),
+
+    /// invalid invoice data inside CBOR: {0}
+    InvalidInvoice(strict_encoding::Error),
+}
+
+impl Invoice {
+    /// Encodes invoice in canonical CBOR form (see [`crate::cbor`] module
+    /// documentation for the details).
+    ///
+    /// # Panics
+    ///
+    /// If the invoice data can't be strict-encoded (which must not happen
+    /// for invoices constructed with library API).
+    pub fn to_cbor(&self) -> Vec<u8> {
+        const ERR: &str = "invoice data are inconsistent for strict encoding";
+
+        let amount = self.amount().strict_serialize().expect(ERR);
+        let beneficiary = self.beneficiary().strict_serialize().expect(ERR);
+        let data = self.strict_serialize().expect(ERR);
+        let offset = 1 + amount.len() + beneficiary.len();
+        let records =
+            tlv::Stream::strict_deserialize(&data[offset..]).expect(ERR);
+
+        let mut cbor = vec![];
+        write_head(&mut cbor, MAJOR_ARRAY, 4);
+        write_head(&mut cbor, MAJOR_UINT, *self.version() as u64);
+        write_bytes(&mut cbor, &amount);
+        write_bytes(&mut cbor, &beneficiary);
+        write_head(&mut cbor, MAJOR_MAP, records.len() as u64);
+        for (ty, value) in &records {
+            write_head(&mut cbor, MAJOR_UINT, ty.into_inner());
+            write_bytes(&mut cbor, value.as_ref());
+        }
+        cbor
+    }
+
+    /// Decodes invoice from canonical CBOR data produced by
+    /// [`Invoice::to_cbor`].
+    pub fn from_cbor(data: &[u8]) -> Result<Invoice, CborError> {
+        let mut reader = Reader { data, pos: 0 };
+
+        if reader.read_head(MAJOR_ARRAY, "array")? != 4 {
+            return Err(CborError::UnexpectedItem("array of 4 items"));
+        }
+        let version = reader.read_head(MAJOR_UINT, "version")?;
+        if version > u8::MAX as u64 {
+            return Err(CborError::VersionOutOfRange(version));
+        }
+        let mut strict = vec![version as u8];
+        strict.extend(reader.read_bytes("amount")?);
+        strict.extend(reader.read_bytes("beneficiary")?);
+
+        let count = reader.read_head(MAJOR_MAP, "TLV map")?;
+        let mut records = tlv::Stream::new();
+        let mut prev = None;
+        for _ in 0..count {
+            let ty = reader.read_head(MAJOR_UINT, "TLV type")?;
+            if prev.map(|prev| prev >= ty).unwrap_or(false) {
+                return Err(CborError::NonCanonical);
+            }
+            prev = Some(ty);
+            let value = reader.read_bytes("TLV value")?;
+            records.insert(tlv::Type::from_inner(ty), value);
+        }
+        if reader.pos != data.len() {
+            return Err(CborError::TrailingData);
+        }
+
+        records
+            .strict_encode(&mut strict)
+            .map_err(CborError::InvalidInvoice)?;
+        let invoice = Invoice::strict_deserialize(&strict)
+            .map_err(CborError::InvalidInvoice)?;
+        // Field boundaries must match the ones of the decoded invoice
+        if invoice.to_cbor() != data {
+            return Err(CborError::NonCanonical);
+        }
+        invoice
+            .check_consistency()
+            .map_err(CborError::InvalidInvoice)?;
+        Ok(invoice)
+    }
+}
+
+fn write_head(cbor: &mut Vec<u8>, major: u8, value: u64) {
+    let major = major << 5;
+    if value < 24 {
+        cbor.push(major | value as u8);
+    } else if value <= u8::MAX as u64 {
+        cbor.push(major | 24);
+        cbor.push(value as u8);
+    } else if value <= u16::MAX as u64 {
+        cbor.push(major | 25);
+        cbor.extend((value as u16).to_be_bytes());
+    } else if value <= u32::MAX as u64 {
+        cbor.push(major | 26);
+        cbor.extend((value as u32).to_be_bytes());
+    } else {
+        cbor.push(major | 27);
+        cbor.extend(value.to_be_bytes());
+    }
+}
+
+fn write_bytes(cbor: &mut Vec<u8>, bytes: &[u8]) {
+    write_head(cbor, MAJOR_BYTES, bytes.len() as u64);
+    cbor.extend(bytes);
+}
+
+struct Reader<'data> {
+    data: &'data [u8],
+    pos: usize,
+}
+
+impl<'data> Reader<'data> {
+    fn take(&mut self, len: usize) -> Result<&'data [u8], CborError> {
+        let end = self
+            .pos
+            .checked_add(len)
+            .filter(|end| *end <= self.data.len())
+            .ok_or(CborError::UnexpectedEnd)?;
+        let slice = &self.data[self.pos..end];
+        self.pos = end;
+        Ok(slice)
+    }
+
+    fn read_head(
+        &mut self,
+        major: u8,
+        expected: &'static str,
+    ) -> Result<u64, CborError> {
+        let initial = self.take(1)?[0];
+        if initial >> 5 != major {
+            return Err(CborError::UnexpectedItem(expected));
+        }
+        let (value, min) = match initial & 0x1F {
+            info @ 0..=23 => return Ok(info as u64),
+            24 => (self.take(1)?[0] as u64, 24),
+            25 => {
+                let mut buf = [0u8; 2];
+                buf.copy_from_slice(self.take(2)?);
+                (u16::from_be_bytes(buf) as u64, u8::MAX as u64 + 1)
+            }
+            26 => {
+                let mut buf = [0u8; 4];
+                buf.copy_from_slice(self.take(4)?);
+                (u32::from_be_bytes(buf) as u64, u16::MAX as u64 + 1)
+            }
+            27 => {
+                let mut buf = [0u8; 8];
+                buf.copy_from_slice(self.take(8)?);
+                (u64::from_be_bytes(buf), u32::MAX as u64 + 1)
+            }
+            // Reserved values and indefinite-length items are not allowed
+            _ => return Err(CborError::NonCanonical),
+        };
+        if value < min {
+            return Err(CborError::NonCanonical);
+        }
+        Ok(value)
+    }
+
+    fn read_bytes(
+        &mut self,
+        expected: &'static str,
+    ) -> Result<&'data [u8], CborError> {
+        let len = self.read_head(MAJOR_BYTES, expected)?;
+        let len = usize::try_from(len).map_err(|_| CborError::UnexpectedEnd)?;
+        self.take(len)
+    }
+}
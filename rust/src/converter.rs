@@ -1,14 +1,31 @@
-use amplify::Wrapper;
+use amplify::{Slice32, Wrapper};
+use bitcoin::bech32::u5;
+use bitcoin::secp256k1::ecdsa::RecoverableSignature;
+use bitcoin::secp256k1::{Message, PublicKey, Secp256k1, SecretKey};
+use bitcoin::util::address::{Payload, WitnessVersion};
 use bitcoin::{
-    hashes::{sha256, Hash},
-    secp256k1::rand::{self},
+    hashes::{sha256, Hash, HashEngine, Hmac, HmacEngine},
+    Address, PubkeyHash, ScriptHash,
 };
+use bitcoin_scripts::hlc::HashLock;
+use chrono::{DateTime, TimeZone, Utc};
+use internet2::addr::NodeId;
 use lightning::ln::PaymentSecret;
-use lightning_invoice::{Currency, InvoiceBuilder, RawInvoice};
+use lightning::routing::gossip::RoutingFees;
+use lightning::routing::router::{RouteHint, RouteHintHop};
+use lightning_invoice::{
+    Currency, Fallback, InvoiceBuilder, InvoiceDescription, RawInvoice,
+    RawTaggedField, TaggedField,
+};
+use lnp::p2p::bolt::ShortChannelId;
 use lnpbp::chain::Chain;
-use std::convert::TryFrom;
+use std::convert::{Infallible, TryFrom};
+use std::time::Duration;
 
-use crate::{Beneficiary, Invoice};
+use crate::{
+    AmountExt, Beneficiary, Invoice, LnAddress, LnFlags, LnPathHint,
+    LnRouteHint, Lock, Network,
+};
 
 #[derive(Copy, Clone, Eq, PartialEq, Hash, Debug, Display, Error)]
 #[display(doc_comments)]
@@ -19,52 +36,498 @@ pub enum InvoiceError {
     UnknownChain,
     /// Invoice cannot contains payment_hash field
     MissingPaymentHash,
+    /// Invoice beneficiary uses PTLC point lock not supported by BOLT-11
+    PointLock,
+    /// Invoice beneficiary is a BOLT-12 offer node without a payment hash
+    OfferBeneficiary,
     /// Raw invoice with missing parts
     ParserError,
+    /// Invoice fallback address is not valid
+    InvalidFallback,
+    /// Invoice amount can't be represented in millisatoshis
+    AmountOverflow,
+    /// Invoice has already expired
+    AlreadyExpired,
+    /// Invoice has no issuance time required for BOLT-11 timestamp
+    MissingTimestamp,
+    /// Invoice beneficiary has no payment secret required by BOLT-11
+    MissingPaymentSecret,
+    /// Invoice issuance time can't be represented as BOLT-11 timestamp
+    InvalidTimestamp,
+    /// Invoice expiry data are inconsistent
+    InconsistentExpiry,
+    /// Signing key does not match the node id of the invoice beneficiary
+    NodeKeyMismatch,
+    /// Signed BOLT-11 invoice is not valid
+    InvalidSignature,
+}
+
+/// Default value for `min_final_cltv_expiry` BOLT-11 field, as defined by the
+/// BOLT-11 specification
+pub const DEFAULT_MIN_FINAL_CLTV_EXPIRY: u16 = 18;
+
+/// Converts invoice amount, measured in satoshis, into millisatoshis used by
+/// BOLT-11. Returns `None` for invoices accepting any amount. BOLT-11 amounts
+/// are encoded in pico-bitcoins, which must fit into `u64`, so amounts above
+/// `u64::MAX / 10` millisatoshis (about 18.4M BTC) are not representable.
+fn amount_msat(amount: &AmountExt) -> Result<Option<u64>, InvoiceError> {
+    let (sat, msat) = match *amount {
+        AmountExt::Any => return Ok(None),
+        AmountExt::Normal(sat) => (sat, 0),
+        AmountExt::Milli(sat, msat) => (sat, msat as u64),
+    };
+    sat.checked_mul(1000)
+        .and_then(|value| value.checked_add(msat))
+        .filter(|msat| msat.checked_mul(10).is_some())
+        .map(Some)
+        .ok_or(InvoiceError::AmountOverflow)
+}
+
+/// Converts time into BOLT-11 timestamp, which has a precision of seconds
+fn bolt11_timestamp(time: DateTime<Utc>) -> Result<u64, InvoiceError> {
+    u64::try_from(time.timestamp()).map_err(|_| InvoiceError::InvalidTimestamp)
 }
 
+/// Requires the invoice to have the issuance time, which is used as the
+/// BOLT-11 timestamp and is the only time the expiry is checked against, and
+/// the lightning beneficiary to have the payment secret. Use
+/// [`Invoice::to_bolt11_raw`] for invoices without them.
 impl TryFrom<Invoice> for RawInvoice {
     type Error = InvoiceError;
 
     fn try_from(invoice: Invoice) -> Result<Self, Self::Error> {
-        if let Beneficiary::Bolt(params) = invoice.beneficiary() {
-            let payment_hash = sha256::Hash::from_slice(&params.lock[..])
-                .map_err(|_| InvoiceError::MissingPaymentHash);
-
-            let min_final_cltv_expiry =
-                params.min_final_cltv_expiry.unwrap_or_default();
-
-            let currency = match params.network {
-                Chain::Mainnet => Ok(Currency::Bitcoin),
-                Chain::Testnet3 => Ok(Currency::BitcoinTestnet),
-                Chain::Regtest(_) => Ok(Currency::Regtest),
-                Chain::Signet => Ok(Currency::Signet),
-                _ => Err(InvoiceError::UnknownChain),
-            };
+        invoice.bolt11_raw(None, None)
+    }
+}
 
-            let description = match invoice.purpose() {
-                Some(desc) => desc,
-                _ => "",
-            };
+impl Invoice {
+    /// Produces unsigned BOLT-11 invoice. Its timestamp is the invoice
+    /// issuance time or, if the invoice doesn't have it, the provided
+    /// current time. Fails with [`InvoiceError::AlreadyExpired`] if the
+    /// invoice expires before the current time. The provided payment secret
+    /// is used only if the lightning beneficiary doesn't have one.
+    pub fn to_bolt11_raw(
+        &self,
+        now: DateTime<Utc>,
+        payment_secret: Option<Slice32>,
+    ) -> Result<RawInvoice, InvoiceError> {
+        self.bolt11_raw(Some(now), payment_secret)
+    }
 
-            let payment_secret = match params.secret {
-                Some(secret) => secret.as_inner().to_owned(),
-                _ => rand::random(),
-            };
-            let payment_secret = PaymentSecret(payment_secret);
-            let bolt11 = InvoiceBuilder::new(currency?)
-                .description(description.to_owned())
-                .amount_milli_satoshis(
-                    invoice.amount().atomic_value().unwrap_or_default(),
-                )
-                .payment_hash(payment_hash?)
-                .payment_secret(payment_secret)
-                .current_timestamp()
-                .min_final_cltv_expiry(min_final_cltv_expiry.into());
-
-            bolt11.build_raw().map_err(|_| InvoiceError::ParserError)
+    fn bolt11_raw(
+        &self,
+        now: Option<DateTime<Utc>>,
+        payment_secret: Option<Slice32>,
+    ) -> Result<RawInvoice, InvoiceError> {
+        let params = self
+            .bolt_beneficiary()
+            .ok_or(InvoiceError::UnknownBeneficiary)?;
+
+        if params.ln_flags.contains(LnFlags::OFFER) {
+            return Err(InvoiceError::OfferBeneficiary);
+        }
+        let payment_hash = match params.lock {
+            Lock::Hash(lock) => sha256::Hash::from_slice(&lock[..])
+                .map_err(|_| InvoiceError::MissingPaymentHash),
+            Lock::Point(_) => Err(InvoiceError::PointLock),
+        };
+
+        let min_final_cltv_expiry = params
+            .min_final_cltv_expiry
+            .unwrap_or(DEFAULT_MIN_FINAL_CLTV_EXPIRY);
+
+        let currency = bolt11_currency(self, params)?;
+
+        let description = match self.purpose() {
+            Some(desc) => desc,
+            _ => "",
+        };
+
+        let payment_secret = params
+            .secret
+            .or(payment_secret)
+            .ok_or(InvoiceError::MissingPaymentSecret)?;
+        let payment_secret = PaymentSecret(payment_secret.into_inner());
+
+        let timestamp = match (self.issued_at(), now) {
+            (Some(issued_at), _) => bolt11_timestamp(*issued_at)?,
+            (None, Some(now)) => bolt11_timestamp(now)?,
+            (None, None) => return Err(InvoiceError::MissingTimestamp),
+        };
+        let now = match now {
+            Some(now) => bolt11_timestamp(now)?,
+            None => timestamp,
+        };
+
+        let mut bolt11 = InvoiceBuilder::new(currency.clone())
+            .description(description.to_owned())
+            .payment_hash(payment_hash?)
+            .payment_secret(payment_secret)
+            .duration_since_epoch(Duration::from_secs(timestamp))
+            .min_final_cltv_expiry(min_final_cltv_expiry.into());
+
+        let expiry = self
+            .effective_expiry()
+            .map_err(|_| InvoiceError::InconsistentExpiry)?;
+        if let Some(expiry) = expiry {
+            let expiry = u64::try_from(expiry.timestamp()).unwrap_or(0);
+            if expiry <= timestamp.max(now) {
+                return Err(InvoiceError::AlreadyExpired);
+            }
+            bolt11 =
+                bolt11.expiry_time(Duration::from_secs(expiry - timestamp));
+        }
+
+        if let Some(msat) = amount_msat(self.amount())? {
+            bolt11 = bolt11.amount_milli_satoshis(msat);
+        }
+
+        for route in &params.path_hints {
+            bolt11 = bolt11.private_route(RouteHint::from(route));
+        }
+
+        // On-chain beneficiaries are provided as fallback addresses, skipping
+        // addresses for networks other than the BOLT-11 currency network
+        let network = currency_network(&currency);
+        for beneficiary in self.beneficiaries() {
+            if let Beneficiary::Address(address) = beneficiary {
+                if network.is_some_and(|n| address.is_valid_for_network(n)) {
+                    bolt11 = bolt11.fallback(fallback_from_address(address)?);
+                }
+            }
+        }
+
+        let mut raw =
+            bolt11.build_raw().map_err(|_| InvoiceError::ParserError)?;
+        // Invoice builder is able to set only optional `basic_mpp` feature
+        if params.ln_flags.contains(LnFlags::MPP_REQUIRED) {
+            for field in &mut raw.data.tagged_fields {
+                if let RawTaggedField::KnownSemantics(TaggedField::Features(
+                    features,
+                )) = field
+                {
+                    features.set_basic_mpp_required();
+                }
+            }
+        }
+        Ok(raw)
+    }
+
+    /// Returns the first lightning beneficiary in the order of beneficiary
+    /// priority, if any.
+    pub fn bolt_beneficiary(&self) -> Option<&LnAddress> {
+        self.beneficiaries()
+            .find_map(|beneficiary| match beneficiary {
+                Beneficiary::Bolt(params) => Some(params),
+                _ => None,
+            })
+    }
+
+    /// Produces signed BOLT-11 invoice, which can be provided to the payer,
+    /// using an external signer. The signer must produce recoverable
+    /// signature with the node key matching [`LnAddress::node_id`]. The
+    /// current time and the payment secret are used as described in
+    /// [`Invoice::to_bolt11_raw`].
+    pub fn to_bolt11_signed(
+        &self,
+        now: DateTime<Utc>,
+        payment_secret: Option<Slice32>,
+        sign: impl FnOnce(&Message) -> RecoverableSignature,
+    ) -> Result<lightning_invoice::Invoice, InvoiceError> {
+        let node_id = self
+            .bolt_beneficiary()
+            .ok_or(InvoiceError::UnknownBeneficiary)?
+            .node_id
+            .public_key();
+        let signed = self
+            .to_bolt11_raw(now, payment_secret)?
+            .sign::<_, Infallible>(|msg| Ok(sign(msg)))
+            .expect("infallible signer");
+        let bolt11 = lightning_invoice::Invoice::from_signed(signed)
+            .map_err(|_| InvoiceError::InvalidSignature)?;
+        if bolt11.recover_payee_pub_key() != node_id {
+            return Err(InvoiceError::NodeKeyMismatch);
+        }
+        Ok(bolt11)
+    }
+
+    /// Produces signed BOLT-11 invoice, which can be provided to the payer,
+    /// signing it with the node secret key. If the lightning beneficiary
+    /// doesn't have the payment secret, it is derived from the node key and
+    /// the payment hash, so the same invoice always produces the same
+    /// BOLT-11 string.
+    pub fn to_bolt11_signed_with_key(
+        &self,
+        now: DateTime<Utc>,
+        node_key: &SecretKey,
+    ) -> Result<lightning_invoice::Invoice, InvoiceError> {
+        let secp = Secp256k1::signing_only();
+        let params = self
+            .bolt_beneficiary()
+            .ok_or(InvoiceError::UnknownBeneficiary)?;
+        if params.node_id.public_key()
+            != PublicKey::from_secret_key(&secp, node_key)
+        {
+            return Err(InvoiceError::NodeKeyMismatch);
+        }
+        let payment_secret = match params.lock {
+            Lock::Hash(lock) => payment_secret(node_key, lock),
+            Lock::Point(_) => return Err(InvoiceError::PointLock),
+        };
+        self.to_bolt11_signed(now, Some(payment_secret), |msg| {
+            secp.sign_ecdsa_recoverable(msg, node_key)
+        })
+    }
+}
+
+impl TryFrom<lightning_invoice::Invoice> for Invoice {
+    type Error = InvoiceError;
+
+    fn try_from(
+        bolt11: lightning_invoice::Invoice,
+    ) -> Result<Self, Self::Error> {
+        let (chain, network, btc_network) = match bolt11.currency() {
+            Currency::Bitcoin => {
+                (Chain::Mainnet, Network::Mainnet, bitcoin::Network::Bitcoin)
+            }
+            Currency::BitcoinTestnet => (
+                Chain::Testnet3,
+                Network::Testnet3,
+                bitcoin::Network::Testnet,
+            ),
+            Currency::Regtest => (
+                Chain::from(bitcoin::Network::Regtest),
+                Network::Regtest,
+                bitcoin::Network::Regtest,
+            ),
+            Currency::Signet => {
+                (Chain::Signet, Network::Signet, bitcoin::Network::Signet)
+            }
+            Currency::Simnet => return Err(InvoiceError::UnknownChain),
+        };
+
+        let node_id = bolt11
+            .payee_pub_key()
+            .copied()
+            .unwrap_or_else(|| bolt11.recover_payee_pub_key());
+
+        let path_hints = bolt11
+            .route_hints()
+            .into_iter()
+            .map(LnRouteHint::from)
+            .collect();
+
+        let ln_flags = match bolt11.features() {
+            Some(features) if features.requires_basic_mpp() => {
+                LnFlags::MPP_REQUIRED
+            }
+            _ => LnFlags::empty(),
+        };
+
+        let ln_address = LnAddress {
+            node_id: NodeId::from(node_id),
+            features: Default::default(),
+            lock: Lock::Hash(HashLock::from(Slice32::from_inner(
+                bolt11.payment_hash().into_inner(),
+            ))),
+            secret: Some(Slice32::from_inner(bolt11.payment_secret().0)),
+            network: chain.clone(),
+            min_final_cltv_expiry: u16::try_from(
+                bolt11.min_final_cltv_expiry(),
+            )
+            .ok(),
+            path_hints,
+            ln_flags,
+        };
+
+        let asset = if chain == Chain::Mainnet {
+            None
         } else {
-            Err(InvoiceError::UnknownBeneficiary)
+            Some(chain.native_asset())
+        };
+        let mut invoice =
+            Invoice::new(Beneficiary::Bolt(ln_address), None, asset);
+
+        if let Some(msat) = bolt11.amount_milli_satoshis() {
+            invoice.set_amount_unchecked(match msat % 1000 {
+                0 => AmountExt::Normal(msat / 1000),
+                frac => AmountExt::Milli(msat / 1000, frac as u16),
+            });
+        }
+
+        if let InvoiceDescription::Direct(description) = bolt11.description() {
+            invoice.set_purpose(description.clone().into_inner());
+        }
+
+        let to_time = |since_epoch: Duration| {
+            i64::try_from(since_epoch.as_secs())
+                .ok()
+                .and_then(|secs| Utc.timestamp_opt(secs, 0).single())
+        };
+        if let Some(issued_at) = to_time(bolt11.duration_since_epoch()) {
+            invoice.set_issued_at(issued_at);
+        }
+        let expiry = bolt11
+            .duration_since_epoch()
+            .checked_add(bolt11.expiry_time())
+            .and_then(to_time);
+        if let Some(expiry) = expiry {
+            invoice.set_expiry(expiry);
+        }
+
+        for fallback in bolt11.fallbacks() {
+            let payload = match fallback {
+                Fallback::SegWitProgram { version, program } => {
+                    Payload::WitnessProgram {
+                        version: WitnessVersion::try_from(version.to_u8())
+                            .map_err(|_| InvoiceError::InvalidFallback)?,
+                        program: program.clone(),
+                    }
+                }
+                Fallback::PubKeyHash(hash) => {
+                    Payload::PubkeyHash(PubkeyHash::from_inner(*hash))
+                }
+                Fallback::ScriptHash(hash) => {
+                    Payload::ScriptHash(ScriptHash::from_inner(*hash))
+                }
+            };
+            invoice
+                .alt_beneficiaries
+                .push(Beneficiary::Address(Address {
+                    payload,
+                    network: btc_network,
+                }));
+        }
+
+        invoice.set_network(network);
+
+        Ok(invoice)
+    }
+}
+
+impl From<&RouteHintHop> for LnPathHint {
+    fn from(hop: &RouteHintHop) -> Self {
+        LnPathHint {
+            node_id: NodeId::from(hop.src_node_id),
+            short_channel_id: short_channel_id_from_u64(hop.short_channel_id),
+            fee_base_msat: hop.fees.base_msat,
+            fee_proportional_millionths: hop.fees.proportional_millionths,
+            cltv_expiry_delta: hop.cltv_expiry_delta,
+        }
+    }
+}
+
+impl From<LnPathHint> for RouteHintHop {
+    fn from(hint: LnPathHint) -> Self {
+        RouteHintHop {
+            src_node_id: hint.node_id.public_key(),
+            short_channel_id: short_channel_id_to_u64(hint.short_channel_id),
+            fees: RoutingFees {
+                base_msat: hint.fee_base_msat,
+                proportional_millionths: hint.fee_proportional_millionths,
+            },
+            cltv_expiry_delta: hint.cltv_expiry_delta,
+            htlc_minimum_msat: None,
+            htlc_maximum_msat: None,
+        }
+    }
+}
+
+impl From<RouteHint> for LnRouteHint {
+    fn from(route: RouteHint) -> Self {
+        LnRouteHint(route.0.iter().map(LnPathHint::from).collect())
+    }
+}
+
+impl From<&LnRouteHint> for RouteHint {
+    fn from(route: &LnRouteHint) -> Self {
+        RouteHint(route.0.iter().copied().map(RouteHintHop::from).collect())
+    }
+}
+
+/// Detects BOLT-11 currency from the invoice-level `network` field, then from
+/// the invoice native asset, falling back to the legacy lightning beneficiary
+/// chain information.
+fn bolt11_currency(
+    invoice: &Invoice,
+    params: &LnAddress,
+) -> Result<Currency, InvoiceError> {
+    let regtest = Chain::from(bitcoin::Network::Regtest);
+    match (invoice.network(), invoice.asset()) {
+        (Some(Network::Mainnet), _) => Ok(Currency::Bitcoin),
+        (Some(Network::Testnet3), _) => Ok(Currency::BitcoinTestnet),
+        (Some(Network::Signet), _) => Ok(Currency::Signet),
+        (Some(Network::Regtest), _) => Ok(Currency::Regtest),
+        (Some(_), _) => Err(InvoiceError::UnknownChain),
+        (None, Some(asset)) if *asset == Chain::Mainnet.native_asset() => {
+            Ok(Currency::Bitcoin)
+        }
+        (None, Some(asset)) if *asset == Chain::Testnet3.native_asset() => {
+            Ok(Currency::BitcoinTestnet)
         }
+        (None, Some(asset)) if *asset == Chain::Signet.native_asset() => {
+            Ok(Currency::Signet)
+        }
+        (None, Some(asset)) if *asset == regtest.native_asset() => {
+            Ok(Currency::Regtest)
+        }
+        (None, _) => match params.network {
+            Chain::Mainnet => Ok(Currency::Bitcoin),
+            Chain::Testnet3 => Ok(Currency::BitcoinTestnet),
+            Chain::Regtest(_) => Ok(Currency::Regtest),
+            Chain::Signet => Ok(Currency::Signet),
+            _ => Err(InvoiceError::UnknownChain),
+        },
     }
 }
+
+/// Derives payment secret for the payment hash from the node key
+fn payment_secret(node_key: &SecretKey, payment_hash: HashLock) -> Slice32 {
+    let mut engine = HmacEngine::<sha256::Hash>::new(&node_key[..]);
+    engine.input(b"LNPBP38:bolt11:payment_secret");
+    engine.input(&payment_hash[..]);
+    Slice32::from_inner(Hmac::from_engine(engine).into_inner())
+}
+
+/// Returns bitcoin network of the BOLT-11 currency
+fn currency_network(currency: &Currency) -> Option<bitcoin::Network> {
+    match currency {
+        Currency::Bitcoin => Some(bitcoin::Network::Bitcoin),
+        Currency::BitcoinTestnet => Some(bitcoin::Network::Testnet),
+        Currency::Regtest => Some(bitcoin::Network::Regtest),
+        Currency::Signet => Some(bitcoin::Network::Signet),
+        Currency::Simnet => None,
+    }
+}
+
+/// Converts on-chain address into BOLT-11 fallback address
+fn fallback_from_address(address: &Address) -> Result<Fallback, InvoiceError> {
+    Ok(match &address.payload {
+        Payload::WitnessProgram { version, program } => {
+            Fallback::SegWitProgram {
+                version: u5::try_from_u8(version.to_num())
+                    .map_err(|_| InvoiceError::InvalidFallback)?,
+                program: program.clone(),
+            }
+        }
+        Payload::PubkeyHash(hash) => Fallback::PubKeyHash(hash.into_inner()),
+        Payload::ScriptHash(hash) => Fallback::ScriptHash(hash.into_inner()),
+    })
+}
+
+/// Converts BOLT-7 short channel id into its numeric representation
+fn short_channel_id_to_u64(scid: ShortChannelId) -> u64 {
+    u64::from(scid.block_height) << 40
+        | u64::from(scid.tx_index) << 16
+        | u64::from(scid.output_index)
+}
+
+/// Converts BOLT-7 short channel id from its numeric representation
+fn short_channel_id_from_u64(scid: u64) -> ShortChannelId {
+    ShortChannelId::with(
+        (scid >> 40) as u32 & 0xFF_FFFF,
+        (scid >> 16) as u32 & 0xFF_FFFF,
+        scid as u16,
+    )
+    .expect("24-bit values never overflow")
+}
@@ -0,0 +1,215 @@
+// LNP/BP universal invoice library implementing LNPBP-38 standard
+// Written in 2021 by
+//     Dr. Maxim Orlovsky <orlovsky@pandoracore.com>
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the MIT License
+// along with this software.
+// If not, see <https://opensource.org/licenses/MIT>.
+
+//! Invoice extensions: TLV records defined by third-party protocols, which
+//! attach their own payment data to the invoice.
+//!
+//! Extensions are encoded together with the core invoice fields, so they
+//! survive bech32 round trips and are covered by the invoice signature.
+//! Extension records must use odd TLV types above the range reserved for
+//! the core invoice fields, so the software not aware of the extension can
+//! still decode the invoice.
+
+use std::collections::BTreeMap;
+
+use amplify::Wrapper;
+#[cfg(feature = "serde")]
+use bitcoin::hashes::hex::{FromHex, ToHex};
+use internet2::tlv;
+
+use crate::Invoice;
+
+/// The largest TLV type reserved for the core invoice fields.
+///
+/// Types from `0x00` to `0x3f` are reserved for the fields defined by the
+/// LNPBP-38 standard, including the fields added by its future versions, so
+/// extension records never clash with core fields added later.
+pub const MAX_CORE_TLV_TYPE: u64 = 0x3f;
+
+/// Errors working with invoice extensions.
+#[derive(Clone, Eq, PartialEq, Hash, Debug, Display, Error)]
+#[display(doc_comments)]
+pub enum ExtensionError {
+    /// TLV type {0} is reserved for the core invoice fields
+    CoreType(u64),
+
+    /// extension TLV type {0} is even, which makes the invoice unreadable by
+    /// the software not aware of the extension
+    EvenType(u64),
+
+    /// invalid data of the invoice extension with TLV type {type_no}:
+    /// {details}
+    InvalidData { type_no: u64, details: String },
+}
+
+/// Checks that the TLV type can be used by an extension record.
+fn check_extension_type(type_no: u64) -> Result<(), ExtensionError> {
+    if type_no <= MAX_CORE_TLV_TYPE {
+        return Err(ExtensionError::CoreType(type_no));
+    }
+    if type_no.is_multiple_of(2) {
+        return Err(ExtensionError::EvenType(type_no));
+    }
+    Ok(())
+}
+
+/// Strongly-typed invoice extension defined by a third-party protocol.
+pub trait InvoiceExtension: Sized {
+    /// Odd TLV type of the extension record, which must be above
+    /// [`MAX_CORE_TLV_TYPE`]
+    const TLV_TYPE: u64;
+
+    /// Serializes extension data into the TLV record value.
+    fn encode(&self) -> Vec<u8>;
+
+    /// Parses extension data from the TLV record value, returning error
+    /// description on failure.
+    fn decode(data: &[u8]) -> Result<Self, String>;
+}
+
+impl Invoice {
+    /// Returns raw value of the extension record with a given TLV type.
+    pub fn extension(&self, type_no: tlv::Type) -> Option<&[u8]> {
+        self.unknown.get(&type_no).map(AsRef::as_ref)
+    }
+
+    /// Detects whether the invoice has unknown even TLV records, meaning that
+    /// it requires features not supported by this library and must not be
+    /// paid. Such invoices are decoded only with
+    /// [`crate::DecodePolicy::accept_unknown_required`] option.
+    pub fn has_unknown_required_fields(&self) -> bool {
+        (&self.unknown)
+            .into_iter()
+            .any(|(type_no, _)| type_no.is_multiple_of(2))
+    }
+
+    /// Iterates over all extension records, ordered by their TLV type.
+    pub fn extensions(&self) -> impl Iterator<Item = (tlv::Type, &[u8])> {
+        (&self.unknown)
+            .into_iter()
+            .map(|(type_no, value)| (*type_no, value.as_ref()))
+    }
+
+    /// Sets extension record value, replacing the previous value of the
+    /// record with the same TLV type.
+    pub fn set_extension(
+        &mut self,
+        type_no: tlv::Type,
+        value: Vec<u8>,
+    ) -> Result<(), ExtensionError> {
+        check_extension_type(type_no.into_inner())?;
+        if self.extension(type_no) == Some(&value) {
+            return Ok(());
+        }
+        self.unknown.insert(type_no, value);
+        self.invalidate_signatures();
+        Ok(())
+    }
+
+    /// Removes extension record with a given TLV type.
+    pub fn remove_extension(&mut self, type_no: tlv::Type) -> bool {
+        if !self.unknown.contains_key(&type_no) {
+            return false;
+        }
+        self.unknown = self
+            .unknown
+            .clone()
+            .into_iter()
+            .filter(|(no, _)| *no != type_no)
+            .collect::<BTreeMap<_, _>>()
+            .into();
+        self.invalidate_signatures();
+        true
+    }
+
+    /// Parses the extension of a given type, returning `None` if the invoice
+    /// does not have it.
+    pub fn typed_extension<E: InvoiceExtension>(
+        &self,
+    ) -> Option<Result<E, ExtensionError>> {
+        self.extension(E::TLV_TYPE.into()).map(|data| {
+            E::decode(data).map_err(|details| ExtensionError::InvalidData {
+                type_no: E::TLV_TYPE,
+                details,
+            })
+        })
+    }
+
+    /// Sets the extension of a given type.
+    pub fn set_typed_extension<E: InvoiceExtension>(
+        &mut self,
+        extension: &E,
+    ) -> Result<(), ExtensionError> {
+        self.set_extension(E::TLV_TYPE.into(), extension.encode())
+    }
+}
+
+/// Serde adapter representing unknown TLV records as a map of TLV types to
+/// hex-encoded values in human-readable formats and to raw bytes otherwise.
+/// Deserialization accepts only the TLV types allowed for extensions by
+/// [`Invoice::set_extension`].
+#[cfg(feature = "serde")]
+pub(crate) struct TlvExtensions;
+
+#[cfg(feature = "serde")]
+impl serde_with::SerializeAs<tlv::Stream> for TlvExtensions {
+    fn serialize_as<S>(
+        source: &tlv::Stream,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        let records = source.into_iter().map(|(type_no, value)| {
+            (type_no.into_inner(), AsRef::<[u8]>::as_ref(value))
+        });
+        if serializer.is_human_readable() {
+            serializer.collect_map(
+                records.map(|(type_no, value)| (type_no, value.to_hex())),
+            )
+        } else {
+            serializer.collect_map(records)
+        }
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde_with::DeserializeAs<'de, tlv::Stream> for TlvExtensions {
+    fn deserialize_as<D>(deserializer: D) -> Result<tlv::Stream, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        let records = if deserializer.is_human_readable() {
+            <BTreeMap<u64, String> as serde::Deserialize>::deserialize(
+                deserializer,
+            )?
+            .into_iter()
+            .map(|(type_no, hex)| {
+                Vec::<u8>::from_hex(&hex)
+                    .map(|value| (type_no, value))
+                    .map_err(serde::de::Error::custom)
+            })
+            .collect::<Result<BTreeMap<_, _>, _>>()?
+        } else {
+            <BTreeMap<u64, Vec<u8>> as serde::Deserialize>::deserialize(
+                deserializer,
+            )?
+        };
+        let mut stream = tlv::Stream::new();
+        for (type_no, value) in records {
+            check_extension_type(type_no).map_err(serde::de::Error::custom)?;
+            stream.insert(type_no.into(), value);
+        }
+        Ok(stream)
+    }
+}
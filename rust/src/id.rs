@@ -0,0 +1,191 @@
+// LNP/BP universal invoice library implementing LNPBP-38 standard
+// Written in 2021 by
+//     Dr. Maxim Orlovsky <orlovsky@pandoracore.com>
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the MIT License
+// along with this software.
+// If not, see <https://opensource.org/licenses/MIT>.
+
+use std::fmt::{self, Display, Formatter};
+use std::str::FromStr;
+
+use amplify::Wrapper;
+use bech32::{FromBase32, ToBase32, Variant};
+use bitcoin::hashes::hex::{self, FromHex};
+use bitcoin::hashes::{sha256, sha256t, Hash, HashEngine};
+
+use crate::Invoice;
+
+/// Tag of the SHA256 hash used for [`InvoiceId`]
+pub const INVOICE_ID_TAG: &str = "LNPBP38:invoice:id";
+
+/// Tag used for [`InvoiceId`] hash type
+pub struct InvoiceIdTag;
+
+impl sha256t::Tag for InvoiceIdTag {
+    #[inline]
+    fn engine() -> sha256::HashEngine {
+        let tag = sha256::Hash::hash(INVOICE_ID_TAG.as_bytes());
+        let mut engine = sha256::Hash::engine();
+        engine.input(&tag[..]);
+        engine.input(&tag[..]);
+        engine
+    }
+}
+
+/// Errors parsing [`InvoiceId`] string.
+#[derive(Clone, PartialEq, Eq, Debug, Display, Error, From)]
+#[display(doc_comments)]
+pub enum InvoiceIdError {
+    /// invoice id has invalid Bech32m encoding: {0}
+    #[from]
+    Bech32(bech32::Error),
+
+    /// invoice id must use Bech32m checksum
+    NonBech32m,
+
+    /// invoice id has unknown prefix; only `invid` is allowed
+    UnknownHrp,
+
+    /// invoice id has invalid hexadecimal encoding: {0}
+    #[from]
+    Hex(hex::Error),
+
+    /// invoice id must be exactly 32 bytes long
+    WrongLen,
+}
+
+/// Stable invoice identifier: tagged SHA256 hash of the invoice strict
+/// encoding with the signature removed, so signing the invoice does not
+/// change its id.
+///
+/// Displayed as a Bech32m string with `invid` prefix; parsed both from
+/// Bech32m and hexadecimal strings.
+#[derive(
+    Wrapper,
+    Copy,
+    Clone,
+    Ord,
+    PartialOrd,
+    Eq,
+    PartialEq,
+    Hash,
+    Debug,
+    From,
+    StrictEncode,
+    StrictDecode,
+)]
+#[wrapper(BorrowSlice)]
+pub struct InvoiceId(sha256t::Hash<InvoiceIdTag>);
+
+impl InvoiceId {
+    /// Bech32m prefix of the invoice ids
+    pub const HRP: &'static str = "invid";
+
+    fn from_slice(data: &[u8]) -> Result<Self, InvoiceIdError> {
+        sha256t::Hash::from_slice(data)
+            .map(InvoiceId)
+            .map_err(|_| InvoiceIdError::WrongLen)
+    }
+}
+
+impl Display for InvoiceId {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let s = bech32::encode(
+            Self::HRP,
+            self.0.into_inner().to_base32(),
+            Variant::Bech32m,
+        )
+        .map_err(|_| fmt::Error)?;
+        f.write_str(&s)
+    }
+}
+
+impl FromStr for InvoiceId {
+    type Err = InvoiceIdError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.len() == 64 {
+            if let Ok(data) = Vec::<u8>::from_hex(s) {
+                return Self::from_slice(&data);
+            }
+        }
+        let (hrp, data, variant) = bech32::decode(s)?;
+        if variant != Variant::Bech32m {
+            return Err(InvoiceIdError::NonBech32m);
+        }
+        if hrp != Self::HRP {
+            return Err(InvoiceIdError::UnknownHrp);
+        }
+        Self::from_slice(&Vec::<u8>::from_base32(&data)?)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl serde::Serialize for InvoiceId {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        if serializer.is_human_readable() {
+            serializer.collect_str(self)
+        } else {
+            serializer.serialize_bytes(&self.0[..])
+        }
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for InvoiceId {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        if deserializer.is_human_readable() {
+            let s = String::deserialize(deserializer)?;
+            InvoiceId::from_str(&s).map_err(serde::de::Error::custom)
+        } else {
+            let data = Vec::<u8>::deserialize(deserializer)?;
+            InvoiceId::from_slice(&data).map_err(serde::de::Error::custom)
+        }
+    }
+}
+
+impl Invoice {
+    /// Computes invoice id, which commits to all invoice data apart from the
+    /// signatures and the beneficiary address proof.
+    ///
+    /// # Test vector
+    ///
+    /// ```
+    /// # use std::str::FromStr;
+    /// use invoice::{Invoice, InvoiceId};
+    ///
+    /// let invoice = Invoice::from_str(
+    ///     "i1q93kqlxpesqqz0lhdkuujgxz2q490ajzwt3tg2ep2dj9ctmwt6k0e5welgdzxqeh2sgjxqcrqqy5mhvl",
+    /// )
+    /// .unwrap();
+    /// let id = invoice.invoice_id();
+    /// assert_eq!(
+    ///     id.to_string(),
+    ///     "invid1elhv0wc8zu8rgn99v3qs88rt9z565sw9jnvuz95n2qr0ep7w2p2sn36eet"
+    /// );
+    /// assert_eq!(
+    ///     id,
+    ///     InvoiceId::from_str(
+    ///         "cfeec7bb07170e344ca56441039c6b28a9aa41c594d9c116935006fc87ce5055"
+    ///     )
+    ///     .unwrap()
+    /// );
+    /// ```
+    pub fn invoice_id(&self) -> InvoiceId {
+        let mut unsigned = self.clone();
+        unsigned.invalidate_signatures();
+        InvoiceId(sha256t::Hash::hash(&unsigned.canonical_bytes()))
+    }
+}
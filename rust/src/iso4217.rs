@@ -0,0 +1,331 @@
+// LNP/BP universal invoice library implementing LNPBP-38 standard
+// Written in 2021 by
+//     Dr. Maxim Orlovsky <orlovsky@pandoracore.com>
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the MIT License
+// along with this software.
+// If not, see <https://opensource.org/licenses/MIT>.
+
+use std::fmt::{self, Display, Formatter, Write};
+use std::io;
+use std::str::FromStr;
+
+use strict_encoding::{StrictDecode, StrictEncode};
+
+/// ISO 4217 currency code.
+///
+/// Any three uppercase ASCII letters form a valid code, so codes missing from
+/// the ISO 4217 table compiled into the crate (like private `XBT`) remain
+/// representable. Use [`Iso4217::is_known`] to check the code against the
+/// table.
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Hash, Debug)]
+// TODO: Move to amplify library
+pub struct Iso4217([u8; 3]);
+
+impl Iso4217 {
+    /// Constructs currency code, checking that it consists of uppercase ASCII
+    /// letters.
+    pub fn new(code: [u8; 3]) -> Result<Self, Iso4217Error> {
+        if !code.iter().all(u8::is_ascii_uppercase) {
+            return Err(Iso4217Error::InvalidChar);
+        }
+        Ok(Iso4217(code))
+    }
+
+    /// Returns string representation of the code.
+    pub fn as_str(&self) -> &str {
+        std::str::from_utf8(&self.0)
+            .expect("Iso4217 code always consists of ASCII letters")
+    }
+
+    /// Checks whether the code is present in ISO 4217 currency table.
+    pub fn is_known(&self) -> bool {
+        self.entry().is_some()
+    }
+
+    /// Returns number of decimal digits in the currency minor unit (e.g. `0`
+    /// for JPY or `3` for BHD). Returns `None` for unknown codes and for the
+    /// codes not having minor units, like precious metals.
+    pub fn exponent(&self) -> Option<u8> {
+        self.entry().and_then(|(_, exponent, _)| *exponent)
+    }
+
+    /// Returns currency name for the codes from ISO 4217 currency table.
+    pub fn name(&self) -> Option<&'static str> {
+        self.entry().map(|(_, _, name)| *name)
+    }
+
+    fn entry(
+        &self,
+    ) -> Option<&'static (&'static str, Option<u8>, &'static str)> {
+        ISO4217_TABLE
+            .binary_search_by(|(code, _, _)| code.as_bytes().cmp(&self.0[..]))
+            .ok()
+            .map(|index| &ISO4217_TABLE[index])
+    }
+}
+
+impl Display for Iso4217 {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.write_char(self.0[0].into())?;
+        f.write_char(self.0[1].into())?;
+        f.write_char(self.0[2].into())
+    }
+}
+
+#[derive(
+    Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Hash, Debug, Display, Error,
+)]
+#[display(doc_comments)]
+pub enum Iso4217Error {
+    /// Wrong string length to parse ISO4217 data
+    WrongLen,
+
+    /// ISO4217 code must consist of uppercase ASCII letters
+    InvalidChar,
+}
+
+impl FromStr for Iso4217 {
+    type Err = Iso4217Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.len() != 3 {
+            return Err(Iso4217Error::WrongLen);
+        }
+
+        let mut inner = [0u8; 3];
+        inner.copy_from_slice(s.as_bytes());
+        Iso4217::new(inner)
+    }
+}
+
+impl StrictEncode for Iso4217 {
+    fn strict_encode<E: io::Write>(
+        &self,
+        mut e: E,
+    ) -> Result<usize, strict_encoding::Error> {
+        e.write_all(&self.0)?;
+        Ok(3)
+    }
+}
+
+impl StrictDecode for Iso4217 {
+    fn strict_decode<D: io::Read>(
+        mut d: D,
+    ) -> Result<Self, strict_encoding::Error> {
+        let mut code = [0u8; 3];
+        d.read_exact(&mut code)?;
+        Iso4217::new(code).map_err(|err| {
+            strict_encoding::Error::DataIntegrityError(err.to_string())
+        })
+    }
+}
+
+/// ISO 4217 currency table: code, minor unit exponent and currency name,
+/// sorted by code.
+const ISO4217_TABLE: &[(&str, Option<u8>, &str)] = &[
+    ("AED", Some(2), "UAE Dirham"),
+    ("AFN", Some(2), "Afghani"),
+    ("ALL", Some(2), "Lek"),
+    ("AMD", Some(2), "Armenian Dram"),
+    ("ANG", Some(2), "Netherlands Antillean Guilder"),
+    ("AOA", Some(2), "Kwanza"),
+    ("ARS", Some(2), "Argentine Peso"),
+    ("AUD", Some(2), "Australian Dollar"),
+    ("AWG", Some(2), "Aruban Florin"),
+    ("AZN", Some(2), "Azerbaijan Manat"),
+    ("BAM", Some(2), "Convertible Mark"),
+    ("BBD", Some(2), "Barbados Dollar"),
+    ("BDT", Some(2), "Taka"),
+    ("BGN", Some(2), "Bulgarian Lev"),
+    ("BHD", Some(3), "Bahraini Dinar"),
+    ("BIF", Some(0), "Burundi Franc"),
+    ("BMD", Some(2), "Bermudian Dollar"),
+    ("BND", Some(2), "Brunei Dollar"),
+    ("BOB", Some(2), "Boliviano"),
+    ("BOV", Some(2), "Mvdol"),
+    ("BRL", Some(2), "Brazilian Real"),
+    ("BSD", Some(2), "Bahamian Dollar"),
+    ("BTN", Some(2), "Ngultrum"),
+    ("BWP", Some(2), "Pula"),
+    ("BYN", Some(2), "Belarusian Ruble"),
+    ("BZD", Some(2), "Belize Dollar"),
+    ("CAD", Some(2), "Canadian Dollar"),
+    ("CDF", Some(2), "Congolese Franc"),
+    ("CHE", Some(2), "WIR Euro"),
+    ("CHF", Some(2), "Swiss Franc"),
+    ("CHW", Some(2), "WIR Franc"),
+    ("CLF", Some(4), "Unidad de Fomento"),
+    ("CLP", Some(0), "Chilean Peso"),
+    ("CNY", Some(2), "Yuan Renminbi"),
+    ("COP", Some(2), "Colombian Peso"),
+    ("COU", Some(2), "Unidad de Valor Real"),
+    ("CRC", Some(2), "Costa Rican Colon"),
+    ("CUC", Some(2), "Peso Convertible"),
+    ("CUP", Some(2), "Cuban Peso"),
+    ("CVE", Some(2), "Cabo Verde Escudo"),
+    ("CZK", Some(2), "Czech Koruna"),
+    ("DJF", Some(0), "Djibouti Franc"),
+    ("DKK", Some(2), "Danish Krone"),
+    ("DOP", Some(2), "Dominican Peso"),
+    ("DZD", Some(2), "Algerian Dinar"),
+    ("EGP", Some(2), "Egyptian Pound"),
+    ("ERN", Some(2), "Nakfa"),
+    ("ETB", Some(2), "Ethiopian Birr"),
+    ("EUR", Some(2), "Euro"),
+    ("FJD", Some(2), "Fiji Dollar"),
+    ("FKP", Some(2), "Falkland Islands Pound"),
+    ("GBP", Some(2), "Pound Sterling"),
+    ("GEL", Some(2), "Lari"),
+    ("GHS", Some(2), "Ghana Cedi"),
+    ("GIP", Some(2), "Gibraltar Pound"),
+    ("GMD", Some(2), "Dalasi"),
+    ("GNF", Some(0), "Guinean Franc"),
+    ("GTQ", Some(2), "Quetzal"),
+    ("GYD", Some(2), "Guyana Dollar"),
+    ("HKD", Some(2), "Hong Kong Dollar"),
+    ("HNL", Some(2), "Lempira"),
+    ("HTG", Some(2), "Gourde"),
+    ("HUF", Some(2), "Forint"),
+    ("IDR", Some(2), "Rupiah"),
+    ("ILS", Some(2), "New Israeli Sheqel"),
+    ("INR", Some(2), "Indian Rupee"),
+    ("IQD", Some(3), "Iraqi Dinar"),
+    ("IRR", Some(2), "Iranian Rial"),
+    ("ISK", Some(0), "Iceland Krona"),
+    ("JMD", Some(2), "Jamaican Dollar"),
+    ("JOD", Some(3), "Jordanian Dinar"),
+    ("JPY", Some(0), "Yen"),
+    ("KES", Some(2), "Kenyan Shilling"),
+    ("KGS", Some(2), "Som"),
+    ("KHR", Some(2), "Riel"),
+    ("KMF", Some(0), "Comorian Franc"),
+    ("KPW", Some(2), "North Korean Won"),
+    ("KRW", Some(0), "Won"),
+    ("KWD", Some(3), "Kuwaiti Dinar"),
+    ("KYD", Some(2), "Cayman Islands Dollar"),
+    ("KZT", Some(2), "Tenge"),
+    ("LAK", Some(2), "Lao Kip"),
+    ("LBP", Some(2), "Lebanese Pound"),
+    ("LKR", Some(2), "Sri Lanka Rupee"),
+    ("LRD", Some(2), "Liberian Dollar"),
+    ("LSL", Some(2), "Loti"),
+    ("LYD", Some(3), "Libyan Dinar"),
+    ("MAD", Some(2), "Moroccan Dirham"),
+    ("MDL", Some(2), "Moldovan Leu"),
+    ("MGA", Some(2), "Malagasy Ariary"),
+    ("MKD", Some(2), "Denar"),
+    ("MMK", Some(2), "Kyat"),
+    ("MNT", Some(2), "Tugrik"),
+    ("MOP", Some(2), "Pataca"),
+    ("MRU", Some(2), "Ouguiya"),
+    ("MUR", Some(2), "Mauritius Rupee"),
+    ("MVR", Some(2), "Rufiyaa"),
+    ("MWK", Some(2), "Malawi Kwacha"),
+    ("MXN", Some(2), "Mexican Peso"),
+    ("MXV", Some(2), "Mexican Unidad de Inversion (UDI)"),
+    ("MYR", Some(2), "Malaysian Ringgit"),
+    ("MZN", Some(2), "Mozambique Metical"),
+    ("NAD", Some(2), "Namibia Dollar"),
+    ("NGN", Some(2), "Naira"),
+    ("NIO", Some(2), "Cordoba Oro"),
+    ("NOK", Some(2), "Norwegian Krone"),
+    ("NPR", Some(2), "Nepalese Rupee"),
+    ("NZD", Some(2), "New Zealand Dollar"),
+    ("OMR", Some(3), "Rial Omani"),
+    ("PAB", Some(2), "Balboa"),
+    ("PEN", Some(2), "Sol"),
+    ("PGK", Some(2), "Kina"),
+    ("PHP", Some(2), "Philippine Peso"),
+    ("PKR", Some(2), "Pakistan Rupee"),
+    ("PLN", Some(2), "Zloty"),
+    ("PYG", Some(0), "Guarani"),
+    ("QAR", Some(2), "Qatari Rial"),
+    ("RON", Some(2), "Romanian Leu"),
+    ("RSD", Some(2), "Serbian Dinar"),
+    ("RUB", Some(2), "Russian Ruble"),
+    ("RWF", Some(0), "Rwanda Franc"),
+    ("SAR", Some(2), "Saudi Riyal"),
+    ("SBD", Some(2), "Solomon Islands Dollar"),
+    ("SCR", Some(2), "Seychelles Rupee"),
+    ("SDG", Some(2), "Sudanese Pound"),
+    ("SEK", Some(2), "Swedish Krona"),
+    ("SGD", Some(2), "Singapore Dollar"),
+    ("SHP", Some(2), "Saint Helena Pound"),
+    ("SLE", Some(2), "Leone"),
+    ("SLL", Some(2), "Leone"),
+    ("SOS", Some(2), "Somali Shilling"),
+    ("SRD", Some(2), "Surinam Dollar"),
+    ("SSP", Some(2), "South Sudanese Pound"),
+    ("STN", Some(2), "Dobra"),
+    ("SVC", Some(2), "El Salvador Colon"),
+    ("SYP", Some(2), "Syrian Pound"),
+    ("SZL", Some(2), "Lilangeni"),
+    ("THB", Some(2), "Baht"),
+    ("TJS", Some(2), "Somoni"),
+    ("TMT", Some(2), "Turkmenistan New Manat"),
+    ("TND", Some(3), "Tunisian Dinar"),
+    ("TOP", Some(2), "Pa'anga"),
+    ("TRY", Some(2), "Turkish Lira"),
+    ("TTD", Some(2), "Trinidad and Tobago Dollar"),
+    ("TWD", Some(2), "New Taiwan Dollar"),
+    ("TZS", Some(2), "Tanzanian Shilling"),
+    ("UAH", Some(2), "Hryvnia"),
+    ("UGX", Some(0), "Uganda Shilling"),
+    ("USD", Some(2), "US Dollar"),
+    ("USN", Some(2), "US Dollar (Next day)"),
+    ("UYI", Some(0), "Uruguay Peso en Unidades Indexadas (UI)"),
+    ("UYU", Some(2), "Peso Uruguayo"),
+    ("UYW", Some(4), "Unidad Previsional"),
+    ("UZS", Some(2), "Uzbekistan Sum"),
+    ("VED", Some(2), "Bolivar Soberano"),
+    ("VES", Some(2), "Bolivar Soberano"),
+    ("VND", Some(0), "Dong"),
+    ("VUV", Some(0), "Vatu"),
+    ("WST", Some(2), "Tala"),
+    ("XAF", Some(0), "CFA Franc BEAC"),
+    ("XAG", None, "Silver"),
+    ("XAU", None, "Gold"),
+    ("XBA", None, "European Composite Unit (EURCO)"),
+    ("XBB", None, "European Monetary Unit (E.M.U.-6)"),
+    ("XBC", None, "European Unit of Account 9 (E.U.A.-9)"),
+    ("XBD", None, "European Unit of Account 17 (E.U.A.-17)"),
+    ("XCD", Some(2), "East Caribbean Dollar"),
+    ("XCG", Some(2), "Caribbean Guilder"),
+    ("XDR", None, "SDR (Special Drawing Right)"),
+    ("XOF", Some(0), "CFA Franc BCEAO"),
+    ("XPD", None, "Palladium"),
+    ("XPF", Some(0), "CFP Franc"),
+    ("XPT", None, "Platinum"),
+    ("XSU", None, "Sucre"),
+    ("XTS", None, "Code reserved for testing purposes"),
+    ("XUA", None, "ADB Unit of Account"),
+    ("XXX", None, "No currency"),
+    ("YER", Some(2), "Yemeni Rial"),
+    ("ZAR", Some(2), "Rand"),
+    ("ZMW", Some(2), "Zambian Kwacha"),
+    ("ZWG", Some(2), "Zimbabwe Gold"),
+    ("ZWL", Some(2), "Zimbabwe Dollar"),
+];
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn table_is_sorted() {
+        for pair in ISO4217_TABLE.windows(2) {
+            assert!(pair[0].0 < pair[1].0, "{} >= {}", pair[0].0, pair[1].0);
+        }
+        for (code, exponent, _) in ISO4217_TABLE {
+            let iso4217 = Iso4217::from_str(code).unwrap();
+            assert!(iso4217.is_known(), "{}", code);
+            assert_eq!(iso4217.exponent(), *exponent, "{}", code);
+        }
+    }
+}
@@ -33,11 +33,81 @@ extern crate strict_encoding;
 #[macro_use]
 extern crate serde_with;
 #[cfg(feature = "serde")]
+#[macro_use]
 extern crate serde_crate as serde;
 
 mod base;
+pub mod bip21;
+mod builder;
+mod extension;
+mod id;
+mod iso4217;
+mod payment;
+mod payment_code;
+mod proof;
+mod qr;
+mod report;
+pub mod seal;
+mod signed;
+mod silent_payment;
+mod tracking;
+mod zip;
 
+#[cfg(feature = "bolt12")]
+pub mod bolt12;
+#[cfg(feature = "cbor")]
+pub mod cbor;
 #[cfg(feature = "bolt11")]
 mod converter;
+#[cfg(feature = "elements")]
+mod liquid;
+#[cfg(feature = "lnurl")]
+pub mod lnurl;
+#[cfg(feature = "musig2")]
+pub mod musig2;
+#[cfg(feature = "ur")]
+pub mod ur;
 
 pub use base::*;
+pub use bip21::Bip21Error;
+#[cfg(feature = "bolt12")]
+pub use bolt12::{LossyConversion, LostField, Offer, OfferError};
+pub use builder::{BuilderError, BuilderWarning, InvoiceBuilder};
+#[cfg(feature = "cbor")]
+pub use cbor::CborError;
+#[cfg(feature = "bolt11")]
+pub use converter::{InvoiceError, DEFAULT_MIN_FINAL_CLTV_EXPIRY};
+pub use extension::{ExtensionError, InvoiceExtension, MAX_CORE_TLV_TYPE};
+pub use id::{InvoiceId, InvoiceIdError, InvoiceIdTag, INVOICE_ID_TAG};
+pub use iso4217::{Iso4217, Iso4217Error};
+#[cfg(feature = "elements")]
+pub use liquid::{LiquidAddress, LiquidAddressError, LiquidNetwork};
+#[cfg(feature = "lnurl")]
+pub use lnurl::{LnurlError, PayRequest};
+#[cfg(feature = "musig2")]
+pub use musig2::{
+    MerchantKeyAggregation, Musig2Error, Musig2Session, PartialSignature,
+    PublicNonce, SecretNonce,
+};
+pub use payment::{
+    apply_psbt_template, validate_psbt_beneficiary, DeriveError, PaymentMatch,
+    PolicyViolation, PsbtTemplateError, ScriptError, REFERENCE_TWEAK_TAG,
+};
+pub use payment_code::{PaymentCode, PaymentCodeError};
+pub use proof::{AddressProof, PaymentProof, ProofError};
+pub use qr::{
+    alphanumeric_capacity, QrCapacity, QrErrorCorrection, QR_MAX_VERSION,
+};
+pub use report::{
+    BeneficiaryKind, BeneficiaryReport, InvoiceReport, SignatureStatus,
+};
+#[allow(deprecated)]
+pub use seal::{
+    blind_utxo, verify_blind_utxo, BlindedUtxo, WitnessVoutSeal,
+    WitnessVoutSealError,
+};
+pub use signed::{SignedInvoice, SignedInvoiceError};
+pub use silent_payment::{SilentPaymentCode, SilentPaymentError};
+pub use tracking::{TrackingBeneficiary, TrackingError};
+#[cfg(feature = "ur")]
+pub use ur::UrError;
@@ -0,0 +1,513 @@
+// LNP/BP universal invoice library implementing LNPBP-38 standard
+// Written in 2021 by
+//     Dr. Maxim Orlovsky <orlovsky@pandoracore.com>
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the MIT License
+// along with this software.
+// If not, see <https://opensource.org/licenses/MIT>.
+
+//! Liquid addresses, including confidential addresses carrying the blinding
+//! key which the payer uses to hide the payment amount and asset.
+//!
+//! Addresses follow the encoding of Elements: explicit addresses use
+//! base58check or bech32(m), while confidential addresses use base58check
+//! with the blinding prefix or blech32(m), a variant of bech32 with a longer
+//! checksum suitable for the longer confidential data.
+//!
+//! The addresses are encoded by this module itself rather than with
+//! `elements::Address`, so the `elements` feature adds no dependencies; the
+//! codec is checked against the addresses produced by Elements. Liquid
+//! assets are likewise identified with [`lnpbp::chain::AssetId`] rather than
+//! `elements::AssetId`.
+
+use std::convert::TryFrom;
+use std::fmt::{self, Display, Formatter};
+use std::io;
+use std::str::FromStr;
+
+use bitcoin::bech32::{self, u5, FromBase32, ToBase32, Variant};
+use bitcoin::hashes::Hash;
+use bitcoin::secp256k1::{self, PublicKey};
+use bitcoin::util::address::{Payload, WitnessVersion};
+use bitcoin::util::base58;
+use bitcoin::{PubkeyHash, Script, ScriptHash};
+
+use strict_encoding::{StrictDecode, StrictEncode};
+
+use crate::Network;
+
+/// Errors parsing Liquid addresses.
+#[derive(Clone, PartialEq, Eq, Debug, Display, Error, From)]
+#[display(doc_comments)]
+pub enum LiquidAddressError {
+    /// address has invalid base58check encoding: {0}
+    #[from]
+    Base58(base58::Error),
+
+    /// address has invalid bech32 encoding: {0}
+    #[from]
+    Bech32(bech32::Error),
+
+    /// address has invalid blech32 encoding
+    InvalidBlech32,
+
+    /// address has invalid blech32 checksum
+    InvalidChecksum,
+
+    /// address prefix does not belong to a known Liquid network
+    UnknownPrefix,
+
+    /// address has invalid length of base58 data
+    WrongLen,
+
+    /// address has invalid witness version {0}
+    InvalidWitnessVersion(u8),
+
+    /// address has invalid witness program length
+    InvalidWitnessProgram,
+
+    /// address checksum variant does not match its witness version
+    WrongVariant,
+
+    /// address contains invalid blinding public key
+    #[from(secp256k1::Error)]
+    InvalidBlindingKey,
+}
+
+/// Network of a Liquid address, defining the address prefixes.
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Hash, Debug, Display)]
+pub enum LiquidNetwork {
+    #[display("liquid")]
+    Liquid,
+
+    #[display("liquidtestnet")]
+    LiquidTestnet,
+
+    /// Default Elements regtest network
+    #[display("elementsregtest")]
+    ElementsRegtest,
+}
+
+impl LiquidNetwork {
+    const ALL: [LiquidNetwork; 3] = [
+        LiquidNetwork::Liquid,
+        LiquidNetwork::LiquidTestnet,
+        LiquidNetwork::ElementsRegtest,
+    ];
+
+    /// Base58 version byte of P2PKH addresses
+    pub const fn p2pkh_prefix(self) -> u8 {
+        match self {
+            LiquidNetwork::Liquid => 57,
+            LiquidNetwork::LiquidTestnet => 36,
+            LiquidNetwork::ElementsRegtest => 235,
+        }
+    }
+
+    /// Base58 version byte of P2SH addresses
+    pub const fn p2sh_prefix(self) -> u8 {
+        match self {
+            LiquidNetwork::Liquid => 39,
+            LiquidNetwork::LiquidTestnet => 19,
+            LiquidNetwork::ElementsRegtest => 75,
+        }
+    }
+
+    /// Base58 version byte preceding the P2PKH or P2SH version of
+    /// confidential addresses
+    pub const fn blinded_prefix(self) -> u8 {
+        match self {
+            LiquidNetwork::Liquid => 12,
+            LiquidNetwork::LiquidTestnet => 23,
+            LiquidNetwork::ElementsRegtest => 4,
+        }
+    }
+
+    /// Bech32 HRP of explicit segwit addresses
+    pub const fn bech_hrp(self) -> &'static str {
+        match self {
+            LiquidNetwork::Liquid => "ex",
+            LiquidNetwork::LiquidTestnet => "tex",
+            LiquidNetwork::ElementsRegtest => "ert",
+        }
+    }
+
+    /// Blech32 HRP of confidential segwit addresses
+    pub const fn blech_hrp(self) -> &'static str {
+        match self {
+            LiquidNetwork::Liquid => "lq",
+            LiquidNetwork::LiquidTestnet => "tlq",
+            LiquidNetwork::ElementsRegtest => "el",
+        }
+    }
+
+    /// Checks whether the addresses of the network may be used by invoices
+    /// issued for the given network. Elements regtest addresses match
+    /// regtest networks with a custom genesis block.
+    pub fn matches_network(self, network: &Network) -> bool {
+        matches!(
+            (self, network),
+            (LiquidNetwork::Liquid, Network::LiquidV1)
+                | (LiquidNetwork::LiquidTestnet, Network::LiquidTestnet)
+                | (LiquidNetwork::ElementsRegtest, Network::RegtestCustom(_))
+        )
+    }
+}
+
+impl StrictEncode for LiquidNetwork {
+    fn strict_encode<E: io::Write>(
+        &self,
+        e: E,
+    ) -> Result<usize, strict_encoding::Error> {
+        match self {
+            LiquidNetwork::Liquid => 0u8,
+            LiquidNetwork::LiquidTestnet => 1u8,
+            LiquidNetwork::ElementsRegtest => 2u8,
+        }
+        .strict_encode(e)
+    }
+}
+
+impl StrictDecode for LiquidNetwork {
+    fn strict_decode<D: io::Read>(
+        d: D,
+    ) -> Result<Self, strict_encoding::Error> {
+        Ok(match u8::strict_decode(d)? {
+            0 => LiquidNetwork::Liquid,
+            1 => LiquidNetwork::LiquidTestnet,
+            2 => LiquidNetwork::ElementsRegtest,
+            tag => {
+                return Err(strict_encoding::Error::EnumValueNotKnown(
+                    "LiquidNetwork",
+                    tag as usize,
+                ))
+            }
+        })
+    }
+}
+
+/// Liquid address, which is confidential if it has a blinding public key.
+#[derive(Clone, Ord, PartialOrd, Eq, PartialEq, Hash, Debug)]
+pub struct LiquidAddress {
+    pub network: LiquidNetwork,
+    /// Output script data of the address
+    pub payload: Payload,
+    /// Key used by the payer for blinding the output amount and asset
+    pub blinding_pubkey: Option<PublicKey>,
+}
+
+/// Encodes the address as its network, output script and optional blinding
+/// key.
+impl StrictEncode for LiquidAddress {
+    fn strict_encode<E: io::Write>(
+        &self,
+        mut e: E,
+    ) -> Result<usize, strict_encoding::Error> {
+        Ok(self.network.strict_encode(&mut e)?
+            + self.script_pubkey().strict_encode(&mut e)?
+            + self.blinding_pubkey.strict_encode(&mut e)?)
+    }
+}
+
+impl StrictDecode for LiquidAddress {
+    fn strict_decode<D: io::Read>(
+        mut d: D,
+    ) -> Result<Self, strict_encoding::Error> {
+        let network = LiquidNetwork::strict_decode(&mut d)?;
+        let script = Script::strict_decode(&mut d)?;
+        let payload = Payload::from_script(&script).map_err(|_| {
+            strict_encoding::Error::DataIntegrityError(s!(
+                "Liquid address script is not a standard address script"
+            ))
+        })?;
+        Ok(LiquidAddress {
+            network,
+            payload,
+            blinding_pubkey: Option::<PublicKey>::strict_decode(&mut d)?,
+        })
+    }
+}
+
+impl LiquidAddress {
+    /// Detects whether the address is confidential.
+    pub fn is_blinded(&self) -> bool {
+        self.blinding_pubkey.is_some()
+    }
+
+    /// Returns explicit address with the same output script.
+    pub fn to_unconfidential(&self) -> LiquidAddress {
+        LiquidAddress {
+            blinding_pubkey: None,
+            ..self.clone()
+        }
+    }
+
+    /// Returns output script of the address.
+    pub fn script_pubkey(&self) -> Script {
+        self.payload.script_pubkey()
+    }
+
+    fn parse_segwit(
+        network: LiquidNetwork,
+        data: &[u5],
+        variant: Variant,
+        blinded: bool,
+    ) -> Result<LiquidAddress, LiquidAddressError> {
+        let (version, data) = data
+            .split_first()
+            .ok_or(LiquidAddressError::InvalidWitnessProgram)?;
+        let version = WitnessVersion::try_from(*version).map_err(|_| {
+            LiquidAddressError::InvalidWitnessVersion(version.to_u8())
+        })?;
+        let expected = match version {
+            WitnessVersion::V0 => Variant::Bech32,
+            _ => Variant::Bech32m,
+        };
+        if variant != expected {
+            return Err(LiquidAddressError::WrongVariant);
+        }
+        let data = Vec::<u8>::from_base32(data)?;
+        let (blinding_pubkey, program) = if blinded {
+            if data.len() < 33 {
+                return Err(LiquidAddressError::InvalidWitnessProgram);
+            }
+            let (key, program) = data.split_at(33);
+            (Some(PublicKey::from_slice(key)?), program.to_vec())
+        } else {
+            (None, data)
+        };
+        if program.len() < 2
+            || program.len() > 40
+            || (version == WitnessVersion::V0
+                && program.len() != 20
+                && program.len() != 32)
+        {
+            return Err(LiquidAddressError::InvalidWitnessProgram);
+        }
+        Ok(LiquidAddress {
+            network,
+            payload: Payload::WitnessProgram { version, program },
+            blinding_pubkey,
+        })
+    }
+
+    fn parse_base58(s: &str) -> Result<LiquidAddress, LiquidAddressError> {
+        let data = base58::from_check(s)?;
+        let (blinded_prefix, prefix, blinding_pubkey, hash) = match data.len() {
+            21 => (None, data[0], None, &data[1..]),
+            55 => (
+                Some(data[0]),
+                data[1],
+                Some(PublicKey::from_slice(&data[2..35])?),
+                &data[35..],
+            ),
+            _ => return Err(LiquidAddressError::WrongLen),
+        };
+        let network = LiquidNetwork::ALL
+            .iter()
+            .copied()
+            .find(|network| {
+                (prefix == network.p2pkh_prefix()
+                    || prefix == network.p2sh_prefix())
+                    && blinded_prefix.is_none_or(|blinded| {
+                        blinded == network.blinded_prefix()
+                    })
+            })
+            .ok_or(LiquidAddressError::UnknownPrefix)?;
+        let payload = if prefix == network.p2pkh_prefix() {
+            Payload::PubkeyHash(PubkeyHash::from_slice(hash).expect("20 bytes"))
+        } else {
+            Payload::ScriptHash(ScriptHash::from_slice(hash).expect("20 bytes"))
+        };
+        Ok(LiquidAddress {
+            network,
+            payload,
+            blinding_pubkey,
+        })
+    }
+}
+
+/// Displays confidential addresses as blech32(m) or base58 strings with the
+/// blinding prefix, and explicit addresses as bech32(m) or base58 strings.
+impl Display for LiquidAddress {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let (prefix, hash) = match &self.payload {
+            Payload::WitnessProgram { version, program } => {
+                let variant = match version {
+                    WitnessVersion::V0 => Variant::Bech32,
+                    _ => Variant::Bech32m,
+                };
+                let mut data = vec![version.to_num()];
+                let s = match self.blinding_pubkey {
+                    Some(key) => {
+                        let mut payload = key.serialize().to_vec();
+                        payload.extend_from_slice(program);
+                        data.extend(
+                            payload.to_base32().iter().map(|v| v.to_u8()),
+                        );
+                        blech32_encode(self.network.blech_hrp(), &data, variant)
+                    }
+                    None => {
+                        let mut data = vec![u5::try_from_u8(data[0])
+                            .expect("witness version fits 5 bits")];
+                        data.extend(program.to_base32());
+                        bech32::encode(self.network.bech_hrp(), data, variant)
+                            .expect("valid bech32 HRP")
+                    }
+                };
+                return f.write_str(&s);
+            }
+            Payload::PubkeyHash(hash) => {
+                (self.network.p2pkh_prefix(), hash.to_vec())
+            }
+            Payload::ScriptHash(hash) => {
+                (self.network.p2sh_prefix(), hash.to_vec())
+            }
+        };
+        let mut data = Vec::with_capacity(55);
+        if let Some(key) = self.blinding_pubkey {
+            data.push(self.network.blinded_prefix());
+            data.push(prefix);
+            data.extend_from_slice(&key.serialize());
+        } else {
+            data.push(prefix);
+        }
+        data.extend(hash);
+        f.write_str(&base58::check_encode_slice(&data))
+    }
+}
+
+/// Parses Liquid address of any of [`LiquidNetwork`] networks.
+impl FromStr for LiquidAddress {
+    type Err = LiquidAddressError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let lower = s.to_lowercase();
+        let has_hrp = |hrp: &str| {
+            lower
+                .strip_prefix(hrp)
+                .is_some_and(|rest| rest.starts_with('1'))
+        };
+        for network in LiquidNetwork::ALL {
+            if has_hrp(network.blech_hrp()) {
+                let (_, data, variant) = blech32_decode(s)?;
+                let data = data
+                    .into_iter()
+                    .map(|v| u5::try_from_u8(v).expect("5-bit value"))
+                    .collect::<Vec<_>>();
+                return LiquidAddress::parse_segwit(
+                    network, &data, variant, true,
+                );
+            }
+            if has_hrp(network.bech_hrp()) {
+                let (_, data, variant) = bech32::decode(s)?;
+                return LiquidAddress::parse_segwit(
+                    network, &data, variant, false,
+                );
+            }
+        }
+        LiquidAddress::parse_base58(s)
+    }
+}
+
+// Blech32 differs from bech32 by the generator of the checksum code, which
+// has a 12-character checksum instead of 6 characters.
+
+const CHARSET: &[u8; 32] = b"qpzry9x8gf2tvdw0s3jn54khce6mua7l";
+
+const BLECH32_CONST: u64 = 1;
+const BLECH32M_CONST: u64 = 0x0455_972a_3350_f7a1;
+const BLECH32_CHECKSUM_LEN: usize = 12;
+
+fn blech32_polymod(values: impl IntoIterator<Item = u8>) -> u64 {
+    const GEN: [u64; 5] = [
+        0x007d_52fb_a40b_d886,
+        0x005e_8dbf_1a03_950c,
+        0x001c_3a3c_7407_2a18,
+        0x0038_5d72_fa0e_5139,
+        0x0070_93e5_a608_865b,
+    ];
+    let mut chk = 1u64;
+    for value in values {
+        let top = chk >> 55;
+        chk = (chk & 0x007f_ffff_ffff_ffff) << 5 ^ value as u64;
+        for (no, gen) in GEN.iter().enumerate() {
+            if (top >> no) & 1 == 1 {
+                chk ^= gen;
+            }
+        }
+    }
+    chk
+}
+
+fn hrp_expand(hrp: &str) -> impl Iterator<Item = u8> + '_ {
+    hrp.bytes()
+        .map(|b| b >> 5)
+        .chain([0])
+        .chain(hrp.bytes().map(|b| b & 0x1f))
+}
+
+fn blech32_const(variant: Variant) -> u64 {
+    match variant {
+        Variant::Bech32 => BLECH32_CONST,
+        Variant::Bech32m => BLECH32M_CONST,
+    }
+}
+
+/// Encodes 5-bit values with blech32 (for [`Variant::Bech32`]) or blech32m
+/// (for [`Variant::Bech32m`]) checksum.
+fn blech32_encode(hrp: &str, data: &[u8], variant: Variant) -> String {
+    let checksum = blech32_polymod(
+        hrp_expand(hrp)
+            .chain(data.iter().copied())
+            .chain([0; BLECH32_CHECKSUM_LEN]),
+    ) ^ blech32_const(variant);
+    let mut s = String::with_capacity(
+        hrp.len() + 1 + data.len() + BLECH32_CHECKSUM_LEN,
+    );
+    s.push_str(hrp);
+    s.push('1');
+    s.extend(data.iter().map(|v| CHARSET[*v as usize] as char));
+    s.extend((0..BLECH32_CHECKSUM_LEN).map(|no| {
+        let v = (checksum >> (5 * (BLECH32_CHECKSUM_LEN - 1 - no))) & 0x1f;
+        CHARSET[v as usize] as char
+    }));
+    s
+}
+
+/// Decodes blech32(m) string into its HRP, 5-bit values without checksum
+/// and checksum variant.
+fn blech32_decode(
+    s: &str,
+) -> Result<(String, Vec<u8>, Variant), LiquidAddressError> {
+    if s.chars().any(|c| c.is_ascii_lowercase())
+        && s.chars().any(|c| c.is_ascii_uppercase())
+    {
+        return Err(LiquidAddressError::InvalidBlech32);
+    }
+    let s = s.to_lowercase();
+    let (hrp, data) = s
+        .rsplit_once('1')
+        .filter(|(hrp, data)| {
+            !hrp.is_empty() && data.len() >= BLECH32_CHECKSUM_LEN
+        })
+        .ok_or(LiquidAddressError::InvalidBlech32)?;
+    let data = data
+        .bytes()
+        .map(|c| CHARSET.iter().position(|v| *v == c).map(|v| v as u8))
+        .collect::<Option<Vec<_>>>()
+        .ok_or(LiquidAddressError::InvalidBlech32)?;
+    let variant =
+        match blech32_polymod(hrp_expand(hrp).chain(data.iter().copied())) {
+            BLECH32_CONST => Variant::Bech32,
+            BLECH32M_CONST => Variant::Bech32m,
+            _ => return Err(LiquidAddressError::InvalidChecksum),
+        };
+    let len = data.len() - BLECH32_CHECKSUM_LEN;
+    Ok((hrp.to_owned(), data[..len].to_vec(), variant))
+}
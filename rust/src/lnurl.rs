@@ -0,0 +1,189 @@
+// LNP/BP universal invoice library implementing LNPBP-38 standard
+// Written in 2021 by
+//     Dr. Maxim Orlovsky <orlovsky@pandoracore.com>
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the MIT License
+// along with this software.
+// If not, see <https://opensource.org/licenses/MIT>.
+
+//! Interoperability with LNURL-pay (LUD-06) services.
+//!
+//! The module converts data only and does no networking: the payer wallet
+//! fetches the pay request and the callback BOLT-11 invoice by itself and
+//! combines them into an invoice with [`PayRequest::to_invoice`].
+
+use std::convert::TryFrom;
+
+use bitcoin::hashes::{sha256, Hash};
+use lightning_invoice::InvoiceDescription;
+
+use crate::{AmountExt, Invoice, InvoiceError};
+
+/// Tag of LNURL-pay responses
+pub const PAY_REQUEST_TAG: &str = "payRequest";
+
+/// Metadata entry type holding payment description
+pub const METADATA_TEXT: &str = "text/plain";
+
+/// Metadata entry type holding receiver internet identifier (LUD-16)
+pub const METADATA_IDENTIFIER: &str = "text/identifier";
+
+/// Errors converting LNURL-pay data.
+#[derive(Clone, Eq, PartialEq, Debug, Display, Error, From)]
+#[display(doc_comments)]
+pub enum LnurlError {
+    /// invalid LNURL-pay response: {0}
+    Json(String),
+
+    /// LNURL response has tag `{0}` instead of `payRequest`
+    WrongTag(String),
+
+    /// LNURL-pay metadata is not a JSON array of string pairs or misses
+    /// `text/plain` entry
+    InvalidMetadata,
+
+    /// callback invoice description hash does not commit to the LNURL-pay
+    /// metadata
+    DescriptionHash,
+
+    /// callback invoice amount {amount} msat is outside of {min}..={max}
+    /// msat range accepted by the service
+    AmountOutOfRange { amount: u64, min: u64, max: u64 },
+
+    /// invalid callback invoice: {0}
+    #[from]
+    Bolt11(InvoiceError),
+}
+
+/// Decoded LNURL-pay response (LUD-06).
+#[derive(Clone, Eq, PartialEq, Hash, Debug, Serialize, Deserialize)]
+#[serde(crate = "serde_crate", rename_all = "camelCase")]
+pub struct PayRequest {
+    /// URL which must be called to get the BOLT-11 invoice
+    pub callback: String,
+    /// Maximal amount accepted by the service, in millisatoshis
+    pub max_sendable: u64,
+    /// Minimal amount accepted by the service, in millisatoshis
+    pub min_sendable: u64,
+    /// JSON-encoded array of `[<type>, <content>]` pairs
+    pub metadata: String,
+    pub tag: String,
+    /// Maximal length of payer comment (LUD-12)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub comment_allowed: Option<u16>,
+}
+
+impl PayRequest {
+    /// Parses LNURL-pay response JSON, checking its tag.
+    pub fn from_json(json: &str) -> Result<PayRequest, LnurlError> {
+        let request: PayRequest = serde_json::from_str(json)
+            .map_err(|err| LnurlError::Json(err.to_string()))?;
+        if request.tag != PAY_REQUEST_TAG {
+            return Err(LnurlError::WrongTag(request.tag));
+        }
+        Ok(request)
+    }
+
+    /// Returns metadata entries as pairs of type and content.
+    pub fn metadata_entries(
+        &self,
+    ) -> Result<Vec<(String, String)>, LnurlError> {
+        let entries: Vec<(String, String)> =
+            serde_json::from_str(&self.metadata)
+                .map_err(|_| LnurlError::InvalidMetadata)?;
+        if !entries.iter().any(|(kind, _)| kind == METADATA_TEXT) {
+            return Err(LnurlError::InvalidMetadata);
+        }
+        Ok(entries)
+    }
+
+    /// Returns content of the first metadata entry of a given type.
+    pub fn metadata_entry(&self, kind: &str) -> Option<String> {
+        self.metadata_entries()
+            .ok()?
+            .into_iter()
+            .find(|(entry_kind, _)| entry_kind == kind)
+            .map(|(_, content)| content)
+    }
+
+    /// Returns invoice amount corresponding to the range of amounts accepted
+    /// by the service: a specific amount if the range has a single value and
+    /// [`AmountExt::Any`] otherwise.
+    pub fn amount(&self) -> AmountExt {
+        if self.min_sendable != self.max_sendable {
+            return AmountExt::Any;
+        }
+        match self.min_sendable % 1000 {
+            0 => AmountExt::Normal(self.min_sendable / 1000),
+            frac => AmountExt::Milli(self.min_sendable / 1000, frac as u16),
+        }
+    }
+
+    /// Checks whether the service accepts the amount in millisatoshis.
+    pub fn accepts(&self, msat: u64) -> bool {
+        (self.min_sendable..=self.max_sendable).contains(&msat)
+    }
+
+    /// Constructs invoice from the BOLT-11 invoice returned by the callback,
+    /// checking that the invoice commits to the metadata and pays an amount
+    /// accepted by the service. Invoice purpose is taken from `text/plain`
+    /// and merchant from `text/identifier` metadata entries.
+    pub fn to_invoice(
+        &self,
+        bolt11: lightning_invoice::Invoice,
+    ) -> Result<Invoice, LnurlError> {
+        let entries = self.metadata_entries()?;
+        let hash = sha256::Hash::hash(self.metadata.as_bytes());
+        match bolt11.description() {
+            InvoiceDescription::Hash(committed) if committed.0 == hash => {}
+            _ => return Err(LnurlError::DescriptionHash),
+        }
+        let amount = bolt11.amount_milli_satoshis();
+        if let Some(amount) = amount {
+            if !self.accepts(amount) {
+                return Err(LnurlError::AmountOutOfRange {
+                    amount,
+                    min: self.min_sendable,
+                    max: self.max_sendable,
+                });
+            }
+        }
+
+        let mut invoice = Invoice::try_from(bolt11)?;
+        if amount.is_none() {
+            invoice.set_amount_unchecked(self.amount());
+        }
+        for (kind, content) in entries {
+            match kind.as_str() {
+                METADATA_TEXT if invoice.purpose().is_none() => {
+                    invoice.set_purpose(content);
+                }
+                METADATA_IDENTIFIER if invoice.merchant().is_none() => {
+                    invoice.set_merchant(content);
+                }
+                _ => {}
+            }
+        }
+        Ok(invoice)
+    }
+}
+
+impl Invoice {
+    /// Produces LNURL-pay metadata JSON describing the invoice: `text/plain`
+    /// entry with the invoice purpose (empty if absent) and
+    /// `text/identifier` entry with the merchant, if any.
+    pub fn to_lnurlpay_metadata(&self) -> String {
+        let mut entries =
+            vec![(METADATA_TEXT, self.purpose().clone().unwrap_or_default())];
+        if let Some(merchant) = self.merchant() {
+            entries.push((METADATA_IDENTIFIER, merchant.clone()));
+        }
+        serde_json::to_string(&entries)
+            .expect("string pairs are always serializable")
+    }
+}
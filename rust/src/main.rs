@@ -11,22 +11,39 @@
 // along with this software.
 // If not, see <https://opensource.org/licenses/MIT>.
 
+#[macro_use]
+extern crate amplify;
 #[macro_use]
 extern crate clap;
 extern crate serde_crate as serde;
 
 use clap::Parser;
 use serde::Serialize;
+use std::convert::TryFrom;
+use std::env;
 use std::fmt::{self, Debug, Display, Formatter};
+use std::fs;
 use std::io::{self, Read};
-use std::str::FromStr;
+use std::path::{Path, PathBuf};
+use std::process;
+use std::str::{self, FromStr};
 
+use amplify::Wrapper;
 use base58::{FromBase58, ToBase58};
 use bitcoin::hashes::hex::{self, FromHex, ToHex};
-use bitcoin::OutPoint;
-use bp::seals::txout::blind::RevealedSeal;
-use invoice::{Beneficiary, Invoice};
-use lnpbp::chain::AssetId;
+use bitcoin::hashes::Hash;
+use bitcoin::secp256k1;
+use bitcoin::secp256k1::rand::thread_rng;
+use bitcoin::{OutPoint, PrivateKey};
+use bp::seals::txout::blind::ConcealedSeal;
+use chrono::{DateTime, Duration, Utc};
+use invoice::{
+    contract_id_to_asset_id, seal, AmountExt, Beneficiary, BuilderError,
+    ConsignmentEndpoint, CurrencyData, DeriveError, Iface, Invoice,
+    InvoiceBuilder, InvoiceReport, Network, Quantity, Recurrent,
+    SignatureError, SigningError,
+};
+use lnpbp::chain::{AssetId, Chain};
 use strict_encoding::{StrictDecode, StrictEncode};
 
 #[derive(Parser, Clone, Debug)]
@@ -35,7 +52,14 @@ use strict_encoding::{StrictDecode, StrictEncode};
     bin_name = "invoice",
     author,
     version,
-    about = "Command-line tool for working with LNP/BP invoicing"
+    about = "Command-line tool for working with LNP/BP invoicing",
+    after_help = "EXIT CODES:\n    \
+                  0   success\n    \
+                  1   invoice signature or blinded UTXO check failed\n    \
+                  64  incorrect arguments or unsupported data format\n    \
+                  65  malformed or invalid input data\n    \
+                  70  data can't be encoded in the requested format\n    \
+                  74  input/output error"
 )]
 pub struct Opts {
     /// Command to execute
@@ -44,6 +68,7 @@ pub struct Opts {
 }
 
 #[derive(Subcommand, Clone, PartialEq, Eq, Debug)]
+#[allow(clippy::large_enum_variant)]
 pub enum Command {
     /// Creates new invoice
     Create {
@@ -55,15 +80,84 @@ pub enum Command {
 
         /// Asset, if not Bitcoin
         asset: Option<rgb::ContractId>,
+
+        /// RGB contract interface expected by the invoice, in form of
+        /// `<name>#<32-byte hex id>`
+        #[clap(long, requires = "asset")]
+        iface: Option<Iface>,
+
+        /// Merchant name
+        #[clap(long)]
+        merchant: Option<String>,
+
+        /// Purpose of the payment
+        #[clap(long)]
+        purpose: Option<String>,
+
+        /// Invoice expiry, either as RFC 3339 date and time or as a duration
+        /// from now (like `30m`, `2h` or `7d`)
+        #[clap(long, value_parser = parse_expiry)]
+        expiry: Option<DateTime<Utc>>,
+
+        /// Payment recurrence in form of
+        /// `<seconds|days|weeks|months|years>:<n>`
+        #[clap(long, value_parser = parse_recurrent)]
+        recurrent: Option<Recurrent>,
+
+        /// Maximal number of recurrent payments
+        #[clap(long, requires = "recurrent")]
+        recurrence_limit: Option<u32>,
+
+        /// Quantity of items in form of `min[,max][,default]`; absent or
+        /// empty `max` means no upper limit, absent `default` means one item
+        /// within the range
+        #[clap(long, value_parser = parse_quantity)]
+        quantity: Option<Quantity>,
+
+        /// Minimal price in fiat currency, in form of
+        /// `<amount> <ISO4217 code> [<price provider>]`; may be repeated
+        #[clap(long = "currency")]
+        currencies: Vec<CurrencyData>,
+
+        /// Network the invoice is issued for: mainnet, testnet, signet,
+        /// regtest or liquidv1. If not given, it is detected from the
+        /// beneficiary address or the native asset, defaulting to mainnet
+        #[clap(long, value_parser = parse_network)]
+        network: Option<Network>,
+
+        /// Do not put network information into the invoice
+        #[clap(long, conflicts_with = "network")]
+        omit_network: bool,
+
+        /// Alternative beneficiary; may be repeated
+        #[clap(long = "alt-beneficiary")]
+        alt_beneficiaries: Vec<Beneficiary>,
+
+        /// Consignment endpoint; may be repeated
+        #[clap(long = "endpoint")]
+        endpoints: Vec<ConsignmentEndpoint>,
+
+        /// Formatting for the output invoice data
+        #[clap(short, long, default_value = "bech32")]
+        output: Format,
     },
 
     /// Converting between different representations of invoice data
     Convert {
         /// Invoice data; if none are given reads from STDIN
+        #[clap(conflicts_with = "in-file")]
         invoice: Option<String>,
 
+        /// Read invoice data from a file instead of STDIN
+        #[clap(long)]
+        in_file: Option<PathBuf>,
+
+        /// Write converted data to a file instead of STDOUT
+        #[clap(long)]
+        out_file: Option<PathBuf>,
+
         /// Formatting of the input invoice data
-        #[clap(short, long, default_value = "bech32")]
+        #[clap(short, long, default_value = "auto")]
         input: Format,
 
         /// Formatting for the output invoice data
@@ -71,16 +165,99 @@ pub enum Command {
         output: Format,
     },
 
+    /// Signs invoice with a merchant private key, printing signed invoice
+    Sign {
+        /// Invoice in Bech32 format; if none is given reads from STDIN
+        invoice: Option<String>,
+
+        /// Private key to sign with, in WIF or hexadecimal format. The key
+        /// leaks into shell history and process lists, so prefer
+        /// `--key-file` or `--key-env` outside of tests and scripts
+        #[clap(
+            long,
+            required_unless_present_any = &["key-file", "key-env"],
+            conflicts_with_all = &["key-file", "key-env"]
+        )]
+        key: Option<String>,
+
+        /// File with the private key to sign with, in WIF or hexadecimal
+        /// format; `-` reads the key from STDIN
+        #[clap(short, long, conflicts_with = "key-env")]
+        key_file: Option<PathBuf>,
+
+        /// Name of environment variable with the private key to sign with,
+        /// in WIF or hexadecimal format
+        #[clap(long)]
+        key_env: Option<String>,
+    },
+
+    /// Verifies invoice signature. Exits with non-zero status if the
+    /// signature is absent or invalid
+    Verify {
+        /// Invoice in Bech32 format; if none is given reads from STDIN
+        invoice: Option<String>,
+
+        /// Public key to verify the signature against instead of the key
+        /// embedded into the invoice
+        #[clap(short, long)]
+        pubkey: Option<secp256k1::PublicKey>,
+
+        /// Require the invoice to be signed with the merchant identity key,
+        /// if the invoice specifies one
+        #[clap(long, conflicts_with = "pubkey")]
+        strict: bool,
+    },
+
+    /// Prints human-readable breakdown of the invoice data
+    Inspect {
+        /// Invoice in Bech32 format; if none is given reads from STDIN
+        invoice: Option<String>,
+
+        /// Print the report in machine-readable JSON format
+        #[clap(long)]
+        json: bool,
+    },
+
+    /// Derives payment address from the invoice descriptor beneficiary
+    Address {
+        /// Invoice in Bech32 format; if none is given reads from STDIN
+        invoice: Option<String>,
+
+        /// Derivation index for descriptors with wildcards
+        #[clap(short, long, default_value = "0")]
+        index: u32,
+
+        /// Network of the address (bitcoin, testnet, signet or regtest); if
+        /// none is given, it is detected from the invoice network
+        #[clap(short, long)]
+        network: Option<bitcoin::Network>,
+    },
+
     /// Converts RGB asset id between representations
     RgbConvert {
         /// Asset id in any format
+        #[clap(conflicts_with = "in-file")]
         asset: Option<String>,
 
-        /// Formatting of the input invoice data
+        /// Read asset id from a file instead of STDIN
+        #[clap(long)]
+        in_file: Option<PathBuf>,
+
+        /// Write converted asset id to a file instead of STDOUT
+        #[clap(long)]
+        out_file: Option<PathBuf>,
+
+        /// Formatting of the input asset id. Both legacy bech32 (`rgb1…`) and
+        /// modern (`rgb:…`) contract ids are accepted by `auto`, `bech32`
+        /// and `base58` formats. The mnemonic checksum suffix (`#…`) of
+        /// modern ids is ignored and not verified
         #[clap(short, long, default_value = "hex")]
         input: Format,
 
-        /// Formatting for the output invoice data
+        /// Formatting for the output asset id; `bech32` produces legacy
+        /// `rgb1…` and `base58` modern `rgb:…` contract id, which is the
+        /// Base58 encoding of the id with `rgb:` prefix and without the
+        /// mnemonic checksum
         #[clap(short, long, default_value = "bech32")]
         output: Format,
     },
@@ -89,12 +266,119 @@ pub enum Command {
     Conceal {
         /// UTXO to conceal
         outpoint: OutPoint,
+
+        /// Save JSON record with the outpoint and blinding factor to a file
+        #[clap(long)]
+        save: Option<PathBuf>,
+    },
+
+    /// Checks that blinded UTXO corresponds to a given outpoint
+    Reveal {
+        /// Blinded UTXO to check
+        concealed: ConcealedSeal,
+
+        /// Outpoint claimed to be concealed
+        #[clap(long)]
+        outpoint: OutPoint,
+
+        /// Blinding factor used for concealing the outpoint
+        #[clap(long)]
+        blinding: u64,
     },
 }
 
+/// Errors happening during command execution. Each of the errors is
+/// reported with its own process exit code, following `sysexits.h`
+/// conventions (see [`CliError::exit_code`]).
+#[derive(Debug, Display, Error, From)]
+#[display(doc_comments)]
+pub enum CliError {
+    /// I/O error: {0}
+    #[from]
+    Io(io::Error),
+
+    /// incorrect {format} input data: {details}
+    Decode { format: Format, details: String },
+
+    /// input data may be in any of {0} formats; please specify input format
+    /// explicitly
+    AmbiguousFormat(String),
+
+    /// data can't be read from {0} format
+    UnsupportedInput(Format),
+
+    /// data can't be written in {0} format
+    UnsupportedOutput(Format),
+
+    /// unable to encode data in {format} format: {details}
+    Encode { format: Format, details: String },
+
+    /// invalid invoice: {0}
+    #[from]
+    Builder(BuilderError),
+
+    /// unable to sign invoice: {0}
+    #[from]
+    Signing(SigningError),
+
+    /// unable to derive address: {0}
+    #[from]
+    Derive(DeriveError),
+
+    /// private key must be given in WIF or hexadecimal format
+    InvalidKey,
+
+    /// environment variable {0} with the private key is not set or is not a
+    /// valid unicode string
+    KeyEnvMissing(String),
+
+    /// private key and invoice can't be both read from STDIN
+    StdinConflict,
+
+    /// invoice signature check failed: {0}
+    #[from]
+    Signature(SignatureError),
+
+    /// blinded UTXO {concealed} does not conceal {outpoint} with the given
+    /// blinding factor
+    SealMismatch {
+        concealed: ConcealedSeal,
+        outpoint: OutPoint,
+    },
+}
+
+impl CliError {
+    /// Returns process exit code for the error:
+    /// - 1 for failed signature and seal checks;
+    /// - 64 (`EX_USAGE`) for unsupported formats and incorrect keys;
+    /// - 65 (`EX_DATAERR`) for malformed input data and invalid invoices;
+    /// - 70 (`EX_SOFTWARE`) for data which can't be encoded;
+    /// - 74 (`EX_IOERR`) for input/output errors.
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            CliError::Signature(_) | CliError::SealMismatch { .. } => 1,
+            CliError::UnsupportedInput(_)
+            | CliError::UnsupportedOutput(_)
+            | CliError::InvalidKey
+            | CliError::KeyEnvMissing(_)
+            | CliError::StdinConflict => 64,
+            CliError::Decode { .. }
+            | CliError::AmbiguousFormat(_)
+            | CliError::Builder(_)
+            | CliError::Derive(_) => 65,
+            CliError::Encode { .. } | CliError::Signing(_) => 70,
+            CliError::Io(_) => 74,
+        }
+    }
+}
+
 /// Formatting of the data
 #[derive(ArgEnum, Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Hash, Debug)]
 pub enum Format {
+    /// Detect format of the input data automatically (see
+    /// [`Format::detect`])
+    Auto,
+
     /// Format according to the rust debug rules
     Debug,
 
@@ -121,11 +405,180 @@ pub enum Format {
 
     /// Produce binary (raw) output according to LNPBP-39 serialization rules
     Raw,
+
+    /// Binary canonical CBOR encoding (invoices only)
+    Cbor,
+
+    /// Multipart uniform resources for animated QR codes, one part per line
+    /// (invoices only)
+    Ur,
+}
+
+/// Maximal length of the data fragment in a single UR part
+const UR_FRAGMENT_LEN: usize = 100;
+
+/// Initial byte of a CBOR-encoded invoice: a header of an array with four
+/// items (major type 4)
+const CBOR_ARRAY4: u8 = 0x84;
+
+/// Data which may have CBOR and UR representations
+trait CborData: Sized {
+    /// Returns `None` if the data type has no CBOR representation
+    fn cbor_encode(&self) -> Option<Vec<u8>>;
+
+    /// Returns `None` if the data type has no CBOR representation
+    fn cbor_decode(data: &[u8]) -> Option<Result<Self, String>>;
+
+    /// Returns `None` if the data type has no UR representation
+    fn ur_encode(&self) -> Option<Vec<String>>;
+
+    /// Returns `None` if the data type has no UR representation
+    fn ur_decode(parts: &str) -> Option<Result<Self, String>>;
+}
+
+impl CborData for Invoice {
+    fn cbor_encode(&self) -> Option<Vec<u8>> {
+        Some(self.to_cbor())
+    }
+
+    fn cbor_decode(data: &[u8]) -> Option<Result<Self, String>> {
+        Some(Invoice::from_cbor(data).map_err(|err| err.to_string()))
+    }
+
+    fn ur_encode(&self) -> Option<Vec<String>> {
+        Some(self.to_ur_parts(UR_FRAGMENT_LEN))
+    }
+
+    fn ur_decode(parts: &str) -> Option<Result<Self, String>> {
+        Some(
+            Invoice::from_ur_parts(parts.split_whitespace())
+                .map_err(|err| err.to_string()),
+        )
+    }
+}
+
+/// Contract id in any of its textual forms: legacy bech32 (`rgb1…`) or
+/// modern Base58 (`rgb:…`) one, optionally split into `-`-separated chunks
+/// and followed by `#`-separated mnemonic checksum, which is not verified.
+/// Both forms encode the same 32 bytes in the same order; the form used for
+/// the display is selected with [`ContractIdText::modern`] flag.
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+struct ContractIdText {
+    contract_id: rgb::ContractId,
+    modern: bool,
+}
+
+impl ContractIdText {
+    /// Prefix of the modern contract id form
+    const PREFIX: &'static str = "rgb:";
+
+    fn with_form(self, modern: bool) -> Self {
+        ContractIdText { modern, ..self }
+    }
+}
+
+impl Display for ContractIdText {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        if !self.modern {
+            return Display::fmt(&self.contract_id, f);
+        }
+        let bytes = self.contract_id.into_inner().into_inner();
+        write!(f, "{}{}", Self::PREFIX, bytes.to_base58())
+    }
+}
+
+impl FromStr for ContractIdText {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if let Ok(contract_id) = rgb::ContractId::from_str(s) {
+            return Ok(ContractIdText {
+                contract_id,
+                modern: false,
+            });
+        }
+        let invalid = || {
+            format!(
+                "`{}` is neither legacy bech32 nor modern `rgb:` contract id",
+                s
+            )
+        };
+        let payload = s.trim_start_matches("urn:lnp-bp:");
+        let payload = payload.strip_prefix(Self::PREFIX).unwrap_or(payload);
+        let payload = payload.split('#').next().unwrap_or_default();
+        let bytes = payload
+            .replace('-', "")
+            .from_base58()
+            .map_err(|_| invalid())?;
+        let bytes = <[u8; 32]>::try_from(bytes).map_err(|_| invalid())?;
+        Ok(ContractIdText {
+            contract_id: rgb::ContractId::from_inner(Hash::from_inner(bytes)),
+            modern: true,
+        })
+    }
+}
+
+impl StrictEncode for ContractIdText {
+    fn strict_encode<E: io::Write>(
+        &self,
+        e: E,
+    ) -> Result<usize, strict_encoding::Error> {
+        self.contract_id.strict_encode(e)
+    }
+}
+
+impl StrictDecode for ContractIdText {
+    fn strict_decode<D: io::Read>(
+        d: D,
+    ) -> Result<Self, strict_encoding::Error> {
+        Ok(ContractIdText {
+            contract_id: rgb::ContractId::strict_decode(d)?,
+            modern: false,
+        })
+    }
+}
+
+impl Serialize for ContractIdText {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> serde::Deserialize<'de> for ContractIdText {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        let s = String::deserialize(deserializer)?;
+        ContractIdText::from_str(&s).map_err(serde::de::Error::custom)
+    }
+}
+
+impl CborData for ContractIdText {
+    fn cbor_encode(&self) -> Option<Vec<u8>> {
+        None
+    }
+
+    fn cbor_decode(_: &[u8]) -> Option<Result<Self, String>> {
+        None
+    }
+
+    fn ur_encode(&self) -> Option<Vec<String>> {
+        None
+    }
+
+    fn ur_decode(_: &str) -> Option<Result<Self, String>> {
+        None
+    }
 }
 
 impl Display for Format {
     fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
         match self {
+            Format::Auto => f.write_str("auto"),
             Format::Debug => f.write_str("debug"),
             Format::Base58 => f.write_str("base58"),
             Format::Base64 => f.write_str("base64"),
@@ -135,6 +588,8 @@ impl Display for Format {
             Format::Hexadecimal => f.write_str("hex"),
             Format::Rust => f.write_str("rust"),
             Format::Raw => f.write_str("raw"),
+            Format::Cbor => f.write_str("cbor"),
+            Format::Ur => f.write_str("ur"),
         }
     }
 }
@@ -144,6 +599,7 @@ impl FromStr for Format {
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
         Ok(match s.trim().to_lowercase().as_str() {
+            "auto" => Format::Auto,
             "debug" => Format::Debug,
             "base58" => Format::Base58,
             "base64" => Format::Base64,
@@ -153,134 +609,749 @@ impl FromStr for Format {
             "hex" => Format::Hexadecimal,
             "raw" | "bin" => Format::Raw,
             "rust" => Format::Rust,
+            "cbor" => Format::Cbor,
+            "ur" => Format::Ur,
             other => Err(format!("Unknown format: {}", other))?,
         })
     }
 }
 
-fn input_read<T>(data: Option<String>, format: Format) -> Result<T, String>
+impl Format {
+    /// Detects candidate formats of the input data. The rules are applied in
+    /// the following order, and the first matching one wins:
+    /// 1. leading CBOR header of a four-item array (which can't start a
+    ///    UTF-8 string) - CBOR or raw binary data, both are returned;
+    /// 2. leading `i1` (or `lnpbp:`/`i:`/`lightning:`/`bitcoin:` URI scheme)
+    ///    in any case, or legacy `rgb1` and modern `rgb:` contract id
+    ///    prefixes - Bech32;
+    /// 3. leading `ur:` in any case - uniform resource;
+    /// 4. leading `{` or `[` - JSON;
+    /// 5. leading `---` or `key:` - YAML;
+    /// 6. even-length hexadecimal string and/or a valid Base64 string - both
+    ///    formats are returned if the data are valid in both of them;
+    /// 7. anything else - raw binary data.
+    ///
+    /// Leading and trailing whitespaces are ignored by all textual rules.
+    pub fn detect(data: &[u8]) -> Vec<Format> {
+        if data.first() == Some(&CBOR_ARRAY4) {
+            return vec![Format::Cbor, Format::Raw];
+        }
+        let s = String::from_utf8_lossy(data);
+        let s = s.trim();
+        let lower = s.to_lowercase();
+        let is_bech32 = [
+            "i1",
+            "lnpbp:",
+            "i:",
+            "lightning:",
+            "bitcoin:",
+            "rgb1",
+            "rgb:",
+        ]
+        .iter()
+        .any(|prefix| lower.starts_with(prefix));
+        if is_bech32 {
+            return vec![Format::Bech32m];
+        }
+        if lower.starts_with("ur:") {
+            return vec![Format::Ur];
+        }
+        if s.starts_with('{') || s.starts_with('[') {
+            return vec![Format::Json];
+        }
+        let is_yaml_key = s
+            .split_once(':')
+            .map(|(key, _)| {
+                !key.is_empty()
+                    && key
+                        .chars()
+                        .all(|c| c.is_ascii_alphanumeric() || c == '_')
+            })
+            .unwrap_or(false);
+        if s.starts_with("---") || is_yaml_key {
+            return vec![Format::Yaml];
+        }
+
+        let mut candidates = vec![];
+        if !s.is_empty()
+            && s.len().is_multiple_of(2)
+            && s.chars().all(|c| c.is_ascii_hexdigit())
+        {
+            candidates.push(Format::Hexadecimal);
+        }
+        if !s.is_empty() && base64::decode(s).is_ok() {
+            candidates.push(Format::Base64);
+        }
+        if candidates.is_empty() {
+            candidates.push(Format::Raw);
+        }
+        candidates
+    }
+}
+
+fn input_read<T>(
+    data: Option<String>,
+    file: Option<&Path>,
+    format: Format,
+) -> Result<T, CliError>
 where
-    T: FromStr + StrictDecode + for<'de> serde::Deserialize<'de>,
+    T: FromStr + StrictDecode + CborData + for<'de> serde::Deserialize<'de>,
     <T as FromStr>::Err: Display,
 {
-    let data = data
-        .map(|d| d.as_bytes().to_vec())
-        .ok_or(String::default())
-        .or_else(|_| -> Result<Vec<u8>, String> {
+    let data = match (data, file) {
+        (Some(data), _) => data.into_bytes(),
+        (None, Some(path)) => fs::read(path)?,
+        (None, None) => {
             let mut buf = Vec::new();
-            io::stdin()
-                .read_to_end(&mut buf)
-                .as_ref()
-                .map_err(io::Error::to_string)?;
-            Ok(buf)
-        })?;
-    let s = &String::from_utf8_lossy(&data);
+            io::stdin().read_to_end(&mut buf)?;
+            buf
+        }
+    };
+    let data = match format {
+        Format::Raw | Format::Cbor | Format::Auto => &data[..],
+        _ => trim_ascii(&data),
+    };
+    input_parse(data, format)
+}
+
+/// Removes leading and trailing ASCII whitespaces from the data
+fn trim_ascii(data: &[u8]) -> &[u8] {
+    let start = data
+        .iter()
+        .position(|byte| !byte.is_ascii_whitespace())
+        .unwrap_or(data.len());
+    let end = data
+        .iter()
+        .rposition(|byte| !byte.is_ascii_whitespace())
+        .map(|pos| pos + 1)
+        .unwrap_or(start);
+    &data[start..end]
+}
+
+fn output_write_to<T>(
+    file: Option<&Path>,
+    data: T,
+    format: Format,
+) -> Result<(), CliError>
+where
+    T: Debug + Display + Serialize + StrictEncode + CborData,
+{
+    match file {
+        Some(path) => output_write(fs::File::create(path)?, data, format),
+        None => output_write(io::stdout(), data, format),
+    }
+}
+
+fn input_parse<T>(data: &[u8], format: Format) -> Result<T, CliError>
+where
+    T: FromStr + StrictDecode + CborData + for<'de> serde::Deserialize<'de>,
+    <T as FromStr>::Err: Display,
+{
+    let decode_err = |details: String| CliError::Decode { format, details };
+    let strict_err = |err: strict_encoding::Error| {
+        decode_err(format!("wrong data: {}", err))
+    };
+    // Binary data are never converted into a string, so they can't be
+    // corrupted by replacing non-UTF-8 bytes
+    let text = || {
+        str::from_utf8(data)
+            .map(str::trim)
+            .map_err(|_| decode_err(s!("data are not a valid UTF-8 string")))
+    };
+    let encoded = || {
+        let s = text()?;
+        if s.chars().any(char::is_whitespace) {
+            return Err(decode_err(s!(
+                "encoded data must not contain whitespaces or line breaks"
+            )));
+        }
+        Ok(s)
+    };
     Ok(match format {
-        Format::Bech32m => T::from_str(s).map_err(|err| err.to_string())?,
-        Format::Base58 => {
-            T::strict_deserialize(s.from_base58().map_err(|err| {
-                format!("Incorrect Base58 encoding: {:?}", err)
-            })?)
-            .map_err(|err| format!("Wrong invoice data: {}", err))?
+        Format::Auto => {
+            let candidates = Format::detect(data);
+            let is_binary = candidates
+                .iter()
+                .any(|format| matches!(format, Format::Raw | Format::Cbor));
+            let data = if is_binary { data } else { trim_ascii(data) };
+            let mut parsed = vec![];
+            let mut errors = vec![];
+            for candidate in &candidates {
+                match input_parse(data, *candidate) {
+                    Ok(value) => parsed.push((*candidate, value)),
+                    Err(err) => errors.push(err),
+                }
+            }
+            if parsed.len() > 1 {
+                let names = parsed
+                    .iter()
+                    .map(|(format, _)| format.to_string())
+                    .collect::<Vec<_>>();
+                return Err(CliError::AmbiguousFormat(names.join(", ")));
+            }
+            match (parsed.pop(), errors.len()) {
+                (Some((_, value)), _) => value,
+                (None, 1) => return Err(errors.remove(0)),
+                (None, _) => {
+                    let details = errors
+                        .iter()
+                        .map(CliError::to_string)
+                        .collect::<Vec<_>>();
+                    return Err(decode_err(details.join("; ")));
+                }
+            }
         }
+        Format::Bech32m => T::from_str(encoded()?)
+            .map_err(|err| decode_err(err.to_string()))?,
+        Format::Base58 => T::strict_deserialize(
+            encoded()?
+                .from_base58()
+                .map_err(|err| decode_err(format!("{:?}", err)))?,
+        )
+        .map_err(strict_err)?,
         Format::Base64 => T::strict_deserialize(
-            &base64::decode(&data)
-                .map_err(|err| format!("Incorrect Base64 encoding: {}", err))?,
+            &base64::decode(encoded()?)
+                .map_err(|err| decode_err(err.to_string()))?,
         )
-        .map_err(|err| format!("Wrong invoice data: {}", err))?,
-        Format::Yaml => {
-            serde_yaml::from_str(s).map_err(|err| err.to_string())?
-        }
-        Format::Json => {
-            serde_json::from_str(s).map_err(|err| err.to_string())?
-        }
+        .map_err(strict_err)?,
+        Format::Yaml => serde_yaml::from_str(text()?)
+            .map_err(|err| decode_err(err.to_string()))?,
+        Format::Json => serde_json::from_str(text()?)
+            .map_err(|err| decode_err(err.to_string()))?,
         Format::Hexadecimal => T::strict_deserialize(
-            Vec::<u8>::from_hex(s)
-                .as_ref()
-                .map_err(hex::Error::to_string)?,
+            Vec::<u8>::from_hex(encoded()?)
+                .map_err(|err: hex::Error| decode_err(err.to_string()))?,
         )
-        .map_err(|err| format!("Wrong invoice data: {}", err))?,
-        Format::Raw => T::strict_deserialize(&data)
-            .map_err(|err| format!("Wrong invoice data: {}", err))?,
-        _ => Err(format!("Can't read data from {} format", format))?,
+        .map_err(strict_err)?,
+        Format::Raw => T::strict_deserialize(data).map_err(strict_err)?,
+        Format::Cbor => T::cbor_decode(data)
+            .ok_or(CliError::UnsupportedInput(format))?
+            .map_err(decode_err)?,
+        Format::Ur => T::ur_decode(text()?)
+            .ok_or(CliError::UnsupportedInput(format))?
+            .map_err(decode_err)?,
+        _ => return Err(CliError::UnsupportedInput(format)),
     })
 }
 
+fn parse_expiry(s: &str) -> Result<DateTime<Utc>, String> {
+    if let Ok(datetime) = DateTime::parse_from_rfc3339(s) {
+        return Ok(datetime.with_timezone(&Utc));
+    }
+    let invalid = || {
+        format!(
+            "Invalid expiry `{}`: must be RFC 3339 date and time or a \
+             duration like `30m`, `2h` or `7d`",
+            s
+        )
+    };
+    let (pos, unit) = s.char_indices().last().ok_or_else(invalid)?;
+    let value = s[..pos].parse::<i64>().map_err(|_| invalid())?;
+    let duration = match unit {
+        's' => Duration::seconds(value),
+        'm' => Duration::minutes(value),
+        'h' => Duration::hours(value),
+        'd' => Duration::days(value),
+        'w' => Duration::weeks(value),
+        _ => return Err(invalid()),
+    };
+    Utc::now().checked_add_signed(duration).ok_or_else(invalid)
+}
+
+fn parse_recurrent(s: &str) -> Result<Recurrent, String> {
+    let invalid = || {
+        format!(
+            "Invalid recurrence `{}`: must be in form of \
+             `<seconds|days|weeks|months|years>:<n>`",
+            s
+        )
+    };
+    let (unit, value) = s.split_once(':').ok_or_else(invalid)?;
+    Ok(match unit {
+        "seconds" => Recurrent::Seconds(value.parse().map_err(|_| invalid())?),
+        "days" => Recurrent::Days(value.parse().map_err(|_| invalid())?),
+        "weeks" => Recurrent::Weeks(value.parse().map_err(|_| invalid())?),
+        "months" => Recurrent::Months(value.parse().map_err(|_| invalid())?),
+        "years" => Recurrent::Years(value.parse().map_err(|_| invalid())?),
+        _ => return Err(invalid()),
+    })
+}
+
+fn parse_quantity(s: &str) -> Result<Quantity, String> {
+    let invalid = || {
+        format!(
+            "Invalid quantity `{}`: must be in form of `min[,max][,default]`",
+            s
+        )
+    };
+    let number = |s: &str| {
+        if s.is_empty() || !s.chars().all(|c| c.is_ascii_digit()) {
+            return Err(invalid());
+        }
+        s.parse::<u32>().map_err(|_| invalid())
+    };
+    let mut parts = s.split(',');
+    let min = number(parts.next().unwrap_or_default())?;
+    let max = match parts.next() {
+        None | Some("") => None,
+        Some(max) => Some(number(max)?),
+    };
+    let default = parts.next().map(number).transpose()?;
+    if parts.next().is_some() {
+        return Err(invalid());
+    }
+    match default {
+        Some(default) => Quantity::new(min, max, default),
+        None => Quantity::with_range(min, max),
+    }
+    .map_err(|err| err.to_string())
+}
+
+fn parse_network(s: &str) -> Result<Network, String> {
+    Ok(match s.to_lowercase().as_str() {
+        "mainnet" | "bitcoin" => Network::Mainnet,
+        "testnet" | "testnet3" => Network::Testnet3,
+        other => Network::from_str(other).map_err(|err| err.to_string())?,
+    })
+}
+
+/// Detects the network of the invoice from its main beneficiary address or
+/// from the native asset of a standard chain, defaulting to mainnet.
+fn detect_network(
+    beneficiary: &Beneficiary,
+    asset: Option<&AssetId>,
+) -> Network {
+    if let Beneficiary::Address(address) = beneficiary {
+        return Network::from(address.network);
+    }
+    asset
+        .and_then(|asset| {
+            Chain::all_standard()
+                .iter()
+                .chain(&[Chain::from(bitcoin::Network::Regtest)])
+                .find(|chain| chain.native_asset() == *asset)
+                .and_then(|chain| Network::try_from(chain.clone()).ok())
+        })
+        .unwrap_or(Network::Mainnet)
+}
+
+fn parse_secret_key(key: &str) -> Result<secp256k1::SecretKey, CliError> {
+    PrivateKey::from_wif(key)
+        .map(|key| key.inner)
+        .or_else(|_| secp256k1::SecretKey::from_str(key))
+        .map_err(|_| CliError::InvalidKey)
+}
+
 fn output_write<T>(
     mut f: impl io::Write,
     data: T,
     format: Format,
-) -> Result<(), String>
+) -> Result<(), CliError>
 where
-    T: Debug + Display + Serialize + StrictEncode,
+    T: Debug + Display + Serialize + StrictEncode + CborData,
 {
-    let strict = data.strict_serialize().map_err(|err| err.to_string())?;
+    let encode_err = |details: String| CliError::Encode { format, details };
+    let strict = data
+        .strict_serialize()
+        .map_err(|err| encode_err(err.to_string()))?;
     match format {
-        Format::Debug => writeln!(f, "{:#?}", data),
-        Format::Bech32m => writeln!(f, "{}", data),
-        Format::Base58 => writeln!(f, "{}", strict.to_base58()),
-        Format::Base64 => writeln!(f, "{}", base64::encode(&strict)),
+        Format::Auto => return Err(CliError::UnsupportedOutput(format)),
+        Format::Debug => writeln!(f, "{:#?}", data)?,
+        Format::Bech32m => writeln!(f, "{}", data)?,
+        Format::Base58 => writeln!(f, "{}", strict.to_base58())?,
+        Format::Base64 => writeln!(f, "{}", base64::encode(&strict))?,
         Format::Yaml => writeln!(
             f,
             "{}",
             serde_yaml::to_string(&data)
-                .as_ref()
-                .map_err(serde_yaml::Error::to_string)?
-        ),
+                .map_err(|err| encode_err(err.to_string()))?
+        )?,
         Format::Json => writeln!(
             f,
             "{}",
             serde_json::to_string(&data)
-                .as_ref()
-                .map_err(serde_json::Error::to_string)?
-        ),
-        Format::Hexadecimal => writeln!(f, "{}", strict.to_hex()),
-        Format::Rust => writeln!(f, "{:#04X?}", strict),
-        Format::Raw => data
-            .strict_encode(f)
-            .map(|_| ())
-            .map_err(|_| io::Error::from_raw_os_error(0)),
-    }
-    .as_ref()
-    .map_err(io::Error::to_string)?;
+                .map_err(|err| encode_err(err.to_string()))?
+        )?,
+        Format::Hexadecimal => writeln!(f, "{}", strict.to_hex())?,
+        Format::Rust => writeln!(f, "{:#04X?}", strict)?,
+        Format::Raw => f.write_all(&strict)?,
+        Format::Cbor => f.write_all(
+            &data
+                .cbor_encode()
+                .ok_or(CliError::UnsupportedOutput(format))?,
+        )?,
+        Format::Ur => {
+            let parts = data
+                .ur_encode()
+                .ok_or(CliError::UnsupportedOutput(format))?;
+            for part in parts {
+                writeln!(f, "{}", part)?;
+            }
+        }
+    }
     Ok(())
 }
 
-fn main() -> Result<(), String> {
-    let opts = Opts::parse();
+fn main() {
+    if let Err(err) = run(Opts::parse()) {
+        eprintln!("Error: {}", err);
+        process::exit(err.exit_code());
+    }
+}
 
+fn run(opts: Opts) -> Result<(), CliError> {
     match opts.command {
         Command::Create {
             beneficiary,
             amount,
             asset,
+            iface,
+            merchant,
+            purpose,
+            expiry,
+            recurrent,
+            recurrence_limit,
+            quantity,
+            currencies,
+            network,
+            omit_network,
+            alt_beneficiaries,
+            endpoints,
+            output,
         } => {
-            let invoice =
-                Invoice::new(beneficiary, amount, asset.map(AssetId::from));
-            output_write(io::stdout(), invoice, Format::Bech32m)?;
+            let asset = asset.map(contract_id_to_asset_id);
+            let network = match network {
+                _ if omit_network => None,
+                Some(network) => Some(network),
+                None => Some(detect_network(&beneficiary, asset.as_ref())),
+            };
+            let mut builder = InvoiceBuilder::new(beneficiary);
+            if let Some(amount) = amount {
+                builder = builder.amount(AmountExt::Normal(amount));
+            }
+            if let Some(asset) = asset {
+                builder = builder.asset(asset);
+            }
+            if let Some(iface) = iface {
+                builder = builder.iface(iface);
+            }
+            if let Some(merchant) = merchant {
+                builder = builder.merchant(&merchant);
+            }
+            if let Some(purpose) = purpose {
+                builder = builder.purpose(&purpose);
+            }
+            if let Some(expiry) = expiry {
+                builder = builder.expiry(expiry);
+            }
+            if let Some(recurrent) = recurrent {
+                builder = builder.recurrent(recurrent);
+            }
+            if let Some(limit) = recurrence_limit {
+                builder = builder.recurrence_limit(limit);
+            }
+            if let Some(quantity) = quantity {
+                builder = builder.quantity(quantity);
+            }
+            for currency in currencies {
+                builder = builder.currency_requirement(currency);
+            }
+            if let Some(network) = network {
+                builder = builder.network(network);
+            }
+            for beneficiary in alt_beneficiaries {
+                builder = builder.alt_beneficiary(beneficiary);
+            }
+            for endpoint in endpoints {
+                builder = builder.consignment_endpoint(endpoint);
+            }
+            let invoice = builder.finish()?;
+            output_write(io::stdout(), invoice, output)?;
         }
         Command::Convert {
             invoice,
+            in_file,
+            out_file,
             input,
             output,
         } => {
-            let invoice: Invoice = input_read(invoice, input)?;
-            output_write(io::stdout(), invoice, output)?;
+            let invoice: Invoice =
+                input_read(invoice, in_file.as_deref(), input)?;
+            output_write_to(out_file.as_deref(), invoice, output)?;
+        }
+        Command::Sign {
+            invoice,
+            key,
+            key_file,
+            key_env,
+        } => {
+            let key = match (key, key_file, key_env) {
+                (Some(key), _, _) => key,
+                (None, _, Some(var)) => env::var(&var)
+                    .map_err(|_| CliError::KeyEnvMissing(var.clone()))?,
+                (None, Some(path), None) if path.as_os_str() == "-" => {
+                    if invoice.is_none() {
+                        return Err(CliError::StdinConflict);
+                    }
+                    let mut key = String::new();
+                    io::stdin().read_to_string(&mut key)?;
+                    key
+                }
+                (None, Some(path), None) => fs::read_to_string(path)?,
+                (None, None, None) => {
+                    unreachable!("clap requires a key source")
+                }
+            };
+            let mut invoice: Invoice =
+                input_read(invoice, None, Format::Bech32m)?;
+            let seckey = parse_secret_key(key.trim())?;
+            let keypair = secp256k1::KeyPair::from_secret_key(
+                &secp256k1::Secp256k1::signing_only(),
+                &seckey,
+            );
+            invoice.sign(&keypair)?;
+            output_write(io::stdout(), invoice, Format::Bech32m)?;
+        }
+        Command::Verify {
+            invoice,
+            pubkey,
+            strict,
+        } => {
+            let invoice: Invoice = input_read(invoice, None, Format::Bech32m)?;
+            let result = match pubkey {
+                Some(pubkey) => invoice.verify_signature_with_key(pubkey),
+                None if strict => invoice.verify_signature_strict(),
+                None => invoice.verify_signature(),
+            };
+            result?;
+            println!("Invoice signature is valid");
+        }
+        Command::Inspect { invoice, json } => {
+            let invoice: Invoice = input_read(invoice, None, Format::Bech32m)?;
+            let report = InvoiceReport::new(&invoice, Utc::now());
+            if let Err(errors) = invoice.validate() {
+                for err in errors {
+                    eprintln!("Warning: {}", err);
+                }
+            }
+            if json {
+                println!(
+                    "{}",
+                    serde_json::to_string_pretty(&report).map_err(|err| {
+                        CliError::Encode {
+                            format: Format::Json,
+                            details: err.to_string(),
+                        }
+                    })?
+                );
+            } else {
+                println!("{}", report);
+            }
+        }
+        Command::Address {
+            invoice,
+            index,
+            network,
+        } => {
+            let invoice: Invoice = input_read(invoice, None, Format::Bech32m)?;
+            let network = network.unwrap_or(match invoice.network() {
+                Some(Network::Regtest | Network::RegtestCustom(_)) => {
+                    bitcoin::Network::Regtest
+                }
+                Some(network) if !network.is_mainnet() => {
+                    bitcoin::Network::Testnet
+                }
+                _ => bitcoin::Network::Bitcoin,
+            });
+            println!("{}", invoice.derive_address(index, network)?);
         }
         Command::RgbConvert {
             asset,
+            in_file,
+            out_file,
             input,
             output,
         } => {
-            let asset: rgb::ContractId = input_read(asset, input)?;
-            output_write(io::stdout(), asset, output)?;
+            // Modern contract ids are textual Base58 strings, so they are
+            // handled by the adapter together with the legacy bech32 ones
+            let input = match input {
+                Format::Base58 => Format::Bech32m,
+                other => other,
+            };
+            let asset: ContractIdText =
+                input_read(asset, in_file.as_deref(), input)?;
+            let (asset, output) = match output {
+                Format::Base58 => (asset.with_form(true), Format::Bech32m),
+                Format::Bech32m => (asset.with_form(false), output),
+                _ => (asset, output),
+            };
+            output_write_to(out_file.as_deref(), asset, output)?;
+        }
+        Command::Conceal { outpoint, save } => {
+            let blinded = seal::blind(outpoint, &mut thread_rng());
+            println!("{}", blinded.revealed);
+            println!("{}", blinded.concealed);
+            println!("blinding: {}", blinded.blinding);
+            if let Some(path) = save {
+                let record = serde_json::json!({
+                    "outpoint": outpoint.to_string(),
+                    "method": blinded.revealed.method.to_string(),
+                    "blinding": blinded.blinding,
+                    "concealed": blinded.concealed.to_string(),
+                });
+                fs::write(path, record.to_string())?;
+            }
         }
-        Command::Conceal { outpoint } => {
-            let seal = RevealedSeal::from(outpoint);
-            println!("{}", seal);
-            println!("{}", seal.to_concealed_seal());
+        Command::Reveal {
+            concealed,
+            outpoint,
+            blinding,
+        } => {
+            if !seal::verify(concealed, outpoint, blinding) {
+                return Err(CliError::SealMismatch {
+                    concealed,
+                    outpoint,
+                });
+            }
+            println!("Blinded UTXO {} conceals {}", concealed, outpoint);
         }
     }
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+
+    use super::*;
+
+    #[test]
+    fn quantity_syntax() {
+        let quantity = |min, max, default| Quantity { min, max, default };
+        assert_eq!(parse_quantity("2"), Ok(quantity(2, None, 2)));
+        assert_eq!(parse_quantity("0"), Ok(quantity(0, None, 1)));
+        assert_eq!(parse_quantity("1,10"), Ok(quantity(1, Some(10), 1)));
+        assert_eq!(parse_quantity("0,0"), Ok(quantity(0, Some(0), 0)));
+        assert_eq!(parse_quantity("1,10,5"), Ok(quantity(1, Some(10), 5)));
+        assert_eq!(parse_quantity("1,,5"), Ok(quantity(1, None, 5)));
+        for invalid in ["", ",5", "1,a", "1..5", "1,2,3,4", "-1", "1,5,"] {
+            assert!(parse_quantity(invalid).is_err(), "{}", invalid);
+        }
+        // Inconsistent ranges and defaults
+        assert!(parse_quantity("5,1").is_err());
+        assert!(parse_quantity("1,5,6").is_err());
+    }
+
+    #[test]
+    fn expiry_syntax() {
+        let now = Utc::now();
+        let expiry = parse_expiry("2h").unwrap();
+        assert!(expiry >= now + Duration::hours(2));
+        assert!(expiry <= Utc::now() + Duration::hours(2));
+        assert_eq!(
+            parse_expiry("2030-01-01T00:00:00+02:00").unwrap(),
+            Utc.timestamp_opt(1_893_448_800, 0).unwrap()
+        );
+        for invalid in ["", "h", "5", "5y", "5€", "€", "5mé", "1.5h"] {
+            assert!(parse_expiry(invalid).is_err(), "{}", invalid);
+        }
+    }
+
+    fn sample() -> Invoice {
+        let address = bitcoin::Address::from_str(
+            "bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t4",
+        )
+        .unwrap();
+        Invoice::with_address(address, Some(1000))
+    }
+
+    #[test]
+    fn detect_bech32() {
+        let invoice = sample().to_string();
+        assert_eq!(Format::detect(invoice.as_bytes()), [Format::Bech32m]);
+        let upper = invoice.to_uppercase();
+        assert_eq!(Format::detect(upper.as_bytes()), [Format::Bech32m]);
+        for scheme in ["lnpbp:", "i:", "lightning:", "LNPBP:"] {
+            let uri = format!("{}{}", scheme, invoice);
+            assert_eq!(Format::detect(uri.as_bytes()), [Format::Bech32m]);
+        }
+        let padded = format!("  {}\n", invoice);
+        assert_eq!(Format::detect(padded.as_bytes()), [Format::Bech32m]);
+        assert_eq!(Format::detect(b"rgb1qqqq"), [Format::Bech32m]);
+        assert_eq!(Format::detect(b"rgb:qqqq"), [Format::Bech32m]);
+    }
+
+    #[test]
+    fn detect_bip21() {
+        let invoice = sample();
+        let uri = invoice.to_bip21().unwrap();
+        assert!(uri.starts_with("bitcoin:"));
+        assert_eq!(Format::detect(uri.as_bytes()), [Format::Bech32m]);
+        let parsed: Invoice = input_parse(uri.as_bytes(), Format::Auto)
+            .expect("BIP-21 URI is detected and parsed");
+        assert_eq!(parsed, invoice);
+        assert_eq!(Format::detect(b"BITCOIN:bc1q"), [Format::Bech32m]);
+    }
+
+    #[test]
+    fn detect_hex() {
+        let hex = sample().strict_serialize().unwrap().to_hex();
+        // Strict-encoded invoice starts with a zero version byte, so its hex
+        // form is not a valid Base64 string
+        assert_eq!(Format::detect(hex.as_bytes()), [Format::Hexadecimal]);
+        assert_eq!(Format::detect(b"0a1"), [Format::Raw]);
+    }
+
+    #[test]
+    fn detect_base64() {
+        let base64 = base64::encode(&sample().strict_serialize().unwrap());
+        assert_eq!(Format::detect(base64.as_bytes()), [Format::Base64]);
+        let parsed: Invoice =
+            input_parse(base64.as_bytes(), Format::Auto).unwrap();
+        assert_eq!(parsed, sample());
+    }
+
+    #[test]
+    fn detect_ambiguous() {
+        assert_eq!(
+            Format::detect(b"abcd"),
+            [Format::Hexadecimal, Format::Base64]
+        );
+        assert_eq!(
+            Format::detect(b" 00112233\n"),
+            [Format::Hexadecimal, Format::Base64]
+        );
+    }
+
+    #[test]
+    fn detect_ur() {
+        let parts = sample().to_ur_parts(UR_FRAGMENT_LEN);
+        assert_eq!(Format::detect(parts[0].as_bytes()), [Format::Ur]);
+        let upper = parts[0].to_uppercase();
+        assert_eq!(Format::detect(upper.as_bytes()), [Format::Ur]);
+    }
+
+    #[test]
+    fn detect_cbor() {
+        let cbor = sample().to_cbor();
+        assert_eq!(cbor[0], CBOR_ARRAY4);
+        assert_eq!(Format::detect(&cbor), [Format::Cbor, Format::Raw]);
+        let parsed: Invoice = input_parse(&cbor, Format::Auto)
+            .expect("CBOR is not a valid strict encoding");
+        assert_eq!(parsed, sample());
+    }
+
+    #[test]
+    fn detect_text_and_raw() {
+        assert_eq!(Format::detect(b"{\"a\": 1}"), [Format::Json]);
+        assert_eq!(Format::detect(b"[1, 2]"), [Format::Json]);
+        assert_eq!(Format::detect(b"---\na: 1"), [Format::Yaml]);
+        assert_eq!(Format::detect(b"beneficiary: x"), [Format::Yaml]);
+        assert_eq!(Format::detect(b""), [Format::Raw]);
+        assert_eq!(Format::detect(&[0x00, 0xff, 0x10]), [Format::Raw]);
+        let raw = sample().strict_serialize().unwrap();
+        let parsed: Invoice = input_parse(&raw, Format::Auto).unwrap();
+        assert_eq!(parsed, sample());
+    }
+}
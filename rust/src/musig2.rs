@@ -0,0 +1,815 @@
+// LNP/BP universal invoice library implementing LNPBP-38 standard
+// Written in 2021 by
+//     Dr. Maxim Orlovsky <orlovsky@pandoracore.com>
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the MIT License
+// along with this software.
+// If not, see <https://opensource.org/licenses/MIT>.
+
+//! MuSig2 (BIP-327) signing of invoices by merchants controlled by multiple
+//! operators.
+//!
+//! Operators aggregate their keys with [`MerchantKeyAggregation`] and sign
+//! in two rounds:
+//! 1. each operator generates nonce for the invoice with [`generate_nonce`]
+//!    and shares the [`PublicNonce`] with the others;
+//! 2. once all public nonces are known, each operator starts the session
+//!    with [`Invoice::sign_musig2_begin`] and shares the
+//!    [`PartialSignature`] produced by [`Musig2Session::partial_sign`].
+//!
+//! Any party collecting all partial signatures completes the signing with
+//! [`Invoice::sign_musig2_complete`]. The result is a plain BIP-340
+//! signature under the aggregated key, kept in the invoice signature
+//! record, so the invoice is verified as a single-signer one.
+//!
+//! The BIP-327 algorithms are implemented here, since no MuSig2 library
+//! supports the version of `secp256k1` used by the crate; they are checked
+//! against the BIP-327 test vectors.
+
+use std::fmt::{self, Debug, Formatter};
+
+use amplify::{Slice32, Wrapper};
+use bitcoin::hashes::{sha256, Hash, HashEngine};
+use bitcoin::secp256k1::rand::RngCore;
+use bitcoin::secp256k1::{
+    self, constants, schnorr, KeyPair, Parity, PublicKey, Scalar, SecretKey,
+};
+use zeroize::Zeroize;
+
+use crate::{Invoice, SignatureScheme};
+
+/// Errors happening during MuSig2 invoice signing.
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug, Display, Error, From)]
+#[display(doc_comments)]
+pub enum Musig2Error {
+    /// key aggregation requires at least one participant key
+    NoParticipants,
+
+    /// public key {0} is not a participant of the key aggregation
+    UnknownParticipant(PublicKey),
+
+    /// public key {0} is given more than once for the key aggregation
+    DuplicateParticipant(PublicKey),
+
+    /// {found} items were provided for {expected} signing participants
+    ParticipantCount { expected: usize, found: usize },
+
+    /// secret nonce was generated for a different public key
+    NonceKeyMismatch,
+
+    /// signing session was started for different invoice data or merchant
+    /// key
+    SessionMismatch,
+
+    /// invoice data are inconsistent for strict serialization
+    InconsistentSerialization,
+
+    /// aggregated signature is not valid under the aggregated merchant key
+    InvalidSignature,
+
+    /// partial signature of {0} is not a valid scalar
+    InvalidPartial(PublicKey),
+
+    /// MuSig2 computation resulted in a point at infinity or zero scalar;
+    /// the signing must be restarted with fresh nonces
+    #[from(secp256k1::Error)]
+    Arithmetic,
+}
+
+/// Computes BIP-340 tagged hash of the data.
+fn tagged_hash(tag: &str, data: &[&[u8]]) -> [u8; 32] {
+    let tag = sha256::Hash::hash(tag.as_bytes());
+    let mut engine = sha256::Hash::engine();
+    engine.input(&tag[..]);
+    engine.input(&tag[..]);
+    for chunk in data {
+        engine.input(chunk);
+    }
+    sha256::Hash::from_engine(engine).into_inner()
+}
+
+/// Interprets 32 bytes as a big-endian integer modulo the curve order.
+fn reduce(mut bytes: [u8; 32]) -> Scalar {
+    if let Ok(scalar) = Scalar::from_be_bytes(bytes) {
+        return scalar;
+    }
+    // Values not less than the order are less than twice the order
+    let mut borrow = 0i16;
+    for (byte, order) in bytes.iter_mut().zip(constants::CURVE_ORDER).rev() {
+        let diff = *byte as i16 - order as i16 - borrow;
+        borrow = (diff < 0) as i16;
+        *byte = diff.rem_euclid(256) as u8;
+    }
+    Scalar::from_be_bytes(bytes).expect("reduced value is below the order")
+}
+
+/// Converts scalar into a secret key, failing for zero.
+fn to_secret(scalar: Scalar) -> Result<SecretKey, secp256k1::Error> {
+    SecretKey::from_slice(&scalar.to_be_bytes())
+}
+
+/// Adds two scalars modulo the curve order, allowing zero values.
+fn add_scalars(a: Scalar, b: Scalar) -> Scalar {
+    match to_secret(a) {
+        // Fails only if the sum is zero
+        Ok(a) => a.add_tweak(&b).map(Scalar::from).unwrap_or(Scalar::ZERO),
+        Err(_) => b,
+    }
+}
+
+/// Parses partial signature value, which may be zero but must be below the
+/// curve order.
+fn partial_scalar(partial: &PartialSignature) -> Result<Scalar, Musig2Error> {
+    Scalar::from_be_bytes(partial.s.into_inner())
+        .map_err(|_| Musig2Error::InvalidPartial(partial.signer))
+}
+
+fn has_even_y(key: &PublicKey) -> bool {
+    key.x_only_public_key().1 == Parity::Even
+}
+
+/// Aggregated merchant key composed from the keys of all operators
+/// (BIP-327 `KeyAgg`). The aggregated key depends on the order of the
+/// participant keys, so all operators must use the same order; each key may
+/// be given only once.
+#[derive(Clone, Eq, PartialEq, Hash, Debug)]
+pub struct MerchantKeyAggregation {
+    participants: Vec<PublicKey>,
+    list_hash: [u8; 32],
+    second_key: Option<PublicKey>,
+    aggregated_key: PublicKey,
+}
+
+impl MerchantKeyAggregation {
+    pub fn new(
+        participants: impl IntoIterator<Item = PublicKey>,
+    ) -> Result<MerchantKeyAggregation, Musig2Error> {
+        let participants = participants.into_iter().collect::<Vec<_>>();
+        for (no, key) in participants.iter().enumerate() {
+            if participants[..no].contains(key) {
+                return Err(Musig2Error::DuplicateParticipant(*key));
+            }
+        }
+        let first = *participants.first().ok_or(Musig2Error::NoParticipants)?;
+
+        let serialized = participants
+            .iter()
+            .map(PublicKey::serialize)
+            .collect::<Vec<_>>();
+        let data = serialized.iter().map(|key| &key[..]).collect::<Vec<_>>();
+        let list_hash = tagged_hash("KeyAgg list", &data);
+        let second_key = participants.iter().copied().find(|key| *key != first);
+
+        let mut aggregation = MerchantKeyAggregation {
+            participants,
+            list_hash,
+            second_key,
+            aggregated_key: first,
+        };
+        let secp = secp256k1::Secp256k1::verification_only();
+        let terms = aggregation
+            .participants
+            .iter()
+            .map(|key| key.mul_tweak(&secp, &aggregation.coefficient(key)))
+            .collect::<Result<Vec<_>, _>>()?;
+        aggregation.aggregated_key =
+            PublicKey::combine_keys(&terms.iter().collect::<Vec<_>>())?;
+        Ok(aggregation)
+    }
+
+    /// Returns participant keys in the aggregation order.
+    pub fn participants(&self) -> &[PublicKey] {
+        &self.participants
+    }
+
+    /// Returns the aggregated key, which x-only form verifies the invoice
+    /// signature.
+    pub fn aggregated_key(&self) -> PublicKey {
+        self.aggregated_key
+    }
+
+    fn coefficient(&self, key: &PublicKey) -> Scalar {
+        if Some(*key) == self.second_key {
+            return Scalar::ONE;
+        }
+        reduce(tagged_hash(
+            "KeyAgg coefficient",
+            &[&self.list_hash, &key.serialize()],
+        ))
+    }
+
+    fn check_participant(&self, key: PublicKey) -> Result<(), Musig2Error> {
+        if !self.participants.contains(&key) {
+            return Err(Musig2Error::UnknownParticipant(key));
+        }
+        Ok(())
+    }
+}
+
+/// Secret part of the signer nonce. It is consumed by signing, since
+/// reusing it for another signature leaks the signer private key, and
+/// must never be shared or persisted. The nonce values are not printed by
+/// [`Debug`] and are zeroized once the nonce is dropped.
+pub struct SecretNonce {
+    k1: [u8; 32],
+    k2: [u8; 32],
+    pubkey: PublicKey,
+}
+
+impl Debug for SecretNonce {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SecretNonce")
+            .field("pubkey", &self.pubkey)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Drop for SecretNonce {
+    fn drop(&mut self) {
+        self.k1.zeroize();
+        self.k2.zeroize();
+    }
+}
+
+/// Public part of the signer nonce, which is shared with other operators
+/// before signing.
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    serde(crate = "serde_crate", rename_all = "camelCase")
+)]
+#[derive(
+    Copy,
+    Clone,
+    Ord,
+    PartialOrd,
+    Eq,
+    PartialEq,
+    Hash,
+    Debug,
+    StrictEncode,
+    StrictDecode,
+)]
+pub struct PublicNonce {
+    pub r1: PublicKey,
+    pub r2: PublicKey,
+}
+
+/// Partial signature of a single operator, which is shared with the party
+/// completing the signing.
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    serde(crate = "serde_crate", rename_all = "camelCase")
+)]
+#[derive(
+    Copy,
+    Clone,
+    Ord,
+    PartialOrd,
+    Eq,
+    PartialEq,
+    Hash,
+    Debug,
+    StrictEncode,
+    StrictDecode,
+)]
+pub struct PartialSignature {
+    pub signer: PublicKey,
+    pub s: Slice32,
+}
+
+/// Generates fresh nonce of the operator for signing the invoice using
+/// randomness taken from `rng`. Following BIP-327 recommendations, the
+/// operator secret key, the aggregated key and the invoice signature hash are
+/// mixed into the nonce, protecting it from a weak randomness source.
+pub fn generate_nonce(
+    keypair: &KeyPair,
+    aggregation: &MerchantKeyAggregation,
+    invoice: &Invoice,
+    rng: &mut impl RngCore,
+) -> Result<(SecretNonce, PublicNonce), Musig2Error> {
+    let pubkey = keypair.public_key();
+    aggregation.check_participant(pubkey)?;
+    let msg = invoice
+        .signing_message(SignatureScheme::Merkle)
+        .map_err(|_| Musig2Error::InconsistentSerialization)?;
+    let aggregated_key =
+        aggregation.aggregated_key.x_only_public_key().0.serialize();
+
+    let mut rand = [0u8; 32];
+    rng.fill_bytes(&mut rand);
+    let nonce = nonce_gen(
+        rand,
+        Some(&keypair.secret_key()),
+        pubkey,
+        Some(&aggregated_key),
+        Some(msg.as_ref()),
+        &[],
+    );
+    rand.zeroize();
+    Ok(nonce)
+}
+
+/// BIP-327 `NonceGen` algorithm with all of its optional inputs.
+fn nonce_gen(
+    mut rand: [u8; 32],
+    secret_key: Option<&SecretKey>,
+    pubkey: PublicKey,
+    aggregated_key: Option<&[u8; 32]>,
+    msg: Option<&[u8]>,
+    extra_in: &[u8],
+) -> (SecretNonce, PublicNonce) {
+    if let Some(secret_key) = secret_key {
+        let aux = tagged_hash("MuSig/aux", &[&rand]);
+        let mut sk = secret_key.secret_bytes();
+        for (byte, (sk, aux)) in rand.iter_mut().zip(sk.iter().zip(aux)) {
+            *byte = sk ^ aux;
+        }
+        sk.zeroize();
+    }
+    let pk = pubkey.serialize();
+    let aggpk = aggregated_key.map(|key| &key[..]).unwrap_or_default();
+    let msg_prefixed = match msg {
+        None => vec![0],
+        Some(msg) => {
+            let mut data = vec![1];
+            data.extend((msg.len() as u64).to_be_bytes());
+            data.extend(msg);
+            data
+        }
+    };
+    let extra_len = (extra_in.len() as u32).to_be_bytes();
+    let nonce = |i: u8| {
+        let k = reduce(tagged_hash(
+            "MuSig/nonce",
+            &[
+                &rand,
+                &[pk.len() as u8],
+                &pk,
+                &[aggpk.len() as u8],
+                aggpk,
+                &msg_prefixed,
+                &extra_len,
+                extra_in,
+                &[i],
+            ],
+        ));
+        // Zero nonce happens with negligible probability
+        to_secret(k).expect("non-zero nonce")
+    };
+    let (k1, k2) = (nonce(0), nonce(1));
+    rand.zeroize();
+
+    let secp = secp256k1::Secp256k1::signing_only();
+    let public = PublicNonce {
+        r1: PublicKey::from_secret_key(&secp, &k1),
+        r2: PublicKey::from_secret_key(&secp, &k2),
+    };
+    let secret = SecretNonce {
+        k1: k1.secret_bytes(),
+        k2: k2.secret_bytes(),
+        pubkey,
+    };
+    (secret, public)
+}
+
+/// Sums public nonces of all participants (BIP-327 `NonceAgg`).
+fn aggregate_nonces(
+    nonces: &[PublicNonce],
+) -> Result<PublicNonce, Musig2Error> {
+    let r1 = nonces.iter().map(|nonce| &nonce.r1).collect::<Vec<_>>();
+    let r2 = nonces.iter().map(|nonce| &nonce.r2).collect::<Vec<_>>();
+    Ok(PublicNonce {
+        r1: PublicKey::combine_keys(&r1)?,
+        r2: PublicKey::combine_keys(&r2)?,
+    })
+}
+
+/// MuSig2 signing session over the invoice signature hash, which can be
+/// computed by each party from the public nonces of all operators.
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct Musig2Session {
+    aggregation: MerchantKeyAggregation,
+    msg: secp256k1::Message,
+    b: SecretKey,
+    e: SecretKey,
+    r: PublicKey,
+}
+
+impl Musig2Session {
+    /// Computes session values (BIP-327 `GetSessionValues`) for the message
+    /// from the public nonces of all participants.
+    fn new(
+        aggregation: &MerchantKeyAggregation,
+        nonces: &[PublicNonce],
+        msg: secp256k1::Message,
+    ) -> Result<Musig2Session, Musig2Error> {
+        let expected = aggregation.participants.len();
+        if nonces.len() != expected {
+            return Err(Musig2Error::ParticipantCount {
+                expected,
+                found: nonces.len(),
+            });
+        }
+
+        let PublicNonce { r1, r2 } = aggregate_nonces(nonces)?;
+        let q = aggregation.aggregated_key.x_only_public_key().0.serialize();
+
+        let b = to_secret(reduce(tagged_hash(
+            "MuSig/noncecoef",
+            &[&r1.serialize(), &r2.serialize(), &q, msg.as_ref()],
+        )))?;
+        let secp = secp256k1::Secp256k1::verification_only();
+        let r = r1.combine(&r2.mul_tweak(&secp, &b.into())?)?;
+        let e = to_secret(reduce(tagged_hash(
+            "BIP0340/challenge",
+            &[&r.x_only_public_key().0.serialize(), &q, msg.as_ref()],
+        )))?;
+
+        Ok(Musig2Session {
+            aggregation: aggregation.clone(),
+            msg,
+            b,
+            e,
+            r,
+        })
+    }
+
+    /// Produces partial signature of the operator, consuming its secret
+    /// nonce.
+    pub fn partial_sign(
+        &self,
+        secnonce: SecretNonce,
+        keypair: &KeyPair,
+    ) -> Result<PartialSignature, Musig2Error> {
+        let pubkey = keypair.public_key();
+        if secnonce.pubkey != pubkey {
+            return Err(Musig2Error::NonceKeyMismatch);
+        }
+        self.aggregation.check_participant(pubkey)?;
+
+        let mut k1 = SecretKey::from_slice(&secnonce.k1)?;
+        let mut k2 = SecretKey::from_slice(&secnonce.k2)?;
+        if !has_even_y(&self.r) {
+            k1 = k1.negate();
+            k2 = k2.negate();
+        }
+        let mut d = keypair.secret_key();
+        if !has_even_y(&self.aggregation.aggregated_key) {
+            d = d.negate();
+        }
+        let ead = d
+            .mul_tweak(&self.aggregation.coefficient(&pubkey))?
+            .mul_tweak(&Scalar::from(self.e))?;
+        let s = k2
+            .mul_tweak(&Scalar::from(self.b))?
+            .add_tweak(&Scalar::from(k1))?
+            .add_tweak(&Scalar::from(ead))?;
+        Ok(PartialSignature {
+            signer: pubkey,
+            s: Slice32::from_inner(s.secret_bytes()),
+        })
+    }
+
+    /// Checks partial signature against the public nonce of its signer,
+    /// allowing to detect operators failing the signing.
+    pub fn verify_partial(
+        &self,
+        partial: &PartialSignature,
+        nonce: &PublicNonce,
+    ) -> Result<(), Musig2Error> {
+        let pubkey = partial.signer;
+        self.aggregation.check_participant(pubkey)?;
+        let secp = secp256k1::Secp256k1::new();
+
+        let s = partial_scalar(partial)?;
+        let mut nonce_point = nonce
+            .r1
+            .combine(&nonce.r2.mul_tweak(&secp, &self.b.into())?)?;
+        if !has_even_y(&self.r) {
+            nonce_point = nonce_point.negate(&secp);
+        }
+        let mut key = pubkey;
+        if !has_even_y(&self.aggregation.aggregated_key) {
+            key = key.negate(&secp);
+        }
+        let key_point = key
+            .mul_tweak(&secp, &self.aggregation.coefficient(&pubkey))?
+            .mul_tweak(&secp, &self.e.into())?;
+        // Zero scalar and the point at infinity are represented with `None`
+        let expected = nonce_point.combine(&key_point).ok();
+        let actual = to_secret(s)
+            .ok()
+            .map(|s| PublicKey::from_secret_key(&secp, &s));
+        if actual != expected {
+            return Err(Musig2Error::InvalidSignature);
+        }
+        Ok(())
+    }
+
+    /// Aggregates partial signatures of all operators into BIP-340
+    /// signature, checking it under the aggregated key.
+    pub fn aggregate(
+        &self,
+        partials: &[PartialSignature],
+    ) -> Result<schnorr::Signature, Musig2Error> {
+        let expected = self.aggregation.participants.len();
+        if partials.len() != expected {
+            return Err(Musig2Error::ParticipantCount {
+                expected,
+                found: partials.len(),
+            });
+        }
+        let mut s = Scalar::ZERO;
+        for partial in partials {
+            s = add_scalars(s, partial_scalar(partial)?);
+        }
+
+        let mut data = self.r.x_only_public_key().0.serialize().to_vec();
+        data.extend(s.to_be_bytes());
+        let signature = schnorr::Signature::from_slice(&data)?;
+        let key = self.aggregation.aggregated_key.x_only_public_key().0;
+        secp256k1::Secp256k1::verification_only()
+            .verify_schnorr(&signature, &self.msg, &key)
+            .map_err(|_| Musig2Error::InvalidSignature)?;
+        Ok(signature)
+    }
+}
+
+impl Invoice {
+    /// Starts MuSig2 signing session over the invoice data, taking public
+    /// nonces of all operators.
+    pub fn sign_musig2_begin(
+        &self,
+        aggregation: &MerchantKeyAggregation,
+        nonces: &[PublicNonce],
+    ) -> Result<Musig2Session, Musig2Error> {
+        let msg = self
+            .signing_message(SignatureScheme::Merkle)
+            .map_err(|_| Musig2Error::InconsistentSerialization)?;
+        Musig2Session::new(aggregation, nonces, msg)
+    }
+
+    /// Completes MuSig2 signing session, putting the aggregated signature
+    /// into the invoice signature record. Fails if the invoice data were
+    /// changed since the session start.
+    pub fn sign_musig2_complete(
+        &mut self,
+        session: &Musig2Session,
+        partials: &[PartialSignature],
+    ) -> Result<(), Musig2Error> {
+        let msg = self
+            .signing_message(SignatureScheme::Merkle)
+            .map_err(|_| Musig2Error::InconsistentSerialization)?;
+        if msg != session.msg {
+            return Err(Musig2Error::SessionMismatch);
+        }
+        let signature = session.aggregate(partials)?;
+        self.set_signature(session.aggregation.aggregated_key, signature);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::str::FromStr;
+
+    use bitcoin::hashes::hex::{FromHex, ToHex};
+
+    use super::*;
+
+    fn key(hex: &str) -> PublicKey {
+        PublicKey::from_str(hex).unwrap()
+    }
+
+    fn nonce(hex: &str) -> PublicNonce {
+        PublicNonce {
+            r1: key(&hex[..66]),
+            r2: key(&hex[66..]),
+        }
+    }
+
+    // BIP-327 `key_agg_vectors.json`
+    #[test]
+    fn key_agg_vectors() {
+        let x1 = key("02F9308A019258C31049344F85F89D5229B531C845836F99B08601F113BCE036F9");
+        let x2 = key("03DFF1D77F2A671C5F36183726DB2341BE58FEAE1DA2DECED843240F7B502BA659");
+        let x3 = key("023590A94E768F8E1815C2F24B4D80A8E3149316C3518CE7B7AD338368D038CA66");
+        for (keys, expected) in [
+            (
+                [x1, x2, x3],
+                "90539EEDE565F5D054F32CC0C220126889ED1E5D193BAF15AEF344FE59D4610C",
+            ),
+            (
+                [x3, x2, x1],
+                "6204DE8B083426DC6EAF9502D27024D53FC826BF7D2012148A0575435DF54B2B",
+            ),
+        ] {
+            let aggregation = MerchantKeyAggregation::new(keys).unwrap();
+            assert_eq!(aggregation.participants(), keys);
+            assert_eq!(
+                aggregation.aggregated_key().x_only_public_key().0.to_hex(),
+                expected.to_lowercase()
+            );
+        }
+        assert_eq!(
+            MerchantKeyAggregation::new([x1, x2, x1]),
+            Err(Musig2Error::DuplicateParticipant(x1))
+        );
+        assert_eq!(
+            MerchantKeyAggregation::new([]),
+            Err(Musig2Error::NoParticipants)
+        );
+    }
+
+    // BIP-327 `nonce_gen_vectors.json`
+    #[test]
+    fn nonce_gen_vectors() {
+        let sk = SecretKey::from_slice(&[2; 32]).unwrap();
+        let pk = key("024D4B6CD1361032CA9BD2AEB9D900AA4D45D9EAD80AC9423374C451A7254D0766");
+        let (secnonce, pubnonce) = nonce_gen(
+            [0x0f; 32],
+            Some(&sk),
+            pk,
+            Some(&[0x07; 32]),
+            Some(&[0x01; 32]),
+            &[0x08; 32],
+        );
+        assert_eq!(secnonce.pubkey, pk);
+        assert_eq!(
+            secnonce.k1.to_hex(),
+            "b114e502beaa4e301dd08a50264172c84e41650e6cb726b410c0694d59effb64"
+        );
+        assert_eq!(
+            secnonce.k2.to_hex(),
+            "95b5caf28d045b973d63e3c99a44b807bde375fd6cb39e46dc4a511708d0e9d2"
+        );
+        let secp = secp256k1::Secp256k1::signing_only();
+        let k1 = SecretKey::from_slice(&secnonce.k1).unwrap();
+        assert_eq!(pubnonce.r1, PublicKey::from_secret_key(&secp, &k1));
+        assert!(!format!("{:?}", secnonce).contains(&secnonce.k1.to_hex()));
+    }
+
+    // BIP-327 `nonce_agg_vectors.json`
+    #[test]
+    fn nonce_agg_vectors() {
+        let pnonces = [
+            nonce("020151C80F435648DF67A22B749CD798CE54E0321D034B92B709B567D60A42E66603BA47FBC1834437B3212E89A84D8425E7BF12E0245D98262268EBDCB385D50641"),
+            nonce("03FF406FFD8ADB9CD29877E4985014F66A59F6CD01C0E88CAA8E5F3166B1F676A60248C264CDD57D3C24D79990B0F865674EB62A0F9018277A95011B41BFC193B833"),
+        ];
+        assert_eq!(
+            aggregate_nonces(&pnonces).unwrap(),
+            nonce("035FE1873B4F2967F52FEA4A06AD5A8ECCBE9D0FD73068012C894E2E87CCB5804B024725377345BDE0E9C33AF3C43C0A29A9249F2F2956FA8CFEB55C8573D0262DC8")
+        );
+    }
+
+    // BIP-327 `sign_verify_vectors.json`
+    #[test]
+    fn sign_vectors() {
+        let secp = secp256k1::Secp256k1::new();
+        let sk = SecretKey::from_str(
+            "7FB9E0E687ADA1EEBF7ECFE2F21E73EBDB51A7D450948DFE8D76D7F2D1007671",
+        )
+        .unwrap();
+        let keypair = KeyPair::from_secret_key(&secp, &sk);
+        let pubkeys = [
+            key("03935F972DA013F80AE011890FA89B67A27B7BE6CCB24D3274D18B2D4067F261A9"),
+            key("02F9308A019258C31049344F85F89D5229B531C845836F99B08601F113BCE036F9"),
+            key("02DFF1D77F2A671C5F36183726DB2341BE58FEAE1DA2DECED843240F7B502BA661"),
+        ];
+        assert_eq!(keypair.public_key(), pubkeys[0]);
+        let pnonces = [
+            nonce("0337C87821AFD50A8644D820A8F3E02E499C931865C2360FB43D0A0D20DAFE07EA0287BF891D2A6DEAEBADC909352AA9405D1428C15F4B75F04DAE642A95C2548480"),
+            nonce("0279BE667EF9DCBBAC55A06295CE870B07029BFCDB2DCE28D959F2815B16F817980279BE667EF9DCBBAC55A06295CE870B07029BFCDB2DCE28D959F2815B16F81798"),
+            nonce("032DE2662628C90B03F5E720284EB52FF7D71F4284F627B68A853D78C78E1FFE9303E4C5524E83FFE1493B9077CF1CA6BEB2090C93D930321071AD40B2F44E599046"),
+        ];
+        let k1 = SecretKey::from_str(
+            "508B81A611F100A6B2B6B29656590898AF488BCF2E1F55CF22E5CFB84421FE61",
+        )
+        .unwrap();
+        let k2 = SecretKey::from_str(
+            "FA27FD49B1D50085B481285E1CA205D55C82CC1B31FF5CD54A489829355901F7",
+        )
+        .unwrap();
+        let secnonce = || SecretNonce {
+            k1: k1.secret_bytes(),
+            k2: k2.secret_bytes(),
+            pubkey: pubkeys[0],
+        };
+        assert_eq!(PublicKey::from_secret_key(&secp, &k1), pnonces[0].r1);
+        assert_eq!(PublicKey::from_secret_key(&secp, &k2), pnonces[0].r2);
+        let msg = secp256k1::Message::from_slice(
+            &Vec::from_hex(
+                "F95466D086770E689964664219266FE5ED215C92AE20BAB5C9D79ADDDDF3C0CF",
+            )
+            .unwrap(),
+        )
+        .unwrap();
+
+        for (order, expected) in [
+            (
+                [0, 1, 2],
+                "012ABBCB52B3016AC03AD82395A1A415C48B93DEF78718E62A7A90052FE224FB",
+            ),
+            (
+                [1, 0, 2],
+                "9FF2F7AAA856150CC8819254218D3ADEEB0535269051897724F9DB3789513A52",
+            ),
+            (
+                [1, 2, 0],
+                "FA23C359F6FAC4E7796BB93BC9F0532A95468C539BA20FF86D7C76ED92227900",
+            ),
+        ] {
+            let aggregation =
+                MerchantKeyAggregation::new(order.map(|no| pubkeys[no]))
+                    .unwrap();
+            let session = Musig2Session::new(
+                &aggregation,
+                &order.map(|no| pnonces[no]),
+                msg,
+            )
+            .unwrap();
+            let partial = session.partial_sign(secnonce(), &keypair).unwrap();
+            assert_eq!(partial.s.to_hex(), expected.to_lowercase());
+            session.verify_partial(&partial, &pnonces[0]).unwrap();
+        }
+    }
+
+    // BIP-327 `sig_agg_vectors.json`
+    #[test]
+    fn sig_agg_vectors() {
+        let pubkeys = [
+            key("03935F972DA013F80AE011890FA89B67A27B7BE6CCB24D3274D18B2D4067F261A9"),
+            key("02D2DC6F5DF7C56ACF38C7FA0AE7A759AE30E19B37359DFDE015872324C7EF6E05"),
+        ];
+        let pnonces = [
+            nonce("036E5EE6E28824029FEA3E8A9DDD2C8483F5AF98F7177C3AF3CB6F47CAF8D94AE902DBA67E4A1F3680826172DA15AFB1A8CA85C7C5CC88900905C8DC8C328511B53E"),
+            nonce("03E4F798DA48A76EEC1C9CC5AB7A880FFBA201A5F064E627EC9CB0031D1D58FC5103E06180315C5A522B7EC7C08B69DCD721C313C940819296D0A7AB8E8795AC1F00"),
+        ];
+        assert_eq!(
+            aggregate_nonces(&pnonces).unwrap(),
+            nonce("0341432722C5CD0268D829C702CF0D1CBCE57033EED201FD335191385227C3210C03D377F2D258B64AADC0E16F26462323D701D286046A2EA93365656AFD9875982B")
+        );
+        let partials = [
+            "B15D2CD3C3D22B04DAE438CE653F6B4ECF042F42CFDED7C41B64AAF9B4AF53FB",
+            "6193D6AC61B354E9105BBDC8937A3454A6D705B6D57322A5A472A02CE99FCB64",
+        ];
+        let partials = [0, 1].map(|no| PartialSignature {
+            signer: pubkeys[no],
+            s: Slice32::from_slice(Vec::from_hex(partials[no]).unwrap())
+                .unwrap(),
+        });
+        let msg = secp256k1::Message::from_slice(
+            &Vec::from_hex(
+                "599C67EA410D005B9DA90817CF03ED3B1C868E4DA4EDF00A5880B0082C237869",
+            )
+            .unwrap(),
+        )
+        .unwrap();
+
+        let aggregation = MerchantKeyAggregation::new(pubkeys).unwrap();
+        let session = Musig2Session::new(&aggregation, &pnonces, msg).unwrap();
+        let signature = session.aggregate(&partials).unwrap();
+        assert_eq!(
+            signature.as_ref().to_hex(),
+            "041DA22223CE65C92C9A0D6C2CAC828AAF1EEE56304FEC371DDF91EBB2B9EF0912F1038025857FEDEB3FF696F8B99FA4BB2C5812F6095A2E0004EC99CE18DE1E".to_lowercase()
+        );
+
+        // Zero is a valid partial signature value
+        let sum = add_scalars(
+            partial_scalar(&partials[0]).unwrap(),
+            partial_scalar(&partials[1]).unwrap(),
+        );
+        let shifted = [
+            PartialSignature {
+                signer: pubkeys[0],
+                s: Slice32::from_inner(sum.to_be_bytes()),
+            },
+            PartialSignature {
+                signer: pubkeys[1],
+                s: Slice32::from_inner(Scalar::ZERO.to_be_bytes()),
+            },
+        ];
+        assert_eq!(session.aggregate(&shifted), Ok(signature));
+
+        // Values not below the curve order are rejected
+        let overflow = PartialSignature {
+            signer: pubkeys[1],
+            s: Slice32::from_inner(constants::CURVE_ORDER),
+        };
+        assert_eq!(
+            session.aggregate(&[partials[0], overflow]),
+            Err(Musig2Error::InvalidPartial(pubkeys[1]))
+        );
+        assert_eq!(
+            session.verify_partial(&overflow, &pnonces[1]),
+            Err(Musig2Error::InvalidPartial(pubkeys[1]))
+        );
+    }
+}
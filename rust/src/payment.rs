@@ -0,0 +1,548 @@
+// LNP/BP universal invoice library implementing LNPBP-38 standard
+// Written in 2021 by
+//     Dr. Maxim Orlovsky <orlovsky@pandoracore.com>
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the MIT License
+// along with this software.
+// If not, see <https://opensource.org/licenses/MIT>.
+
+//! Recognition of on-chain payments of invoices.
+
+use std::ops::Range;
+
+use amplify::Wrapper;
+use bitcoin::hashes::{sha256, Hash, HashEngine};
+use bitcoin::secp256k1::{self, Scalar, Secp256k1, VerifyOnly};
+use bitcoin::{Address, Script, Transaction};
+use miniscript::{descriptor::DescriptorPublicKey, Descriptor};
+use wallet::psbt::{Output, Psbt};
+
+use crate::{
+    is_native_asset, AmountError, AmountExt, Beneficiary, BeneficiaryKind,
+    Invoice, Network, OnchainPolicy,
+};
+
+/// The first hardened derivation index, which can't be used for deriving
+/// public keys from descriptors
+pub(crate) const HARDENED_INDEX: u32 = 1 << 31;
+
+/// Tag of the pay-to-contract tweaks committing to the invoice payment
+/// reference
+pub const REFERENCE_TWEAK_TAG: &str = "LNPBP38:reference";
+
+/// Errors collecting output scripts of the invoice beneficiaries.
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug, Display, Error)]
+#[display(doc_comments)]
+pub enum ScriptError {
+    /// beneficiary descriptor has a wildcard, so the range of derivation
+    /// indexes must be provided
+    RangeRequired,
+
+    /// derivation index {0} is hardened, which can't be used with public
+    /// keys
+    HardenedIndex(u32),
+
+    /// beneficiary descriptor requires hardened derivation, which can't be
+    /// done with public keys
+    HardenedDerivation,
+
+    /// invoice has no beneficiaries paid with on-chain outputs; the main
+    /// beneficiary is {0}
+    NoScripts(BeneficiaryKind),
+}
+
+/// Errors deriving payment address from the invoice beneficiary.
+#[derive(Clone, Eq, PartialEq, Debug, Display, Error)]
+#[display(doc_comments)]
+pub enum DeriveError {
+    /// invoice beneficiary is {0} and not a descriptor
+    NotDescriptor(BeneficiaryKind),
+
+    /// derivation index {0} is hardened, which can't be used with public
+    /// keys
+    HardenedIndex(u32),
+
+    /// beneficiary descriptor requires hardened derivation, which can't be
+    /// done with public keys
+    HardenedDerivation,
+
+    /// derivation index {0} lies outside of the range allowed by the invoice
+    IndexOutOfRange(u32),
+
+    /// beneficiary descriptor has no address form: {0}
+    NoAddress(String),
+
+    /// address for {address} can't be used with the invoice issued for
+    /// {invoice} network
+    NetworkMismatch {
+        invoice: Network,
+        address: bitcoin::Network,
+    },
+}
+
+/// Errors validating PSBT beneficiaries and applying them to payer PSBTs.
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug, Display, Error, From)]
+#[display(doc_comments)]
+pub enum PsbtTemplateError {
+    /// invoice beneficiary is {0} and not a PSBT
+    NotPsbt(BeneficiaryKind),
+
+    /// PSBT template has {0} inputs, while invoices must not spend payer
+    /// funds
+    TemplateInputs(usize),
+
+    /// PSBT template outputs pay {outputs} sats, while the invoice requires
+    /// {invoice} sats
+    AmountMismatch { outputs: u64, invoice: u64 },
+
+    /// invoice amount can't be computed: {0}
+    #[from]
+    Amount(AmountError),
+
+    /// payer PSBT has a proprietary key of the template with a different
+    /// value
+    ProprietaryConflict,
+}
+
+/// Requirements of the invoice on-chain policy not met by a payment
+/// transaction.
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug, Display)]
+#[display(doc_comments)]
+pub enum PolicyViolation {
+    /// transaction feerate {feerate} sat/vB is below {required} sat/vB
+    /// required by the invoice
+    Feerate { feerate: u32, required: u32 },
+
+    /// transaction does not signal replaceability required by the invoice
+    RbfRequired,
+
+    /// transaction signals replaceability not allowed by the invoice
+    RbfForbidden,
+}
+
+/// Result of matching a transaction against an invoice.
+#[derive(Clone, Eq, PartialEq, Hash, Debug, Display)]
+#[display(doc_comments)]
+pub enum PaymentMatch {
+    /// transaction pays {amount} sats to the invoice beneficiaries
+    Paid {
+        /// Indexes of the transaction outputs paying the invoice
+        output_indexes: Vec<u32>,
+        amount: u64,
+    },
+
+    /// transaction pays {received} sats to the invoice beneficiaries, while
+    /// {expected} sats are required
+    Underpaid { received: u64, expected: u64 },
+
+    /// transaction pays {amount} sats to the invoice beneficiaries, violating
+    /// the invoice on-chain policy: {violation}
+    PolicyViolation {
+        /// Indexes of the transaction outputs paying the invoice
+        output_indexes: Vec<u32>,
+        amount: u64,
+        violation: PolicyViolation,
+    },
+
+    /// transaction does not pay the invoice
+    NoMatch,
+}
+
+impl Invoice {
+    /// Checks whether the transaction pays the invoice, summing up all
+    /// outputs paying to the main and alternative beneficiaries and
+    /// comparing the sum with [`Invoice::total_amount`] for the default
+    /// quantity.
+    ///
+    /// Only address and descriptor beneficiaries are recognized. Ranged
+    /// descriptors are derived at each index of the `derivation_range`
+    /// allowed by the invoice, and are not matched if no range is given by
+    /// either the caller or the tracking descriptor. Invoices in assets other
+    /// than bitcoin never match; invoices for any amount match any positive
+    /// payment.
+    ///
+    /// The invoice on-chain policy is checked for the RBF signalling only;
+    /// use [`Invoice::matches_tx_with_feerate`] to check the feerate.
+    pub fn matches_tx(
+        &self,
+        tx: &Transaction,
+        derivation_range: Option<Range<u32>>,
+    ) -> PaymentMatch {
+        self.matches_tx_with_feerate(tx, derivation_range, None)
+    }
+
+    /// Checks whether the transaction pays the invoice like
+    /// [`Invoice::matches_tx`], additionally checking the transaction
+    /// `feerate` (in sat/vB, rounded down), if known, against the invoice
+    /// on-chain policy.
+    pub fn matches_tx_with_feerate(
+        &self,
+        tx: &Transaction,
+        derivation_range: Option<Range<u32>>,
+        feerate: Option<u32>,
+    ) -> PaymentMatch {
+        if let Some(asset) = self.asset {
+            if !is_native_asset(&asset, None) {
+                return PaymentMatch::NoMatch;
+            }
+        }
+        let expected = match self.total_amount(None) {
+            Ok(AmountExt::Any) => 1,
+            Ok(AmountExt::Normal(amount)) => amount,
+            // On-chain payments can't pay fractions of a satoshi
+            Ok(AmountExt::Milli(amount, frac)) => {
+                amount.saturating_add(u64::from(frac > 0))
+            }
+            Err(_) => return PaymentMatch::NoMatch,
+        };
+
+        let scripts = self.payment_scripts(derivation_range);
+        let (output_indexes, outputs): (Vec<u32>, Vec<u64>) = tx
+            .output
+            .iter()
+            .enumerate()
+            .filter(|(_, output)| scripts.contains(&output.script_pubkey))
+            .map(|(index, output)| (index as u32, output.value))
+            .unzip();
+        if output_indexes.is_empty() {
+            return PaymentMatch::NoMatch;
+        }
+        let received = outputs
+            .into_iter()
+            .fold(0u64, |sum, value| sum.saturating_add(value));
+        if received < expected {
+            return PaymentMatch::Underpaid { received, expected };
+        }
+        let policy = self.onchain_policy().unwrap_or_default();
+        match policy.check_tx(tx, feerate) {
+            Ok(()) => PaymentMatch::Paid {
+                output_indexes,
+                amount: received,
+            },
+            Err(violation) => PaymentMatch::PolicyViolation {
+                output_indexes,
+                amount: received,
+                violation,
+            },
+        }
+    }
+
+    /// Returns output scripts which wallets must watch to detect payments
+    /// to the main and alternative invoice beneficiaries.
+    ///
+    /// Ranged descriptors are derived at each index of the
+    /// `derivation_range`; PSBT beneficiaries provide scripts of all their
+    /// outputs. Beneficiaries not paid with on-chain outputs (lightning,
+    /// blinded UTXO, unknown and others) are skipped. If none of the
+    /// beneficiaries has output scripts, [`ScriptError::NoScripts`] naming
+    /// the kind of the main beneficiary is returned instead of an empty
+    /// list, which is left for derivation ranges without indexes.
+    pub fn script_pubkeys(
+        &self,
+        derivation_range: Option<Range<u32>>,
+    ) -> Result<Vec<Script>, ScriptError> {
+        if let Some(range) = &derivation_range {
+            if range.end > HARDENED_INDEX {
+                return Err(ScriptError::HardenedIndex(range.end - 1));
+            }
+        }
+        let secp = Secp256k1::verification_only();
+        let mut scripts = vec![];
+        let mut found = false;
+        for beneficiary in self.beneficiaries() {
+            if let Some(list) = beneficiary_scripts(
+                beneficiary,
+                derivation_range.as_ref(),
+                &secp,
+            )? {
+                scripts.extend(list);
+                found = true;
+            }
+        }
+        if !found {
+            return Err(ScriptError::NoScripts(self.beneficiary().into()));
+        }
+        Ok(scripts)
+    }
+
+    /// Derives payment address from the main invoice beneficiary, which must
+    /// be a descriptor. The `index` is ignored for descriptors without
+    /// wildcards and must be allowed by the tracking descriptors.
+    ///
+    /// Multipath descriptors (BIP-389 `<0;1>` derivation steps) are not
+    /// supported by miniscript 9 used by the library, so they can't be
+    /// invoice beneficiaries and the external path can't be selected here;
+    /// issuers must put the external path descriptor into the invoice.
+    pub fn derive_address(
+        &self,
+        index: u32,
+        network: bitcoin::Network,
+    ) -> Result<Address, DeriveError> {
+        let (descriptor, tracking) = match self.beneficiary() {
+            Beneficiary::Descriptor(descriptor) => (descriptor, None),
+            Beneficiary::TrackingDescriptor(tracking) => {
+                (&tracking.descriptor, Some(tracking))
+            }
+            beneficiary => {
+                return Err(DeriveError::NotDescriptor(beneficiary.into()))
+            }
+        };
+        if let Some(invoice) = *self.network() {
+            if !invoice.matches_address_network(network) {
+                return Err(DeriveError::NetworkMismatch {
+                    invoice,
+                    address: network,
+                });
+            }
+        }
+        let index = if descriptor.has_wildcard() { index } else { 0 };
+        if index >= HARDENED_INDEX {
+            return Err(DeriveError::HardenedIndex(index));
+        }
+        match tracking {
+            Some(tracking)
+                if descriptor.has_wildcard() && !tracking.contains(index) =>
+            {
+                return Err(DeriveError::IndexOutOfRange(index))
+            }
+            _ => {}
+        }
+        descriptor
+            .derived_descriptor(&Secp256k1::verification_only(), index)
+            .map_err(|_| DeriveError::HardenedDerivation)?
+            .address(network)
+            .map_err(|err| DeriveError::NoAddress(err.to_string()))
+    }
+
+    /// Computes pay-to-contract tweak binding the key of a payment output to
+    /// the invoice payment reference, if any. The tweak is BIP-340 tagged
+    /// hash with [`REFERENCE_TWEAK_TAG`] of the compressed key followed by
+    /// the reference bytes.
+    pub fn reference_tweak(
+        &self,
+        pubkey: &secp256k1::PublicKey,
+    ) -> Option<sha256::Hash> {
+        let reference = self.reference().as_ref()?;
+        let tag = sha256::Hash::hash(REFERENCE_TWEAK_TAG.as_bytes());
+        let mut engine = sha256::Hash::engine();
+        engine.input(&tag[..]);
+        engine.input(&tag[..]);
+        engine.input(&pubkey.serialize());
+        engine.input(reference.as_inner());
+        Some(sha256::Hash::from_engine(engine))
+    }
+
+    /// Tweaks the key with [`Invoice::reference_tweak`], producing the key
+    /// of a payment output bound to the invoice reference, or `None` if the
+    /// invoice has no reference. The merchant spends the output with the
+    /// private key tweaked by the same value with
+    /// [`secp256k1::SecretKey::add_tweak`].
+    pub fn reference_tweaked_key(
+        &self,
+        pubkey: &secp256k1::PublicKey,
+    ) -> Result<Option<secp256k1::PublicKey>, secp256k1::Error> {
+        let tweak = match self.reference_tweak(pubkey) {
+            Some(tweak) => Scalar::from_be_bytes(tweak.into_inner())
+                .map_err(|_| secp256k1::Error::InvalidTweak)?,
+            None => return Ok(None),
+        };
+        pubkey
+            .add_exp_tweak(&Secp256k1::verification_only(), &tweak)
+            .map(Some)
+    }
+
+    /// Returns PSBT template of the main invoice beneficiary, if any.
+    pub fn psbt_template(&self) -> Option<&Psbt> {
+        match self.beneficiary() {
+            Beneficiary::Psbt(psbt) => Some(psbt),
+            _ => None,
+        }
+    }
+
+    /// Collects output scripts of address and descriptor beneficiaries,
+    /// skipping those which can't be derived.
+    fn payment_scripts(
+        &self,
+        derivation_range: Option<Range<u32>>,
+    ) -> Vec<Script> {
+        let secp = Secp256k1::verification_only();
+        let range = derivation_range
+            .map(|range| range.start..range.end.min(HARDENED_INDEX));
+        self.beneficiaries()
+            .filter(|beneficiary| {
+                matches!(
+                    beneficiary,
+                    Beneficiary::Address(_)
+                        | Beneficiary::Descriptor(_)
+                        | Beneficiary::TrackingDescriptor(_)
+                )
+            })
+            .filter_map(|beneficiary| {
+                beneficiary_scripts(beneficiary, range.as_ref(), &secp)
+                    .ok()
+                    .flatten()
+            })
+            .flatten()
+            .collect()
+    }
+}
+
+impl OnchainPolicy {
+    /// Checks transaction replaceability signalling and its `feerate` (in
+    /// sat/vB), if known, against the policy.
+    pub fn check_tx(
+        &self,
+        tx: &Transaction,
+        feerate: Option<u32>,
+    ) -> Result<(), PolicyViolation> {
+        if let (Some(feerate), Some(required)) =
+            (feerate, self.min_feerate_sat_vb)
+        {
+            if feerate < required {
+                return Err(PolicyViolation::Feerate { feerate, required });
+            }
+        }
+        match (self.rbf_required, tx.is_explicitly_rbf()) {
+            (Some(true), false) => Err(PolicyViolation::RbfRequired),
+            (Some(false), true) => Err(PolicyViolation::RbfForbidden),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Derives output scripts of a descriptor over the derivation range, which
+/// is required for descriptors with wildcards.
+fn descriptor_scripts(
+    descriptor: &Descriptor<DescriptorPublicKey>,
+    derivation_range: Option<Range<u32>>,
+    secp: &Secp256k1<VerifyOnly>,
+) -> Result<Vec<Script>, ScriptError> {
+    let range = match derivation_range {
+        _ if !descriptor.has_wildcard() => 0..1,
+        Some(range) => range,
+        None => return Err(ScriptError::RangeRequired),
+    };
+    range
+        .map(|index| {
+            descriptor
+                .derived_descriptor(secp, index)
+                .map(|derived| derived.script_pubkey())
+                .map_err(|_| ScriptError::HardenedDerivation)
+        })
+        .collect()
+}
+
+/// Returns output scripts of a beneficiary, or `None` if the beneficiary is
+/// not paid with on-chain outputs. Derivation range must not include
+/// hardened indexes.
+fn beneficiary_scripts(
+    beneficiary: &Beneficiary,
+    derivation_range: Option<&Range<u32>>,
+    secp: &Secp256k1<VerifyOnly>,
+) -> Result<Option<Vec<Script>>, ScriptError> {
+    Ok(Some(match beneficiary {
+        Beneficiary::Address(address) => vec![address.script_pubkey()],
+        Beneficiary::Descriptor(descriptor) => {
+            descriptor_scripts(descriptor, derivation_range.cloned(), secp)?
+        }
+        Beneficiary::TrackingDescriptor(tracking) => {
+            // Only indexes allowed by the invoice are derived
+            let range = match (derivation_range, &tracking.index_range) {
+                (Some(range), Some(allowed)) => Some(
+                    range.start.max(allowed.start)..range.end.min(allowed.end),
+                ),
+                (Some(range), None) => Some(range.clone()),
+                (None, allowed) => allowed.clone(),
+            };
+            descriptor_scripts(&tracking.descriptor, range, secp)?
+        }
+        Beneficiary::Psbt(psbt) => psbt
+            .outputs
+            .iter()
+            .map(|output| output.script.to_inner())
+            .collect(),
+        _ => return Ok(None),
+    }))
+}
+
+/// Checks that the main invoice beneficiary is a PSBT template without
+/// inputs, which outputs sum up to the invoice amount for the default
+/// quantity. Amounts of invoices in assets other than bitcoin and of
+/// invoices for any amount are not checked.
+pub fn validate_psbt_beneficiary(
+    invoice: &Invoice,
+) -> Result<(), PsbtTemplateError> {
+    checked_psbt_template(invoice).map(|_| ())
+}
+
+/// Appends outputs of the invoice PSBT template to the payer PSBT and copies
+/// global proprietary keys of the template. The template is checked against
+/// the invoice with [`validate_psbt_beneficiary`] first, and the payer PSBT
+/// is not modified on error.
+pub fn apply_psbt_template(
+    invoice: &Invoice,
+    payer_psbt: &mut Psbt,
+) -> Result<(), PsbtTemplateError> {
+    let template = checked_psbt_template(invoice)?;
+    if template.proprietary.iter().any(|(key, value)| {
+        matches!(payer_psbt.proprietary.get(key), Some(v) if v != value)
+    }) {
+        return Err(PsbtTemplateError::ProprietaryConflict);
+    }
+    payer_psbt.proprietary.extend(
+        template
+            .proprietary
+            .iter()
+            .map(|(key, value)| (key.clone(), value.clone())),
+    );
+    let offset = payer_psbt.outputs.len();
+    payer_psbt
+        .outputs
+        .extend(template.outputs.iter().enumerate().map(|(no, output)| {
+            // Output indexes must follow the existing payer outputs
+            let (v0, txout) = output.clone().split();
+            Output::with(offset + no, v0, txout)
+        }));
+    Ok(())
+}
+
+/// Returns PSBT template of the main invoice beneficiary after checking it
+/// with the rules of [`validate_psbt_beneficiary`].
+fn checked_psbt_template(
+    invoice: &Invoice,
+) -> Result<&Psbt, PsbtTemplateError> {
+    let template = invoice.psbt_template().ok_or_else(|| {
+        PsbtTemplateError::NotPsbt(invoice.beneficiary().into())
+    })?;
+    if !template.inputs.is_empty() {
+        return Err(PsbtTemplateError::TemplateInputs(template.inputs.len()));
+    }
+    if let Some(asset) = invoice.asset {
+        if !is_native_asset(&asset, None) {
+            return Ok(template);
+        }
+    }
+    let expected = match invoice.total_amount(None)? {
+        AmountExt::Any => return Ok(template),
+        AmountExt::Normal(amount) => amount,
+        AmountExt::Milli(amount, frac) => {
+            amount.saturating_add(u64::from(frac > 0))
+        }
+    };
+    let outputs = template
+        .outputs
+        .iter()
+        .fold(0u64, |sum, output| sum.saturating_add(output.amount));
+    if outputs != expected {
+        return Err(PsbtTemplateError::AmountMismatch {
+            outputs,
+            invoice: expected,
+        });
+    }
+    Ok(template)
+}
@@ -0,0 +1,135 @@
+// LNP/BP universal invoice library implementing LNPBP-38 standard
+// Written in 2021 by
+//     Dr. Maxim Orlovsky <orlovsky@pandoracore.com>
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the MIT License
+// along with this software.
+// If not, see <https://opensource.org/licenses/MIT>.
+
+use std::fmt::{self, Display, Formatter};
+use std::str::FromStr;
+
+use amplify::{Slice32, Wrapper};
+use bitcoin::secp256k1;
+use bitcoin::util::base58;
+
+/// Errors parsing BIP-47 payment code.
+#[derive(Clone, PartialEq, Eq, Debug, Display, Error, From)]
+#[display(doc_comments)]
+pub enum PaymentCodeError {
+    /// payment code has invalid base58check encoding: {0}
+    #[from]
+    Base58(base58::Error),
+
+    /// payment code has wrong prefix byte {0:#04x}; BIP-47 payment codes
+    /// must start with `0x47`
+    WrongPrefix(u8),
+
+    /// payment code payload must be exactly 80 bytes long
+    WrongLen,
+
+    /// payment code contains invalid public key
+    #[from(secp256k1::Error)]
+    InvalidKey,
+
+    /// payment code has non-zero reserved bytes
+    ReservedBytes,
+}
+
+/// Reusable BIP-47 payment code. Payer and receiver derive a unique address
+/// for each payment from the code after a notification transaction, so the
+/// code is suitable for recurrent payments.
+#[derive(
+    Copy,
+    Clone,
+    Ord,
+    PartialOrd,
+    Eq,
+    PartialEq,
+    Hash,
+    Debug,
+    StrictEncode,
+    StrictDecode,
+)]
+pub struct PaymentCode {
+    pub version: u8,
+    /// Feature byte; bit 0 signals support for bitmessage notification
+    pub features: u8,
+    pub public_key: secp256k1::PublicKey,
+    pub chain_code: Slice32,
+}
+
+impl PaymentCode {
+    /// Base58check version byte of serialized payment codes, producing
+    /// `PM8T...` strings.
+    pub const PREFIX: u8 = 0x47;
+    /// Length of the payment code payload, excluding the prefix byte
+    pub const PAYLOAD_LEN: usize = 80;
+
+    pub fn new(
+        public_key: secp256k1::PublicKey,
+        chain_code: Slice32,
+    ) -> PaymentCode {
+        PaymentCode {
+            version: 1,
+            features: 0,
+            public_key,
+            chain_code,
+        }
+    }
+
+    /// Returns 80-byte BIP-47 payload of the payment code.
+    pub fn to_payload(&self) -> [u8; Self::PAYLOAD_LEN] {
+        let mut payload = [0u8; Self::PAYLOAD_LEN];
+        payload[0] = self.version;
+        payload[1] = self.features;
+        payload[2..35].copy_from_slice(&self.public_key.serialize());
+        payload[35..67].copy_from_slice(self.chain_code.as_inner());
+        payload
+    }
+
+    /// Parses 80-byte BIP-47 payload of the payment code.
+    pub fn from_payload(payload: &[u8]) -> Result<Self, PaymentCodeError> {
+        if payload.len() != Self::PAYLOAD_LEN {
+            return Err(PaymentCodeError::WrongLen);
+        }
+        if payload[67..].iter().any(|byte| *byte != 0) {
+            return Err(PaymentCodeError::ReservedBytes);
+        }
+        let mut chain_code = [0u8; 32];
+        chain_code.copy_from_slice(&payload[35..67]);
+        Ok(PaymentCode {
+            version: payload[0],
+            features: payload[1],
+            public_key: secp256k1::PublicKey::from_slice(&payload[2..35])?,
+            chain_code: Slice32::from_inner(chain_code),
+        })
+    }
+}
+
+impl Display for PaymentCode {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let mut data = Vec::with_capacity(1 + Self::PAYLOAD_LEN);
+        data.push(Self::PREFIX);
+        data.extend_from_slice(&self.to_payload());
+        f.write_str(&base58::check_encode_slice(&data))
+    }
+}
+
+impl FromStr for PaymentCode {
+    type Err = PaymentCodeError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let data = base58::from_check(s)?;
+        match data.split_first() {
+            Some((&Self::PREFIX, payload)) => Self::from_payload(payload),
+            Some((prefix, _)) => Err(PaymentCodeError::WrongPrefix(*prefix)),
+            None => Err(PaymentCodeError::WrongLen),
+        }
+    }
+}
@@ -0,0 +1,611 @@
+// LNP/BP universal invoice library implementing LNPBP-38 standard
+// Written in 2021 by
+//     Dr. Maxim Orlovsky <orlovsky@pandoracore.com>
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the MIT License
+// along with this software.
+// If not, see <https://opensource.org/licenses/MIT>.
+
+//! Proofs of completed lightning payments and of control over the invoice
+//! beneficiary address.
+
+use amplify::{Slice32, Wrapper};
+use bitcoin::blockdata::opcodes::all::OP_RETURN;
+use bitcoin::blockdata::script::Builder;
+use bitcoin::hashes::{sha256, Hash, HashEngine};
+use bitcoin::schnorr::TapTweak;
+use bitcoin::secp256k1::{self, Secp256k1};
+use bitcoin::util::sighash::{Prevouts, SighashCache};
+use bitcoin::{
+    EcdsaSig, EcdsaSighashType, OutPoint, PackedLockTime, SchnorrSig,
+    SchnorrSighashType, Script, Sequence, Transaction, TxIn, TxOut, Txid,
+    Witness,
+};
+use bitcoin_scripts::hlc::HashPreimage;
+#[cfg(feature = "serde")]
+use serde_with::{As, DisplayFromStr};
+
+use crate::{Beneficiary, BeneficiaryKind, Invoice, InvoiceId, LnAddress};
+
+/// Errors verifying lightning payment preimages.
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug, Display, Error)]
+#[display(doc_comments)]
+pub enum ProofError {
+    /// invoice has no lightning beneficiaries; the main beneficiary is {0}
+    NoLightning(BeneficiaryKind),
+
+    /// preimage does not match payment hash of any invoice lightning
+    /// beneficiary
+    WrongPreimage,
+
+    /// payment proof is made for invoice {proof}, while invoice {invoice} is
+    /// provided
+    InvoiceMismatch {
+        proof: InvoiceId,
+        invoice: InvoiceId,
+    },
+
+    /// invoice has no beneficiary address proof
+    NoAddressProof,
+
+    /// main invoice beneficiary {0} has no single address which control can
+    /// be proven
+    NoAddress(BeneficiaryKind),
+
+    /// beneficiary address proofs are supported only for P2WPKH and P2TR
+    /// addresses
+    UnsupportedAddress,
+
+    /// signing key does not control the beneficiary address
+    WrongKey,
+
+    /// beneficiary address proof does not match the address or invoice id
+    InvalidAddressProof,
+
+    /// beneficiary address proof signature uses sighash type {0:#04x}, which
+    /// does not commit to the whole proof transaction
+    SighashType(u32),
+}
+
+/// Proof of a completed lightning payment of an invoice, which merchants
+/// may archive: the payment hash preimage revealed to the payer together
+/// with the id of the paid invoice.
+#[cfg_attr(
+    feature = "serde",
+    serde_as,
+    derive(Serialize, Deserialize),
+    serde(crate = "serde_crate", rename_all = "camelCase")
+)]
+#[derive(
+    Clone,
+    Ord,
+    PartialOrd,
+    Eq,
+    PartialEq,
+    Hash,
+    Debug,
+    StrictEncode,
+    StrictDecode,
+)]
+pub struct PaymentProof {
+    pub invoice_id: InvoiceId,
+    #[cfg_attr(feature = "serde", serde(with = "As::<DisplayFromStr>"))]
+    pub preimage: HashPreimage,
+    /// Note provided by the payer
+    pub payer_note: Option<String>,
+}
+
+impl PaymentProof {
+    /// Constructs payment proof, checking the preimage against the invoice
+    /// lightning beneficiaries.
+    pub fn new(
+        invoice: &Invoice,
+        preimage: [u8; 32],
+        payer_note: Option<String>,
+    ) -> Result<PaymentProof, ProofError> {
+        invoice.verify_payment_preimage(&preimage)?;
+        Ok(PaymentProof {
+            invoice_id: invoice.invoice_id(),
+            preimage: HashPreimage::from_inner(Slice32::from_inner(preimage)),
+            payer_note,
+        })
+    }
+
+    /// Checks that the proof is made for the invoice and that its preimage
+    /// matches one of the invoice lightning beneficiaries.
+    pub fn verify(&self, invoice: &Invoice) -> Result<(), ProofError> {
+        let invoice_id = invoice.invoice_id();
+        if self.invoice_id != invoice_id {
+            return Err(ProofError::InvoiceMismatch {
+                proof: self.invoice_id,
+                invoice: invoice_id,
+            });
+        }
+        invoice
+            .verify_payment_preimage(&self.preimage.into_inner().into_inner())
+    }
+}
+
+impl LnAddress {
+    /// Checks that SHA256 hash of the preimage matches the payment hash;
+    /// always fails for PTLC point locks.
+    pub fn verify_preimage(&self, preimage: &[u8; 32]) -> bool {
+        self.lock.verify_preimage(preimage)
+    }
+}
+
+impl Invoice {
+    /// Checks the preimage revealed by a lightning payment against the
+    /// payment hashes of the invoice lightning beneficiaries.
+    pub fn verify_payment_preimage(
+        &self,
+        preimage: &[u8; 32],
+    ) -> Result<(), ProofError> {
+        let mut addresses = self.lightning_beneficiaries().peekable();
+        if addresses.peek().is_none() {
+            return Err(ProofError::NoLightning(self.beneficiary().into()));
+        }
+        if addresses.any(|address| address.verify_preimage(preimage)) {
+            Ok(())
+        } else {
+            Err(ProofError::WrongPreimage)
+        }
+    }
+}
+
+/// Tag of BIP-322 message hashes
+const BIP322_TAG: &[u8] = b"BIP0322-signed-message";
+
+/// BIP-322 "simple" signature proving control over the main invoice
+/// beneficiary address, independent of the invoice signature key. The
+/// signed message is the string representation of the invoice id, so the
+/// proof can be made by wallets supporting BIP-322 message signing.
+#[cfg_attr(
+    feature = "serde",
+    serde_as,
+    derive(Serialize, Deserialize),
+    serde(crate = "serde_crate", rename_all = "camelCase")
+)]
+#[derive(
+    Clone,
+    Ord,
+    PartialOrd,
+    Eq,
+    PartialEq,
+    Hash,
+    Debug,
+    Default,
+    StrictEncode,
+    StrictDecode,
+)]
+pub struct AddressProof {
+    /// Witness stack of the BIP-322 `to_sign` transaction input
+    #[cfg_attr(
+        feature = "serde",
+        serde(with = "As::<Vec<serde_with::hex::Hex>>")
+    )]
+    pub witness: Vec<Vec<u8>>,
+}
+
+impl AddressProof {
+    pub fn with(witness: &Witness) -> AddressProof {
+        AddressProof {
+            witness: witness.to_vec(),
+        }
+    }
+
+    /// Returns witness stack of the proof, which consensus serialization is
+    /// the BIP-322 simple signature.
+    pub fn to_witness(&self) -> Witness {
+        Witness::from_vec(self.witness.clone())
+    }
+}
+
+/// Constructs BIP-322 `to_sign` transaction for a message and the script of
+/// the address which control is proven.
+fn bip322_to_sign(script_pubkey: &Script, message: &[u8]) -> Transaction {
+    let tag = sha256::Hash::hash(BIP322_TAG);
+    let mut engine = sha256::Hash::engine();
+    engine.input(&tag[..]);
+    engine.input(&tag[..]);
+    engine.input(message);
+    let message_hash = sha256::Hash::from_engine(engine);
+
+    let to_spend = Transaction {
+        version: 0,
+        lock_time: PackedLockTime::ZERO,
+        input: vec![TxIn {
+            previous_output: OutPoint::new(Txid::all_zeros(), 0xFFFFFFFF),
+            script_sig: Builder::new()
+                .push_int(0)
+                .push_slice(&message_hash[..])
+                .into_script(),
+            sequence: Sequence::ZERO,
+            witness: Witness::new(),
+        }],
+        output: vec![TxOut {
+            value: 0,
+            script_pubkey: script_pubkey.clone(),
+        }],
+    };
+    Transaction {
+        version: 0,
+        lock_time: PackedLockTime::ZERO,
+        input: vec![TxIn {
+            previous_output: OutPoint::new(to_spend.txid(), 0),
+            script_sig: Script::new(),
+            sequence: Sequence::ZERO,
+            witness: Witness::new(),
+        }],
+        output: vec![TxOut {
+            value: 0,
+            script_pubkey: Builder::new().push_opcode(OP_RETURN).into_script(),
+        }],
+    }
+}
+
+impl Invoice {
+    /// Returns output script of the main beneficiary which control is
+    /// proven by [`AddressProof`]: either an address or a descriptor without
+    /// wildcards.
+    fn proof_script(&self) -> Result<Script, ProofError> {
+        let script = match self.beneficiary() {
+            Beneficiary::Address(address) => address.script_pubkey(),
+            Beneficiary::Descriptor(descriptor)
+                if !descriptor.has_wildcard() =>
+            {
+                descriptor
+                    .derived_descriptor(&Secp256k1::verification_only(), 0)
+                    .map_err(|_| {
+                        ProofError::NoAddress(self.beneficiary().into())
+                    })?
+                    .script_pubkey()
+            }
+            beneficiary => {
+                return Err(ProofError::NoAddress(beneficiary.into()))
+            }
+        };
+        if !script.is_v0_p2wpkh() && !script.is_v1_p2tr() {
+            return Err(ProofError::UnsupportedAddress);
+        }
+        Ok(script)
+    }
+
+    /// Signs the invoice id with the key controlling P2WPKH or key-path
+    /// P2TR main beneficiary address (for P2TR the key is the internal one,
+    /// without a script tree) and attaches the resulting proof.
+    pub fn sign_address_proof(
+        &mut self,
+        secret_key: &secp256k1::SecretKey,
+    ) -> Result<(), ProofError> {
+        let script = self.proof_script()?;
+        let witness = bip322_sign(
+            &script,
+            self.invoice_id().to_string().as_bytes(),
+            secret_key,
+        )?;
+        self.attach_address_proof(AddressProof { witness });
+        Ok(())
+    }
+
+    /// Verifies beneficiary address proof with BIP-322 "simple" verification
+    /// for P2WPKH and key-path P2TR addresses. Invoices without the proof
+    /// fail with [`ProofError::NoAddressProof`], which must be treated as the
+    /// absence of the proof and not as an invalid one.
+    ///
+    /// Signatures must commit to the whole `to_sign` transaction, so P2WPKH
+    /// signatures must use `SIGHASH_ALL` and P2TR signatures
+    /// `SIGHASH_DEFAULT` or `SIGHASH_ALL`; other sighash types are rejected
+    /// with [`ProofError::SighashType`].
+    pub fn verify_address_proof(&self) -> Result<(), ProofError> {
+        let proof = self
+            .beneficiary_proof()
+            .as_ref()
+            .ok_or(ProofError::NoAddressProof)?;
+        let script = self.proof_script()?;
+        bip322_verify(
+            &script,
+            self.invoice_id().to_string().as_bytes(),
+            &proof.witness,
+        )
+    }
+}
+
+/// Produces BIP-322 "simple" signature witness for a message with the key
+/// controlling P2WPKH or key-path P2TR output script.
+fn bip322_sign(
+    script: &Script,
+    message: &[u8],
+    secret_key: &secp256k1::SecretKey,
+) -> Result<Vec<Vec<u8>>, ProofError> {
+    let to_sign = bip322_to_sign(script, message);
+    let mut cache = SighashCache::new(&to_sign);
+    let secp = Secp256k1::new();
+
+    if script.is_v0_p2wpkh() {
+        let pubkey = bitcoin::PublicKey::new(
+            secp256k1::PublicKey::from_secret_key(&secp, secret_key),
+        );
+        let wpkh = pubkey.wpubkey_hash().expect("compressed key");
+        if &Script::new_v0_p2wpkh(&wpkh) != script {
+            return Err(ProofError::WrongKey);
+        }
+        let script_code = script.p2wpkh_script_code().expect("P2WPKH script");
+        let sighash = cache
+            .segwit_signature_hash(0, &script_code, 0, EcdsaSighashType::All)
+            .expect("single input");
+        let msg = secp256k1::Message::from_slice(&sighash[..])
+            .expect("sighash is a 32-byte hash");
+        let sig = EcdsaSig::sighash_all(secp.sign_ecdsa(&msg, secret_key));
+        Ok(vec![sig.to_vec(), pubkey.to_bytes()])
+    } else {
+        let keypair = secp256k1::KeyPair::from_secret_key(&secp, secret_key);
+        let tweaked = keypair.tap_tweak(&secp, None).to_inner();
+        let output_key = tweaked.x_only_public_key().0;
+        if script[2..] != output_key.serialize()[..] {
+            return Err(ProofError::WrongKey);
+        }
+        let prevout = TxOut {
+            value: 0,
+            script_pubkey: script.clone(),
+        };
+        let sighash = cache
+            .taproot_key_spend_signature_hash(
+                0,
+                &Prevouts::All(&[prevout]),
+                SchnorrSighashType::Default,
+            )
+            .expect("single input");
+        let msg = secp256k1::Message::from_slice(&sighash[..])
+            .expect("sighash is a 32-byte hash");
+        let sig = SchnorrSig {
+            sig: secp.sign_schnorr(&msg, &tweaked),
+            hash_ty: SchnorrSighashType::Default,
+        };
+        Ok(vec![sig.to_vec()])
+    }
+}
+
+/// Verifies BIP-322 "simple" signature witness of a message for P2WPKH or
+/// key-path P2TR output script.
+fn bip322_verify(
+    script: &Script,
+    message: &[u8],
+    witness: &[Vec<u8>],
+) -> Result<(), ProofError> {
+    let to_sign = bip322_to_sign(script, message);
+    let mut cache = SighashCache::new(&to_sign);
+    let secp = Secp256k1::verification_only();
+    fn invalid<E>(_: E) -> ProofError {
+        ProofError::InvalidAddressProof
+    }
+
+    match witness {
+        [sig, pubkey] if script.is_v0_p2wpkh() => {
+            let sig = EcdsaSig::from_slice(sig).map_err(invalid)?;
+            if sig.hash_ty != EcdsaSighashType::All {
+                return Err(ProofError::SighashType(sig.hash_ty.to_u32()));
+            }
+            let pubkey =
+                bitcoin::PublicKey::from_slice(pubkey).map_err(invalid)?;
+            let wpkh = pubkey
+                .wpubkey_hash()
+                .ok_or(ProofError::InvalidAddressProof)?;
+            if &Script::new_v0_p2wpkh(&wpkh) != script {
+                return Err(ProofError::InvalidAddressProof);
+            }
+            let script_code =
+                script.p2wpkh_script_code().expect("P2WPKH script");
+            let sighash = cache
+                .segwit_signature_hash(0, &script_code, 0, sig.hash_ty)
+                .expect("single input");
+            let msg = secp256k1::Message::from_slice(&sighash[..])
+                .expect("sighash is a 32-byte hash");
+            secp.verify_ecdsa(&msg, &sig.sig, &pubkey.inner)
+                .map_err(invalid)
+        }
+        [sig] if script.is_v1_p2tr() => {
+            let sig = SchnorrSig::from_slice(sig).map_err(invalid)?;
+            if !matches!(
+                sig.hash_ty,
+                SchnorrSighashType::Default | SchnorrSighashType::All
+            ) {
+                return Err(ProofError::SighashType(sig.hash_ty as u32));
+            }
+            let output_key =
+                secp256k1::XOnlyPublicKey::from_slice(&script[2..])
+                    .map_err(invalid)?;
+            let prevout = TxOut {
+                value: 0,
+                script_pubkey: script.clone(),
+            };
+            let sighash = cache
+                .taproot_key_spend_signature_hash(
+                    0,
+                    &Prevouts::All(&[prevout]),
+                    sig.hash_ty,
+                )
+                .map_err(invalid)?;
+            let msg = secp256k1::Message::from_slice(&sighash[..])
+                .expect("sighash is a 32-byte hash");
+            secp.verify_schnorr(&sig.sig, &msg, &output_key)
+                .map_err(invalid)
+        }
+        _ => Err(ProofError::InvalidAddressProof),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::str::FromStr;
+
+    use bitcoin::hashes::hex::FromHex;
+    use bitcoin::{Address, PrivateKey};
+
+    use super::*;
+
+    // Test vectors from BIP-322
+    const WIF: &str = "L3VFeEujGtevx9w18HD1fhRbCH67Az2dpCymeRE1SoPK6XQtaN2k";
+    const P2WPKH: &str = "bc1q9vza2e8x573nczrlzms0wvx3gsqjx7vavgkx0l";
+    const P2TR: &str =
+        "bc1ppv609nr0vr25u07u95waq5lucwfm6tde4nydujnu8npg4q75mr5sxq8lt3";
+    const PUBKEY: &str =
+        "02c7f12003196442943d8588e01aee840423cc54fc1521526a3b85c2b0cbd58872";
+
+    fn script_pubkey(address: &str) -> Script {
+        Address::from_str(address).unwrap().script_pubkey()
+    }
+
+    fn witness(items: &[&str]) -> Vec<Vec<u8>> {
+        items
+            .iter()
+            .map(|item| Vec::from_hex(item).unwrap())
+            .collect()
+    }
+
+    fn secret_key() -> secp256k1::SecretKey {
+        PrivateKey::from_wif(WIF).unwrap().inner
+    }
+
+    #[test]
+    fn to_sign_transaction() {
+        for (message, to_spend, to_sign) in [
+            (
+                &b""[..],
+                "c5680aa69bb8d860bf82d4e9cd3504b55dde018de765a91bb566283c545a99a7",
+                "1e9654e951a5ba44c8604c4de6c67fd78a27e81dcadcfe1edf638ba3aaebaed6",
+            ),
+            (
+                &b"Hello World"[..],
+                "b79d196740ad5217771c1098fc4a4b51e0535c32236c71f1ea4d61a2d603352b",
+                "88737ae86f2077145f93cc4b153ae9a1cb8d56afa511988c149c5c8c9d93bddf",
+            ),
+        ] {
+            let tx = bip322_to_sign(&script_pubkey(P2WPKH), message);
+            assert_eq!(tx.input[0].previous_output.txid.to_string(), to_spend);
+            assert_eq!(tx.txid().to_string(), to_sign);
+        }
+    }
+
+    #[test]
+    fn p2wpkh_vectors() {
+        for (message, sig) in [
+            (
+                &b""[..],
+                "30440220336801010aaf657d79662cac98a990a43ac6f376af2c84f8f76401ccb9d0231602201693a4e683db4a91944ca5cb11527840366daf583a2c695fccf8e93483b52e3401",
+            ),
+            (
+                &b"Hello World"[..],
+                "304402206517c8637a7bfc3a154edcba6196d64bbd5b73955cb7da7d1626bcdde466c364022022bf10d19fc0bb69b4596e306b362acaa835293cf693bb176f7324b531f5afec01",
+            ),
+        ] {
+            let script = script_pubkey(P2WPKH);
+            bip322_verify(&script, message, &witness(&[sig, PUBKEY])).unwrap();
+            assert_eq!(
+                bip322_verify(&script, b"Hello", &witness(&[sig, PUBKEY])),
+                Err(ProofError::InvalidAddressProof)
+            );
+
+            let signed = bip322_sign(&script, message, &secret_key()).unwrap();
+            assert_eq!(signed[1], witness(&[PUBKEY])[0]);
+            bip322_verify(&script, message, &signed).unwrap();
+        }
+    }
+
+    #[test]
+    fn p2tr_vectors() {
+        let script = script_pubkey(P2TR);
+        let sig = "ddebd3eb25012ffa82937d9f25f9644e047bb2f472ab6c5089bbb53588ada2884cb5bcc53911f32d8dcf9548733b694d120db6a4e485194559e8d8fe668d269f01";
+        bip322_verify(&script, b"Hello World", &witness(&[sig])).unwrap();
+        assert_eq!(
+            bip322_verify(&script, b"", &witness(&[sig])),
+            Err(ProofError::InvalidAddressProof)
+        );
+
+        for message in [&b""[..], b"Hello World"] {
+            let signed = bip322_sign(&script, message, &secret_key()).unwrap();
+            assert_eq!(signed.len(), 1);
+            assert_eq!(signed[0].len(), 64);
+            bip322_verify(&script, message, &signed).unwrap();
+        }
+    }
+
+    #[test]
+    fn wrong_key_is_rejected() {
+        let key = secp256k1::SecretKey::from_slice(&[1u8; 32]).unwrap();
+        for address in [P2WPKH, P2TR] {
+            assert_eq!(
+                bip322_sign(&script_pubkey(address), b"", &key),
+                Err(ProofError::WrongKey)
+            );
+        }
+    }
+
+    #[test]
+    fn sighash_types_are_restricted() {
+        let secp = Secp256k1::new();
+        let secret_key = secret_key();
+
+        let script = script_pubkey(P2WPKH);
+        let to_sign = bip322_to_sign(&script, b"");
+        let script_code = script.p2wpkh_script_code().unwrap();
+        for hash_ty in [
+            EcdsaSighashType::None,
+            EcdsaSighashType::Single,
+            EcdsaSighashType::AllPlusAnyoneCanPay,
+        ] {
+            let sighash = SighashCache::new(&to_sign)
+                .segwit_signature_hash(0, &script_code, 0, hash_ty)
+                .unwrap();
+            let msg = secp256k1::Message::from_slice(&sighash[..]).unwrap();
+            let sig = EcdsaSig {
+                sig: secp.sign_ecdsa(&msg, &secret_key),
+                hash_ty,
+            };
+            assert_eq!(
+                bip322_verify(
+                    &script,
+                    b"",
+                    &[sig.to_vec(), witness(&[PUBKEY]).remove(0)]
+                ),
+                Err(ProofError::SighashType(hash_ty.to_u32()))
+            );
+        }
+
+        let script = script_pubkey(P2TR);
+        let to_sign = bip322_to_sign(&script, b"");
+        let keypair = secp256k1::KeyPair::from_secret_key(&secp, &secret_key);
+        let tweaked = keypair.tap_tweak(&secp, None).to_inner();
+        let prevout = TxOut {
+            value: 0,
+            script_pubkey: script.clone(),
+        };
+        for hash_ty in [
+            SchnorrSighashType::None,
+            SchnorrSighashType::Single,
+            SchnorrSighashType::AllPlusAnyoneCanPay,
+        ] {
+            let sighash = SighashCache::new(&to_sign)
+                .taproot_key_spend_signature_hash(
+                    0,
+                    &Prevouts::All(std::slice::from_ref(&prevout)),
+                    hash_ty,
+                )
+                .unwrap();
+            let msg = secp256k1::Message::from_slice(&sighash[..]).unwrap();
+            let sig = SchnorrSig {
+                sig: secp.sign_schnorr(&msg, &tweaked),
+                hash_ty,
+            };
+            assert_eq!(
+                bip322_verify(&script, b"", &[sig.to_vec()]),
+                Err(ProofError::SighashType(hash_ty as u32))
+            );
+        }
+    }
+}
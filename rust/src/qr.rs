@@ -0,0 +1,168 @@
+// LNP/BP universal invoice library implementing LNPBP-38 standard
+// Written in 2021 by
+//     Dr. Maxim Orlovsky <orlovsky@pandoracore.com>
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the MIT License
+// along with this software.
+// If not, see <https://opensource.org/licenses/MIT>.
+
+use std::io;
+
+use strict_encoding::StrictEncode;
+
+use crate::Invoice;
+
+/// Maximal QR code version
+pub const QR_MAX_VERSION: u8 = 40;
+
+/// QR code error correction level.
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Hash, Debug, Display)]
+pub enum QrErrorCorrection {
+    /// Recovers 7% of the data
+    #[display("L")]
+    Low,
+
+    /// Recovers 15% of the data
+    #[display("M")]
+    Medium,
+
+    /// Recovers 25% of the data
+    #[display("Q")]
+    Quartile,
+
+    /// Recovers 30% of the data
+    #[display("H")]
+    High,
+}
+
+impl QrErrorCorrection {
+    /// All error correction levels, from the lowest to the highest
+    pub const ALL: [QrErrorCorrection; 4] = [
+        QrErrorCorrection::Low,
+        QrErrorCorrection::Medium,
+        QrErrorCorrection::Quartile,
+        QrErrorCorrection::High,
+    ];
+}
+
+/// Smallest QR code able to hold uppercase bech32 string of a given length
+/// in alphanumeric mode.
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Hash, Debug, Display)]
+#[display("{version}-{error_correction}")]
+pub struct QrCapacity {
+    /// QR code version (1 to 40), defining the number of modules
+    pub version: u8,
+    pub error_correction: QrErrorCorrection,
+}
+
+impl QrCapacity {
+    /// Finds the smallest QR code version holding `len` alphanumeric
+    /// characters with a given error correction level. Returns `None` if the
+    /// data do not fit into a QR code.
+    pub fn with(
+        len: usize,
+        error_correction: QrErrorCorrection,
+    ) -> Option<QrCapacity> {
+        (1..=QR_MAX_VERSION)
+            .find(|version| {
+                alphanumeric_capacity(*version, error_correction) >= len
+            })
+            .map(|version| QrCapacity {
+                version,
+                error_correction,
+            })
+    }
+
+    /// Finds the highest error correction level at which `len` alphanumeric
+    /// characters fit into a QR code not exceeding `max_version`, using the
+    /// smallest version for that level.
+    pub fn best_fit(len: usize, max_version: u8) -> Option<QrCapacity> {
+        QrErrorCorrection::ALL
+            .iter()
+            .rev()
+            .filter_map(|ecl| QrCapacity::with(len, *ecl))
+            .find(|capacity| capacity.version <= max_version)
+    }
+
+    /// Number of alphanumeric characters the QR code holds.
+    pub fn capacity(self) -> usize {
+        alphanumeric_capacity(self.version, self.error_correction)
+    }
+}
+
+/// Number of alphanumeric characters a QR code of a given version and error
+/// correction level holds; zero for invalid versions.
+pub fn alphanumeric_capacity(
+    version: u8,
+    error_correction: QrErrorCorrection,
+) -> usize {
+    if !(1..=QR_MAX_VERSION).contains(&version) {
+        return 0;
+    }
+    let codewords =
+        DATA_CODEWORDS[error_correction as usize][version as usize - 1];
+    let count_bits = match version {
+        1..=9 => 9,
+        10..=26 => 11,
+        _ => 13,
+    };
+    // Mode indicator takes 4 bits; each pair of characters takes 11 bits and
+    // the last odd character takes 6 bits
+    let bits = codewords as usize * 8 - 4 - count_bits;
+    bits / 11 * 2 + usize::from(bits % 11 >= 6)
+}
+
+impl Invoice {
+    /// Returns length of the invoice strict encoding, without allocating
+    /// the encoded data.
+    pub fn encoded_len(&self) -> usize {
+        self.strict_encode(io::sink())
+            .expect("invoice data are inconsistent for strict encoding")
+    }
+
+    /// Returns number of characters in the invoice bech32 string, including
+    /// the prefix and checksum.
+    pub fn bech32_len(&self) -> usize {
+        self.to_string().len()
+    }
+
+    /// Returns the smallest QR code holding the uppercase invoice string
+    /// with a given error correction level, if the invoice fits into a QR
+    /// code.
+    pub fn qr_capacity(
+        &self,
+        error_correction: QrErrorCorrection,
+    ) -> Option<QrCapacity> {
+        QrCapacity::with(self.bech32_len(), error_correction)
+    }
+}
+
+/// Number of data codewords in QR codes of each version, for each error
+/// correction level (ISO/IEC 18004, table 7)
+const DATA_CODEWORDS: [[u16; 40]; 4] = [
+    [
+        19, 34, 55, 80, 108, 136, 156, 194, 232, 274, 324, 370, 428, 461, 523,
+        589, 647, 721, 795, 861, 932, 1006, 1094, 1174, 1276, 1370, 1468, 1531,
+        1631, 1735, 1843, 1955, 2071, 2191, 2306, 2434, 2566, 2702, 2812, 2956,
+    ],
+    [
+        16, 28, 44, 64, 86, 108, 124, 154, 182, 216, 254, 290, 334, 365, 415,
+        453, 507, 563, 627, 669, 714, 782, 860, 914, 1000, 1062, 1128, 1193,
+        1267, 1373, 1455, 1541, 1631, 1725, 1812, 1914, 1992, 2102, 2216, 2334,
+    ],
+    [
+        13, 22, 34, 48, 62, 76, 88, 110, 132, 154, 180, 206, 244, 261, 295,
+        325, 367, 397, 445, 485, 512, 568, 614, 664, 718, 754, 808, 871, 911,
+        985, 1033, 1115, 1171, 1231, 1286, 1354, 1426, 1502, 1582, 1666,
+    ],
+    [
+        9, 16, 26, 36, 46, 60, 66, 86, 100, 122, 140, 158, 180, 197, 223, 253,
+        283, 313, 341, 385, 406, 442, 464, 514, 538, 596, 628, 661, 701, 745,
+        793, 845, 901, 961, 986, 1054, 1096, 1142, 1222, 1276,
+    ],
+];
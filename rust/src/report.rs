@@ -0,0 +1,436 @@
+// LNP/BP universal invoice library implementing LNPBP-38 standard
+// Written in 2021 by
+//     Dr. Maxim Orlovsky <orlovsky@pandoracore.com>
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the MIT License
+// along with this software.
+// If not, see <https://opensource.org/licenses/MIT>.
+
+//! Human-readable analysis of invoice data.
+
+use bitcoin::hashes::hex::ToHex;
+use chrono::{DateTime, Local, Utc};
+use std::collections::BTreeMap;
+use std::fmt::{self, Display, Formatter};
+
+use lnpbp::bech32::ToBech32String;
+
+use crate::{
+    Beneficiary, ConsignmentEndpoint, CurrencyData, Details, InlineDetails,
+    Invoice, OnchainPolicy, Quantity, SignatureError, SplitShare,
+};
+
+/// Kind of the invoice beneficiary
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Hash, Debug, Display)]
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize),
+    serde(crate = "serde_crate", rename_all = "kebab-case")
+)]
+pub enum BeneficiaryKind {
+    #[display("address")]
+    Address,
+
+    #[display("blind-utxo")]
+    BlindUtxo,
+
+    #[display("descriptor")]
+    Descriptor,
+
+    #[display("psbt")]
+    Psbt,
+
+    #[display("lightning")]
+    Lightning,
+
+    #[display("unknown")]
+    Unknown,
+
+    #[display("silent-payment")]
+    SilentPayment,
+
+    #[display("payment-code")]
+    PaymentCode,
+
+    #[display("bifrost")]
+    Bifrost,
+
+    #[display("witness-vout")]
+    WitnessVout,
+
+    #[display("tracking-descriptor")]
+    TrackingDescriptor,
+
+    #[cfg(feature = "elements")]
+    #[display("liquid-address")]
+    LiquidAddress,
+}
+
+impl From<&Beneficiary> for BeneficiaryKind {
+    fn from(beneficiary: &Beneficiary) -> Self {
+        match beneficiary {
+            Beneficiary::Address(_) => BeneficiaryKind::Address,
+            Beneficiary::BlindUtxo(_) => BeneficiaryKind::BlindUtxo,
+            Beneficiary::Descriptor(_) => BeneficiaryKind::Descriptor,
+            Beneficiary::Psbt(_) => BeneficiaryKind::Psbt,
+            Beneficiary::Bolt(_) => BeneficiaryKind::Lightning,
+            Beneficiary::Unknown(_) => BeneficiaryKind::Unknown,
+            Beneficiary::SilentPayment(_) => BeneficiaryKind::SilentPayment,
+            Beneficiary::PaymentCode(_) => BeneficiaryKind::PaymentCode,
+            Beneficiary::Bifrost(_) => BeneficiaryKind::Bifrost,
+            Beneficiary::WitnessVoutSeal(_) => BeneficiaryKind::WitnessVout,
+            Beneficiary::TrackingDescriptor(_) => {
+                BeneficiaryKind::TrackingDescriptor
+            }
+            #[cfg(feature = "elements")]
+            Beneficiary::LiquidAddress(_) => BeneficiaryKind::LiquidAddress,
+        }
+    }
+}
+
+/// Beneficiary labelled with its kind
+#[derive(Clone, Eq, PartialEq, Hash, Debug, Display)]
+#[cfg_attr(feature = "serde", derive(Serialize), serde(crate = "serde_crate"))]
+#[display("{kind} {value}")]
+pub struct BeneficiaryReport {
+    pub kind: BeneficiaryKind,
+    pub value: String,
+}
+
+impl From<&Beneficiary> for BeneficiaryReport {
+    fn from(beneficiary: &Beneficiary) -> Self {
+        BeneficiaryReport {
+            kind: beneficiary.into(),
+            value: beneficiary.to_string(),
+        }
+    }
+}
+
+/// Result of the invoice signature check
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug, Display)]
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize),
+    serde(crate = "serde_crate", rename_all = "lowercase")
+)]
+pub enum SignatureStatus {
+    /// invoice is not signed
+    #[display("absent")]
+    Absent,
+
+    /// invoice signature is valid
+    #[display("valid")]
+    Valid,
+
+    /// invoice signature does not match invoice data
+    #[display("invalid")]
+    Invalid,
+}
+
+/// Human-readable breakdown of the invoice data, suitable for presenting to
+/// support staff or for machine-readable serialization.
+#[derive(Clone, Eq, PartialEq, Debug)]
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize),
+    serde(crate = "serde_crate", rename_all = "camelCase")
+)]
+pub struct InvoiceReport {
+    /// Beneficiaries in the order of priority
+    pub beneficiaries: Vec<BeneficiaryReport>,
+
+    /// Shares of the payment received by each of the alternative
+    /// beneficiaries; empty unless the invoice is a split payment invoice
+    pub split_shares: Vec<String>,
+
+    /// Amount, as presented in the invoice
+    pub amount: String,
+
+    /// Classification of the invoice asset
+    pub asset: String,
+
+    /// RGB contract interface expected by the invoice
+    #[cfg(feature = "rgb")]
+    pub iface: Option<String>,
+
+    /// Time of the invoice issuance
+    pub issued_at: Option<DateTime<Utc>>,
+
+    /// Time until which the invoice can be paid (in UTC)
+    pub payable_until: Option<DateTime<Utc>>,
+
+    /// Seconds left until the invoice expires, as for the report creation
+    /// time
+    pub seconds_left: Option<i64>,
+
+    /// Description of the payment recurrence
+    pub recurrence: String,
+
+    pub quantity: Option<Quantity>,
+
+    /// Requirements to on-chain payment transactions
+    pub onchain_policy: Option<String>,
+
+    /// Minimal prices in fiat currencies, in the order of merchant preference
+    pub currency_requirements: Vec<String>,
+
+    pub merchant: Option<String>,
+
+    pub purpose: Option<String>,
+
+    /// Payment reference in hex encoding
+    pub reference: Option<String>,
+
+    pub details: Option<Details>,
+
+    /// Description of the document embedded into the invoice
+    pub inline_details: Option<String>,
+
+    /// Consignment endpoints grouped by their protocol
+    pub consignment_endpoints: BTreeMap<String, Vec<String>>,
+
+    /// Beneficiary receiving refunds, supplied by the payer
+    pub refund: Option<BeneficiaryReport>,
+
+    pub signature: SignatureStatus,
+}
+
+impl InvoiceReport {
+    /// Analyzes invoice data; `now` is used to compute time left until the
+    /// invoice expiry.
+    pub fn new(invoice: &Invoice, now: DateTime<Utc>) -> InvoiceReport {
+        let mut consignment_endpoints = BTreeMap::<String, Vec<String>>::new();
+        for endpoint in invoice.consignment_endpoints() {
+            let (protocol, value) = match endpoint {
+                ConsignmentEndpoint::Storm(addr) => ("storm", addr.to_string()),
+                ConsignmentEndpoint::RgbHttpJsonRpc(url) => {
+                    ("rgbhttpjsonrpc", url.to_string())
+                }
+                ConsignmentEndpoint::RgbWebSocket(url) => {
+                    ("rgbws", url.to_string())
+                }
+                ConsignmentEndpoint::Bifrost(addr) => {
+                    ("bifrost", addr.to_string())
+                }
+                ConsignmentEndpoint::Unknown { protocol, data } => {
+                    ("unknown", format!("{}:{}", protocol, data))
+                }
+            };
+            consignment_endpoints
+                .entry(protocol.to_owned())
+                .or_default()
+                .push(value);
+        }
+
+        let signature = match invoice.verify_signature() {
+            Ok(()) => SignatureStatus::Valid,
+            Err(SignatureError::NoSignature) => SignatureStatus::Absent,
+            Err(_) => SignatureStatus::Invalid,
+        };
+
+        InvoiceReport {
+            beneficiaries: invoice
+                .beneficiaries()
+                .map(BeneficiaryReport::from)
+                .collect(),
+            split_shares: invoice
+                .split_shares()
+                .iter()
+                .map(SplitShare::to_string)
+                .collect(),
+            amount: invoice.amount().to_string(),
+            asset: invoice.classify_asset_auto().to_string(),
+            #[cfg(feature = "rgb")]
+            iface: invoice.iface().map(ToString::to_string),
+            issued_at: *invoice.issued_at(),
+            payable_until: invoice.payable_until(),
+            seconds_left: invoice
+                .time_left(now)
+                .map(|duration| duration.num_seconds()),
+            recurrence: match invoice.recurrence_limit() {
+                Some(limit) => {
+                    format!("{}, at most {} times", invoice.recurrent(), limit)
+                }
+                None => invoice.recurrent().to_string(),
+            },
+            quantity: *invoice.quantity(),
+            onchain_policy: invoice
+                .onchain_policy()
+                .as_ref()
+                .map(OnchainPolicy::to_string),
+            currency_requirements: invoice
+                .currency_requirements()
+                .iter()
+                .map(CurrencyData::to_string)
+                .collect(),
+            merchant: invoice.merchant_name().map(str::to_owned),
+            purpose: invoice.purpose().clone(),
+            reference: invoice
+                .reference()
+                .as_ref()
+                .map(|reference| reference.to_hex()),
+            details: invoice.details().clone(),
+            inline_details: invoice
+                .inline_details()
+                .as_ref()
+                .map(InlineDetails::to_string),
+            consignment_endpoints,
+            refund: invoice.refund().as_ref().map(BeneficiaryReport::from),
+            signature,
+        }
+    }
+}
+
+impl Display for InvoiceReport {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        for (no, beneficiary) in self.beneficiaries.iter().enumerate() {
+            match (no, self.split_shares.get(no.wrapping_sub(1))) {
+                (0, _) => writeln!(f, "{:<14}{}", "Beneficiary:", beneficiary)?,
+                (_, Some(share)) => writeln!(
+                    f,
+                    "{:<14}{} ({} of the payment)",
+                    "Split part:", beneficiary, share
+                )?,
+                (_, None) => {
+                    writeln!(f, "{:<14}{}", "Alternative:", beneficiary)?
+                }
+            }
+        }
+        writeln!(f, "{:<14}{} ({})", "Amount:", self.amount, self.asset)?;
+        #[cfg(feature = "rgb")]
+        if let Some(iface) = &self.iface {
+            writeln!(f, "{:<14}{}", "Interface:", iface)?;
+        }
+        if let Some(issued_at) = self.issued_at {
+            let local = issued_at.with_timezone(&Local);
+            writeln!(f, "{:<14}{}", "Issued:", local.format("%F %T %:z"))?;
+        }
+        match (self.payable_until, self.seconds_left) {
+            (Some(until), Some(left)) => {
+                let local = until.with_timezone(&Local);
+                write!(f, "{:<14}{}", "Expiry:", local.format("%F %T %:z"))?;
+                if left > 0 {
+                    writeln!(f, " (expires in {})", format_duration(left))?;
+                } else {
+                    writeln!(f, " (expired)")?;
+                }
+            }
+            _ => writeln!(f, "{:<14}never", "Expiry:")?,
+        }
+        writeln!(f, "{:<14}{}", "Recurrence:", self.recurrence)?;
+        if let Some(quantity) = self.quantity {
+            writeln!(f, "{:<14}{}", "Quantity:", describe_quantity(&quantity))?;
+        }
+        if let Some(policy) = &self.onchain_policy {
+            writeln!(f, "{:<14}{}", "On-chain:", policy)?;
+        }
+        for currency in &self.currency_requirements {
+            writeln!(f, "{:<14}{}", "Min price:", currency)?;
+        }
+        if let Some(merchant) = &self.merchant {
+            writeln!(f, "{:<14}{}", "Merchant:", merchant)?;
+        }
+        if let Some(purpose) = &self.purpose {
+            writeln!(f, "{:<14}{}", "Purpose:", purpose)?;
+        }
+        if let Some(reference) = &self.reference {
+            writeln!(f, "{:<14}{}", "Reference:", reference)?;
+        }
+        if let Some(details) = &self.details {
+            writeln!(f, "{:<14}{}", "Details:", details)?;
+        }
+        if let Some(inline_details) = &self.inline_details {
+            writeln!(f, "{:<14}{}", "Document:", inline_details)?;
+        }
+        if !self.consignment_endpoints.is_empty() {
+            writeln!(f, "Consignment endpoints:")?;
+            for (protocol, endpoints) in &self.consignment_endpoints {
+                writeln!(f, "  {}:", protocol)?;
+                for endpoint in endpoints {
+                    writeln!(f, "    {}", endpoint)?;
+                }
+            }
+        }
+        if let Some(refund) = &self.refund {
+            writeln!(f, "{:<14}{}", "Refund:", refund)?;
+        }
+        write!(f, "{:<14}{}", "Signature:", self.signature)
+    }
+}
+
+impl InvoiceReport {
+    /// Writes compact summary of the report, used by the alternate format of
+    /// the [`Invoice`].
+    fn fmt_summary(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{:<14}{} ({})", "Amount:", self.amount, self.asset)?;
+        if let Some(beneficiary) = self.beneficiaries.first() {
+            writeln!(f, "{:<14}{}", "Beneficiary:", beneficiary)?;
+        }
+        match self.seconds_left {
+            Some(left) if left > 0 => {
+                writeln!(f, "{:<14}in {}", "Expiry:", format_duration(left))?
+            }
+            Some(_) => writeln!(f, "{:<14}expired", "Expiry:")?,
+            None => writeln!(f, "{:<14}never", "Expiry:")?,
+        }
+        if let Some(merchant) = &self.merchant {
+            writeln!(f, "{:<14}{}", "Merchant:", merchant)?;
+        }
+        if let Some(purpose) = &self.purpose {
+            writeln!(f, "{:<14}{}", "Purpose:", purpose)?;
+        }
+        let alternatives = self.beneficiaries.len().saturating_sub(1);
+        if alternatives > 0 {
+            writeln!(f, "{:<14}{}", "Alternatives:", alternatives)?;
+        }
+        let endpoints = self
+            .consignment_endpoints
+            .values()
+            .map(Vec::len)
+            .sum::<usize>();
+        if endpoints > 0 {
+            writeln!(f, "{:<14}{}", "Endpoints:", endpoints)?;
+        }
+        write!(f, "{:<14}{}", "Signature:", self.signature)
+    }
+}
+
+impl Display for Invoice {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        if f.alternate() {
+            InvoiceReport::new(self, Utc::now()).fmt_summary(f)
+        } else {
+            f.write_str(&self.to_bech32_string())
+        }
+    }
+}
+
+/// Describes quantity range, like `3 items (or from 1 to 10)`
+fn describe_quantity(quantity: &Quantity) -> String {
+    let items = format!("{} items", quantity.default);
+    match (quantity.min, quantity.max) {
+        (min, Some(max)) if min == max => items,
+        (0, Some(max)) => format!("{} (or any amount up to {})", items, max),
+        (0, None) => items,
+        (min, Some(max)) => format!("{} (or from {} to {})", items, min, max),
+        (min, None) => format!("{} (or any amount above {})", items, min),
+    }
+}
+
+/// Formats number of seconds as a short duration, like `2h 13m`
+fn format_duration(seconds: i64) -> String {
+    let days = seconds / 86400;
+    let hours = seconds % 86400 / 3600;
+    let minutes = seconds % 3600 / 60;
+    match (days, hours, minutes) {
+        (0, 0, 0) => format!("{}s", seconds),
+        (0, 0, m) => format!("{}m", m),
+        (0, h, m) => format!("{}h {}m", h, m),
+        (d, h, _) => format!("{}d {}h", d, h),
+    }
+}
@@ -0,0 +1,216 @@
+// LNP/BP universal invoice library implementing LNPBP-38 standard
+// Written in 2021 by
+//     Dr. Maxim Orlovsky <orlovsky@pandoracore.com>
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the MIT License
+// along with this software.
+// If not, see <https://opensource.org/licenses/MIT>.
+
+//! Blinding of UTXOs used as [`crate::Beneficiary::BlindUtxo`] and witness
+//! transaction output seals used as [`crate::Beneficiary::WitnessVoutSeal`].
+
+use std::num::ParseIntError;
+use std::str::FromStr;
+
+use bitcoin::secp256k1::rand::RngCore;
+use bitcoin::OutPoint;
+use bp::seals::txout::blind::{ConcealedSeal, RevealedSeal};
+use bp::seals::txout::{CloseMethod, MethodParseError};
+
+use crate::Beneficiary;
+
+/// Blinded UTXO together with the data revealing it, which the receiver
+/// must persist alongside the issued invoice: the blinding factor is
+/// required to prove which UTXO the concealed seal refers to.
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    serde(crate = "serde_crate")
+)]
+#[derive(
+    Copy,
+    Clone,
+    Ord,
+    PartialOrd,
+    Eq,
+    PartialEq,
+    Hash,
+    Debug,
+    StrictEncode,
+    StrictDecode,
+)]
+pub struct BlindedUtxo {
+    pub concealed: ConcealedSeal,
+    pub revealed: RevealedSeal,
+    pub blinding: u64,
+}
+
+impl BlindedUtxo {
+    /// Returns invoice beneficiary receiving the assets to the blinded UTXO.
+    pub fn beneficiary(&self) -> Beneficiary {
+        Beneficiary::BlindUtxo(self.concealed)
+    }
+}
+
+/// Conceals the outpoint with a random blinding factor taken from `rng`.
+pub fn blind(outpoint: OutPoint, rng: &mut impl RngCore) -> BlindedUtxo {
+    let revealed = RevealedSeal::with(
+        CloseMethod::TapretFirst,
+        Some(outpoint.txid),
+        outpoint.vout,
+        rng,
+    );
+    BlindedUtxo {
+        concealed: revealed.to_concealed_seal(),
+        revealed,
+        blinding: revealed.blinding,
+    }
+}
+
+/// Checks that the concealed seal commits to the given outpoint with the
+/// given blinding factor, using any of the seal closing methods.
+pub fn verify(
+    concealed: ConcealedSeal,
+    outpoint: OutPoint,
+    blinding: u64,
+) -> bool {
+    [CloseMethod::TapretFirst, CloseMethod::OpretFirst]
+        .iter()
+        .any(|method| {
+            let revealed = RevealedSeal {
+                method: *method,
+                txid: Some(outpoint.txid),
+                vout: outpoint.vout,
+                blinding,
+            };
+            revealed.to_concealed_seal() == concealed
+        })
+}
+
+/// Conceals the outpoint with a random blinding factor taken from `rng`.
+/// The returned revealed seal must be kept by the receiver, since its
+/// blinding factor is required to prove which UTXO the concealed seal refers
+/// to.
+#[deprecated(since = "0.9.2", note = "use `seal::blind`")]
+pub fn blind_utxo(
+    outpoint: OutPoint,
+    rng: &mut impl RngCore,
+) -> (ConcealedSeal, RevealedSeal) {
+    let blinded = blind(outpoint, rng);
+    (blinded.concealed, blinded.revealed)
+}
+
+/// Checks that the concealed seal commits to the given outpoint with the
+/// given blinding factor, using any of the seal closing methods.
+#[deprecated(since = "0.9.2", note = "use `seal::verify`")]
+pub fn verify_blind_utxo(
+    concealed: ConcealedSeal,
+    outpoint: OutPoint,
+    blinding: u64,
+) -> bool {
+    verify(concealed, outpoint, blinding)
+}
+
+/// Errors parsing [`WitnessVoutSeal`] string representation.
+#[derive(Clone, PartialEq, Eq, Debug, Display, Error, From)]
+#[display(doc_comments)]
+pub enum WitnessVoutSealError {
+    /// witness output seal must have `wvout:<method>:<blinding>` format
+    InvalidFormat,
+
+    /// {0}
+    #[from]
+    Method(MethodParseError),
+
+    /// invalid witness output seal blinding factor: {0}
+    #[from]
+    Blinding(ParseIntError),
+}
+
+/// Seal defined by an output of the witness transaction, i.e. the
+/// transaction paying the invoice. Payer assigns RGB state to its
+/// transaction output number `vout`, so the receiver does not need to
+/// provide an existing UTXO; the closing method and blinding factor come
+/// from the receiver.
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    serde(crate = "serde_crate")
+)]
+#[derive(
+    Copy,
+    Clone,
+    Ord,
+    PartialOrd,
+    Eq,
+    PartialEq,
+    Hash,
+    Debug,
+    Display,
+    StrictEncode,
+    StrictDecode,
+)]
+#[display("wvout:{method}:{blinding}")]
+pub struct WitnessVoutSeal {
+    pub method: CloseMethod,
+    pub blinding: u64,
+}
+
+impl WitnessVoutSeal {
+    /// Constructs the seal using a random blinding factor taken from `rng`.
+    pub fn with(method: CloseMethod, rng: &mut impl RngCore) -> Self {
+        WitnessVoutSeal {
+            method,
+            blinding: rng.next_u64(),
+        }
+    }
+
+    /// Reveals the seal once the payer has defined the witness transaction
+    /// output receiving the state.
+    pub fn to_revealed_seal(self, vout: u32) -> RevealedSeal {
+        RevealedSeal {
+            method: self.method,
+            txid: None,
+            vout,
+            blinding: self.blinding,
+        }
+    }
+
+    /// Returns concealed form of the seal for a given witness transaction
+    /// output, which can be used uniformly with
+    /// [`crate::Beneficiary::BlindUtxo`] seals.
+    pub fn to_concealed_seal(self, vout: u32) -> ConcealedSeal {
+        self.to_revealed_seal(vout).to_concealed_seal()
+    }
+
+    /// Converts the seal into RGB seal endpoint for a given witness
+    /// transaction output.
+    #[cfg(feature = "rgb")]
+    pub fn to_seal_endpoint(self, vout: u32) -> rgb::SealEndpoint {
+        rgb::SealEndpoint::WitnessVout {
+            method: self.method,
+            vout,
+            blinding: self.blinding,
+        }
+    }
+}
+
+impl FromStr for WitnessVoutSeal {
+    type Err = WitnessVoutSealError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (method, blinding) = s
+            .strip_prefix("wvout:")
+            .and_then(|s| s.split_once(':'))
+            .ok_or(WitnessVoutSealError::InvalidFormat)?;
+        Ok(WitnessVoutSeal {
+            method: CloseMethod::from_str(method)?,
+            blinding: u64::from_str(blinding)?,
+        })
+    }
+}
@@ -0,0 +1,171 @@
+// LNP/BP universal invoice library implementing LNPBP-38 standard
+// Written in 2021 by
+//     Dr. Maxim Orlovsky <orlovsky@pandoracore.com>
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the MIT License
+// along with this software.
+// If not, see <https://opensource.org/licenses/MIT>.
+
+use std::convert::TryFrom;
+use std::fmt::{self, Display, Formatter};
+use std::io;
+use std::ops::Deref;
+use std::str::FromStr;
+
+use strict_encoding::{StrictDecode, StrictEncode};
+
+use crate::{Invoice, InvoiceParseError, SignatureError};
+
+/// Errors parsing [`SignedInvoice`].
+#[derive(Clone, PartialEq, Eq, Debug, Display, Error, From)]
+#[display(doc_comments)]
+pub enum SignedInvoiceError {
+    /// {0}
+    #[from]
+    Parse(InvoiceParseError),
+
+    /// {0}
+    #[from]
+    Signature(SignatureError),
+}
+
+/// Invoice which signature is verified against the public key embedded into
+/// the invoice.
+///
+/// The invoice data are accessible only for reading; to modify them the
+/// invoice must be converted back into an unsigned [`Invoice`] with
+/// [`SignedInvoice::into_unsigned`]:
+///
+/// ```compile_fail
+/// # use invoice::SignedInvoice;
+/// fn tamper(mut signed: SignedInvoice) {
+///     signed.set_purpose("tea".to_owned());
+/// }
+/// ```
+///
+/// ```
+/// # use invoice::SignedInvoice;
+/// fn modify(signed: SignedInvoice) {
+///     let mut invoice = signed.into_unsigned();
+///     invoice.set_purpose("tea".to_owned());
+/// }
+/// ```
+#[derive(Clone, Ord, PartialOrd, Eq, PartialEq, Debug)]
+pub struct SignedInvoice(Invoice);
+
+impl Invoice {
+    /// Verifies invoice signature with [`Invoice::verify_signature`],
+    /// returning the invoice together with the verification error on
+    /// failure.
+    #[allow(clippy::result_large_err)]
+    pub fn into_signed(
+        self,
+    ) -> Result<SignedInvoice, (Invoice, SignatureError)> {
+        match self.verify_signature() {
+            Ok(()) => Ok(SignedInvoice(self)),
+            Err(err) => Err((self, err)),
+        }
+    }
+}
+
+impl SignedInvoice {
+    /// Returns the invoice, keeping its signature.
+    pub fn as_invoice(&self) -> &Invoice {
+        &self.0
+    }
+
+    /// Downgrades to an unsigned invoice, removing the signature so the
+    /// invoice can be modified.
+    pub fn into_unsigned(self) -> Invoice {
+        let mut invoice = self.0;
+        invoice.remove_signature();
+        invoice
+    }
+}
+
+impl Deref for SignedInvoice {
+    type Target = Invoice;
+
+    fn deref(&self) -> &Invoice {
+        &self.0
+    }
+}
+
+impl AsRef<Invoice> for SignedInvoice {
+    fn as_ref(&self) -> &Invoice {
+        &self.0
+    }
+}
+
+impl From<SignedInvoice> for Invoice {
+    fn from(signed: SignedInvoice) -> Self {
+        signed.0
+    }
+}
+
+impl TryFrom<Invoice> for SignedInvoice {
+    type Error = SignatureError;
+
+    fn try_from(invoice: Invoice) -> Result<Self, Self::Error> {
+        invoice.into_signed().map_err(|(_, err)| err)
+    }
+}
+
+impl Display for SignedInvoice {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        Display::fmt(&self.0, f)
+    }
+}
+
+impl FromStr for SignedInvoice {
+    type Err = SignedInvoiceError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(SignedInvoice::try_from(Invoice::from_str(s)?)?)
+    }
+}
+
+impl StrictEncode for SignedInvoice {
+    fn strict_encode<E: io::Write>(
+        &self,
+        e: E,
+    ) -> Result<usize, strict_encoding::Error> {
+        self.0.strict_encode(e)
+    }
+}
+
+impl StrictDecode for SignedInvoice {
+    fn strict_decode<D: io::Read>(
+        d: D,
+    ) -> Result<Self, strict_encoding::Error> {
+        SignedInvoice::try_from(Invoice::strict_decode(d)?).map_err(|err| {
+            strict_encoding::Error::DataIntegrityError(err.to_string())
+        })
+    }
+}
+
+#[cfg(feature = "serde")]
+impl serde::Serialize for SignedInvoice {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        self.0.serialize(serializer)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for SignedInvoice {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        SignedInvoice::try_from(Invoice::deserialize(deserializer)?)
+            .map_err(serde::de::Error::custom)
+    }
+}
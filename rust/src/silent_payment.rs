@@ -0,0 +1,154 @@
+// LNP/BP universal invoice library implementing LNPBP-38 standard
+// Written in 2021 by
+//     Dr. Maxim Orlovsky <orlovsky@pandoracore.com>
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the MIT License
+// along with this software.
+// If not, see <https://opensource.org/licenses/MIT>.
+
+use std::fmt::{self, Display, Formatter};
+use std::str::FromStr;
+
+use bech32::{FromBase32, ToBase32, Variant};
+use bitcoin::secp256k1;
+
+/// Errors parsing BIP-352 silent payment code.
+#[derive(
+    Copy,
+    Clone,
+    Ord,
+    PartialOrd,
+    Eq,
+    PartialEq,
+    Hash,
+    Debug,
+    Display,
+    Error,
+    From,
+)]
+#[display(doc_comments)]
+pub enum SilentPaymentError {
+    /// silent payment code has invalid Bech32m encoding: {0}
+    #[from]
+    Bech32(bech32::Error),
+
+    /// silent payment code must use Bech32m checksum
+    NonBech32m,
+
+    /// silent payment code has unknown prefix; only `sp` and `tsp` are
+    /// allowed
+    UnknownHrp,
+
+    /// silent payment code version {0} is not supported
+    UnsupportedVersion(u8),
+
+    /// silent payment code must contain exactly two public keys
+    WrongLen,
+
+    /// silent payment code contains invalid public key
+    #[from(secp256k1::Error)]
+    InvalidKey,
+}
+
+/// Reusable BIP-352 silent payment code, consisting of the scan and spend
+/// public keys of the receiver. Payers must derive a fresh taproot output
+/// from these keys and the inputs of the paying transaction, so the code
+/// can't be paid as a plain address.
+#[derive(
+    Copy,
+    Clone,
+    Ord,
+    PartialOrd,
+    Eq,
+    PartialEq,
+    Hash,
+    Debug,
+    StrictEncode,
+    StrictDecode,
+)]
+pub struct SilentPaymentCode {
+    /// Whether the code is intended for test networks (`tsp` prefix)
+    pub testnet: bool,
+    pub scan_key: secp256k1::PublicKey,
+    pub spend_key: secp256k1::PublicKey,
+}
+
+impl SilentPaymentCode {
+    /// Bech32m prefix of the codes for bitcoin mainnet
+    pub const HRP_MAINNET: &'static str = "sp";
+    /// Bech32m prefix of the codes for test networks
+    pub const HRP_TESTNET: &'static str = "tsp";
+    /// The only silent payment code version supported by the library
+    pub const VERSION: u8 = 0;
+
+    pub fn new(
+        scan_key: secp256k1::PublicKey,
+        spend_key: secp256k1::PublicKey,
+        testnet: bool,
+    ) -> SilentPaymentCode {
+        SilentPaymentCode {
+            testnet,
+            scan_key,
+            spend_key,
+        }
+    }
+
+    fn hrp(&self) -> &'static str {
+        if self.testnet {
+            Self::HRP_TESTNET
+        } else {
+            Self::HRP_MAINNET
+        }
+    }
+}
+
+impl Display for SilentPaymentCode {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let mut payload = Vec::with_capacity(66);
+        payload.extend(self.scan_key.serialize());
+        payload.extend(self.spend_key.serialize());
+        let mut data = vec![bech32::u5::try_from_u8(Self::VERSION)
+            .expect("silent payment version fits into 5 bits")];
+        data.extend(payload.to_base32());
+        let s = bech32::encode(self.hrp(), data, Variant::Bech32m)
+            .map_err(|_| fmt::Error)?;
+        f.write_str(&s)
+    }
+}
+
+impl FromStr for SilentPaymentCode {
+    type Err = SilentPaymentError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (hrp, data, variant) = bech32::decode(s)?;
+        if variant != Variant::Bech32m {
+            return Err(SilentPaymentError::NonBech32m);
+        }
+        let testnet = match hrp.as_str() {
+            Self::HRP_MAINNET => false,
+            Self::HRP_TESTNET => true,
+            _ => return Err(SilentPaymentError::UnknownHrp),
+        };
+        let (version, data) =
+            data.split_first().ok_or(SilentPaymentError::WrongLen)?;
+        if version.to_u8() != Self::VERSION {
+            return Err(SilentPaymentError::UnsupportedVersion(
+                version.to_u8(),
+            ));
+        }
+        let payload = Vec::<u8>::from_base32(data)?;
+        if payload.len() != 66 {
+            return Err(SilentPaymentError::WrongLen);
+        }
+        Ok(SilentPaymentCode {
+            testnet,
+            scan_key: secp256k1::PublicKey::from_slice(&payload[..33])?,
+            spend_key: secp256k1::PublicKey::from_slice(&payload[33..])?,
+        })
+    }
+}
@@ -0,0 +1,222 @@
+// LNP/BP universal invoice library implementing LNPBP-38 standard
+// Written in 2021 by
+//     Dr. Maxim Orlovsky <orlovsky@pandoracore.com>
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the MIT License
+// along with this software.
+// If not, see <https://opensource.org/licenses/MIT>.
+
+use std::cmp::Ordering;
+use std::fmt::{self, Display, Formatter};
+use std::io;
+use std::ops::Range;
+use std::str::FromStr;
+
+use miniscript::{descriptor::DescriptorPublicKey, Descriptor};
+#[cfg(feature = "serde")]
+use serde_with::{As, DisplayFromStr};
+use strict_encoding::{StrictDecode, StrictEncode};
+
+use crate::payment::HARDENED_INDEX;
+
+/// Errors constructing or parsing [`TrackingBeneficiary`].
+#[derive(Clone, Eq, PartialEq, Hash, Debug, Display, Error)]
+#[display(doc_comments)]
+pub enum TrackingError {
+    /// tracking beneficiary must have `track:[<start>..<end>][@<next>]:`
+    /// prefix followed by a descriptor
+    Format,
+
+    /// invalid tracking beneficiary descriptor: {0}
+    Descriptor(String),
+
+    /// derivation index range {0:?} is empty or includes hardened indexes
+    InvalidRange(Range<u32>),
+
+    /// next derivation index {0} lies outside of the allowed index range
+    HintOutOfRange(u32),
+}
+
+/// Descriptor beneficiary together with the derivation metadata of the
+/// receiver tracking account: the range of derivation indexes valid for the
+/// invoice and the next index not used by the receiver yet.
+///
+/// Displayed as `track:[<start>..<end>][@<next>]:<descriptor>`.
+#[cfg_attr(
+    feature = "serde",
+    serde_as,
+    derive(Serialize, Deserialize),
+    serde(crate = "serde_crate", rename_all = "camelCase")
+)]
+#[derive(Clone, Eq, PartialEq, Hash, Debug)]
+pub struct TrackingBeneficiary {
+    #[cfg_attr(feature = "serde", serde(with = "As::<DisplayFromStr>"))]
+    pub descriptor: Descriptor<DescriptorPublicKey>,
+
+    /// Derivation indexes which may be used for paying the invoice; any
+    /// unhardened index if absent
+    pub index_range: Option<Range<u32>>,
+
+    /// The first derivation index not used by the receiver yet
+    pub next_index_hint: Option<u32>,
+}
+
+/// Tracking beneficiaries are ordered by their descriptor, then by the
+/// bounds of the index range and then by the next index hint.
+impl Ord for TrackingBeneficiary {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.descriptor
+            .cmp(&other.descriptor)
+            .then_with(|| {
+                let bounds = |range: &Option<Range<u32>>| {
+                    range.as_ref().map(|range| (range.start, range.end))
+                };
+                bounds(&self.index_range).cmp(&bounds(&other.index_range))
+            })
+            .then_with(|| self.next_index_hint.cmp(&other.next_index_hint))
+    }
+}
+
+impl PartialOrd for TrackingBeneficiary {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl TrackingBeneficiary {
+    /// String prefix of the tracking beneficiaries
+    pub const PREFIX: &'static str = "track:";
+
+    /// Constructs tracking beneficiary, checking that the index range is
+    /// non-empty and unhardened and that it contains the next index hint.
+    pub fn new(
+        descriptor: Descriptor<DescriptorPublicKey>,
+        index_range: Option<Range<u32>>,
+        next_index_hint: Option<u32>,
+    ) -> Result<TrackingBeneficiary, TrackingError> {
+        let tracking = TrackingBeneficiary {
+            descriptor,
+            index_range,
+            next_index_hint,
+        };
+        if let Some(range) = &tracking.index_range {
+            if range.is_empty() || range.end > HARDENED_INDEX {
+                return Err(TrackingError::InvalidRange(range.clone()));
+            }
+        }
+        if let Some(hint) = tracking.next_index_hint {
+            if !tracking.contains(hint) {
+                return Err(TrackingError::HintOutOfRange(hint));
+            }
+        }
+        Ok(tracking)
+    }
+
+    /// Derivation indexes allowed for the invoice payments.
+    pub fn derivation_range(&self) -> Range<u32> {
+        self.index_range.clone().unwrap_or(0..HARDENED_INDEX)
+    }
+
+    /// Checks whether the derivation index may be used for paying the
+    /// invoice.
+    pub fn contains(&self, index: u32) -> bool {
+        self.derivation_range().contains(&index)
+    }
+
+    /// Returns derivation index for the `payment_no`-th payment of a
+    /// recurrent invoice (starting from zero), counting from the next index
+    /// hint or the start of the index range. Returns `None` if the index
+    /// range is exhausted.
+    pub fn payment_index(&self, payment_no: u32) -> Option<u32> {
+        let base = self
+            .next_index_hint
+            .unwrap_or_else(|| self.derivation_range().start);
+        base.checked_add(payment_no)
+            .filter(|index| self.contains(*index))
+    }
+}
+
+impl From<Descriptor<DescriptorPublicKey>> for TrackingBeneficiary {
+    fn from(descriptor: Descriptor<DescriptorPublicKey>) -> Self {
+        TrackingBeneficiary {
+            descriptor,
+            index_range: None,
+            next_index_hint: None,
+        }
+    }
+}
+
+impl Display for TrackingBeneficiary {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.write_str(Self::PREFIX)?;
+        if let Some(range) = &self.index_range {
+            write!(f, "{}..{}", range.start, range.end)?;
+        }
+        if let Some(hint) = self.next_index_hint {
+            write!(f, "@{}", hint)?;
+        }
+        write!(f, ":{}", self.descriptor)
+    }
+}
+
+impl FromStr for TrackingBeneficiary {
+    type Err = TrackingError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (meta, descriptor) = s
+            .strip_prefix(Self::PREFIX)
+            .and_then(|s| s.split_once(':'))
+            .ok_or(TrackingError::Format)?;
+        let (range, hint) = match meta.split_once('@') {
+            Some((range, hint)) => (
+                range,
+                Some(hint.parse().map_err(|_| TrackingError::Format)?),
+            ),
+            None => (meta, None),
+        };
+        let range = match range.split_once("..") {
+            Some((start, end)) => Some(
+                start.parse().map_err(|_| TrackingError::Format)?
+                    ..end.parse().map_err(|_| TrackingError::Format)?,
+            ),
+            None if range.is_empty() => None,
+            None => return Err(TrackingError::Format),
+        };
+        let descriptor = Descriptor::from_str(descriptor)
+            .map_err(|err| TrackingError::Descriptor(err.to_string()))?;
+        TrackingBeneficiary::new(descriptor, range, hint)
+    }
+}
+
+impl StrictEncode for TrackingBeneficiary {
+    fn strict_encode<E: io::Write>(
+        &self,
+        mut e: E,
+    ) -> Result<usize, strict_encoding::Error> {
+        Ok(strict_encode_list!(e;
+            self.descriptor,
+            self.index_range,
+            self.next_index_hint
+        ))
+    }
+}
+
+impl StrictDecode for TrackingBeneficiary {
+    fn strict_decode<D: io::Read>(
+        mut d: D,
+    ) -> Result<Self, strict_encoding::Error> {
+        TrackingBeneficiary::new(
+            StrictDecode::strict_decode(&mut d)?,
+            StrictDecode::strict_decode(&mut d)?,
+            StrictDecode::strict_decode(&mut d)?,
+        )
+        .map_err(|err| {
+            strict_encoding::Error::DataIntegrityError(err.to_string())
+        })
+    }
+}
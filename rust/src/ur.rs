@@ -0,0 +1,587 @@
+// LNP/BP universal invoice library implementing LNPBP-38 standard
+// Written in 2021 by
+//     Dr. Maxim Orlovsky <orlovsky@pandoracore.com>
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the MIT License
+// along with this software.
+// If not, see <https://opensource.org/licenses/MIT>.
+
+//! Uniform Resources (BC-UR, BCR-2020-005) encoding of invoices for the
+//! transfer with animated QR codes.
+//!
+//! Invoice CBOR representation (see [`crate::cbor`]) is split into fragments
+//! which are emitted as `ur:lnpbp-invoice/<seq>-<count>/<bytewords>` parts.
+//! Parts with sequence number above the fragment count are fountain-coded
+//! mixes of several fragments (BCR-2024-001), so a decoder may reconstruct
+//! the invoice from any sufficient subset of the parts. Each part carries
+//! the number of fragments, message length and checksum, allowing decoders
+//! to detect completion.
+
+use bitcoin::hashes::{sha256, Hash};
+use std::collections::{BTreeMap, BTreeSet};
+use std::convert::TryFrom;
+
+use crate::{CborError, Invoice};
+
+/// UR type used for invoices
+pub const INVOICE_UR_TYPE: &str = "lnpbp-invoice";
+
+/// Minimal length of a multipart UR fragment
+pub const MIN_FRAGMENT_LEN: usize = 10;
+
+/// Maximal number of fragments in a multipart UR accepted by decoders
+pub const MAX_FRAGMENT_COUNT: u32 = 4096;
+
+/// Errors decoding uniform resources.
+#[derive(Clone, PartialEq, Debug, Display, Error, From)]
+#[display(doc_comments)]
+pub enum UrError {
+    /// string is not a uniform resource
+    NotUr,
+
+    /// uniform resource has type `{0}` while `{1}` was expected
+    WrongType(String, &'static str),
+
+    /// invalid bytewords encoding
+    InvalidBytewords,
+
+    /// uniform resource checksum does not match its data
+    ChecksumMismatch,
+
+    /// invalid multipart uniform resource part
+    InvalidPart,
+
+    /// uniform resource parts belong to different messages
+    InconsistentParts,
+
+    /// not enough uniform resource parts to reconstruct the data
+    Incomplete,
+
+    /// invalid invoice data: {0}
+    #[from]
+    InvalidInvoice(CborError),
+}
+
+impl Invoice {
+    /// Encodes invoice as a list of UR parts, each of which has no more than
+    /// `max_fragment_len` bytes of the invoice data. If the whole invoice
+    /// fits into a single fragment a single-part UR is produced; otherwise
+    /// the result contains all fragments followed by the same number of
+    /// fountain-coded parts, providing redundancy for lossy channels.
+    pub fn to_ur_parts(&self, max_fragment_len: usize) -> Vec<String> {
+        let encoder =
+            UrEncoder::new(INVOICE_UR_TYPE, &self.to_cbor(), max_fragment_len);
+        if encoder.is_single_part() {
+            return vec![encoder.single_part()];
+        }
+        let count = encoder.fragment_count() * 2;
+        encoder.take(count).collect()
+    }
+
+    /// Reconstructs invoice from UR parts, which may go in any order,
+    /// contain duplicates or miss some of the parts as long as the remaining
+    /// ones are sufficient for the fountain decoding.
+    pub fn from_ur_parts<'part>(
+        parts: impl IntoIterator<Item = &'part str>,
+    ) -> Result<Invoice, UrError> {
+        let mut decoder = UrDecoder::new(INVOICE_UR_TYPE);
+        for part in parts {
+            if decoder.receive(part)? {
+                break;
+            }
+        }
+        let message = decoder.message().ok_or(UrError::Incomplete)?;
+        Invoice::from_cbor(&message).map_err(UrError::from)
+    }
+}
+
+/// Endless iterator over UR parts for the provided message. The first
+/// [`UrEncoder::fragment_count`] parts contain message fragments as is, the
+/// following ones are fountain-coded.
+#[derive(Clone, Debug)]
+pub struct UrEncoder {
+    ur_type: String,
+    message_len: usize,
+    checksum: u32,
+    fragments: Vec<Vec<u8>>,
+    seq_num: u32,
+}
+
+impl UrEncoder {
+    /// Constructs encoder splitting message into fragments of the same
+    /// length, not exceeding `max_fragment_len` bytes.
+    pub fn new(ur_type: &str, message: &[u8], max_fragment_len: usize) -> Self {
+        let fragment_len = fragment_len(message.len(), max_fragment_len);
+        let mut fragments = message
+            .chunks(fragment_len)
+            .map(<[u8]>::to_vec)
+            .collect::<Vec<_>>();
+        if let Some(last) = fragments.last_mut() {
+            last.resize(fragment_len, 0);
+        }
+        UrEncoder {
+            ur_type: ur_type.to_owned(),
+            message_len: message.len(),
+            checksum: crc32(message),
+            fragments,
+            seq_num: 0,
+        }
+    }
+
+    /// Number of fragments the message is split into
+    pub fn fragment_count(&self) -> usize {
+        self.fragments.len()
+    }
+
+    /// Detects whether the message fits into a single UR part
+    pub fn is_single_part(&self) -> bool {
+        self.fragments.len() <= 1
+    }
+
+    /// Returns single-part UR containing the whole message
+    pub fn single_part(&self) -> String {
+        let mut message = self.fragments.concat();
+        message.truncate(self.message_len);
+        format!("ur:{}/{}", self.ur_type, bytewords_encode(&message))
+    }
+}
+
+impl Iterator for UrEncoder {
+    type Item = String;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.seq_num = self.seq_num.wrapping_add(1);
+        let seq_len = self.fragments.len() as u32;
+        let mut data = vec![0u8; self.fragments[0].len()];
+        for index in choose_fragments(self.seq_num, seq_len, self.checksum) {
+            xor_into(&mut data, &self.fragments[index]);
+        }
+
+        let mut part = vec![];
+        cbor_head(&mut part, 4, 5);
+        cbor_head(&mut part, 0, self.seq_num as u64);
+        cbor_head(&mut part, 0, seq_len as u64);
+        cbor_head(&mut part, 0, self.message_len as u64);
+        cbor_head(&mut part, 0, self.checksum as u64);
+        cbor_head(&mut part, 2, data.len() as u64);
+        part.extend(data);
+
+        Some(format!(
+            "ur:{}/{}-{}/{}",
+            self.ur_type,
+            self.seq_num,
+            seq_len,
+            bytewords_encode(&part)
+        ))
+    }
+}
+
+/// Fountain decoder collecting UR parts until the message can be
+/// reconstructed.
+#[derive(Clone, Debug)]
+pub struct UrDecoder {
+    ur_type: &'static str,
+    params: Option<(u32, usize, u32)>,
+    simple: BTreeMap<usize, Vec<u8>>,
+    mixed: Vec<(BTreeSet<usize>, Vec<u8>)>,
+    message: Option<Vec<u8>>,
+}
+
+impl UrDecoder {
+    /// Constructs decoder accepting parts of a given UR type
+    pub fn new(ur_type: &'static str) -> Self {
+        UrDecoder {
+            ur_type,
+            params: None,
+            simple: empty!(),
+            mixed: empty!(),
+            message: None,
+        }
+    }
+
+    /// Returns reconstructed message, if enough parts were received
+    pub fn message(&self) -> Option<Vec<u8>> {
+        self.message.clone()
+    }
+
+    /// Processes next UR part, returning whether the message is complete.
+    pub fn receive(&mut self, part: &str) -> Result<bool, UrError> {
+        if self.message.is_some() {
+            return Ok(true);
+        }
+        let part = part.trim().to_ascii_lowercase();
+        let rest = part.strip_prefix("ur:").ok_or(UrError::NotUr)?;
+        let (ur_type, rest) = rest.split_once('/').ok_or(UrError::NotUr)?;
+        if ur_type != self.ur_type {
+            return Err(UrError::WrongType(ur_type.to_owned(), self.ur_type));
+        }
+
+        let (seq, body) = match rest.split_once('/') {
+            None => {
+                self.message = Some(bytewords_decode(rest)?);
+                return Ok(true);
+            }
+            Some((seq, body)) => (seq, body),
+        };
+        let data = bytewords_decode(body)?;
+        let (seq_num, seq_len, message_len, checksum, fragment) =
+            parse_part(&data)?;
+        if seq != format!("{}-{}", seq_num, seq_len)
+            || seq_num == 0
+            || seq_len == 0
+            || seq_len > MAX_FRAGMENT_COUNT
+        {
+            return Err(UrError::InvalidPart);
+        }
+        let params = (seq_len, message_len, checksum);
+        match self.params {
+            None => self.params = Some(params),
+            Some(known) if known != params => {
+                return Err(UrError::InconsistentParts)
+            }
+            Some(_) => {}
+        }
+        let fragment_len = fragment_len(message_len, fragment.len());
+        if fragment.len() != fragment_len
+            || message_len.div_ceil(fragment_len) != seq_len as usize
+        {
+            return Err(UrError::InvalidPart);
+        }
+
+        let indexes = choose_fragments(seq_num, seq_len, checksum);
+        self.process(indexes, fragment);
+
+        if self.simple.len() == seq_len as usize {
+            let message = self
+                .simple
+                .values()
+                .flatten()
+                .copied()
+                .take(message_len)
+                .collect::<Vec<_>>();
+            if crc32(&message) != checksum {
+                return Err(UrError::ChecksumMismatch);
+            }
+            self.message = Some(message);
+        }
+        Ok(self.message.is_some())
+    }
+
+    fn process(&mut self, indexes: BTreeSet<usize>, data: Vec<u8>) {
+        let mut queue = vec![(indexes, data)];
+        while let Some((mut indexes, mut data)) = queue.pop() {
+            // Reduce by the known simple fragments
+            for (index, fragment) in &self.simple {
+                if indexes.len() > 1 && indexes.remove(index) {
+                    xor_into(&mut data, fragment);
+                }
+            }
+            if indexes.len() == 1 {
+                let index = *indexes.iter().next().expect("one index");
+                if self.simple.contains_key(&index) {
+                    continue;
+                }
+                self.simple.insert(index, data.clone());
+                // Mixed parts containing new simple fragment are reduced
+                let (affected, rest) = self
+                    .mixed
+                    .drain(..)
+                    .partition::<Vec<_>, _>(|(set, _)| set.contains(&index));
+                self.mixed = rest;
+                queue.extend(affected);
+            } else if !indexes.is_empty()
+                && !self.mixed.iter().any(|(set, _)| *set == indexes)
+            {
+                self.mixed.push((indexes, data));
+            }
+        }
+    }
+}
+
+/// Computes length of fragments which don't exceed `max_fragment_len`
+fn fragment_len(message_len: usize, max_fragment_len: usize) -> usize {
+    let max_fragment_len = max_fragment_len.max(MIN_FRAGMENT_LEN);
+    let max_count = (message_len / MIN_FRAGMENT_LEN).max(1);
+    let mut len = message_len.max(1);
+    for count in 1..=max_count {
+        len = message_len.div_ceil(count);
+        if len <= max_fragment_len {
+            break;
+        }
+    }
+    len.max(1)
+}
+
+fn choose_fragments(
+    seq_num: u32,
+    seq_len: u32,
+    checksum: u32,
+) -> BTreeSet<usize> {
+    if seq_num <= seq_len {
+        return bset! { seq_num as usize - 1 };
+    }
+    let mut seed = seq_num.to_be_bytes().to_vec();
+    seed.extend(checksum.to_be_bytes());
+    let mut rng = Xoshiro256::from_seed(&seed);
+
+    let weights = (1..=seq_len).map(|i| 1.0 / i as f64).collect::<Vec<_>>();
+    let degree = WeightedSampler::new(&weights).next(&mut rng) + 1;
+
+    let mut remaining = (0..seq_len as usize).collect::<Vec<_>>();
+    let mut shuffled = Vec::with_capacity(remaining.len());
+    while !remaining.is_empty() {
+        let index = rng.next_int(0, remaining.len() as u64 - 1) as usize;
+        shuffled.push(remaining.remove(index));
+    }
+    shuffled.into_iter().take(degree).collect()
+}
+
+fn xor_into(target: &mut [u8], source: &[u8]) {
+    for (target, source) in target.iter_mut().zip(source) {
+        *target ^= source;
+    }
+}
+
+fn parse_part(data: &[u8]) -> Result<(u32, u32, usize, u32, Vec<u8>), UrError> {
+    let mut pos = 0usize;
+    let mut head = |major: u8| -> Result<u64, UrError> {
+        let initial = *data.get(pos).ok_or(UrError::InvalidPart)?;
+        pos += 1;
+        if initial >> 5 != major {
+            return Err(UrError::InvalidPart);
+        }
+        let len = match initial & 0x1F {
+            info @ 0..=23 => return Ok(info as u64),
+            24 => 1,
+            25 => 2,
+            26 => 4,
+            27 => 8,
+            _ => return Err(UrError::InvalidPart),
+        };
+        let bytes = data.get(pos..pos + len).ok_or(UrError::InvalidPart)?;
+        pos += len;
+        Ok(bytes.iter().fold(0u64, |acc, byte| acc << 8 | *byte as u64))
+    };
+    let u32_item =
+        |value: u64| u32::try_from(value).map_err(|_| UrError::InvalidPart);
+
+    if head(4)? != 5 {
+        return Err(UrError::InvalidPart);
+    }
+    let seq_num = u32_item(head(0)?)?;
+    let seq_len = u32_item(head(0)?)?;
+    let message_len = u32_item(head(0)?)? as usize;
+    let checksum = u32_item(head(0)?)?;
+    let len = head(2)? as usize;
+    let fragment = data
+        .get(pos..)
+        .filter(|fragment| fragment.len() == len)
+        .ok_or(UrError::InvalidPart)?
+        .to_vec();
+    Ok((seq_num, seq_len, message_len, checksum, fragment))
+}
+
+fn cbor_head(data: &mut Vec<u8>, major: u8, value: u64) {
+    let major = major << 5;
+    match value {
+        0..=23 => data.push(major | value as u8),
+        24..=0xFF => data.extend([major | 24, value as u8]),
+        0x100..=0xFFFF => {
+            data.push(major | 25);
+            data.extend((value as u16).to_be_bytes());
+        }
+        0x10000..=0xFFFF_FFFF => {
+            data.push(major | 26);
+            data.extend((value as u32).to_be_bytes());
+        }
+        _ => {
+            data.push(major | 27);
+            data.extend(value.to_be_bytes());
+        }
+    }
+}
+
+/// CRC-32 (ISO-HDLC, as used by zlib) checksum
+fn crc32(data: &[u8]) -> u32 {
+    !data.iter().fold(!0u32, |crc, byte| {
+        (0..8).fold(crc ^ *byte as u32, |crc, _| {
+            (crc >> 1) ^ (0xEDB8_8320 & (!(crc & 1)).wrapping_add(1))
+        })
+    })
+}
+
+/// Bytewords (BCR-2020-012) in the alphabetic order
+const BYTEWORDS: [&str; 256] = [
+    "able", "acid", "also", "apex", "aqua", "arch", "atom", "aunt", "away",
+    "axis", "back", "bald", "barn", "belt", "beta", "bias", "blue", "body",
+    "brag", "brew", "bulb", "buzz", "calm", "cash", "cats", "chef", "city",
+    "claw", "code", "cola", "cook", "cost", "crux", "curl", "cusp", "cyan",
+    "dark", "data", "days", "deli", "dice", "diet", "door", "down", "draw",
+    "drop", "drum", "dull", "duty", "each", "easy", "echo", "edge", "epic",
+    "even", "exam", "exit", "eyes", "fact", "fair", "fern", "figs", "film",
+    "fish", "fizz", "flap", "flew", "flux", "foxy", "free", "frog", "fuel",
+    "fund", "gala", "game", "gear", "gems", "gift", "girl", "glow", "good",
+    "gray", "grim", "guru", "gush", "gyro", "half", "hang", "hard", "hawk",
+    "heat", "help", "high", "hill", "holy", "hope", "horn", "huts", "iced",
+    "idea", "idle", "inch", "inky", "into", "iris", "iron", "item", "jade",
+    "jazz", "join", "jolt", "jowl", "judo", "jugs", "jump", "junk", "jury",
+    "keep", "keno", "kept", "keys", "kick", "kiln", "king", "kite", "kiwi",
+    "knob", "lamb", "lava", "lazy", "leaf", "legs", "liar", "limp", "lion",
+    "list", "logo", "loud", "love", "luau", "luck", "lung", "main", "many",
+    "math", "maze", "memo", "menu", "meow", "mild", "mint", "miss", "monk",
+    "nail", "navy", "need", "news", "next", "noon", "note", "numb", "obey",
+    "oboe", "omit", "onyx", "open", "oval", "owls", "paid", "part", "peck",
+    "play", "plus", "poem", "pool", "pose", "puff", "puma", "purr", "quad",
+    "quiz", "race", "ramp", "real", "redo", "rich", "road", "rock", "roof",
+    "ruby", "ruin", "runs", "rust", "safe", "saga", "scar", "sets", "silk",
+    "skew", "slot", "soap", "solo", "song", "stub", "surf", "swan", "taco",
+    "task", "taxi", "tent", "tied", "time", "tiny", "toil", "tomb", "toys",
+    "trip", "tuna", "twin", "ugly", "undo", "unit", "urge", "user", "vast",
+    "very", "veto", "vial", "vibe", "view", "visa", "void", "vows", "wall",
+    "wand", "warm", "wasp", "wave", "waxy", "webs", "what", "when", "whiz",
+    "wolf", "work", "yank", "yawn", "yell", "yoga", "yurt", "zaps", "zero",
+    "zest", "zinc", "zone", "zoom",
+];
+
+/// Encodes data with appended checksum as minimal bytewords
+fn bytewords_encode(data: &[u8]) -> String {
+    let checksum = crc32(data).to_be_bytes();
+    data.iter()
+        .chain(&checksum)
+        .flat_map(|byte| {
+            let word = BYTEWORDS[*byte as usize].as_bytes();
+            [word[0] as char, word[3] as char]
+        })
+        .collect()
+}
+
+/// Decodes minimal bytewords, checking and removing the checksum
+fn bytewords_decode(s: &str) -> Result<Vec<u8>, UrError> {
+    if !s.len().is_multiple_of(2) || !s.is_ascii() {
+        return Err(UrError::InvalidBytewords);
+    }
+    let mut data = s
+        .as_bytes()
+        .chunks(2)
+        .map(|pair| {
+            BYTEWORDS
+                .iter()
+                .position(|word| {
+                    let word = word.as_bytes();
+                    word[0] == pair[0] && word[3] == pair[1]
+                })
+                .map(|pos| pos as u8)
+                .ok_or(UrError::InvalidBytewords)
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    if data.len() < 4 {
+        return Err(UrError::InvalidBytewords);
+    }
+    let checksum = data.split_off(data.len() - 4);
+    if crc32(&data).to_be_bytes()[..] != checksum[..] {
+        return Err(UrError::ChecksumMismatch);
+    }
+    Ok(data)
+}
+
+/// Xoshiro256** generator seeded with SHA-256 of the seed, as specified
+/// by BC-UR
+struct Xoshiro256([u64; 4]);
+
+impl Xoshiro256 {
+    fn from_seed(seed: &[u8]) -> Self {
+        let hash = sha256::Hash::hash(seed).into_inner();
+        let mut state = [0u64; 4];
+        for (no, chunk) in hash.chunks(8).enumerate() {
+            let mut buf = [0u8; 8];
+            buf.copy_from_slice(chunk);
+            state[no] = u64::from_be_bytes(buf);
+        }
+        Xoshiro256(state)
+    }
+
+    fn next(&mut self) -> u64 {
+        let s = &mut self.0;
+        let result = s[1].wrapping_mul(5).rotate_left(7).wrapping_mul(9);
+        let t = s[1] << 17;
+        s[2] ^= s[0];
+        s[3] ^= s[1];
+        s[1] ^= s[2];
+        s[0] ^= s[3];
+        s[2] ^= t;
+        s[3] = s[3].rotate_left(45);
+        result
+    }
+
+    fn next_double(&mut self) -> f64 {
+        self.next() as f64 / (u64::MAX as f64 + 1.0)
+    }
+
+    fn next_int(&mut self, low: u64, high: u64) -> u64 {
+        (self.next_double() * (high - low + 1) as f64) as u64 + low
+    }
+}
+
+/// Walker-Vose alias method sampler
+struct WeightedSampler {
+    probs: Vec<f64>,
+    aliases: Vec<usize>,
+}
+
+impl WeightedSampler {
+    fn new(weights: &[f64]) -> Self {
+        let count = weights.len();
+        let sum = weights.iter().sum::<f64>();
+        let mut probs = weights
+            .iter()
+            .map(|weight| weight * count as f64 / sum)
+            .collect::<Vec<_>>();
+
+        let mut small = vec![];
+        let mut large = vec![];
+        for (no, prob) in probs.iter().enumerate().rev() {
+            if *prob < 1.0 {
+                small.push(no);
+            } else {
+                large.push(no);
+            }
+        }
+
+        let mut result = vec![0.0; count];
+        let mut aliases = vec![0; count];
+        while let (Some(a), Some(g)) = (small.pop(), large.pop()) {
+            result[a] = probs[a];
+            aliases[a] = g;
+            probs[g] += probs[a] - 1.0;
+            if probs[g] < 1.0 {
+                small.push(g);
+            } else {
+                large.push(g);
+            }
+        }
+        for no in large.into_iter().chain(small) {
+            result[no] = 1.0;
+        }
+
+        WeightedSampler {
+            probs: result,
+            aliases,
+        }
+    }
+
+    fn next(&self, rng: &mut Xoshiro256) -> usize {
+        let r1 = rng.next_double();
+        let r2 = rng.next_double();
+        let no = (self.probs.len() as f64 * r1) as usize;
+        if r2 < self.probs[no] {
+            no
+        } else {
+            self.aliases[no]
+        }
+    }
+}
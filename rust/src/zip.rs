@@ -0,0 +1,172 @@
+// LNP/BP universal invoice library implementing LNPBP-38 standard
+// Written in 2021 by
+//     Dr. Maxim Orlovsky <orlovsky@pandoracore.com>
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the MIT License
+// along with this software.
+// If not, see <https://opensource.org/licenses/MIT>.
+
+//! Bech32m strings with DEFLATE-compressed data, produced in the same way as
+//! `lnpbp::bech32::strategies::CompressedStrictEncoding` does, but reusing
+//! per-thread compression state and buffers: allocating the compressor state
+//! costs much more than compressing a typical invoice.
+
+use std::cell::RefCell;
+use std::io::Write;
+use std::mem;
+
+use bech32::{Bech32Writer, FromBase32, ToBase32, Variant};
+use deflate::write::DeflateEncoder;
+use deflate::Compression;
+use inflate::InflateStream;
+use lnpbp::bech32::{Error, RAW_DATA_ENCODING_DEFLATE};
+
+use crate::DecodeLimit;
+
+/// Buffers which have grown above this capacity are released after use, so
+/// a single large invoice does not pin its memory for the thread lifetime.
+const MAX_REUSED_CAPACITY: usize = 0x10000;
+
+thread_local! {
+    static ENCODER: RefCell<Option<Encoder>> = const { RefCell::new(None) };
+    static INFLATER: RefCell<Option<InflateStream>> = const { RefCell::new(None) };
+}
+
+struct Encoder {
+    payload: Vec<u8>,
+    deflate: DeflateEncoder<Vec<u8>>,
+    spare: Vec<u8>,
+}
+
+impl Encoder {
+    fn new() -> Encoder {
+        Encoder {
+            payload: vec![],
+            deflate: DeflateEncoder::new(
+                vec![RAW_DATA_ENCODING_DEFLATE],
+                Compression::Best,
+            ),
+            spare: vec![],
+        }
+    }
+
+    fn encode(
+        &mut self,
+        hrp: &str,
+        payload: impl FnOnce(&mut Vec<u8>),
+    ) -> String {
+        self.payload.clear();
+        payload(&mut self.payload);
+        self.deflate
+            .write_all(&self.payload)
+            .expect("in-memory compression failure");
+
+        let mut next = mem::take(&mut self.spare);
+        next.clear();
+        next.push(RAW_DATA_ENCODING_DEFLATE);
+        let data = self
+            .deflate
+            .reset(next)
+            .expect("in-memory compression failure");
+
+        // HRP, separator, data characters and checksum
+        let len = hrp.len() + 1 + (data.len() * 8).div_ceil(5) + 6;
+        let mut s = String::with_capacity(len);
+        let mut writer = Bech32Writer::new(hrp, Variant::Bech32m, &mut s)
+            .expect("writing to string can't fail");
+        data.write_base32(&mut writer)
+            .and_then(|_| writer.finalize())
+            .expect("writing to string can't fail");
+
+        self.spare = data;
+        for buf in [&mut self.payload, &mut self.spare] {
+            if buf.capacity() > MAX_REUSED_CAPACITY {
+                *buf = vec![];
+            }
+        }
+        s
+    }
+}
+
+/// Constructs bech32m string with the given HRP for the data written by
+/// `payload` closure, compressing them with DEFLATE.
+pub(crate) fn to_bech32_zip_string(
+    hrp: &str,
+    payload: impl FnOnce(&mut Vec<u8>),
+) -> String {
+    ENCODER.with(|cell| match cell.try_borrow_mut() {
+        Ok(mut encoder) => encoder
+            .get_or_insert_with(Encoder::new)
+            .encode(hrp, payload),
+        // Nested encoding from the `payload` closure
+        Err(_) => Encoder::new().encode(hrp, payload),
+    })
+}
+
+/// Decompresses data from bech32m string with the given HRP, failing as
+/// soon as the decompressed data exceed `max_len` bytes.
+pub(crate) fn bech32_zip_str_to_payload(
+    hrp: &str,
+    s: &str,
+    max_len: usize,
+) -> Result<Vec<u8>, Error> {
+    let (prefix, data, variant) = bech32::decode(s)?;
+    if prefix != hrp {
+        return Err(Error::WrongPrefix);
+    }
+    if variant != Variant::Bech32m {
+        return Err(Error::WrongVariant);
+    }
+    let data = Vec::<u8>::from_base32(&data)?;
+    match *data.first().ok_or(Error::NoEncodingPrefix)? {
+        RAW_DATA_ENCODING_DEFLATE => {
+            INFLATER.with(|cell| match cell.try_borrow_mut() {
+                Ok(mut inflater) => {
+                    let inflater =
+                        inflater.get_or_insert_with(InflateStream::new);
+                    inflater.reset();
+                    inflate(inflater, &data[1..], max_len)
+                }
+                Err(_) => {
+                    inflate(&mut InflateStream::new(), &data[1..], max_len)
+                }
+            })
+        }
+        unknown => Err(Error::UnknownRawDataEncoding(unknown)),
+    }
+}
+
+fn inflate(
+    inflater: &mut InflateStream,
+    data: &[u8],
+    max_len: usize,
+) -> Result<Vec<u8>, Error> {
+    let mut decoded = Vec::with_capacity(data.len() * 2);
+    let mut pos = 0;
+    loop {
+        let (read, bytes) =
+            inflater.update(&data[pos..]).map_err(Error::InflateError)?;
+        if bytes.is_empty() {
+            break;
+        }
+        pos += read;
+        decoded.extend_from_slice(bytes);
+        // Stops DEFLATE bombs before they exhaust memory
+        if decoded.len() > max_len {
+            let limit = DecodeLimit {
+                field: "payload",
+                count: decoded.len(),
+                limit: max_len,
+            };
+            return Err(Error::NotStrictEncoded(
+                strict_encoding::Error::DataIntegrityError(limit.to_string()),
+            ));
+        }
+    }
+    Ok(decoded)
+}
@@ -0,0 +1,133 @@
+// LNP/BP universal invoice library implementing LNPBP-38 standard
+// Written in 2021 by
+//     Dr. Maxim Orlovsky <orlovsky@pandoracore.com>
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the MIT License
+// along with this software.
+// If not, see <https://opensource.org/licenses/MIT>.
+
+mod common;
+
+use common::invoice;
+use invoice::{AmountError, AmountExt, Invoice, Quantity};
+
+fn with_quantity(amount: AmountExt, quantity: Option<Quantity>) -> Invoice {
+    let mut invoice = invoice();
+    invoice.set_amount(amount).unwrap();
+    if let Some(quantity) = quantity {
+        invoice.set_quantity(quantity).unwrap();
+    }
+    invoice
+}
+
+#[test]
+fn total_amount_without_quantity() {
+    let invoice = with_quantity(AmountExt::Normal(1000), None);
+    assert_eq!(invoice.total_amount(None), Ok(AmountExt::Normal(1000)));
+    assert_eq!(invoice.total_amount(Some(1)), Ok(AmountExt::Normal(1000)));
+    // Invoices without quantity are paid for a single item only
+    assert_eq!(
+        invoice.total_amount(Some(2)),
+        Err(AmountError::QuantityOutOfRange {
+            requested: 2,
+            min: 1,
+            max: Some(1)
+        })
+    );
+}
+
+#[test]
+fn total_amount_for_default_quantity() {
+    let quantity = Quantity::new(1, Some(10), 3).unwrap();
+    let invoice = with_quantity(AmountExt::Normal(1000), Some(quantity));
+    assert_eq!(invoice.total_amount(None), Ok(AmountExt::Normal(3000)));
+    assert_eq!(
+        invoice.total_amount(Some(10)),
+        Ok(AmountExt::Normal(10_000))
+    );
+
+    let quantity = Quantity::with_range(2, None).unwrap();
+    let invoice = with_quantity(AmountExt::Milli(1, 500), Some(quantity));
+    assert_eq!(invoice.total_amount(None), Ok(AmountExt::Normal(3)));
+    assert_eq!(invoice.total_amount(Some(3)), Ok(AmountExt::Milli(4, 500)));
+}
+
+#[test]
+fn total_amount_for_out_of_range_quantity() {
+    let quantity = Quantity::new(2, Some(5), 2).unwrap();
+    let invoice = with_quantity(AmountExt::Normal(1000), Some(quantity));
+    for requested in [0, 1, 6, u32::MAX] {
+        assert_eq!(
+            invoice.total_amount(Some(requested)),
+            Err(AmountError::QuantityOutOfRange {
+                requested,
+                min: 2,
+                max: Some(5)
+            })
+        );
+    }
+    assert_eq!(invoice.total_amount(Some(5)), Ok(AmountExt::Normal(5000)));
+
+    // Quantity without upper limit
+    let quantity = Quantity::with_range(2, None).unwrap();
+    let invoice = with_quantity(AmountExt::Normal(1), Some(quantity));
+    assert_eq!(
+        invoice.total_amount(Some(u32::MAX)),
+        Ok(AmountExt::Normal(u32::MAX as u64))
+    );
+    assert!(invoice.total_amount(Some(1)).is_err());
+}
+
+#[test]
+fn total_amount_for_any_amount() {
+    let invoice = with_quantity(AmountExt::Any, None);
+    assert_eq!(invoice.total_amount(None), Ok(AmountExt::Any));
+    assert_eq!(
+        invoice.total_amount(Some(1)),
+        Err(AmountError::NotComputable)
+    );
+
+    let quantity = Quantity::new(1, Some(10), 3).unwrap();
+    let invoice = with_quantity(AmountExt::Any, Some(quantity));
+    assert_eq!(invoice.total_amount(None), Ok(AmountExt::Any));
+    assert_eq!(
+        invoice.total_amount(Some(3)),
+        Err(AmountError::NotComputable)
+    );
+}
+
+#[test]
+fn total_amount_overflow() {
+    let quantity = Quantity::with_range(1, None).unwrap();
+    let invoice = with_quantity(AmountExt::Normal(u64::MAX), Some(quantity));
+    assert_eq!(invoice.total_amount(None), Ok(AmountExt::Normal(u64::MAX)));
+    assert_eq!(invoice.total_amount(Some(2)), Err(AmountError::Overflow));
+
+    let invoice =
+        with_quantity(AmountExt::Normal(u64::MAX / 2), Some(quantity));
+    assert_eq!(
+        invoice.total_amount(Some(2)),
+        Ok(AmountExt::Normal(u64::MAX - 1))
+    );
+    assert_eq!(invoice.total_amount(Some(3)), Err(AmountError::Overflow));
+
+    // Fractions are summed up before checking for the overflow
+    let invoice =
+        with_quantity(AmountExt::Milli(u64::MAX / 2, 999), Some(quantity));
+    assert_eq!(
+        invoice.total_amount(Some(2)),
+        Ok(AmountExt::Milli(u64::MAX, 998))
+    );
+    let invoice =
+        with_quantity(AmountExt::Milli(u64::MAX, 999), Some(quantity));
+    assert_eq!(
+        invoice.total_amount(Some(1)),
+        Ok(AmountExt::Milli(u64::MAX, 999))
+    );
+    assert_eq!(invoice.total_amount(Some(2)), Err(AmountError::Overflow));
+}
@@ -0,0 +1,92 @@
+// LNP/BP universal invoice library implementing LNPBP-38 standard
+// Written in 2021 by
+//     Dr. Maxim Orlovsky <orlovsky@pandoracore.com>
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the MIT License
+// along with this software.
+// If not, see <https://opensource.org/licenses/MIT>.
+
+mod common;
+
+use std::str::FromStr;
+
+use common::invoice;
+use invoice::{AmountExt, AssetMetadata, AssetMetadataError, CurrencyData};
+
+fn metadata(ticker: &str, precision: u8) -> AssetMetadata {
+    AssetMetadata::new(ticker, None, precision).unwrap()
+}
+
+#[test]
+fn format_decimals() {
+    let usdt = metadata("USDT", 8);
+    assert_eq!(usdt.format_amount(150_000_000), "1.5 USDT");
+    assert_eq!(usdt.format_amount(100_000_000), "1 USDT");
+    assert_eq!(usdt.format_amount(1), "0.00000001 USDT");
+    assert_eq!(usdt.format_amount(0), "0 USDT");
+    assert_eq!(usdt.format_amount(123_456_789), "1.23456789 USDT");
+    assert_eq!(usdt.format_amount(1_000_000_010), "10.0000001 USDT");
+
+    assert_eq!(metadata("GEM", 0).format_amount(42), "42 GEM");
+
+    let max = metadata("MAX", AssetMetadata::MAX_PRECISION);
+    assert_eq!(max.format_amount(u64::MAX), "18.446744073709551615 MAX");
+    assert_eq!(max.format_amount(1), "0.000000000000000001 MAX");
+}
+
+#[test]
+fn format_invoice_amount() {
+    let mut invoice = invoice();
+    invoice.set_amount(AmountExt::Normal(2_500_000)).unwrap();
+    assert_eq!(invoice.format_amount(), None);
+
+    invoice.set_asset_metadata(metadata("USDC", 6));
+    assert_eq!(invoice.format_amount().as_deref(), Some("2.5 USDC"));
+
+    // Only whole numbers of atomic units can be rendered
+    invoice
+        .set_amount(AmountExt::Milli(2_500_000, 500))
+        .unwrap();
+    assert_eq!(invoice.format_amount(), None);
+    invoice.set_amount(AmountExt::Any).unwrap();
+    assert_eq!(invoice.format_amount(), None);
+}
+
+#[test]
+fn format_with_currency_requirement() {
+    // Fiat price does not affect rendering of the asset amount
+    let mut invoice = invoice();
+    invoice.set_amount(AmountExt::Normal(12_500_000)).unwrap();
+    invoice.set_asset_metadata(metadata("USDT", 6));
+    invoice
+        .add_currency_requirement(CurrencyData::from_str("12.50 EUR").unwrap())
+        .unwrap();
+    assert_eq!(invoice.format_amount().as_deref(), Some("12.5 USDT"));
+    assert_eq!(invoice.currency_requirements()[0].to_string(), "12.50 EUR");
+}
+
+#[test]
+fn metadata_validation() {
+    assert_eq!(
+        AssetMetadata::new("USDT", Some("Tether".to_owned()), 6)
+            .unwrap()
+            .to_string(),
+        "USDT (Tether)"
+    );
+    assert_eq!(metadata("ABCDEFGH", 18).to_string(), "ABCDEFGH");
+    for ticker in ["", "usdt", "USD1", "ABCDEFGHI", "US DT"] {
+        assert_eq!(
+            AssetMetadata::new(ticker, None, 6),
+            Err(AssetMetadataError::Ticker(ticker.to_owned()))
+        );
+    }
+    assert_eq!(
+        AssetMetadata::new("USDT", None, 19),
+        Err(AssetMetadataError::Precision(19))
+    );
+}
@@ -0,0 +1,200 @@
+// LNP/BP universal invoice library implementing LNPBP-38 standard
+// Written in 2021 by
+//     Dr. Maxim Orlovsky <orlovsky@pandoracore.com>
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the MIT License
+// along with this software.
+// If not, see <https://opensource.org/licenses/MIT>.
+
+mod common;
+
+use std::io::Write;
+use std::str::FromStr;
+
+use bech32::{ToBase32, Variant};
+use common::invoice;
+use deflate::write::DeflateEncoder;
+use deflate::Compression;
+use invoice::{Bip21Error, Invoice, InvoiceParseError, INVOICE_URI_SCHEMES};
+use lnpbp::bech32::RAW_DATA_ENCODING_DEFLATE;
+use strict_encoding::StrictEncode;
+
+#[test]
+fn lowercase() {
+    let invoice = invoice();
+    let s = invoice.to_string();
+    assert!(s.starts_with("i1"));
+    assert!(!s.bytes().any(|c| c.is_ascii_uppercase()));
+    assert_eq!(Invoice::from_str(&s).unwrap(), invoice);
+}
+
+#[test]
+fn uppercase() {
+    let invoice = invoice();
+    let s = invoice.to_bech32_string_upper();
+    assert_eq!(s, invoice.to_string().to_ascii_uppercase());
+    assert_eq!(Invoice::from_str(&s).unwrap(), invoice);
+}
+
+#[test]
+fn mixed_case() {
+    let s = invoice().to_string();
+    let mixed = format!("I{}", &s[1..]);
+    assert_eq!(
+        Invoice::from_str(&mixed),
+        Err(InvoiceParseError::Bech32(bech32::Error::MixedCase))
+    );
+    let mixed = format!("{}{}", &s[..10], s[10..].to_ascii_uppercase());
+    assert_eq!(
+        Invoice::from_str(&mixed),
+        Err(InvoiceParseError::Bech32(bech32::Error::MixedCase))
+    );
+}
+
+#[test]
+fn prefixed() {
+    let invoice = invoice();
+    for scheme in INVOICE_URI_SCHEMES {
+        let s = format!("{}{}", scheme, invoice);
+        assert_eq!(Invoice::from_str(&s).unwrap(), invoice);
+        let s = s.to_ascii_uppercase();
+        assert_eq!(Invoice::from_str(&s).unwrap(), invoice);
+    }
+    let s = format!("lightning:{}", invoice.to_bech32_string_upper());
+    assert_eq!(Invoice::from_str(&s).unwrap(), invoice);
+    // Only a single scheme is removed; the rest is checksummed as the
+    // human-readable part
+    assert_eq!(
+        Invoice::from_str(&format!("lightning:lnpbp:{}", invoice)),
+        Err(InvoiceParseError::Checksum)
+    );
+}
+
+#[test]
+fn bip21_prefixed() {
+    let invoice = invoice();
+    let uri = invoice.to_bip21().unwrap();
+    assert!(uri.starts_with("bitcoin:"));
+    assert_eq!(Invoice::from_str(&uri).unwrap(), invoice);
+    let uri = uri.replacen("bitcoin:", "BITCOIN:", 1);
+    assert_eq!(Invoice::from_str(&uri).unwrap(), invoice);
+
+    let uri = format!("bitcoin:{}?amount=1", invoice);
+    assert!(matches!(
+        Invoice::from_str(&uri),
+        Err(InvoiceParseError::Bip21(Bip21Error::InvalidAddress(_)))
+    ));
+}
+
+/// Encodes raw data as bech32m string with a given human-readable part,
+/// without compressing them
+fn encode(hrp: &str, data: &[u8]) -> String {
+    bech32::encode(hrp, data.to_base32(), Variant::Bech32m).unwrap()
+}
+
+/// Compresses invoice data into bech32m string with a given
+/// human-readable part
+fn encode_zip(hrp: &str, data: &[u8]) -> String {
+    let zipped = vec![RAW_DATA_ENCODING_DEFLATE];
+    let mut encoder = DeflateEncoder::new(zipped, Compression::Best);
+    encoder.write_all(data).unwrap();
+    encode(hrp, &encoder.finish().unwrap())
+}
+
+#[test]
+fn wrong_hrp() {
+    let data = invoice().strict_serialize().unwrap();
+    assert_eq!(Invoice::from_str(&encode_zip("i", &data)), Ok(invoice()));
+    let err = Invoice::from_str(&encode_zip("lnbc", &data)).unwrap_err();
+    assert_eq!(
+        err,
+        InvoiceParseError::WrongHrp {
+            found: "lnbc".to_owned()
+        }
+    );
+    assert_eq!(
+        err.to_string(),
+        "the string is not an invoice: it has `lnbc` prefix instead of `i`"
+    );
+}
+
+#[test]
+fn checksum() {
+    let s = invoice().to_string();
+    let last = if s.ends_with('q') { 'p' } else { 'q' };
+    let s = format!("{}{}", &s[..s.len() - 1], last);
+    let err = Invoice::from_str(&s).unwrap_err();
+    assert_eq!(err, InvoiceParseError::Checksum);
+    assert_eq!(
+        err.to_string(),
+        "invoice string is corrupted or mistyped: checksum does not match"
+    );
+
+    // Bech32 checksum of bech32m data
+    let data = invoice().strict_serialize().unwrap();
+    let s = bech32::encode("i", data.to_base32(), Variant::Bech32).unwrap();
+    assert_eq!(Invoice::from_str(&s), Err(InvoiceParseError::Checksum));
+}
+
+#[test]
+fn malformed_bech32() {
+    let s = invoice().to_string();
+    let s = format!("{}b{}", &s[..5], &s[6..]);
+    let err = Invoice::from_str(&s).unwrap_err();
+    assert_eq!(
+        err,
+        InvoiceParseError::Bech32(bech32::Error::InvalidChar('b'))
+    );
+    assert_eq!(
+        err.to_string(),
+        "invoice string is malformed: invalid character (code=b)"
+    );
+}
+
+#[test]
+fn decompression() {
+    // Unknown raw data encoding
+    let s = encode("i", &[0x07, 0x00, 0x01]);
+    assert_eq!(Invoice::from_str(&s), Err(InvoiceParseError::Decompression));
+
+    // Deflate encoding with corrupted compressed data
+    let s = encode("i", &[RAW_DATA_ENCODING_DEFLATE, 0xff, 0xff, 0xff]);
+    let err = Invoice::from_str(&s).unwrap_err();
+    assert_eq!(err, InvoiceParseError::Decompression);
+    assert_eq!(
+        err.to_string(),
+        "invoice data are corrupted and can't be decompressed"
+    );
+}
+
+#[test]
+fn encoding() {
+    let data = invoice().strict_serialize().unwrap();
+    let err = Invoice::from_str(&encode_zip("i", &data[..20])).unwrap_err();
+    assert!(matches!(err, InvoiceParseError::Encoding(_)), "{:?}", err);
+    assert!(err.to_string().starts_with("invoice data are invalid: "));
+}
+
+#[test]
+fn unsupported_version() {
+    let mut data = invoice().strict_serialize().unwrap();
+    data[0] = 1;
+    let err = Invoice::from_str(&encode_zip("i", &data)).unwrap_err();
+    assert_eq!(err, InvoiceParseError::UnsupportedVersion(1));
+    assert_eq!(
+        err.to_string(),
+        "invoice has version 1, which is not supported by this software"
+    );
+}
+
+#[test]
+fn bip21() {
+    let err = Invoice::from_str("bitcoin:?amount=1").unwrap_err();
+    assert!(matches!(err, InvoiceParseError::Bip21(_)), "{:?}", err);
+    assert!(err.to_string().starts_with("invalid BIP-21 URI: "));
+}
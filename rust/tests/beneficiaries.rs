@@ -0,0 +1,496 @@
+// LNP/BP universal invoice library implementing LNPBP-38 standard
+// Written in 2021 by
+//     Dr. Maxim Orlovsky <orlovsky@pandoracore.com>
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the MIT License
+// along with this software.
+// If not, see <https://opensource.org/licenses/MIT>.
+
+mod common;
+
+use std::str::FromStr;
+
+use bitcoin::bech32::{self, ToBase32, Variant};
+use bitcoin::consensus::serialize;
+use bitcoin::hashes::hex::{FromHex, ToHex};
+use bitcoin::psbt::PartiallySignedTransaction;
+use bitcoin::util::base58;
+use bitcoin::Address;
+use common::invoice;
+use invoice::{
+    Beneficiary, BifrostAddress, BifrostAddressError, Invoice,
+    PayerCapabilities, PaymentCode, PaymentCodeError, Recurrent,
+    SilentPaymentCode, SilentPaymentError, SplitShare,
+};
+use strict_encoding::{StrictDecode, StrictEncode};
+
+fn address(s: &str) -> Beneficiary {
+    Beneficiary::Address(Address::from_str(s).unwrap())
+}
+
+fn alt(no: usize) -> Beneficiary {
+    [
+        address("bc1qar0srrr7xfkvy5l643lydnw9re59gtzzwf5mdq"),
+        address("1BvBMSEYstWetqTFn5Au4m4GFg7xJaNVN2"),
+        address("3J98t1WpEZ73CNmQviecrnyiWrnqRhWNLy"),
+    ][no]
+        .clone()
+}
+
+/// Invoice with the main beneficiary and `count` alternative ones
+fn with_alts(count: usize) -> Invoice {
+    let mut invoice = invoice();
+    for no in 0..count {
+        invoice.add_alt_beneficiary(alt(no));
+    }
+    invoice
+}
+
+#[test]
+fn iter_without_alt_beneficiaries() {
+    let invoice = with_alts(0);
+    let mut iter = invoice.beneficiaries();
+    assert_eq!(iter.len(), 1);
+    assert_eq!(iter.next(), Some(invoice.beneficiary()));
+    assert_eq!(iter.len(), 0);
+    assert_eq!(iter.next(), None);
+    assert_eq!(iter.next_back(), None);
+    // Iterator is fused
+    assert_eq!(iter.next(), None);
+
+    let mut iter = invoice.beneficiaries();
+    assert_eq!(iter.next_back(), Some(invoice.beneficiary()));
+    assert_eq!(iter.next(), None);
+    assert_eq!((&invoice).into_iter().count(), 1);
+}
+
+#[test]
+fn reverse_iteration() {
+    let invoice = with_alts(3);
+    let reversed = invoice.beneficiaries().rev().collect::<Vec<_>>();
+    assert_eq!(
+        reversed,
+        vec![&alt(2), &alt(1), &alt(0), invoice.beneficiary()]
+    );
+    assert_eq!(invoice.beneficiaries().last(), Some(&alt(2)));
+    assert_eq!(
+        invoice.beneficiaries().rev().last(),
+        Some(invoice.beneficiary())
+    );
+}
+
+#[test]
+fn mixed_forward_and_backward_iteration() {
+    let invoice = with_alts(3);
+    let mut iter = invoice.beneficiaries();
+    assert_eq!(iter.len(), 4);
+    assert_eq!(iter.next(), Some(invoice.beneficiary()));
+    assert_eq!(iter.next_back(), Some(&alt(2)));
+    assert_eq!(iter.size_hint(), (2, Some(2)));
+    assert_eq!(iter.next_back(), Some(&alt(1)));
+    assert_eq!(iter.next(), Some(&alt(0)));
+    assert_eq!(iter.len(), 0);
+    assert_eq!(iter.next(), None);
+    assert_eq!(iter.next_back(), None);
+
+    // Iterators meet in the middle without yielding items twice
+    let mut iter = invoice.beneficiaries();
+    assert_eq!(iter.next_back(), Some(&alt(2)));
+    assert_eq!(iter.next_back(), Some(&alt(1)));
+    assert_eq!(iter.next_back(), Some(&alt(0)));
+    assert_eq!(iter.next(), Some(invoice.beneficiary()));
+    assert_eq!(iter.next_back(), None);
+
+    let mut iter = invoice.beneficiaries();
+    assert_eq!(iter.nth(1), Some(&alt(0)));
+    assert_eq!(iter.collect::<Vec<_>>(), vec![&alt(1), &alt(2)]);
+}
+
+const DESCRIPTOR: &str = "wpkh(\
+    02f9308a019258c31049344f85f89d5229b531c845836f99b08601f113bce036f9)";
+
+const BOLT: &str = "bolt:\
+    02f9308a019258c31049344f85f89d5229b531c845836f99b08601f113bce036f9@\
+    0101010101010101010101010101010101010101010101010101010101010101";
+
+#[test]
+fn select_alternative_beneficiary() {
+    let descriptor = Beneficiary::from_str(DESCRIPTOR).unwrap();
+    let bolt = Beneficiary::from_str(BOLT).unwrap();
+    let mut invoice = invoice();
+    invoice.add_alt_beneficiary(bolt.clone());
+    invoice.add_alt_beneficiary(descriptor.clone());
+
+    // Only an alternative beneficiary is compatible with the payer
+    assert_eq!(
+        invoice.select_beneficiary(PayerCapabilities::DESCRIPTOR),
+        Some(&descriptor)
+    );
+    assert_eq!(
+        invoice.select_beneficiary(PayerCapabilities::LIGHTNING_BOLT),
+        Some(&bolt)
+    );
+    // The first compatible beneficiary wins
+    assert_eq!(
+        invoice.select_beneficiary(
+            PayerCapabilities::DESCRIPTOR | PayerCapabilities::LIGHTNING_BOLT
+        ),
+        Some(&bolt)
+    );
+    assert_eq!(
+        invoice.select_beneficiary(
+            PayerCapabilities::DESCRIPTOR | PayerCapabilities::ON_CHAIN_ADDRESS
+        ),
+        Some(invoice.beneficiary())
+    );
+    assert_eq!(invoice.select_beneficiary(PayerCapabilities::PSBT), None);
+    assert_eq!(
+        invoice.select_beneficiary(PayerCapabilities::default()),
+        None
+    );
+    assert_eq!(
+        invoice
+            .selectable_beneficiaries(PayerCapabilities::DESCRIPTOR)
+            .collect::<Vec<_>>(),
+        vec![&descriptor]
+    );
+}
+
+#[test]
+fn select_split_payment_beneficiary() {
+    let descriptor = Beneficiary::from_str(DESCRIPTOR).unwrap();
+    let mut invoice = invoice();
+    invoice.add_alt_beneficiary(descriptor);
+    invoice
+        .set_payment_split(vec![SplitShare::BasisPoints(5000)])
+        .unwrap();
+
+    // Split payments must pay all of the beneficiaries
+    assert_eq!(
+        invoice.select_beneficiary(PayerCapabilities::DESCRIPTOR),
+        None
+    );
+    assert_eq!(
+        invoice.select_beneficiary(PayerCapabilities::ON_CHAIN_ADDRESS),
+        None
+    );
+    assert_eq!(
+        invoice.select_beneficiary(
+            PayerCapabilities::DESCRIPTOR | PayerCapabilities::ON_CHAIN_ADDRESS
+        ),
+        Some(invoice.beneficiary())
+    );
+}
+
+// Receiving address of the BIP-352 test vectors
+const SILENT_PAYMENT: &str = "sp1qqgste7k9hx0qftg6qmwlkqtwuy6cycyavzmzj85c6qdfhjdpdjtdgqjuexzk6murw56suy3e0rd2cgqvycxttddwsvgxe2usfpxumr70xc9pkqwv";
+
+// Payment code of Alice from the BIP-47 test vectors
+const PAYMENT_CODE: &str = "PM8TJTLJbPRGxSbc8EJi42Wrr6QbNSaSSVJ5Y3E4pbCYiTHUskHg13935Ubb7q8tx9GVbh2UuRnBc3WSyJHhUrw8KhprKnn9eDznYGieTzFcwQRya4GA";
+
+#[test]
+fn silent_payment_round_trip() {
+    let code = SilentPaymentCode::from_str(SILENT_PAYMENT).unwrap();
+    assert!(!code.testnet);
+    assert_eq!(code.to_string(), SILENT_PAYMENT);
+
+    let beneficiary = Beneficiary::from_str(SILENT_PAYMENT).unwrap();
+    assert_eq!(beneficiary, Beneficiary::SilentPayment(code));
+    assert_eq!(beneficiary.to_string(), SILENT_PAYMENT);
+    let forced = Beneficiary::from_str(&format!("sp:{}", SILENT_PAYMENT));
+    assert_eq!(forced.unwrap(), beneficiary);
+
+    let testnet = SilentPaymentCode::new(code.scan_key, code.spend_key, true);
+    let s = testnet.to_string();
+    assert!(s.starts_with("tsp1"));
+    assert_eq!(SilentPaymentCode::from_str(&s), Ok(testnet));
+    assert_eq!(
+        Beneficiary::from_str(&s).unwrap(),
+        Beneficiary::SilentPayment(testnet)
+    );
+}
+
+#[test]
+fn silent_payment_errors() {
+    let code = SilentPaymentCode::from_str(SILENT_PAYMENT).unwrap();
+    let mut payload = code.scan_key.serialize().to_vec();
+    payload.extend(code.spend_key.serialize());
+    let encode = |hrp: &str, version: u8, payload: &[u8], variant| {
+        let mut data = vec![bech32::u5::try_from_u8(version).unwrap()];
+        data.extend(payload.to_base32());
+        bech32::encode(hrp, data, variant).unwrap()
+    };
+    assert_eq!(encode("sp", 0, &payload, Variant::Bech32m), SILENT_PAYMENT);
+    for (s, err) in [
+        (
+            encode("sp", 0, &payload, Variant::Bech32),
+            SilentPaymentError::NonBech32m,
+        ),
+        (
+            encode("bc", 0, &payload, Variant::Bech32m),
+            SilentPaymentError::UnknownHrp,
+        ),
+        (
+            encode("sp", 1, &payload, Variant::Bech32m),
+            SilentPaymentError::UnsupportedVersion(1),
+        ),
+        (
+            encode("sp", 0, &payload[..65], Variant::Bech32m),
+            SilentPaymentError::WrongLen,
+        ),
+        (
+            encode("sp", 0, &[0x05; 66], Variant::Bech32m),
+            SilentPaymentError::InvalidKey,
+        ),
+    ] {
+        assert_eq!(SilentPaymentCode::from_str(&s), Err(err), "{}", s);
+        assert!(Beneficiary::from_str(&format!("sp:{}", s)).is_err());
+    }
+}
+
+#[test]
+fn payment_code_round_trip() {
+    let code = PaymentCode::from_str(PAYMENT_CODE).unwrap();
+    assert_eq!(code.version, 1);
+    assert_eq!(code.to_string(), PAYMENT_CODE);
+    assert_eq!(PaymentCode::from_payload(&code.to_payload()), Ok(code));
+
+    let beneficiary = Beneficiary::from_str(PAYMENT_CODE).unwrap();
+    assert_eq!(beneficiary, Beneficiary::PaymentCode(code));
+    assert_eq!(beneficiary.to_string(), PAYMENT_CODE);
+
+    let new = PaymentCode::new(code.public_key, code.chain_code);
+    assert_eq!(PaymentCode::from_str(&new.to_string()), Ok(new));
+}
+
+#[test]
+fn payment_code_vector() {
+    // Alice's payment code from the BIP-47 test vectors
+    let payload = Vec::<u8>::from_hex(
+        "010002b85034fb08a8bfefd22848238257b252721454bbbfba2c3667f168837ea2cdad\
+         671af9f65904632e2dcc0c6ad314e11d53fc82fa4c4ea27a4a14eccecc478fee\
+         00000000000000000000000000",
+    )
+    .unwrap();
+    let code = PaymentCode::from_str(PAYMENT_CODE).unwrap();
+    assert_eq!(code.to_payload().to_vec(), payload);
+    assert_eq!(code.version, 1);
+    assert_eq!(code.features, 0);
+    assert_eq!(
+        code.public_key.to_string(),
+        "02b85034fb08a8bfefd22848238257b252721454bbbfba2c3667f168837ea2cdad"
+    );
+    assert_eq!(
+        code.chain_code.to_hex(),
+        "671af9f65904632e2dcc0c6ad314e11d53fc82fa4c4ea27a4a14eccecc478fee"
+    );
+    assert_eq!(PaymentCode::new(code.public_key, code.chain_code), code);
+}
+
+#[test]
+fn fresh_address_per_payment() {
+    let mut invoice = invoice();
+    assert!(!invoice.requires_fresh_address_per_payment());
+    invoice.set_recurrent(Recurrent::Months(1));
+    assert!(invoice.requires_fresh_address_per_payment());
+
+    let code = PaymentCode::from_str(PAYMENT_CODE).unwrap();
+    invoice.set_beneficiary(Beneficiary::PaymentCode(code));
+    assert!(!invoice.requires_fresh_address_per_payment());
+    invoice.set_beneficiary(Beneficiary::from_str(SILENT_PAYMENT).unwrap());
+    assert!(!invoice.requires_fresh_address_per_payment());
+
+    invoice
+        .set_beneficiary(address("bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t4"));
+    invoice.set_recurrent(Recurrent::NonRecurrent);
+    assert!(!invoice.requires_fresh_address_per_payment());
+}
+
+#[test]
+fn payment_code_errors() {
+    let code = PaymentCode::from_str(PAYMENT_CODE).unwrap();
+    let encode = |prefix: u8, payload: &[u8]| {
+        let mut data = vec![prefix];
+        data.extend(payload);
+        base58::check_encode_slice(&data)
+    };
+    let payload = code.to_payload();
+    assert_eq!(encode(PaymentCode::PREFIX, &payload), PAYMENT_CODE);
+
+    let mut reserved = payload;
+    reserved[79] = 1;
+    let mut invalid_key = payload;
+    invalid_key[2] = 0x05;
+    for (s, err) in [
+        (encode(0x48, &payload), PaymentCodeError::WrongPrefix(0x48)),
+        (
+            encode(PaymentCode::PREFIX, &payload[..79]),
+            PaymentCodeError::WrongLen,
+        ),
+        (
+            encode(PaymentCode::PREFIX, &reserved),
+            PaymentCodeError::ReservedBytes,
+        ),
+        (
+            encode(PaymentCode::PREFIX, &invalid_key),
+            PaymentCodeError::InvalidKey,
+        ),
+    ] {
+        assert_eq!(PaymentCode::from_str(&s), Err(err), "{}", s);
+    }
+    // Broken checksum
+    let mut broken = PAYMENT_CODE.to_owned();
+    broken.replace_range(10..11, "x");
+    assert!(matches!(
+        PaymentCode::from_str(&broken),
+        Err(PaymentCodeError::Base58(_))
+    ));
+}
+
+// BIP-174 test vector for a PSBT with one P2PKH input and two outputs
+const PSBT: &str = "cHNidP8BAFUCAAAAASeaIyOl37UfxF8iD6WLD8E+HjNCeSqF1+Ns1jM7XLw5AAAAAAD/////AaBa6gsAAAAAGXapFP/pwAYQl8w7Y28ssEYPpPxCfStFiKwAAAAAAAEBIJVe6gsAAAAAF6kUY0UgD2jRieGtwN8cTRbqjxTA2+uHIgIDsTQcy6doO2r08SOM1ul+cWfVafrEfx5I1HVBhENVvUZGMEMCIAQktY7/qqaU4VWepck7v9SokGQiQFXN8HC2dxRpRC0HAh9cjrD+plFtYLisszrWTt5g6Hhb+zqpS5m9+GFR25qaAQEEIgAgdx/RitRZZm3Unz1WTj28QvTIR3TjYK2haBao7UiNVoEBBUdSIQOxNBzLp2g7avTxI4zW6X5xZ9Vp+sR/HkjUdUGEQ1W9RiED3lXR4drIBeP4pYwfv5uUwC89uq/hJ/78pJlfJvggg71SriIGA7E0HMunaDtq9PEjjNbpfnFn1Wn6xH8eSNR1QYRDVb1GELSmumcAAACAAAAAgAQAAIAiBgPeVdHh2sgF4/iljB+/m5TALz26r+En/vykmV8m+CCDvRC0prpnAAAAgAAAAIAFAACAAAA=";
+
+fn psbt_bytes() -> Vec<u8> {
+    serialize(&PartiallySignedTransaction::from_str(PSBT).unwrap())
+}
+
+#[test]
+fn psbt_round_trip() {
+    let beneficiary = Beneficiary::from_str(PSBT).unwrap();
+    assert!(matches!(beneficiary, Beneficiary::Psbt(_)));
+    // Displayed as a standard BIP-174 base64 string
+    assert_eq!(beneficiary.to_string(), PSBT);
+    assert_eq!(
+        Beneficiary::from_str(&format!("psbt:{}", PSBT)).unwrap(),
+        beneficiary
+    );
+
+    // Hexadecimal PSBT is detected by its magic bytes
+    let hex = psbt_bytes().to_hex();
+    assert!(hex.starts_with("70736274ff"));
+    assert_eq!(Beneficiary::from_str(&hex).unwrap(), beneficiary);
+    assert_eq!(
+        Beneficiary::from_str(&hex.to_uppercase()).unwrap(),
+        beneficiary
+    );
+
+    let data = beneficiary.strict_serialize().unwrap();
+    assert_eq!(Beneficiary::strict_deserialize(&data).unwrap(), beneficiary);
+
+    let invoice = Invoice::new(beneficiary.clone(), Some(1000), None);
+    let decoded = Invoice::from_str(&invoice.to_string()).unwrap();
+    assert_eq!(decoded.beneficiary(), &beneficiary);
+    assert_eq!(decoded.beneficiary().to_string(), PSBT);
+}
+
+#[test]
+fn malformed_psbt() {
+    let mut truncated = psbt_bytes();
+    truncated.truncate(truncated.len() - 10);
+    let truncated = truncated.to_hex();
+    assert!(Beneficiary::from_str(&format!("psbt:{}", truncated)).is_err());
+    // Without the prefix the string is neither a PSBT nor any other
+    // beneficiary, except for the raw data blob
+    assert!(!matches!(
+        Beneficiary::from_str(&truncated),
+        Ok(Beneficiary::Psbt(_))
+    ));
+}
+
+const NODE_ID: &str =
+    "0279be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798";
+const CHANNEL_ID: [u8; 32] = [2u8; 32];
+
+// Bifrost channel ids are displayed in bech32m with `lnch` prefix
+fn channel() -> String {
+    bech32::encode("lnch", CHANNEL_ID.to_base32(), Variant::Bech32m).unwrap()
+}
+
+#[test]
+fn bifrost_vectors() {
+    let bifrost = format!("bifrost:{}", NODE_ID);
+    let address = BifrostAddress::from_str(&bifrost).unwrap();
+    assert!(address.protocols.is_empty());
+    assert_eq!(address.channel_hint, None);
+    assert_eq!(address.to_string(), bifrost);
+    let beneficiary = Beneficiary::from_str(&bifrost).unwrap();
+    assert_eq!(beneficiary, Beneficiary::Bifrost(address));
+    assert_eq!(
+        beneficiary.strict_serialize().unwrap().to_hex(),
+        format!("08{}000000", NODE_ID)
+    );
+
+    let full = format!(
+        "bifrost:{}?protocols=rgb,storm-1&channel={}",
+        NODE_ID,
+        channel()
+    );
+    let address = BifrostAddress::from_str(&full).unwrap();
+    assert_eq!(address.protocols, vec!["rgb", "storm-1"]);
+    assert_eq!(address.to_string(), full);
+    let beneficiary = Beneficiary::Bifrost(address);
+    let data = beneficiary.strict_serialize().unwrap();
+    assert_eq!(
+        data.to_hex(),
+        format!(
+            "08{}02000300726762070073746f726d2d3101{}",
+            NODE_ID,
+            CHANNEL_ID.to_hex()
+        )
+    );
+    assert_eq!(Beneficiary::strict_deserialize(&data).unwrap(), beneficiary);
+    assert_eq!(Beneficiary::from_str(&full).unwrap(), beneficiary);
+
+    let channel_only = format!("bifrost:{}?channel={}", NODE_ID, channel());
+    assert_eq!(
+        BifrostAddress::from_str(&channel_only).unwrap().to_string(),
+        channel_only
+    );
+}
+
+#[test]
+fn bifrost_errors() {
+    for (s, err) in [
+        (NODE_ID.to_owned(), BifrostAddressError::NoPrefix),
+        ("bifrost:02aa".to_owned(), BifrostAddressError::NodeId),
+        (
+            format!("bifrost:{}?protocols=rgb,", NODE_ID),
+            BifrostAddressError::Protocol("".to_owned()),
+        ),
+        (
+            format!("bifrost:{}?protocols=r_g_b", NODE_ID),
+            BifrostAddressError::Protocol("r_g_b".to_owned()),
+        ),
+        (
+            format!("bifrost:{}?channel=02", NODE_ID),
+            BifrostAddressError::Channel,
+        ),
+        (
+            format!("bifrost:{}?fee=1", NODE_ID),
+            BifrostAddressError::UnknownParam("fee=1".to_owned()),
+        ),
+    ] {
+        assert_eq!(BifrostAddress::from_str(&s), Err(err), "{}", s);
+    }
+}
+
+#[test]
+fn select_bifrost_beneficiary() {
+    let bifrost =
+        Beneficiary::from_str(&format!("bifrost:{}", NODE_ID)).unwrap();
+    let mut invoice = invoice();
+    invoice.add_alt_beneficiary(bifrost.clone());
+    assert_eq!(
+        invoice.select_beneficiary(PayerCapabilities::BIFROST),
+        Some(&bifrost)
+    );
+    assert_eq!(
+        invoice.select_beneficiary(PayerCapabilities::LIGHTNING_BOLT),
+        None
+    );
+}
@@ -0,0 +1,146 @@
+// LNP/BP universal invoice library implementing LNPBP-38 standard
+// Written in 2021 by
+//     Dr. Maxim Orlovsky <orlovsky@pandoracore.com>
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the MIT License
+// along with this software.
+// If not, see <https://opensource.org/licenses/MIT>.
+
+mod common;
+
+use std::str::FromStr;
+
+use bitcoin::hashes::Hash;
+use bitcoin::Address;
+use common::invoice;
+use invoice::bip21::BIP21_LNPBP_PARAM;
+use invoice::{AmountExt, Bip21Error, Invoice};
+use lnpbp::chain::{AssetId, Chain};
+
+const ADDRESS: &str = "bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t4";
+
+fn labeled_invoice() -> Invoice {
+    let mut invoice = invoice();
+    invoice.set_amount(AmountExt::Normal(150_000_000)).unwrap();
+    invoice.set_merchant("Coffee & Co".to_owned());
+    invoice.set_purpose("Order #1".to_owned());
+    invoice
+}
+
+#[test]
+fn uri_round_trip() {
+    let invoice = labeled_invoice();
+    let uri = invoice.to_bip21().unwrap();
+    assert_eq!(
+        uri,
+        format!(
+            "bitcoin:{}?amount=1.5&label=Coffee%20%26%20Co\
+             &message=Order%20%231&lnpbp={}",
+            ADDRESS, invoice
+        )
+    );
+    assert_eq!(Invoice::from_bip21(&uri).unwrap(), invoice);
+}
+
+#[test]
+fn uri_without_amount() {
+    let mut invoice = invoice();
+    invoice.set_amount(AmountExt::Any).unwrap();
+    let uri = invoice.to_bip21().unwrap();
+    assert_eq!(uri, format!("bitcoin:{}?lnpbp={}", ADDRESS, invoice));
+    assert_eq!(Invoice::from_bip21(&uri).unwrap(), invoice);
+
+    invoice.set_amount(AmountExt::Milli(1, 500)).unwrap();
+    assert_eq!(invoice.to_bip21(), Err(Bip21Error::UnrepresentableAmount));
+}
+
+#[test]
+fn uri_amount_for_native_asset_only() {
+    let testnet =
+        Address::from_str("tb1qw508d6qejxtdg4y5r3zarvary0c5xw7kxpjzsx")
+            .unwrap();
+    let invoice = Invoice::with_address(testnet.clone(), Some(1000));
+    assert_eq!(*invoice.asset(), Some(Chain::Testnet3.native_asset()));
+    let uri = invoice.to_bip21().unwrap();
+    assert!(uri.starts_with(&format!("bitcoin:{}?amount=0.00001&", testnet)));
+    assert_eq!(Invoice::from_bip21(&uri).unwrap(), invoice);
+
+    let mut invoice = invoice;
+    invoice.set_asset(AssetId::from_inner([7; 32]));
+    let uri = invoice.to_bip21().unwrap();
+    assert_eq!(uri, format!("bitcoin:{}?lnpbp={}", testnet, invoice));
+    assert_eq!(Invoice::from_bip21(&uri).unwrap(), invoice);
+    // Amount of the asset can't be given in BTC
+    let uri = format!("bitcoin:{}?amount=0.00001&lnpbp={}", testnet, invoice);
+    assert_eq!(
+        Invoice::from_bip21(&uri),
+        Err(Bip21Error::InconsistentParam("amount".to_owned()))
+    );
+}
+
+#[test]
+fn uri_contradicting_invoice() {
+    let invoice = labeled_invoice();
+    let uri = |params: &str| {
+        format!(
+            "bitcoin:{}?{}&{}={}",
+            ADDRESS, params, BIP21_LNPBP_PARAM, invoice
+        )
+    };
+    for (params, param) in [
+        ("amount=1.4", "amount"),
+        ("label=Tea", "label"),
+        ("message=Order%20%232", "message"),
+    ] {
+        assert_eq!(
+            Invoice::from_bip21(&uri(params)),
+            Err(Bip21Error::InconsistentParam(param.to_owned()))
+        );
+    }
+    // Omitted and empty parameters are not contradicting
+    assert_eq!(Invoice::from_bip21(&uri("label=")).unwrap(), invoice);
+    assert_eq!(
+        Invoice::from_bip21(&uri("amount=1.50000000&message=Order%20%231"))
+            .unwrap(),
+        invoice
+    );
+
+    let mut any = invoice.clone();
+    any.set_amount(AmountExt::Any).unwrap();
+    let uri = format!("bitcoin:{}?amount=1&lnpbp={}", ADDRESS, any);
+    assert_eq!(
+        Invoice::from_bip21(&uri),
+        Err(Bip21Error::InconsistentParam("amount".to_owned()))
+    );
+
+    let uri = format!(
+        "bitcoin:1BvBMSEYstWetqTFn5Au4m4GFg7xJaNVN2?lnpbp={}",
+        invoice
+    );
+    assert_eq!(
+        Invoice::from_bip21(&uri),
+        Err(Bip21Error::InconsistentInvoice)
+    );
+}
+
+#[test]
+fn plain_uri() {
+    let uri = format!("BITCOIN:{}?amount=0.00001&label=Shop&req-x=1", ADDRESS);
+    assert_eq!(
+        Invoice::from_bip21(&uri),
+        Err(Bip21Error::UnknownRequiredParam("req-x".to_owned()))
+    );
+    let uri = format!("bitcoin:{}?amount=0.00001&label=Shop&x=1", ADDRESS);
+    let invoice = Invoice::from_bip21(&uri).unwrap();
+    assert_eq!(*invoice.amount(), AmountExt::Normal(1000));
+    assert_eq!(invoice.merchant_name(), Some("Shop"));
+    assert_eq!(
+        Invoice::from_bip21("lightning:abc"),
+        Err(Bip21Error::WrongScheme)
+    );
+}
@@ -0,0 +1,509 @@
+// LNP/BP universal invoice library implementing LNPBP-38 standard
+// Written in 2021 by
+//     Dr. Maxim Orlovsky <orlovsky@pandoracore.com>
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the MIT License
+// along with this software.
+// If not, see <https://opensource.org/licenses/MIT>.
+
+#![cfg(feature = "bolt11")]
+
+mod common;
+
+use std::convert::TryFrom;
+use std::str::FromStr;
+use std::time::Duration;
+
+use amplify::{Slice32, Wrapper};
+use bitcoin::hashes::{sha256, Hash, HashEngine, Hmac, HmacEngine};
+use bitcoin::secp256k1::SECP256K1;
+use bitcoin::util::address::Payload;
+use bitcoin::{Address, PubkeyHash};
+use bitcoin_scripts::hlc::HashLock;
+use chrono::{DateTime, TimeZone, Utc};
+use common::keypair;
+use internet2::addr::NodeId;
+use invoice::{
+    AmountExt, Beneficiary, ExpiryTerm, Invoice, InvoiceError, LnAddress,
+    LnFlags, LnPathHint, LnRouteHint, Lock, Network,
+    DEFAULT_MIN_FINAL_CLTV_EXPIRY,
+};
+use lightning::ln::PaymentSecret;
+use lightning_invoice::{Currency, Fallback, InvoiceBuilder, RawInvoice};
+use lnp::p2p::bolt::ShortChannelId;
+use lnpbp::chain::Chain;
+
+fn hop(no: u8) -> LnPathHint {
+    LnPathHint {
+        node_id: NodeId::from(keypair(10 + no).public_key()),
+        short_channel_id: ShortChannelId::from_str(&format!(
+            "{}x{}x0",
+            700_000 + no as u32,
+            no
+        ))
+        .unwrap(),
+        fee_base_msat: 1000,
+        fee_proportional_millionths: 100 * no as u32,
+        cltv_expiry_delta: 40 + no as u16,
+    }
+}
+
+fn ln_address(path_hints: Vec<LnRouteHint>) -> LnAddress {
+    LnAddress {
+        node_id: NodeId::from(keypair(1).public_key()),
+        features: Default::default(),
+        lock: Lock::Hash(HashLock::from(Slice32::from_inner([0x11; 32]))),
+        secret: Some(Slice32::from_inner([0x22; 32])),
+        network: Chain::Mainnet,
+        min_final_cltv_expiry: Some(18),
+        path_hints,
+        ln_flags: Default::default(),
+    }
+}
+
+fn bolt_invoice(path_hints: Vec<LnRouteHint>) -> Invoice {
+    let mut invoice = Invoice::new(
+        Beneficiary::Bolt(ln_address(path_hints)),
+        Some(1000),
+        None,
+    );
+    invoice.set_issued_at(issued_at());
+    invoice
+}
+
+fn issued_at() -> DateTime<Utc> {
+    Utc.timestamp_opt(1_600_000_000, 0).unwrap()
+}
+
+/// Current time used for the conversions, later than the issuance time.
+fn now() -> DateTime<Utc> {
+    issued_at() + chrono::Duration::hours(1)
+}
+
+/// Converts the invoice into BOLT-11 signed with the beneficiary node key and
+/// back.
+fn round_trip(invoice: &Invoice) -> (lightning_invoice::Invoice, Invoice) {
+    let bolt11 = invoice
+        .to_bolt11_signed_with_key(now(), &keypair(1).secret_key())
+        .unwrap();
+    let decoded = Invoice::try_from(bolt11.clone()).unwrap();
+    (bolt11, decoded)
+}
+
+#[test]
+fn two_hop_route_round_trip() {
+    let routes =
+        vec![LnRouteHint(vec![hop(1), hop(2)]), LnRouteHint(vec![hop(3)])];
+    let (bolt11, decoded) = round_trip(&bolt_invoice(routes.clone()));
+    let hints = bolt11.route_hints();
+    assert_eq!(hints.len(), 2);
+    assert_eq!(hints[0].0.len(), 2);
+    assert_eq!(hints[0].0[1].short_channel_id, (700_002 << 40) | (2 << 16));
+    assert_eq!(decoded.bolt_beneficiary().unwrap().path_hints, routes);
+
+    let parsed =
+        lightning_invoice::Invoice::from_str(&bolt11.to_string()).unwrap();
+    let decoded = Invoice::try_from(parsed).unwrap();
+    assert_eq!(decoded.bolt_beneficiary().unwrap().path_hints, routes);
+}
+
+#[test]
+fn single_hop_route_round_trip() {
+    let routes = vec![LnRouteHint(vec![hop(1)])];
+    let invoice = bolt_invoice(routes.clone());
+    let raw = invoice.to_bolt11_raw(now(), None).unwrap();
+    assert_eq!(raw.private_routes().len(), 1);
+    let (bolt11, decoded) = round_trip(&invoice);
+    let hop = &bolt11.route_hints()[0].0[0];
+    assert_eq!(hop.src_node_id, keypair(11).public_key());
+    assert_eq!(hop.fees.base_msat, 1000);
+    assert_eq!(hop.fees.proportional_millionths, 100);
+    assert_eq!(hop.cltv_expiry_delta, 41);
+    assert_eq!(decoded.bolt_beneficiary().unwrap().path_hints, routes);
+}
+
+#[test]
+fn no_route_round_trip() {
+    let invoice = bolt_invoice(vec![]);
+    let raw = invoice.to_bolt11_raw(now(), None).unwrap();
+    assert!(raw.private_routes().is_empty());
+    let (bolt11, decoded) = round_trip(&invoice);
+    assert!(bolt11.route_hints().is_empty());
+    assert!(decoded.bolt_beneficiary().unwrap().path_hints.is_empty());
+}
+
+#[test]
+fn ln_flags_round_trip() {
+    let mut invoice = bolt_invoice(vec![]);
+    let (bolt11, decoded) = round_trip(&invoice);
+    assert!(!bolt11.features().unwrap().requires_basic_mpp());
+    assert_eq!(decoded.ln_flags(), Some(LnFlags::empty()));
+
+    invoice.set_ln_flags(LnFlags::MPP_REQUIRED);
+    let (bolt11, decoded) = round_trip(&invoice);
+    assert!(bolt11.features().unwrap().requires_basic_mpp());
+    assert_eq!(decoded.ln_flags(), Some(LnFlags::MPP_REQUIRED));
+
+    // Other flags have no BOLT-11 representation
+    invoice.set_ln_flags(LnFlags::HODL | LnFlags::KEYSEND_OK);
+    let (_, decoded) = round_trip(&invoice);
+    assert_eq!(decoded.ln_flags(), Some(LnFlags::empty()));
+
+    invoice.set_ln_flags(LnFlags::OFFER);
+    assert_eq!(
+        invoice.to_bolt11_signed_with_key(now(), &keypair(1).secret_key()),
+        Err(InvoiceError::OfferBeneficiary)
+    );
+}
+
+#[test]
+fn bolt11_round_trip() {
+    let route = LnRouteHint(vec![hop(1), hop(2)]);
+    let bolt11 = InvoiceBuilder::new(Currency::Bitcoin)
+        .description("coffee".to_owned())
+        .payment_hash(sha256::Hash::from_inner([0x11; 32]))
+        .payment_secret(PaymentSecret([0x22; 32]))
+        .duration_since_epoch(Duration::from_secs(1_600_000_000))
+        .min_final_cltv_expiry(40)
+        .expiry_time(Duration::from_secs(3600))
+        .amount_milli_satoshis(2_500_000)
+        .private_route((&route).into())
+        .fallback(Fallback::PubKeyHash([0x33; 20]))
+        .build_signed(|msg| {
+            SECP256K1.sign_ecdsa_recoverable(msg, &keypair(1).secret_key())
+        })
+        .unwrap();
+
+    let invoice = Invoice::try_from(bolt11.clone()).unwrap();
+    let address = invoice.bolt_beneficiary().unwrap();
+    assert_eq!(address.node_id, ln_address(vec![]).node_id);
+    assert_eq!(address.lock, ln_address(vec![]).lock);
+    assert_eq!(address.secret, ln_address(vec![]).secret);
+    assert_eq!(address.min_final_cltv_expiry, Some(40));
+    assert_eq!(address.path_hints, vec![route]);
+    assert_eq!(*invoice.amount(), AmountExt::Normal(2500));
+    assert_eq!(invoice.purpose().as_deref(), Some("coffee"));
+    assert_eq!(*invoice.issued_at(), Some(issued_at()));
+    assert_eq!(
+        invoice.effective_expiry().unwrap(),
+        Some(issued_at() + chrono::Duration::hours(1))
+    );
+    assert_eq!(*invoice.network(), Some(Network::Mainnet));
+    assert_eq!(
+        *invoice.alt_beneficiaries(),
+        vec![Beneficiary::Address(Address {
+            payload: Payload::PubkeyHash(PubkeyHash::from_inner([0x33; 20])),
+            network: bitcoin::Network::Bitcoin,
+        })]
+    );
+
+    let converted = invoice
+        .to_bolt11_signed_with_key(
+            now() - chrono::Duration::minutes(30),
+            &keypair(1).secret_key(),
+        )
+        .unwrap();
+    assert_eq!(converted, bolt11);
+    assert_eq!(converted.to_string(), bolt11.to_string());
+}
+
+#[test]
+fn timestamp_is_issuance_time() {
+    let invoice = bolt_invoice(vec![]);
+    let raw = invoice.to_bolt11_raw(now(), None).unwrap();
+    assert_eq!(raw.data.timestamp.as_unix_timestamp(), 1_600_000_000);
+    let raw = RawInvoice::try_from(invoice).unwrap();
+    assert_eq!(raw.data.timestamp.as_unix_timestamp(), 1_600_000_000);
+}
+
+#[test]
+fn timestamp_falls_back_to_provided_time() {
+    let invoice =
+        Invoice::new(Beneficiary::Bolt(ln_address(vec![])), Some(1000), None);
+    let raw = invoice.to_bolt11_raw(now(), None).unwrap();
+    assert_eq!(raw.data.timestamp.as_unix_timestamp(), 1_600_003_600);
+    assert_eq!(
+        RawInvoice::try_from(invoice.clone()),
+        Err(InvoiceError::MissingTimestamp)
+    );
+    assert_eq!(
+        invoice.to_bolt11_raw(Utc.timestamp_opt(-1, 0).unwrap(), None),
+        Err(InvoiceError::InvalidTimestamp)
+    );
+}
+
+#[test]
+fn signed_with_fixed_key() {
+    let mut address = ln_address(vec![]);
+    address.secret = None;
+    let mut invoice =
+        Invoice::new(Beneficiary::Bolt(address), Some(1000), None);
+    invoice.set_issued_at(issued_at());
+    invoice.set_purpose("coffee".to_owned());
+
+    let node_key = keypair(1).secret_key();
+    let bolt11 = invoice.to_bolt11_signed_with_key(now(), &node_key).unwrap();
+    assert_eq!(
+        bolt11.to_string(),
+        "lnbc10u1p04uyqqdq2vdhkven9v5pp5zyg3zyg3zyg3zyg3zyg3zyg3zyg3zyg3zyg3\
+         zyg3zyg3zyg3zygssp5p8rn0q4s4llwzr2j34w9wr7cxzjrg4gt5xx6ymnhqjvj7zk8\
+         ne3q9qrsgqcqpjcyskvdq49pu49da7ayytfgful2y7mdftgzj46nfpvyx5h9pg68rs0\
+         yfhq4gae9kle3w59y6yk3u6quf4jqy2sa24grj0z4segrg6lrgpqpw3ga"
+    );
+    // Payment secret is derived from the node key and the payment hash
+    let mut engine = HmacEngine::<sha256::Hash>::new(&node_key[..]);
+    engine.input(b"LNPBP38:bolt11:payment_secret");
+    engine.input(&[0x11; 32]);
+    let secret = Hmac::from_engine(engine).into_inner();
+    assert_eq!(bolt11.payment_secret(), &PaymentSecret(secret));
+    assert_eq!(
+        invoice.to_bolt11_signed_with_key(now(), &node_key).unwrap(),
+        bolt11
+    );
+    assert_eq!(
+        invoice.to_bolt11_signed_with_key(now(), &keypair(2).secret_key()),
+        Err(InvoiceError::NodeKeyMismatch)
+    );
+}
+
+#[test]
+fn payment_secret_is_injected() {
+    let mut address = ln_address(vec![]);
+    address.secret = None;
+    let mut invoice =
+        Invoice::new(Beneficiary::Bolt(address), Some(1000), None);
+    invoice.set_issued_at(issued_at());
+    let secret = Slice32::from_inner([0x44; 32]);
+
+    assert_eq!(
+        RawInvoice::try_from(invoice.clone()),
+        Err(InvoiceError::MissingPaymentSecret)
+    );
+    assert_eq!(
+        invoice.to_bolt11_raw(now(), None),
+        Err(InvoiceError::MissingPaymentSecret)
+    );
+    let raw = invoice.to_bolt11_raw(now(), Some(secret)).unwrap();
+    assert_eq!(raw.payment_secret(), Some(&PaymentSecret([0x44; 32])));
+
+    let bolt11 = invoice
+        .to_bolt11_signed(now(), Some(secret), |msg| {
+            SECP256K1.sign_ecdsa_recoverable(msg, &keypair(1).secret_key())
+        })
+        .unwrap();
+    assert_eq!(bolt11.payment_secret(), &PaymentSecret([0x44; 32]));
+
+    // Payment secret of the beneficiary takes precedence
+    let invoice = bolt_invoice(vec![]);
+    let raw = invoice.to_bolt11_raw(now(), Some(secret)).unwrap();
+    assert_eq!(raw.payment_secret(), Some(&PaymentSecret([0x22; 32])));
+}
+
+fn amount_pico_btc(amount: AmountExt) -> Result<Option<u64>, InvoiceError> {
+    let mut invoice = bolt_invoice(vec![]);
+    invoice.set_amount(amount).unwrap();
+    Ok(invoice.to_bolt11_raw(now(), None)?.amount_pico_btc())
+}
+
+#[test]
+fn amount_conversion() {
+    assert_eq!(amount_pico_btc(AmountExt::Any), Ok(None));
+    assert_eq!(amount_pico_btc(AmountExt::Normal(1)), Ok(Some(10_000)));
+    assert_eq!(amount_pico_btc(AmountExt::Milli(0, 1)), Ok(Some(10)));
+    assert_eq!(amount_pico_btc(AmountExt::Milli(1, 999)), Ok(Some(19_990)));
+    // 1M BTC
+    assert_eq!(
+        amount_pico_btc(AmountExt::Normal(100_000_000_000_000)),
+        Ok(Some(1_000_000_000_000_000_000))
+    );
+}
+
+#[test]
+fn amount_round_trip() {
+    for amount in [
+        AmountExt::Any,
+        AmountExt::Normal(1),
+        AmountExt::Milli(0, 1),
+        AmountExt::Milli(21, 500),
+        AmountExt::Normal(100_000_000_000_000),
+    ] {
+        let mut invoice = bolt_invoice(vec![]);
+        invoice.set_amount(amount).unwrap();
+        let (_, decoded) = round_trip(&invoice);
+        assert_eq!(*decoded.amount(), amount);
+    }
+}
+
+#[test]
+fn amount_overflow() {
+    // 21M BTC don't fit into BOLT-11 pico-bitcoin amount
+    assert_eq!(
+        amount_pico_btc(AmountExt::Normal(2_100_000_000_000_000)),
+        Err(InvoiceError::AmountOverflow)
+    );
+    assert_eq!(
+        amount_pico_btc(AmountExt::Normal(u64::MAX / 10_000)),
+        Ok(Some(18_446_744_073_709_550_000))
+    );
+    assert_eq!(
+        amount_pico_btc(AmountExt::Milli(u64::MAX / 10_000, 999)),
+        Err(InvoiceError::AmountOverflow)
+    );
+    assert_eq!(
+        amount_pico_btc(AmountExt::Normal(u64::MAX / 1000 + 1)),
+        Err(InvoiceError::AmountOverflow)
+    );
+}
+
+#[test]
+fn expiry_and_min_final_cltv_round_trip() {
+    let mut invoice = bolt_invoice(vec![]);
+    invoice.set_expiry(issued_at() + chrono::Duration::hours(2));
+    let mut address = ln_address(vec![]);
+    address.min_final_cltv_expiry = Some(40);
+    invoice.set_beneficiary(Beneficiary::Bolt(address));
+
+    let raw = invoice.to_bolt11_raw(now(), None).unwrap();
+    assert_eq!(raw.expiry_time().unwrap().as_seconds(), 7200);
+    assert_eq!(raw.min_final_cltv_expiry().unwrap().0, 40);
+    let (_, decoded) = round_trip(&invoice);
+    assert_eq!(
+        decoded.effective_expiry(),
+        Ok(Some(issued_at() + chrono::Duration::hours(2)))
+    );
+    let address = decoded.bolt_beneficiary().unwrap();
+    assert_eq!(address.min_final_cltv_expiry, Some(40));
+
+    invoice.set_expiry_term(ExpiryTerm::RelativeSeconds(600));
+    let raw = invoice.to_bolt11_raw(issued_at(), None).unwrap();
+    assert_eq!(raw.expiry_time().unwrap().as_seconds(), 600);
+}
+
+#[test]
+fn absent_expiry_and_min_final_cltv() {
+    let mut invoice = bolt_invoice(vec![]);
+    let mut address = ln_address(vec![]);
+    address.min_final_cltv_expiry = None;
+    invoice.set_beneficiary(Beneficiary::Bolt(address));
+
+    let raw = invoice.to_bolt11_raw(now(), None).unwrap();
+    assert!(raw.expiry_time().is_none());
+    assert_eq!(
+        raw.min_final_cltv_expiry().unwrap().0,
+        DEFAULT_MIN_FINAL_CLTV_EXPIRY as u64
+    );
+    // BOLT-11 invoices without expiry expire in one hour
+    let (_, decoded) = round_trip(&invoice);
+    assert_eq!(
+        decoded.effective_expiry(),
+        Ok(Some(issued_at() + chrono::Duration::hours(1)))
+    );
+    let address = decoded.bolt_beneficiary().unwrap();
+    assert_eq!(
+        address.min_final_cltv_expiry,
+        Some(DEFAULT_MIN_FINAL_CLTV_EXPIRY)
+    );
+}
+
+#[test]
+fn expired_invoice() {
+    let mut invoice = bolt_invoice(vec![]);
+    invoice.set_expiry(now());
+    assert_eq!(
+        invoice.to_bolt11_raw(now(), None),
+        Err(InvoiceError::AlreadyExpired)
+    );
+    assert!(invoice.to_bolt11_raw(issued_at(), None).is_ok());
+
+    invoice.set_expiry(issued_at());
+    assert_eq!(
+        RawInvoice::try_from(invoice),
+        Err(InvoiceError::AlreadyExpired)
+    );
+}
+
+#[test]
+fn address_primary_with_bolt_alternative() {
+    let mainnet =
+        Address::from_str("bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t4")
+            .unwrap();
+    let testnet =
+        Address::from_str("tb1qw508d6qejxtdg4y5r3zarvary0c5xw7kxpjzsx")
+            .unwrap();
+    let mut invoice = Invoice::with_address(mainnet.clone(), Some(1000));
+    invoice.set_issued_at(issued_at());
+    invoice.add_alt_beneficiary(Beneficiary::Address(testnet.clone()));
+    invoice.add_alt_beneficiary(Beneficiary::Bolt(ln_address(vec![])));
+
+    let raw = invoice.to_bolt11_raw(now(), None).unwrap();
+    assert_eq!(raw.currency(), Currency::Bitcoin);
+    // Testnet address is not a valid fallback for mainnet BOLT-11 invoice
+    let (bolt11, decoded) = round_trip(&invoice);
+    assert_eq!(bolt11.fallbacks().len(), 1);
+    assert_eq!(
+        *decoded.alt_beneficiaries(),
+        vec![Beneficiary::Address(mainnet)]
+    );
+
+    let mut invoice = Invoice::with_address(testnet.clone(), Some(1000));
+    invoice.set_issued_at(issued_at());
+    let mut address = ln_address(vec![]);
+    address.network = Chain::Testnet3;
+    invoice.add_alt_beneficiary(Beneficiary::Bolt(address));
+    let raw = invoice.to_bolt11_raw(now(), None).unwrap();
+    assert_eq!(raw.currency(), Currency::BitcoinTestnet);
+    let bolt11 = invoice
+        .to_bolt11_signed_with_key(now(), &keypair(1).secret_key())
+        .unwrap();
+    let decoded = Invoice::try_from(bolt11).unwrap();
+    assert_eq!(
+        *decoded.alt_beneficiaries(),
+        vec![Beneficiary::Address(testnet)]
+    );
+}
+
+#[test]
+fn payment_secret_round_trip() {
+    let (bolt11, decoded) = round_trip(&bolt_invoice(vec![]));
+    assert_eq!(bolt11.payment_secret(), &PaymentSecret([0x22; 32]));
+    let address = decoded.bolt_beneficiary().unwrap();
+    assert_eq!(address.secret, Some(Slice32::from_inner([0x22; 32])));
+
+    // BOLT-11 invoices always have the secret, so invoices without it get
+    // the derived one
+    let mut invoice = bolt_invoice(vec![]);
+    let mut address = ln_address(vec![]);
+    address.secret = None;
+    invoice.set_beneficiary(Beneficiary::Bolt(address));
+    let (bolt11, decoded) = round_trip(&invoice);
+    let address = decoded.bolt_beneficiary().unwrap();
+    assert_eq!(
+        address.secret,
+        Some(Slice32::from_inner(bolt11.payment_secret().0))
+    );
+    assert_ne!(address.secret, Some(Slice32::from_inner([0x22; 32])));
+}
+
+#[test]
+fn chain_from_invoice_network() {
+    let mut address = ln_address(vec![]);
+    address.network = Chain::Testnet3;
+    let mut invoice =
+        Invoice::new(Beneficiary::Bolt(address), Some(1000), None);
+    invoice.set_issued_at(issued_at());
+    // Legacy beneficiary chain is the last fallback
+    let raw = invoice.to_bolt11_raw(now(), None).unwrap();
+    assert_eq!(raw.currency(), Currency::BitcoinTestnet);
+    invoice.set_network(Network::Signet);
+    let raw = invoice.to_bolt11_raw(now(), None).unwrap();
+    assert_eq!(raw.currency(), Currency::Signet);
+
+    let (_, decoded) = round_trip(&invoice);
+    assert_eq!(*decoded.network(), Some(Network::Signet));
+    assert_eq!(decoded.bolt_beneficiary().unwrap().network, Chain::Signet);
+}
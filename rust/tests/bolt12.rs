@@ -0,0 +1,436 @@
+// LNP/BP universal invoice library implementing LNPBP-38 standard
+// Written in 2021 by
+//     Dr. Maxim Orlovsky <orlovsky@pandoracore.com>
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the MIT License
+// along with this software.
+// If not, see <https://opensource.org/licenses/MIT>.
+
+#![cfg(feature = "bolt12")]
+
+mod common;
+
+use std::convert::TryFrom;
+use std::str::FromStr;
+
+use amplify::Slice32;
+use bitcoin::hashes::Hash;
+use bitcoin::secp256k1::PublicKey;
+use bitcoin::BlockHash;
+use chrono::{TimeZone, Utc};
+use common::{invoice, keypair};
+use invoice::bolt12::{
+    OfferRecurrence, RecurrenceBase, RecurrencePaywindow, OFFER_HRP,
+};
+use invoice::{
+    AmountExt, Beneficiary, Invoice, Iso4217, LnFlags, LostField, Network,
+    Offer, OfferError, Quantity, Recurrent,
+};
+use lnpbp::chain::{AssetId, Chain};
+
+fn node_id() -> PublicKey {
+    keypair(1).public_key()
+}
+
+fn full_offer() -> Offer {
+    let mut offer = Offer::new(node_id(), "coffee beans");
+    offer.chains = vec![*Chain::Testnet3.as_genesis_hash()];
+    offer.metadata = Some(vec![0xde, 0xad]);
+    offer.amount = Some(250_000);
+    offer.features = vec![0x02];
+    offer.absolute_expiry = Some(1_900_000_000);
+    offer.paths = Some(vec![1, 2, 3]);
+    offer.issuer = Some("Bean Shop".to_owned());
+    offer.quantity_max = Some(10);
+    offer.recurrence = Some(OfferRecurrence::Months(1));
+    offer.recurrence_base = Some(RecurrenceBase {
+        start_any_period: true,
+        basetime: 1_700_000_000,
+    });
+    offer.recurrence_paywindow = Some(RecurrencePaywindow {
+        seconds_before: 3600,
+        proportional_amount: false,
+        seconds_after: 86400,
+    });
+    offer.recurrence_limit = Some(12);
+    offer.set_unknown_record(41, vec![0xff]).unwrap();
+    offer
+}
+
+/// Offer with the data shared by offers and invoices only.
+fn shared_offer() -> Offer {
+    let mut offer = Offer::new(node_id(), "monthly subscription");
+    offer.chains = vec![*Chain::Signet.as_genesis_hash()];
+    offer.amount = Some(21_000_500);
+    offer.absolute_expiry = Some(1_900_000_000);
+    offer.issuer = Some("Bean Shop".to_owned());
+    offer.quantity_max = Some(0);
+    offer.recurrence = Some(OfferRecurrence::Months(1));
+    offer.recurrence_limit = Some(12);
+    offer
+}
+
+#[test]
+fn offer_string_round_trip() {
+    let offer = full_offer();
+    let s = offer.to_string();
+    assert!(s.starts_with(&format!("{}1", OFFER_HRP)));
+    assert_eq!(Offer::from_str(&s), Ok(offer.clone()));
+    assert_eq!(Offer::from_bytes(&offer.to_bytes()), Ok(offer.clone()));
+    assert_eq!(Offer::from_str(&s.to_ascii_uppercase()), Ok(offer.clone()));
+
+    let split = format!("{}+\n  {}+{}", &s[..20], &s[20..50], &s[50..]);
+    assert_eq!(Offer::from_str(&split), Ok(offer));
+    for invalid in [
+        format!("{}+", s),
+        format!("+{}", s),
+        format!("{}++{}", &s[..20], &s[20..]),
+        format!("{} {}", &s[..20], &s[20..]),
+    ] {
+        assert_eq!(Offer::from_str(&invalid), Err(OfferError::InvalidEncoding));
+    }
+    let mixed = format!("{}{}", &s[..10], s[10..].to_ascii_uppercase());
+    assert_eq!(Offer::from_str(&mixed), Err(OfferError::InvalidEncoding));
+    assert_eq!(
+        Offer::from_str(&s.replacen(OFFER_HRP, "lni", 1)),
+        Err(OfferError::InvalidPrefix)
+    );
+}
+
+#[test]
+fn offer_tlv_layout() {
+    // `offer_description` "a" and `offer_node_id`, followed by unknown odd
+    // record
+    let mut data = vec![0x0a, 0x01, b'a', 0x16, 0x21];
+    data.extend(node_id().serialize());
+    data.extend([0x21, 0x00]);
+    let offer = Offer::from_bytes(&data).unwrap();
+    assert_eq!(offer.description.as_deref(), Some("a"));
+    assert_eq!(offer.node_id, node_id());
+    assert_eq!(
+        offer.unknown_records().collect::<Vec<_>>(),
+        [(0x21, &[][..])]
+    );
+    assert_eq!(offer.to_bytes(), data);
+
+    let mut offer = Offer::new(node_id(), "");
+    offer.amount = Some(0x0100);
+    offer.recurrence = Some(OfferRecurrence::Days(7));
+    let data = offer.to_bytes();
+    assert_eq!(data[..4], [0x08, 0x02, 0x01, 0x00]);
+    assert_eq!(data[4..6], [0x0a, 0x00]);
+    assert_eq!(data[41..], [0x1a, 0x02, 0x01, 0x07]);
+}
+
+#[test]
+fn offer_rejects_invalid_data() {
+    let valid = Offer::new(node_id(), "a").to_bytes();
+    let with = |record: &[u8]| {
+        let mut data = valid.clone();
+        data.extend(record);
+        Offer::from_bytes(&data)
+    };
+
+    assert_eq!(
+        with(&[0x30, 0x00]),
+        Err(OfferError::UnknownRequiredRecord(48))
+    );
+    assert_eq!(with(&[0x50, 0x00]), Err(OfferError::OutOfRangeRecord(80)));
+    assert_eq!(with(&[0x02, 0x00]), Err(OfferError::InvalidTlvStream));
+    assert_eq!(with(&[0x41, 0x02, 0x00]), Err(OfferError::InvalidTlvStream));
+    assert_eq!(
+        with(&[0x42, 0x02, 0x00, 0x01]),
+        Err(OfferError::InvalidRecord(0x42))
+    );
+    assert_eq!(
+        with(&[0x1a, 0x02, 0x04, 0x01]),
+        Err(OfferError::InvalidRecord(0x1a))
+    );
+    // Non-minimal BigSize type
+    assert_eq!(
+        with(&[0xfd, 0x00, 0x41, 0x00]),
+        Err(OfferError::InvalidTlvStream)
+    );
+    assert_eq!(
+        Offer::from_bytes(&valid[..valid.len() - 1]),
+        Err(OfferError::InvalidTlvStream)
+    );
+    let mut offer = Offer::new(node_id(), "a");
+    offer.amount = Some(1000);
+    offer.description = None;
+    assert_eq!(
+        Offer::from_bytes(&offer.to_bytes()),
+        Err(OfferError::MissingDescription)
+    );
+    assert_eq!(
+        Offer::from_bytes(&valid[..3]),
+        Err(OfferError::MissingNodeId)
+    );
+
+    let mut offer = Offer::new(node_id(), "a");
+    offer.currency = Some(Iso4217::from_str("USD").unwrap());
+    assert_eq!(
+        Offer::from_bytes(&offer.to_bytes()),
+        Err(OfferError::MissingAmount)
+    );
+    let mut offer = Offer::new(node_id(), "a");
+    assert_eq!(
+        offer.set_unknown_record(40, vec![]),
+        Err(OfferError::UnknownRequiredRecord(40))
+    );
+    assert_eq!(
+        offer.set_unknown_record(81, vec![]),
+        Err(OfferError::OutOfRangeRecord(81))
+    );
+}
+
+// Valid offers from BOLT-12 `offers-test.json`
+#[test]
+fn upstream_valid_offers() {
+    let issuer_id = PublicKey::from_str(
+        "02eec7245d6b7d2ccb30380bfbe2a3648cd7a942653f5aa340edcea1f283686619",
+    )
+    .unwrap();
+    let offer = |description: Option<&str>| {
+        let mut offer = Offer::new(issuer_id, "");
+        offer.description = description.map(str::to_owned);
+        offer
+    };
+    let described = || offer(Some("Test vectors"));
+
+    let minimal = offer(None);
+    let mut testnet = described();
+    testnet.chains = vec![*Chain::Testnet3.as_genesis_hash()];
+    let mut amount = described();
+    amount.amount = Some(10_000);
+    let mut currency = amount.clone();
+    currency.currency = Some(Iso4217::from_str("USD").unwrap());
+    let mut expiry = described();
+    expiry.absolute_expiry = Some(2_051_184_600);
+    let mut issuer = described();
+    issuer.issuer = Some("https://bolt12.org BOLT12 industries".to_owned());
+    let mut quantity = described();
+    quantity.quantity_max = Some(5);
+    let mut unlimited = described();
+    unlimited.quantity_max = Some(0);
+    let mut single = described();
+    single.quantity_max = Some(1);
+    let mut feature = described();
+    feature.features = vec![0x08, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0];
+
+    for (s, expected) in [
+        ("lno1zcss9mk8y3wkklfvevcrszlmu23kfrxh49px20665dqwmn4p72pksese", minimal),
+        ("lno1pgx9getnwss8vetrw3hhyuckyypwa3eyt44h6txtxquqh7lz5djge4afgfjn7k4rgrkuag0jsd5xvxg", described()),
+        ("lno1qgsyxjtl6luzd9t3pr62xr7eemp6awnejusgf6gw45q75vcfqqqqqqq2p32x2um5ypmx2cm5dae8x93pqthvwfzadd7jejes8q9lhc4rvjxd022zv5l44g6qah82ru5rdpnpj", testnet),
+        ("lno1pqpzwyq2p32x2um5ypmx2cm5dae8x93pqthvwfzadd7jejes8q9lhc4rvjxd022zv5l44g6qah82ru5rdpnpj", amount),
+        ("lno1qcp4256ypqpzwyq2p32x2um5ypmx2cm5dae8x93pqthvwfzadd7jejes8q9lhc4rvjxd022zv5l44g6qah82ru5rdpnpj", currency),
+        ("lno1pgx9getnwss8vetrw3hhyucwq3ay997czcss9mk8y3wkklfvevcrszlmu23kfrxh49px20665dqwmn4p72pksese", expiry),
+        ("lno1pgx9getnwss8vetrw3hhyucjy358garswvaz7tmzdak8gvfj9ehhyeeqgf85c4p3xgsxjmnyw4ehgunfv4e3vggzamrjghtt05kvkvpcp0a79gmy3nt6jsn98ad2xs8de6sl9qmgvcvs", issuer),
+        ("lno1pgx9getnwss8vetrw3hhyuc5qyz3vggzamrjghtt05kvkvpcp0a79gmy3nt6jsn98ad2xs8de6sl9qmgvcvs", quantity),
+        ("lno1pgx9getnwss8vetrw3hhyuc5qqtzzqhwcuj966ma9n9nqwqtl032xeyv6755yeflt235pmww58egx6rxry", unlimited),
+        ("lno1pgx9getnwss8vetrw3hhyuc5qyq3vggzamrjghtt05kvkvpcp0a79gmy3nt6jsn98ad2xs8de6sl9qmgvcvs", single),
+        ("lno1pgx9getnwss8vetrw3hhyucvp5yqqqqqqqqqqqqqqqqqqqqkyypwa3eyt44h6txtxquqh7lz5djge4afgfjn7k4rgrkuag0jsd5xvxg", feature),
+    ] {
+        assert_eq!(Offer::from_str(s), Ok(expected.clone()), "{}", s);
+        assert_eq!(expected.to_string(), s);
+    }
+}
+
+// Malformed offers from BOLT-12 `offers-test.json`
+#[test]
+fn upstream_malformed_offers() {
+    for (s, err) in [
+        ("lno1", OfferError::MissingNodeId),
+        ("lno1pg", OfferError::InvalidTlvStream),
+        ("lno1pt7s", OfferError::InvalidTlvStream),
+        ("lno1pgpq", OfferError::InvalidTlvStream),
+        ("lno1pgpyz", OfferError::InvalidTlvStream),
+        ("lno1pgx9getnwss8vetrw3hhyuc", OfferError::MissingNodeId),
+        ("lno1pgqcq93pqthvwfzadd7jejes8q9lhc4rvjxd022zv5l44g6qah82ru5rdpnpj", OfferError::InvalidRecord(10)),
+        ("lno1pgpgqsgkyypwa3eyt44h6txtxquqh7lz5djge4afgfjn7k4rgrkuag0jsd5xvxg", OfferError::InvalidRecord(10)),
+        ("lno1qcpgqsg2q4q5cj2rg5tzzqhwcuj966ma9n9nqwqtl032xeyv6755yeflt235pmww58egx6rxry", OfferError::InvalidRecord(6)),
+    ] {
+        assert_eq!(Offer::from_str(s), Err(err), "{}", s);
+    }
+}
+
+#[test]
+fn offer_invoice_round_trip() {
+    let offer = shared_offer();
+    let (invoice, lost) = offer.to_invoice().unwrap();
+    assert!(lost.is_lossless(), "{}", lost);
+
+    assert_eq!(invoice.amount(), &AmountExt::Milli(21_000, 500));
+    assert_eq!(invoice.purpose().as_deref(), Some("monthly subscription"));
+    assert_eq!(invoice.merchant().as_deref(), Some("Bean Shop"));
+    assert_eq!(
+        invoice.expiry(),
+        &Some(Utc.timestamp_opt(1_900_000_000, 0).unwrap())
+    );
+    assert_eq!(invoice.recurrent(), &Recurrent::Months(1));
+    assert_eq!(invoice.recurrence_limit(), &Some(12));
+    assert_eq!(
+        invoice.quantity(),
+        &Some(Quantity {
+            min: 1,
+            max: None,
+            default: 1
+        })
+    );
+    assert_eq!(invoice.network(), &Some(Network::Signet));
+    assert_eq!(invoice.asset(), &Some(Chain::Signet.native_asset()));
+    assert_eq!(invoice.ln_flags(), Some(LnFlags::OFFER));
+    match invoice.beneficiary() {
+        Beneficiary::Bolt(address) => {
+            assert_eq!(address.node_id.public_key(), node_id())
+        }
+        _ => panic!("offer invoice must be paid to lightning node"),
+    }
+
+    let (back, lost) = invoice.to_bolt12_offer().unwrap();
+    assert!(lost.is_lossless(), "{}", lost);
+    assert_eq!(back, offer);
+    assert_eq!(Invoice::try_from(back.clone()), Ok(invoice.clone()));
+    assert_eq!(Offer::try_from(invoice), Ok(back));
+}
+
+#[test]
+fn recurrence_conversion() {
+    for (recurrence, recurrent) in [
+        (OfferRecurrence::Seconds(3600), Recurrent::Seconds(3600)),
+        (OfferRecurrence::Days(14), Recurrent::Days(14)),
+        (OfferRecurrence::Months(3), Recurrent::Months(3)),
+        (OfferRecurrence::Years(1), Recurrent::Years(1)),
+    ] {
+        let mut offer = Offer::new(node_id(), "");
+        offer.recurrence = Some(recurrence);
+        let (invoice, _) = offer.to_invoice().unwrap();
+        assert_eq!(invoice.recurrent(), &recurrent);
+        assert_eq!(invoice.to_bolt12_offer().unwrap().0, offer);
+    }
+
+    let mut invoice = Offer::new(node_id(), "").to_invoice().unwrap().0;
+    invoice.set_recurrent(Recurrent::Weeks(2));
+    assert_eq!(
+        invoice.to_bolt12_offer().unwrap().0.recurrence,
+        Some(OfferRecurrence::Days(14))
+    );
+    invoice.set_recurrent(Recurrent::Unknown(0x10, vec![]));
+    assert_eq!(
+        invoice.to_bolt12_offer(),
+        Err(OfferError::UnsupportedRecurrence)
+    );
+
+    let mut offer = Offer::new(node_id(), "");
+    offer.recurrence = Some(OfferRecurrence::Days(100_000));
+    assert_eq!(
+        offer.to_invoice().unwrap().0.recurrent(),
+        &Recurrent::Seconds(8_640_000_000)
+    );
+    offer.recurrence = Some(OfferRecurrence::Years(300));
+    assert_eq!(offer.to_invoice(), Err(OfferError::UnsupportedRecurrence));
+}
+
+#[test]
+fn lossy_conversion_report() {
+    let (_, lost) = full_offer().to_invoice().unwrap();
+    assert_eq!(
+        lost.lost_fields().collect::<Vec<_>>(),
+        [
+            LostField::Extensions,
+            LostField::Metadata,
+            LostField::OfferFeatures,
+            LostField::BlindedPaths,
+            LostField::RecurrenceBase,
+            LostField::RecurrencePaywindow,
+        ]
+    );
+    assert_eq!(
+        lost.to_string(),
+        "lost unknown records and extensions, offer metadata, offer \
+         features, blinded paths to the offer node, recurrence base time, \
+         recurrence payment window"
+    );
+
+    let mut invoice = shared_offer().to_invoice().unwrap().0;
+    let mut address = match invoice.beneficiary() {
+        Beneficiary::Bolt(address) => address.clone(),
+        _ => unreachable!(),
+    };
+    address.ln_flags = LnFlags::HODL;
+    address.secret = Some(Slice32::from([7u8; 32]));
+    address.min_final_cltv_expiry = Some(40);
+    invoice.set_beneficiary(Beneficiary::Bolt(address));
+    invoice.add_alt_beneficiary(common::invoice().beneficiary().clone());
+    invoice.set_issued_at(Utc.timestamp_opt(1_800_000_000, 0).unwrap());
+    invoice
+        .set_quantity(Quantity {
+            min: 2,
+            max: Some(5),
+            default: 2,
+        })
+        .unwrap();
+
+    let (offer, lost) = invoice.to_bolt12_offer().unwrap();
+    assert_eq!(
+        lost.lost_fields().collect::<Vec<_>>(),
+        [
+            LostField::AltBeneficiaries,
+            LostField::PaymentLock,
+            LostField::PaymentSecret,
+            LostField::MinFinalCltvExpiry,
+            LostField::LnFlags,
+            LostField::QuantityBounds,
+            LostField::IssuedAt,
+        ]
+    );
+    assert!(lost.is_lost(LostField::PaymentLock));
+    assert!(!lost.is_lost(LostField::RouteHints));
+    assert_eq!(offer.quantity_max, Some(5));
+    assert_eq!(offer.node_id, node_id());
+}
+
+#[test]
+fn conversion_errors() {
+    assert_eq!(
+        invoice().to_bolt12_offer(),
+        Err(OfferError::NoLightningBeneficiary)
+    );
+
+    let mut invoice = shared_offer().to_invoice().unwrap().0;
+    invoice.set_asset(AssetId::from_inner([1; 32]));
+    assert_eq!(invoice.to_bolt12_offer(), Err(OfferError::NonNativeAsset));
+
+    let mut offer = Offer::new(node_id(), "");
+    offer.currency = Some(Iso4217::from_str("EUR").unwrap());
+    offer.amount = Some(500);
+    assert_eq!(
+        offer.to_invoice(),
+        Err(OfferError::FiatAmount(Iso4217::from_str("EUR").unwrap()))
+    );
+
+    let mut offer = Offer::new(node_id(), "");
+    offer.chains = vec![*Chain::Testnet3.as_genesis_hash(); 2];
+    offer.chains[1] = BlockHash::from_inner([1; 32]);
+    assert!(offer.to_invoice().unwrap().1.is_lost(LostField::Chains));
+    offer.chains.reverse();
+    assert_eq!(offer.to_invoice(), Err(OfferError::UnknownChain));
+}
+
+#[cfg(feature = "bolt11")]
+#[test]
+fn offer_beneficiary_is_not_bolt11() {
+    use invoice::InvoiceError;
+    use lightning_invoice::RawInvoice;
+
+    let invoice = shared_offer().to_invoice().unwrap().0;
+    assert_eq!(
+        RawInvoice::try_from(invoice),
+        Err(InvoiceError::OfferBeneficiary)
+    );
+}
@@ -0,0 +1,565 @@
+// LNP/BP universal invoice library implementing LNPBP-38 standard
+// Written in 2021 by
+//     Dr. Maxim Orlovsky <orlovsky@pandoracore.com>
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the MIT License
+// along with this software.
+// If not, see <https://opensource.org/licenses/MIT>.
+
+//! Tests running the command-line tool binary.
+
+#![cfg(feature = "cli")]
+
+use std::io::Write;
+use std::path::PathBuf;
+use std::process::{Command, Output, Stdio};
+use std::str::FromStr;
+use std::{env, fs, process};
+
+use amplify::s;
+use bitcoin::hashes::hex::ToHex;
+use bitcoin::hashes::Hash;
+use bitcoin::secp256k1::{KeyPair, SECP256K1};
+use bitcoin::Address;
+use invoice::{Invoice, Network};
+use lnpbp::chain::{AssetId, Chain};
+use miniscript::Descriptor;
+use strict_encoding::StrictEncode;
+
+const OUTPOINT: &str =
+    "9a9c2c0d8e84a3a4d0b8a21ea4fb67a8e8b5f1c93a8dbb5e3f4cb3bb07a3a7e2:1";
+const OTHER_OUTPOINT: &str =
+    "9a9c2c0d8e84a3a4d0b8a21ea4fb67a8e8b5f1c93a8dbb5e3f4cb3bb07a3a7e2:2";
+
+fn invoice(args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_invoice"))
+        .args(args)
+        .output()
+        .expect("the binary can be run")
+}
+
+fn temp_path(name: &str) -> PathBuf {
+    env::temp_dir().join(format!("invoice-cli-{}-{}", process::id(), name))
+}
+
+/// Conceals [`OUTPOINT`] returning the blinded UTXO and its blinding factor
+fn conceal(name: &str) -> (String, u64) {
+    let path = temp_path(name);
+    let output =
+        invoice(&["conceal", OUTPOINT, "--save", path.to_str().unwrap()]);
+    assert!(output.status.success());
+    let record: serde_json::Value =
+        serde_json::from_slice(&fs::read(&path).unwrap()).unwrap();
+    fs::remove_file(path).unwrap();
+    assert_eq!(record["outpoint"], OUTPOINT);
+    (
+        record["concealed"].as_str().unwrap().to_owned(),
+        record["blinding"].as_u64().unwrap(),
+    )
+}
+
+fn reveal(concealed: &str, outpoint: &str, blinding: u64) -> Output {
+    let blinding = blinding.to_string();
+    invoice(&[
+        "reveal",
+        concealed,
+        "--outpoint",
+        outpoint,
+        "--blinding",
+        &blinding,
+    ])
+}
+
+#[test]
+fn reveal_matching_seal() {
+    let (concealed, blinding) = conceal("reveal-matching");
+    let output = reveal(&concealed, OUTPOINT, blinding);
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.contains(&concealed) && stdout.contains(OUTPOINT));
+}
+
+#[test]
+fn reveal_mismatched_blinding() {
+    let (concealed, blinding) = conceal("reveal-blinding");
+    let output = reveal(&concealed, OUTPOINT, blinding.wrapping_add(1));
+    assert_eq!(output.status.code(), Some(1));
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.contains("does not conceal"), "{}", stderr);
+}
+
+#[test]
+fn reveal_mismatched_outpoint() {
+    let (concealed, blinding) = conceal("reveal-outpoint");
+    let output = reveal(&concealed, OTHER_OUTPOINT, blinding);
+    assert_eq!(output.status.code(), Some(1));
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.contains(OTHER_OUTPOINT), "{}", stderr);
+}
+
+const SECRET_KEY: &str =
+    "0101010101010101010101010101010101010101010101010101010101010101";
+
+fn unsigned_invoice() -> String {
+    let address =
+        Address::from_str("bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t4")
+            .unwrap();
+    Invoice::with_address(address, Some(1000)).to_string()
+}
+
+fn assert_signed(output: Output) {
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    let signed = String::from_utf8(output.stdout).unwrap();
+    let signed = Invoice::from_str(signed.trim()).unwrap();
+    let keypair = KeyPair::from_seckey_str(SECP256K1, SECRET_KEY).unwrap();
+    signed
+        .verify_signature_with_key(keypair.public_key())
+        .unwrap();
+}
+
+#[test]
+fn sign_with_key_file() {
+    let path = temp_path("sign-key-file");
+    fs::write(&path, format!("{}\n", SECRET_KEY)).unwrap();
+    let output = invoice(&[
+        "sign",
+        &unsigned_invoice(),
+        "--key-file",
+        path.to_str().unwrap(),
+    ]);
+    fs::remove_file(path).unwrap();
+    assert_signed(output);
+}
+
+#[test]
+fn sign_with_key_env() {
+    let output = Command::new(env!("CARGO_BIN_EXE_invoice"))
+        .args(["sign", &unsigned_invoice(), "--key-env", "INVOICE_TEST_KEY"])
+        .env("INVOICE_TEST_KEY", SECRET_KEY)
+        .output()
+        .unwrap();
+    assert_signed(output);
+
+    let output = Command::new(env!("CARGO_BIN_EXE_invoice"))
+        .args(["sign", &unsigned_invoice(), "--key-env", "INVOICE_TEST_KEY"])
+        .env_remove("INVOICE_TEST_KEY")
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(64));
+}
+
+#[test]
+fn sign_with_key_from_stdin() {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_invoice"))
+        .args(["sign", &unsigned_invoice(), "--key-file", "-"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(SECRET_KEY.as_bytes())
+        .unwrap();
+    assert_signed(child.wait_with_output().unwrap());
+
+    // Invoice and key can't be both read from STDIN
+    let output = invoice(&["sign", "--key-file", "-"]);
+    assert_eq!(output.status.code(), Some(64));
+}
+
+#[test]
+fn sign_with_key_argument() {
+    let output = invoice(&["sign", &unsigned_invoice(), "--key", SECRET_KEY]);
+    assert_signed(output);
+
+    let output = invoice(&["sign", &unsigned_invoice(), "--key", "00"]);
+    assert_eq!(output.status.code(), Some(64));
+}
+
+#[test]
+fn sign_requires_single_key_source() {
+    let output = invoice(&["sign", &unsigned_invoice()]);
+    assert_eq!(output.status.code(), Some(2));
+    let output = invoice(&[
+        "sign",
+        &unsigned_invoice(),
+        "--key-file",
+        "-",
+        "--key-env",
+        "INVOICE_TEST_KEY",
+    ]);
+    assert_eq!(output.status.code(), Some(2));
+    let output = invoice(&[
+        "sign",
+        &unsigned_invoice(),
+        "--key",
+        SECRET_KEY,
+        "--key-file",
+        "-",
+    ]);
+    assert_eq!(output.status.code(), Some(2));
+}
+
+fn signed_invoice() -> String {
+    let output = invoice(&["sign", &unsigned_invoice(), "--key", SECRET_KEY]);
+    assert!(output.status.success());
+    String::from_utf8(output.stdout).unwrap().trim().to_owned()
+}
+
+#[test]
+fn verify_signed_invoice() {
+    let output = invoice(&["verify", &signed_invoice()]);
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert_eq!(stdout.trim(), "Invoice signature is valid");
+
+    let keypair = KeyPair::from_seckey_str(SECP256K1, SECRET_KEY).unwrap();
+    let pubkey = keypair.public_key().to_hex();
+    let output = invoice(&["verify", &signed_invoice(), "--pubkey", &pubkey]);
+    assert!(output.status.success());
+}
+
+#[test]
+fn verify_from_stdin() {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_invoice"))
+        .arg("verify")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(format!("{}\n", signed_invoice()).as_bytes())
+        .unwrap();
+    assert!(child.wait_with_output().unwrap().status.success());
+}
+
+#[test]
+fn verify_rejects_bad_signatures() {
+    let output = invoice(&["verify", &unsigned_invoice()]);
+    assert_eq!(output.status.code(), Some(1));
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.contains("signature check failed"), "{}", stderr);
+
+    let other = KeyPair::from_seckey_str(
+        SECP256K1,
+        "0202020202020202020202020202020202020202020202020202020202020202",
+    )
+    .unwrap();
+    let output = invoice(&[
+        "verify",
+        &signed_invoice(),
+        "--pubkey",
+        &other.public_key().to_hex(),
+    ]);
+    assert_eq!(output.status.code(), Some(1));
+}
+
+#[test]
+fn verify_rejects_malformed_invoice() {
+    let output = invoice(&["verify", "lnbp1qqqq"]);
+    assert_eq!(output.status.code(), Some(65));
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.contains("incorrect"), "{}", stderr);
+    assert!(!stderr.contains("signature check failed"), "{}", stderr);
+}
+
+#[test]
+fn address_from_descriptor() {
+    // Account key and receive addresses of the BIP-84 test vectors
+    let descriptor = "wpkh(xpub6CatWdiZiodmUeTDp8LT5or8nmbKNcuyvz7WyksVFkKB4RHwCD3XyuvPEbvqAQY3rAPshWcMLoP2fMFMKHPJ4ZeZXYVUhLv1VMrjPC7PW6V/0/*)";
+    let invoice_str = Invoice::with_descriptor(
+        Descriptor::from_str(descriptor).unwrap(),
+        Some(1000),
+        &Chain::Mainnet,
+    )
+    .to_string();
+
+    let output = invoice(&["address", &invoice_str]);
+    assert!(output.status.success());
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap().trim(),
+        "bc1qcr8te4kr609gcawutmrza0j4xv80jy8z306fyu"
+    );
+    let output = invoice(&["address", &invoice_str, "--index", "1"]);
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap().trim(),
+        "bc1qnjg0jd8228aq7egyzacy8cys3knf9xvrerkf9g"
+    );
+
+    let output = invoice(&["address", &invoice_str, "--network", "testnet"]);
+    assert_eq!(output.status.code(), Some(65));
+    let output = invoice(&["address", &unsigned_invoice()]);
+    assert_eq!(output.status.code(), Some(65));
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.contains("not a descriptor"), "{}", stderr);
+}
+
+/// Converts the invoice into `format` written to a temporary file and reads
+/// it back, returning the Bech32 invoice string
+fn file_round_trip(name: &str, format: &str) -> String {
+    let path = temp_path(name);
+    let path = path.to_str().unwrap();
+    let output = invoice(&[
+        "convert",
+        &unsigned_invoice(),
+        "-i",
+        "bech32",
+        "-o",
+        format,
+        "--out-file",
+        path,
+    ]);
+    assert!(output.status.success());
+    assert!(output.stdout.is_empty());
+    let output =
+        invoice(&["convert", "--in-file", path, "-i", format, "-o", "bech32"]);
+    fs::remove_file(path).unwrap();
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    String::from_utf8(output.stdout).unwrap().trim().to_owned()
+}
+
+#[test]
+fn file_round_trip_raw() {
+    assert_eq!(file_round_trip("raw", "raw"), unsigned_invoice());
+}
+
+#[test]
+fn file_round_trip_hex() {
+    assert_eq!(file_round_trip("hex", "hex"), unsigned_invoice());
+}
+
+#[test]
+fn file_round_trip_bech32() {
+    assert_eq!(file_round_trip("bech32", "bech32"), unsigned_invoice());
+}
+
+#[test]
+fn file_input_is_trimmed_for_text_formats() {
+    let path = temp_path("trimmed");
+    fs::write(&path, format!("{}\r\n\n", unsigned_invoice())).unwrap();
+    let output = invoice(&[
+        "convert",
+        "--in-file",
+        path.to_str().unwrap(),
+        "-o",
+        "bech32",
+    ]);
+    fs::remove_file(path).unwrap();
+    assert!(output.status.success());
+    let converted = String::from_utf8(output.stdout).unwrap();
+    assert_eq!(converted.trim(), unsigned_invoice());
+}
+
+#[test]
+fn rgb_convert_file_round_trip() {
+    let contract_id =
+        "f7e2d0a9a8e13e2c4f8e1f0f7a9e26b7d0c3f1a0b5c6d7e8f9a0b1c2d3e4f5a6";
+    let path = temp_path("rgb-convert");
+    let path = path.to_str().unwrap();
+    let output =
+        invoice(&["rgb-convert", contract_id, "-o", "raw", "--out-file", path]);
+    assert!(output.status.success());
+    assert_eq!(fs::read(path).unwrap().len(), 32);
+    let output =
+        invoice(&["rgb-convert", "--in-file", path, "-i", "raw", "-o", "hex"]);
+    fs::remove_file(path).unwrap();
+    assert!(output.status.success());
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap().trim(),
+        contract_id
+    );
+}
+
+/// Contract id of the genesis from rgb-core test vectors, in its byte
+/// (hex), legacy bech32m and modern forms
+const CONTRACT_ID_HEX: &str =
+    "1cd08cb4db227bd87c3e43812425aa91fe28f3b3051aca1af0c3ae44739d7e97";
+const CONTRACT_ID_LEGACY: &str =
+    "rgb1rnggedxmyfaaslp7gwqjgfd2j8lz3uanq5dv5xhscwhyguua06tsrtc9g2";
+const CONTRACT_ID_MODERN: &str =
+    "rgb:2wUrkwuio5u4bx4HkzXpcxqpx8t7uTnZGoGAodMKJRc2";
+
+fn rgb_convert(asset: &str, input: &str, output: &str) -> String {
+    let output = invoice(&["rgb-convert", asset, "-i", input, "-o", output]);
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    String::from_utf8(output.stdout).unwrap().trim().to_owned()
+}
+
+#[test]
+fn rgb_convert_contract_id_vectors() {
+    for (asset, input) in [
+        (CONTRACT_ID_HEX, "hex"),
+        (CONTRACT_ID_LEGACY, "bech32"),
+        (CONTRACT_ID_LEGACY, "auto"),
+        (CONTRACT_ID_MODERN, "auto"),
+        (CONTRACT_ID_MODERN, "bech32"),
+        (CONTRACT_ID_MODERN, "base58"),
+        // Unprefixed Base58 id produced by the previous versions
+        (&CONTRACT_ID_MODERN[4..], "base58"),
+    ] {
+        assert_eq!(rgb_convert(asset, input, "hex"), CONTRACT_ID_HEX);
+        assert_eq!(rgb_convert(asset, input, "bech32"), CONTRACT_ID_LEGACY);
+        assert_eq!(rgb_convert(asset, input, "base58"), CONTRACT_ID_MODERN);
+    }
+}
+
+#[test]
+fn rgb_convert_modern_contract_id_decorations() {
+    // Chunk separators, URN prefix and mnemonic checksum suffix are
+    // accepted; the checksum is not verified
+    for asset in [
+        "rgb:2wUrkwu-io5u4bx-4HkzXpc-xqpx8t7-uTnZGoG-AodMKJR-c2",
+        "urn:lnp-bp:rgb:2wUrkwuio5u4bx4HkzXpcxqpx8t7uTnZGoGAodMKJRc2",
+        "rgb:2wUrkwu-io5u4bx-4HkzXpc-xqpx8t7-uTnZGoG-AodMKJR-c2#any-words",
+    ] {
+        assert_eq!(rgb_convert(asset, "auto", "hex"), CONTRACT_ID_HEX);
+    }
+
+    // Base58 data of a wrong length or with invalid characters
+    for asset in [
+        "rgb:2wUrkwuio5u4bx4HkzXpcxqpx8t7uTnZGoGAodMKJR",
+        "rgb:0wUrkwuio5u4bx4HkzXpcxqpx8t7uTnZGoGAodMKJRc2",
+    ] {
+        let output =
+            invoice(&["rgb-convert", asset, "-i", "auto", "-o", "hex"]);
+        assert!(!output.status.success(), "{}", asset);
+    }
+}
+
+/// Runs `convert` feeding the data to STDIN
+fn convert_stdin(data: &[u8], input: &str, output: &str) -> Output {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_invoice"))
+        .args(["convert", "-i", input, "-o", output])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child.stdin.take().unwrap().write_all(data).unwrap();
+    child.wait_with_output().unwrap()
+}
+
+/// Strict-encoded invoice which is not a valid UTF-8 string
+fn binary_invoice() -> Vec<u8> {
+    let mut invoice = Invoice::from_str(&unsigned_invoice()).unwrap();
+    invoice.set_asset(AssetId::from_inner([0xFF; 32]));
+    // Trailing whitespace bytes must not be trimmed from binary data
+    invoice.set_purpose(s!("binary\n"));
+    let data = invoice.strict_serialize().unwrap();
+    assert!(data.contains(&0xFF));
+    assert!(std::str::from_utf8(&data).is_err());
+    data
+}
+
+#[test]
+fn raw_stdin_round_trip() {
+    let data = binary_invoice();
+    for input in ["raw", "auto"] {
+        let output = convert_stdin(&data, input, "raw");
+        assert!(
+            output.status.success(),
+            "{}",
+            String::from_utf8_lossy(&output.stderr)
+        );
+        assert_eq!(output.stdout, data, "{}", input);
+    }
+}
+
+#[test]
+fn raw_file_round_trip() {
+    let data = binary_invoice();
+    let path = temp_path("binary");
+    fs::write(&path, &data).unwrap();
+    let output = invoice(&[
+        "convert",
+        "--in-file",
+        path.to_str().unwrap(),
+        "-i",
+        "raw",
+        "-o",
+        "raw",
+    ]);
+    fs::remove_file(path).unwrap();
+    assert!(output.status.success());
+    assert_eq!(output.stdout, data);
+}
+
+#[test]
+fn encoded_stdin_with_line_breaks() {
+    let hex = binary_invoice().to_hex();
+    let output = convert_stdin(format!("{}\n", hex).as_bytes(), "hex", "raw");
+    assert!(output.status.success());
+    assert_eq!(output.stdout, binary_invoice());
+
+    let (head, tail) = hex.split_at(hex.len() / 2);
+    let broken = format!("{}\n{}", head, tail);
+    let output = convert_stdin(broken.as_bytes(), "hex", "raw");
+    assert_eq!(output.status.code(), Some(65));
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.contains("line breaks"), "{}", stderr);
+}
+
+const TESTNET_ADDRESS: &str = "tb1qw508d6qejxtdg4y5r3zarvary0c5xw7kxpjzsx";
+
+fn create(args: &[&str]) -> Invoice {
+    let output = invoice(&[&["create"], args].concat());
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    Invoice::from_str(String::from_utf8(output.stdout).unwrap().trim()).unwrap()
+}
+
+#[test]
+fn create_sets_network() {
+    let created = create(&[TESTNET_ADDRESS, "1000"]);
+    assert_eq!(created.network(), &Some(Network::Testnet3));
+
+    let created = create(&["bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t4"]);
+    assert_eq!(created.network(), &Some(Network::Mainnet));
+
+    let created = create(&[TESTNET_ADDRESS, "1000", "--omit-network"]);
+    assert_eq!(created.network(), &None);
+
+    let output = invoice(&[
+        "create",
+        TESTNET_ADDRESS,
+        "--network",
+        "testnet",
+        "--omit-network",
+    ]);
+    assert_eq!(output.status.code(), Some(2));
+}
+
+#[test]
+fn create_rejects_invalid_expiry() {
+    let output = invoice(&["create", TESTNET_ADDRESS, "--expiry", "5€"]);
+    assert_eq!(output.status.code(), Some(2));
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.contains("Invalid expiry `5€`"), "{}", stderr);
+}
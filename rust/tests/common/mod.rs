@@ -0,0 +1,90 @@
+// LNP/BP universal invoice library implementing LNPBP-38 standard
+// Written in 2021 by
+//     Dr. Maxim Orlovsky <orlovsky@pandoracore.com>
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the MIT License
+// along with this software.
+// If not, see <https://opensource.org/licenses/MIT>.
+
+//! Helpers shared by the integration tests.
+
+#![allow(dead_code)]
+
+use std::io;
+use std::str::FromStr;
+
+use bitcoin::secp256k1::{self, KeyPair, SECP256K1};
+use bitcoin::Address;
+use internet2::tlv;
+use invoice::{AmountExt, Beneficiary, Invoice};
+use lnpbp::bech32;
+use strict_encoding::{StrictDecode, StrictEncode};
+
+/// Raw invoice data, encoded into bech32 string as is.
+#[derive(Clone)]
+pub struct RawInvoice(pub Vec<u8>);
+
+impl bech32::Strategy for RawInvoice {
+    const HRP: &'static str = "i";
+    type Strategy = bech32::strategies::CompressedStrictEncoding;
+}
+
+impl StrictEncode for RawInvoice {
+    fn strict_encode<E: io::Write>(
+        &self,
+        mut e: E,
+    ) -> Result<usize, strict_encoding::Error> {
+        e.write_all(&self.0)?;
+        Ok(self.0.len())
+    }
+}
+
+impl StrictDecode for RawInvoice {
+    fn strict_decode<D: io::Read>(
+        mut d: D,
+    ) -> Result<Self, strict_encoding::Error> {
+        let mut data = vec![];
+        d.read_to_end(&mut data)?;
+        Ok(RawInvoice(data))
+    }
+}
+
+pub fn invoice() -> Invoice {
+    let address =
+        Address::from_str("bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t4")
+            .unwrap();
+    Invoice::with_address(address, Some(1000))
+}
+
+/// Serializes invoice adding raw TLV records to its TLV stream.
+pub fn with_records(invoice: &Invoice, records: &[(u64, &[u8])]) -> Vec<u8> {
+    let (mut payload, mut stream) =
+        split_records(&invoice.strict_serialize().unwrap());
+    for (type_no, value) in records {
+        stream.insert((*type_no).into(), *value);
+    }
+    stream.strict_encode(&mut payload).unwrap();
+    payload
+}
+
+/// Splits strict-encoded invoice into its fixed fields and TLV stream.
+pub fn split_records(data: &[u8]) -> (Vec<u8>, tlv::Stream) {
+    let mut cursor = io::Cursor::new(data);
+    u8::strict_decode(&mut cursor).unwrap();
+    AmountExt::strict_decode(&mut cursor).unwrap();
+    Beneficiary::strict_decode(&mut cursor).unwrap();
+    let fields_len = cursor.position() as usize;
+    let stream = tlv::Stream::strict_decode(&mut cursor).unwrap();
+    (data[..fields_len].to_vec(), stream)
+}
+
+/// Deterministic key pair used for signing test invoices.
+pub fn keypair(byte: u8) -> KeyPair {
+    let sk = secp256k1::SecretKey::from_slice(&[byte; 32]).unwrap();
+    KeyPair::from_secret_key(SECP256K1, &sk)
+}
@@ -0,0 +1,293 @@
+// LNP/BP universal invoice library implementing LNPBP-38 standard
+// Written in 2021 by
+//     Dr. Maxim Orlovsky <orlovsky@pandoracore.com>
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the MIT License
+// along with this software.
+// If not, see <https://opensource.org/licenses/MIT>.
+
+mod common;
+
+use std::str::FromStr;
+
+use amplify::Wrapper;
+use common::{invoice, keypair, split_records, with_records, RawInvoice};
+use invoice::{CurrencyData, CurrencyError, Invoice, Iso4217, Iso4217Error};
+use lnpbp::bech32::ToBech32String;
+use strict_encoding::{StrictDecode, StrictEncode};
+
+fn currency(s: &str) -> CurrencyData {
+    CurrencyData::from_str(s).unwrap()
+}
+
+/// Record in the format used for a single requirement before the decimals
+/// were made explicit
+fn legacy_record(iso4217: &str, coins: u32, fractions: u8) -> Vec<u8> {
+    let mut record = Iso4217::from_str(iso4217)
+        .unwrap()
+        .strict_serialize()
+        .unwrap();
+    record.extend(coins.strict_serialize().unwrap());
+    record.push(fractions);
+    record.extend("".strict_serialize().unwrap());
+    record
+}
+
+fn record_types(invoice: &Invoice) -> Vec<u64> {
+    let (_, stream) = split_records(&invoice.strict_serialize().unwrap());
+    (&stream)
+        .into_iter()
+        .map(|(type_no, _)| type_no.into_inner())
+        .collect()
+}
+
+#[test]
+fn single_requirement_uses_legacy_record() {
+    let mut invoice = invoice();
+    let eur = currency("10.00 EUR");
+    invoice.add_currency_requirement(eur.clone()).unwrap();
+    assert_eq!(invoice.currency_requirements(), std::slice::from_ref(&eur));
+    assert_eq!(record_types(&invoice), vec![0x08, 0x0b]);
+
+    let (_, stream) = split_records(&invoice.strict_serialize().unwrap());
+    let record = stream.get(&0x08u64.into()).unwrap();
+    assert_eq!(AsRef::<[u8]>::as_ref(record), legacy_record("EUR", 10, 0));
+}
+
+#[test]
+fn requirement_list_uses_own_record() {
+    let mut invoice = invoice();
+    let eur = currency("10.00 EUR");
+    let usd = currency("11.50 USD");
+    invoice.add_currency_requirement(eur.clone()).unwrap();
+    invoice.add_currency_requirement(usd.clone()).unwrap();
+    assert_eq!(invoice.currency_requirements(), [eur.clone(), usd.clone()]);
+    assert_eq!(record_types(&invoice), vec![0x0b, 0x0e]);
+
+    let decoded = Invoice::from_str(&invoice.to_bech32_string()).unwrap();
+    assert_eq!(decoded, invoice);
+    assert_eq!(decoded.currency_requirements(), [eur.clone(), usd]);
+
+    invoice.remove_currency_requirement(Iso4217::from_str("USD").unwrap());
+    assert_eq!(invoice.currency_requirements(), [eur]);
+    assert_eq!(record_types(&invoice), vec![0x08, 0x0b]);
+}
+
+#[test]
+fn legacy_single_requirement_decodes() {
+    let eur = currency("10.05 EUR");
+    let record = legacy_record("EUR", 10, 5);
+    let data = with_records(&invoice(), &[(0x08, &record)]);
+    let s = RawInvoice(data).to_bech32_string();
+    let decoded = Invoice::from_str(&s).unwrap();
+    assert_eq!(decoded.currency_requirements(), [eur]);
+    assert_eq!(decoded.to_bech32_string(), s);
+}
+
+#[test]
+fn ambiguous_requirements_are_rejected() {
+    let record = legacy_record("EUR", 10, 0);
+    let list = vec![currency("11.50 USD"), currency("1000 JPY")]
+        .strict_serialize()
+        .unwrap();
+    let data = with_records(&invoice(), &[(0x08, &record), (0x0e, &list)]);
+    let s = RawInvoice(data).to_bech32_string();
+    assert!(Invoice::from_str(&s).is_err());
+}
+
+#[test]
+fn duplicated_requirements_are_rejected() {
+    let list = vec![currency("11.50 USD"), currency("12.00 USD")]
+        .strict_serialize()
+        .unwrap();
+    let data = with_records(&invoice(), &[(0x0e, &list)]);
+    assert!(Invoice::strict_deserialize(data).is_err());
+
+    let mut invoice = invoice();
+    invoice
+        .add_currency_requirement(currency("11.50 USD"))
+        .unwrap();
+    invoice
+        .add_currency_requirement(currency("12.00 USD"))
+        .unwrap();
+    assert_eq!(invoice.currency_requirements(), [currency("12.00 USD")]);
+}
+
+#[test]
+fn requirement_mutators_invalidate_signature() {
+    let mut invoice = invoice();
+    invoice.sign(&keypair(1)).unwrap();
+    assert!(invoice
+        .add_currency_requirement(currency("10.00 EUR"))
+        .unwrap());
+    assert_eq!(invoice.signature(), None);
+
+    invoice.sign(&keypair(1)).unwrap();
+    assert!(!invoice
+        .add_currency_requirement(currency("10.00 EUR"))
+        .unwrap());
+    invoice.verify_signature().unwrap();
+    assert!(
+        invoice.remove_currency_requirement(Iso4217::from_str("EUR").unwrap())
+    );
+    assert_eq!(invoice.signature(), None);
+}
+
+#[test]
+fn amount_keeps_currency_decimals() {
+    let usd = currency("10.05 USD");
+    assert_eq!((usd.coins, usd.fractions, usd.decimals), (10, 5, 2));
+    assert_eq!(usd.to_string(), "10.05 USD");
+    assert_eq!(currency("10.5 USD").to_string(), "10.50 USD");
+    assert_eq!(usd.to_minor_units(), 1005);
+
+    let jpy = currency("1000 JPY");
+    assert_eq!((jpy.coins, jpy.fractions, jpy.decimals), (1000, 0, 0));
+    assert_eq!(jpy.to_string(), "1000 JPY");
+    assert!(CurrencyData::from_str("1000.5 JPY").unwrap().decimals == 1);
+
+    let bhd = currency("1.500 BHD");
+    assert_eq!((bhd.coins, bhd.fractions, bhd.decimals), (1, 500, 3));
+    assert_eq!(bhd.to_string(), "1.500 BHD");
+    assert_eq!(bhd.to_minor_units(), 1500);
+    assert_eq!(currency("1.5 BHD"), bhd);
+    assert_eq!(
+        CurrencyData::from_major_minor(
+            Iso4217::from_str("BHD").unwrap(),
+            1,
+            500
+        )
+        .unwrap(),
+        bhd
+    );
+
+    let precise = currency("0.1250 USD");
+    assert_eq!((precise.fractions, precise.decimals), (1250, 4));
+    assert_eq!(precise.to_string(), "0.1250 USD");
+    assert_eq!(
+        CurrencyData::from_str("0.12505 USD"),
+        Err(CurrencyError::DecimalsOutOfRange(5))
+    );
+    assert_eq!(
+        CurrencyData::new(Iso4217::from_str("USD").unwrap(), 1, 100, 2, None),
+        Err(CurrencyError::FractionsOutOfRange {
+            fractions: 100,
+            decimals: 2
+        })
+    );
+}
+
+#[test]
+fn amount_with_explicit_decimals_uses_list_record() {
+    for s in ["1.500 BHD", "0.125 USD", "1.0000 JPY"] {
+        let mut invoice = invoice();
+        let data = currency(s);
+        assert!(!data.is_legacy_representable(), "{}", s);
+        invoice.add_currency_requirement(data.clone()).unwrap();
+        assert_eq!(record_types(&invoice), vec![0x0b, 0x0e], "{}", s);
+
+        let decoded = Invoice::from_str(&invoice.to_bech32_string()).unwrap();
+        assert_eq!(decoded.currency_requirements(), [data]);
+    }
+
+    let mut invoice = invoice();
+    let data = currency("1.300 KWD");
+    assert!(!data.is_legacy_representable());
+    invoice
+        .add_currency_requirement(currency("1.200 KWD"))
+        .unwrap();
+    assert_eq!(record_types(&invoice), vec![0x08, 0x0b]);
+    invoice.add_currency_requirement(data).unwrap();
+    assert_eq!(record_types(&invoice), vec![0x0b, 0x0e]);
+}
+
+#[test]
+fn invalid_list_amount_is_rejected() {
+    let mut record = Iso4217::from_str("USD")
+        .unwrap()
+        .strict_serialize()
+        .unwrap();
+    record.extend(10u32.strict_serialize().unwrap());
+    record.extend(100u16.strict_serialize().unwrap());
+    record.push(2);
+    record.extend("".strict_serialize().unwrap());
+    let mut list = 1u16.strict_serialize().unwrap();
+    list.extend(record);
+    let data = with_records(&invoice(), &[(0x0e, &list)]);
+    assert!(Invoice::strict_deserialize(data).is_err());
+}
+
+#[test]
+fn iso4217_codes_are_validated() {
+    for code in ["USD", "JPY", "XBT", "AAA"] {
+        let iso4217 = Iso4217::from_str(code).unwrap();
+        assert_eq!(iso4217.as_str(), code);
+        assert_eq!(iso4217.to_string(), code);
+    }
+    for code in ["usd", "Usd", "US1", "U D", "US-"] {
+        assert_eq!(Iso4217::from_str(code), Err(Iso4217Error::InvalidChar));
+    }
+    // Non-ASCII characters, including three-byte ones like the euro sign
+    assert_eq!(Iso4217::from_str("€"), Err(Iso4217Error::InvalidChar));
+    assert_eq!(Iso4217::from_str("UÉ"), Err(Iso4217Error::InvalidChar));
+    for code in ["", "US", "USDT", "ÉUR"] {
+        assert_eq!(Iso4217::from_str(code), Err(Iso4217Error::WrongLen));
+    }
+    assert_eq!(Iso4217::new(*b"EUR").unwrap().as_str(), "EUR");
+    assert_eq!(
+        Iso4217::new([0xFF, b'U', b'R']),
+        Err(Iso4217Error::InvalidChar)
+    );
+}
+
+#[test]
+fn iso4217_table_lookup() {
+    let lookup = |code| {
+        let iso4217 = Iso4217::from_str(code).unwrap();
+        (iso4217.is_known(), iso4217.exponent(), iso4217.name())
+    };
+    assert_eq!(lookup("USD"), (true, Some(2), Some("US Dollar")));
+    assert_eq!(lookup("JPY"), (true, Some(0), Some("Yen")));
+    assert_eq!(lookup("BHD"), (true, Some(3), Some("Bahraini Dinar")));
+    assert_eq!(lookup("XAU").1, None);
+    assert!(lookup("XAU").0);
+    // Well-formed private codes are accepted, but not known
+    assert_eq!(lookup("XBT"), (false, None, None));
+    assert_eq!(lookup("AAA"), (false, None, None));
+}
+
+#[test]
+fn iso4217_strict_decoding_is_validated() {
+    assert_eq!(
+        Iso4217::strict_deserialize(b"XBT").unwrap(),
+        Iso4217::from_str("XBT").unwrap()
+    );
+    for code in [b"usd", b"U\0D", &[0xFF, 0xFF, 0xFF]] {
+        assert!(Iso4217::strict_deserialize(code).is_err());
+    }
+    let mut record = legacy_record("USD", 1, 50);
+    record[0] = b'u';
+    let data = with_records(&invoice(), &[(0x08, &record)]);
+    assert!(Invoice::strict_deserialize(&data).is_err());
+}
+
+#[test]
+fn currency_amounts_use_exponent() {
+    assert_eq!(currency("5 JPY").to_string(), "5 JPY");
+    assert_eq!(currency("1.5 BHD").to_string(), "1.500 BHD");
+    assert_eq!(currency("1.05 USD").to_string(), "1.05 USD");
+    // More fractional digits than the exponent are kept as decimals
+    assert_eq!(currency("1.05 JPY").exponent(), 2);
+    assert_eq!(currency("5 JPY").exponent(), 0);
+    // Currencies not in the table use the default exponent
+    assert_eq!(currency("5 XBT").exponent(), CurrencyData::DEFAULT_EXPONENT);
+    assert!(matches!(
+        CurrencyData::from_str("1.05 usd"),
+        Err(CurrencyError::Code(Iso4217Error::InvalidChar))
+    ));
+}
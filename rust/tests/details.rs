@@ -0,0 +1,119 @@
+// LNP/BP universal invoice library implementing LNPBP-38 standard
+// Written in 2021 by
+//     Dr. Maxim Orlovsky <orlovsky@pandoracore.com>
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the MIT License
+// along with this software.
+// If not, see <https://opensource.org/licenses/MIT>.
+
+//! Fixed vectors of the details document commitments.
+
+mod common;
+
+use std::str::FromStr;
+
+use bitcoin::hashes::hex::ToHex;
+use bitcoin::hashes::{sha256, Hash};
+use common::invoice;
+use invoice::{Details, InlineDetails, Url};
+use strict_encoding::StrictEncode;
+
+const SOURCE: &str = "https://shop.example/terms.txt";
+const TERMS: &[u8] = b"Terms of sale: no refunds.\n";
+
+fn source() -> Url {
+    Url::from_str(SOURCE).unwrap()
+}
+
+#[test]
+fn commitment_vectors() {
+    // Double SHA256 of the raw document bytes, displayed in reversed order
+    let empty = Details::commit_to(b"", source());
+    assert_eq!(
+        empty.commitment.to_hex(),
+        "56944c5d3f98413ef45cf54545538103cc9f298e0575820ad3591376e2e0f65d"
+    );
+    assert_eq!(
+        empty.commitment.into_inner().to_hex(),
+        "5df6e0e2761359d30a8275058e299fcc0381534545f55cf43e41983f5d4c9456"
+    );
+
+    let terms = Details::commit_to(TERMS, source());
+    assert_eq!(
+        terms.commitment.to_hex(),
+        "da990a1f551ddae4f0ecee220b75629d84b324a1ff64cd6dd9c67e131ab742e3"
+    );
+    assert_eq!(
+        terms.to_string(),
+        format!("{}#{}", SOURCE, terms.commitment)
+    );
+    // Commitment bytes in the hash order, followed by the length-prefixed URL
+    assert_eq!(
+        terms.strict_serialize().unwrap().to_hex(),
+        "e342b71a137ec6d96dcd64ffa124b3849d62750b22eeecf0e4da1d551f0a99da\
+         1e0068747470733a2f2f73686f702e6578616d706c652f7465726d732e747874"
+    );
+}
+
+#[test]
+fn verification_uses_raw_bytes() {
+    let details = Details::commit_to(TERMS, source());
+    assert!(details.verify(TERMS));
+    // No canonicalization of line endings or whitespace is done
+    assert!(!details.verify(b"Terms of sale: no refunds.\r\n"));
+    assert!(!details.verify(b"Terms of sale: no refunds."));
+    assert!(!details.verify(b""));
+    let mut tampered = TERMS.to_vec();
+    tampered[0] ^= 1;
+    assert!(!details.verify(&tampered));
+}
+
+#[test]
+fn details_leaf_vectors() {
+    let mut invoice = invoice();
+    invoice.set_details(Details::commit_to(TERMS, source()));
+    invoice
+        .set_inline_details(InlineDetails::new("text/plain", TERMS.to_vec()));
+    let leaves = invoice
+        .signature_leaves()
+        .iter()
+        .map(ToHex::to_hex)
+        .collect::<Vec<_>>();
+
+    // Record leaf is SHA256 of the TLV type and length-prefixed value
+    let leaf = |ty: u64, value: Vec<u8>| {
+        let mut record = ty.strict_serialize().unwrap();
+        record.extend(value.strict_serialize().unwrap());
+        sha256::Hash::hash(&record).to_hex()
+    };
+    let details = invoice.details().as_ref().unwrap();
+    assert_eq!(leaves[3], leaf(0x09, details.strict_serialize().unwrap()));
+    let inline = invoice.inline_details().as_ref().unwrap();
+    assert_eq!(leaves[5], leaf(0x0f, inline.strict_serialize().unwrap()));
+    assert_eq!(
+        leaves,
+        vec![
+            // version
+            "6e340b9cffb37a989ca544e6bb780a2c78901d3fb33738768511a30617afa01d",
+            // amount
+            "987b7f1352550612fad9f28f3491ed574f4a41189cb60237d3df5c4a709d4e37",
+            // beneficiary
+            "e61fb0a3c4ad628df526812169932278e5ace71f121ff6b1ab54c63fbaee1cc7",
+            // details (0x09)
+            "038e6d0e339fee11e1474b213b38fda006ca1a844f4241dc9b5ae589312dafda",
+            // network (0x0b)
+            "05d80c9112fc8342ddd4c4a22a99c81a940d6b5b4e09edbda0356a788628599d",
+            // inline details (0x0f)
+            "039435d4a92834d76efeb62a1a6e7430dd65bc81914ababd853d698c19091b87",
+        ]
+    );
+    assert_eq!(
+        invoice.signature_hash().to_hex(),
+        "fc63c28da31c15750c22d8060c7f7bd4de1b2bea99892e9295eedb5297b2fc30"
+    );
+}
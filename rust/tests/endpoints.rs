@@ -0,0 +1,286 @@
+// LNP/BP universal invoice library implementing LNPBP-38 standard
+// Written in 2021 by
+//     Dr. Maxim Orlovsky <orlovsky@pandoracore.com>
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the MIT License
+// along with this software.
+// If not, see <https://opensource.org/licenses/MIT>.
+
+mod common;
+
+use std::str::FromStr;
+
+use common::{invoice, with_records, RawInvoice};
+use invoice::{
+    BuilderError, ConsignmentEndpoint, ConsignmentEndpointParseError,
+    ConsignmentEndpointsError, EndpointProtocol, Invoice, InvoiceBuilder,
+    InvoiceInconsistency, MAX_CONSIGNMENT_ENDPOINTS,
+};
+use lnpbp::bech32::ToBech32String;
+use strict_encoding::{StrictDecode, StrictEncode};
+
+fn endpoint(no: usize) -> ConsignmentEndpoint {
+    ConsignmentEndpoint::from_str(&format!(
+        "rgbhttpjsonrpc:https://proxy{}.example.com/json-rpc",
+        no
+    ))
+    .unwrap()
+}
+
+fn builder() -> InvoiceBuilder {
+    InvoiceBuilder::new(invoice().beneficiary().clone())
+}
+
+#[test]
+fn builder_accepts_endpoint_limit() {
+    let builder = (0..MAX_CONSIGNMENT_ENDPOINTS)
+        .chain([0])
+        .fold(builder(), |builder, no| {
+            builder.consignment_endpoint(endpoint(no))
+        });
+    let invoice = builder.finish().unwrap();
+    assert_eq!(
+        invoice.consignment_endpoints(),
+        (0..MAX_CONSIGNMENT_ENDPOINTS)
+            .map(endpoint)
+            .collect::<Vec<_>>()
+    );
+}
+
+#[test]
+fn builder_rejects_too_many_endpoints() {
+    let builder = (0..=MAX_CONSIGNMENT_ENDPOINTS)
+        .fold(builder(), |builder, no| {
+            builder.consignment_endpoint(endpoint(no))
+        });
+    let err = ConsignmentEndpointsError::TooMany(MAX_CONSIGNMENT_ENDPOINTS + 1);
+    assert_eq!(
+        builder.clone().finish(),
+        Err(BuilderError::Inconsistency(err.into()))
+    );
+    assert_eq!(
+        builder.finish_with_warnings(),
+        Err(BuilderError::Inconsistency(
+            InvoiceInconsistency::ConsignmentEndpoints(err)
+        ))
+    );
+}
+
+#[cfg(feature = "rgb")]
+#[test]
+fn setting_too_many_endpoints_fails() {
+    let mut invoice = invoice();
+    let endpoints = (0..MAX_CONSIGNMENT_ENDPOINTS).map(endpoint);
+    assert_eq!(
+        invoice.set_consignment_endpoints(endpoints.clone()),
+        Ok(true)
+    );
+    assert_eq!(
+        invoice.set_consignment_endpoints(endpoints.clone()),
+        Ok(false)
+    );
+
+    invoice.sign(&common::keypair(1)).unwrap();
+    assert_eq!(
+        invoice.set_consignment_endpoints(
+            endpoints
+                .clone()
+                .chain([endpoint(MAX_CONSIGNMENT_ENDPOINTS)])
+        ),
+        Err(ConsignmentEndpointsError::TooMany(
+            MAX_CONSIGNMENT_ENDPOINTS + 1
+        ))
+    );
+    assert_eq!(
+        invoice.consignment_endpoints().len(),
+        MAX_CONSIGNMENT_ENDPOINTS
+    );
+    invoice.verify_signature().unwrap();
+
+    assert_eq!(
+        invoice
+            .set_consignment_endpoints(endpoints.chain([endpoint(0)]).skip(1)),
+        Ok(true)
+    );
+    assert_eq!(invoice.signature(), None);
+}
+
+const NODE: &str =
+    "02f9308a019258c31049344f85f89d5229b531c845836f99b08601f113bce036f9";
+
+#[test]
+fn websocket_and_bifrost_round_trips() {
+    let bifrost = format!("bifrost:{}@127.0.0.1:9735", NODE);
+    let bifrost_lan = format!("bifrost:{}@192.168.1.1:9999", NODE);
+    for (s, protocol, tag) in [
+        (
+            "rgbws:ws://proxy.example.com/ws",
+            EndpointProtocol::RgbWebSocket,
+            2u8,
+        ),
+        (
+            "rgbws:wss://proxy.example.com:8080/ws",
+            EndpointProtocol::RgbWebSocket,
+            2,
+        ),
+        (&bifrost, EndpointProtocol::Bifrost, 3),
+        (&bifrost_lan, EndpointProtocol::Bifrost, 3),
+    ] {
+        let endpoint = ConsignmentEndpoint::from_str(s).unwrap();
+        assert_eq!(endpoint.protocol(), protocol);
+        assert_eq!(endpoint.to_string(), s);
+
+        // New protocols wrap their data into a length-prefixed byte string
+        let data = endpoint.strict_serialize().unwrap();
+        assert_eq!(data[0], tag, "{}", s);
+        assert_eq!(
+            u16::from_le_bytes([data[1], data[2]]) as usize,
+            data.len() - 3
+        );
+        assert_eq!(
+            ConsignmentEndpoint::strict_deserialize(&data).unwrap(),
+            endpoint
+        );
+
+        let invoice = builder()
+            .consignment_endpoint(endpoint.clone())
+            .finish()
+            .unwrap();
+        let decoded = Invoice::from_str(&invoice.to_string()).unwrap();
+        assert_eq!(decoded.consignment_endpoints(), &[endpoint]);
+    }
+}
+
+#[test]
+fn unknown_endpoint_protocol() {
+    let endpoint =
+        ConsignmentEndpoint::strict_deserialize([4u8, 2, 0, 0xaa, 0xbb])
+            .unwrap();
+    assert_eq!(endpoint.protocol(), EndpointProtocol::Unknown(4));
+    assert_eq!(
+        endpoint.strict_serialize().unwrap(),
+        vec![4u8, 2, 0, 0xaa, 0xbb]
+    );
+    assert_eq!(
+        ConsignmentEndpoint::from_str(&endpoint.to_string()),
+        Ok(endpoint)
+    );
+}
+
+#[test]
+fn invalid_websocket_and_bifrost_endpoints() {
+    for s in [
+        "rgbws:https://proxy.example.com/ws",
+        "rgbws:proxy.example.com/ws",
+        "rgbhttpjsonrpc:ws://proxy.example.com/ws",
+    ] {
+        assert!(
+            matches!(
+                ConsignmentEndpoint::from_str(s),
+                Err(ConsignmentEndpointParseError::RgbWebSocket(_))
+                    | Err(ConsignmentEndpointParseError::RgbHttpJsonRpc(_))
+            ),
+            "{}",
+            s
+        );
+    }
+    assert_eq!(
+        ConsignmentEndpoint::from_str("bifrost:02aa"),
+        Err(ConsignmentEndpointParseError::Bifrost("02aa".to_owned()))
+    );
+    // Node address must include the internet address of the node
+    assert_eq!(
+        ConsignmentEndpoint::from_str(&format!("bifrost:{}", NODE)),
+        Err(ConsignmentEndpointParseError::Bifrost(NODE.to_owned()))
+    );
+    assert!(ConsignmentEndpoint::from_str("unknown:3:00").is_err());
+}
+
+fn storm(port: u16) -> ConsignmentEndpoint {
+    ConsignmentEndpoint::from_str(&format!("storm:{}@127.0.0.1:{}", NODE, port))
+        .unwrap()
+}
+
+#[test]
+fn mixed_endpoints() {
+    let ws = ConsignmentEndpoint::from_str("rgbws:wss://proxy.example.com/ws")
+        .unwrap();
+    let invoice = builder()
+        .consignment_endpoint(storm(1))
+        .consignment_endpoint(endpoint(1))
+        .consignment_endpoint(ws.clone())
+        .consignment_endpoint(storm(2))
+        .consignment_endpoint(endpoint(2))
+        .finish()
+        .unwrap();
+    let invoice = Invoice::from_str(&invoice.to_string()).unwrap();
+    assert_eq!(
+        invoice.consignment_endpoints(),
+        &[storm(1), endpoint(1), ws.clone(), storm(2), endpoint(2)]
+    );
+
+    let http = invoice
+        .consignment_endpoints_http()
+        .map(ToString::to_string)
+        .collect::<Vec<_>>();
+    assert_eq!(
+        http,
+        vec![
+            "https://proxy1.example.com/json-rpc",
+            "https://proxy2.example.com/json-rpc"
+        ]
+    );
+    let storm_nodes = invoice
+        .consignment_endpoints_storm()
+        .map(|addr| format!("storm:{}", addr))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        storm_nodes,
+        vec![storm(1).to_string(), storm(2).to_string()]
+    );
+
+    assert_eq!(
+        invoice.first_endpoint(EndpointProtocol::Storm),
+        Some(&storm(1))
+    );
+    assert_eq!(
+        invoice.first_endpoint(EndpointProtocol::RgbHttpJsonRpc),
+        Some(&endpoint(1))
+    );
+    assert_eq!(
+        invoice.first_endpoint(EndpointProtocol::RgbWebSocket),
+        Some(&ws)
+    );
+    assert_eq!(invoice.first_endpoint(EndpointProtocol::Bifrost), None);
+}
+
+#[test]
+fn dedup_decoded_endpoints() {
+    let same = ConsignmentEndpoint::from_str(
+        "rgbhttpjsonrpc:https://proxy1.example.com/json-rpc/",
+    )
+    .unwrap();
+    let endpoints = vec![endpoint(1), storm(1), same.clone(), endpoint(2)];
+    let data = with_records(
+        &invoice(),
+        &[(0x0a, &endpoints.strict_serialize().unwrap())],
+    );
+    let s = RawInvoice(data).to_bech32_string();
+
+    // Decoding keeps duplicates
+    let mut invoice = Invoice::from_str(&s).unwrap();
+    assert_eq!(invoice.consignment_endpoints(), &endpoints[..]);
+    assert_eq!(invoice.to_string(), s);
+
+    assert!(invoice.dedup_consignment_endpoints());
+    assert_eq!(
+        invoice.consignment_endpoints(),
+        &[endpoint(1), storm(1), endpoint(2)]
+    );
+    assert!(!invoice.dedup_consignment_endpoints());
+}
@@ -0,0 +1,162 @@
+// LNP/BP universal invoice library implementing LNPBP-38 standard
+// Written in 2021 by
+//     Dr. Maxim Orlovsky <orlovsky@pandoracore.com>
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the MIT License
+// along with this software.
+// If not, see <https://opensource.org/licenses/MIT>.
+
+mod common;
+
+use std::str::FromStr;
+
+use chrono::{DateTime, Duration, TimeZone, Utc};
+use common::invoice;
+use invoice::{
+    BuilderError, ExpiryError, ExpiryTerm, Invoice, InvoiceBuilder,
+    InvoiceInconsistency, InvoiceParseError, Recurrent,
+};
+
+fn time(day: u32, hour: u32, sec: u32) -> DateTime<Utc> {
+    Utc.with_ymd_and_hms(2022, 1, day, hour, 0, sec).unwrap()
+}
+
+#[test]
+fn no_expiry() {
+    let invoice = invoice();
+    assert_eq!(invoice.payable_until(), None);
+    assert_eq!(invoice.time_left(time(1, 0, 0)), None);
+    assert!(!invoice.is_expired(time(1, 0, 0)));
+    assert!(!invoice.is_expired(DateTime::<Utc>::MAX_UTC));
+}
+
+#[test]
+fn absolute_expiry() {
+    let expiry = time(1, 12, 0);
+    let mut invoice = invoice();
+    invoice.set_expiry(expiry);
+    assert_eq!(invoice.payable_until(), Some(expiry));
+
+    // Not yet expired
+    let now = time(1, 11, 0);
+    assert!(!invoice.is_expired(now));
+    assert_eq!(invoice.time_left(now), Some(Duration::hours(1)));
+
+    // The last second before expiry
+    let now = expiry - Duration::seconds(1);
+    assert!(!invoice.is_expired(now));
+    assert_eq!(invoice.time_left(now), Some(Duration::seconds(1)));
+
+    // Invoice expires exactly at the expiry time
+    assert!(invoice.is_expired(expiry));
+    assert_eq!(invoice.time_left(expiry), Some(Duration::zero()));
+
+    // Expired
+    let now = time(2, 0, 0);
+    assert!(invoice.is_expired(now));
+    assert_eq!(invoice.time_left(now), Some(Duration::zero()));
+
+    assert!(invoice.set_no_expiry());
+    assert!(!invoice.is_expired(now));
+    assert_eq!(invoice.time_left(now), None);
+}
+
+#[test]
+fn recurrent_expiry() {
+    // Expiry bounds the start of the last recurrence period, which payment
+    // is accepted until the period ends
+    let expiry = time(1, 12, 0);
+    let mut invoice = invoice();
+    invoice.set_expiry(expiry);
+    invoice.set_recurrent(Recurrent::Days(1));
+    let until = time(2, 12, 0);
+    assert_eq!(invoice.payable_until(), Some(until));
+
+    assert!(!invoice.is_expired(expiry));
+    assert_eq!(invoice.time_left(expiry), Some(Duration::days(1)));
+    assert!(!invoice.is_expired(until - Duration::seconds(1)));
+    assert!(invoice.is_expired(until));
+    assert_eq!(invoice.time_left(until), Some(Duration::zero()));
+
+    invoice.set_recurrent(Recurrent::Seconds(30));
+    assert_eq!(invoice.payable_until(), Some(time(1, 12, 30)));
+    assert!(!invoice.is_expired(time(1, 12, 29)));
+    assert!(invoice.is_expired(time(1, 12, 30)));
+}
+
+#[test]
+fn relative_expiry() {
+    let issued_at = time(1, 12, 0);
+    let mut invoice = invoice();
+    invoice.set_issued_at(issued_at);
+    invoice.set_expiry_term(ExpiryTerm::RelativeSeconds(600));
+    assert_eq!(
+        invoice.expiry_term(),
+        Some(ExpiryTerm::RelativeSeconds(600))
+    );
+
+    let expiry = time(1, 12, 0) + Duration::minutes(10);
+    assert_eq!(invoice.effective_expiry(), Ok(Some(expiry)));
+    assert_eq!(invoice.payable_until(), Some(expiry));
+    assert_eq!(invoice.time_left(issued_at), Some(Duration::minutes(10)));
+    assert!(!invoice.is_expired(expiry - Duration::seconds(1)));
+    assert!(invoice.is_expired(expiry));
+    assert_eq!(invoice.validate(), Ok(()));
+
+    let decoded = Invoice::from_str(&invoice.to_string()).unwrap();
+    assert_eq!(decoded.effective_expiry(), Ok(Some(expiry)));
+}
+
+#[test]
+fn relative_expiry_without_issuance_time() {
+    let mut invoice = invoice();
+    invoice.set_expiry_term(ExpiryTerm::RelativeSeconds(600));
+    assert_eq!(invoice.effective_expiry(), Err(ExpiryError::NoIssuanceTime));
+
+    // Such invoices can't be paid
+    assert_eq!(invoice.payable_until(), Some(DateTime::<Utc>::MIN_UTC));
+    assert!(invoice.is_expired(time(1, 0, 0)));
+    assert_eq!(invoice.time_left(time(1, 0, 0)), Some(Duration::zero()));
+
+    assert_eq!(
+        invoice.validate(),
+        Err(vec![InvoiceInconsistency::Expiry(
+            ExpiryError::NoIssuanceTime
+        )])
+    );
+    assert_eq!(
+        InvoiceBuilder::new(invoice.beneficiary().clone())
+            .expiry_term(ExpiryTerm::RelativeSeconds(600))
+            .finish(),
+        Err(BuilderError::Expiry(ExpiryError::NoIssuanceTime))
+    );
+
+    // Decoding fails instead of panicking
+    let err = Invoice::from_str(&invoice.to_string()).unwrap_err();
+    assert!(matches!(
+        err,
+        InvoiceParseError::Encoding(
+            strict_encoding::Error::DataIntegrityError(_)
+        )
+    ));
+    assert!(
+        err.to_string()
+            .contains("invoice has relative expiry but no issuance time"),
+        "{}",
+        err
+    );
+}
+
+#[test]
+fn relative_expiry_out_of_range() {
+    let mut invoice = invoice();
+    invoice.set_issued_at(DateTime::<Utc>::MAX_UTC);
+    invoice.set_expiry_term(ExpiryTerm::RelativeSeconds(u32::MAX));
+    assert_eq!(invoice.effective_expiry(), Err(ExpiryError::OutOfRange));
+    assert!(invoice.is_expired(time(1, 0, 0)));
+}
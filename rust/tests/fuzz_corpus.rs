@@ -0,0 +1,231 @@
+// LNP/BP universal invoice library implementing LNPBP-38 standard
+// Written in 2021 by
+//     Dr. Maxim Orlovsky <orlovsky@pandoracore.com>
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the MIT License
+// along with this software.
+// If not, see <https://opensource.org/licenses/MIT>.
+
+//! Replays the seed corpus of the `fuzz/` targets, together with its
+//! truncated and bit-flipped variants, through the same decoders, so panics
+//! are caught without running cargo-fuzz. The corpus is generated from the
+//! golden vectors in `tests/vectors/` with
+//! `cargo test --all-features --test fuzz_corpus -- --ignored
+//! regenerate_fuzz_corpus`.
+
+use std::fs;
+use std::path::PathBuf;
+use std::str::FromStr;
+
+use bitcoin::hashes::hex::FromHex;
+use invoice::{Beneficiary, ConsignmentEndpoint, Invoice};
+use strict_encoding::{StrictDecode, StrictEncode};
+
+const VECTORS: [&str; 7] = [
+    "address",
+    "blind_utxo",
+    "bolt",
+    "descriptor",
+    "psbt",
+    "signed",
+    "unknown_tlv",
+];
+
+const ENDPOINTS: [&str; 6] = [
+    "storm:02f9308a019258c31049344f85f89d5229b531c845836f99b08601f113bce036f9",
+    "storm:02f9308a019258c31049344f85f89d5229b531c845836f99b08601f113bce036f9@127.0.0.1:9735",
+    "rgbhttpjsonrpc:https://proxy1.example.com/json-rpc",
+    "rgbws:wss://proxy.example.com:8080/ws",
+    "bifrost:02f9308a019258c31049344f85f89d5229b531c845836f99b08601f113bce036f9",
+    "rgbhttpjsonrpc:http://[::1]:3000",
+];
+
+fn vector(name: &str, ext: &str) -> String {
+    let path = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/vectors")
+        .join(format!("{}.{}", name, ext));
+    fs::read_to_string(path).unwrap().trim().to_owned()
+}
+
+fn corpus_dir(target: &str) -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("fuzz/corpus")
+        .join(target)
+}
+
+/// Seed inputs of a fuzz target, by their file names.
+type Seeds = Vec<(String, Vec<u8>)>;
+
+/// Seed inputs of each fuzz target.
+fn seeds() -> Vec<(&'static str, Seeds)> {
+    let strict = VECTORS
+        .iter()
+        .map(|name| {
+            let data = Vec::<u8>::from_hex(&vector(name, "hex")).unwrap();
+            (name.to_string(), data)
+        })
+        .collect::<Vec<_>>();
+    let bech32 = VECTORS
+        .iter()
+        .map(|name| (name.to_string(), vector(name, "bech32").into_bytes()))
+        .collect::<Vec<_>>();
+    let beneficiaries = VECTORS
+        .iter()
+        .map(|name| {
+            let invoice = Invoice::from_str(&vector(name, "bech32")).unwrap();
+            let beneficiary = invoice.beneficiary().to_string();
+            (name.to_string(), beneficiary.into_bytes())
+        })
+        .collect::<Vec<_>>();
+    let endpoints = ENDPOINTS
+        .iter()
+        .enumerate()
+        .map(|(no, s)| (format!("endpoint{}", no), s.as_bytes().to_vec()))
+        .collect::<Vec<_>>();
+
+    #[allow(unused_mut)]
+    let mut seeds = vec![
+        ("invoice_strict_decode", strict),
+        ("invoice_from_str", bech32),
+        ("beneficiary_from_str", beneficiaries),
+        ("consignment_endpoint_from_str", endpoints),
+    ];
+    #[cfg(feature = "ur")]
+    seeds.push(("ur_decode", ur_seeds()));
+    seeds
+}
+
+/// Single-part and multi-part URs of the vectors, one part per line.
+#[cfg(feature = "ur")]
+fn ur_seeds() -> Seeds {
+    let mut seeds = vec![];
+    for name in VECTORS {
+        let invoice = Invoice::from_str(&vector(name, "bech32")).unwrap();
+        let single = invoice.to_ur_parts(1000).join("\n");
+        seeds.push((format!("{}_single", name), single.into_bytes()));
+        let parts = invoice.to_ur_parts(64);
+        if parts.len() > 1 {
+            let multi = parts.join("\n");
+            seeds.push((format!("{}_multi", name), multi.into_bytes()));
+        }
+    }
+    seeds
+}
+
+/// Runs input through the checks of the fuzz target with the given name.
+fn run(target: &str, data: &[u8]) {
+    let s = std::str::from_utf8(data);
+    match (target, s) {
+        ("invoice_strict_decode", _) => {
+            if let Ok(invoice) = Invoice::strict_deserialize(data) {
+                let encoded = invoice.strict_serialize().unwrap();
+                assert_eq!(
+                    Invoice::strict_deserialize(&encoded).ok(),
+                    Some(invoice)
+                );
+            }
+        }
+        ("invoice_from_str", Ok(s)) => {
+            if let Ok(invoice) = Invoice::from_str(s) {
+                assert_eq!(
+                    Invoice::from_str(&invoice.to_string()),
+                    Ok(invoice)
+                );
+            }
+        }
+        ("beneficiary_from_str", Ok(s)) => {
+            let _ = Beneficiary::from_str(s);
+        }
+        ("consignment_endpoint_from_str", Ok(s)) => {
+            if let Ok(endpoint) = ConsignmentEndpoint::from_str(s) {
+                assert_eq!(
+                    ConsignmentEndpoint::from_str(&endpoint.to_string()).ok(),
+                    Some(endpoint)
+                );
+            }
+        }
+        #[cfg(feature = "ur")]
+        ("ur_decode", Ok(s)) => {
+            let _ = Invoice::from_ur_parts(s.lines());
+        }
+        _ => {}
+    }
+}
+
+/// Truncations of the input and its variants with a single bit flipped in
+/// each byte (moving the bit position from byte to byte), similar to the
+/// first mutations made by the fuzzer.
+fn variants(data: &[u8]) -> impl Iterator<Item = Vec<u8>> + '_ {
+    let truncated = (0..data.len()).map(move |len| data[..len].to_vec());
+    let flipped = (0..data.len()).map(move |pos| {
+        let mut data = data.to_vec();
+        data[pos] ^= 1 << (pos % 8);
+        data
+    });
+    truncated.chain(flipped)
+}
+
+#[test]
+fn corpus_matches_seeds() {
+    for (target, seeds) in seeds() {
+        for (name, data) in seeds {
+            let path = corpus_dir(target).join(&name);
+            let corpus = fs::read(&path).unwrap_or_else(|err| {
+                panic!("corpus file {}: {}", path.display(), err)
+            });
+            assert_eq!(corpus, data, "{}/{}", target, name);
+        }
+    }
+}
+
+#[test]
+fn corpus_variants_do_not_panic() {
+    for (target, _) in seeds() {
+        for entry in fs::read_dir(corpus_dir(target)).unwrap() {
+            let data = fs::read(entry.unwrap().path()).unwrap();
+            run(target, &data);
+            for variant in variants(&data) {
+                run(target, &variant);
+            }
+        }
+    }
+}
+
+#[test]
+fn malformed_beneficiaries_are_errors() {
+    let strict = |name| Vec::<u8>::from_hex(&vector(name, "hex")).unwrap();
+
+    // Lightning node features requiring `option_data_loss_protect` (bit 0)
+    let mut bolt = strict("bolt");
+    assert_eq!(bolt[44..46], [0, 0]);
+    bolt.splice(44..46, [1, 0, 1]);
+    assert!(matches!(
+        Invoice::strict_deserialize(&bolt),
+        Err(strict_encoding::Error::DataIntegrityError(_))
+    ));
+
+    // PSBT output with a taproot tree made of the rest of the output data
+    let mut psbt = strict("psbt");
+    assert_eq!(psbt[485], 0);
+    psbt[485] = 1;
+    assert!(matches!(
+        Invoice::strict_deserialize(&psbt),
+        Err(strict_encoding::Error::DataIntegrityError(_))
+    ));
+}
+
+#[test]
+#[ignore]
+fn regenerate_fuzz_corpus() {
+    for (target, seeds) in seeds() {
+        fs::create_dir_all(corpus_dir(target)).unwrap();
+        for (name, data) in seeds {
+            fs::write(corpus_dir(target).join(name), data).unwrap();
+        }
+    }
+}
@@ -0,0 +1,188 @@
+// LNP/BP universal invoice library implementing LNPBP-38 standard
+// Written in 2021 by
+//     Dr. Maxim Orlovsky <orlovsky@pandoracore.com>
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the MIT License
+// along with this software.
+// If not, see <https://opensource.org/licenses/MIT>.
+
+//! Wire encoding of lightning beneficiaries, which must stay compatible with
+//! the decoders predating PTLC locks, multi-hop route hints and lightning
+//! flags.
+
+use std::str::FromStr;
+
+use amplify::{Slice32, Wrapper};
+use bitcoin_scripts::hlc::HashLock;
+use internet2::addr::NodeId;
+use invoice::{LnAddress, LnFlags, LnPathHint, LnRouteHint, Lock};
+use lnp::p2p::bolt::{InitFeatures, ShortChannelId};
+use lnpbp::chain::Chain;
+use strict_encoding::{StrictDecode, StrictEncode};
+
+const NODE_ID: &str =
+    "02f9308a019258c31049344f85f89d5229b531c845836f99b08601f113bce036f9";
+
+fn node_id() -> NodeId {
+    NodeId::from_str(NODE_ID).unwrap()
+}
+
+fn hop(no: u8) -> LnPathHint {
+    LnPathHint {
+        node_id: node_id(),
+        short_channel_id: ShortChannelId::from_str(&format!(
+            "{}x1x0",
+            700_000 + no as u32
+        ))
+        .unwrap(),
+        fee_base_msat: 1000,
+        fee_proportional_millionths: 100 * no as u32,
+        cltv_expiry_delta: 40,
+    }
+}
+
+fn hash_lock() -> HashLock {
+    HashLock::from(Slice32::from_inner([0x11; 32]))
+}
+
+fn ln_address() -> LnAddress {
+    LnAddress {
+        node_id: node_id(),
+        features: InitFeatures::default(),
+        lock: Lock::Hash(hash_lock()),
+        secret: Some(Slice32::from_inner([0x22; 32])),
+        network: Chain::Mainnet,
+        min_final_cltv_expiry: Some(18),
+        path_hints: vec![LnRouteHint(vec![hop(1)]), LnRouteHint(vec![hop(2)])],
+        ln_flags: Default::default(),
+    }
+}
+
+/// Encodes lightning address fields in the layout used before PTLC locks,
+/// multi-hop route hints and lightning flags: hash lock and a flat list of
+/// single-hop route hints.
+fn legacy_encoding(
+    features: &InitFeatures,
+    lock: HashLock,
+    path_hints: &[LnPathHint],
+) -> Vec<u8> {
+    let address = ln_address();
+    let mut data = vec![];
+    address.node_id.strict_encode(&mut data).unwrap();
+    features.strict_encode(&mut data).unwrap();
+    lock.strict_encode(&mut data).unwrap();
+    address.secret.strict_encode(&mut data).unwrap();
+    address.network.strict_encode(&mut data).unwrap();
+    address
+        .min_final_cltv_expiry
+        .strict_encode(&mut data)
+        .unwrap();
+    path_hints.to_vec().strict_encode(&mut data).unwrap();
+    data
+}
+
+/// Decodes data with the decoding of the node features used by the
+/// decoders preceding the wire encoding bits.
+fn legacy_features(
+    data: &[u8],
+) -> Result<InitFeatures, strict_encoding::Error> {
+    InitFeatures::strict_decode(&data[33..])
+}
+
+#[test]
+fn hash_lock_encoding_is_unchanged() {
+    let address = ln_address();
+    let legacy = legacy_encoding(
+        &InitFeatures::default(),
+        hash_lock(),
+        &[hop(1), hop(2)],
+    );
+    assert_eq!(address.strict_serialize().unwrap(), legacy);
+    assert_eq!(LnAddress::strict_deserialize(&legacy).unwrap(), address);
+    assert!(legacy_features(&legacy).is_ok());
+}
+
+#[test]
+fn point_lock_is_rejected_by_legacy_decoders() {
+    let mut address = ln_address();
+    let point = bitcoin::secp256k1::PublicKey::from_str(NODE_ID).unwrap();
+    address.lock = Lock::Point(point);
+    let data = address.strict_serialize().unwrap();
+    assert_eq!(LnAddress::PTLC_FEATURE_BIT % 2, 0);
+    assert!(legacy_features(&data).is_err());
+    assert_eq!(LnAddress::strict_deserialize(&data).unwrap(), address);
+}
+
+#[test]
+fn multi_hop_routes_are_rejected_by_legacy_decoders() {
+    let mut address = ln_address();
+    address.path_hints = vec![LnRouteHint(vec![hop(1), hop(2)])];
+    let data = address.strict_serialize().unwrap();
+    assert_eq!(LnAddress::MULTI_HOP_FEATURE_BIT % 2, 0);
+    assert!(legacy_features(&data).is_err());
+    assert_eq!(LnAddress::strict_deserialize(&data).unwrap(), address);
+}
+
+#[test]
+fn ln_flags_round_trip() {
+    let flags = [
+        LnFlags::HODL,
+        LnFlags::KEYSEND_OK,
+        LnFlags::AMP_OK,
+        LnFlags::MPP_REQUIRED,
+        LnFlags::OFFER,
+    ];
+    for flag in flags {
+        let mut address = ln_address();
+        address.ln_flags = flag;
+        let data = address.strict_serialize().unwrap();
+        assert_eq!(LnAddress::strict_deserialize(&data).unwrap(), address);
+        // Only the flags which can't be ignored are rejected by the legacy
+        // decoders
+        let required = LnFlags::REQUIRED.contains(flag);
+        assert_eq!(legacy_features(&data).is_err(), required, "{:?}", flag);
+    }
+
+    let mut address = ln_address();
+    address.ln_flags = LnFlags::all();
+    let data = address.strict_serialize().unwrap();
+    assert_eq!(LnAddress::strict_deserialize(&data).unwrap(), address);
+    assert!(legacy_features(&data).is_err());
+}
+
+#[test]
+fn optional_ln_flags_are_kept_by_legacy_decoders() {
+    let mut address = ln_address();
+    address.ln_flags = LnFlags::HODL | LnFlags::KEYSEND_OK | LnFlags::AMP_OK;
+    let data = address.strict_serialize().unwrap();
+    let features = legacy_features(&data).unwrap();
+    assert!(features.unknown.is_set(LnAddress::LN_FLAGS_FEATURE_BIT + 1));
+    assert!(features.unknown.is_set(LnAddress::LN_FLAGS_FEATURE_BIT + 3));
+    assert!(features.unknown.is_set(LnAddress::LN_FLAGS_FEATURE_BIT + 5));
+    assert_eq!(features.unknown.count_flags(), 3);
+}
+
+#[test]
+fn payment_secret_presence() {
+    let address = ln_address();
+    let with_secret = address.strict_serialize().unwrap();
+    assert_eq!(
+        LnAddress::strict_deserialize(&with_secret).unwrap(),
+        address
+    );
+
+    let mut address = ln_address();
+    address.secret = None;
+    let without_secret = address.strict_serialize().unwrap();
+    assert_eq!(without_secret.len(), with_secret.len() - 32);
+    assert_eq!(
+        LnAddress::strict_deserialize(&without_secret).unwrap(),
+        address
+    );
+    assert!(legacy_features(&without_secret).is_ok());
+}
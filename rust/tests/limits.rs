@@ -0,0 +1,249 @@
+// LNP/BP universal invoice library implementing LNPBP-38 standard
+// Written in 2021 by
+//     Dr. Maxim Orlovsky <orlovsky@pandoracore.com>
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the MIT License
+// along with this software.
+// If not, see <https://opensource.org/licenses/MIT>.
+
+mod common;
+
+use std::str::FromStr;
+
+use amplify::{Slice32, Wrapper};
+use bitcoin::util::address::Address;
+use bitcoin::PublicKey;
+use bitcoin_scripts::hlc::HashLock;
+use common::{invoice, keypair, with_records, RawInvoice};
+use internet2::addr::NodeId;
+use invoice::{
+    Beneficiary, ConsignmentEndpoint, DecodeLimit, DecodePolicy, Details,
+    Invoice, InvoiceParseError, LnAddress, LnPathHint, LnRouteHint, Lock, Url,
+    MAX_CONSIGNMENT_ENDPOINTS,
+};
+use lnp::p2p::bolt::{InitFeatures, ShortChannelId};
+use lnpbp::bech32::ToBech32String;
+use lnpbp::chain::Chain;
+use strict_encoding::StrictEncode;
+
+fn alt(no: u8) -> Beneficiary {
+    let key = PublicKey::new(keypair(no + 1).public_key());
+    Beneficiary::Address(
+        Address::p2wpkh(&key, bitcoin::Network::Bitcoin).unwrap(),
+    )
+}
+
+fn hop(no: usize) -> LnPathHint {
+    LnPathHint {
+        node_id: NodeId::from(keypair(1).public_key()),
+        short_channel_id: ShortChannelId::from_str(&format!(
+            "{}x1x0",
+            700_000 + no
+        ))
+        .unwrap(),
+        fee_base_msat: 1000,
+        fee_proportional_millionths: 100,
+        cltv_expiry_delta: 40,
+    }
+}
+
+fn bolt(hops: usize) -> Beneficiary {
+    Beneficiary::Bolt(LnAddress {
+        node_id: NodeId::from(keypair(1).public_key()),
+        features: InitFeatures::default(),
+        lock: Lock::Hash(HashLock::from(Slice32::from_inner([0x11; 32]))),
+        secret: None,
+        network: Chain::Mainnet,
+        min_final_cltv_expiry: None,
+        path_hints: (0..hops).map(|no| LnRouteHint(vec![hop(no)])).collect(),
+        ln_flags: Default::default(),
+    })
+}
+
+fn endpoint(no: usize) -> ConsignmentEndpoint {
+    ConsignmentEndpoint::from_str(&format!(
+        "rgbhttpjsonrpc:https://proxy{}.example.com/json-rpc",
+        no
+    ))
+    .unwrap()
+}
+
+/// Checks that the invoice is rejected on parsing with the default policy
+/// and accepted with the limit raised by `raise`.
+fn assert_over_limit(
+    invoice: &Invoice,
+    limit: DecodeLimit,
+    raise: impl FnOnce(&mut DecodePolicy),
+) {
+    let policy = DecodePolicy::default();
+    assert_eq!(invoice.validate_limits(&policy), Err(limit));
+    let s = invoice.to_string();
+    match Invoice::from_str(&s) {
+        Err(InvoiceParseError::Encoding(err)) => {
+            assert!(err.to_string().contains(limit.field), "{}", err)
+        }
+        res => panic!("over-limit invoice is not rejected: {:?}", res),
+    }
+    let mut policy = DecodePolicy::default();
+    raise(&mut policy);
+    assert_eq!(invoice.validate_limits(&policy), Ok(()));
+    assert_eq!(&Invoice::parse_with_policy(&s, &policy).unwrap(), invoice);
+}
+
+#[test]
+fn alt_beneficiaries_limit() {
+    let max = DecodePolicy::default().max_alt_beneficiaries;
+    let mut invoice = invoice();
+    for no in 0..max as u8 {
+        assert!(invoice.add_alt_beneficiary(alt(no)));
+    }
+    assert_eq!(Invoice::from_str(&invoice.to_string()).unwrap(), invoice);
+
+    invoice.add_alt_beneficiary(alt(max as u8));
+    assert_over_limit(
+        &invoice,
+        DecodeLimit {
+            field: "alternative beneficiaries",
+            count: max + 1,
+            limit: max,
+        },
+        |policy| policy.max_alt_beneficiaries = max + 1,
+    );
+}
+
+#[test]
+fn path_hints_limit() {
+    let max = DecodePolicy::default().max_path_hints;
+    let invoice = Invoice::new(bolt(max), Some(1000), None);
+    assert_eq!(Invoice::from_str(&invoice.to_string()).unwrap(), invoice);
+
+    let limit = DecodeLimit {
+        field: "lightning path hints",
+        count: max + 1,
+        limit: max,
+    };
+    let invoice = Invoice::new(bolt(max + 1), Some(1000), None);
+    assert_over_limit(&invoice, limit, |policy| {
+        policy.max_path_hints = max + 1
+    });
+
+    // Alternative lightning beneficiaries are checked as well
+    let mut invoice = common::invoice();
+    invoice.add_alt_beneficiary(bolt(max + 1));
+    assert_over_limit(&invoice, limit, |policy| {
+        policy.max_path_hints = max + 1
+    });
+}
+
+#[test]
+fn string_limits() {
+    let max = DecodePolicy::default().max_string_len;
+    let limit = |field| DecodeLimit {
+        field,
+        count: max + 1,
+        limit: max,
+    };
+    let raise = |policy: &mut DecodePolicy| policy.max_string_len = max + 1;
+
+    let mut invoice = common::invoice();
+    invoice.set_merchant("m".repeat(max));
+    invoice.set_purpose("p".repeat(max));
+    assert_eq!(Invoice::from_str(&invoice.to_string()).unwrap(), invoice);
+
+    let mut merchant = invoice.clone();
+    merchant.set_merchant("m".repeat(max + 1));
+    assert_over_limit(&merchant, limit("merchant"), raise);
+
+    // Multi-byte characters are counted in bytes
+    let mut purpose = invoice.clone();
+    purpose.set_purpose(format!("{}é", "p".repeat(max - 1)));
+    assert_over_limit(&purpose, limit("purpose"), raise);
+
+    let prefix = "https://example.com/";
+    let source = Url::from_str(&format!(
+        "{}{}",
+        prefix,
+        "d".repeat(max + 1 - prefix.len())
+    ))
+    .unwrap();
+    let mut details = common::invoice();
+    details.set_details(Details::commit_to(b"terms", source));
+    assert_over_limit(&details, limit("details source"), raise);
+}
+
+#[test]
+fn consignment_endpoints_limit() {
+    let endpoints = |count| {
+        let endpoints = (0..count).map(endpoint).collect::<Vec<_>>();
+        let data = with_records(
+            &invoice(),
+            &[(0x0a, &endpoints.strict_serialize().unwrap())],
+        );
+        RawInvoice(data).to_bech32_string()
+    };
+
+    let s = endpoints(3);
+    let policy = DecodePolicy {
+        max_consignment_endpoints: 2,
+        ..DecodePolicy::default()
+    };
+    assert!(Invoice::parse_with_policy(&s, &policy).is_err());
+    let invoice = Invoice::from_str(&s).unwrap();
+    assert_eq!(
+        invoice.validate_limits(&policy),
+        Err(DecodeLimit {
+            field: "consignment endpoints",
+            count: 3,
+            limit: 2,
+        })
+    );
+
+    let s = endpoints(MAX_CONSIGNMENT_ENDPOINTS);
+    assert!(Invoice::from_str(&s).is_ok());
+
+    // Endpoints over the hard limit are rejected under any policy
+    let s = endpoints(MAX_CONSIGNMENT_ENDPOINTS + 1);
+    assert!(Invoice::from_str(&s).is_err());
+    let policy = DecodePolicy {
+        max_consignment_endpoints: MAX_CONSIGNMENT_ENDPOINTS * 2,
+        ..DecodePolicy::default()
+    };
+    assert!(Invoice::parse_with_policy(&s, &policy).is_err());
+}
+
+#[test]
+fn payload_limit() {
+    let max = DecodePolicy::default().max_payload_len;
+
+    // Small string decompressing into a megabyte of data
+    let bomb = RawInvoice(vec![0u8; 0x100000]).to_bech32_string();
+    assert!(bomb.len() < 0x1000);
+    match Invoice::from_str(&bomb) {
+        Err(InvoiceParseError::Encoding(err)) => {
+            assert!(err.to_string().contains("payload"), "{}", err)
+        }
+        res => panic!("DEFLATE bomb is not rejected: {:?}", res),
+    }
+
+    let invoice = invoice();
+    let s = invoice.to_string();
+    let len = invoice.strict_serialize().unwrap().len();
+    assert!(len < max);
+    let mut policy = DecodePolicy {
+        max_payload_len: len - 1,
+        ..DecodePolicy::default()
+    };
+    match Invoice::parse_with_policy(&s, &policy) {
+        Err(InvoiceParseError::Encoding(err)) => {
+            assert!(err.to_string().contains("payload"), "{}", err)
+        }
+        res => panic!("over-limit payload is not rejected: {:?}", res),
+    }
+    policy.max_payload_len = len;
+    assert_eq!(Invoice::parse_with_policy(&s, &policy).unwrap(), invoice);
+}
@@ -0,0 +1,251 @@
+// LNP/BP universal invoice library implementing LNPBP-38 standard
+// Written in 2021 by
+//     Dr. Maxim Orlovsky <orlovsky@pandoracore.com>
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the MIT License
+// along with this software.
+// If not, see <https://opensource.org/licenses/MIT>.
+
+#![cfg(feature = "elements")]
+
+mod common;
+
+use std::str::FromStr;
+
+use bitcoin::hashes::Hash;
+use bitcoin::util::address::{Payload, WitnessVersion};
+use bitcoin::{PubkeyHash, ScriptHash};
+use common::keypair;
+use invoice::{
+    AssetClass, Beneficiary, BeneficiaryKind, Invoice, InvoiceInconsistency,
+    LiquidAddress, LiquidAddressError, LiquidNetwork, Network,
+    PayerCapabilities,
+};
+use lnpbp::chain::{AssetId, Chain};
+use strict_encoding::{StrictDecode, StrictEncode};
+
+/// Confidential P2WPKH address of Liquid mainnet, encoded by Elements
+const LIQUID_CONFIDENTIAL: &str = "lq1qqf8er278e6nyvuwtgf39e6ewvdcnjupn9a86rzpx655y5lhkt0walu3djf9cklkxd3ryld97hu8h3xepw7sh2rlu7q45dcew5";
+
+fn confidential_testnet() -> LiquidAddress {
+    LiquidAddress {
+        network: LiquidNetwork::LiquidTestnet,
+        payload: Payload::WitnessProgram {
+            version: WitnessVersion::V0,
+            program: vec![0x5a; 20],
+        },
+        blinding_pubkey: Some(keypair(1).public_key()),
+    }
+}
+
+fn liquid_asset() -> AssetId {
+    AssetId::from_inner([1; 32])
+}
+
+fn liquid_invoice(address: LiquidAddress, network: Network) -> Invoice {
+    Invoice::new_on_network(
+        Beneficiary::LiquidAddress(address),
+        Some(1000),
+        None,
+        network,
+    )
+}
+
+#[test]
+fn external_confidential_address() {
+    let address = LiquidAddress::from_str(LIQUID_CONFIDENTIAL).unwrap();
+    assert_eq!(address.network, LiquidNetwork::Liquid);
+    assert!(address.is_blinded());
+    assert!(matches!(
+        address.payload,
+        Payload::WitnessProgram {
+            version: WitnessVersion::V0,
+            ref program,
+        } if program.len() == 20
+    ));
+    assert_eq!(address.to_string(), LIQUID_CONFIDENTIAL);
+    assert_eq!(
+        LiquidAddress::from_str(&LIQUID_CONFIDENTIAL.to_uppercase()),
+        Ok(address)
+    );
+}
+
+#[test]
+fn confidential_testnet_round_trip() {
+    let address = confidential_testnet();
+    let s = address.to_string();
+    assert!(s.starts_with("tlq1qq"));
+    assert_eq!(LiquidAddress::from_str(&s), Ok(address.clone()));
+
+    let beneficiary = Beneficiary::from_str(&s).unwrap();
+    assert_eq!(beneficiary, Beneficiary::LiquidAddress(address.clone()));
+    assert_eq!(beneficiary.to_string(), s);
+    assert_eq!(
+        BeneficiaryKind::from(&beneficiary),
+        BeneficiaryKind::LiquidAddress
+    );
+
+    let invoice = liquid_invoice(address, Network::LiquidTestnet);
+    assert_eq!(invoice.validate(), Ok(()));
+    let data = invoice.strict_serialize().unwrap();
+    assert_eq!(Invoice::strict_deserialize(&data).unwrap(), invoice);
+    assert_eq!(Invoice::from_str(&invoice.to_string()), Ok(invoice));
+}
+
+#[test]
+#[cfg(feature = "serde")]
+fn confidential_testnet_serde_round_trip() {
+    let beneficiary = Beneficiary::LiquidAddress(confidential_testnet());
+    let json = serde_json::to_string(&beneficiary).unwrap();
+    assert_eq!(json, format!("\"{}\"", beneficiary));
+    assert_eq!(
+        serde_json::from_str::<Beneficiary>(&json).unwrap(),
+        beneficiary
+    );
+}
+
+#[test]
+fn explicit_and_base58_round_trips() {
+    let confidential = confidential_testnet();
+    let explicit = confidential.to_unconfidential();
+    let s = explicit.to_string();
+    assert!(s.starts_with("tex1q"));
+    assert_eq!(LiquidAddress::from_str(&s), Ok(explicit.clone()));
+    assert_eq!(explicit.script_pubkey(), confidential.script_pubkey());
+
+    let taproot = LiquidAddress {
+        network: LiquidNetwork::Liquid,
+        payload: Payload::WitnessProgram {
+            version: WitnessVersion::V1,
+            program: vec![0x21; 32],
+        },
+        blinding_pubkey: Some(keypair(2).public_key()),
+    };
+    assert!(taproot.to_string().starts_with("lq1p"));
+    assert_eq!(LiquidAddress::from_str(&taproot.to_string()), Ok(taproot));
+
+    for network in [
+        LiquidNetwork::Liquid,
+        LiquidNetwork::LiquidTestnet,
+        LiquidNetwork::ElementsRegtest,
+    ] {
+        for payload in [
+            Payload::PubkeyHash(PubkeyHash::from_inner([3; 20])),
+            Payload::ScriptHash(ScriptHash::from_inner([4; 20])),
+        ] {
+            for blinding_pubkey in [None, Some(keypair(3).public_key())] {
+                let address = LiquidAddress {
+                    network,
+                    payload: payload.clone(),
+                    blinding_pubkey,
+                };
+                assert_eq!(
+                    LiquidAddress::from_str(&address.to_string()),
+                    Ok(address.clone())
+                );
+                let data = address.strict_serialize().unwrap();
+                assert_eq!(
+                    LiquidAddress::strict_deserialize(&data).unwrap(),
+                    address
+                );
+            }
+        }
+    }
+}
+
+#[test]
+fn malformed_addresses() {
+    let mut s = confidential_testnet().to_string();
+    let last = if s.ends_with('q') { 'p' } else { 'q' };
+    s.pop();
+    s.push(last);
+    assert_eq!(
+        LiquidAddress::from_str(&s),
+        Err(LiquidAddressError::InvalidChecksum)
+    );
+    assert!(Beneficiary::from_str(&s)
+        .unwrap_err()
+        .attempts()
+        .contains(&"Liquid address"));
+
+    let mut mixed = LIQUID_CONFIDENTIAL.to_owned();
+    mixed.replace_range(..2, "LQ");
+    assert_eq!(
+        LiquidAddress::from_str(&mixed),
+        Err(LiquidAddressError::InvalidBlech32)
+    );
+
+    // Explicit address data with bech32 checksum under blech32 HRP
+    let explicit = confidential_testnet().to_unconfidential().to_string();
+    let blech = explicit.replacen("tex1", "tlq1", 1);
+    assert!(LiquidAddress::from_str(&blech).is_err());
+}
+
+#[test]
+fn liquid_asset_classification() {
+    let mut address = confidential_testnet();
+    address.network = LiquidNetwork::Liquid;
+    let mut invoice = liquid_invoice(address, Network::LiquidV1);
+    invoice.set_asset(liquid_asset());
+    assert_eq!(
+        invoice.classify_asset(Some(Chain::LiquidV1)),
+        AssetClass::Liquid(liquid_asset())
+    );
+    assert_eq!(
+        invoice.classify_asset_auto(),
+        AssetClass::Liquid(liquid_asset())
+    );
+    #[cfg(feature = "rgb")]
+    assert_eq!(invoice.rgb_asset(), None);
+
+    invoice.set_asset(Chain::LiquidV1.native_asset());
+    assert_eq!(invoice.classify_asset_auto(), AssetClass::Native);
+    invoice.set_network(Network::Mainnet);
+    assert_eq!(
+        invoice.classify_asset_auto(),
+        AssetClass::InvalidNativeChain
+    );
+}
+
+#[test]
+fn liquid_address_validation() {
+    let address = confidential_testnet();
+    let mut invoice = liquid_invoice(address.clone(), Network::LiquidV1);
+    assert_eq!(
+        invoice.validate(),
+        Err(vec![InvoiceInconsistency::LiquidAddressNetwork(
+            address.clone()
+        )])
+    );
+
+    invoice.set_network(Network::LiquidTestnet);
+    let explicit = address.to_unconfidential();
+    assert!(invoice
+        .add_alt_beneficiary(Beneficiary::LiquidAddress(explicit.clone())));
+    assert_eq!(
+        invoice.validate(),
+        Err(vec![InvoiceInconsistency::MixedLiquidConfidentiality])
+    );
+
+    let mut invoice = liquid_invoice(address, Network::LiquidTestnet);
+    let mut refund = explicit;
+    refund.network = LiquidNetwork::Liquid;
+    invoice.set_refund(Beneficiary::LiquidAddress(refund.clone()));
+    assert_eq!(
+        invoice.validate(),
+        Err(vec![InvoiceInconsistency::LiquidAddressNetwork(refund)])
+    );
+}
+
+#[test]
+fn payer_capabilities() {
+    let beneficiary = Beneficiary::LiquidAddress(confidential_testnet());
+    assert!(PayerCapabilities::LIQUID_ADDRESS.supports(&beneficiary));
+    assert!(PayerCapabilities::all().supports(&beneficiary));
+    assert!(!PayerCapabilities::ON_CHAIN_ADDRESS.supports(&beneficiary));
+}
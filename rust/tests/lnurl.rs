@@ -0,0 +1,233 @@
+// LNP/BP universal invoice library implementing LNPBP-38 standard
+// Written in 2021 by
+//     Dr. Maxim Orlovsky <orlovsky@pandoracore.com>
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the MIT License
+// along with this software.
+// If not, see <https://opensource.org/licenses/MIT>.
+
+#![cfg(feature = "lnurl")]
+
+mod common;
+
+use std::time::Duration;
+
+use bitcoin::hashes::{sha256, Hash};
+use bitcoin::secp256k1::SECP256K1;
+use common::{invoice, keypair};
+use invoice::lnurl::{LnurlError, PayRequest};
+use invoice::AmountExt;
+use lightning::ln::PaymentSecret;
+use lightning_invoice::{Currency, InvoiceBuilder};
+
+/// LNURL-pay response of a service accepting 1 sat to 100 000 sats
+const PAY_REQUEST: &str = r#"{
+    "callback": "https://shop.example/lnurlp/alice/callback",
+    "maxSendable": 100000000,
+    "minSendable": 1000,
+    "metadata": "[[\"text/plain\",\"Coffee beans\"],[\"text/identifier\",\"alice@shop.example\"]]",
+    "tag": "payRequest",
+    "commentAllowed": 140
+}"#;
+
+/// LNURL-pay response with a single accepted amount of 2500.5 sats and no
+/// identifier metadata
+const FIXED_PAY_REQUEST: &str = r#"{
+    "callback": "https://shop.example/lnurlp/bob/callback",
+    "maxSendable": 2500500,
+    "minSendable": 2500500,
+    "metadata": "[[\"text/plain\",\"Tea\"]]",
+    "tag": "payRequest"
+}"#;
+
+fn pay_request() -> PayRequest {
+    PayRequest::from_json(PAY_REQUEST).unwrap()
+}
+
+/// Builds callback BOLT-11 invoice committing to the description hash
+fn bolt11(
+    description_hash: sha256::Hash,
+    msat: Option<u64>,
+) -> lightning_invoice::Invoice {
+    let builder = InvoiceBuilder::new(Currency::Bitcoin)
+        .description_hash(description_hash)
+        .payment_hash(sha256::Hash::from_inner([0x11; 32]))
+        .payment_secret(PaymentSecret([0x22; 32]))
+        .duration_since_epoch(Duration::from_secs(1_600_000_000))
+        .min_final_cltv_expiry(40);
+    let builder = match msat {
+        Some(msat) => builder.amount_milli_satoshis(msat),
+        None => builder,
+    };
+    builder
+        .build_signed(|msg| {
+            SECP256K1.sign_ecdsa_recoverable(msg, &keypair(1).secret_key())
+        })
+        .unwrap()
+}
+
+fn metadata_hash(request: &PayRequest) -> sha256::Hash {
+    sha256::Hash::hash(request.metadata.as_bytes())
+}
+
+#[test]
+fn parse_pay_request() {
+    let request = pay_request();
+    assert_eq!(
+        request.callback,
+        "https://shop.example/lnurlp/alice/callback"
+    );
+    assert_eq!(request.min_sendable, 1000);
+    assert_eq!(request.max_sendable, 100_000_000);
+    assert_eq!(request.comment_allowed, Some(140));
+    assert_eq!(
+        request.metadata_entries().unwrap(),
+        vec![
+            ("text/plain".to_owned(), "Coffee beans".to_owned()),
+            (
+                "text/identifier".to_owned(),
+                "alice@shop.example".to_owned()
+            )
+        ]
+    );
+    assert_eq!(
+        request.metadata_entry("text/identifier").as_deref(),
+        Some("alice@shop.example")
+    );
+    assert_eq!(request.metadata_entry("image/png;base64"), None);
+
+    let request = PayRequest::from_json(FIXED_PAY_REQUEST).unwrap();
+    assert_eq!(request.comment_allowed, None);
+    assert_eq!(request.metadata_entry("text/plain").as_deref(), Some("Tea"));
+}
+
+#[test]
+fn invalid_pay_request() {
+    let json = PAY_REQUEST.replace("payRequest", "withdrawRequest");
+    assert_eq!(
+        PayRequest::from_json(&json),
+        Err(LnurlError::WrongTag("withdrawRequest".to_owned()))
+    );
+    assert!(matches!(
+        PayRequest::from_json(r#"{"tag": "payRequest"}"#),
+        Err(LnurlError::Json(_))
+    ));
+    assert!(matches!(
+        PayRequest::from_json("[]"),
+        Err(LnurlError::Json(_))
+    ));
+
+    let mut request = pay_request();
+    request.metadata = r#"[["text/identifier","alice@shop.example"]]"#.into();
+    assert_eq!(request.metadata_entries(), Err(LnurlError::InvalidMetadata));
+    request.metadata = r#"{"text/plain":"Coffee beans"}"#.into();
+    assert_eq!(request.metadata_entries(), Err(LnurlError::InvalidMetadata));
+    assert_eq!(request.metadata_entry("text/plain"), None);
+}
+
+#[test]
+fn amounts() {
+    let request = pay_request();
+    assert_eq!(request.amount(), AmountExt::Any);
+    assert!(!request.accepts(999));
+    assert!(request.accepts(1000));
+    assert!(request.accepts(100_000_000));
+    assert!(!request.accepts(100_000_001));
+
+    let request = PayRequest::from_json(FIXED_PAY_REQUEST).unwrap();
+    assert_eq!(request.amount(), AmountExt::Milli(2500, 500));
+
+    let mut request = request;
+    request.min_sendable = 2_500_000;
+    request.max_sendable = 2_500_000;
+    assert_eq!(request.amount(), AmountExt::Normal(2500));
+}
+
+#[test]
+fn callback_invoice() {
+    let request = pay_request();
+    let hash = metadata_hash(&request);
+
+    let invoice = request.to_invoice(bolt11(hash, Some(2_500_000))).unwrap();
+    assert_eq!(invoice.amount(), &AmountExt::Normal(2500));
+    assert_eq!(invoice.purpose().as_deref(), Some("Coffee beans"));
+    assert_eq!(invoice.merchant().as_deref(), Some("alice@shop.example"));
+    assert!(invoice.bolt_beneficiary().is_some());
+
+    // Amount of the invoice without amount is taken from the pay request
+    let invoice = request.to_invoice(bolt11(hash, None)).unwrap();
+    assert_eq!(invoice.amount(), &AmountExt::Any);
+    let request = PayRequest::from_json(FIXED_PAY_REQUEST).unwrap();
+    let invoice = request
+        .to_invoice(bolt11(metadata_hash(&request), None))
+        .unwrap();
+    assert_eq!(invoice.amount(), &AmountExt::Milli(2500, 500));
+    assert_eq!(invoice.purpose().as_deref(), Some("Tea"));
+    assert_eq!(invoice.merchant(), &None);
+}
+
+#[test]
+fn invalid_callback_invoice() {
+    let request = pay_request();
+    let hash = metadata_hash(&request);
+
+    assert_eq!(
+        request.to_invoice(bolt11(hash, Some(200_000_000))),
+        Err(LnurlError::AmountOutOfRange {
+            amount: 200_000_000,
+            min: 1000,
+            max: 100_000_000
+        })
+    );
+    assert_eq!(
+        request.to_invoice(bolt11(hash, Some(999))),
+        Err(LnurlError::AmountOutOfRange {
+            amount: 999,
+            min: 1000,
+            max: 100_000_000
+        })
+    );
+
+    let other = sha256::Hash::hash(b"[[\"text/plain\",\"Tea\"]]");
+    assert_eq!(
+        request.to_invoice(bolt11(other, Some(2_500_000))),
+        Err(LnurlError::DescriptionHash)
+    );
+
+    let bolt11 = InvoiceBuilder::new(Currency::Bitcoin)
+        .description("Coffee beans".to_owned())
+        .payment_hash(sha256::Hash::from_inner([0x11; 32]))
+        .payment_secret(PaymentSecret([0x22; 32]))
+        .duration_since_epoch(Duration::from_secs(1_600_000_000))
+        .min_final_cltv_expiry(40)
+        .build_signed(|msg| {
+            SECP256K1.sign_ecdsa_recoverable(msg, &keypair(1).secret_key())
+        })
+        .unwrap();
+    assert_eq!(request.to_invoice(bolt11), Err(LnurlError::DescriptionHash));
+}
+
+#[test]
+fn lnurlpay_metadata() {
+    let mut invoice = invoice();
+    assert_eq!(invoice.to_lnurlpay_metadata(), r#"[["text/plain",""]]"#);
+
+    invoice.set_purpose("Coffee beans".to_owned());
+    invoice.set_merchant("alice@shop.example".to_owned());
+    let metadata = invoice.to_lnurlpay_metadata();
+    assert_eq!(metadata, pay_request().metadata);
+
+    // Metadata produced from the invoice are accepted by pay requests
+    let mut request = PayRequest::from_json(FIXED_PAY_REQUEST).unwrap();
+    request.metadata = metadata;
+    let converted = request
+        .to_invoice(bolt11(metadata_hash(&request), None))
+        .unwrap();
+    assert_eq!(converted.purpose(), invoice.purpose());
+    assert_eq!(converted.merchant(), invoice.merchant());
+}
@@ -0,0 +1,148 @@
+// LNP/BP universal invoice library implementing LNPBP-38 standard
+// Written in 2021 by
+//     Dr. Maxim Orlovsky <orlovsky@pandoracore.com>
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the MIT License
+// along with this software.
+// If not, see <https://opensource.org/licenses/MIT>.
+
+#![cfg(feature = "musig2")]
+
+mod common;
+
+use std::str::FromStr;
+
+use bitcoin::secp256k1::rand::thread_rng;
+use common::{invoice, keypair};
+use invoice::musig2::generate_nonce;
+use invoice::{Invoice, MerchantKeyAggregation, Musig2Error};
+use lnpbp::bech32::ToBech32String;
+
+#[test]
+fn two_party_signing() {
+    let alice = keypair(1);
+    let bob = keypair(2);
+    let aggregation =
+        MerchantKeyAggregation::new([alice.public_key(), bob.public_key()])
+            .unwrap();
+    let mut invoice = invoice();
+
+    let (alice_secnonce, alice_nonce) =
+        generate_nonce(&alice, &aggregation, &invoice, &mut thread_rng())
+            .unwrap();
+    let (bob_secnonce, bob_nonce) =
+        generate_nonce(&bob, &aggregation, &invoice, &mut thread_rng())
+            .unwrap();
+    let nonces = [alice_nonce, bob_nonce];
+
+    // Each party computes the session on its own
+    let alice_session =
+        invoice.sign_musig2_begin(&aggregation, &nonces).unwrap();
+    let bob_session = invoice.sign_musig2_begin(&aggregation, &nonces).unwrap();
+    assert_eq!(alice_session, bob_session);
+
+    let alice_partial =
+        alice_session.partial_sign(alice_secnonce, &alice).unwrap();
+    let bob_partial = bob_session.partial_sign(bob_secnonce, &bob).unwrap();
+    alice_session
+        .verify_partial(&bob_partial, &bob_nonce)
+        .unwrap();
+    bob_session
+        .verify_partial(&alice_partial, &alice_nonce)
+        .unwrap();
+    assert_eq!(
+        alice_session.verify_partial(&bob_partial, &alice_nonce),
+        Err(Musig2Error::InvalidSignature)
+    );
+
+    invoice
+        .sign_musig2_complete(&alice_session, &[alice_partial, bob_partial])
+        .unwrap();
+    let signature = invoice.signature().unwrap();
+    assert_eq!(signature.pubkey, aggregation.aggregated_key());
+    invoice.verify_signature().unwrap();
+    invoice
+        .verify_signature_with_key(aggregation.aggregated_key())
+        .unwrap();
+
+    let decoded = Invoice::from_str(&invoice.to_bech32_string()).unwrap();
+    decoded.verify_signature().unwrap();
+}
+
+#[test]
+fn key_order_matters() {
+    let (alice, bob) = (keypair(1).public_key(), keypair(2).public_key());
+    let ab = MerchantKeyAggregation::new([alice, bob]).unwrap();
+    let ba = MerchantKeyAggregation::new([bob, alice]).unwrap();
+    assert_eq!(ab.participants(), [alice, bob]);
+    assert_eq!(ba.participants(), [bob, alice]);
+    assert_ne!(ab.aggregated_key(), ba.aggregated_key());
+    assert_eq!(
+        MerchantKeyAggregation::new([alice, bob, alice]),
+        Err(Musig2Error::DuplicateParticipant(alice))
+    );
+}
+
+#[test]
+fn session_is_bound_to_invoice() {
+    let alice = keypair(1);
+    let bob = keypair(2);
+    let aggregation =
+        MerchantKeyAggregation::new([alice.public_key(), bob.public_key()])
+            .unwrap();
+    let mut invoice = invoice();
+    let (alice_secnonce, alice_nonce) =
+        generate_nonce(&alice, &aggregation, &invoice, &mut thread_rng())
+            .unwrap();
+    let (bob_secnonce, bob_nonce) =
+        generate_nonce(&bob, &aggregation, &invoice, &mut thread_rng())
+            .unwrap();
+    let nonces = [alice_nonce, bob_nonce];
+    let session = invoice.sign_musig2_begin(&aggregation, &nonces).unwrap();
+    assert_eq!(
+        invoice.sign_musig2_begin(&aggregation, &nonces[..1]),
+        Err(Musig2Error::ParticipantCount {
+            expected: 2,
+            found: 1
+        })
+    );
+
+    let carol = keypair(3);
+    assert_eq!(
+        generate_nonce(&carol, &aggregation, &invoice, &mut thread_rng())
+            .unwrap_err(),
+        Musig2Error::UnknownParticipant(carol.public_key())
+    );
+    let carol_aggregation =
+        MerchantKeyAggregation::new([alice.public_key(), carol.public_key()])
+            .unwrap();
+    let (carol_secnonce, _) =
+        generate_nonce(&carol, &carol_aggregation, &invoice, &mut thread_rng())
+            .unwrap();
+    assert_eq!(
+        session.partial_sign(carol_secnonce, &keypair(3)),
+        Err(Musig2Error::UnknownParticipant(keypair(3).public_key()))
+    );
+
+    let partials = [
+        session.partial_sign(alice_secnonce, &alice).unwrap(),
+        session.partial_sign(bob_secnonce, &bob).unwrap(),
+    ];
+    assert_eq!(
+        invoice.sign_musig2_complete(&session, &partials[..1]),
+        Err(Musig2Error::ParticipantCount {
+            expected: 2,
+            found: 1
+        })
+    );
+    invoice.set_purpose("tea".to_owned());
+    assert_eq!(
+        invoice.sign_musig2_complete(&session, &partials),
+        Err(Musig2Error::SessionMismatch)
+    );
+}
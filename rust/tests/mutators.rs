@@ -0,0 +1,314 @@
+// LNP/BP universal invoice library implementing LNPBP-38 standard
+// Written in 2021 by
+//     Dr. Maxim Orlovsky <orlovsky@pandoracore.com>
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the MIT License
+// along with this software.
+// If not, see <https://opensource.org/licenses/MIT>.
+
+//! Every invoice mutator must invalidate the invoice signatures.
+
+mod common;
+
+use std::str::FromStr;
+
+use amplify::{Slice32, Wrapper};
+use bitcoin::hashes::Hash;
+use bitcoin::{Address, PrivateKey};
+use bitcoin_scripts::hlc::HashLock;
+use chrono::{TimeZone, Utc};
+use common::keypair;
+use internet2::addr::NodeId;
+use invoice::{
+    AmountExt, AssetMetadata, Beneficiary, CurrencyData, Details, ExpiryTerm,
+    InlineDetails, Invoice, Iso4217, LnAddress, LnFlags, Lock, MerchantInfo,
+    Network, OnchainPolicy, Quantity, Recurrent, SignerRole, SplitShare, Url,
+};
+use lnpbp::bech32::Blob;
+use lnpbp::chain::{AssetId, Chain};
+
+// Key of the BIP-322 test vectors and its P2WPKH address
+const WIF: &str = "L3VFeEujGtevx9w18HD1fhRbCH67Az2dpCymeRE1SoPK6XQtaN2k";
+const P2WPKH: &str = "bc1q9vza2e8x573nczrlzms0wvx3gsqjx7vavgkx0l";
+
+fn address(s: &str) -> Beneficiary {
+    Beneficiary::Address(Address::from_str(s).unwrap())
+}
+
+fn alt_address() -> Beneficiary {
+    address("bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t4")
+}
+
+fn details(source: &str) -> Details {
+    Details::commit_to(source.as_bytes(), Url::from_str(source).unwrap())
+}
+
+/// Invoice having all of the fields set, so each of them can be changed or
+/// removed.
+fn full_invoice() -> Invoice {
+    let mut invoice =
+        Invoice::with_address(Address::from_str(P2WPKH).unwrap(), Some(1000));
+    invoice.add_alt_beneficiary(alt_address());
+    invoice
+        .set_payment_split(vec![SplitShare::BasisPoints(5000)])
+        .unwrap();
+    invoice.set_asset(AssetId::from_inner([1; 32]));
+    invoice.set_asset_metadata(AssetMetadata::new("USDT", None, 6).unwrap());
+    invoice.set_recurrent(Recurrent::Months(1));
+    invoice.set_recurrence_limit(12);
+    invoice.set_issued_at(Utc.timestamp_opt(1_700_000_000, 0).unwrap());
+    invoice.set_expiry_term(ExpiryTerm::RelativeSeconds(3600));
+    invoice.set_quantity(Quantity::default()).unwrap();
+    invoice
+        .add_currency_requirement(CurrencyData::from_str("10.00 EUR").unwrap())
+        .unwrap();
+    invoice.set_merchant("Coffee shop".to_owned());
+    invoice.set_merchant_info(MerchantInfo::new("Coffee shop"));
+    invoice.set_purpose("coffee".to_owned());
+    invoice
+        .set_reference(Blob::from_inner(vec![1, 2, 3]))
+        .unwrap();
+    invoice.set_details(details("https://example.com/order"));
+    invoice.set_inline_details(InlineDetails::new("text/plain", b"1".to_vec()));
+    invoice.set_refund(alt_address());
+    invoice.set_onchain_policy(OnchainPolicy {
+        min_feerate_sat_vb: Some(2),
+        rbf_required: None,
+        min_confirmations: Some(1),
+    });
+    invoice.set_extension(0x41u64.into(), vec![1]).unwrap();
+    invoice
+}
+
+type Mutator = fn(&mut Invoice) -> bool;
+
+// The deprecated single-currency setter must still invalidate signatures
+#[allow(deprecated)]
+fn set_currency_requirement(invoice: &mut Invoice) -> bool {
+    invoice
+        .set_currency_requirement(CurrencyData::from_str("12.00 GBP").unwrap())
+}
+
+fn mutators() -> Vec<(&'static str, Mutator)> {
+    vec![
+        ("set_beneficiary", |i| {
+            i.set_beneficiary(address(
+                "bc1qrp33g0q5c5txsp9arysrx4k6zdkfs4nce4xj0gdcccefvpysxf3qccfmv3",
+            ))
+        }),
+        ("add_alt_beneficiary", |i| {
+            i.add_alt_beneficiary(address(
+                "bc1p0xlxvlhemja6c4dqv22uapctqupfhlxm9h8z3k2e72q4k9hcz7vqzk5jj0",
+            ))
+        }),
+        ("remove_alt_beneficiary", |i| {
+            i.remove_alt_beneficiary(&alt_address())
+        }),
+        ("set_asset", |i| i.set_asset(AssetId::from_inner([2; 32]))),
+        ("remove_asset", Invoice::remove_asset),
+        ("set_asset_metadata", |i| {
+            i.set_asset_metadata(AssetMetadata::new("USDC", None, 6).unwrap())
+        }),
+        ("remove_asset_metadata", Invoice::remove_asset_metadata),
+        ("set_amount", |i| {
+            i.set_amount(AmountExt::Normal(2000)).unwrap()
+        }),
+        ("set_recurrent", |i| i.set_recurrent(Recurrent::Years(1))),
+        ("set_recurrence_limit", |i| i.set_recurrence_limit(24)),
+        ("remove_recurrence_limit", Invoice::remove_recurrence_limit),
+        ("set_expiry", |i| {
+            i.set_expiry(Utc.timestamp_opt(1_800_000_000, 0).unwrap())
+        }),
+        ("set_expiry_term", |i| {
+            i.set_expiry_term(ExpiryTerm::RelativeSeconds(7200))
+        }),
+        ("set_issued_at", |i| {
+            i.set_issued_at(Utc.timestamp_opt(1_700_000_001, 0).unwrap())
+        }),
+        ("set_no_expiry", Invoice::set_no_expiry),
+        ("set_payment_split", |i| {
+            i.set_payment_split(vec![SplitShare::Amount(100)]).unwrap()
+        }),
+        ("remove_payment_split", Invoice::remove_payment_split),
+        ("set_quantity", |i| {
+            i.set_quantity(Quantity {
+                min: 1,
+                max: Some(10),
+                default: 1,
+            })
+            .unwrap()
+        }),
+        ("remove_quantity", Invoice::remove_quantity),
+        ("add_currency_requirement", |i| {
+            i.add_currency_requirement(
+                CurrencyData::from_str("11.00 USD").unwrap(),
+            )
+            .unwrap()
+        }),
+        ("set_currency_requirement", set_currency_requirement),
+        ("remove_currency_requirement", |i| {
+            i.remove_currency_requirement(Iso4217::from_str("EUR").unwrap())
+        }),
+        ("set_merchant", |i| i.set_merchant("Tea shop".to_owned())),
+        ("remove_merchant", Invoice::remove_merchant),
+        ("set_merchant_info", |i| {
+            i.set_merchant_info(MerchantInfo::new("Tea shop"))
+        }),
+        ("remove_merchant_info", Invoice::remove_merchant_info),
+        ("set_purpose", |i| i.set_purpose("tea".to_owned())),
+        ("remove_purpose", Invoice::remove_purpose),
+        ("set_reference", |i| {
+            i.set_reference(Blob::from_inner(vec![4])).unwrap()
+        }),
+        ("remove_reference", Invoice::remove_reference),
+        ("set_details", |i| {
+            i.set_details(details("https://example.com/other"))
+        }),
+        ("remove_details", Invoice::remove_details),
+        ("set_inline_details", |i| {
+            i.set_inline_details(InlineDetails::new(
+                "text/plain",
+                b"2".to_vec(),
+            ))
+        }),
+        ("remove_inline_details", Invoice::remove_inline_details),
+        ("set_network", |i| i.set_network(Network::Testnet3)),
+        ("remove_network", Invoice::remove_network),
+        ("set_refund", |i| {
+            i.set_refund(address("bc1p0xlxvlhemja6c4dqv22uapctqupfhlxm9h8z3k2e72q4k9hcz7vqzk5jj0"))
+        }),
+        ("remove_refund", Invoice::remove_refund),
+        ("set_onchain_policy", |i| {
+            i.set_onchain_policy(OnchainPolicy::default())
+        }),
+        ("remove_onchain_policy", Invoice::remove_onchain_policy),
+        ("set_extension", |i| {
+            i.set_extension(0x43u64.into(), vec![1]).is_ok()
+        }),
+        ("remove_address_proof", Invoice::remove_address_proof),
+        ("remove_extension", |i| i.remove_extension(0x41u64.into())),
+    ]
+}
+
+/// Adds beneficiary address proof (if the beneficiary is the test vector
+/// address), signs the invoice and adds a co-signature.
+fn sign(invoice: &mut Invoice) {
+    if invoice.beneficiary() == &address(P2WPKH) {
+        invoice
+            .sign_address_proof(&PrivateKey::from_wif(WIF).unwrap().inner)
+            .unwrap();
+        invoice.verify_address_proof().unwrap();
+    }
+    invoice.sign(&keypair(1)).unwrap();
+    invoice.cosign(&keypair(2), SignerRole::Platform).unwrap();
+    assert!(invoice
+        .verify_signatures()
+        .into_iter()
+        .all(|(_, _, res)| res.is_ok()));
+}
+
+fn assert_invalidates(name: &str, mut invoice: Invoice, mutator: Mutator) {
+    sign(&mut invoice);
+    let sig = invoice.signature().unwrap();
+    let hash = invoice.signature_hash();
+
+    assert!(mutator(&mut invoice), "{} does not change invoice", name);
+    assert_eq!(invoice.signature(), None, "{}", name);
+    assert!(invoice.cosignatures().is_empty(), "{}", name);
+    assert_eq!(invoice.beneficiary_proof(), &None, "{}", name);
+    assert_ne!(invoice.signature_hash(), hash, "{}", name);
+
+    // The signature can't be re-attached to the modified invoice
+    invoice.set_signature(sig.pubkey, sig.signature);
+    assert!(invoice.verify_signature().is_err(), "{}", name);
+}
+
+#[test]
+fn mutators_invalidate_signatures() {
+    for (name, mutator) in mutators() {
+        assert_invalidates(name, full_invoice(), mutator);
+    }
+}
+
+#[test]
+fn ln_mutators_invalidate_signatures() {
+    let mut invoice = full_invoice();
+    invoice.set_beneficiary(Beneficiary::Bolt(LnAddress {
+        node_id: NodeId::from(keypair(3).public_key()),
+        features: Default::default(),
+        lock: Lock::Hash(HashLock::from(Slice32::from_inner([0x11; 32]))),
+        secret: Some(Slice32::from_inner([0x22; 32])),
+        network: Chain::Mainnet,
+        min_final_cltv_expiry: Some(18),
+        path_hints: vec![],
+        ln_flags: LnFlags::HODL,
+    }));
+
+    let mutators: Vec<(&str, Mutator)> = vec![("set_ln_flags", |i| {
+        i.set_ln_flags(LnFlags::HODL | LnFlags::KEYSEND_OK)
+    })];
+    for (name, mutator) in mutators {
+        assert_invalidates(name, invoice.clone(), mutator);
+    }
+    assert!(!invoice.set_ln_flags(LnFlags::HODL));
+}
+
+#[cfg(feature = "rgb")]
+#[test]
+fn rgb_mutators_invalidate_signatures() {
+    use amplify::Slice32;
+    use invoice::{ConsignmentEndpoint, Iface};
+
+    fn endpoint(no: usize) -> ConsignmentEndpoint {
+        ConsignmentEndpoint::from_str(&format!(
+            "rgbhttpjsonrpc:https://proxy{}.example.com/json-rpc",
+            no
+        ))
+        .unwrap()
+    }
+
+    let mut invoice = full_invoice();
+    invoice.set_iface(Iface::new(Slice32::from_inner([1; 32]), "RGB20"));
+    invoice.add_consignment_endpoint(endpoint(1));
+
+    let mutators: Vec<(&str, Mutator)> = vec![
+        ("set_iface", |i| {
+            i.set_iface(Iface::new(Slice32::from_inner([2; 32]), "RGB21"))
+        }),
+        ("remove_iface", Invoice::remove_iface),
+        ("add_consignment_endpoint", |i| {
+            i.add_consignment_endpoint(endpoint(2))
+        }),
+        ("remove_consignment_endpoint", |i| {
+            i.remove_consignment_endpoint(&endpoint(1))
+        }),
+        ("set_consignment_endpoints", |i| {
+            i.set_consignment_endpoints([endpoint(3)]).unwrap()
+        }),
+    ];
+    for (name, mutator) in mutators {
+        assert_invalidates(name, invoice.clone(), mutator);
+    }
+}
+
+#[test]
+fn unchanged_data_keep_signatures() {
+    for (name, mutator) in mutators() {
+        // Extensions are replaced and address proof is re-created by `sign`,
+        // so these mutators always change the invoice
+        if name == "set_extension" || name == "remove_address_proof" {
+            continue;
+        }
+        let mut invoice = full_invoice();
+        mutator(&mut invoice);
+        sign(&mut invoice);
+        let signed = invoice.clone();
+        assert!(!mutator(&mut invoice), "{} changes invoice twice", name);
+        assert_eq!(invoice, signed, "{}", name);
+    }
+}
@@ -0,0 +1,238 @@
+// LNP/BP universal invoice library implementing LNPBP-38 standard
+// Written in 2021 by
+//     Dr. Maxim Orlovsky <orlovsky@pandoracore.com>
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the MIT License
+// along with this software.
+// If not, see <https://opensource.org/licenses/MIT>.
+
+mod common;
+
+use std::convert::TryFrom;
+use std::str::FromStr;
+
+use bitcoin::hashes::hex::ToHex;
+use bitcoin::hashes::Hash;
+use bitcoin::Address;
+use bitcoin::BlockHash;
+use common::{invoice, keypair, with_records, RawInvoice};
+use invoice::{
+    Beneficiary, Invoice, InvoiceInconsistency, Network, NetworkParams,
+};
+use lnpbp::bech32::ToBech32String;
+use lnpbp::chain::Chain;
+use strict_encoding::{StrictDecode, StrictEncode};
+
+fn params(data: &[u8]) -> NetworkParams {
+    NetworkParams::try_from(data).unwrap()
+}
+
+#[test]
+fn unknown_network_keeps_params() {
+    let network = Network::strict_deserialize([9u8, 0xaa, 0xbb]).unwrap();
+    assert_eq!(network, Network::Unknown(9, params(&[0xaa, 0xbb])));
+    assert_eq!(network.strict_serialize().unwrap(), vec![9u8, 0xaa, 0xbb]);
+
+    let network = Network::strict_deserialize([0xffu8]).unwrap();
+    assert_eq!(network, Network::Unknown(0xff, NetworkParams::default()));
+    assert_eq!(network.strict_serialize().unwrap(), vec![0xffu8]);
+}
+
+#[test]
+fn unknown_network_params_limit() {
+    let mut data = vec![9u8];
+    data.extend([0xab; NetworkParams::MAX_LEN]);
+    let network = Network::strict_deserialize(&data).unwrap();
+    assert_eq!(network, Network::Unknown(9, params(&[0xab; 32])));
+    assert_eq!(network.strict_serialize().unwrap(), data);
+
+    data.push(0xab);
+    assert!(Network::strict_deserialize(&data).is_err());
+    assert!(NetworkParams::try_from(&data[1..]).is_err());
+    assert!(
+        Network::from_str(&format!("unknown:9:{}", "ab".repeat(33))).is_err()
+    );
+}
+
+#[test]
+fn unknown_network_params_order() {
+    assert!(params(&[0x01]) < params(&[0x01, 0x00]));
+    assert!(params(&[0x01, 0xff]) < params(&[0x02]));
+    assert!(NetworkParams::default() < params(&[0x00]));
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn unknown_network_serde() {
+    let network = Network::Unknown(9, params(&[0xaa, 0xbb]));
+    let json = serde_json::to_string(&network).unwrap();
+    assert_eq!(json, r#"{"unknown":[9,"aabb"]}"#);
+    assert_eq!(serde_json::from_str::<Network>(&json).unwrap(), network);
+}
+
+#[test]
+fn unknown_network_rejects_known_tags() {
+    for tag in 0..=Network::MAX_KNOWN_TAG {
+        assert!(Network::Unknown(tag, NetworkParams::default())
+            .strict_serialize()
+            .is_err());
+        assert!(Network::from_str(&format!("unknown:{}", tag)).is_err());
+    }
+    assert_eq!(
+        Network::strict_deserialize([0u8]).unwrap(),
+        Network::Mainnet
+    );
+}
+
+#[test]
+fn network_display_round_trip() {
+    for network in [
+        Network::Mainnet,
+        Network::Testnet3,
+        Network::Signet,
+        Network::Regtest,
+        Network::LiquidV1,
+        Network::Testnet4,
+        Network::LiquidTestnet,
+        Network::Unknown(9, NetworkParams::default()),
+        Network::Unknown(0x42, params(&[0xaa, 0xbb])),
+    ] {
+        let s = network.to_string();
+        assert_eq!(Network::from_str(&s).unwrap(), network, "{}", s);
+        let data = network.strict_serialize().unwrap();
+        assert_eq!(Network::strict_deserialize(&data).unwrap(), network);
+    }
+    assert_eq!(
+        Network::Unknown(9, params(&[0xaa, 0xbb])).to_string(),
+        "unknown:9:aabb"
+    );
+}
+
+#[test]
+fn network_encoding_pins() {
+    let hash = BlockHash::from_inner([0xab; 32]);
+    for (network, hex) in [
+        (Network::Mainnet, "00".to_owned()),
+        (Network::Testnet3, "01".to_owned()),
+        (Network::Signet, "02".to_owned()),
+        (Network::Regtest, "03".to_owned()),
+        (Network::LiquidV1, "04".to_owned()),
+        (Network::Testnet4, "05".to_owned()),
+        (
+            Network::SignetCustom(hash),
+            format!("06{}", "ab".repeat(32)),
+        ),
+        (Network::LiquidTestnet, "07".to_owned()),
+        (
+            Network::RegtestCustom(hash),
+            format!("08{}", "ab".repeat(32)),
+        ),
+    ] {
+        let data = network.strict_serialize().unwrap();
+        assert_eq!(data.to_hex(), hex, "{}", network);
+        assert_eq!(Network::strict_deserialize(&data).unwrap(), network);
+        let s = network.to_string();
+        assert_eq!(Network::from_str(&s).unwrap(), network, "{}", s);
+    }
+}
+
+#[test]
+fn network_chain_conversion() {
+    let hash = BlockHash::from_inner([0xab; 32]);
+    for (network, chain) in [
+        (Network::Mainnet, Chain::Mainnet),
+        (Network::Testnet3, Chain::Testnet3),
+        (Network::Signet, Chain::Signet),
+        (Network::Regtest, Chain::from(bitcoin::Network::Regtest)),
+        (Network::LiquidV1, Chain::LiquidV1),
+        (Network::SignetCustom(hash), Chain::SignetCustom(hash)),
+        (Network::RegtestCustom(hash), Chain::Regtest(hash)),
+    ] {
+        assert_eq!(Chain::try_from(network), Ok(chain), "{}", network);
+    }
+    for network in [
+        Network::Testnet4,
+        Network::LiquidTestnet,
+        Network::Unknown(9, NetworkParams::default()),
+    ] {
+        assert!(Chain::try_from(network).is_err());
+    }
+}
+
+#[test]
+fn invoice_with_unknown_network() {
+    let mut invoice = invoice();
+    invoice.remove_network();
+    invoice.sign(&keypair(1)).unwrap();
+    let data = with_records(&invoice, &[(0x0b, &[9, 0xaa, 0xbb])]);
+
+    let decoded = Invoice::strict_deserialize(&data).unwrap();
+    assert_eq!(
+        decoded.network(),
+        &Some(Network::Unknown(9, params(&[0xaa, 0xbb])))
+    );
+    assert_eq!(decoded.strict_serialize().unwrap(), data);
+    assert_eq!(
+        decoded.validate().unwrap_err(),
+        vec![InvoiceInconsistency::UnknownNetwork(9)]
+    );
+
+    let s = RawInvoice(data).to_bech32_string();
+    assert_eq!(Invoice::from_str(&s).unwrap(), decoded);
+}
+
+#[test]
+fn unknown_network_keeps_signature_valid() {
+    let mut invoice = invoice();
+    invoice.set_network(Network::Unknown(9, params(&[0xaa, 0xbb])));
+    invoice.sign(&keypair(1)).unwrap();
+    let decoded = Invoice::from_str(&invoice.to_bech32_string()).unwrap();
+    assert_eq!(decoded, invoice);
+    decoded.verify_signature().unwrap();
+}
+
+#[test]
+fn with_address_sets_network() {
+    for (address, network, asset) in [
+        (
+            "bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t4",
+            Network::Mainnet,
+            None,
+        ),
+        (
+            "tb1qw508d6qejxtdg4y5r3zarvary0c5xw7kxpjzsx",
+            Network::Testnet3,
+            Some(Chain::Testnet3.native_asset()),
+        ),
+        (
+            "bcrt1qw508d6qejxtdg4y5r3zarvary0c5xw7kygt080",
+            Network::Regtest,
+            Some(Chain::from(bitcoin::Network::Regtest).native_asset()),
+        ),
+    ] {
+        let address = Address::from_str(address).unwrap();
+        let invoice = Invoice::with_address(address.clone(), Some(1000));
+        assert_eq!(invoice.network(), &Some(network), "{}", address);
+        assert_eq!(invoice.asset(), &asset, "{}", address);
+        assert_eq!(invoice.beneficiary(), &Beneficiary::Address(address));
+
+        let decoded = Invoice::from_str(&invoice.to_string()).unwrap();
+        assert_eq!(decoded.network(), &Some(network));
+    }
+
+    let beneficiary = invoice().beneficiary().clone();
+    assert_eq!(
+        Invoice::new(beneficiary.clone(), None, None).network(),
+        &None
+    );
+    assert_eq!(
+        Invoice::new_on_network(beneficiary, None, None, Network::Signet)
+            .network(),
+        &Some(Network::Signet)
+    );
+}
@@ -0,0 +1,639 @@
+// LNP/BP universal invoice library implementing LNPBP-38 standard
+// Written in 2021 by
+//     Dr. Maxim Orlovsky <orlovsky@pandoracore.com>
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the MIT License
+// along with this software.
+// If not, see <https://opensource.org/licenses/MIT>.
+
+use std::str::FromStr;
+
+use bitcoin::hashes::Hash;
+use bitcoin::{
+    Address, PackedLockTime, Script, Sequence, Transaction, TxIn, TxOut,
+};
+use bp::seals::txout::blind::ConcealedSeal;
+use invoice::{
+    apply_psbt_template, validate_psbt_beneficiary, AmountExt, Beneficiary,
+    BeneficiaryKind, DeriveError, Invoice, InvoiceInconsistency, Network,
+    OnchainPolicy, OnchainPolicyError, PaymentMatch, PolicyViolation,
+    PsbtTemplateError, ScriptError, TrackingBeneficiary,
+};
+use lnpbp::chain::{AssetId, Chain};
+use miniscript::{descriptor::DescriptorPublicKey, Descriptor};
+use strict_encoding::{StrictDecode, StrictEncode};
+use wallet::psbt::{ProprietaryKey, Psbt, PsbtVersion};
+
+// Account key and first receive addresses of the BIP-84 test vectors
+const XPUB: &str = "xpub6CatWdiZiodmUeTDp8LT5or8nmbKNcuyvz7WyksVFkKB4RHwCD3XyuvPEbvqAQY3rAPshWcMLoP2fMFMKHPJ4ZeZXYVUhLv1VMrjPC7PW6V";
+const RECEIVE_0: &str = "bc1qcr8te4kr609gcawutmrza0j4xv80jy8z306fyu";
+const RECEIVE_1: &str = "bc1qnjg0jd8228aq7egyzacy8cys3knf9xvrerkf9g";
+const RECEIVE_2: &str = "bc1qp59yckz4ae5c4efgw2s5wfyvrz0ala7rgvuz8z";
+const CHANGE_0: &str = "bc1q8c6fshw2dlwun7ekn9qwf37cu2rn755upcp6el";
+const OTHER: &str = "bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t4";
+
+fn address(s: &str) -> Address {
+    Address::from_str(s).unwrap()
+}
+
+fn seal() -> ConcealedSeal {
+    ConcealedSeal::strict_deserialize([7u8; 32]).unwrap()
+}
+
+fn descriptor(s: &str) -> Descriptor<DescriptorPublicKey> {
+    Descriptor::from_str(&s.replace("XPUB", XPUB)).unwrap()
+}
+
+/// Builds transaction with the given outputs and a single input which does
+/// not signal replaceability
+fn tx(outputs: &[(&str, u64)]) -> Transaction {
+    Transaction {
+        version: 2,
+        lock_time: PackedLockTime::ZERO,
+        input: vec![TxIn::default()],
+        output: outputs
+            .iter()
+            .map(|(addr, value)| TxOut {
+                value: *value,
+                script_pubkey: address(addr).script_pubkey(),
+            })
+            .collect(),
+    }
+}
+
+fn paid(output_indexes: Vec<u32>, amount: u64) -> PaymentMatch {
+    PaymentMatch::Paid {
+        output_indexes,
+        amount,
+    }
+}
+
+#[test]
+fn address_payment() {
+    let invoice = Invoice::with_address(address(RECEIVE_0), Some(1000));
+    let exact = tx(&[(OTHER, 5000), (RECEIVE_0, 1000)]);
+    assert_eq!(invoice.matches_tx(&exact, None), paid(vec![1], 1000));
+
+    let overpaid = tx(&[(RECEIVE_0, 1500)]);
+    assert_eq!(invoice.matches_tx(&overpaid, None), paid(vec![0], 1500));
+}
+
+#[test]
+fn partial_payments() {
+    let invoice = Invoice::with_address(address(RECEIVE_0), Some(1000));
+
+    let partial = tx(&[(RECEIVE_0, 400), (OTHER, 5000), (RECEIVE_0, 500)]);
+    assert_eq!(
+        invoice.matches_tx(&partial, None),
+        PaymentMatch::Underpaid {
+            received: 900,
+            expected: 1000
+        }
+    );
+
+    let full = tx(&[
+        (RECEIVE_0, 400),
+        (OTHER, 5000),
+        (RECEIVE_0, 500),
+        (RECEIVE_0, 100),
+    ]);
+    assert_eq!(invoice.matches_tx(&full, None), paid(vec![0, 2, 3], 1000));
+}
+
+#[test]
+fn alt_beneficiary_payments() {
+    let mut invoice = Invoice::with_address(address(RECEIVE_0), Some(1000));
+    invoice.add_alt_beneficiary(Beneficiary::Address(address(RECEIVE_1)));
+
+    let tx = tx(&[(RECEIVE_1, 600), (RECEIVE_0, 400)]);
+    assert_eq!(invoice.matches_tx(&tx, None), paid(vec![0, 1], 1000));
+}
+
+#[test]
+fn no_match() {
+    let invoice = Invoice::with_address(address(RECEIVE_0), Some(1000));
+    let tx = tx(&[(OTHER, 1000), (RECEIVE_1, 1000)]);
+    assert_eq!(invoice.matches_tx(&tx, None), PaymentMatch::NoMatch);
+}
+
+#[test]
+fn any_amount_payment() {
+    let invoice = Invoice::with_address(address(RECEIVE_0), None);
+    assert_eq!(invoice.amount(), &AmountExt::Any);
+
+    assert_eq!(
+        invoice.matches_tx(&tx(&[(RECEIVE_0, 1)]), None),
+        paid(vec![0], 1)
+    );
+    assert_eq!(
+        invoice.matches_tx(&tx(&[(RECEIVE_0, 0)]), None),
+        PaymentMatch::Underpaid {
+            received: 0,
+            expected: 1
+        }
+    );
+}
+
+#[test]
+fn milli_amount_rounds_up() {
+    let mut invoice = Invoice::with_address(address(RECEIVE_0), None);
+    invoice.set_amount(AmountExt::Milli(1000, 1)).unwrap();
+
+    assert_eq!(
+        invoice.matches_tx(&tx(&[(RECEIVE_0, 1000)]), None),
+        PaymentMatch::Underpaid {
+            received: 1000,
+            expected: 1001
+        }
+    );
+    assert_eq!(
+        invoice.matches_tx(&tx(&[(RECEIVE_0, 1001)]), None),
+        paid(vec![0], 1001)
+    );
+}
+
+#[test]
+fn non_native_asset_never_matches() {
+    let mut invoice = Invoice::with_address(address(RECEIVE_0), Some(1000));
+    let tx = tx(&[(RECEIVE_0, 1000)]);
+
+    invoice.set_asset(Chain::Mainnet.native_asset());
+    assert_eq!(invoice.matches_tx(&tx, None), paid(vec![0], 1000));
+
+    invoice.set_asset(AssetId::from_inner([1; 32]));
+    assert_eq!(invoice.matches_tx(&tx, None), PaymentMatch::NoMatch);
+}
+
+#[test]
+fn descriptor_payment() {
+    let invoice = Invoice::with_descriptor(
+        descriptor("wpkh(XPUB/0/*)"),
+        Some(1000),
+        &Chain::Mainnet,
+    );
+    let tx = tx(&[(RECEIVE_1, 1000)]);
+
+    assert_eq!(invoice.matches_tx(&tx, Some(0..2)), paid(vec![0], 1000));
+    // Ranged descriptors are not derived without the range
+    assert_eq!(invoice.matches_tx(&tx, None), PaymentMatch::NoMatch);
+    assert_eq!(invoice.matches_tx(&tx, Some(2..10)), PaymentMatch::NoMatch);
+}
+
+fn scripts(addresses: &[&str]) -> Vec<Script> {
+    addresses
+        .iter()
+        .map(|addr| address(addr).script_pubkey())
+        .collect()
+}
+
+#[test]
+fn address_script_pubkeys() {
+    let invoice = Invoice::with_address(address(RECEIVE_0), Some(1000));
+    assert_eq!(invoice.script_pubkeys(None), Ok(scripts(&[RECEIVE_0])));
+    // Range is ignored for addresses
+    assert_eq!(
+        invoice.script_pubkeys(Some(0..5)),
+        Ok(scripts(&[RECEIVE_0]))
+    );
+}
+
+#[test]
+fn descriptor_script_pubkeys() {
+    let invoice = Invoice::with_descriptor(
+        descriptor("wpkh(XPUB/0/*)"),
+        Some(1000),
+        &Chain::Mainnet,
+    );
+    assert_eq!(
+        invoice.script_pubkeys(Some(0..3)),
+        Ok(scripts(&[RECEIVE_0, RECEIVE_1, RECEIVE_2]))
+    );
+    assert_eq!(
+        invoice.script_pubkeys(Some(1..2)),
+        Ok(scripts(&[RECEIVE_1]))
+    );
+    assert_eq!(invoice.script_pubkeys(Some(3..3)), Ok(vec![]));
+    assert_eq!(
+        invoice.script_pubkeys(None),
+        Err(ScriptError::RangeRequired)
+    );
+    assert_eq!(
+        invoice.script_pubkeys(Some(0x7fff_fff0..0x8000_0001)),
+        Err(ScriptError::HardenedIndex(0x8000_0000))
+    );
+
+    let invoice = Invoice::with_descriptor(
+        descriptor("wpkh(XPUB/0h/*)"),
+        Some(1000),
+        &Chain::Mainnet,
+    );
+    assert_eq!(
+        invoice.script_pubkeys(Some(0..1)),
+        Err(ScriptError::HardenedDerivation)
+    );
+}
+
+#[test]
+fn alt_beneficiary_script_pubkeys() {
+    let mut invoice = Invoice::with_address(address(RECEIVE_0), Some(1000));
+    invoice.add_alt_beneficiary(Beneficiary::Descriptor(descriptor(
+        "wpkh(XPUB/1/*)",
+    )));
+    assert_eq!(
+        invoice.script_pubkeys(Some(0..1)),
+        Ok(scripts(&[RECEIVE_0, CHANGE_0]))
+    );
+
+    // Beneficiaries without on-chain outputs are skipped
+    let mut invoice =
+        Invoice::new(Beneficiary::BlindUtxo(seal()), Some(1000), None);
+    invoice.add_alt_beneficiary(Beneficiary::Address(address(RECEIVE_1)));
+    assert_eq!(invoice.script_pubkeys(None), Ok(scripts(&[RECEIVE_1])));
+}
+
+#[test]
+fn no_script_pubkeys() {
+    let invoice =
+        Invoice::new(Beneficiary::BlindUtxo(seal()), Some(1000), None);
+    assert_eq!(
+        invoice.script_pubkeys(None),
+        Err(ScriptError::NoScripts(BeneficiaryKind::BlindUtxo))
+    );
+}
+
+// Testnet account key of the BIP-84 test vectors and its first receive
+// address
+const TPUB: &str = "tpubDCxX2sYFS5bDkSe5GKKYHjBW7tgyN1R3UchpLJvdbf54ohxeGRtd8MbDUe1cguVHe4vnK68DsuD5MXjxi9EXx16rb9EnNsaF5KT99CinaJz";
+const TESTNET_RECEIVE_0: &str = "tb1qcr8te4kr609gcawutmrza0j4xv80jy8zmfp6l0";
+// Public key of the first BIP-84 test vector receive address
+const PUBKEY: &str =
+    "0330d54fd0dd420a6e5f8d3624f5f3482cae350f79d5f0753bf5beef9c2d91af3c";
+
+fn descriptor_invoice(s: &str) -> Invoice {
+    Invoice::with_descriptor(descriptor(s), Some(1000), &Chain::Mainnet)
+}
+
+#[test]
+fn derive_address_vectors() {
+    let invoice = descriptor_invoice("wpkh(XPUB/0/*)");
+    for (index, addr) in [(0, RECEIVE_0), (1, RECEIVE_1), (2, RECEIVE_2)] {
+        assert_eq!(
+            invoice.derive_address(index, bitcoin::Network::Bitcoin),
+            Ok(address(addr))
+        );
+    }
+
+    // Index is ignored for descriptors without wildcards
+    let invoice = descriptor_invoice(&format!("wpkh({})", PUBKEY));
+    assert_eq!(
+        invoice.derive_address(7, bitcoin::Network::Bitcoin),
+        Ok(address(RECEIVE_0))
+    );
+
+    let invoice = Invoice::with_descriptor(
+        Descriptor::from_str(&format!("wpkh({}/0/*)", TPUB)).unwrap(),
+        Some(1000),
+        &Chain::Testnet3,
+    );
+    assert_eq!(
+        invoice.derive_address(0, bitcoin::Network::Testnet),
+        Ok(address(TESTNET_RECEIVE_0))
+    );
+}
+
+#[test]
+fn derive_address_network_mismatch() {
+    let mut invoice = descriptor_invoice("wpkh(XPUB/0/*)");
+    assert_eq!(
+        invoice.derive_address(0, bitcoin::Network::Testnet),
+        Err(DeriveError::NetworkMismatch {
+            invoice: Network::Mainnet,
+            address: bitcoin::Network::Testnet
+        })
+    );
+
+    // Addresses of any network are derived for invoices without network
+    invoice.remove_network();
+    assert_eq!(
+        invoice
+            .derive_address(0, bitcoin::Network::Testnet)
+            .unwrap()
+            .to_string(),
+        TESTNET_RECEIVE_0
+    );
+}
+
+#[test]
+fn derive_address_index_checks() {
+    let invoice = descriptor_invoice("wpkh(XPUB/0/*)");
+    assert_eq!(
+        invoice.derive_address(0x8000_0000, bitcoin::Network::Bitcoin),
+        Err(DeriveError::HardenedIndex(0x8000_0000))
+    );
+
+    let invoice = descriptor_invoice("wpkh(XPUB/0h/*)");
+    assert_eq!(
+        invoice.derive_address(0, bitcoin::Network::Bitcoin),
+        Err(DeriveError::HardenedDerivation)
+    );
+
+    let tracking = TrackingBeneficiary::new(
+        descriptor("wpkh(XPUB/0/*)"),
+        Some(1..2),
+        None,
+    )
+    .unwrap();
+    let invoice = Invoice::new_on_network(
+        Beneficiary::TrackingDescriptor(tracking),
+        Some(1000),
+        None,
+        Network::Mainnet,
+    );
+    assert_eq!(
+        invoice.derive_address(1, bitcoin::Network::Bitcoin),
+        Ok(address(RECEIVE_1))
+    );
+    for index in [0, 2] {
+        assert_eq!(
+            invoice.derive_address(index, bitcoin::Network::Bitcoin),
+            Err(DeriveError::IndexOutOfRange(index))
+        );
+    }
+}
+
+#[test]
+fn derive_address_beneficiary_checks() {
+    let invoice = Invoice::with_address(address(RECEIVE_0), Some(1000));
+    assert_eq!(
+        invoice.derive_address(0, bitcoin::Network::Bitcoin),
+        Err(DeriveError::NotDescriptor(BeneficiaryKind::Address))
+    );
+
+    // Bare descriptors have no address form
+    let invoice = descriptor_invoice(&format!("pk({})", PUBKEY));
+    assert!(matches!(
+        invoice.derive_address(0, bitcoin::Network::Bitcoin),
+        Err(DeriveError::NoAddress(_))
+    ));
+
+    // Multipath descriptors are not supported by miniscript 9
+    let multipath = format!("wpkh({}/<0;1>/*)", XPUB);
+    assert!(Beneficiary::from_str(&multipath).is_err());
+}
+
+fn proprietary_key(key: u8) -> ProprietaryKey {
+    ProprietaryKey {
+        prefix: b"invoice".to_vec(),
+        subtype: 0,
+        key: vec![key],
+    }
+}
+
+fn psbt(inputs: usize, outputs: &[(&str, u64)]) -> Psbt {
+    let mut tx = tx(outputs);
+    tx.input = vec![TxIn::default(); inputs];
+    Psbt::with(tx, PsbtVersion::V0).unwrap()
+}
+
+/// Invoice with PSBT template paying 1000 sats in two outputs
+fn psbt_invoice(amount: Option<u64>) -> Invoice {
+    let mut template = psbt(0, &[(RECEIVE_0, 600), (RECEIVE_1, 400)]);
+    template.proprietary.insert(proprietary_key(1), vec![0xaa]);
+    Invoice::new(Beneficiary::Psbt(template), amount, None)
+}
+
+fn payer_psbt() -> Psbt {
+    let mut psbt = psbt(1, &[(OTHER, 5000)]);
+    psbt.proprietary.insert(proprietary_key(2), vec![0xbb]);
+    psbt
+}
+
+#[test]
+fn psbt_template_merge() {
+    let invoice = psbt_invoice(Some(1000));
+    assert_eq!(validate_psbt_beneficiary(&invoice), Ok(()));
+
+    let mut payer = payer_psbt();
+    apply_psbt_template(&invoice, &mut payer).unwrap();
+    assert_eq!(payer.inputs, payer_psbt().inputs);
+    assert_eq!(
+        payer.to_unsigned_tx().output,
+        tx(&[(OTHER, 5000), (RECEIVE_0, 600), (RECEIVE_1, 400)]).output
+    );
+    assert_eq!(
+        payer
+            .outputs
+            .iter()
+            .map(|output| output.index())
+            .collect::<Vec<_>>(),
+        vec![0, 1, 2]
+    );
+    assert_eq!(
+        payer.proprietary.into_iter().collect::<Vec<_>>(),
+        vec![
+            (proprietary_key(1), vec![0xaa]),
+            (proprietary_key(2), vec![0xbb])
+        ]
+    );
+
+    // Payer PSBT may already have the same proprietary keys
+    let mut payer = payer_psbt();
+    payer.proprietary.insert(proprietary_key(1), vec![0xaa]);
+    apply_psbt_template(&invoice, &mut payer).unwrap();
+    assert_eq!(payer.outputs.len(), 3);
+
+    // Any amount invoices accept templates paying any amount
+    let invoice = psbt_invoice(None);
+    assert_eq!(validate_psbt_beneficiary(&invoice), Ok(()));
+    apply_psbt_template(&invoice, &mut payer_psbt()).unwrap();
+}
+
+fn assert_not_applied(invoice: &Invoice, payer: Psbt, err: PsbtTemplateError) {
+    let mut merged = payer.clone();
+    assert_eq!(apply_psbt_template(invoice, &mut merged), Err(err));
+    assert_eq!(merged, payer);
+}
+
+#[test]
+fn psbt_template_errors() {
+    let mut payer = payer_psbt();
+    payer.proprietary.insert(proprietary_key(1), vec![0xcc]);
+    assert_not_applied(
+        &psbt_invoice(Some(1000)),
+        payer,
+        PsbtTemplateError::ProprietaryConflict,
+    );
+
+    let invoice = psbt_invoice(Some(1500));
+    let err = PsbtTemplateError::AmountMismatch {
+        outputs: 1000,
+        invoice: 1500,
+    };
+    assert_eq!(validate_psbt_beneficiary(&invoice), Err(err));
+    assert_not_applied(&invoice, payer_psbt(), err);
+
+    let template = psbt(1, &[(RECEIVE_0, 1000)]);
+    let invoice = Invoice::new(Beneficiary::Psbt(template), Some(1000), None);
+    let err = PsbtTemplateError::TemplateInputs(1);
+    assert_eq!(validate_psbt_beneficiary(&invoice), Err(err));
+    assert_not_applied(&invoice, payer_psbt(), err);
+
+    let invoice = Invoice::with_address(address(RECEIVE_0), Some(1000));
+    let err = PsbtTemplateError::NotPsbt(BeneficiaryKind::Address);
+    assert_eq!(validate_psbt_beneficiary(&invoice), Err(err));
+    assert_not_applied(&invoice, payer_psbt(), err);
+}
+
+/// Builds transaction paying the invoice address, which input signals
+/// replaceability if `rbf` is set
+fn policy_tx(value: u64, rbf: bool) -> Transaction {
+    let mut tx = tx(&[(RECEIVE_0, value)]);
+    if rbf {
+        tx.input[0].sequence = Sequence::ENABLE_RBF_NO_LOCKTIME;
+    }
+    tx
+}
+
+fn policy(
+    min_feerate_sat_vb: Option<u32>,
+    rbf_required: Option<bool>,
+) -> OnchainPolicy {
+    OnchainPolicy {
+        min_feerate_sat_vb,
+        rbf_required,
+        min_confirmations: Some(1),
+    }
+}
+
+#[test]
+fn policy_rbf_checks() {
+    let (final_tx, rbf_tx) = (policy_tx(1000, false), policy_tx(1000, true));
+
+    let required = policy(None, Some(true));
+    assert_eq!(
+        required.check_tx(&final_tx, None),
+        Err(PolicyViolation::RbfRequired)
+    );
+    assert_eq!(required.check_tx(&rbf_tx, None), Ok(()));
+
+    let forbidden = policy(None, Some(false));
+    assert_eq!(forbidden.check_tx(&final_tx, None), Ok(()));
+    assert_eq!(
+        forbidden.check_tx(&rbf_tx, None),
+        Err(PolicyViolation::RbfForbidden)
+    );
+
+    let any = policy(None, None);
+    assert_eq!(any.check_tx(&final_tx, None), Ok(()));
+    assert_eq!(any.check_tx(&rbf_tx, None), Ok(()));
+}
+
+#[test]
+fn policy_feerate_checks() {
+    let tx = policy_tx(1000, false);
+    let policy = policy(Some(5), None);
+    assert_eq!(
+        policy.check_tx(&tx, Some(4)),
+        Err(PolicyViolation::Feerate {
+            feerate: 4,
+            required: 5
+        })
+    );
+    assert_eq!(policy.check_tx(&tx, Some(5)), Ok(()));
+    // Unknown feerate is not checked
+    assert_eq!(policy.check_tx(&tx, None), Ok(()));
+}
+
+#[test]
+fn policy_violation_match() {
+    let mut invoice = Invoice::with_address(address(RECEIVE_0), Some(1000));
+    invoice.set_onchain_policy(policy(Some(5), Some(false)));
+
+    assert_eq!(
+        invoice.matches_tx_with_feerate(&policy_tx(1000, false), None, Some(2)),
+        PaymentMatch::PolicyViolation {
+            output_indexes: vec![0],
+            amount: 1000,
+            violation: PolicyViolation::Feerate {
+                feerate: 2,
+                required: 5
+            }
+        }
+    );
+    assert_eq!(
+        invoice.matches_tx(&policy_tx(1000, true), None),
+        PaymentMatch::PolicyViolation {
+            output_indexes: vec![0],
+            amount: 1000,
+            violation: PolicyViolation::RbfForbidden
+        }
+    );
+    assert_eq!(
+        invoice.matches_tx_with_feerate(&policy_tx(1000, false), None, Some(5)),
+        paid(vec![0], 1000)
+    );
+    // Amount is checked before the policy
+    assert_eq!(
+        invoice.matches_tx_with_feerate(&policy_tx(999, true), None, Some(2)),
+        PaymentMatch::Underpaid {
+            received: 999,
+            expected: 1000
+        }
+    );
+}
+
+#[test]
+fn policy_feerate_bounds() {
+    assert_eq!(policy(None, None).validate(), Ok(()));
+    assert_eq!(policy(Some(1), None).validate(), Ok(()));
+    assert_eq!(
+        policy(Some(OnchainPolicy::MAX_FEERATE), None).validate(),
+        Ok(())
+    );
+    for feerate in [0, OnchainPolicy::MAX_FEERATE + 1] {
+        assert_eq!(
+            policy(Some(feerate), None).validate(),
+            Err(OnchainPolicyError::Feerate(feerate))
+        );
+    }
+
+    let mut invoice = Invoice::with_address(address(RECEIVE_0), Some(1000));
+    invoice.set_onchain_policy(policy(Some(0), None));
+    assert_eq!(
+        invoice.validate(),
+        Err(vec![InvoiceInconsistency::OnchainPolicy(
+            OnchainPolicyError::Feerate(0)
+        )])
+    );
+}
+
+#[test]
+fn policy_round_trip() {
+    for policy in [
+        OnchainPolicy::default(),
+        policy(Some(5), None),
+        policy(Some(1), Some(true)),
+        policy(None, Some(false)),
+    ] {
+        let data = policy.strict_serialize().unwrap();
+        assert_eq!(OnchainPolicy::strict_deserialize(&data).unwrap(), policy);
+
+        let mut invoice = Invoice::with_address(address(RECEIVE_0), Some(1000));
+        invoice.set_onchain_policy(policy);
+        let decoded = Invoice::from_str(&invoice.to_string()).unwrap();
+        assert_eq!(decoded.onchain_policy(), &Some(policy));
+        assert_eq!(decoded, invoice);
+
+        #[cfg(feature = "serde")]
+        {
+            let json = serde_json::to_string(&invoice).unwrap();
+            let decoded: Invoice = serde_json::from_str(&json).unwrap();
+            assert_eq!(decoded.onchain_policy(), &Some(policy));
+        }
+    }
+}
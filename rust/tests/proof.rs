@@ -0,0 +1,266 @@
+// LNP/BP universal invoice library implementing LNPBP-38 standard
+// Written in 2021 by
+//     Dr. Maxim Orlovsky <orlovsky@pandoracore.com>
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the MIT License
+// along with this software.
+// If not, see <https://opensource.org/licenses/MIT>.
+
+mod common;
+
+use std::str::FromStr;
+
+use amplify::{Slice32, Wrapper};
+use bitcoin::hashes::hex::ToHex;
+use bitcoin::hashes::{sha256, Hash};
+use bitcoin::secp256k1::SECP256K1;
+use bitcoin::{Address, Network, PrivateKey};
+use bitcoin_scripts::hlc::HashLock;
+use internet2::addr::NodeId;
+use invoice::{
+    AddressProof, Beneficiary, BeneficiaryKind, Invoice, LnAddress, Lock,
+    PaymentProof, ProofError,
+};
+use lnpbp::bech32::ToBech32String;
+use lnpbp::chain::Chain;
+use strict_encoding::{StrictDecode, StrictEncode};
+
+// Key and addresses of the BIP-322 test vectors
+const WIF: &str = "L3VFeEujGtevx9w18HD1fhRbCH67Az2dpCymeRE1SoPK6XQtaN2k";
+const P2WPKH: &str = "bc1q9vza2e8x573nczrlzms0wvx3gsqjx7vavgkx0l";
+const P2TR: &str =
+    "bc1ppv609nr0vr25u07u95waq5lucwfm6tde4nydujnu8npg4q75mr5sxq8lt3";
+
+/// Proof signature of the P2WPKH test invoice
+const P2WPKH_PROOF_SIG: &str = "3045022100cd24fcb921c2dbe3388e7ce96f608798b0c4e78f4cafe4adca6d0decbe43d72502205820b2a4925b9e97a9ef003fe00aa7e9b187e07de5607c43826f47512b14a4ab01";
+
+fn secret_key() -> bitcoin::secp256k1::SecretKey {
+    PrivateKey::from_wif(WIF).unwrap().inner
+}
+
+fn invoice(address: &str) -> Invoice {
+    let mut invoice =
+        Invoice::with_address(Address::from_str(address).unwrap(), Some(1000));
+    invoice.set_purpose("coffee".to_owned());
+    invoice
+}
+
+#[test]
+fn vector_key_controls_addresses() {
+    let pubkey = PrivateKey::from_wif(WIF).unwrap().public_key(SECP256K1);
+    assert_eq!(
+        Address::p2wpkh(&pubkey, Network::Bitcoin)
+            .unwrap()
+            .to_string(),
+        P2WPKH
+    );
+    assert_eq!(
+        Address::p2tr(SECP256K1, pubkey.inner.into(), None, Network::Bitcoin)
+            .to_string(),
+        P2TR
+    );
+}
+
+#[test]
+fn p2wpkh_proof() {
+    let mut invoice = invoice(P2WPKH);
+    invoice.sign_address_proof(&secret_key()).unwrap();
+    invoice.verify_address_proof().unwrap();
+
+    // ECDSA signatures are deterministic, so the proof is fixed
+    let proof = invoice.beneficiary_proof().clone().unwrap();
+    assert_eq!(proof.witness.len(), 2);
+    assert_eq!(proof.witness[0].to_hex(), P2WPKH_PROOF_SIG);
+    assert_eq!(
+        proof.witness[1].to_hex(),
+        "02c7f12003196442943d8588e01aee840423cc54fc1521526a3b85c2b0cbd58872"
+    );
+
+    let decoded = Invoice::from_str(&invoice.to_bech32_string()).unwrap();
+    decoded.verify_address_proof().unwrap();
+}
+
+#[test]
+fn p2tr_proof() {
+    let mut invoice = invoice(P2TR);
+    invoice.sign_address_proof(&secret_key()).unwrap();
+    invoice.verify_address_proof().unwrap();
+    let proof = invoice.beneficiary_proof().clone().unwrap();
+    assert_eq!(proof.witness.len(), 1);
+    assert_eq!(proof.witness[0].len(), 64);
+
+    let decoded = Invoice::from_str(&invoice.to_bech32_string()).unwrap();
+    decoded.verify_address_proof().unwrap();
+}
+
+#[test]
+fn tampered_proof_fails() {
+    for address in [P2WPKH, P2TR] {
+        let mut invoice = invoice(address);
+        invoice.sign_address_proof(&secret_key()).unwrap();
+        let mut proof = invoice.beneficiary_proof().clone().unwrap();
+        proof.witness[0][10] ^= 1;
+        invoice.attach_address_proof(proof);
+        assert_eq!(
+            invoice.verify_address_proof(),
+            Err(ProofError::InvalidAddressProof)
+        );
+    }
+}
+
+#[test]
+fn non_default_sighash_is_rejected() {
+    for (address, sighash) in [(P2WPKH, 0x02), (P2WPKH, 0x81), (P2TR, 0x83)] {
+        let mut invoice = invoice(address);
+        invoice.sign_address_proof(&secret_key()).unwrap();
+        let mut proof = invoice.beneficiary_proof().clone().unwrap();
+        let sig = &mut proof.witness[0];
+        if sig.len() == 64 {
+            sig.push(sighash as u8);
+        } else {
+            *sig.last_mut().unwrap() = sighash as u8;
+        }
+        invoice.attach_address_proof(proof);
+        assert_eq!(
+            invoice.verify_address_proof(),
+            Err(ProofError::SighashType(sighash)),
+            "{}",
+            address
+        );
+    }
+}
+
+#[test]
+fn proof_is_bound_to_invoice() {
+    let mut invoice = invoice(P2WPKH);
+    invoice.sign_address_proof(&secret_key()).unwrap();
+    let proof = invoice.beneficiary_proof().clone().unwrap();
+
+    invoice.set_purpose("tea".to_owned());
+    assert_eq!(invoice.beneficiary_proof(), &None);
+    assert_eq!(
+        invoice.verify_address_proof(),
+        Err(ProofError::NoAddressProof)
+    );
+    invoice.attach_address_proof(proof);
+    assert_eq!(
+        invoice.verify_address_proof(),
+        Err(ProofError::InvalidAddressProof)
+    );
+
+    let mut invoice = self::invoice(P2WPKH);
+    let key = bitcoin::secp256k1::SecretKey::from_slice(&[1; 32]).unwrap();
+    assert_eq!(invoice.sign_address_proof(&key), Err(ProofError::WrongKey));
+    invoice.attach_address_proof(AddressProof { witness: vec![] });
+    assert_eq!(
+        invoice.verify_address_proof(),
+        Err(ProofError::InvalidAddressProof)
+    );
+}
+
+/// SHA256 of 32 zero bytes, used as the payment hash of the zero preimage
+const ZERO_PREIMAGE_HASH: &str =
+    "66687aadf862bd776c8fc18b8e9f8e20089714856ee233b3902a591d0d5f2925";
+
+fn ln_address(lock: Lock) -> LnAddress {
+    LnAddress {
+        node_id: NodeId::from(common::keypair(3).public_key()),
+        features: Default::default(),
+        lock,
+        secret: Some(Slice32::from_inner([0x22; 32])),
+        network: Chain::Mainnet,
+        min_final_cltv_expiry: Some(18),
+        path_hints: vec![],
+        ln_flags: Default::default(),
+    }
+}
+
+fn hash_lock(hex: &str) -> Lock {
+    Lock::Hash(HashLock::from(Slice32::from_str(hex).unwrap()))
+}
+
+fn ln_invoice() -> Invoice {
+    let address = ln_address(hash_lock(ZERO_PREIMAGE_HASH));
+    let mut invoice = Invoice::new(Beneficiary::Bolt(address), None, None);
+    invoice.set_purpose("coffee".to_owned());
+    invoice
+}
+
+#[test]
+fn preimage_vector() {
+    let address = ln_address(hash_lock(ZERO_PREIMAGE_HASH));
+    assert!(address.verify_preimage(&[0; 32]));
+    assert!(!address.verify_preimage(&[1; 32]));
+
+    let point = common::keypair(4).public_key();
+    let address = ln_address(Lock::Point(point));
+    assert!(!address.verify_preimage(&[0; 32]));
+}
+
+#[test]
+fn payment_preimage() {
+    let mut invoice = ln_invoice();
+    assert_eq!(invoice.verify_payment_preimage(&[0; 32]), Ok(()));
+    assert_eq!(
+        invoice.verify_payment_preimage(&[1; 32]),
+        Err(ProofError::WrongPreimage)
+    );
+
+    // Preimage of any lightning beneficiary is accepted
+    let alt_hash = sha256::Hash::hash(&[1; 32]).to_hex();
+    invoice.add_alt_beneficiary(Beneficiary::Bolt(ln_address(hash_lock(
+        &alt_hash,
+    ))));
+    assert_eq!(invoice.verify_payment_preimage(&[1; 32]), Ok(()));
+    assert_eq!(
+        invoice.verify_payment_preimage(&[2; 32]),
+        Err(ProofError::WrongPreimage)
+    );
+
+    assert_eq!(
+        self::invoice(P2WPKH).verify_payment_preimage(&[0; 32]),
+        Err(ProofError::NoLightning(BeneficiaryKind::Address))
+    );
+}
+
+#[test]
+fn payment_proof() {
+    let invoice = ln_invoice();
+    assert_eq!(
+        PaymentProof::new(&invoice, [1; 32], None),
+        Err(ProofError::WrongPreimage)
+    );
+
+    let proof =
+        PaymentProof::new(&invoice, [0; 32], Some("table 4".to_owned()))
+            .unwrap();
+    assert_eq!(proof.invoice_id, invoice.invoice_id());
+    assert_eq!(proof.verify(&invoice), Ok(()));
+
+    let mut other = invoice.clone();
+    other.set_purpose("tea".to_owned());
+    assert_eq!(
+        proof.verify(&other),
+        Err(ProofError::InvoiceMismatch {
+            proof: invoice.invoice_id(),
+            invoice: other.invoice_id()
+        })
+    );
+
+    let data = proof.strict_serialize().unwrap();
+    assert_eq!(PaymentProof::strict_deserialize(&data).unwrap(), proof);
+
+    #[cfg(feature = "serde")]
+    {
+        let json = serde_json::to_value(&proof).unwrap();
+        assert_eq!(json["preimage"], [0u8; 32].to_hex());
+        assert_eq!(json["payerNote"], "table 4");
+        let decoded: PaymentProof = serde_json::from_value(json).unwrap();
+        assert_eq!(decoded, proof);
+    }
+}
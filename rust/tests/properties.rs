@@ -0,0 +1,733 @@
+// LNP/BP universal invoice library implementing LNPBP-38 standard
+// Written in 2021 by
+//     Dr. Maxim Orlovsky <orlovsky@pandoracore.com>
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the MIT License
+// along with this software.
+// If not, see <https://opensource.org/licenses/MIT>.
+
+//! Property tests checking that the lossless invoice representations
+//! round-trip. Values are produced by seeded generators preferring the edge
+//! values (empty collections, maximal integers and lengths, "any" amounts
+//! and zero quantities); failing cases are reproduced by the case number
+//! printed in the assertion message.
+
+mod common;
+
+use std::cmp::Ordering;
+use std::fmt::{Debug, Display};
+use std::str::FromStr;
+
+use amplify::{Slice32, Wrapper};
+use bitcoin::hashes::Hash;
+use bitcoin::secp256k1::{self, SECP256K1};
+use bitcoin::{Address, Network as BitcoinNetwork, PrivateKey};
+use chrono::{TimeZone, Utc};
+use common::{keypair, with_records, RawInvoice};
+use invoice::{
+    AmountExt, AmountParseError, AssetMetadata, Beneficiary, BuilderError,
+    CanonicalInvoice, ConsignmentEndpoint, CurrencyData, Details, ExpiryTerm,
+    InlineDetails, Invoice, InvoiceBuilder, Iso4217, MerchantInfo,
+    OnchainPolicy, PaymentCode, Quantity, Recurrent, SilentPaymentCode,
+    SplitShare, Url, MAX_CONSIGNMENT_ENDPOINTS, MAX_REFERENCE_LEN,
+};
+use lnpbp::bech32::{Blob, FromBech32Str, ToBech32String};
+use lnpbp::chain::AssetId;
+use rand::distributions::uniform::SampleUniform;
+use rand::rngs::StdRng;
+use rand::{Rng, RngCore, SeedableRng};
+use strict_encoding::{StrictDecode, StrictEncode};
+
+const CASES: u64 = 256;
+
+// Default decoding limit of the string fields
+const MAX_STRING_LEN: usize = 1024;
+
+// BIP-174 test vector for a PSBT with one P2PKH input and two outputs
+const PSBT: &str = "cHNidP8BAFUCAAAAASeaIyOl37UfxF8iD6WLD8E+HjNCeSqF1+Ns1jM7XLw5AAAAAAD/////AaBa6gsAAAAAGXapFP/pwAYQl8w7Y28ssEYPpPxCfStFiKwAAAAAAAEBIJVe6gsAAAAAF6kUY0UgD2jRieGtwN8cTRbqjxTA2+uHIgIDsTQcy6doO2r08SOM1ul+cWfVafrEfx5I1HVBhENVvUZGMEMCIAQktY7/qqaU4VWepck7v9SokGQiQFXN8HC2dxRpRC0HAh9cjrD+plFtYLisszrWTt5g6Hhb+zqpS5m9+GFR25qaAQEEIgAgdx/RitRZZm3Unz1WTj28QvTIR3TjYK2haBao7UiNVoEBBUdSIQOxNBzLp2g7avTxI4zW6X5xZ9Vp+sR/HkjUdUGEQ1W9RiED3lXR4drIBeP4pYwfv5uUwC89uq/hJ/78pJlfJvggg71SriIGA7E0HMunaDtq9PEjjNbpfnFn1Wn6xH8eSNR1QYRDVb1GELSmumcAAACAAAAAgAQAAIAiBgPeVdHh2sgF4/iljB+/m5TALz26r+En/vykmV8m+CCDvRC0prpnAAAAgAAAAIAFAACAAAA=";
+
+const CURRENCIES: [&str; 6] = ["USD", "EUR", "JPY", "BHD", "CHF", "XAU"];
+
+/// Seeded random generator of the invoice data.
+struct Gen(StdRng);
+
+impl Gen {
+    fn cases() -> impl Iterator<Item = (u64, Gen)> {
+        (0..CASES).map(|case| (case, Gen(StdRng::seed_from_u64(case))))
+    }
+
+    fn option<T>(&mut self, f: impl FnOnce(&mut Self) -> T) -> Option<T> {
+        if self.0.gen() {
+            Some(f(self))
+        } else {
+            None
+        }
+    }
+
+    /// Generates integer up to `max`, preferring zero and `max`.
+    fn int<T>(&mut self, max: T) -> T
+    where
+        T: Copy + Default + PartialOrd + SampleUniform,
+    {
+        match self.0.gen_range(0..4) {
+            0 => T::default(),
+            1 => max,
+            _ => self.0.gen_range(T::default()..=max),
+        }
+    }
+
+    /// Generates length up to `max`, preferring zero and `max`.
+    fn len(&mut self, max: usize) -> usize {
+        match self.0.gen_range(0..4) {
+            0 => 0,
+            1 => max,
+            _ => self.0.gen_range(0..=max.min(64)),
+        }
+    }
+
+    fn bytes(&mut self, max: usize) -> Vec<u8> {
+        let mut data = vec![0u8; self.len(max)];
+        self.0.fill_bytes(&mut data);
+        data
+    }
+
+    /// Generates string of up to `max` bytes, including multi-byte
+    /// characters.
+    fn string(&mut self, max: usize) -> String {
+        let len = self.len(max);
+        let mut s = String::with_capacity(len);
+        while s.len() < len {
+            let c =
+                ['a', 'Z', '0', ' ', 'é', '₿', '🦀'][self.0.gen_range(0..7)];
+            s.push(if s.len() + c.len_utf8() <= len {
+                c
+            } else {
+                'a'
+            });
+        }
+        s
+    }
+
+    fn byte_array(&mut self) -> [u8; 32] {
+        let mut data = [0u8; 32];
+        self.0.fill_bytes(&mut data);
+        data
+    }
+
+    fn secret_key(&mut self) -> secp256k1::SecretKey {
+        secp256k1::SecretKey::new(&mut self.0)
+    }
+
+    fn public_key(&mut self) -> secp256k1::PublicKey {
+        self.secret_key().public_key(SECP256K1)
+    }
+
+    fn url(&mut self, scheme: &str) -> String {
+        let path = (0..self.len(3))
+            .map(|_| format!("/p{}", self.0.gen::<u16>()))
+            .collect::<String>();
+        format!(
+            "{}://host{}.example.com{}",
+            scheme,
+            self.0.gen::<u8>(),
+            path
+        )
+    }
+
+    fn amount(&mut self) -> AmountExt {
+        match self.0.gen_range(0..4) {
+            0 => AmountExt::Any,
+            1 => AmountExt::Normal(self.int(u64::MAX)),
+            2 => AmountExt::Milli(self.int(u64::MAX), 999),
+            _ => AmountExt::Milli(self.0.gen(), self.0.gen_range(1..=999)),
+        }
+    }
+
+    /// Generates amount out of a small range of values, so the generated
+    /// amounts are often equal in value.
+    fn small_amount(&mut self) -> AmountExt {
+        let val = self.0.gen_range(0..3);
+        match self.0.gen_range(0..3) {
+            0 => AmountExt::Any,
+            1 => AmountExt::Normal(val),
+            _ => AmountExt::Milli(val, [1, 500, 999][self.0.gen_range(0..3)]),
+        }
+    }
+
+    fn quantity(&mut self) -> Quantity {
+        let min = self.int(u32::MAX);
+        let max = self.option(|gen| min.saturating_add(gen.int(u32::MAX)));
+        let default = match max {
+            Some(max) => self.int(max - min) + min,
+            None => min.saturating_add(self.int(u32::MAX)),
+        };
+        Quantity::new(min, max, default).unwrap()
+    }
+
+    fn currency(&mut self) -> CurrencyData {
+        let iso4217 =
+            Iso4217::from_str(CURRENCIES[self.0.gen_range(0..6)]).unwrap();
+        let decimals = self.int(CurrencyData::MAX_DECIMALS);
+        let fractions = self.int(10u16.pow(decimals.into()) - 1);
+        let coins = self.int(u32::MAX);
+        let price_provider =
+            self.option(|gen| Url::from_str(&gen.url("https")).unwrap());
+        CurrencyData::new(iso4217, coins, fractions, decimals, price_provider)
+            .unwrap()
+    }
+
+    fn recurrent(&mut self) -> Recurrent {
+        match self.0.gen_range(0..7) {
+            0 => Recurrent::NonRecurrent,
+            1 => Recurrent::Seconds(self.int(u64::MAX)),
+            2 => Recurrent::Months(self.int(u8::MAX)),
+            3 => Recurrent::Years(self.int(u8::MAX)),
+            4 => Recurrent::Days(self.int(u16::MAX)),
+            5 => Recurrent::Weeks(self.int(u16::MAX)),
+            _ => Recurrent::Unknown(
+                self.0.gen_range(Recurrent::MAX_KNOWN_TAG + 1..=u8::MAX),
+                self.bytes(64),
+            ),
+        }
+    }
+
+    fn endpoint(&mut self) -> ConsignmentEndpoint {
+        let node = |gen: &mut Self| {
+            format!(
+                "{}@127.0.0.{}:{}",
+                gen.public_key(),
+                gen.0.gen::<u8>(),
+                gen.0.gen::<u16>()
+            )
+        };
+        let s = match self.0.gen_range(0..5) {
+            0 => format!("storm:{}", node(self)),
+            1 => format!("rgbhttpjsonrpc:{}", self.url("https")),
+            2 => format!("rgbws:{}", self.url("wss")),
+            3 => format!("bifrost:{}", node(self)),
+            _ => {
+                return ConsignmentEndpoint::Unknown {
+                    protocol: self.0.gen_range(4..=u8::MAX),
+                    data: Blob::from_inner(self.bytes(64)),
+                }
+            }
+        };
+        ConsignmentEndpoint::from_str(&s).unwrap()
+    }
+
+    fn network(&mut self) -> BitcoinNetwork {
+        [
+            BitcoinNetwork::Bitcoin,
+            BitcoinNetwork::Testnet,
+            BitcoinNetwork::Signet,
+            BitcoinNetwork::Regtest,
+        ][self.0.gen_range(0..4)]
+    }
+
+    /// Generates network having distinct address prefixes, since signet
+    /// and regtest addresses may look like testnet ones.
+    fn distinct_network(&mut self) -> BitcoinNetwork {
+        [BitcoinNetwork::Bitcoin, BitcoinNetwork::Testnet]
+            [self.0.gen_range(0..2)]
+    }
+
+    fn address(&mut self, network: BitcoinNetwork) -> Address {
+        let pk =
+            PrivateKey::new(self.secret_key(), network).public_key(SECP256K1);
+        match self.0.gen_range(0..3) {
+            0 => Address::p2pkh(&pk, network),
+            1 => Address::p2wpkh(&pk, network).unwrap(),
+            _ => Address::p2tr(SECP256K1, pk.inner.into(), None, network),
+        }
+    }
+
+    fn beneficiary(&mut self, network: BitcoinNetwork) -> Beneficiary {
+        let parse = |s: String| Beneficiary::from_str(&s).unwrap();
+        match self.0.gen_range(0..10) {
+            0 => Beneficiary::Address(self.address(network)),
+            1 => Beneficiary::BlindUtxo(
+                StrictDecode::strict_deserialize(self.byte_array()).unwrap(),
+            ),
+            2 => parse(format!("wpkh({})", self.public_key())),
+            3 => parse(PSBT.to_owned()),
+            4 => parse(format!(
+                "bolt:{}@{}",
+                self.public_key(),
+                Slice32::from_inner(self.byte_array())
+            )),
+            5 => Beneficiary::Unknown(Blob::from_inner(self.bytes(64))),
+            6 => Beneficiary::SilentPayment(SilentPaymentCode::new(
+                self.public_key(),
+                self.public_key(),
+                network != BitcoinNetwork::Bitcoin,
+            )),
+            7 => Beneficiary::PaymentCode(PaymentCode::new(
+                self.public_key(),
+                Slice32::from_inner(self.byte_array()),
+            )),
+            8 => parse(format!(
+                "wvout:{}:{}",
+                ["tapret1st", "opret1st"][self.0.gen_range(0..2)],
+                self.0.gen::<u64>()
+            )),
+            _ => parse(format!("bifrost:{}", self.public_key())),
+        }
+    }
+
+    /// Generates consistent invoice with random fields present.
+    fn invoice(&mut self) -> Invoice {
+        let network = self.network();
+        self.invoice_on(network)
+    }
+
+    /// Generates consistent invoice with addresses on the given network.
+    fn invoice_on(&mut self, network: BitcoinNetwork) -> Invoice {
+        let beneficiary = self.beneficiary(network);
+        let is_rgb = matches!(
+            beneficiary,
+            Beneficiary::BlindUtxo(_) | Beneficiary::WitnessVoutSeal(_)
+        );
+        let mut builder =
+            InvoiceBuilder::new(beneficiary).amount(self.amount());
+        if is_rgb || self.0.gen() {
+            builder = builder.asset(AssetId::from_inner(self.byte_array()));
+            for _ in 0..1 + self.len(MAX_CONSIGNMENT_ENDPOINTS - 1) {
+                builder = builder.consignment_endpoint(self.endpoint());
+            }
+        }
+        if self.0.gen() {
+            builder = builder.network(network.into());
+        }
+        let mut invoice = builder.finish().unwrap();
+
+        for _ in 0..self.len(3) {
+            let alt = self.beneficiary(network);
+            invoice.add_alt_beneficiary(alt);
+        }
+        if self.0.gen() {
+            invoice.set_recurrent(self.recurrent());
+        }
+        if self.0.gen() {
+            invoice.set_recurrence_limit(self.int(u32::MAX));
+        }
+        if self.0.gen() {
+            let issued_at = self.0.gen_range(1_500_000_000..2_000_000_000);
+            invoice.set_issued_at(Utc.timestamp_opt(issued_at, 0).unwrap());
+            let term = if self.0.gen() {
+                ExpiryTerm::RelativeSeconds(self.0.gen_range(1..=u32::MAX))
+            } else {
+                let expiry = issued_at + self.int(1_000_000_000);
+                ExpiryTerm::Absolute(Utc.timestamp_opt(expiry, 0).unwrap())
+            };
+            invoice.set_expiry_term(term);
+        }
+        if invoice.amount() != &AmountExt::Any && self.0.gen() {
+            invoice.set_quantity(self.quantity()).unwrap();
+        }
+        for _ in 0..self.len(3) {
+            let currency = self.currency();
+            // Requirements repeating a currency are rejected
+            let _ = invoice.add_currency_requirement(currency);
+        }
+        if self.0.gen() {
+            let shares = (0..self.len(3))
+                .map(|_| SplitShare::BasisPoints(self.int(10_000)))
+                .collect();
+            // Shares exceeding the whole payment are rejected
+            let _ = invoice.set_payment_split(shares);
+        }
+        if self.0.gen() {
+            let ticker = ["A", "USDT", "ABCDEFGH"][self.0.gen_range(0..3)];
+            let name = self.option(|gen| gen.string(64));
+            let precision = self.int(AssetMetadata::MAX_PRECISION);
+            let metadata = AssetMetadata::new(ticker, name, precision).unwrap();
+            invoice.set_asset_metadata(metadata);
+        }
+        if self.0.gen() {
+            invoice.set_merchant(self.string(MAX_STRING_LEN));
+        }
+        if self.0.gen() {
+            invoice.set_merchant_info(MerchantInfo::new(
+                self.string(MAX_STRING_LEN),
+            ));
+        }
+        if self.0.gen() {
+            invoice.set_purpose(self.string(MAX_STRING_LEN));
+        }
+        if self.0.gen() {
+            let reference = Blob::from_inner(self.bytes(MAX_REFERENCE_LEN));
+            invoice.set_reference(reference).unwrap();
+        }
+        if self.0.gen() {
+            let url = Url::from_str(&self.url("https")).unwrap();
+            invoice.set_details(Details::commit_to(&self.bytes(256), url));
+        }
+        if self.0.gen() {
+            let details = InlineDetails::new("text/plain", self.bytes(256));
+            invoice.set_inline_details(details);
+        }
+        if self.0.gen() {
+            let refund = Beneficiary::Address(self.address(network));
+            invoice.set_refund(refund);
+        }
+        if self.0.gen() {
+            invoice.set_onchain_policy(OnchainPolicy {
+                min_feerate_sat_vb: self.option(|gen| {
+                    gen.0.gen_range(1..=OnchainPolicy::MAX_FEERATE)
+                }),
+                rbf_required: self.option(|gen| gen.0.gen()),
+                min_confirmations: self.option(|gen| gen.int(u8::MAX)),
+            });
+        }
+        for _ in 0..self.len(3) {
+            let ty = 0x41 + 2 * u64::from(self.0.gen::<u8>());
+            invoice.set_extension(ty.into(), self.bytes(64)).unwrap();
+        }
+        invoice
+    }
+}
+
+fn assert_strict_round_trip<T>(case: u64, value: &T)
+where
+    T: StrictEncode + StrictDecode + Eq + Debug,
+{
+    let data = value.strict_serialize().unwrap();
+    let decoded = T::strict_deserialize(&data).unwrap();
+    assert_eq!(&decoded, value, "case {}", case);
+    assert_eq!(decoded.strict_serialize().unwrap(), data, "case {}", case);
+}
+
+fn assert_str_round_trip<T>(case: u64, value: &T)
+where
+    T: Display + FromStr + Eq + Debug,
+    T::Err: Debug,
+{
+    let s = value.to_string();
+    assert_eq!(&T::from_str(&s).unwrap(), value, "case {}: {}", case, s);
+}
+
+#[test]
+fn generated_invoices_are_consistent() {
+    for (case, mut gen) in Gen::cases() {
+        let invoice = gen.invoice();
+        assert_eq!(invoice.validate(), Ok(()), "case {}", case);
+    }
+}
+
+#[test]
+fn strict_round_trip() {
+    for (case, mut gen) in Gen::cases() {
+        assert_strict_round_trip(case, &gen.invoice());
+        assert_strict_round_trip(
+            case,
+            &gen.beneficiary(BitcoinNetwork::Bitcoin),
+        );
+        assert_strict_round_trip(case, &gen.amount());
+        assert_strict_round_trip(case, &gen.quantity());
+        assert_strict_round_trip(case, &gen.currency());
+        assert_strict_round_trip(case, &gen.recurrent());
+        assert_strict_round_trip(case, &gen.endpoint());
+    }
+}
+
+#[test]
+fn bech32_round_trip() {
+    for (case, mut gen) in Gen::cases() {
+        let invoice = gen.invoice();
+        let s = invoice.to_bech32_string();
+        let parsed = Invoice::from_str(&s).unwrap();
+        assert_eq!(parsed, invoice, "case {}", case);
+        assert_eq!(parsed.to_bech32_string(), s, "case {}", case);
+    }
+}
+
+/// Invoices reuse per-thread compression state, which must produce the same
+/// strings as the generic lnpbp compressed strict encoding, whatever the
+/// previously encoded invoices were.
+#[test]
+fn bech32_matches_generic_encoding() {
+    for (case, mut gen) in Gen::cases() {
+        let invoice = gen.invoice();
+        let data = invoice.strict_serialize().unwrap();
+        let s = invoice.to_bech32_string();
+        assert_eq!(
+            s,
+            RawInvoice(data.clone()).to_bech32_string(),
+            "case {}",
+            case
+        );
+        assert_eq!(
+            RawInvoice::from_bech32_str(&s).unwrap().0,
+            data,
+            "case {}",
+            case
+        );
+    }
+}
+
+#[test]
+fn display_from_str_round_trip() {
+    for (case, mut gen) in Gen::cases() {
+        assert_str_round_trip(case, &gen.amount());
+        assert_str_round_trip(case, &gen.quantity());
+        assert_str_round_trip(case, &gen.endpoint());
+
+        // Lightning beneficiaries are displayed as node ids only
+        let network = gen.distinct_network();
+        match gen.beneficiary(network) {
+            Beneficiary::Bolt(_) => {}
+            beneficiary => assert_str_round_trip(case, &beneficiary),
+        }
+
+        // Amounts with fewer decimals than the currency exponent are
+        // parsed with the exponent as decimals
+        let currency = gen.currency();
+        if currency.decimals >= CurrencyData::implied_decimals(currency.iso4217)
+        {
+            assert_str_round_trip(case, &currency);
+        }
+    }
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn serde_round_trip() {
+    for (case, mut gen) in Gen::cases() {
+        let network = gen.distinct_network();
+        let invoice = gen.invoice_on(network);
+        let json = serde_json::to_string(&invoice).unwrap();
+        let decoded: Invoice = serde_json::from_str(&json).unwrap();
+        assert_eq!(decoded, invoice, "case {}: {}", case, json);
+
+        let yaml = serde_yaml::to_string(&invoice).unwrap();
+        let decoded: Invoice = serde_yaml::from_str(&yaml).unwrap();
+        assert_eq!(decoded, invoice, "case {}: {}", case, yaml);
+    }
+}
+
+#[test]
+fn signature_commits_to_every_byte() {
+    for (case, mut gen) in Gen::cases() {
+        let mut invoice = gen.invoice();
+        invoice.sign(&keypair(1)).unwrap();
+        let parsed = Invoice::from_str(&invoice.to_bech32_string()).unwrap();
+        parsed.verify_signature().unwrap();
+
+        // Flipping any bit breaks either decoding or the signature, unless
+        // it changes the parity of the signing key (BIP-340 signatures
+        // commit to x-only keys) or the decoder normalizes the changed data
+        // (like the legacy chain parameters of lightning beneficiaries)
+        let sig = invoice.signature().unwrap();
+        let data = invoice.strict_serialize().unwrap();
+        for _ in 0..8 {
+            let pos = gen.0.gen_range(0..data.len());
+            let mut tampered = data.clone();
+            tampered[pos] ^= 1 << gen.0.gen_range(0..8);
+            let mut decoded = match Invoice::strict_deserialize(&tampered) {
+                Ok(decoded) if decoded.verify_signature().is_ok() => decoded,
+                _ => continue,
+            };
+            let tampered_sig = decoded.signature().unwrap();
+            assert_eq!(
+                tampered_sig.pubkey.x_only_public_key().0,
+                sig.pubkey.x_only_public_key().0,
+                "case {}: byte {} of {}",
+                case,
+                pos,
+                data.len()
+            );
+            decoded.set_signature(sig.pubkey, tampered_sig.signature);
+            assert_eq!(decoded, invoice, "case {}: byte {}", case, pos);
+        }
+    }
+}
+
+#[test]
+fn amount_order_is_antisymmetric() {
+    for (case, mut gen) in Gen::cases() {
+        let (a, b) = (gen.small_amount(), gen.small_amount());
+        assert_eq!(a.cmp(&b), b.cmp(&a).reverse(), "case {}", case);
+        assert_eq!(a.cmp(&b) == Ordering::Equal, a == b, "case {}", case);
+        assert_eq!(a.partial_cmp(&b), Some(a.cmp(&b)), "case {}", case);
+    }
+}
+
+#[test]
+fn amount_order_is_transitive() {
+    for (case, mut gen) in Gen::cases() {
+        let amounts =
+            [gen.small_amount(), gen.small_amount(), gen.small_amount()];
+        for a in &amounts {
+            for b in &amounts {
+                for c in &amounts {
+                    if a <= b && b <= c {
+                        assert!(a <= c, "case {}", case);
+                    }
+                    if a < b && b <= c {
+                        assert!(a < c, "case {}", case);
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[test]
+fn amount_order_follows_value() {
+    for (case, mut gen) in Gen::cases() {
+        let (a, b) = (gen.amount(), gen.amount());
+        match (a.to_msat(), b.to_msat()) {
+            (Some(x), Some(y)) if x != y => {
+                assert_eq!(a.cmp(&b), x.cmp(&y), "case {}", case)
+            }
+            _ => {}
+        }
+        if a == AmountExt::Any {
+            assert!(a <= b, "case {}", case);
+        }
+    }
+    assert!(AmountExt::Milli(1, 999) < AmountExt::Normal(2));
+    assert!(AmountExt::Normal(u64::MAX) > AmountExt::Milli(1, 999));
+    assert!(AmountExt::Any < AmountExt::Normal(0));
+}
+
+#[test]
+fn invoice_order_is_consistent_with_eq() {
+    let invoices = Gen::cases()
+        .take(32)
+        .map(|(_, mut gen)| gen.invoice())
+        .collect::<Vec<_>>();
+    for (x, a) in invoices.iter().enumerate() {
+        for (y, b) in invoices.iter().enumerate() {
+            let case = format!("cases {} and {}", x, y);
+            assert_eq!(a.cmp(b), b.cmp(a).reverse(), "{}", case);
+            assert_eq!(a.cmp(b) == Ordering::Equal, a == b, "{}", case);
+            assert_eq!(a.partial_cmp(b), Some(a.cmp(b)), "{}", case);
+            for c in &invoices {
+                if a <= b && b <= c {
+                    assert!(a <= c, "{} and {:?}", case, c);
+                }
+            }
+            let (ca, cb) = (
+                CanonicalInvoice::new(a.clone()),
+                CanonicalInvoice::new(b.clone()),
+            );
+            let (bytes_a, bytes_b) = (a.canonical_bytes(), b.canonical_bytes());
+            assert_eq!(ca.cmp(&cb), bytes_a.cmp(&bytes_b), "{}", case);
+            assert_eq!(ca == cb, a == b, "{}", case);
+        }
+    }
+
+    let mut sorted = invoices.clone();
+    sorted.sort();
+    assert!(sorted.windows(2).all(|pair| pair[0] <= pair[1]));
+    let mut bytes = invoices
+        .iter()
+        .map(Invoice::canonical_bytes)
+        .collect::<Vec<_>>();
+    bytes.sort();
+    let mut canonical = invoices
+        .into_iter()
+        .map(CanonicalInvoice::new)
+        .collect::<Vec<_>>();
+    canonical.sort();
+    assert_eq!(
+        canonical
+            .iter()
+            .map(|invoice| invoice.canonical_bytes().to_vec())
+            .collect::<Vec<_>>(),
+        bytes
+    );
+}
+
+#[test]
+fn equivalent_urls_make_equal_invoices() {
+    let url = "https://proxy.example.com/json-rpc";
+    let endpoint =
+        ConsignmentEndpoint::from_str(&format!("rgbhttpjsonrpc:{}", url))
+            .unwrap();
+    let builder = InvoiceBuilder::new(common::invoice().beneficiary().clone());
+    let normalized = builder
+        .clone()
+        .consignment_endpoint(endpoint.clone())
+        .finish()
+        .unwrap();
+
+    // Strict decoding keeps the URL with the trailing slash verbatim
+    let verbatim = format!("{}/", url);
+    let mut record = vec![1u8, 0, 1];
+    record.extend((verbatim.len() as u16).to_le_bytes());
+    record.extend(verbatim.as_bytes());
+    let data = with_records(&builder.finish().unwrap(), &[(0x0a, &record)]);
+    let decoded = Invoice::strict_deserialize(&data).unwrap();
+    assert_eq!(decoded.consignment_endpoints(), &[endpoint]);
+
+    assert_ne!(decoded.canonical_bytes(), normalized.canonical_bytes());
+    assert_ne!(decoded.to_string(), normalized.to_string());
+    assert_eq!(decoded, normalized);
+    assert_eq!(decoded.cmp(&normalized), Ordering::Equal);
+    assert_ne!(
+        CanonicalInvoice::new(decoded),
+        CanonicalInvoice::new(normalized)
+    );
+}
+
+#[test]
+fn milli_fraction_is_checked() {
+    assert_eq!(AmountExt::with_milli(1, 0), Ok(AmountExt::Normal(1)));
+    assert_eq!(AmountExt::with_milli(1, 999), Ok(AmountExt::Milli(1, 999)));
+    assert!(AmountExt::with_milli(1, 1000).is_err());
+    assert!(AmountExt::Milli(1, 1000).strict_serialize().is_err());
+    assert!(AmountExt::Milli(1, 999).strict_serialize().is_ok());
+
+    // Invoices with unencodable amounts can't be constructed
+    let mut invoice = common::invoice();
+    invoice.sign(&keypair(1)).unwrap();
+    let signed = invoice.clone();
+    assert_eq!(
+        invoice.set_amount(AmountExt::Milli(1, 1000)),
+        Err(AmountParseError)
+    );
+    assert_eq!(invoice, signed);
+    assert_eq!(invoice.set_amount(AmountExt::Milli(1, 999)), Ok(true));
+    invoice.canonical_bytes();
+
+    let builder = InvoiceBuilder::new(invoice.beneficiary().clone());
+    assert_eq!(
+        builder.clone().amount(AmountExt::Milli(1, 1000)).finish(),
+        Err(BuilderError::AmountFraction(1000))
+    );
+    let built = builder.amount(AmountExt::Milli(1, 999)).finish().unwrap();
+    assert_eq!(built.amount(), &AmountExt::Milli(1, 999));
+}
+
+#[test]
+fn edge_values_round_trip() {
+    let address =
+        Address::from_str("bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t4")
+            .unwrap();
+    let mut invoice = Invoice::new(Beneficiary::Address(address), None, None);
+    assert_eq!(invoice.amount(), &AmountExt::Any);
+    assert_strict_round_trip(0, &invoice);
+
+    // Strings as long as the decoding limit allows
+    let too_long = "🦀".repeat(MAX_STRING_LEN / 4) + "a";
+    invoice.set_purpose(too_long);
+    assert!(Invoice::from_str(&invoice.to_bech32_string()).is_err());
+    invoice.set_purpose("🦀".repeat(MAX_STRING_LEN / 4 - 1) + "éaa");
+    invoice.set_amount(AmountExt::Normal(u64::MAX)).unwrap();
+    invoice
+        .set_quantity(Quantity::new(0, Some(0), 0).unwrap())
+        .unwrap();
+    invoice.set_recurrent(Recurrent::Seconds(u64::MAX));
+    invoice.set_recurrence_limit(u32::MAX);
+    assert_strict_round_trip(0, &invoice);
+    let parsed = Invoice::from_str(&invoice.to_bech32_string()).unwrap();
+    assert_eq!(parsed, invoice);
+    assert_eq!(parsed.purpose().as_ref().unwrap().len(), MAX_STRING_LEN);
+}
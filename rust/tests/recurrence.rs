@@ -0,0 +1,156 @@
+// LNP/BP universal invoice library implementing LNPBP-38 standard
+// Written in 2021 by
+//     Dr. Maxim Orlovsky <orlovsky@pandoracore.com>
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the MIT License
+// along with this software.
+// If not, see <https://opensource.org/licenses/MIT>.
+
+mod common;
+
+use std::str::FromStr;
+
+use chrono::{DateTime, TimeZone, Utc};
+use common::{invoice, keypair, with_records};
+use invoice::{Invoice, Recurrent};
+use lnpbp::bech32::ToBech32String;
+use strict_encoding::{StrictDecode, StrictEncode};
+
+#[test]
+fn recurrent_round_trip() {
+    for recurrent in [
+        Recurrent::NonRecurrent,
+        Recurrent::Seconds(3600),
+        Recurrent::Months(1),
+        Recurrent::Years(2),
+        Recurrent::Days(14),
+        Recurrent::Weeks(2),
+        Recurrent::Unknown(6, vec![]),
+        Recurrent::Unknown(0xff, vec![1, 2, 3]),
+    ] {
+        let data = recurrent.strict_serialize().unwrap();
+        assert_eq!(Recurrent::strict_deserialize(&data).unwrap(), recurrent);
+    }
+}
+
+#[test]
+fn unknown_recurrence_keeps_params() {
+    let recurrent = Recurrent::strict_deserialize([9u8, 0xaa, 0xbb]).unwrap();
+    assert_eq!(recurrent, Recurrent::Unknown(9, vec![0xaa, 0xbb]));
+    assert_eq!(recurrent.strict_serialize().unwrap(), vec![9u8, 0xaa, 0xbb]);
+
+    let start = Utc.timestamp_opt(1_700_000_000, 0).unwrap();
+    assert_eq!(recurrent.nth_occurrence(start, 1), None);
+    assert_eq!(recurrent.next_after(start, Some(start)), None);
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn unknown_recurrence_serde() {
+    let recurrent = Recurrent::Unknown(9, vec![0xaa, 0xbb]);
+    let json = serde_json::to_string(&recurrent).unwrap();
+    assert_eq!(json, r#"{"unknown":[9,"aabb"]}"#);
+    assert_eq!(serde_json::from_str::<Recurrent>(&json).unwrap(), recurrent);
+}
+
+#[test]
+fn unknown_recurrence_rejects_known_tags() {
+    for tag in 0..=Recurrent::MAX_KNOWN_TAG {
+        assert!(Recurrent::Unknown(tag, vec![]).strict_serialize().is_err());
+    }
+    assert_eq!(
+        Recurrent::strict_deserialize([0u8]).unwrap(),
+        Recurrent::NonRecurrent
+    );
+}
+
+#[test]
+fn invoice_with_unknown_recurrence() {
+    let data = with_records(&invoice(), &[(0x04, &[9, 0xaa, 0xbb])]);
+    let decoded = Invoice::strict_deserialize(&data).unwrap();
+    assert_eq!(
+        decoded.recurrent(),
+        &Recurrent::Unknown(9, vec![0xaa, 0xbb])
+    );
+    assert_eq!(decoded.strict_serialize().unwrap(), data);
+
+    let mut invoice = invoice();
+    invoice.set_recurrent(Recurrent::Unknown(9, vec![0xaa, 0xbb]));
+    invoice.sign(&keypair(1)).unwrap();
+    let decoded = Invoice::from_str(&invoice.to_bech32_string()).unwrap();
+    assert_eq!(decoded, invoice);
+    decoded.verify_signature().unwrap();
+}
+
+fn date(year: i32, month: u32, day: u32) -> DateTime<Utc> {
+    Utc.with_ymd_and_hms(year, month, day, 12, 0, 0).unwrap()
+}
+
+#[test]
+fn month_end_is_clamped() {
+    let monthly = Recurrent::Months(1);
+    let start = date(2023, 1, 31);
+    assert_eq!(monthly.nth_occurrence(start, 1), Some(date(2023, 2, 28)));
+    // Each occurrence is computed from the start, so clamping doesn't
+    // accumulate
+    assert_eq!(monthly.nth_occurrence(start, 2), Some(date(2023, 3, 31)));
+    assert_eq!(monthly.nth_occurrence(start, 3), Some(date(2023, 4, 30)));
+    assert_eq!(monthly.nth_occurrence(start, 12), Some(date(2024, 1, 31)));
+    assert_eq!(monthly.nth_occurrence(start, 13), Some(date(2024, 2, 29)));
+    assert_eq!(monthly.period_end(start), Some(date(2023, 2, 28)));
+
+    let leap = date(2024, 1, 31);
+    assert_eq!(monthly.nth_occurrence(leap, 1), Some(date(2024, 2, 29)));
+    assert_eq!(
+        Recurrent::Months(3).nth_occurrence(date(2023, 11, 30), 1),
+        Some(date(2024, 2, 29))
+    );
+    assert_eq!(
+        monthly
+            .occurrences_between(start, date(2023, 6, 1))
+            .collect::<Vec<_>>(),
+        vec![
+            date(2023, 1, 31),
+            date(2023, 2, 28),
+            date(2023, 3, 31),
+            date(2023, 4, 30),
+            date(2023, 5, 31)
+        ]
+    );
+}
+
+#[test]
+fn leap_day_is_clamped() {
+    let yearly = Recurrent::Years(1);
+    let start = date(2024, 2, 29);
+    assert_eq!(yearly.nth_occurrence(start, 1), Some(date(2025, 2, 28)));
+    assert_eq!(yearly.nth_occurrence(start, 3), Some(date(2027, 2, 28)));
+    assert_eq!(yearly.nth_occurrence(start, 4), Some(date(2028, 2, 29)));
+    assert_eq!(
+        Recurrent::Months(12).nth_occurrence(start, 1),
+        Some(date(2025, 2, 28))
+    );
+}
+
+#[test]
+fn next_payment_after_month_end() {
+    let monthly = Recurrent::Months(1);
+    let start = date(2023, 1, 31);
+    assert_eq!(
+        monthly.next_after(start, Some(date(2023, 2, 28))),
+        Some(date(2023, 3, 31))
+    );
+    assert_eq!(
+        monthly.next_after(start, Some(date(2023, 2, 27))),
+        Some(date(2023, 2, 28))
+    );
+    assert_eq!(
+        monthly.next_after(start, Some(date(2023, 3, 1))),
+        Some(date(2023, 3, 31))
+    );
+}
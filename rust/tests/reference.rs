@@ -0,0 +1,153 @@
+// LNP/BP universal invoice library implementing LNPBP-38 standard
+// Written in 2021 by
+//     Dr. Maxim Orlovsky <orlovsky@pandoracore.com>
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the MIT License
+// along with this software.
+// If not, see <https://opensource.org/licenses/MIT>.
+
+mod common;
+
+use std::str::FromStr;
+
+use amplify::Wrapper;
+use bitcoin::hashes::hex::ToHex;
+use bitcoin::hashes::Hash;
+use bitcoin::secp256k1::{self, Scalar, SECP256K1};
+use common::{invoice, keypair, with_records, RawInvoice};
+use invoice::{
+    BuilderError, Invoice, InvoiceBuilder, InvoiceInconsistency,
+    ReferenceError, MAX_REFERENCE_LEN,
+};
+use lnpbp::bech32::{Blob, ToBech32String};
+
+fn reference(data: &[u8]) -> Blob {
+    Blob::from_inner(data.to_vec())
+}
+
+#[test]
+fn reference_tweak_vector() {
+    let sk = secp256k1::SecretKey::from_slice(&[
+        0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
+        0, 0, 0, 0, 0, 0, 0, 1,
+    ])
+    .unwrap();
+    let pubkey = sk.public_key(SECP256K1);
+
+    let mut invoice = invoice();
+    assert_eq!(invoice.reference_tweak(&pubkey), None);
+    assert_eq!(invoice.reference_tweaked_key(&pubkey), Ok(None));
+
+    invoice.set_reference(reference(b"order-42")).unwrap();
+    let tweak = invoice.reference_tweak(&pubkey).unwrap();
+    assert_eq!(
+        tweak.to_hex(),
+        "5de801458a65e9f7474a23bb00e1b249cbf323511be7656e3401ca09d1d07dde"
+    );
+
+    let tweaked = invoice.reference_tweaked_key(&pubkey).unwrap().unwrap();
+    assert_eq!(
+        tweaked.to_string(),
+        "02a75d6454a995d7d3bede18dad2346665aa34e7213d33679278a5516629102566"
+    );
+    // The merchant spends the output with the private key tweaked by the
+    // same value
+    let tweak = Scalar::from_be_bytes(tweak.into_inner()).unwrap();
+    let tweaked_sk = sk.add_tweak(&tweak).unwrap();
+    assert_eq!(tweaked_sk.public_key(SECP256K1), tweaked);
+}
+
+#[test]
+fn reference_tweak_commits_to_key_and_reference() {
+    let pubkey = keypair(1).public_key();
+    let other_pubkey = keypair(2).public_key();
+
+    let mut invoice = invoice();
+    invoice.set_reference(reference(b"order-42")).unwrap();
+    let tweak = invoice.reference_tweak(&pubkey);
+    assert_ne!(invoice.reference_tweak(&other_pubkey), tweak);
+
+    invoice.set_reference(reference(b"order-43")).unwrap();
+    assert_ne!(invoice.reference_tweak(&pubkey), tweak);
+}
+
+#[test]
+fn reference_length() {
+    let mut invoice = invoice();
+    assert_eq!(
+        invoice.set_reference(reference(&[7; MAX_REFERENCE_LEN])),
+        Ok(true)
+    );
+    assert_eq!(
+        invoice.set_reference(reference(&[7; MAX_REFERENCE_LEN])),
+        Ok(false)
+    );
+    assert_eq!(
+        invoice.set_reference(reference(&[7; MAX_REFERENCE_LEN + 1])),
+        Err(ReferenceError::TooLong(MAX_REFERENCE_LEN + 1))
+    );
+    assert_eq!(
+        invoice.reference(),
+        &Some(reference(&[7; MAX_REFERENCE_LEN]))
+    );
+
+    let err = InvoiceBuilder::new(common::invoice().beneficiary().clone())
+        .reference(reference(&[7; MAX_REFERENCE_LEN + 1]))
+        .finish()
+        .unwrap_err();
+    assert_eq!(
+        err,
+        BuilderError::Inconsistency(InvoiceInconsistency::Reference(
+            ReferenceError::TooLong(MAX_REFERENCE_LEN + 1)
+        ))
+    );
+
+    let mut record = vec![MAX_REFERENCE_LEN as u8 + 1, 0];
+    record.extend([7; MAX_REFERENCE_LEN + 1]);
+    let data = with_records(&common::invoice(), &[(0x1d, &record)]);
+    let s = RawInvoice(data).to_bech32_string();
+    let err = Invoice::from_str(&s).unwrap_err().to_string();
+    assert!(err.contains("exceeding the limit of 32 bytes"), "{}", err);
+}
+
+#[test]
+fn reference_round_trip() {
+    for data in [&[][..], b"order-42", &[0xff; MAX_REFERENCE_LEN]] {
+        let mut invoice = invoice();
+        invoice.set_reference(reference(data)).unwrap();
+
+        let decoded = Invoice::from_str(&invoice.to_string()).unwrap();
+        assert_eq!(decoded.reference(), &Some(reference(data)));
+        assert_eq!(decoded, invoice);
+
+        #[cfg(feature = "serde")]
+        {
+            let json = serde_json::to_value(&invoice).unwrap();
+            assert_eq!(json["reference"], data.to_hex());
+            let decoded: Invoice = serde_json::from_value(json).unwrap();
+            assert_eq!(decoded, invoice);
+        }
+    }
+}
+
+#[test]
+fn reference_commits_to_id() {
+    let mut invoice = invoice();
+    let id = invoice.invoice_id();
+
+    invoice.set_reference(reference(b"order-42")).unwrap();
+    let order_id = invoice.invoice_id();
+    assert_ne!(order_id, id);
+
+    invoice.set_reference(reference(b"order-43")).unwrap();
+    assert_ne!(invoice.invoice_id(), order_id);
+
+    assert!(invoice.remove_reference());
+    assert!(!invoice.remove_reference());
+    assert_eq!(invoice.invoice_id(), id);
+}
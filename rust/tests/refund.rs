@@ -0,0 +1,96 @@
+// LNP/BP universal invoice library implementing LNPBP-38 standard
+// Written in 2021 by
+//     Dr. Maxim Orlovsky <orlovsky@pandoracore.com>
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the MIT License
+// along with this software.
+// If not, see <https://opensource.org/licenses/MIT>.
+
+mod common;
+
+use std::str::FromStr;
+
+use bitcoin::Address;
+use common::{invoice, keypair};
+use invoice::{Beneficiary, Invoice, SignatureError, SignerRole};
+
+fn refund() -> Beneficiary {
+    Beneficiary::Address(
+        Address::from_str("bc1qar0srrr7xfkvy5l643lydnw9re59gtzzwf5mdq")
+            .unwrap(),
+    )
+}
+
+/// Invoice signed by the merchant and co-signed by the platform
+fn signed() -> Invoice {
+    let mut invoice = invoice();
+    invoice.sign(&keypair(1)).unwrap();
+    invoice.cosign(&keypair(2), SignerRole::Platform).unwrap();
+    assert_eq!(invoice.signatures().len(), 2);
+    invoice
+}
+
+#[test]
+fn set_refund_clears_signatures() {
+    let mut invoice = signed();
+    assert!(invoice.set_refund(refund()));
+    assert_eq!(invoice.refund(), &Some(refund()));
+    assert_eq!(invoice.signature(), None);
+    assert!(invoice.cosignatures().is_empty());
+    assert_eq!(invoice.verify_signature(), Err(SignatureError::NoSignature));
+
+    // Setting the same refund beneficiary keeps the signatures
+    invoice.sign(&keypair(1)).unwrap();
+    assert!(!invoice.set_refund(refund()));
+    assert_eq!(invoice.verify_signature(), Ok(()));
+}
+
+#[test]
+fn remove_refund_clears_signatures() {
+    // Removing absent refund keeps the signatures
+    let mut invoice = signed();
+    assert!(!invoice.remove_refund());
+    assert_eq!(invoice.signatures().len(), 2);
+
+    invoice.set_refund(refund());
+    invoice.sign(&keypair(1)).unwrap();
+    invoice.cosign(&keypair(2), SignerRole::Platform).unwrap();
+    assert!(invoice.remove_refund());
+    assert_eq!(invoice.refund(), &None);
+    assert_eq!(invoice.signature(), None);
+    assert!(invoice.cosignatures().is_empty());
+}
+
+#[test]
+fn signature_commits_to_refund() {
+    // Payer adding refund to the signed invoice invalidates the merchant
+    // signature even if it is re-attached
+    let mut invoice = invoice();
+    invoice.sign(&keypair(1)).unwrap();
+    let sig = invoice.signature().unwrap();
+    invoice.set_refund(refund());
+    invoice.set_signature(sig.pubkey, sig.signature);
+    assert_eq!(
+        invoice.verify_signature(),
+        Err(SignatureError::InvalidSignature)
+    );
+
+    // Recommended flow: merchant signs the invoice with the refund
+    // beneficiary supplied by the payer, and the payer co-signs it
+    let mut invoice = common::invoice();
+    invoice.set_refund(refund());
+    invoice.sign(&keypair(1)).unwrap();
+    invoice.cosign(&keypair(3), SignerRole::Payer).unwrap();
+    let results = invoice.verify_signatures();
+    assert_eq!(results.len(), 2);
+    assert!(results.into_iter().all(|(_, _, result)| result.is_ok()));
+
+    let decoded = Invoice::from_str(&invoice.to_string()).unwrap();
+    assert_eq!(decoded.refund(), &Some(refund()));
+    assert_eq!(decoded.verify_signature(), Ok(()));
+}
@@ -0,0 +1,211 @@
+// LNP/BP universal invoice library implementing LNPBP-38 standard
+// Written in 2021 by
+//     Dr. Maxim Orlovsky <orlovsky@pandoracore.com>
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the MIT License
+// along with this software.
+// If not, see <https://opensource.org/licenses/MIT>.
+
+mod common;
+
+use std::str::FromStr;
+
+use amplify::Wrapper;
+use bitcoin::Address;
+use chrono::{TimeZone, Utc};
+use common::{invoice, keypair};
+use invoice::{
+    Beneficiary, Invoice, InvoiceReport, OnchainPolicy, Recurrent,
+    SignatureStatus,
+};
+use lnpbp::bech32::{Blob, ToBech32String};
+
+#[test]
+fn plain_summary() {
+    assert_eq!(
+        format!("{:#}", invoice()),
+        "\
+Amount:       1000 (native chain asset)
+Beneficiary:  address bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t4
+Expiry:       never
+Signature:    absent"
+    );
+}
+
+#[test]
+fn detailed_summary() {
+    // Expiry in the past keeps the snapshot independent of the current time
+    let mut invoice = invoice();
+    invoice.set_expiry(Utc.timestamp_opt(1_600_000_000, 0).unwrap());
+    invoice.set_merchant("ACME Corp".to_owned());
+    invoice.set_purpose("Order #42".to_owned());
+    invoice.add_alt_beneficiary(Beneficiary::Address(
+        Address::from_str("bc1qar0srrr7xfkvy5l643lydnw9re59gtzzwf5mdq")
+            .unwrap(),
+    ));
+    invoice.sign(&keypair(1)).unwrap();
+    assert_eq!(
+        format!("{:#}", invoice),
+        "\
+Amount:       1000 (native chain asset)
+Beneficiary:  address bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t4
+Expiry:       expired
+Merchant:     ACME Corp
+Purpose:      Order #42
+Alternatives: 1
+Signature:    valid"
+    );
+}
+
+#[test]
+#[cfg(feature = "rgb")]
+fn endpoints_summary() {
+    use invoice::ConsignmentEndpoint;
+
+    let mut invoice = invoice();
+    invoice
+        .set_consignment_endpoints(
+            [
+                "rgbhttpjsonrpc:https://proxy.example.com/rpc",
+                "rgbws:wss://proxy.example.com/ws",
+            ]
+            .iter()
+            .map(|s| ConsignmentEndpoint::from_str(s).unwrap()),
+        )
+        .unwrap();
+    assert_eq!(
+        format!("{:#}", invoice),
+        "\
+Amount:       1000 (native chain asset)
+Beneficiary:  address bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t4
+Expiry:       never
+Endpoints:    2
+Signature:    absent"
+    );
+}
+
+#[test]
+fn default_display_is_bech32() {
+    let invoice = invoice();
+    assert_eq!(invoice.to_string(), invoice.to_bech32_string());
+    assert_eq!(Invoice::from_str(&invoice.to_string()).unwrap(), invoice);
+}
+
+fn report(invoice: &Invoice) -> InvoiceReport {
+    InvoiceReport::new(invoice, Utc.timestamp_opt(1_600_000_000, 0).unwrap())
+}
+
+#[test]
+fn plain_report() {
+    let report = report(&invoice());
+    assert_eq!(report.signature, SignatureStatus::Absent);
+    assert_eq!(
+        report.to_string(),
+        "\
+Beneficiary:  address bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t4
+Amount:       1000 (native chain asset)
+Expiry:       never
+Recurrence:   non-recurrent
+Signature:    absent"
+    );
+}
+
+#[test]
+fn signed_report() {
+    let mut invoice = invoice();
+    invoice.set_merchant("ACME Corp".to_owned());
+    invoice.set_purpose("Order #42".to_owned());
+    invoice
+        .set_reference(Blob::from_inner(vec![0x42; 4]))
+        .unwrap();
+    invoice.set_recurrent(Recurrent::Months(1));
+    invoice.set_recurrence_limit(12);
+    invoice.add_alt_beneficiary(Beneficiary::Address(
+        Address::from_str("bc1qar0srrr7xfkvy5l643lydnw9re59gtzzwf5mdq")
+            .unwrap(),
+    ));
+    invoice.sign(&keypair(1)).unwrap();
+
+    let report = report(&invoice);
+    assert_eq!(report.signature, SignatureStatus::Valid);
+    assert_eq!(
+        report.to_string(),
+        "\
+Beneficiary:  address bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t4
+Alternative:  address bc1qar0srrr7xfkvy5l643lydnw9re59gtzzwf5mdq
+Amount:       1000 (native chain asset)
+Expiry:       never
+Recurrence:   each 1 months, at most 12 times
+Merchant:     ACME Corp
+Purpose:      Order #42
+Reference:    42424242
+Signature:    valid"
+    );
+
+    // Signature re-attached to the modified invoice
+    let sig = invoice.signature().unwrap();
+    invoice.set_purpose("Order #43".to_owned());
+    invoice.set_signature(sig.pubkey, sig.signature);
+    assert_eq!(self::report(&invoice).signature, SignatureStatus::Invalid);
+}
+
+#[test]
+fn onchain_policy_report() {
+    let mut invoice = invoice();
+    invoice.set_onchain_policy(OnchainPolicy {
+        min_feerate_sat_vb: Some(5),
+        rbf_required: Some(false),
+        min_confirmations: Some(3),
+    });
+    assert_eq!(
+        report(&invoice).to_string(),
+        "\
+Beneficiary:  address bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t4
+Amount:       1000 (native chain asset)
+Expiry:       never
+Recurrence:   non-recurrent
+On-chain:     min feerate 5 sat/vB, RBF not allowed, 3 confirmations
+Signature:    absent"
+    );
+}
+
+#[test]
+fn expiry_report() {
+    let mut invoice = invoice();
+    let expiry = Utc.timestamp_opt(1_600_000_000 + 8000, 0).unwrap();
+    invoice.set_expiry(expiry);
+    let report = report(&invoice);
+    assert_eq!(report.payable_until, Some(expiry));
+    assert_eq!(report.seconds_left, Some(8000));
+    assert!(report.to_string().contains(" (expires in 2h 13m)\n"));
+
+    invoice.set_expiry(Utc.timestamp_opt(1_500_000_000, 0).unwrap());
+    let report = self::report(&invoice);
+    assert_eq!(report.seconds_left, Some(0));
+    assert!(report.to_string().contains(" (expired)\n"));
+}
+
+#[test]
+#[cfg(feature = "serde")]
+fn json_report() {
+    let mut invoice = invoice();
+    invoice.set_purpose("Order #42".to_owned());
+    invoice.sign(&keypair(1)).unwrap();
+    let json = serde_json::to_value(report(&invoice)).unwrap();
+    assert_eq!(
+        json["beneficiaries"],
+        serde_json::json!([{
+            "kind": "address",
+            "value": "bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t4"
+        }])
+    );
+    assert_eq!(json["amount"], "1000");
+    assert_eq!(json["purpose"], "Order #42");
+    assert_eq!(json["payableUntil"], serde_json::Value::Null);
+    assert_eq!(json["signature"], "valid");
+}
@@ -0,0 +1,365 @@
+// LNP/BP universal invoice library implementing LNPBP-38 standard
+// Written in 2021 by
+//     Dr. Maxim Orlovsky <orlovsky@pandoracore.com>
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the MIT License
+// along with this software.
+// If not, see <https://opensource.org/licenses/MIT>.
+
+#![cfg(feature = "rgb")]
+
+mod common;
+
+use std::str::FromStr;
+
+use bitcoin::hashes::Hash;
+use bp::seals::txout::blind::ConcealedSeal;
+use common::invoice;
+use invoice::{
+    asset_id_to_contract_id, contract_id_to_asset_id, AssetClass, Beneficiary,
+    ConsignmentEndpoint, Invoice, Network, NoConsignmentEndpoint,
+    WitnessVoutSeal,
+};
+use lnpbp::chain::{AssetId, Chain};
+use strict_encoding::StrictDecode;
+
+fn seal() -> ConcealedSeal {
+    ConcealedSeal::strict_deserialize([7u8; 32]).unwrap()
+}
+
+fn contract_id() -> rgb::ContractId {
+    asset_id_to_contract_id(AssetId::from_inner([1u8; 32]))
+}
+
+fn endpoint(no: usize) -> ConsignmentEndpoint {
+    ConsignmentEndpoint::from_str(&format!(
+        "rgbhttpjsonrpc:https://proxy{}.example.com/json-rpc",
+        no
+    ))
+    .unwrap()
+}
+
+fn rgb_invoice() -> Invoice {
+    Invoice::new_rgb(
+        contract_id(),
+        seal(),
+        Some(100),
+        vec![endpoint(1), endpoint(2)],
+    )
+    .unwrap()
+}
+
+#[test]
+fn new_rgb_round_trip() {
+    let invoice = rgb_invoice();
+    assert_eq!(invoice.beneficiary(), &Beneficiary::BlindUtxo(seal()));
+    assert_eq!(invoice.amount().to_string(), "100");
+    assert_eq!(
+        invoice.asset(),
+        &Some(contract_id_to_asset_id(contract_id()))
+    );
+    assert_eq!(invoice.consignment_endpoints(), &[endpoint(1), endpoint(2)]);
+    assert!(invoice.is_rgb());
+    assert_eq!(invoice.rgb_asset(), Some(contract_id()));
+    assert_eq!(invoice.rgb_seal(), Some(seal()));
+
+    let decoded = Invoice::from_str(&invoice.to_string()).unwrap();
+    assert_eq!(decoded, invoice);
+    assert!(decoded.is_rgb());
+    assert_eq!(decoded.rgb_asset(), Some(contract_id()));
+    assert_eq!(decoded.rgb_seal(), Some(seal()));
+}
+
+#[test]
+fn new_rgb_requires_endpoint() {
+    assert_eq!(
+        Invoice::new_rgb(contract_id(), seal(), Some(100), vec![]),
+        Err(NoConsignmentEndpoint)
+    );
+}
+
+#[test]
+fn is_rgb_native() {
+    let invoice = invoice();
+    assert!(!invoice.is_rgb());
+    assert_eq!(invoice.rgb_asset(), None);
+    assert_eq!(invoice.rgb_seal(), None);
+
+    // Blinded UTXO paid in bitcoins is not an RGB invoice
+    let mut invoice = rgb_invoice();
+    invoice.set_asset(Chain::Mainnet.native_asset());
+    assert!(!invoice.is_rgb());
+    assert_eq!(invoice.rgb_seal(), None);
+}
+
+const TESTNET_ADDRESS: &str = "tb1qw508d6qejxtdg4y5r3zarvary0c5xw7kxpjzsx";
+
+#[test]
+fn is_rgb_regression() {
+    // Mainnet invoice without asset
+    assert!(!invoice().is_rgb());
+
+    // Testnet invoice with testnet native asset
+    let address = bitcoin::Address::from_str(TESTNET_ADDRESS).unwrap();
+    let testnet = Invoice::with_address(address, Some(1000));
+    assert_eq!(testnet.asset(), &Some(Chain::Testnet3.native_asset()));
+    assert!(!testnet.is_rgb());
+    assert_eq!(testnet.rgb_asset(), None);
+
+    // RGB contract invoice
+    let mut rgb = testnet;
+    rgb.set_asset(contract_id_to_asset_id(contract_id()));
+    assert!(rgb.is_rgb());
+    assert_eq!(rgb.rgb_asset(), Some(contract_id()));
+}
+
+#[test]
+fn rgb_asset() {
+    let mut invoice = invoice();
+    for chain in Chain::all_standard() {
+        invoice.set_asset(chain.native_asset());
+        assert_eq!(invoice.rgb_asset(), None, "{}", chain);
+    }
+    invoice.set_asset(Chain::from(bitcoin::Network::Regtest).native_asset());
+    assert_eq!(invoice.rgb_asset(), None);
+
+    invoice.set_asset(contract_id_to_asset_id(contract_id()));
+    assert_eq!(invoice.rgb_asset(), Some(contract_id()));
+    assert_eq!(
+        contract_id_to_asset_id(invoice.rgb_asset().unwrap()),
+        invoice.asset().unwrap()
+    );
+
+    // Non-native assets on Liquid are Liquid assets and not RGB contracts
+    #[cfg(feature = "elements")]
+    {
+        invoice.set_network(Network::LiquidV1);
+        assert_eq!(invoice.rgb_asset(), None);
+        assert!(!invoice.is_rgb());
+    }
+}
+
+const REGTEST_ADDRESS: &str = "bcrt1qw508d6qejxtdg4y5r3zarvary0c5xw7kygt080";
+
+#[test]
+fn classify_asset_chains() {
+    let regtest = Chain::from(bitcoin::Network::Regtest);
+    let rgb = contract_id_to_asset_id(contract_id());
+    let beneficiary = invoice().beneficiary().clone();
+    for (asset, chain, class) in [
+        (None, Some(Chain::Mainnet), AssetClass::Native),
+        (None, Some(Chain::Testnet3), AssetClass::InvalidNativeChain),
+        (None, Some(regtest.clone()), AssetClass::InvalidNativeChain),
+        (None, None, AssetClass::InvalidNativeChain),
+        (
+            Some(Chain::Testnet3.native_asset()),
+            Some(Chain::Testnet3),
+            AssetClass::Native,
+        ),
+        (
+            Some(Chain::Testnet3.native_asset()),
+            Some(Chain::Mainnet),
+            AssetClass::InvalidNativeChain,
+        ),
+        (
+            Some(Chain::Testnet3.native_asset()),
+            None,
+            AssetClass::InvalidNativeChain,
+        ),
+        (
+            Some(Chain::Signet.native_asset()),
+            Some(Chain::Signet),
+            AssetClass::Native,
+        ),
+        (
+            Some(Chain::LiquidV1.native_asset()),
+            Some(Chain::LiquidV1),
+            AssetClass::Native,
+        ),
+        (
+            Some(Chain::LiquidV1.native_asset()),
+            Some(Chain::Mainnet),
+            AssetClass::InvalidNativeChain,
+        ),
+        (
+            Some(regtest.native_asset()),
+            Some(regtest.clone()),
+            AssetClass::Native,
+        ),
+        (
+            Some(regtest.native_asset()),
+            Some(Chain::Testnet3),
+            AssetClass::InvalidNativeChain,
+        ),
+        (
+            Some(regtest.native_asset()),
+            None,
+            AssetClass::InvalidNativeChain,
+        ),
+        (
+            Some(rgb),
+            Some(Chain::Mainnet),
+            AssetClass::Rgb(contract_id()),
+        ),
+        (
+            Some(rgb),
+            Some(regtest.clone()),
+            AssetClass::Rgb(contract_id()),
+        ),
+        (Some(rgb), None, AssetClass::Rgb(contract_id())),
+    ] {
+        let invoice = Invoice::new(beneficiary.clone(), Some(1000), asset);
+        assert_eq!(
+            invoice.classify_asset(chain.clone()),
+            class,
+            "{:?} on {:?}",
+            asset,
+            chain
+        );
+    }
+}
+
+#[test]
+fn classify_regtest_invoice() {
+    let address = bitcoin::Address::from_str(REGTEST_ADDRESS).unwrap();
+    let regtest = Chain::from(bitcoin::Network::Regtest);
+    let invoice = Invoice::with_address(address.clone(), Some(1000));
+    assert_eq!(invoice.network(), &Some(Network::Regtest));
+    assert_eq!(invoice.asset(), &Some(regtest.native_asset()));
+    assert_eq!(invoice.classify_asset(Some(regtest)), AssetClass::Native);
+    assert_eq!(invoice.classify_asset_auto(), AssetClass::Native);
+    assert_eq!(invoice.rgb_asset(), None);
+    assert!(!invoice.is_rgb());
+
+    // Network is inferred from the regtest address
+    let invoice = Invoice::new(
+        Beneficiary::Address(address),
+        Some(1000),
+        *invoice.asset(),
+    );
+    assert_eq!(invoice.network(), &None);
+    assert_eq!(invoice.classify_asset_auto(), AssetClass::Native);
+}
+
+#[test]
+fn classify_asset_auto() {
+    let address = bitcoin::Address::from_str(TESTNET_ADDRESS).unwrap();
+    let rgb = contract_id_to_asset_id(contract_id());
+
+    // Chain inferred from the address network
+    let beneficiary = Beneficiary::Address(address.clone());
+    let testnet_native = Some(Chain::Testnet3.native_asset());
+    let invoice = Invoice::new(beneficiary.clone(), None, testnet_native);
+    assert_eq!(invoice.classify_asset_auto(), AssetClass::Native);
+    let invoice = Invoice::new(
+        beneficiary.clone(),
+        None,
+        Some(Chain::Mainnet.native_asset()),
+    );
+    assert_eq!(
+        invoice.classify_asset_auto(),
+        AssetClass::InvalidNativeChain
+    );
+    let invoice = Invoice::new(beneficiary, None, None);
+    assert_eq!(
+        invoice.classify_asset_auto(),
+        AssetClass::InvalidNativeChain
+    );
+
+    // Explicit network field
+    let mut invoice = Invoice::with_address(address, None);
+    assert_eq!(invoice.classify_asset_auto(), AssetClass::Native);
+    invoice.set_network(Network::Mainnet);
+    assert_eq!(
+        invoice.classify_asset_auto(),
+        AssetClass::InvalidNativeChain
+    );
+    invoice.set_asset(Chain::Mainnet.native_asset());
+    assert_eq!(
+        invoice.classify_asset_auto(),
+        AssetClass::InvalidNativeChain
+    );
+    invoice.set_asset(rgb);
+    assert_eq!(
+        invoice.classify_asset_auto(),
+        AssetClass::Rgb(contract_id())
+    );
+
+    // Defaults to mainnet without network and address
+    let mut invoice = Invoice::new(Beneficiary::BlindUtxo(seal()), None, None);
+    assert_eq!(invoice.classify_asset_auto(), AssetClass::Native);
+    invoice.set_asset(Chain::Testnet3.native_asset());
+    assert_eq!(
+        invoice.classify_asset_auto(),
+        AssetClass::InvalidNativeChain
+    );
+    invoice.set_network(Network::Testnet3);
+    assert_eq!(invoice.classify_asset_auto(), AssetClass::Native);
+
+    // RGB assets are classified as such on any chain
+    for network in [Network::Mainnet, Network::Testnet3, Network::Regtest] {
+        invoice.set_network(network);
+        invoice.set_asset(rgb);
+        assert_eq!(
+            invoice.classify_asset_auto(),
+            AssetClass::Rgb(contract_id())
+        );
+    }
+}
+
+#[test]
+fn rgb_seal() {
+    let witness = Beneficiary::WitnessVoutSeal(
+        WitnessVoutSeal::from_str("wvout:tapret1st:42").unwrap(),
+    );
+    let mut rgb = rgb_invoice();
+    assert_eq!(rgb.rgb_seal(), Some(seal()));
+    rgb.set_beneficiary(witness);
+    assert!(rgb.is_rgb());
+    assert_eq!(rgb.rgb_seal(), None);
+
+    let mut invoice = invoice();
+    invoice.set_beneficiary(Beneficiary::BlindUtxo(seal()));
+    assert_eq!(invoice.rgb_seal(), None);
+    invoice.set_asset(contract_id_to_asset_id(contract_id()));
+    assert_eq!(invoice.rgb_seal(), Some(seal()));
+}
+
+#[test]
+fn rgb_beneficiaries() {
+    let witness = Beneficiary::WitnessVoutSeal(
+        WitnessVoutSeal::from_str("wvout:tapret1st:42").unwrap(),
+    );
+    let blind = Beneficiary::BlindUtxo(seal());
+
+    assert_eq!(invoice().rgb_beneficiaries().count(), 0);
+
+    let mut invoice = rgb_invoice();
+    assert_eq!(
+        invoice.rgb_beneficiaries().collect::<Vec<_>>(),
+        vec![&blind]
+    );
+    invoice.add_alt_beneficiary(common::invoice().beneficiary().clone());
+    invoice.add_alt_beneficiary(witness.clone());
+    assert_eq!(
+        invoice.rgb_beneficiaries().collect::<Vec<_>>(),
+        vec![&blind, &witness]
+    );
+    assert_eq!(
+        invoice.rgb_beneficiaries().rev().collect::<Vec<_>>(),
+        vec![&witness, &blind]
+    );
+
+    // Primary beneficiary may be a non-RGB one
+    let mut invoice = common::invoice();
+    invoice.add_alt_beneficiary(witness.clone());
+    assert_eq!(
+        invoice.rgb_beneficiaries().collect::<Vec<_>>(),
+        vec![&witness]
+    );
+}
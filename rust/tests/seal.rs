@@ -0,0 +1,111 @@
+// LNP/BP universal invoice library implementing LNPBP-38 standard
+// Written in 2021 by
+//     Dr. Maxim Orlovsky <orlovsky@pandoracore.com>
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the MIT License
+// along with this software.
+// If not, see <https://opensource.org/licenses/MIT>.
+
+use std::str::FromStr;
+
+use bitcoin::hashes::hex::ToHex;
+use bitcoin::OutPoint;
+use bp::seals::txout::blind::RevealedSeal;
+use bp::seals::txout::CloseMethod;
+use invoice::seal::{self, BlindedUtxo};
+use invoice::Beneficiary;
+use rand::rngs::mock::StepRng;
+use strict_encoding::{StrictDecode, StrictEncode};
+
+/// Blinding factor returned by the deterministic random number generator
+const BLINDING: u64 = 0x0102_0304_0506_0708;
+
+fn outpoint() -> OutPoint {
+    OutPoint::from_str(
+        "4a5e1e4baab89f3a32518a88c31bc87f618f76673e2cc77ab2127b7afdeda33b:1",
+    )
+    .unwrap()
+}
+
+fn blinded() -> BlindedUtxo {
+    seal::blind(outpoint(), &mut StepRng::new(BLINDING, 1))
+}
+
+#[test]
+fn deterministic_blinding() {
+    let blinded = blinded();
+    assert_eq!(blinded, self::blinded());
+    assert_eq!(blinded.blinding, BLINDING);
+    assert_eq!(
+        blinded.concealed.to_hex(),
+        "7d2077cc4c63275b3c43160024963441e5cc345d9c2b1e39699eee8a202db67e"
+    );
+    assert_eq!(
+        blinded.revealed,
+        RevealedSeal {
+            method: CloseMethod::TapretFirst,
+            txid: Some(outpoint().txid),
+            vout: 1,
+            blinding: blinded.blinding
+        }
+    );
+    assert_eq!(blinded.concealed, blinded.revealed.to_concealed_seal());
+    assert_eq!(
+        blinded.beneficiary(),
+        Beneficiary::BlindUtxo(blinded.concealed)
+    );
+
+    let other = seal::blind(outpoint(), &mut StepRng::new(BLINDING + 1, 1));
+    assert_ne!(other.blinding, blinded.blinding);
+    assert_ne!(other.concealed, blinded.concealed);
+}
+
+#[test]
+fn verify_blinding() {
+    let blinded = blinded();
+    assert!(seal::verify(
+        blinded.concealed,
+        outpoint(),
+        blinded.blinding
+    ));
+    assert!(!seal::verify(
+        blinded.concealed,
+        outpoint(),
+        blinded.blinding.wrapping_add(1)
+    ));
+    let mut other = outpoint();
+    other.vout = 0;
+    assert!(!seal::verify(blinded.concealed, other, blinded.blinding));
+
+    // Seals closed with OP_RETURN commitments are verified as well
+    let opret = RevealedSeal {
+        method: CloseMethod::OpretFirst,
+        ..blinded.revealed
+    };
+    assert!(seal::verify(
+        opret.to_concealed_seal(),
+        outpoint(),
+        blinded.blinding
+    ));
+}
+
+#[test]
+fn encoding_round_trip() {
+    let blinded = blinded();
+    let data = blinded.strict_serialize().unwrap();
+    assert_eq!(BlindedUtxo::strict_deserialize(&data).unwrap(), blinded);
+
+    #[cfg(feature = "serde")]
+    {
+        let json = serde_json::to_string(&blinded).unwrap();
+        assert_eq!(
+            serde_json::from_str::<BlindedUtxo>(&json).unwrap(),
+            blinded
+        );
+    }
+}
@@ -0,0 +1,328 @@
+// LNP/BP universal invoice library implementing LNPBP-38 standard
+// Written in 2021 by
+//     Dr. Maxim Orlovsky <orlovsky@pandoracore.com>
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the MIT License
+// along with this software.
+// If not, see <https://opensource.org/licenses/MIT>.
+
+mod common;
+
+use bitcoin::hashes::hex::ToHex;
+use bitcoin::hashes::{sha256, Hash};
+use bitcoin::secp256k1::{KeyPair, Message, SECP256K1};
+use commit_verify::merkle::{merklize, MerkleNode};
+use common::{invoice, keypair, split_records, with_records};
+use invoice::{
+    ExpiryTerm, Invoice, MerchantInfo, SignatureError, SignatureScheme,
+    SignerRole, INVOICE_MERKLE_PREFIX, SIGNATURE_SCHEME_TLV_TYPE,
+};
+use strict_encoding::{StrictDecode, StrictEncode};
+
+#[test]
+fn sign_and_verify() {
+    let mut invoice = invoice();
+    assert_eq!(invoice.verify_signature(), Err(SignatureError::NoSignature));
+
+    let merchant = keypair(1);
+    invoice.sign(&merchant).unwrap();
+    let sig = invoice.signature().unwrap();
+    assert_eq!(sig.scheme, SignatureScheme::Merkle);
+    assert_eq!(sig.pubkey, merchant.public_key());
+    invoice.verify_signature().unwrap();
+    invoice
+        .verify_signature_with_key(merchant.public_key())
+        .unwrap();
+    assert_eq!(
+        invoice.verify_signature_with_key(keypair(2).public_key()),
+        Err(SignatureError::InvalidSignature)
+    );
+
+    let decoded =
+        Invoice::strict_deserialize(invoice.strict_serialize().unwrap())
+            .unwrap();
+    assert_eq!(decoded, invoice);
+    decoded.verify_signature().unwrap();
+}
+
+#[test]
+fn tampered_invoice_fails_verification() {
+    let mut invoice = invoice();
+    invoice.sign(&keypair(1)).unwrap();
+    let sig = invoice.signature().unwrap();
+    invoice.set_amount(2000.into()).unwrap();
+    assert_eq!(invoice.signature(), None);
+
+    // Re-attaching the old signature to the modified invoice
+    invoice.set_signature(sig.pubkey, sig.signature);
+    assert_eq!(
+        invoice.verify_signature(),
+        Err(SignatureError::InvalidSignature)
+    );
+}
+
+#[test]
+fn signature_hash_excludes_signature() {
+    let mut invoice = invoice();
+    let merkle = invoice.signature_hash();
+    let flat = invoice.signature_hash_with(SignatureScheme::Flat);
+    invoice.sign(&keypair(1)).unwrap();
+    assert_eq!(invoice.signature_hash(), merkle);
+    assert_eq!(invoice.signature_hash_with(SignatureScheme::Flat), flat);
+    invoice.cosign(&keypair(2), SignerRole::Platform).unwrap();
+    assert_eq!(invoice.signature_hash(), merkle);
+    assert_eq!(invoice.signature_hash_with(SignatureScheme::Flat), flat);
+
+    // Re-signing produces the same hash
+    invoice.sign(&keypair(3)).unwrap();
+    assert_eq!(invoice.signature_hash(), merkle);
+}
+
+/// Adds flat-scheme signature record produced by the legacy signers.
+fn sign_flat(invoice: &Invoice) -> Vec<u8> {
+    let keypair = keypair(1);
+    let hash = invoice.signature_hash_with(SignatureScheme::Flat);
+    let msg = Message::from_slice(&hash).unwrap();
+    let signature = SECP256K1.sign_schnorr(&msg, &keypair);
+    let mut record = keypair.public_key().strict_serialize().unwrap();
+    record.extend(signature.strict_serialize().unwrap());
+    assert_eq!(record.len(), 97);
+    with_records(invoice, &[(0x00, &record)])
+}
+
+#[test]
+fn legacy_flat_signature() {
+    let invoice = invoice();
+    let data = sign_flat(&invoice);
+    let decoded = Invoice::strict_deserialize(&data).unwrap();
+    assert_eq!(decoded.signature().unwrap().scheme, SignatureScheme::Flat);
+    decoded.verify_signature().unwrap();
+    // Legacy encoding is preserved
+    assert_eq!(decoded.strict_serialize().unwrap(), data);
+}
+
+#[test]
+fn signature_scheme_is_not_guessed() {
+    let invoice = invoice();
+
+    // Flat signature marked as merkle one
+    let mut decoded = Invoice::strict_deserialize(sign_flat(&invoice)).unwrap();
+    let sig = decoded.signature().unwrap();
+    decoded.set_signature(sig.pubkey, sig.signature);
+    assert_eq!(
+        decoded.verify_signature(),
+        Err(SignatureError::InvalidSignature)
+    );
+
+    // Merkle signature with the scheme record stripped
+    let mut signed = invoice.clone();
+    signed.sign(&keypair(1)).unwrap();
+    let (_, stream) = split_records(&signed.strict_serialize().unwrap());
+    let record = stream.get(&0x00u64.into()).unwrap().to_vec();
+    let data = with_records(&invoice, &[(0x00, &record)]);
+    let decoded = Invoice::strict_deserialize(data).unwrap();
+    assert_eq!(decoded.signature().unwrap().scheme, SignatureScheme::Flat);
+    assert_eq!(
+        decoded.verify_signature(),
+        Err(SignatureError::InvalidSignature)
+    );
+}
+
+#[test]
+fn strict_signature_verification() {
+    let with_identity = |key: Option<KeyPair>| {
+        let mut invoice = invoice();
+        let mut info = MerchantInfo::new("Coffee shop");
+        info.identity_key = key.map(|key| key.public_key());
+        invoice.set_merchant_info(info);
+        invoice
+    };
+
+    assert_eq!(
+        invoice().verify_signature_strict(),
+        Err(SignatureError::NoSignature)
+    );
+    for key in [None, Some(keypair(1))] {
+        let mut invoice = with_identity(key);
+        invoice.sign(&keypair(1)).unwrap();
+        assert_eq!(invoice.verify_signature_strict(), Ok(()));
+
+        let decoded =
+            Invoice::strict_deserialize(sign_flat(&with_identity(key)))
+                .unwrap();
+        assert_eq!(decoded.signature().unwrap().scheme, SignatureScheme::Flat);
+        assert_eq!(decoded.verify_signature_strict(), Ok(()));
+    }
+
+    // Signed with a key different from the merchant identity key
+    let mut invoice = with_identity(Some(keypair(2)));
+    invoice.sign(&keypair(1)).unwrap();
+    assert_eq!(invoice.verify_signature(), Ok(()));
+    assert_eq!(
+        invoice.verify_signature_strict(),
+        Err(SignatureError::MerchantKeyMismatch)
+    );
+    let decoded = Invoice::strict_deserialize(sign_flat(&with_identity(Some(
+        keypair(2),
+    ))))
+    .unwrap();
+    assert_eq!(
+        decoded.verify_signature_strict(),
+        Err(SignatureError::MerchantKeyMismatch)
+    );
+
+    // Tampered invoice signed with the merchant identity key
+    let mut invoice = with_identity(Some(keypair(1)));
+    invoice.sign(&keypair(1)).unwrap();
+    let sig = invoice.signature().unwrap();
+    invoice.set_purpose("tea".to_owned());
+    invoice.set_signature(sig.pubkey, sig.signature);
+    assert_eq!(
+        invoice.verify_signature_strict(),
+        Err(SignatureError::InvalidSignature)
+    );
+}
+
+#[test]
+fn signature_record_encoding() {
+    let mut invoice = invoice();
+    invoice.sign(&keypair(1)).unwrap();
+    let sig = invoice.signature().unwrap();
+    let (_, stream) = split_records(&invoice.strict_serialize().unwrap());
+
+    // Signature record keeps the encoding used before merkle signatures
+    let mut record = sig.pubkey.strict_serialize().unwrap();
+    record.extend(sig.signature.strict_serialize().unwrap());
+    assert_eq!(&stream.get(&0x00u64.into()).unwrap()[..], &record[..]);
+    assert_eq!(
+        &stream.get(&SIGNATURE_SCHEME_TLV_TYPE.into()).unwrap()[..],
+        &[0x01]
+    );
+
+    let unknown = with_records(&invoice, &[(SIGNATURE_SCHEME_TLV_TYPE, &[2])]);
+    assert!(Invoice::strict_deserialize(unknown).is_err());
+}
+
+/// Test invoice with a few TLV records besides the network one.
+fn vector_invoice() -> Invoice {
+    let mut invoice = invoice();
+    invoice.set_purpose("coffee".to_owned());
+    invoice.set_expiry_term(ExpiryTerm::RelativeSeconds(3600));
+    invoice
+}
+
+#[test]
+fn signature_hash_vectors() {
+    let invoice = vector_invoice();
+    let data = invoice.strict_serialize().unwrap();
+    assert_eq!(data.to_hex(), "0001e80300000000000000f9beb4d9001400751e76e8199196d454941c45d1b3a323f1433bd60300070000000000000008000600636f666665650b000000000000000100000c000000000000000400100e0000");
+
+    // Flat scheme is a single SHA256 hash of the serialization
+    let flat = invoice.signature_hash_with(SignatureScheme::Flat);
+    assert_eq!(flat.to_hex(), sha256::Hash::hash(&data).to_hex());
+    assert_eq!(
+        flat.to_hex(),
+        "1fac8f60e02cfe7b4b46245cac925344908dc6e996d44f5e473b74a8a1d18281"
+    );
+
+    // Leaves are SHA256 hashes of the fields and TLV records, each given
+    // with its type
+    let leaves = invoice
+        .signature_leaves()
+        .iter()
+        .map(ToHex::to_hex)
+        .collect::<Vec<_>>();
+    let (_, stream) = split_records(&data);
+    let mut expected = vec![
+        sha256::Hash::hash(&[0x00]).to_hex(),
+        sha256::Hash::hash(&invoice.amount().strict_serialize().unwrap())
+            .to_hex(),
+        sha256::Hash::hash(&invoice.beneficiary().strict_serialize().unwrap())
+            .to_hex(),
+    ];
+    for (ty, value) in &stream {
+        let mut record = ty.strict_serialize().unwrap();
+        record.extend(value.strict_serialize().unwrap());
+        expected.push(sha256::Hash::hash(&record).to_hex());
+    }
+    assert_eq!(leaves, expected);
+    assert_eq!(
+        leaves,
+        vec![
+            // version
+            "6e340b9cffb37a989ca544e6bb780a2c78901d3fb33738768511a30617afa01d",
+            // amount
+            "987b7f1352550612fad9f28f3491ed574f4a41189cb60237d3df5c4a709d4e37",
+            // beneficiary
+            "e61fb0a3c4ad628df526812169932278e5ace71f121ff6b1ab54c63fbaee1cc7",
+            // purpose (0x07)
+            "447bdaa8ed01a11954702bf5634787099feb241b63669995f064e5c73c750ed4",
+            // network (0x0b)
+            "05d80c9112fc8342ddd4c4a22a99c81a940d6b5b4e09edbda0356a788628599d",
+            // relative expiry (0x0c)
+            "2aa1467f80869f2f14123611e041a5476c7a80627a056bcf22093a649bda5a41",
+        ]
+    );
+
+    let root = invoice.signature_hash();
+    assert_eq!(
+        root,
+        merklize(INVOICE_MERKLE_PREFIX, invoice.signature_leaves()).0
+    );
+    assert_eq!(
+        root.to_hex(),
+        "d0e1ac449c09c98906f26281597f8d94d2f8df5a55dfd72c8a52fb69e26bd16b"
+    );
+}
+
+#[test]
+fn signature_vector() {
+    let mut invoice = vector_invoice();
+    let keypair = keypair(1);
+    let msg = Message::from_slice(&invoice.signature_hash()).unwrap();
+    let signature = SECP256K1.sign_schnorr_no_aux_rand(&msg, &keypair);
+    assert_eq!(signature.to_hex(), "c8516d78de0c5b6ce2a41b91f45a32237011d7d7a62dd4b870e6b74de361050f7696d1318794f7c9e251a2f73b4c8255346bead89695c2de727fb8a85672ad36");
+    invoice.set_signature(keypair.public_key(), signature);
+    invoice.verify_signature().unwrap();
+
+    // Signature record is not committed to
+    assert_eq!(invoice.signature_hash(), vector_invoice().signature_hash());
+    assert_eq!(
+        invoice.signature_leaves(),
+        vector_invoice().signature_leaves()
+    );
+}
+
+#[test]
+fn cosignature_hash_vector() {
+    let mut invoice = vector_invoice();
+    let keypair = keypair(1);
+    let msg = Message::from_slice(&invoice.signature_hash()).unwrap();
+    let signature = SECP256K1.sign_schnorr_no_aux_rand(&msg, &keypair);
+    invoice.set_signature(keypair.public_key(), signature);
+
+    // Co-signers commit to the issuer signature record as well
+    let mut leaves = invoice.signature_leaves();
+    let (_, stream) = split_records(&invoice.strict_serialize().unwrap());
+    for (pos, ty) in [(3, 0x00u64), (7, SIGNATURE_SCHEME_TLV_TYPE)] {
+        let mut record = ty.strict_serialize().unwrap();
+        record.extend(
+            stream.get(&ty.into()).unwrap().strict_serialize().unwrap(),
+        );
+        leaves.insert(pos, MerkleNode::hash(&record));
+    }
+    leaves.push(MerkleNode::hash(
+        &SignerRole::Platform.strict_serialize().unwrap(),
+    ));
+    let hash = invoice.cosignature_hash(SignerRole::Platform);
+    assert_eq!(hash, merklize(INVOICE_MERKLE_PREFIX, leaves).0);
+    assert_eq!(
+        hash.to_hex(),
+        "0f8ef0d04691a618ac939973d5f74829ab0ed6225025c654a58a114989e4b2da"
+    );
+}
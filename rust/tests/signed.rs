@@ -0,0 +1,116 @@
+// LNP/BP universal invoice library implementing LNPBP-38 standard
+// Written in 2021 by
+//     Dr. Maxim Orlovsky <orlovsky@pandoracore.com>
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the MIT License
+// along with this software.
+// If not, see <https://opensource.org/licenses/MIT>.
+
+mod common;
+
+use std::convert::TryFrom;
+use std::str::FromStr;
+
+use common::{invoice, keypair};
+use invoice::{Invoice, SignatureError, SignedInvoice, SignedInvoiceError};
+use strict_encoding::{StrictDecode, StrictEncode};
+
+fn signed() -> Invoice {
+    let mut invoice = invoice();
+    invoice.set_purpose("coffee".to_owned());
+    invoice.sign(&keypair(1)).unwrap();
+    invoice
+}
+
+/// Signed invoice which amount was changed after signing
+fn tampered() -> Invoice {
+    let mut invoice = signed();
+    let sig = invoice.signature().unwrap();
+    invoice.set_amount(2000.into()).unwrap();
+    invoice.set_signature(sig.pubkey, sig.signature);
+    invoice
+}
+
+#[test]
+fn sign_and_verify() {
+    let (invoice, err) = common::invoice().into_signed().unwrap_err();
+    assert_eq!(invoice, common::invoice());
+    assert_eq!(err, SignatureError::NoSignature);
+
+    let invoice = signed();
+    let signed = invoice.clone().into_signed().unwrap();
+    assert_eq!(signed.as_invoice(), &invoice);
+    assert_eq!(signed.purpose(), &Some("coffee".to_owned()));
+    assert_eq!(SignedInvoice::try_from(invoice.clone()), Ok(signed.clone()));
+    assert_eq!(Invoice::from(signed.clone()), invoice);
+
+    // Downgrading removes the signature, so the invoice can be modified
+    let mut unsigned = signed.into_unsigned();
+    assert_eq!(unsigned.signature(), None);
+    assert!(unsigned.set_amount(2000.into()).unwrap());
+}
+
+#[test]
+fn tampered_invoice_is_rejected() {
+    let invoice = tampered();
+    let (rejected, err) = invoice.clone().into_signed().unwrap_err();
+    assert_eq!(rejected, invoice);
+    assert_eq!(err, SignatureError::InvalidSignature);
+    assert_eq!(
+        SignedInvoice::try_from(invoice.clone()),
+        Err(SignatureError::InvalidSignature)
+    );
+
+    assert_eq!(
+        SignedInvoice::from_str(&invoice.to_string()),
+        Err(SignedInvoiceError::Signature(
+            SignatureError::InvalidSignature
+        ))
+    );
+    assert!(matches!(
+        SignedInvoice::from_str(&common::invoice().to_string()),
+        Err(SignedInvoiceError::Signature(SignatureError::NoSignature))
+    ));
+    assert!(matches!(
+        SignedInvoice::from_str("i1invalid"),
+        Err(SignedInvoiceError::Parse(_))
+    ));
+
+    let data = invoice.strict_serialize().unwrap();
+    assert!(matches!(
+        SignedInvoice::strict_deserialize(&data),
+        Err(strict_encoding::Error::DataIntegrityError(_))
+    ));
+
+    #[cfg(feature = "serde")]
+    {
+        let json = serde_json::to_string(&invoice).unwrap();
+        assert!(serde_json::from_str::<SignedInvoice>(&json).is_err());
+    }
+}
+
+#[test]
+fn encoding_round_trip() {
+    let signed = signed().into_signed().unwrap();
+
+    let s = signed.to_string();
+    assert_eq!(s, signed.as_invoice().to_string());
+    assert_eq!(SignedInvoice::from_str(&s), Ok(signed.clone()));
+
+    let data = signed.strict_serialize().unwrap();
+    assert_eq!(data, signed.as_invoice().strict_serialize().unwrap());
+    assert_eq!(SignedInvoice::strict_deserialize(&data).unwrap(), signed);
+
+    #[cfg(feature = "serde")]
+    {
+        let json = serde_json::to_string(&signed).unwrap();
+        assert_eq!(json, serde_json::to_string(signed.as_invoice()).unwrap());
+        let decoded: SignedInvoice = serde_json::from_str(&json).unwrap();
+        assert_eq!(decoded, signed);
+    }
+}
@@ -0,0 +1,200 @@
+// LNP/BP universal invoice library implementing LNPBP-38 standard
+// Written in 2021 by
+//     Dr. Maxim Orlovsky <orlovsky@pandoracore.com>
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the MIT License
+// along with this software.
+// If not, see <https://opensource.org/licenses/MIT>.
+
+mod common;
+
+use std::str::FromStr;
+
+use amplify::Wrapper;
+use chrono::{TimeZone, Utc};
+use common::{invoice, split_records, with_records, RawInvoice};
+use invoice::{
+    DecodePolicy, ExpiryTerm, ExtensionError, Invoice, MerchantInfo,
+    MAX_CORE_TLV_TYPE,
+};
+use lnpbp::bech32::ToBech32String;
+use strict_encoding::{StrictDecode, StrictEncode};
+
+fn lenient() -> DecodePolicy {
+    DecodePolicy {
+        accept_unknown_required: true,
+        ..DecodePolicy::default()
+    }
+}
+
+#[test]
+fn unknown_odd_record_is_preserved() {
+    let data = with_records(&invoice(), &[(0x23, b"odd"), (0x41, b"ext")]);
+    let decoded = Invoice::strict_deserialize(&data).unwrap();
+    assert_eq!(decoded.extension(0x23u64.into()), Some(&b"odd"[..]));
+    assert_eq!(decoded.extension(0x41u64.into()), Some(&b"ext"[..]));
+    assert!(!decoded.has_unknown_required_fields());
+    assert_eq!(decoded.strict_serialize().unwrap(), data);
+
+    let s = RawInvoice(data).to_bech32_string();
+    let parsed = Invoice::from_str(&s).unwrap();
+    assert_eq!(parsed, decoded);
+    assert_eq!(parsed.to_bech32_string(), s);
+}
+
+#[test]
+fn unknown_even_record_is_rejected() {
+    for type_no in [0x10, 0x14, 0x16, 0x18, 0x1e, 0x3e, 0x40, 0x1000] {
+        let data = with_records(&invoice(), &[(type_no, b"even")]);
+        assert!(Invoice::strict_deserialize(&data).is_err(), "{}", type_no);
+        let s = RawInvoice(data).to_bech32_string();
+        assert!(Invoice::from_str(&s).is_err(), "{}", type_no);
+    }
+}
+
+#[test]
+fn lenient_decoding_flags_unknown_even_records() {
+    for type_no in [0x10, 0x14, 0x16, 0x18, 0x1e, 0x3e, 0x40, 0x1000] {
+        let data = with_records(&invoice(), &[(type_no, b"even")]);
+        let s = RawInvoice(data).to_bech32_string();
+        let decoded = Invoice::parse_with_policy(&s, &lenient()).unwrap();
+        assert!(decoded.has_unknown_required_fields(), "{}", type_no);
+        assert_eq!(decoded.extension(type_no.into()), Some(&b"even"[..]));
+        assert_eq!(decoded.amount(), invoice().amount());
+    }
+}
+
+#[test]
+fn lenient_decoding_keeps_known_fields() {
+    let mut invoice = invoice();
+    invoice.set_purpose("coffee".to_owned());
+    invoice.set_issued_at(Utc.timestamp_opt(1_700_000_000, 0).unwrap());
+    invoice.set_expiry_term(ExpiryTerm::RelativeSeconds(3600));
+    invoice.set_recurrence_limit(12);
+    let data = with_records(&invoice, &[(0x14, b"even"), (0x23, b"odd")]);
+    let s = RawInvoice(data).to_bech32_string();
+    let decoded = Invoice::parse_with_policy(&s, &lenient()).unwrap();
+    assert_eq!(decoded.purpose(), invoice.purpose());
+    assert_eq!(decoded.expiry_term(), invoice.expiry_term());
+    assert_eq!(decoded.issued_at(), invoice.issued_at());
+    assert_eq!(decoded.recurrence_limit(), invoice.recurrence_limit());
+    assert!(decoded.has_unknown_required_fields());
+    assert_eq!(decoded.extension(0x23u64.into()), Some(&b"odd"[..]));
+}
+
+#[test]
+fn extensions_use_types_above_core_range() {
+    let mut invoice = invoice();
+    assert_eq!(MAX_CORE_TLV_TYPE, 0x3f);
+    assert_eq!(
+        invoice.set_extension(0x3fu64.into(), vec![1]),
+        Err(ExtensionError::CoreType(0x3f))
+    );
+    assert_eq!(
+        invoice.set_extension(0x42u64.into(), vec![1]),
+        Err(ExtensionError::EvenType(0x42))
+    );
+    invoice.set_extension(0x41u64.into(), vec![1]).unwrap();
+    assert_eq!(invoice.extension(0x41u64.into()), Some(&[1u8][..]));
+}
+
+#[test]
+#[cfg(feature = "serde")]
+fn serde_extensions_use_types_above_core_range() {
+    let mut invoice = invoice();
+    invoice.set_extension(0x41u64.into(), vec![1]).unwrap();
+    let yaml = serde_yaml::to_value(&invoice).unwrap();
+    assert_eq!(
+        serde_yaml::from_value::<Invoice>(yaml.clone()).unwrap(),
+        invoice
+    );
+
+    // Merchant name record and even record above the core range
+    for type_no in [0x05u64, 0x42] {
+        let mut yaml = yaml.clone();
+        let mut extensions = serde_yaml::Mapping::new();
+        extensions.insert(type_no.into(), "636f66666565".into());
+        yaml["extensions"] = extensions.into();
+        let err = serde_yaml::from_value::<Invoice>(yaml).unwrap_err();
+        let expected = if type_no == 0x05 {
+            ExtensionError::CoreType(type_no)
+        } else {
+            ExtensionError::EvenType(type_no)
+        };
+        assert!(err.to_string().contains(&expected.to_string()), "{}", err);
+    }
+}
+
+#[test]
+fn field_types_follow_even_odd_rule() {
+    let mut invoice = invoice();
+    invoice.set_issued_at(Utc.timestamp_opt(1_700_000_000, 0).unwrap());
+    invoice.set_expiry_term(ExpiryTerm::RelativeSeconds(3600));
+    invoice.set_recurrence_limit(12);
+    invoice.set_merchant_info(MerchantInfo::new("Coffee shop"));
+    let (_, stream) = split_records(&invoice.strict_serialize().unwrap());
+    let types = (&stream)
+        .into_iter()
+        .map(|(type_no, _)| type_no.into_inner())
+        .collect::<Vec<_>>();
+    // network, relative expiry, issuance time, recurrence limit and merchant
+    assert_eq!(types, vec![0x0b, 0x0c, 0x0d, 0x1f, 0x21]);
+}
+
+/// Encodes TLV stream with the records in the given order.
+fn raw_stream(records: &[(u64, &[u8])]) -> Vec<u8> {
+    let mut data = (records.len() as u16).to_le_bytes().to_vec();
+    for (type_no, value) in records {
+        data.extend(type_no.to_le_bytes());
+        data.extend((value.len() as u16).to_le_bytes());
+        data.extend(*value);
+    }
+    data
+}
+
+#[test]
+fn records_are_ordered_by_type() {
+    let records: [(u64, &[u8]); 2] = [(0x23, b"odd"), (0x41, b"ext")];
+    let data = with_records(&invoice(), &records);
+    let (payload, stream) = split_records(&data);
+    let mut records = stream
+        .into_iter()
+        .map(|(ty, value)| {
+            (ty.into_inner(), AsRef::<[u8]>::as_ref(&value).to_vec())
+        })
+        .collect::<Vec<_>>();
+    let types = records.iter().map(|(ty, _)| *ty).collect::<Vec<_>>();
+    let mut sorted = types.clone();
+    sorted.sort_unstable();
+    assert_eq!(types, sorted);
+    let with_payload = |records: &[(u64, Vec<u8>)]| {
+        let records = records
+            .iter()
+            .map(|(ty, value)| (*ty, value.as_slice()))
+            .collect::<Vec<_>>();
+        let mut data = payload.clone();
+        data.extend(raw_stream(&records));
+        data
+    };
+    assert_eq!(with_payload(&records), data);
+
+    // Records are encoded in the order of their types regardless of the order
+    // they were added in
+    let reversed = with_records(&invoice(), &[(0x41, b"ext"), (0x23, b"odd")]);
+    assert_eq!(reversed, data);
+    let decoded = Invoice::strict_deserialize(&data).unwrap();
+    assert_eq!(decoded.canonical_bytes(), data);
+
+    // Out of order and duplicated records are rejected
+    let last = records.len() - 1;
+    records.swap(0, last);
+    assert!(Invoice::strict_deserialize(with_payload(&records)).is_err());
+    records.swap(0, last);
+    records.push(records[last].clone());
+    assert!(Invoice::strict_deserialize(with_payload(&records)).is_err());
+}
@@ -0,0 +1,199 @@
+// LNP/BP universal invoice library implementing LNPBP-38 standard
+// Written in 2021 by
+//     Dr. Maxim Orlovsky <orlovsky@pandoracore.com>
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the MIT License
+// along with this software.
+// If not, see <https://opensource.org/licenses/MIT>.
+
+use std::str::FromStr;
+
+use invoice::{Beneficiary, Invoice, TrackingBeneficiary, TrackingError};
+use lnpbp::chain::Chain;
+use miniscript::descriptor::DescriptorPublicKey;
+use miniscript::Descriptor;
+use strict_encoding::{StrictDecode, StrictEncode};
+
+// BIP-84 test vector account key and its first receive addresses
+const XPUB: &str = "xpub6CatWdiZiodmUeTDp8LT5or8nmbKNcuyvz7WyksVFkKB4RHwCD3XyuvPEbvqAQY3rAPshWcMLoP2fMFMKHPJ4ZeZXYVUhLv1VMrjPC7PW6V";
+const RECEIVE_0: &str = "bc1qcr8te4kr609gcawutmrza0j4xv80jy8z306fyu";
+const RECEIVE_1: &str = "bc1qnjg0jd8228aq7egyzacy8cys3knf9xvrerkf9g";
+
+fn descriptor() -> Descriptor<DescriptorPublicKey> {
+    Descriptor::from_str(&format!("wpkh({}/0/*)", XPUB)).unwrap()
+}
+
+fn tracking(s: &str) -> TrackingBeneficiary {
+    let s = s.replace("DESC", &descriptor().to_string());
+    TrackingBeneficiary::from_str(&s).unwrap()
+}
+
+/// Invoice paying to the legacy descriptor beneficiary
+fn legacy_invoice() -> Invoice {
+    Invoice::with_descriptor(descriptor(), Some(1000), &Chain::Mainnet)
+}
+
+#[test]
+fn legacy_descriptor_migration() {
+    // Invoices with the descriptor beneficiary keep decoding as is
+    let legacy = legacy_invoice();
+    let data = legacy.strict_serialize().unwrap();
+    assert_eq!(Invoice::strict_deserialize(&data).unwrap(), legacy);
+    let decoded = Invoice::from_str(&legacy.to_string()).unwrap();
+    assert_eq!(decoded, legacy);
+
+    let descriptor = match decoded.beneficiary() {
+        Beneficiary::Descriptor(descriptor) => descriptor.clone(),
+        beneficiary => panic!("unexpected beneficiary {}", beneficiary),
+    };
+    let tracking = TrackingBeneficiary::from(descriptor.clone());
+    assert_eq!(tracking.descriptor, descriptor);
+    assert_eq!(tracking.index_range, None);
+    assert_eq!(tracking.next_index_hint, None);
+
+    let mut migrated = decoded.clone();
+    assert!(migrated.set_beneficiary(Beneficiary::TrackingDescriptor(tracking)));
+    assert_ne!(migrated, legacy);
+    let decoded = Invoice::from_str(&migrated.to_string()).unwrap();
+    assert_eq!(decoded, migrated);
+
+    // Migration keeps the payment addresses
+    for index in 0..2 {
+        assert_eq!(
+            migrated.derive_address(index, bitcoin::Network::Bitcoin),
+            legacy.derive_address(index, bitcoin::Network::Bitcoin)
+        );
+    }
+    assert_eq!(
+        migrated.script_pubkeys(Some(0..2)),
+        legacy.script_pubkeys(Some(0..2))
+    );
+    for (index, address) in [(0, RECEIVE_0), (1, RECEIVE_1)] {
+        assert_eq!(
+            migrated
+                .derive_address(index, bitcoin::Network::Bitcoin)
+                .unwrap()
+                .to_string(),
+            address
+        );
+    }
+}
+
+#[test]
+fn wire_tags() {
+    let legacy = Beneficiary::Descriptor(descriptor());
+    let data = legacy.strict_serialize().unwrap();
+    assert_eq!(data[0], 2);
+    assert_eq!(&data[1..], descriptor().strict_serialize().unwrap());
+
+    let tracking = tracking("track:0..100@5:DESC");
+    let beneficiary = Beneficiary::TrackingDescriptor(tracking.clone());
+    let data = beneficiary.strict_serialize().unwrap();
+    assert_eq!(data[0], 10);
+    assert_eq!(&data[1..], tracking.strict_serialize().unwrap());
+    assert_eq!(Beneficiary::strict_deserialize(&data).unwrap(), beneficiary);
+}
+
+#[test]
+fn strict_decoding_checks_metadata() {
+    let invalid = TrackingBeneficiary {
+        descriptor: descriptor(),
+        index_range: Some(10..20),
+        next_index_hint: Some(30),
+    };
+    let data = invalid.strict_serialize().unwrap();
+    assert!(matches!(
+        TrackingBeneficiary::strict_deserialize(&data),
+        Err(strict_encoding::Error::DataIntegrityError(_))
+    ));
+}
+
+#[test]
+fn display_round_trip() {
+    for s in [
+        "track::DESC",
+        "track:0..100:DESC",
+        "track:@5:DESC",
+        "track:10..20@15:DESC",
+    ] {
+        let tracking = tracking(s);
+        let s = s.replace("DESC", &descriptor().to_string());
+        assert_eq!(tracking.to_string(), s);
+        assert_eq!(
+            Beneficiary::from_str(&s).unwrap(),
+            Beneficiary::TrackingDescriptor(tracking)
+        );
+    }
+}
+
+#[test]
+fn parse_errors() {
+    let desc = descriptor().to_string();
+    let parse = |s: &str| {
+        TrackingBeneficiary::from_str(&s.replace("DESC", &desc)).unwrap_err()
+    };
+    assert_eq!(parse("DESC"), TrackingError::Format);
+    assert_eq!(parse("track:0..x:DESC"), TrackingError::Format);
+    assert_eq!(parse("track:10:DESC"), TrackingError::Format);
+    assert_eq!(
+        parse("track:10..10:DESC"),
+        TrackingError::InvalidRange(10..10)
+    );
+    assert_eq!(
+        parse("track:0..2147483649:DESC"),
+        TrackingError::InvalidRange(0..0x8000_0001)
+    );
+    assert_eq!(
+        parse("track:0..10@10:DESC"),
+        TrackingError::HintOutOfRange(10)
+    );
+    assert!(matches!(
+        parse("track::wpkh(xpub)"),
+        TrackingError::Descriptor(_)
+    ));
+}
+
+#[test]
+fn payment_indexes() {
+    let tracking = tracking("track:10..13@11:DESC");
+    assert_eq!(tracking.derivation_range(), 10..13);
+    assert!(!tracking.contains(9));
+    assert!(tracking.contains(12));
+    assert_eq!(tracking.payment_index(0), Some(11));
+    assert_eq!(tracking.payment_index(1), Some(12));
+    assert_eq!(tracking.payment_index(2), None);
+
+    let tracking = TrackingBeneficiary::from(descriptor());
+    assert_eq!(tracking.derivation_range(), 0..0x8000_0000);
+    assert_eq!(tracking.payment_index(0), Some(0));
+    assert_eq!(tracking.payment_index(0x7fff_ffff), Some(0x7fff_ffff));
+    assert_eq!(tracking.payment_index(0x8000_0000), None);
+    assert_eq!(tracking.payment_index(u32::MAX), None);
+}
+
+#[test]
+#[cfg(feature = "serde")]
+fn serde_round_trip() {
+    let tracking = tracking("track:0..100@5:DESC");
+    let json = serde_json::to_value(&tracking).unwrap();
+    assert_eq!(
+        json,
+        serde_json::json!({
+            "descriptor": descriptor().to_string(),
+            "indexRange": { "start": 0, "end": 100 },
+            "nextIndexHint": 5
+        })
+    );
+    let decoded: TrackingBeneficiary = serde_json::from_value(json).unwrap();
+    assert_eq!(decoded, tracking);
+
+    let mut invoice = legacy_invoice();
+    invoice.set_beneficiary(Beneficiary::TrackingDescriptor(tracking));
+    let json = serde_json::to_string(&invoice).unwrap();
+    assert_eq!(serde_json::from_str::<Invoice>(&json).unwrap(), invoice);
+}
@@ -0,0 +1,126 @@
+// LNP/BP universal invoice library implementing LNPBP-38 standard
+// Written in 2021 by
+//     Dr. Maxim Orlovsky <orlovsky@pandoracore.com>
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the MIT License
+// along with this software.
+// If not, see <https://opensource.org/licenses/MIT>.
+
+#![cfg(feature = "ur")]
+
+mod common;
+
+use common::invoice;
+use invoice::ur::{UrDecoder, UrEncoder, INVOICE_UR_TYPE};
+use invoice::{Invoice, UrError};
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::SeedableRng;
+
+const FRAGMENT_LEN: usize = 20;
+
+/// Invoice which doesn't fit into a single fragment
+fn large() -> Invoice {
+    let mut invoice = invoice();
+    invoice.set_purpose("a purpose long enough to span many fragments".into());
+    invoice.set_merchant("Merchant".into());
+    invoice
+}
+
+#[test]
+fn single_part() {
+    let parts = invoice().to_ur_parts(1000);
+    assert_eq!(parts.len(), 1);
+    assert!(parts[0].starts_with("ur:lnpbp-invoice/"));
+    assert_eq!(parts[0].matches('/').count(), 1);
+    let decoded = Invoice::from_ur_parts(parts.iter().map(String::as_str));
+    assert_eq!(decoded.unwrap(), invoice());
+}
+
+#[test]
+fn multipart_in_order() {
+    let parts = large().to_ur_parts(FRAGMENT_LEN);
+    assert!(parts.len() > 4);
+    let count = parts.len() / 2;
+    for (no, part) in parts.iter().enumerate() {
+        let prefix = format!("ur:lnpbp-invoice/{}-{}/", no + 1, count);
+        assert!(part.starts_with(&prefix), "{}", part);
+    }
+    let decoded = Invoice::from_ur_parts(parts.iter().map(String::as_str));
+    assert_eq!(decoded.unwrap(), large());
+}
+
+#[test]
+fn multipart_shuffled() {
+    let mut parts = large().to_ur_parts(FRAGMENT_LEN);
+    for seed in 0..16 {
+        parts.shuffle(&mut StdRng::seed_from_u64(seed));
+        let decoded = Invoice::from_ur_parts(parts.iter().map(String::as_str));
+        assert_eq!(decoded.unwrap(), large(), "seed {}", seed);
+    }
+}
+
+#[test]
+fn multipart_with_dropped_fragments() {
+    let parts = large().to_ur_parts(FRAGMENT_LEN);
+    let count = parts.len() / 2;
+    // Dropping any single fragment is recovered by the fountain-coded parts
+    for dropped in 0..count {
+        let decoded = Invoice::from_ur_parts(
+            parts
+                .iter()
+                .enumerate()
+                .filter(|(no, _)| *no != dropped)
+                .map(|(_, part)| part.as_str()),
+        );
+        assert_eq!(decoded.unwrap(), large(), "fragment {}", dropped + 1);
+    }
+}
+
+#[test]
+fn fountain_parts_only() {
+    let message = large().to_cbor();
+    let encoder = UrEncoder::new(INVOICE_UR_TYPE, &message, FRAGMENT_LEN);
+    let count = encoder.fragment_count();
+    let mut decoder = UrDecoder::new(INVOICE_UR_TYPE);
+    let mut received = 0;
+    for part in encoder.skip(count).take(count * 10) {
+        received += 1;
+        if decoder.receive(&part).unwrap() {
+            break;
+        }
+    }
+    assert!(received >= count);
+    assert_eq!(decoder.message(), Some(message));
+}
+
+#[test]
+fn insufficient_parts() {
+    let parts = large().to_ur_parts(FRAGMENT_LEN);
+    let count = parts.len() / 2;
+    let decoded =
+        Invoice::from_ur_parts(parts[..count - 1].iter().map(String::as_str));
+    assert!(matches!(decoded, Err(UrError::Incomplete)));
+    let decoded = Invoice::from_ur_parts(
+        parts[..count - 1]
+            .iter()
+            .chain(&parts[..count - 1])
+            .map(String::as_str),
+    );
+    assert!(matches!(decoded, Err(UrError::Incomplete)));
+}
+
+#[test]
+fn parts_of_other_message() {
+    let parts = large().to_ur_parts(FRAGMENT_LEN);
+    let mut other = large();
+    other.set_purpose("other purpose of the same length, other data!".into());
+    let other = other.to_ur_parts(FRAGMENT_LEN);
+    let decoded = Invoice::from_ur_parts([parts[0].as_str(), &other[1]]);
+    assert!(matches!(decoded, Err(UrError::InconsistentParts)));
+}
@@ -0,0 +1,304 @@
+// LNP/BP universal invoice library implementing LNPBP-38 standard
+// Written in 2021 by
+//     Dr. Maxim Orlovsky <orlovsky@pandoracore.com>
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the MIT License
+// along with this software.
+// If not, see <https://opensource.org/licenses/MIT>.
+
+//! Golden test vectors kept in `tests/vectors/` as `<name>.hex` (strict
+//! encoding), `<name>.bech32` and `<name>.cbor` (hex of canonical CBOR)
+//! files. After an intentional change of the encoding, the files are
+//! regenerated with `cargo test --all-features --test vectors -- --ignored
+//! regenerate_vectors`.
+
+mod common;
+
+use std::fs;
+use std::path::PathBuf;
+use std::str::FromStr;
+
+use bitcoin::hashes::hex::{FromHex, ToHex};
+use bitcoin::hashes::Hash;
+use bitcoin::secp256k1::{Message, SECP256K1};
+use bp::seals::txout::blind::ConcealedSeal;
+use common::{invoice, keypair, with_records, RawInvoice};
+use invoice::{
+    Beneficiary, BuilderWarning, ConsignmentEndpoint, Invoice, InvoiceBuilder,
+    Network, QrCapacity, QrErrorCorrection, QR_MAX_VERSION,
+};
+use lnpbp::bech32::ToBech32String;
+use lnpbp::chain::AssetId;
+use strict_encoding::{StrictDecode, StrictEncode};
+
+const DESCRIPTOR: &str = "wpkh(\
+    02f9308a019258c31049344f85f89d5229b531c845836f99b08601f113bce036f9)";
+
+// BIP-174 test vector for a PSBT with one P2PKH input and two outputs
+const PSBT: &str = "cHNidP8BAFUCAAAAASeaIyOl37UfxF8iD6WLD8E+HjNCeSqF1+Ns1jM7XLw5AAAAAAD/////AaBa6gsAAAAAGXapFP/pwAYQl8w7Y28ssEYPpPxCfStFiKwAAAAAAAEBIJVe6gsAAAAAF6kUY0UgD2jRieGtwN8cTRbqjxTA2+uHIgIDsTQcy6doO2r08SOM1ul+cWfVafrEfx5I1HVBhENVvUZGMEMCIAQktY7/qqaU4VWepck7v9SokGQiQFXN8HC2dxRpRC0HAh9cjrD+plFtYLisszrWTt5g6Hhb+zqpS5m9+GFR25qaAQEEIgAgdx/RitRZZm3Unz1WTj28QvTIR3TjYK2haBao7UiNVoEBBUdSIQOxNBzLp2g7avTxI4zW6X5xZ9Vp+sR/HkjUdUGEQ1W9RiED3lXR4drIBeP4pYwfv5uUwC89uq/hJ/78pJlfJvggg71SriIGA7E0HMunaDtq9PEjjNbpfnFn1Wn6xH8eSNR1QYRDVb1GELSmumcAAACAAAAAgAQAAIAiBgPeVdHh2sgF4/iljB+/m5TALz26r+En/vykmV8m+CCDvRC0prpnAAAAgAAAAIAFAACAAAA=";
+
+const BOLT: &str = "bolt:\
+    02f9308a019258c31049344f85f89d5229b531c845836f99b08601f113bce036f9@\
+    0101010101010101010101010101010101010101010101010101010101010101";
+
+fn beneficiary(s: &str) -> Beneficiary {
+    Beneficiary::from_str(s).unwrap()
+}
+
+fn endpoint(no: usize) -> ConsignmentEndpoint {
+    ConsignmentEndpoint::from_str(&format!(
+        "rgbhttpjsonrpc:https://proxy{}.example.com/json-rpc",
+        no
+    ))
+    .unwrap()
+}
+
+/// Invoice signed with a deterministic (no auxiliary randomness) signature.
+fn signed() -> Invoice {
+    let mut invoice = invoice();
+    invoice.set_purpose("coffee".to_owned());
+    let keypair = keypair(1);
+    let msg = Message::from_slice(&invoice.signature_hash()).unwrap();
+    let signature = SECP256K1.sign_schnorr_no_aux_rand(&msg, &keypair);
+    invoice.set_signature(keypair.public_key(), signature);
+    invoice
+}
+
+/// Strict-encoded test vectors by their names.
+fn vectors() -> Vec<(&'static str, Vec<u8>)> {
+    let blind_utxo = InvoiceBuilder::new(Beneficiary::BlindUtxo(
+        ConcealedSeal::strict_deserialize([7u8; 32]).unwrap(),
+    ))
+    .amount(100.into())
+    .asset(AssetId::from_inner([1; 32]))
+    .consignment_endpoint(endpoint(1))
+    .consignment_endpoint(endpoint(2))
+    .network(Network::Testnet3)
+    .finish()
+    .unwrap();
+
+    vec![
+        ("address", invoice()),
+        (
+            "descriptor",
+            Invoice::new(beneficiary(DESCRIPTOR), Some(5000), None),
+        ),
+        ("psbt", Invoice::new(beneficiary(PSBT), None, None)),
+        ("bolt", Invoice::new(beneficiary(BOLT), Some(21), None)),
+        ("blind_utxo", blind_utxo),
+        ("signed", signed()),
+    ]
+    .into_iter()
+    .map(|(name, invoice)| (name, invoice.strict_serialize().unwrap()))
+    .chain([(
+        "unknown_tlv",
+        with_records(&invoice(), &[(0x23, b"odd"), (0x41, b"ext")]),
+    )])
+    .collect()
+}
+
+fn fixture(name: &str, ext: &str) -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/vectors")
+        .join(format!("{}.{}", name, ext))
+}
+
+fn read_fixture(name: &str, ext: &str) -> String {
+    fs::read_to_string(fixture(name, ext))
+        .unwrap_or_else(|err| panic!("fixture {}.{}: {}", name, ext, err))
+        .trim()
+        .to_owned()
+}
+
+#[test]
+fn vectors_match_fixtures() {
+    for (name, data) in vectors() {
+        assert_eq!(read_fixture(name, "hex"), data.to_hex(), "{}", name);
+        assert_eq!(
+            read_fixture(name, "bech32"),
+            RawInvoice(data).to_bech32_string(),
+            "{}",
+            name
+        );
+    }
+}
+
+#[test]
+fn fixtures_decode_and_round_trip() {
+    for (name, _) in vectors() {
+        let data = Vec::<u8>::from_hex(&read_fixture(name, "hex")).unwrap();
+        let decoded = Invoice::strict_deserialize(&data).unwrap();
+        assert_eq!(decoded.strict_serialize().unwrap(), data, "{}", name);
+
+        let s = read_fixture(name, "bech32");
+        let parsed = Invoice::from_str(&s).unwrap();
+        assert_eq!(parsed, decoded, "{}", name);
+        assert_eq!(parsed.to_bech32_string(), s, "{}", name);
+        assert!(parsed.validate().is_ok(), "{}", name);
+    }
+}
+
+#[test]
+fn fixtures_keep_field_values() {
+    let parse =
+        |name| Invoice::from_str(&read_fixture(name, "bech32")).unwrap();
+
+    let signed = parse("signed");
+    signed.verify_signature().unwrap();
+    assert_eq!(signed.signature_hash(), self::signed().signature_hash());
+
+    let unknown = parse("unknown_tlv");
+    assert_eq!(unknown.extension(0x23u64.into()), Some(&b"odd"[..]));
+    assert_eq!(unknown.extension(0x41u64.into()), Some(&b"ext"[..]));
+
+    let blind_utxo = parse("blind_utxo");
+    assert!(matches!(
+        blind_utxo.beneficiary(),
+        Beneficiary::BlindUtxo(_)
+    ));
+    assert_eq!(blind_utxo.consignment_endpoints().len(), 2);
+    assert_eq!(blind_utxo.network(), &Some(Network::Testnet3));
+
+    assert!(matches!(parse("bolt").beneficiary(), Beneficiary::Bolt(_)));
+    assert!(matches!(parse("psbt").beneficiary(), Beneficiary::Psbt(_)));
+    assert!(matches!(
+        parse("descriptor").beneficiary(),
+        Beneficiary::Descriptor(_)
+    ));
+}
+
+#[test]
+fn size_estimation_matches_fixtures() {
+    for (name, _) in vectors() {
+        let data = Vec::<u8>::from_hex(&read_fixture(name, "hex")).unwrap();
+        let s = read_fixture(name, "bech32");
+        let invoice = Invoice::strict_deserialize(&data).unwrap();
+        assert_eq!(invoice.encoded_len(), data.len(), "{}", name);
+        assert_eq!(invoice.bech32_len(), s.len(), "{}", name);
+        assert_eq!(invoice.to_bech32_string_upper().len(), s.len(), "{}", name);
+
+        let mut prev = None;
+        for error_correction in QrErrorCorrection::ALL {
+            let capacity = invoice.qr_capacity(error_correction);
+            assert_eq!(
+                capacity,
+                QrCapacity::with(s.len(), error_correction),
+                "{}",
+                name
+            );
+            let capacity = capacity.unwrap();
+            assert_eq!(capacity.error_correction, error_correction);
+            // Stronger error correction never needs a smaller code
+            if let Some(prev) = prev {
+                assert!(capacity.version >= prev, "{}", name);
+            }
+            prev = Some(capacity.version);
+        }
+    }
+}
+
+#[test]
+fn qr_capacity_boundaries() {
+    let low = QrErrorCorrection::Low;
+    let high = QrErrorCorrection::High;
+    let version = |len, error_correction| {
+        QrCapacity::with(len, error_correction).map(|capacity| capacity.version)
+    };
+    // Alphanumeric capacities from ISO/IEC 18004, table 7
+    assert_eq!(version(25, low), Some(1));
+    assert_eq!(version(26, low), Some(2));
+    assert_eq!(version(10, high), Some(1));
+    assert_eq!(version(11, high), Some(2));
+    assert_eq!(version(4296, low), Some(QR_MAX_VERSION));
+    assert_eq!(version(4297, low), None);
+    assert_eq!(version(1852, high), Some(QR_MAX_VERSION));
+    assert_eq!(version(1853, high), None);
+}
+
+#[test]
+fn builder_warns_on_size_limit() {
+    let beneficiary = invoice().beneficiary().clone();
+    let builder = InvoiceBuilder::new(beneficiary).amount(1000.into());
+    let len = builder.clone().finish().unwrap().bech32_len();
+
+    let (_, warnings) = builder
+        .clone()
+        .size_limit(len)
+        .finish_with_warnings()
+        .unwrap();
+    assert!(warnings.is_empty());
+    let (invoice, warnings) =
+        builder.size_limit(len - 1).finish_with_warnings().unwrap();
+    assert_eq!(invoice.bech32_len(), len);
+    assert_eq!(
+        warnings,
+        vec![BuilderWarning::SizeLimit {
+            len,
+            limit: len - 1
+        }]
+    );
+}
+
+#[test]
+#[cfg(feature = "cbor")]
+fn cbor_vectors_match_fixtures() {
+    for (name, data) in vectors() {
+        let invoice = Invoice::strict_deserialize(&data).unwrap();
+        let cbor = invoice.to_cbor();
+        assert_eq!(read_fixture(name, "cbor"), cbor.to_hex(), "{}", name);
+        // Canonical encoding doesn't depend on the way invoice was obtained
+        let reparsed = Invoice::from_cbor(&cbor).unwrap();
+        assert_eq!(reparsed.to_cbor(), cbor, "{}", name);
+    }
+}
+
+#[test]
+#[cfg(feature = "cbor")]
+fn cbor_fixtures_decode() {
+    for (name, _) in vectors() {
+        let cbor = Vec::<u8>::from_hex(&read_fixture(name, "cbor")).unwrap();
+        let decoded = Invoice::from_cbor(&cbor).unwrap();
+        let data = Vec::<u8>::from_hex(&read_fixture(name, "hex")).unwrap();
+        assert_eq!(decoded.strict_serialize().unwrap(), data, "{}", name);
+    }
+
+    let cbor =
+        Vec::<u8>::from_hex(&read_fixture("unknown_tlv", "cbor")).unwrap();
+    let unknown = Invoice::from_cbor(&cbor).unwrap();
+    assert_eq!(unknown.extension(0x23u64.into()), Some(&b"odd"[..]));
+    assert_eq!(unknown.extension(0x41u64.into()), Some(&b"ext"[..]));
+
+    let cbor = Vec::<u8>::from_hex(&read_fixture("signed", "cbor")).unwrap();
+    Invoice::from_cbor(&cbor)
+        .unwrap()
+        .verify_signature()
+        .unwrap();
+}
+
+#[test]
+#[ignore]
+fn regenerate_vectors() {
+    for (name, data) in vectors() {
+        fs::write(fixture(name, "hex"), data.to_hex() + "\n").unwrap();
+        #[cfg(feature = "cbor")]
+        fs::write(
+            fixture(name, "cbor"),
+            Invoice::strict_deserialize(&data)
+                .unwrap()
+                .to_cbor()
+                .to_hex()
+                + "\n",
+        )
+        .unwrap();
+        fs::write(
+            fixture(name, "bech32"),
+            RawInvoice(data).to_bech32_string() + "\n",
+        )
+        .unwrap();
+    }
+}
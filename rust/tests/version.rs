@@ -0,0 +1,85 @@
+// LNP/BP universal invoice library implementing LNPBP-38 standard
+// Written in 2021 by
+//     Dr. Maxim Orlovsky <orlovsky@pandoracore.com>
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the MIT License
+// along with this software.
+// If not, see <https://opensource.org/licenses/MIT>.
+
+mod common;
+
+use std::str::FromStr;
+
+use common::{invoice, RawInvoice};
+use invoice::{Invoice, InvoiceParseError, VersionedInvoice};
+use lnpbp::bech32::ToBech32String;
+use strict_encoding::StrictEncode;
+
+/// Invoice data with the version byte replaced
+fn versioned(version: u8) -> Vec<u8> {
+    let mut data = invoice().strict_serialize().unwrap();
+    data[0] = version;
+    data
+}
+
+#[test]
+fn current_version() {
+    assert_eq!(Invoice::VERSION, 0);
+    assert_eq!(*invoice().version(), Invoice::VERSION);
+
+    let decoded = Invoice::decode_any_version(versioned(0)).unwrap();
+    assert_eq!(decoded.version(), 0);
+    assert_eq!(decoded, VersionedInvoice::Supported(invoice()));
+}
+
+#[test]
+fn future_version() {
+    let data = versioned(2);
+
+    let decoded = Invoice::decode_any_version(&data).unwrap();
+    assert_eq!(decoded.version(), 2);
+    assert_eq!(
+        decoded,
+        VersionedInvoice::Unsupported {
+            version: 2,
+            payload: data[1..].to_vec()
+        }
+    );
+
+    let s = RawInvoice(data).to_bech32_string();
+    assert_eq!(
+        Invoice::from_str(&s),
+        Err(InvoiceParseError::UnsupportedVersion(2))
+    );
+}
+
+#[test]
+fn future_version_with_any_payload() {
+    // Payload of unsupported versions is not interpreted
+    let data = [0xff, 1, 2, 3];
+    assert_eq!(
+        Invoice::decode_any_version(data).unwrap(),
+        VersionedInvoice::Unsupported {
+            version: 0xff,
+            payload: vec![1, 2, 3]
+        }
+    );
+    assert_eq!(
+        Invoice::from_str(&RawInvoice(data.to_vec()).to_bech32_string()),
+        Err(InvoiceParseError::UnsupportedVersion(0xff))
+    );
+}
+
+#[test]
+fn malformed_data() {
+    assert!(Invoice::decode_any_version([]).is_err());
+
+    let data = invoice().strict_serialize().unwrap();
+    // Truncated inside the beneficiary address
+    assert!(Invoice::decode_any_version(&data[..20]).is_err());
+}